@@ -0,0 +1,83 @@
+// benches/pipeline.rs - Lexer, parser and type checker throughput on large generated programs
+//
+// Run with `cargo bench`; criterion keeps the last run in target/criterion
+// and reports changes against it.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use minilang_compiler::{Lexer, Parser, TypeChecker};
+use std::fmt::Write;
+
+/// Program sizes, in generated functions
+const SIZES: [usize; 3] = [10, 100, 1000];
+
+/// A valid program of `functions` functions, each with locals, a loop, a
+/// branch, strings, floats and a call to the previous one, plus `main`
+fn generate_program(functions: usize) -> String {
+    let mut source = String::new();
+    for i in 0..functions {
+        let _ = write!(source, "\
+# Generated function {i}
+func work_{i}(n: int, scale: float) -> int {{
+    let total: int = 0;
+    let factor: float = scale * 2.5;
+    for let k: int = 0; k < n; k = k + 1 {{
+        if k % 3 == 0 AND k > {i} {{
+            total = total + k * 2;
+        }} else {{
+            total = total - 1;
+        }}
+    }}
+    let label: string = \"work {i}\";
+    display label, total, factor;
+");
+        if i == 0 {
+            source.push_str("    send total;\n}\n\n");
+        } else {
+            let _ = write!(source, "    send total + work_{}(n - 1, scale);\n}}\n\n", i - 1);
+        }
+    }
+    let _ = write!(source, "func main() {{\n    display work_{}(10, 1.5);\n}}\n", functions - 1);
+    source
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    for functions in SIZES {
+        let source = generate_program(functions);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(functions), &source, |b, source| {
+            b.iter(|| Lexer::new(source).tokenize().unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn bench_parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for functions in SIZES {
+        let source = generate_program(functions);
+        let tokens = Lexer::new(&source).tokenize().unwrap();
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(functions), &tokens, |b, tokens| {
+            b.iter(|| Parser::new(tokens.clone(), source.clone()).parse_program().unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn bench_type_checker(c: &mut Criterion) {
+    let mut group = c.benchmark_group("type_check");
+    for functions in SIZES {
+        let source = generate_program(functions);
+        let tokens = Lexer::new(&source).tokenize().unwrap();
+        let program = Parser::new(tokens, source.clone()).parse_program().unwrap();
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(functions), &program, |b, program| {
+            b.iter(|| TypeChecker::new().check_program(program).unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lexer, bench_parser, bench_type_checker);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+// build.rs - Record which commit and target the compiler is built for
+//
+// `minilang self version` prints both, so machines running different builds
+// of the same release can be told apart. Without git (e.g. a source tarball)
+// the hash is "unknown" unless MINILANG_GIT_HASH is set.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=MINILANG_GIT_HASH");
+    let hash = env::var("MINILANG_GIT_HASH")
+        .ok()
+        .or_else(|| git(&["rev-parse", "--short=12", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=MINILANG_GIT_HASH={}", hash);
+    println!("cargo:rustc-env=MINILANG_TARGET={}", env::var("TARGET").unwrap_or_default());
+
+    // HEAD changes when switching branches, the branch's ref on each commit
+    for path in ["HEAD", "packed-refs"].into_iter().map(String::from).chain(git(&["symbolic-ref", "-q", "HEAD"])) {
+        if let Some(file) = git(&["rev-parse", "--git-path", &path]) {
+            if Path::new(&file).exists() {
+                println!("cargo:rerun-if-changed={}", file);
+            }
+        }
+    }
+}
+
+/// The trimmed output of a successful git command
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let text = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !text.is_empty()).then_some(text)
+}
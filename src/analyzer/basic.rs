@@ -1,25 +1,85 @@
-// src/analyzer/basic.rs - Basic counts: LOC, statement count, parameter count
+// src/analyzer/basic.rs - Basic counts: LOC, comment and blank lines, statement count, parameter count
 
 use crate::ast::{Function, Block, Statement};
+use crate::lexer::{Comment, Lexer};
+
+/// How the lines of a function divide up. A line with code and a trailing
+/// comment counts as both a code and a comment line, as in SonarQube.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineCounts {
+    pub code: usize,
+    pub comment: usize,
+    pub blank: usize,
+}
 
 /// Count lines of code for a function using its span in the source
 pub fn count_loc(func: &Function, source: &str) -> usize {
+    count_lines(func, source, &Lexer::new(source).comments()).code
+}
+
+/// Count the code, comment and blank lines of a function. The comment lines
+/// directly above the function belong to it as its documentation.
+pub fn count_lines(func: &Function, source: &str, comments: &[Comment]) -> LineCounts {
     let start = func.span.start.min(source.len());
     let end = func.span.end.min(source.len());
 
     if start >= end {
-        return 0;
+        return LineCounts::default();
     }
 
-    let slice = &source[start..end];
+    let in_comment = |pos: usize| comments.iter().any(|c| c.span.contains(&pos));
+    let classify = |line_start: usize, line: &str| -> (bool, bool) {
+        let mut code = false;
+        let mut comment = false;
+        for (offset, ch) in line.char_indices() {
+            if in_comment(line_start + offset) {
+                comment = true;
+            } else if !ch.is_whitespace() {
+                code = true;
+            }
+        }
+        (code, comment)
+    };
 
-    slice.lines()
-        .filter(|line| {
-            let trimmed = line.trim();
-            // Count non-empty, non-comment-only lines
-            !trimmed.is_empty() && !trimmed.starts_with("//")
-        })
-        .count()
+    let first_line = source[..start].rfind('\n').map_or(0, |pos| pos + 1);
+    let mut counts = LineCounts::default();
+
+    let mut line_start = first_line;
+    for line in source[first_line..end].split('\n') {
+        match classify(line_start, line) {
+            (true, comment) => {
+                counts.code += 1;
+                counts.comment += comment as usize;
+            }
+            (false, true) => counts.comment += 1,
+            (false, false) => counts.blank += 1,
+        }
+        line_start += line.len() + 1;
+    }
+
+    // Documentation: comment-only lines running up to the function
+    let mut line_end = first_line;
+    while line_end > 0 {
+        line_end -= 1; // step back over the newline
+        let line_begin = source[..line_end].rfind('\n').map_or(0, |pos| pos + 1);
+        if classify(line_begin, &source[line_begin..line_end]) != (false, true) {
+            break;
+        }
+        counts.comment += 1;
+        line_end = line_begin;
+    }
+
+    counts
+}
+
+/// Comment lines as a percentage of code and comment lines together
+/// (SonarQube's comment density)
+pub fn comment_density(code: usize, comment: usize) -> f64 {
+    if code + comment == 0 {
+        0.0
+    } else {
+        comment as f64 * 100.0 / (code + comment) as f64
+    }
 }
 
 /// Recursively count all statements in a block (including nested blocks)
@@ -58,8 +118,15 @@ fn count_nested_statements(stmt: &Statement) -> usize {
         Statement::Block(block) => {
             count_statements(block)
         }
+        Statement::Defer(defer_stmt) => {
+            count_statements(&defer_stmt.body)
+        }
+        Statement::Spawn(spawn_stmt) => {
+            count_statements(&spawn_stmt.body)
+        }
         // These statements have no nested blocks
         Statement::Let(_)
+        | Statement::LetTuple(_)
         | Statement::Display(_)
         | Statement::Return(_)
         | Statement::Expression(_)
@@ -80,13 +147,18 @@ mod tests {
     fn make_function(name: &str, params: Vec<Parameter>, stmts: Vec<Statement>, span: Span) -> Function {
         Function {
             name: name.to_string(),
+            visibility: Visibility::Public,
+            is_pure: false,
+            attributes: vec![],
             params,
             return_type: None,
             body: Block {
                 statements: stmts,
                 span: span.clone(),
+                id: NodeId::default(),
             },
             span,
+            id: NodeId::default(),
         }
     }
 
@@ -94,12 +166,14 @@ mod tests {
     fn make_let(name: &str) -> Statement {
         Statement::Let(LetStmt {
             name: name.to_string(),
-            typ: Type::Int,
+            typ: Some(Type::Int),
             value: Some(Expression::Literal(LiteralExpr {
                 value: Literal::Integer(0),
                 span: Span::default(),
+                id: NodeId::default(),
             })),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -109,8 +183,11 @@ mod tests {
             expressions: vec![Expression::Literal(LiteralExpr {
                 value: Literal::String("hello".to_string()),
                 span: Span::default(),
+                id: NodeId::default(),
             })],
+            newline: true,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -120,16 +197,20 @@ mod tests {
             condition: Expression::Literal(LiteralExpr {
                 value: Literal::Boolean(true),
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             then_block: Block {
                 statements: then_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             else_block: else_stmts.map(|stmts| Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -139,12 +220,15 @@ mod tests {
             condition: Expression::Literal(LiteralExpr {
                 value: Literal::Boolean(true),
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -171,7 +255,7 @@ mod tests {
 
     #[test]
     fn test_loc_with_comments() {
-        let source = "func main() {\n    // this is a comment\n    let x: int = 5;\n}\n";
+        let source = "func main() {\n    # this is a comment\n    let x: int = 5;\n}\n";
         let func = make_function("main", vec![], vec![], Span::new(0, source.len()));
         let loc = count_loc(&func, source);
         // comment line excluded: func, let, }
@@ -187,6 +271,32 @@ mod tests {
         assert_eq!(loc, 2);
     }
 
+    #[test]
+    fn test_count_lines_splits_code_comments_and_blanks() {
+        let source = "func main() {\n    # setup\n\n    let x: int = 5; # trailing\n    ## block\n       comment ##\n}\n";
+        let func = make_function("main", vec![], vec![], Span::new(0, source.len() - 1));
+        let counts = count_lines(&func, source, &Lexer::new(source).comments());
+        // code: func, let, }; comments: setup, trailing, both block lines
+        assert_eq!(counts, LineCounts { code: 3, comment: 4, blank: 1 });
+    }
+
+    #[test]
+    fn test_count_lines_includes_doc_comment_above() {
+        let source = "# unrelated\n\n# Adds one\n# to x\nfunc inc() {\n}\n";
+        let start = source.find("func").unwrap();
+        let func = make_function("inc", vec![], vec![], Span::new(start, source.len() - 1));
+        let counts = count_lines(&func, source, &Lexer::new(source).comments());
+        // the blank line ends the doc comment, so "unrelated" is not counted
+        assert_eq!(counts, LineCounts { code: 2, comment: 2, blank: 0 });
+    }
+
+    #[test]
+    fn test_comment_density() {
+        assert_eq!(comment_density(0, 0), 0.0);
+        assert_eq!(comment_density(3, 1), 25.0);
+        assert_eq!(comment_density(0, 2), 100.0);
+    }
+
     // ---- Statement count tests ----
 
     #[test]
@@ -194,6 +304,7 @@ mod tests {
         let block = Block {
             statements: vec![],
             span: Span::default(),
+            id: NodeId::default(),
         };
         assert_eq!(count_statements(&block), 0);
     }
@@ -207,6 +318,7 @@ mod tests {
                 make_display(),
             ],
             span: Span::default(),
+            id: NodeId::default(),
         };
         assert_eq!(count_statements(&block), 3);
     }
@@ -222,6 +334,7 @@ mod tests {
                 ),
             ],
             span: Span::default(),
+            id: NodeId::default(),
         };
         // 1 (the if) + 2 (then) + 1 (else) = 4
         assert_eq!(count_statements(&block), 4);
@@ -235,6 +348,7 @@ mod tests {
                 make_while(vec![make_let("x"), make_display()]),
             ],
             span: Span::default(),
+            id: NodeId::default(),
         };
         // 1 (the while) + 2 (body) = 3
         assert_eq!(count_statements(&block), 3);
@@ -250,6 +364,7 @@ mod tests {
                 ]),
             ],
             span: Span::default(),
+            id: NodeId::default(),
         };
         // 1 (while) + 1 (if) + 1 (let) = 3
         assert_eq!(count_statements(&block), 3);
@@ -266,9 +381,9 @@ mod tests {
     #[test]
     fn test_param_count_multiple() {
         let params = vec![
-            Parameter { name: "a".to_string(), typ: Type::Int, span: Span::default() },
-            Parameter { name: "b".to_string(), typ: Type::Float, span: Span::default() },
-            Parameter { name: "c".to_string(), typ: Type::Bool, span: Span::default() },
+            Parameter { name: "a".to_string(), typ: Type::Int, span: Span::default(), id: NodeId::default() },
+            Parameter { name: "b".to_string(), typ: Type::Float, span: Span::default(), id: NodeId::default() },
+            Parameter { name: "c".to_string(), typ: Type::Bool, span: Span::default(), id: NodeId::default() },
         ];
         let func = make_function("add", params, vec![], Span::default());
         assert_eq!(func.params.len(), 3);
@@ -10,6 +10,7 @@
 // 5. Nesting level increases inside control flow bodies
 
 use crate::ast::*;
+use crate::control_flow::Loop;
 
 /// Calculate cognitive complexity for a function
 pub fn calculate(func: &Function) -> usize {
@@ -36,30 +37,19 @@ fn cognitive_statement(stmt: &Statement, nesting: usize) -> usize {
             }
             score
         }
-        Statement::While(while_stmt) => {
+        Statement::While(_) | Statement::DoWhile(_) | Statement::For(_) => {
+            let Some(lp) = Loop::of(stmt) else { return 0 };
             let mut score = 1 + nesting;
-            score += cognitive_expression(&while_stmt.condition);
-            score += cognitive_block(&while_stmt.body, nesting + 1);
-            score
-        }
-        Statement::DoWhile(do_while_stmt) => {
-            let mut score = 1 + nesting;
-            score += cognitive_expression(&do_while_stmt.condition);
-            score += cognitive_block(&do_while_stmt.body, nesting + 1);
-            score
-        }
-        Statement::For(for_stmt) => {
-            let mut score = 1 + nesting;
-            if let Some(ref init) = for_stmt.init {
+            if let Some(init) = lp.init {
                 score += cognitive_statement(init, nesting);
             }
-            if let Some(ref cond) = for_stmt.condition {
+            if let Some(cond) = lp.condition {
                 score += cognitive_expression(cond);
             }
-            if let Some(ref update) = for_stmt.update {
+            if let Some(update) = lp.update {
                 score += cognitive_expression(update);
             }
-            score += cognitive_block(&for_stmt.body, nesting + 1);
+            score += cognitive_block(lp.body, nesting + 1);
             score
         }
         Statement::Break(_) => 1,
@@ -68,6 +58,14 @@ fn cognitive_statement(stmt: &Statement, nesting: usize) -> usize {
             // Bare block: no increment, no nesting increase
             cognitive_block(block, nesting)
         }
+        Statement::Defer(defer_stmt) => {
+            // Deferred code doesn't branch, but it does run out of line
+            cognitive_block(&defer_stmt.body, nesting + 1)
+        }
+        Statement::Spawn(spawn_stmt) => {
+            // So does spawned code, on another thread
+            cognitive_block(&spawn_stmt.body, nesting + 1)
+        }
         // Non-control-flow: only logical operators in expressions count
         Statement::Let(let_stmt) => {
             if let Some(ref value) = let_stmt.value {
@@ -79,9 +77,12 @@ fn cognitive_statement(stmt: &Statement, nesting: usize) -> usize {
         Statement::Const(const_stmt) => {
             cognitive_expression(&const_stmt.value)
         }
+        Statement::LetTuple(let_tuple) => {
+            cognitive_expression(&let_tuple.value)
+        }
         Statement::Display(display_stmt) => {
             display_stmt.expressions.iter()
-                .map(|e| cognitive_expression(e))
+                .map(cognitive_expression)
                 .sum()
         }
         Statement::Return(ret_stmt) => {
@@ -159,25 +160,32 @@ mod tests {
     fn make_function(stmts: Vec<Statement>) -> Function {
         Function {
             name: "test".to_string(),
+            visibility: Visibility::Public,
+            is_pure: false,
+            attributes: vec![],
             params: vec![],
             return_type: None,
             body: Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         }
     }
 
     fn make_let(name: &str) -> Statement {
         Statement::Let(LetStmt {
             name: name.to_string(),
-            typ: Type::Int,
+            typ: Some(Type::Int),
             value: Some(Expression::Literal(LiteralExpr {
                 value: Literal::Integer(0),
                 span: Span::default(),
+                id: NodeId::default(),
             })),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -185,6 +193,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::Boolean(val),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -192,6 +201,7 @@ mod tests {
         Expression::Identifier(IdentifierExpr {
             name: name.to_string(),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -202,6 +212,8 @@ mod tests {
             right: Box::new(right),
             span: Span::default(),
             optimization_hint: None,
+            parenthesized: false,
+            id: NodeId::default(),
         })
     }
 
@@ -211,12 +223,15 @@ mod tests {
             then_block: Block {
                 statements: then_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             else_block: else_stmts.map(|stmts| Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -226,8 +241,10 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -237,8 +254,10 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -250,17 +269,20 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
+            parallel: false,
         })
     }
 
     fn make_break() -> Statement {
-        Statement::Break(BreakStmt { span: Span::default() })
+        Statement::Break(BreakStmt { span: Span::default(), id: NodeId::default() })
     }
 
     fn make_continue() -> Statement {
-        Statement::Continue(ContinueStmt { span: Span::default() })
+        Statement::Continue(ContinueStmt { span: Span::default(), id: NodeId::default() })
     }
 
     // ---- Basic tests ----
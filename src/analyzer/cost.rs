@@ -0,0 +1,669 @@
+// src/analyzer/cost.rs - Static Cost Estimate
+//
+// Estimates how many operations a function performs, as a polynomial in n,
+// the unknown size of its input, and from that a heuristic growth class.
+// Every operator, call, index, assignment, declaration, display and return
+// is one operation. A `for` loop counting between literals
+// (`for let i = 0; i < 10; i = i + 1`) runs its body that many times. Any
+// other loop runs it n times when its bounds depend on the function's
+// parameters (directly or through variables assigned from them) and once
+// otherwise. An if costs its condition plus the more expensive branch. A
+// call to another function of the program adds that function's cost, as a
+// constant when no argument depends on the caller's input, and a recursive
+// call counts as one operation.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::ast::*;
+
+/// Operation count as a polynomial in n: `terms[k]` is the coefficient of n^k
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cost {
+    pub terms: Vec<u64>,
+}
+
+impl Cost {
+    pub fn constant(ops: u64) -> Self {
+        Cost { terms: vec![ops] }.trimmed()
+    }
+
+    /// Highest power of n with a non-zero coefficient (0 for a constant)
+    pub fn degree(&self) -> usize {
+        self.terms.len().saturating_sub(1)
+    }
+
+    /// The growth class, e.g. `O(n²)`
+    pub fn big_o(&self) -> String {
+        match self.degree() {
+            0 => "O(1)".to_string(),
+            degree => format!("O({})", power(degree)),
+        }
+    }
+
+    fn add(&self, other: &Cost) -> Cost {
+        let len = self.terms.len().max(other.terms.len());
+        let terms = (0..len)
+            .map(|k| {
+                let a = self.terms.get(k).copied().unwrap_or(0);
+                let b = other.terms.get(k).copied().unwrap_or(0);
+                a.saturating_add(b)
+            })
+            .collect();
+        Cost { terms }.trimmed()
+    }
+
+    fn times(&self, count: u64) -> Cost {
+        Cost { terms: self.terms.iter().map(|c| c.saturating_mul(count)).collect() }.trimmed()
+    }
+
+    fn times_n(&self) -> Cost {
+        if self.terms.is_empty() {
+            return Cost::default();
+        }
+        let mut terms = vec![0];
+        terms.extend(&self.terms);
+        Cost { terms }
+    }
+
+    fn trimmed(mut self) -> Cost {
+        while self.terms.last() == Some(&0) {
+            self.terms.pop();
+        }
+        self
+    }
+}
+
+/// Costs compare by growth: first by degree, then coefficient by coefficient
+/// from the highest power down
+impl Ord for Cost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.degree().cmp(&other.degree())
+            .then_with(|| self.terms.iter().rev().cmp(other.terms.iter().rev()))
+    }
+}
+
+impl PartialOrd for Cost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// `3n² + 5n + 2`
+impl fmt::Display for Cost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.terms.iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, &c)| c > 0)
+            .map(|(k, &c)| match (k, c) {
+                (0, c) => c.to_string(),
+                (k, 1) => power(k),
+                (k, c) => format!("{}{}", c, power(k)),
+            })
+            .collect();
+        if parts.is_empty() {
+            write!(f, "0")
+        } else {
+            write!(f, "{}", parts.join(" + "))
+        }
+    }
+}
+
+fn power(k: usize) -> String {
+    match k {
+        1 => "n".to_string(),
+        2 => "n²".to_string(),
+        3 => "n³".to_string(),
+        k => format!("n^{}", k),
+    }
+}
+
+/// The estimate for one function
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Estimate {
+    pub name: String,
+    pub cost: Cost,
+    /// Calls itself, directly or through other functions. The cost then
+    /// covers a single call, not the whole recursion.
+    pub recursive: bool,
+}
+
+impl Estimate {
+    /// The heuristic growth class, e.g. `O(n²)`, or `O(n) per call
+    /// (recursive)`
+    pub fn big_o(&self) -> String {
+        if self.recursive {
+            format!("{} per call (recursive)", self.cost.big_o())
+        } else {
+            self.cost.big_o()
+        }
+    }
+}
+
+/// Estimates for every function in the program, in program order
+pub fn calculate_all(program: &Program) -> Vec<Estimate> {
+    let mut estimator = Estimator::new(program);
+    let costs: Vec<(String, Cost)> = program.functions.iter()
+        .map(|func| (func.name.clone(), estimator.function(&func.name)))
+        .collect();
+    costs.into_iter()
+        .map(|(name, cost)| Estimate { recursive: estimator.recursive.contains(&name), name, cost })
+        .collect()
+}
+
+/// Estimated cost of one call to `func`, which belongs to `program`
+pub fn calculate(func: &Function, program: &Program) -> Cost {
+    Estimator::new(program).function(&func.name)
+}
+
+struct Estimator<'a> {
+    functions: HashMap<&'a str, &'a Function>,
+    known: HashMap<String, Cost>,
+    // Functions being estimated, innermost last, to spot recursion
+    stack: Vec<String>,
+    // The input-dependent names of each function on the stack
+    inputs: Vec<HashSet<String>>,
+    recursive: HashSet<String>,
+}
+
+impl<'a> Estimator<'a> {
+    fn new(program: &'a Program) -> Self {
+        Estimator {
+            functions: program.functions.iter().map(|f| (f.name.as_str(), f)).collect(),
+            known: HashMap::new(),
+            stack: Vec::new(),
+            inputs: Vec::new(),
+            recursive: HashSet::new(),
+        }
+    }
+
+    fn function(&mut self, name: &str) -> Cost {
+        if let Some(cost) = self.known.get(name) {
+            return cost.clone();
+        }
+        let Some(func) = self.functions.get(name).copied() else {
+            return Cost::default();
+        };
+
+        self.stack.push(name.to_string());
+        self.inputs.push(input_names(func));
+        let cost = self.block(&func.body);
+        self.inputs.pop();
+        self.stack.pop();
+
+        self.known.insert(name.to_string(), cost.clone());
+        cost
+    }
+
+    /// Whether `expr` depends on the current function's input
+    fn on_input(&self, expr: &Expression) -> bool {
+        self.inputs.last().is_some_and(|names| mentions(expr, names))
+    }
+
+    /// `iteration` repeated n times when the loop is bounded by the input,
+    /// and counted once otherwise
+    fn repeat(&self, iteration: Cost, bounds: &[Option<&Expression>]) -> Cost {
+        if bounds.iter().flatten().any(|expr| self.on_input(expr)) {
+            iteration.times_n()
+        } else {
+            iteration
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> Cost {
+        block.statements.iter()
+            .fold(Cost::default(), |total, stmt| total.add(&self.statement(stmt)))
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Cost {
+        match stmt {
+            Statement::Let(let_stmt) => match &let_stmt.value {
+                Some(value) => self.expression(value).add(&Cost::constant(1)),
+                None => Cost::constant(1),
+            },
+            Statement::Const(const_stmt) => self.expression(&const_stmt.value).add(&Cost::constant(1)),
+            Statement::LetTuple(let_tuple) => self.expression(&let_tuple.value).add(&Cost::constant(1)),
+            Statement::Display(display_stmt) => display_stmt.expressions.iter()
+                .fold(Cost::constant(1), |total, expr| total.add(&self.expression(expr))),
+            Statement::If(if_stmt) => {
+                let then_cost = self.block(&if_stmt.then_block);
+                let else_cost = match &if_stmt.else_block {
+                    Some(else_block) => self.block(else_block),
+                    None => Cost::default(),
+                };
+                self.expression(&if_stmt.condition).add(&then_cost.max(else_cost))
+            }
+            Statement::While(while_stmt) => {
+                let iteration = self.expression(&while_stmt.condition).add(&self.block(&while_stmt.body));
+                self.repeat(iteration, &[Some(&while_stmt.condition)])
+            }
+            Statement::DoWhile(do_while) => {
+                let iteration = self.block(&do_while.body).add(&self.expression(&do_while.condition));
+                self.repeat(iteration, &[Some(&do_while.condition)])
+            }
+            Statement::For(for_stmt) => {
+                let init = match &for_stmt.init {
+                    Some(init) => self.statement(init),
+                    None => Cost::default(),
+                };
+                let mut iteration = self.block(&for_stmt.body);
+                if let Some(condition) = &for_stmt.condition {
+                    iteration = iteration.add(&self.expression(condition));
+                }
+                if let Some(update) = &for_stmt.update {
+                    iteration = iteration.add(&self.expression(update));
+                }
+                let repeated = match trip_count(for_stmt) {
+                    Some(count) => iteration.times(count),
+                    None => self.repeat(iteration, &[for_stmt.condition.as_ref(), for_stmt.update.as_ref()]),
+                };
+                init.add(&repeated)
+            }
+            Statement::Return(ret_stmt) => match &ret_stmt.value {
+                Some(value) => self.expression(value).add(&Cost::constant(1)),
+                None => Cost::constant(1),
+            },
+            Statement::Expression(expr_stmt) => self.expression(&expr_stmt.expression),
+            Statement::Block(block) => self.block(block),
+            Statement::Defer(defer_stmt) => self.block(&defer_stmt.body),
+            Statement::Spawn(spawn_stmt) => self.block(&spawn_stmt.body),
+            Statement::Break(_) | Statement::Continue(_) => Cost::default(),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Cost {
+        match expr {
+            Expression::Literal(lit_expr) => match &lit_expr.value {
+                Literal::Array(elements) | Literal::Tuple(elements) => elements.iter()
+                    .fold(Cost::default(), |total, element| total.add(&self.expression(element))),
+                Literal::InterpolatedString(parts) => parts.iter()
+                    .filter_map(|part| match part {
+                        StringPart::Expression(expr) => Some(expr),
+                        StringPart::Text(_) => None,
+                    })
+                    .fold(Cost::default(), |total, expr| total.add(&self.expression(expr))),
+                _ => Cost::default(),
+            },
+            Expression::Identifier(_) => Cost::default(),
+            Expression::Binary(bin) => self.expression(&bin.left)
+                .add(&self.expression(&bin.right))
+                .add(&Cost::constant(1)),
+            Expression::Unary(un) => self.expression(&un.operand).add(&Cost::constant(1)),
+            Expression::Index(index) => self.expression(&index.array)
+                .add(&self.expression(&index.index))
+                .add(&Cost::constant(1)),
+            Expression::Assign(assign) => self.expression(&assign.value).add(&Cost::constant(1)),
+            Expression::Call(call) => {
+                let args = call.args.iter()
+                    .fold(Cost::constant(1), |total, arg| total.add(&self.expression(arg)));
+                if let Some(at) = self.stack.iter().position(|name| *name == call.function) {
+                    // Everything from the callee down to here is part of the cycle
+                    self.recursive.extend(self.stack[at..].iter().cloned());
+                    return args;
+                }
+                let callee = self.function(&call.function);
+                if call.args.iter().any(|arg| self.on_input(arg)) {
+                    args.add(&callee)
+                } else {
+                    // With the same arguments on every run, n is fixed for the callee
+                    let ops = callee.terms.iter().fold(0u64, |total, &c| total.saturating_add(c));
+                    args.add(&Cost::constant(ops))
+                }
+            }
+        }
+    }
+}
+
+/// Names in `func` whose values depend on its parameters: the parameters
+/// and, transitively, every variable assigned from one of them or compared
+/// with one in a loop condition (the counter of `while i < n` grows to n)
+fn input_names(func: &Function) -> HashSet<String> {
+    let mut assignments = Vec::new();
+    block_assignments(&func.body, &mut assignments);
+
+    let mut names: HashSet<String> = func.params.iter().map(|param| param.name.clone()).collect();
+    loop {
+        let before = names.len();
+        for (target, value) in &assignments {
+            if mentions(value, &names) {
+                names.insert(target.to_string());
+            }
+        }
+        if names.len() == before {
+            return names;
+        }
+    }
+}
+
+/// Every `name = value` in a block, from declarations and assignments. The
+/// variables in a loop condition are recorded as assigned from it.
+fn block_assignments<'a>(block: &'a Block, out: &mut Vec<(&'a str, &'a Expression)>) {
+    for stmt in &block.statements {
+        statement_assignments(stmt, out);
+    }
+}
+
+fn statement_assignments<'a>(stmt: &'a Statement, out: &mut Vec<(&'a str, &'a Expression)>) {
+    match stmt {
+        Statement::Let(let_stmt) => {
+            if let Some(value) = &let_stmt.value {
+                out.push((&let_stmt.name, value));
+                expression_assignments(value, out);
+            }
+        }
+        Statement::Const(const_stmt) => out.push((&const_stmt.name, &const_stmt.value)),
+        Statement::LetTuple(let_tuple) => {
+            for name in &let_tuple.names {
+                out.push((name, &let_tuple.value));
+            }
+            expression_assignments(&let_tuple.value, out);
+        }
+        Statement::Display(display_stmt) => {
+            for expr in &display_stmt.expressions {
+                expression_assignments(expr, out);
+            }
+        }
+        Statement::If(if_stmt) => {
+            expression_assignments(&if_stmt.condition, out);
+            block_assignments(&if_stmt.then_block, out);
+            if let Some(else_block) = &if_stmt.else_block {
+                block_assignments(else_block, out);
+            }
+        }
+        Statement::While(while_stmt) => {
+            loop_condition(&while_stmt.condition, out);
+            block_assignments(&while_stmt.body, out);
+        }
+        Statement::DoWhile(do_while) => {
+            block_assignments(&do_while.body, out);
+            loop_condition(&do_while.condition, out);
+        }
+        Statement::For(for_stmt) => {
+            if let Some(init) = &for_stmt.init {
+                statement_assignments(init, out);
+            }
+            if let Some(condition) = &for_stmt.condition {
+                loop_condition(condition, out);
+            }
+            if let Some(update) = &for_stmt.update {
+                expression_assignments(update, out);
+            }
+            block_assignments(&for_stmt.body, out);
+        }
+        Statement::Return(ret_stmt) => {
+            if let Some(value) = &ret_stmt.value {
+                expression_assignments(value, out);
+            }
+        }
+        Statement::Expression(expr_stmt) => expression_assignments(&expr_stmt.expression, out),
+        Statement::Block(block) => block_assignments(block, out),
+        Statement::Defer(defer_stmt) => block_assignments(&defer_stmt.body, out),
+        Statement::Spawn(spawn_stmt) => block_assignments(&spawn_stmt.body, out),
+        Statement::Break(_) | Statement::Continue(_) => {}
+    }
+}
+
+fn loop_condition<'a>(condition: &'a Expression, out: &mut Vec<(&'a str, &'a Expression)>) {
+    let mut names = Vec::new();
+    identifiers(condition, &mut names);
+    out.extend(names.into_iter().map(|name| (name, condition)));
+    expression_assignments(condition, out);
+}
+
+fn identifiers<'a>(expr: &'a Expression, out: &mut Vec<&'a str>) {
+    match expr {
+        Expression::Identifier(id_expr) => out.push(&id_expr.name),
+        Expression::Binary(bin) => {
+            identifiers(&bin.left, out);
+            identifiers(&bin.right, out);
+        }
+        Expression::Unary(un) => identifiers(&un.operand, out),
+        Expression::Index(index) => {
+            identifiers(&index.array, out);
+            identifiers(&index.index, out);
+        }
+        Expression::Call(call) => {
+            for arg in &call.args {
+                identifiers(arg, out);
+            }
+        }
+        Expression::Assign(assign) => identifiers(&assign.value, out),
+        Expression::Literal(_) => {}
+    }
+}
+
+fn expression_assignments<'a>(expr: &'a Expression, out: &mut Vec<(&'a str, &'a Expression)>) {
+    match expr {
+        Expression::Assign(assign) => {
+            out.push((&assign.target, &assign.value));
+            expression_assignments(&assign.value, out);
+        }
+        Expression::Binary(bin) => {
+            expression_assignments(&bin.left, out);
+            expression_assignments(&bin.right, out);
+        }
+        Expression::Unary(un) => expression_assignments(&un.operand, out),
+        Expression::Index(index) => {
+            expression_assignments(&index.array, out);
+            expression_assignments(&index.index, out);
+        }
+        Expression::Call(call) => {
+            for arg in &call.args {
+                expression_assignments(arg, out);
+            }
+        }
+        Expression::Literal(_) | Expression::Identifier(_) => {}
+    }
+}
+
+/// Whether `expr` reads any of `names`
+fn mentions(expr: &Expression, names: &HashSet<String>) -> bool {
+    match expr {
+        Expression::Identifier(id_expr) => names.contains(&id_expr.name),
+        Expression::Literal(lit_expr) => match &lit_expr.value {
+            Literal::Array(elements) | Literal::Tuple(elements) => elements.iter().any(|e| mentions(e, names)),
+            Literal::InterpolatedString(parts) => parts.iter().any(|part| match part {
+                StringPart::Expression(expr) => mentions(expr, names),
+                StringPart::Text(_) => false,
+            }),
+            _ => false,
+        },
+        Expression::Binary(bin) => mentions(&bin.left, names) || mentions(&bin.right, names),
+        Expression::Unary(un) => mentions(&un.operand, names),
+        Expression::Index(index) => mentions(&index.array, names) || mentions(&index.index, names),
+        Expression::Call(call) => call.args.iter().any(|arg| mentions(arg, names)),
+        Expression::Assign(assign) => mentions(&assign.value, names),
+    }
+}
+
+/// How many times a `for` loop runs, when it counts an int variable from
+/// one literal to another by a literal step
+fn trip_count(for_stmt: &ForStmt) -> Option<u64> {
+    let (var, start) = match for_stmt.init.as_deref()? {
+        Statement::Let(LetStmt { name, value: Some(value), .. }) => (name.as_str(), int_literal(value)?),
+        Statement::Expression(ExprStmt { expression: Expression::Assign(assign), .. }) => {
+            (assign.target.as_str(), int_literal(&assign.value)?)
+        }
+        _ => return None,
+    };
+
+    let Expression::Binary(condition) = for_stmt.condition.as_ref()? else { return None };
+    if !matches!(condition.left.as_ref(), Expression::Identifier(id) if id.name == var) {
+        return None;
+    }
+    let end = int_literal(&condition.right)?;
+
+    let Expression::Assign(update) = for_stmt.update.as_ref()? else { return None };
+    let Expression::Binary(step) = update.value.as_ref() else { return None };
+    if update.target != var || !matches!(step.left.as_ref(), Expression::Identifier(id) if id.name == var) {
+        return None;
+    }
+    let step_size = int_literal(&step.right)?;
+    if step_size <= 0 {
+        return None;
+    }
+
+    // Distance covered, counting the end value for <= and >=
+    let distance = match (&step.op, &condition.op) {
+        (BinaryOp::Add, BinaryOp::Less) => end - start,
+        (BinaryOp::Add, BinaryOp::LessEqual) => end - start + 1,
+        (BinaryOp::Subtract, BinaryOp::Greater) => start - end,
+        (BinaryOp::Subtract, BinaryOp::GreaterEqual) => start - end + 1,
+        _ => return None,
+    };
+    Some(if distance <= 0 { 0 } else { (distance + step_size - 1) / step_size } as u64)
+}
+
+fn int_literal(expr: &Expression) -> Option<i64> {
+    match expr {
+        Expression::Literal(LiteralExpr { value: Literal::Integer(n), .. }) => Some(i64::from(*n)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    // ---- Helpers ----
+
+    fn parse(source: &str) -> Program {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        Parser::new(tokens, source.to_string()).parse_program().unwrap()
+    }
+
+    fn estimate_of(source: &str, name: &str) -> Estimate {
+        calculate_all(&parse(source)).into_iter()
+            .find(|estimate| estimate.name == name)
+            .unwrap()
+    }
+
+    fn cost_of(source: &str, name: &str) -> Cost {
+        estimate_of(source, name).cost
+    }
+
+    fn poly(terms: &[u64]) -> Cost {
+        Cost { terms: terms.to_vec() }
+    }
+
+    // ---- Polynomials ----
+
+    #[test]
+    fn test_display_and_big_o() {
+        assert_eq!(poly(&[]).to_string(), "0");
+        assert_eq!(poly(&[4]).to_string(), "4");
+        assert_eq!(poly(&[2, 0, 3]).to_string(), "3n² + 2");
+        assert_eq!(poly(&[0, 1, 0, 0, 2]).to_string(), "2n^4 + n");
+        assert_eq!(poly(&[7]).big_o(), "O(1)");
+        assert_eq!(poly(&[1, 5]).big_o(), "O(n)");
+        assert_eq!(poly(&[0, 0, 0, 1]).big_o(), "O(n³)");
+    }
+
+    #[test]
+    fn test_ordering_by_growth() {
+        assert!(poly(&[0, 1]) > poly(&[1000]));
+        assert!(poly(&[0, 0, 1]) > poly(&[0, 99]));
+        assert!(poly(&[5, 2]) > poly(&[9, 1]));
+        assert!(poly(&[5, 2]) < poly(&[6, 2]));
+    }
+
+    // ---- Statements ----
+
+    #[test]
+    fn test_straight_line_code() {
+        // let: 1 + add: 1, display: 1, send: 1
+        let source = "func f(a: int) -> int {\n let b: int = a + 1;\n display b;\n send b;\n}\n";
+        assert_eq!(cost_of(source, "f"), poly(&[4]));
+    }
+
+    #[test]
+    fn test_if_takes_costlier_branch() {
+        let source = "func f(a: int) {\n if a > 0 {\n display a * 2 + 1;\n } else {\n display a;\n }\n}\n";
+        // condition 1 + then branch (display 1, two operators 2)
+        assert_eq!(cost_of(source, "f"), poly(&[4]));
+    }
+
+    #[test]
+    fn test_unknown_loops_multiply_by_n() {
+        let source = "func f(n: int) {\n let i: int = 0;\n while i < n {\n let j: int = 0;\n while j < n {\n j = j + 1;\n }\n i = i + 1;\n }\n}\n";
+        let cost = cost_of(source, "f");
+        assert_eq!(cost.degree(), 2);
+        assert_eq!(cost.big_o(), "O(n²)");
+        // inner: (cond 1 + assign 2) n; outer body: let 1 + inner + assign 2, plus cond 1
+        assert_eq!(cost, poly(&[1, 4, 3]));
+    }
+
+    #[test]
+    fn test_loops_not_bounded_by_input_count_once() {
+        let source = "func f(n: int) {\n let i: int = 0;\n while i < 10 {\n display n;\n i = i + 1;\n }\n}\n";
+        // let 1, then one iteration: condition 1 + display 1 + assign 2
+        assert_eq!(cost_of(source, "f"), poly(&[5]));
+    }
+
+    #[test]
+    fn test_bounds_derived_from_parameters() {
+        let source = "func f(n: int) {\n let limit: int = n * 2;\n let last: int = limit - 1;\n let i: int = 0;\n while i <= last {\n i = i + 1;\n }\n}\n";
+        assert_eq!(cost_of(source, "f").big_o(), "O(n)");
+
+        // The inner loop is bounded by i, which counts up to n
+        let source = "func f(n: int) {\n for let i: int = 0; i < n; i = i + 1 {\n for let j: int = 0; j < i; j = j + 1 {\n display j;\n }\n }\n}\n";
+        assert_eq!(cost_of(source, "f").big_o(), "O(n²)");
+
+        // Counting a parameter down to a literal still depends on it
+        let source = "func f(n: int) {\n while n > 0 {\n n = n - 1;\n }\n}\n";
+        assert_eq!(cost_of(source, "f").big_o(), "O(n)");
+    }
+
+    #[test]
+    fn test_literal_for_loops_run_exactly() {
+        let source = "func f() {\n for let i: int = 0; i < 10; i = i + 1 {\n display i;\n }\n}\n";
+        // init 1, then 10 × (display 1 + condition 1 + update 2)
+        assert_eq!(cost_of(source, "f"), poly(&[41]));
+
+        let source = "func f() {\n for let i: int = 10; i >= 1; i = i - 3 {\n display i;\n }\n}\n";
+        // 10, 7, 4, 1
+        assert_eq!(cost_of(source, "f"), poly(&[17]));
+
+        let source = "func f(n: int) {\n for let i: int = 0; i < n; i = i + 1 {\n display i;\n }\n}\n";
+        assert_eq!(cost_of(source, "f").big_o(), "O(n)");
+    }
+
+    // ---- Calls ----
+
+    #[test]
+    fn test_calls_add_callee_cost() {
+        let source = "func inner(n: int) {\n for let i: int = 0; i < n; i = i + 1 {\n display i;\n }\n}\nfunc outer(n: int) {\n for let i: int = 0; i < n; i = i + 1 {\n inner(n);\n }\n}\n";
+        assert_eq!(cost_of(source, "inner").big_o(), "O(n)");
+        assert_eq!(cost_of(source, "outer").big_o(), "O(n²)");
+    }
+
+    #[test]
+    fn test_constant_arguments_fix_callee_cost() {
+        let source = "func work(n: int) {\n for let i: int = 0; i < n; i = i + 1 {\n display i;\n }\n}\nfunc main() {\n work(15);\n}\n";
+        assert_eq!(cost_of(source, "work"), poly(&[1, 4]));
+        // call 1 + work's 4n + 1 with n fixed
+        assert_eq!(cost_of(source, "main"), poly(&[6]));
+    }
+
+    #[test]
+    fn test_recursion_counts_call_once() {
+        let source = "func fact(n: int) -> int {\n if n <= 1 {\n send 1;\n }\n send n * fact(n - 1);\n}\nfunc main() {\n display fact(5);\n}\n";
+        // condition 1 + send 1, then send: multiply 1 + call 1 + subtract 1 + send 1
+        let fact = estimate_of(source, "fact");
+        assert_eq!(fact.cost, poly(&[6]));
+        assert!(fact.recursive);
+        assert_eq!(fact.big_o(), "O(1) per call (recursive)");
+        assert!(!estimate_of(source, "main").recursive);
+    }
+
+    #[test]
+    fn test_mutual_recursion_marks_both() {
+        let source = "func is_even(n: int) -> bool {\n if n == 0 {\n send true;\n }\n send is_odd(n - 1);\n}\nfunc is_odd(n: int) -> bool {\n if n == 0 {\n send false;\n }\n send is_even(n - 1);\n}\n";
+        assert!(estimate_of(source, "is_even").recursive);
+        assert!(estimate_of(source, "is_odd").recursive);
+    }
+}
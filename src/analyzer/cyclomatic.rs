@@ -4,121 +4,121 @@
 //
 // Decision points:
 //   if, while, do-while, for     → +1 each
-//   && (And), || (Or)            → +1 each
+//   && (And), || (Or)            → +1 each (strict mode only)
+//
+// Strict mode is the default and matches lizard and radon, which count short
+// circuit operators as branches. Classic mode is McCabe's original count.
+// A `match` arm will be one decision point each once the language has one.
 
+use serde::{Serialize, Deserialize};
 use crate::ast::*;
+use crate::control_flow::Loop;
+
+/// Which decision points cyclomatic complexity counts, as chosen with `--cyclomatic`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum CyclomaticMode {
+    /// Branches and loops, plus every AND / OR
+    #[default]
+    Strict,
+    /// Branches and loops only
+    Classic,
+}
 
-/// Calculate cyclomatic complexity for a function
+/// Calculate cyclomatic complexity for a function, in strict mode
 pub fn calculate(func: &Function) -> usize {
+    calculate_with(func, CyclomaticMode::Strict)
+}
+
+/// Calculate cyclomatic complexity for a function, counting decisions as `mode` says
+pub fn calculate_with(func: &Function, mode: CyclomaticMode) -> usize {
     // Start at 1 (the base path through the function)
-    1 + count_decisions_in_block(&func.body)
+    1 + DecisionCounter { mode }.block(&func.body)
 }
 
-fn count_decisions_in_block(block: &Block) -> usize {
-    let mut count = 0;
-    for stmt in &block.statements {
-        count += count_decisions_in_statement(stmt);
-    }
-    count
+struct DecisionCounter {
+    mode: CyclomaticMode,
 }
 
-fn count_decisions_in_statement(stmt: &Statement) -> usize {
-    match stmt {
-        Statement::If(if_stmt) => {
-            let mut count = 1; // the if itself is a decision
-            count += count_decisions_in_expression(&if_stmt.condition);
-            count += count_decisions_in_block(&if_stmt.then_block);
-            if let Some(ref else_block) = if_stmt.else_block {
-                count += count_decisions_in_block(else_block);
-            }
-            count
-        }
-        Statement::While(while_stmt) => {
-            let mut count = 1;
-            count += count_decisions_in_expression(&while_stmt.condition);
-            count += count_decisions_in_block(&while_stmt.body);
-            count
-        }
-        Statement::DoWhile(do_while_stmt) => {
-            let mut count = 1;
-            count += count_decisions_in_expression(&do_while_stmt.condition);
-            count += count_decisions_in_block(&do_while_stmt.body);
-            count
-        }
-        Statement::For(for_stmt) => {
-            let mut count = 1;
-            if let Some(ref condition) = for_stmt.condition {
-                count += count_decisions_in_expression(condition);
+impl DecisionCounter {
+    fn block(&self, block: &Block) -> usize {
+        block.statements.iter().map(|stmt| self.statement(stmt)).sum()
+    }
+
+    fn statement(&self, stmt: &Statement) -> usize {
+        match stmt {
+            Statement::If(if_stmt) => {
+                let mut count = 1; // the if itself is a decision
+                count += self.expression(&if_stmt.condition);
+                count += self.block(&if_stmt.then_block);
+                if let Some(ref else_block) = if_stmt.else_block {
+                    count += self.block(else_block);
+                }
+                count
             }
-            if let Some(ref init) = for_stmt.init {
-                count += count_decisions_in_statement(init);
+            Statement::While(_) | Statement::DoWhile(_) | Statement::For(_) => {
+                let Some(lp) = Loop::of(stmt) else { return 0 };
+                let mut count = 1;
+                if let Some(condition) = lp.condition {
+                    count += self.expression(condition);
+                }
+                if let Some(init) = lp.init {
+                    count += self.statement(init);
+                }
+                if let Some(update) = lp.update {
+                    count += self.expression(update);
+                }
+                count += self.block(lp.body);
+                count
             }
-            if let Some(ref update) = for_stmt.update {
-                count += count_decisions_in_expression(update);
+            Statement::Block(block) => self.block(block),
+            Statement::Defer(defer_stmt) => self.block(&defer_stmt.body),
+            Statement::Spawn(spawn_stmt) => self.block(&spawn_stmt.body),
+            // Statements that may contain expressions with && / ||
+            Statement::Let(let_stmt) => {
+                let_stmt.value.as_ref().map_or(0, |value| self.expression(value))
             }
-            count += count_decisions_in_block(&for_stmt.body);
-            count
-        }
-        Statement::Block(block) => {
-            count_decisions_in_block(block)
-        }
-        // Statements that may contain expressions with && / ||
-        Statement::Let(let_stmt) => {
-            if let Some(ref value) = let_stmt.value {
-                count_decisions_in_expression(value)
-            } else {
-                0
+            Statement::Const(const_stmt) => self.expression(&const_stmt.value),
+            Statement::LetTuple(let_tuple) => self.expression(&let_tuple.value),
+            Statement::Display(display_stmt) => {
+                display_stmt.expressions.iter().map(|expr| self.expression(expr)).sum()
             }
-        }
-        Statement::Const(const_stmt) => {
-            count_decisions_in_expression(&const_stmt.value)
-        }
-        Statement::Display(display_stmt) => {
-            display_stmt.expressions.iter()
-                .map(|e| count_decisions_in_expression(e))
-                .sum()
-        }
-        Statement::Return(ret_stmt) => {
-            if let Some(ref value) = ret_stmt.value {
-                count_decisions_in_expression(value)
-            } else {
-                0
+            Statement::Return(ret_stmt) => {
+                ret_stmt.value.as_ref().map_or(0, |value| self.expression(value))
             }
+            Statement::Expression(expr_stmt) => self.expression(&expr_stmt.expression),
+            Statement::Break(_) | Statement::Continue(_) => 0,
         }
-        Statement::Expression(expr_stmt) => {
-            count_decisions_in_expression(&expr_stmt.expression)
-        }
-        Statement::Break(_) | Statement::Continue(_) => 0,
     }
-}
 
-fn count_decisions_in_expression(expr: &Expression) -> usize {
-    match expr {
-        Expression::Binary(bin) => {
-            let op_count = match bin.op {
-                BinaryOp::And | BinaryOp::Or => 1,
+    fn expression(&self, expr: &Expression) -> usize {
+        match expr {
+            Expression::Binary(bin) => {
+                let op_count = match bin.op {
+                    BinaryOp::And | BinaryOp::Or if self.mode == CyclomaticMode::Strict => 1,
+                    _ => 0,
+                };
+                op_count + self.expression(&bin.left) + self.expression(&bin.right)
+            }
+            Expression::Unary(un) => self.expression(&un.operand),
+            Expression::Call(call) => call.args.iter().map(|arg| self.expression(arg)).sum(),
+            Expression::Index(idx) => self.expression(&idx.array) + self.expression(&idx.index),
+            Expression::Assign(assign) => self.expression(&assign.value),
+            // Conditions can hide inside array, tuple and interpolated string literals
+            Expression::Literal(lit) => match &lit.value {
+                Literal::Array(elements) | Literal::Tuple(elements) => {
+                    elements.iter().map(|element| self.expression(element)).sum()
+                }
+                Literal::InterpolatedString(parts) => parts.iter()
+                    .map(|part| match part {
+                        StringPart::Expression(expr) => self.expression(expr),
+                        StringPart::Text(_) => 0,
+                    })
+                    .sum(),
                 _ => 0,
-            };
-            op_count
-                + count_decisions_in_expression(&bin.left)
-                + count_decisions_in_expression(&bin.right)
-        }
-        Expression::Unary(un) => {
-            count_decisions_in_expression(&un.operand)
-        }
-        Expression::Call(call) => {
-            call.args.iter()
-                .map(|a| count_decisions_in_expression(a))
-                .sum()
-        }
-        Expression::Index(idx) => {
-            count_decisions_in_expression(&idx.array)
-                + count_decisions_in_expression(&idx.index)
-        }
-        Expression::Assign(assign) => {
-            count_decisions_in_expression(&assign.value)
+            },
+            Expression::Identifier(_) => 0,
         }
-        Expression::Literal(_) | Expression::Identifier(_) => 0,
     }
 }
 
@@ -133,25 +133,32 @@ mod tests {
     fn make_function(stmts: Vec<Statement>) -> Function {
         Function {
             name: "test".to_string(),
+            visibility: Visibility::Public,
+            is_pure: false,
+            attributes: vec![],
             params: vec![],
             return_type: None,
             body: Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         }
     }
 
     fn make_let(name: &str) -> Statement {
         Statement::Let(LetStmt {
             name: name.to_string(),
-            typ: Type::Int,
+            typ: Some(Type::Int),
             value: Some(Expression::Literal(LiteralExpr {
                 value: Literal::Integer(0),
                 span: Span::default(),
+                id: NodeId::default(),
             })),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -159,6 +166,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::Boolean(val),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -166,6 +174,7 @@ mod tests {
         Expression::Identifier(IdentifierExpr {
             name: name.to_string(),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -176,6 +185,8 @@ mod tests {
             right: Box::new(right),
             span: Span::default(),
             optimization_hint: None,
+            parenthesized: false,
+            id: NodeId::default(),
         })
     }
 
@@ -185,12 +196,15 @@ mod tests {
             then_block: Block {
                 statements: then_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             else_block: else_stmts.map(|stmts| Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -200,8 +214,10 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -213,8 +229,11 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
+            parallel: false,
         })
     }
 
@@ -224,8 +243,10 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -410,4 +431,57 @@ mod tests {
         ]);
         assert_eq!(calculate(&func), 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_classic_mode_ignores_logical_operators() {
+        // if (a && b || c) → classic counts only the if → complexity = 2
+        let condition = make_binary(
+            make_binary(make_identifier("a"), BinaryOp::And, make_identifier("b")),
+            BinaryOp::Or,
+            make_identifier("c"),
+        );
+        let func = make_function(vec![
+            make_if(condition, vec![make_let("x")], None),
+        ]);
+        assert_eq!(calculate_with(&func, CyclomaticMode::Classic), 2);
+        assert_eq!(calculate_with(&func, CyclomaticMode::Strict), 4);
+        assert_eq!(calculate(&func), 4);
+    }
+
+    #[test]
+    fn test_logical_operators_inside_literals() {
+        // let flags = [a && b, c || d]; display "{a && b}"; → 3 decisions → complexity = 4
+        let array = Expression::Literal(LiteralExpr {
+            value: Literal::Array(vec![
+                make_binary(make_identifier("a"), BinaryOp::And, make_identifier("b")),
+                make_binary(make_identifier("c"), BinaryOp::Or, make_identifier("d")),
+            ]),
+            span: Span::default(),
+            id: NodeId::default(),
+        });
+        let interpolated = Expression::Literal(LiteralExpr {
+            value: Literal::InterpolatedString(vec![
+                StringPart::Expression(make_binary(make_identifier("a"), BinaryOp::And, make_identifier("b"))),
+            ]),
+            span: Span::default(),
+            id: NodeId::default(),
+        });
+        let func = make_function(vec![
+            Statement::Let(LetStmt {
+                name: "flags".to_string(),
+                typ: None,
+                value: Some(array),
+                span: Span::default(),
+                id: NodeId::default(),
+            }),
+            Statement::Display(DisplayStmt {
+                expressions: vec![interpolated],
+                newline: true,
+                span: Span::default(),
+                id: NodeId::default(),
+            }),
+        ]);
+        assert_eq!(calculate(&func), 4);
+        assert_eq!(calculate_with(&func, CyclomaticMode::Classic), 1);
+    }
+}
@@ -8,9 +8,14 @@ use crate::ast::*;
 
 /// Calculate fan-out: number of distinct functions called
 pub fn calculate(func: &Function) -> usize {
+    called_functions(func).len()
+}
+
+/// Names of the functions called anywhere in the body
+pub fn called_functions(func: &Function) -> HashSet<String> {
     let mut called: HashSet<String> = HashSet::new();
     collect_block(&func.body, &mut called);
-    called.len()
+    called
 }
 
 fn collect_block(block: &Block, called: &mut HashSet<String>) {
@@ -29,6 +34,9 @@ fn collect_statement(stmt: &Statement, called: &mut HashSet<String>) {
         Statement::Const(const_stmt) => {
             collect_expression(&const_stmt.value, called);
         }
+        Statement::LetTuple(let_tuple) => {
+            collect_expression(&let_tuple.value, called);
+        }
         Statement::Display(display_stmt) => {
             for expr in &display_stmt.expressions {
                 collect_expression(expr, called);
@@ -72,6 +80,12 @@ fn collect_statement(stmt: &Statement, called: &mut HashSet<String>) {
         Statement::Block(block) => {
             collect_block(block, called);
         }
+        Statement::Defer(defer_stmt) => {
+            collect_block(&defer_stmt.body, called);
+        }
+        Statement::Spawn(spawn_stmt) => {
+            collect_block(&spawn_stmt.body, called);
+        }
         Statement::Break(_) | Statement::Continue(_) => {}
     }
 }
@@ -100,7 +114,7 @@ fn collect_expression(expr: &Expression, called: &mut HashSet<String>) {
         }
         Expression::Literal(lit_expr) => {
             match &lit_expr.value {
-                Literal::Array(elements) => {
+                Literal::Array(elements) | Literal::Tuple(elements) => {
                     for elem in elements {
                         collect_expression(elem, called);
                     }
@@ -130,31 +144,38 @@ mod tests {
     fn make_function(stmts: Vec<Statement>) -> Function {
         Function {
             name: "test".to_string(),
+            visibility: Visibility::Public,
+            is_pure: false,
+            attributes: vec![],
             params: vec![],
             return_type: None,
             body: Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         }
     }
 
     fn make_let(name: &str, value: Expression) -> Statement {
         Statement::Let(LetStmt {
             name: name.to_string(),
-            typ: Type::Int,
+            typ: Some(Type::Int),
             value: Some(value),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
     fn make_let_no_value(name: &str) -> Statement {
         Statement::Let(LetStmt {
             name: name.to_string(),
-            typ: Type::Int,
+            typ: Some(Type::Int),
             value: None,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -162,6 +183,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::Integer(n),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -169,6 +191,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::Boolean(val),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -176,6 +199,7 @@ mod tests {
         Expression::Identifier(IdentifierExpr {
             name: name.to_string(),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -184,6 +208,7 @@ mod tests {
             function: name.to_string(),
             args,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -191,6 +216,7 @@ mod tests {
         Statement::Expression(ExprStmt {
             expression: expr,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -200,12 +226,15 @@ mod tests {
             then_block: Block {
                 statements: then_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             else_block: else_stmts.map(|stmts| Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -215,8 +244,10 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -228,8 +259,11 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
+            parallel: false,
         })
     }
 
@@ -239,8 +273,10 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -248,13 +284,16 @@ mod tests {
         Statement::Return(ReturnStmt {
             value: Some(value),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
     fn make_display(exprs: Vec<Expression>) -> Statement {
         Statement::Display(DisplayStmt {
             expressions: exprs,
+            newline: true,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -350,9 +389,11 @@ mod tests {
                 then_block: Block {
                     statements: vec![],
                     span: Span::default(),
+                    id: NodeId::default(),
                 },
                 else_block: None,
                 span: Span::default(),
+                id: NodeId::default(),
             }),
         ]);
         assert_eq!(calculate(&func), 1);
@@ -435,6 +476,8 @@ mod tests {
                 right: Box::new(make_call("bar", vec![])),
                 span: Span::default(),
                 optimization_hint: None,
+                parenthesized: false,
+                id: NodeId::default(),
             })),
         ]);
         assert_eq!(calculate(&func), 2);
@@ -448,6 +491,7 @@ mod tests {
                 target: "x".to_string(),
                 value: Box::new(make_call("compute", vec![])),
                 span: Span::default(),
+                id: NodeId::default(),
             })),
         ]);
         assert_eq!(calculate(&func), 1);
@@ -472,6 +516,7 @@ mod tests {
                         make_expr_stmt(make_call("bar", vec![])),
                     ],
                     span: Span::default(),
+                    id: NodeId::default(),
                 },
                 else_block: Some(Block {
                     statements: vec![
@@ -479,8 +524,10 @@ mod tests {
                         make_expr_stmt(make_call("foo", vec![])), // dup
                     ],
                     span: Span::default(),
+                    id: NodeId::default(),
                 }),
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             make_while(vec![
                 make_expr_stmt(make_call("qux", vec![])),
@@ -498,6 +545,8 @@ mod tests {
                 op: UnaryOp::Not,
                 operand: Box::new(make_call("is_valid", vec![])),
                 span: Span::default(),
+                parenthesized: false,
+                id: NodeId::default(),
             })),
         ]);
         assert_eq!(calculate(&func), 1);
@@ -9,11 +9,19 @@
 // Volume:      N * log2(n)
 // Difficulty: (n1 / 2) * (N2 / n2)
 // Effort:     difficulty * volume
+// Time:       effort / 18 seconds (Stroud number)
+// Bugs:       volume / 3000 (delivered bugs)
 
 use std::collections::HashSet;
 use crate::ast::*;
 use crate::analyzer::HalsteadMetrics;
 
+/// Elementary mental discriminations per second, used to turn effort into time
+const STROUD_NUMBER: f64 = 18.0;
+
+/// Volume per delivered bug
+const VOLUME_PER_BUG: f64 = 3000.0;
+
 /// Collector that accumulates operators and operands
 struct HalsteadCollector {
     operators: Vec<String>,
@@ -61,6 +69,8 @@ impl HalsteadCollector {
         };
 
         let effort = difficulty * volume;
+        let time_seconds = effort / STROUD_NUMBER;
+        let delivered_bugs = volume / VOLUME_PER_BUG;
 
         HalsteadMetrics {
             unique_operators: n1,
@@ -72,6 +82,8 @@ impl HalsteadCollector {
             volume,
             difficulty,
             effort,
+            time_seconds,
+            delivered_bugs,
         }
     }
 }
@@ -99,6 +111,14 @@ fn collect_statement(stmt: &Statement, c: &mut HalsteadCollector) {
                 collect_expression(value, c);
             }
         }
+        Statement::LetTuple(let_tuple) => {
+            c.add_operator("let");
+            for name in &let_tuple.names {
+                c.add_operand(name);
+            }
+            c.add_operator("=");
+            collect_expression(&let_tuple.value, c);
+        }
         Statement::Const(const_stmt) => {
             c.add_operator("const");
             c.add_operand(&const_stmt.name);
@@ -161,6 +181,14 @@ fn collect_statement(stmt: &Statement, c: &mut HalsteadCollector) {
         Statement::Continue(_) => {
             c.add_operator("continue");
         }
+        Statement::Defer(defer_stmt) => {
+            c.add_operator("defer");
+            collect_block(&defer_stmt.body, c);
+        }
+        Statement::Spawn(spawn_stmt) => {
+            c.add_operator("spawn");
+            collect_block(&spawn_stmt.body, c);
+        }
     }
 }
 
@@ -178,6 +206,12 @@ fn collect_expression(expr: &Expression, c: &mut HalsteadCollector) {
                         collect_expression(elem, c);
                     }
                 }
+                Literal::Tuple(elements) => {
+                    c.add_operator("()");
+                    for elem in elements {
+                        collect_expression(elem, c);
+                    }
+                }
                 Literal::InterpolatedString(parts) => {
                     c.add_operator("interpolation");
                     for part in parts {
@@ -250,13 +284,18 @@ mod tests {
     fn make_function(stmts: Vec<Statement>) -> Function {
         Function {
             name: "test".to_string(),
+            visibility: Visibility::Public,
+            is_pure: false,
+            attributes: vec![],
             params: vec![],
             return_type: None,
             body: Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         }
     }
 
@@ -264,6 +303,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::Integer(n),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -271,6 +311,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::Float(f),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -278,6 +319,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::String(s.to_string()),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -285,6 +327,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::Boolean(val),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -292,6 +335,7 @@ mod tests {
         Expression::Identifier(IdentifierExpr {
             name: name.to_string(),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -302,6 +346,8 @@ mod tests {
             right: Box::new(right),
             span: Span::default(),
             optimization_hint: None,
+            parenthesized: false,
+            id: NodeId::default(),
         })
     }
 
@@ -310,31 +356,37 @@ mod tests {
             op,
             operand: Box::new(operand),
             span: Span::default(),
+            parenthesized: false,
+            id: NodeId::default(),
         })
     }
 
     fn make_let(name: &str, value: Expression) -> Statement {
         Statement::Let(LetStmt {
             name: name.to_string(),
-            typ: Type::Int,
+            typ: Some(Type::Int),
             value: Some(value),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
     fn make_let_no_value(name: &str) -> Statement {
         Statement::Let(LetStmt {
             name: name.to_string(),
-            typ: Type::Int,
+            typ: Some(Type::Int),
             value: None,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
     fn make_display(exprs: Vec<Expression>) -> Statement {
         Statement::Display(DisplayStmt {
             expressions: exprs,
+            newline: true,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -344,9 +396,11 @@ mod tests {
             then_block: Block {
                 statements: then_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             else_block: None,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -354,6 +408,7 @@ mod tests {
         Statement::Return(ReturnStmt {
             value: Some(value),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -362,6 +417,7 @@ mod tests {
             function: name.to_string(),
             args,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -370,6 +426,7 @@ mod tests {
             target: target.to_string(),
             value: Box::new(value),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -538,6 +595,7 @@ mod tests {
                     make_int_literal(5),
                 ]),
                 span: Span::default(),
+                id: NodeId::default(),
             }),
         ]);
         let h = calculate(&func);
@@ -558,6 +616,7 @@ mod tests {
                     make_binary(make_identifier("x"), BinaryOp::Add, make_int_literal(1)),
                 ),
                 span: Span::default(),
+                id: NodeId::default(),
             }),
         ]);
         let h = calculate(&func);
@@ -608,29 +667,51 @@ mod tests {
         assert!((h.difficulty - 2.25).abs() < 0.001);
     }
 
+    #[test]
+    fn test_time_and_bugs_estimates() {
+        // let x: int = a + a;
+        // vocabulary = 5, length = 6, volume = 6 * log2(5)
+        // effort = 2.25 * volume, time = effort / 18, bugs = volume / 3000
+        let func = make_function(vec![
+            make_let("x",
+                make_binary(make_identifier("a"), BinaryOp::Add, make_identifier("a")),
+            ),
+        ]);
+        let h = calculate(&func);
+        let volume = 6.0 * 5f64.log2();
+        assert!((h.time_seconds - 2.25 * volume / 18.0).abs() < 0.001);
+        assert!((h.delivered_bugs - volume / 3000.0).abs() < 0.000001);
+
+        let empty = calculate(&make_function(vec![]));
+        assert_eq!(empty.time_seconds, 0.0);
+        assert_eq!(empty.delivered_bugs, 0.0);
+    }
+
     #[test]
     fn test_float_and_bool_operands() {
-        // let pi: float = 3.14;
+        // let pi: float = 2.75;
         // let flag: bool = true;
         // operators: "let", "=", "let", "="
-        // operands: "pi", "3.14", "flag", "true"
+        // operands: "pi", "2.75", "flag", "true"
         let func = make_function(vec![
             Statement::Let(LetStmt {
                 name: "pi".to_string(),
-                typ: Type::Float,
-                value: Some(make_float_literal(3.14)),
+                typ: Some(Type::Float),
+                value: Some(make_float_literal(2.75)),
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             Statement::Let(LetStmt {
                 name: "flag".to_string(),
-                typ: Type::Bool,
+                typ: Some(Type::Bool),
                 value: Some(make_bool_literal(true)),
                 span: Span::default(),
+                id: NodeId::default(),
             }),
         ]);
         let h = calculate(&func);
         assert_eq!(h.unique_operators, 2);  // let, =
-        assert_eq!(h.unique_operands, 4);   // pi, 3.14, flag, true
+        assert_eq!(h.unique_operands, 4);   // pi, 2.75, flag, true
         assert_eq!(h.total_operators, 4);   // let, =, let, =
         assert_eq!(h.total_operands, 4);
     }
@@ -651,9 +732,11 @@ mod tests {
                 then_block: Block {
                     statements: vec![make_display(vec![make_identifier("x")])],
                     span: Span::default(),
+                    id: NodeId::default(),
                 },
                 else_block: None,
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             make_return(make_identifier("y")),
         ]);
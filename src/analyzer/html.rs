@@ -0,0 +1,220 @@
+// src/analyzer/html.rs - Standalone HTML rendering of a project report
+//
+// One self-contained page (inline CSS, no scripts) that can be archived as a
+// CI artifact or opened straight from disk.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use super::{format_duration, function_warnings, FunctionMetrics, ProgramMetrics, ProjectReport, Rating};
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 70em; color: #222; }
+h1, h2, h3 { font-weight: 600; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { padding: 0.3em 0.8em; border-bottom: 1px solid #ddd; text-align: right; }
+th:first-child, td:first-child, td.name { text-align: left; }
+code { font-family: ui-monospace, monospace; }
+.rating { font-weight: 700; padding: 0 0.4em; border-radius: 0.3em; color: #fff; }
+.rating-A { background: #2e7d32; }
+.rating-B { background: #9e9d24; }
+.rating-C { background: #ef6c00; }
+.rating-D { background: #c62828; }
+.rating-F { background: #4a148c; }
+ul.warnings { margin: 0; padding-left: 1.2em; text-align: left; color: #8a4b00; }
+";
+
+/// Render `report` as a complete HTML document
+pub fn render(report: &ProjectReport) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>MiniLang Analysis Report</title>\n");
+    let _ = writeln!(html, "<style>\n{}</style>\n</head>\n<body>", STYLE);
+    html.push_str("<h1>MiniLang Analysis Report</h1>\n");
+
+    html.push_str("<h2>Summary</h2>\n");
+    write_totals(&mut html, &report.project_totals, report.files.len());
+
+    if !report.worst_functions.is_empty() {
+        html.push_str("<h2>Most Complex Functions</h2>\n<table>\n");
+        html.push_str("<tr><th>#</th><th>Function</th><th>File</th><th>Rating</th><th>Cyclomatic</th><th>Cognitive</th><th>Nesting</th><th>LOC</th><th>Warnings</th></tr>\n");
+        for (rank, ranked) in report.worst_functions.iter().enumerate() {
+            let func = &ranked.function;
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"name\"><code>{}</code></td><td class=\"name\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                rank + 1,
+                escape(&func.name),
+                escape(&ranked.file),
+                rating_badge(&func.rating),
+                func.cyclomatic_complexity,
+                func.cognitive_complexity,
+                func.max_nesting_depth,
+                func.loc,
+                warnings_list(func),
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Files</h2>\n");
+    for file in &report.files {
+        let _ = writeln!(
+            html,
+            "<h3>{} {}</h3>",
+            escape(&file.path),
+            rating_badge(&file.report.program_totals.overall_rating),
+        );
+        // A column for each metric a plugin gave for any of the functions
+        let plugin_columns: BTreeSet<&String> = file.report.functions.iter()
+            .flat_map(|func| func.plugin_metrics.keys())
+            .collect();
+        let plugin_headers: String = plugin_columns.iter()
+            .map(|name| format!("<th>{}</th>", escape(name)))
+            .collect();
+        let _ = writeln!(html, "<table>\n<tr><th>Function</th><th>Rating</th><th>LOC</th><th>Comments</th><th>Statements</th><th>Parameters</th><th>Cyclomatic</th><th>Cognitive</th><th>Nesting</th><th>Halstead volume</th><th>Est. time</th><th>Est. bugs</th><th>Fan-out</th>{}</tr>", plugin_headers);
+        for func in &file.report.functions {
+            let plugin_cells: String = plugin_columns.iter()
+                .map(|name| match func.plugin_metrics.get(*name) {
+                    Some(value) => format!("<td>{}</td>", value),
+                    None => "<td></td>".to_string(),
+                })
+                .collect();
+            let _ = writeln!(
+                html,
+                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{} ({:.0}%)</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td><td>{}</td><td>{:.3}</td><td>{}</td>{}</tr>",
+                escape(&func.name),
+                rating_badge(&func.rating),
+                func.loc,
+                func.comment_lines,
+                func.comment_density,
+                func.statement_count,
+                func.parameter_count,
+                func.cyclomatic_complexity,
+                func.cognitive_complexity,
+                func.max_nesting_depth,
+                func.halstead.volume,
+                format_duration(func.halstead.time_seconds),
+                func.halstead.delivered_bugs,
+                func.fan_out,
+                plugin_cells,
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// The project totals as a two-column table
+fn write_totals(html: &mut String, totals: &ProgramMetrics, files: usize) {
+    let rows = [
+        ("Files", files.to_string()),
+        ("Functions", totals.total_functions.to_string()),
+        ("Total LOC", totals.total_loc.to_string()),
+        ("Comment lines", format!("{} ({:.0}%)", totals.total_comment_lines, totals.comment_density)),
+        ("Blank lines", totals.total_blank_lines.to_string()),
+        ("Total statements", totals.total_statements.to_string()),
+        ("Avg cyclomatic", format!("{:.1}", totals.avg_cyclomatic)),
+        ("Max cyclomatic", totals.max_cyclomatic.to_string()),
+        ("Avg cognitive", format!("{:.1}", totals.avg_cognitive)),
+        ("Max cognitive", totals.max_cognitive.to_string()),
+        ("Halstead volume", format!("{:.1}", totals.total_halstead_volume)),
+        ("Est. time", format_duration(totals.estimated_time_seconds)),
+        ("Est. bugs", format!("{:.2}", totals.estimated_bugs)),
+    ];
+    html.push_str("<table>\n");
+    for (label, value) in rows {
+        let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", label, value);
+    }
+    let _ = writeln!(
+        html,
+        "<tr><th>Overall rating</th><td>{} {}</td></tr>",
+        rating_badge(&totals.overall_rating),
+        totals.overall_rating.label(),
+    );
+    html.push_str("</table>\n");
+}
+
+fn rating_badge(rating: &Rating) -> String {
+    format!("<span class=\"rating rating-{}\" title=\"{}\">{}</span>", rating, rating.label(), rating)
+}
+
+fn warnings_list(func: &FunctionMetrics) -> String {
+    let warnings = function_warnings(func);
+    if warnings.is_empty() {
+        return String::new();
+    }
+    let items: String = warnings.iter()
+        .map(|warning| format!("<li>{}</li>", escape(warning)))
+        .collect();
+    format!("<ul class=\"warnings\">{}</ul>", items)
+}
+
+/// Escape text for use in HTML content and attribute values
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{AnalysisReport, FileReport, HalsteadMetrics};
+
+    fn make_metrics(name: &str, cyclomatic: usize, rating: Rating) -> FunctionMetrics {
+        FunctionMetrics {
+            name: name.to_string(),
+            loc: 3,
+            comment_lines: 0,
+            blank_lines: 0,
+            comment_density: 0.0,
+            statement_count: 1,
+            parameter_count: 0,
+            cyclomatic_complexity: cyclomatic,
+            max_nesting_depth: 0,
+            cognitive_complexity: 0,
+            halstead: HalsteadMetrics::default(),
+            fan_out: 0,
+            rating,
+            plugin_metrics: Default::default(),
+        }
+    }
+
+    fn make_project(path: &str, functions: Vec<FunctionMetrics>) -> ProjectReport {
+        let report = AnalysisReport {
+            program_totals: crate::analyzer::compute_program_totals(&functions),
+            functions,
+        };
+        ProjectReport::new(vec![FileReport { path: path.to_string(), report }], 10)
+    }
+
+    #[test]
+    fn test_render_is_a_complete_document() {
+        let html = render(&make_project("src/main.mini", vec![make_metrics("main", 1, Rating::A)]));
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.trim_end().ends_with("</html>"));
+        assert!(html.contains("<h3>src/main.mini <span class=\"rating rating-A\""));
+        assert!(html.contains("<code>main</code>"));
+    }
+
+    #[test]
+    fn test_render_lists_worst_functions_with_warnings() {
+        let html = render(&make_project("lib.mini", vec![
+            make_metrics("simple", 1, Rating::A),
+            make_metrics("tangled", 12, Rating::C),
+        ]));
+        let worst = &html[html.find("Most Complex Functions").unwrap()..html.find("<h2>Files").unwrap()];
+        assert!(worst.find("tangled").unwrap() < worst.find("simple").unwrap());
+        assert!(worst.contains("<li>High cyclomatic complexity (12)"));
+    }
+
+    #[test]
+    fn test_render_escapes_paths() {
+        let html = render(&make_project("a<b>&c.mini", vec![]));
+        assert!(html.contains("a&lt;b&gt;&amp;c.mini"));
+        assert!(!html.contains("a<b>"));
+    }
+}
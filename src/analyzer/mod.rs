@@ -6,9 +6,17 @@ pub mod nesting;
 pub mod cognitive;
 pub mod halstead;
 pub mod fanout;
+pub mod cost;
+pub mod html;
+
+pub use cyclomatic::CyclomaticMode;
 
 use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
 use crate::ast::Program;
+use crate::lexer::Lexer;
+use crate::plugin::{self, CompilerPlugin};
+use crate::style::{self, Color};
 
 /// Complete analysis report for a program
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +30,10 @@ pub struct AnalysisReport {
 pub struct FunctionMetrics {
     pub name: String,
     pub loc: usize,
+    pub comment_lines: usize,
+    pub blank_lines: usize,
+    /// Comment lines as a percentage of code and comment lines
+    pub comment_density: f64,
     pub statement_count: usize,
     pub parameter_count: usize,
     pub cyclomatic_complexity: usize,
@@ -30,6 +42,9 @@ pub struct FunctionMetrics {
     pub halstead: HalsteadMetrics,
     pub fan_out: usize,
     pub rating: Rating,
+    /// What plugins measured, by `<plugin>.<metric>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub plugin_metrics: BTreeMap<String, f64>,
 }
 
 /// Program-wide aggregate metrics
@@ -37,14 +52,45 @@ pub struct FunctionMetrics {
 pub struct ProgramMetrics {
     pub total_functions: usize,
     pub total_loc: usize,
+    pub total_comment_lines: usize,
+    pub total_blank_lines: usize,
+    pub comment_density: f64,
     pub total_statements: usize,
     pub avg_cyclomatic: f64,
     pub max_cyclomatic: usize,
     pub avg_cognitive: f64,
     pub max_cognitive: usize,
+    pub total_halstead_volume: f64,
+    pub total_halstead_effort: f64,
+    /// Sum of the functions' Halstead time estimates
+    pub estimated_time_seconds: f64,
+    /// Sum of the functions' Halstead delivered bug estimates
+    pub estimated_bugs: f64,
     pub overall_rating: Rating,
 }
 
+/// Analysis of several files, with totals and rankings across all of them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectReport {
+    pub files: Vec<FileReport>,
+    pub project_totals: ProgramMetrics,
+    pub worst_functions: Vec<RankedFunction>,
+}
+
+/// The analysis of one file in a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileReport {
+    pub path: String,
+    pub report: AnalysisReport,
+}
+
+/// A function together with the file it is defined in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedFunction {
+    pub file: String,
+    pub function: FunctionMetrics,
+}
+
 /// Halstead complexity metrics
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct HalsteadMetrics {
@@ -57,11 +103,16 @@ pub struct HalsteadMetrics {
     pub volume: f64,
     pub difficulty: f64,
     pub effort: f64,
+    /// Estimated time to write the function, in seconds
+    pub time_seconds: f64,
+    /// Estimated number of bugs delivered with the function
+    pub delivered_bugs: f64,
 }
 
 /// Complexity rating
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd, Default)]
 pub enum Rating {
+    #[default]
     A,
     B,
     C,
@@ -69,12 +120,6 @@ pub enum Rating {
     F,
 }
 
-impl Default for Rating {
-    fn default() -> Self {
-        Rating::A
-    }
-}
-
 impl std::fmt::Display for Rating {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -100,7 +145,7 @@ impl Rating {
     }
 
     /// Get an emoji for the rating
-    pub fn emoji(&self) -> &str {
+    pub fn emoji(&self) -> &'static str {
         match self {
             Rating::A => "🟢",
             Rating::B => "🟡",
@@ -147,21 +192,46 @@ fn rate_function(func: &FunctionMetrics) -> Rating {
 
 /// Main entry point: analyze an entire program
 pub fn analyze_program(program: &Program, source: &str) -> AnalysisReport {
+    analyze_program_with(program, source, CyclomaticMode::default())
+}
+
+/// Analyze an entire program, counting cyclomatic complexity as `mode` says
+pub fn analyze_program_with(program: &Program, source: &str, mode: CyclomaticMode) -> AnalysisReport {
+    analyze_program_with_plugins(program, source, mode, &plugin::installed())
+}
+
+/// Analyze an entire program, adding the metrics of these plugins instead
+/// of the installed ones
+pub fn analyze_program_with_plugins(
+    program: &Program,
+    source: &str,
+    mode: CyclomaticMode,
+    plugins: &[&'static dyn CompilerPlugin],
+) -> AnalysisReport {
     let mut functions = Vec::new();
+    let comments = Lexer::new(source).comments();
 
     for func in &program.functions {
-        let loc = basic::count_loc(func, source);
+        let lines = basic::count_lines(func, source, &comments);
         let statement_count = basic::count_statements(&func.body);
         let parameter_count = func.params.len();
-        let cyclomatic_complexity = cyclomatic::calculate(func);
+        let cyclomatic_complexity = cyclomatic::calculate_with(func, mode);
         let max_nesting_depth = nesting::calculate(func);
         let cognitive_complexity = cognitive::calculate(func);
         let halstead = halstead::calculate(func);
         let fan_out = fanout::calculate(func);
+        let plugin_metrics = plugins.iter()
+            .flat_map(|plugin| plugin.metrics(func).into_iter().map(|metric| {
+                (format!("{}.{}", plugin.name(), metric.name), metric.value)
+            }))
+            .collect();
 
         let mut metrics = FunctionMetrics {
             name: func.name.clone(),
-            loc,
+            loc: lines.code,
+            comment_lines: lines.comment,
+            blank_lines: lines.blank,
+            comment_density: basic::comment_density(lines.code, lines.comment),
             statement_count,
             parameter_count,
             cyclomatic_complexity,
@@ -170,6 +240,7 @@ pub fn analyze_program(program: &Program, source: &str) -> AnalysisReport {
             halstead,
             fan_out,
             rating: Rating::default(),
+            plugin_metrics,
         };
 
         metrics.rating = rate_function(&metrics);
@@ -184,10 +255,59 @@ pub fn analyze_program(program: &Program, source: &str) -> AnalysisReport {
     }
 }
 
+impl AnalysisReport {
+    /// Keep only the functions named in `names`, with the program totals
+    /// recomputed over them. Returns the names that matched no function.
+    pub fn retain_functions(&mut self, names: &[String]) -> Vec<String> {
+        self.functions.retain(|f| names.contains(&f.name));
+        self.program_totals = compute_program_totals(&self.functions);
+
+        names.iter()
+            .filter(|name| !self.functions.iter().any(|f| &f.name == *name))
+            .cloned()
+            .collect()
+    }
+}
+
+impl ProjectReport {
+    /// Combine per-file reports, totalling every function and listing the
+    /// `worst` most complex ones: worst rating first, then by cognitive and
+    /// cyclomatic complexity
+    pub fn new(files: Vec<FileReport>, worst: usize) -> ProjectReport {
+        let functions: Vec<FunctionMetrics> = files.iter()
+            .flat_map(|file| file.report.functions.iter().cloned())
+            .collect();
+        let project_totals = compute_program_totals(&functions);
+
+        let mut ranked: Vec<RankedFunction> = files.iter()
+            .flat_map(|file| file.report.functions.iter().map(|function| RankedFunction {
+                file: file.path.clone(),
+                function: function.clone(),
+            }))
+            .collect();
+        // Stable, so ties keep the order of the files
+        ranked.sort_by(|a, b| {
+            b.function.rating.partial_cmp(&a.function.rating).unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.function.cognitive_complexity.cmp(&a.function.cognitive_complexity))
+                .then(b.function.cyclomatic_complexity.cmp(&a.function.cyclomatic_complexity))
+        });
+        ranked.truncate(worst);
+
+        ProjectReport {
+            files,
+            project_totals,
+            worst_functions: ranked,
+        }
+    }
+}
+
 /// Compute aggregate metrics across all functions
 fn compute_program_totals(functions: &[FunctionMetrics]) -> ProgramMetrics {
     let total_functions = functions.len();
     let total_loc: usize = functions.iter().map(|f| f.loc).sum();
+    let total_comment_lines: usize = functions.iter().map(|f| f.comment_lines).sum();
+    let total_blank_lines: usize = functions.iter().map(|f| f.blank_lines).sum();
+    let comment_density = basic::comment_density(total_loc, total_comment_lines);
     let total_statements: usize = functions.iter().map(|f| f.statement_count).sum();
 
     let avg_cyclomatic = if total_functions > 0 {
@@ -206,6 +326,11 @@ fn compute_program_totals(functions: &[FunctionMetrics]) -> ProgramMetrics {
     };
     let max_cognitive = functions.iter().map(|f| f.cognitive_complexity).max().unwrap_or(0);
 
+    let total_halstead_volume = functions.iter().map(|f| f.halstead.volume).sum();
+    let total_halstead_effort = functions.iter().map(|f| f.halstead.effort).sum();
+    let estimated_time_seconds = functions.iter().map(|f| f.halstead.time_seconds).sum();
+    let estimated_bugs = functions.iter().map(|f| f.halstead.delivered_bugs).sum();
+
     let overall_rating = functions.iter()
         .map(|f| &f.rating)
         .fold(Rating::A, |worst, r| Rating::worst(&worst, r));
@@ -213,32 +338,41 @@ fn compute_program_totals(functions: &[FunctionMetrics]) -> ProgramMetrics {
     ProgramMetrics {
         total_functions,
         total_loc,
+        total_comment_lines,
+        total_blank_lines,
+        comment_density,
         total_statements,
         avg_cyclomatic,
         max_cyclomatic,
         avg_cognitive,
         max_cognitive,
+        total_halstead_volume,
+        total_halstead_effort,
+        estimated_time_seconds,
+        estimated_bugs,
         overall_rating,
     }
 }
 
 /// Pretty-print the analysis report to the terminal
 pub fn display_report(report: &AnalysisReport) {
-    println!("\n{}", "═".repeat(62));
-    println!("  📊 Static Analysis Report");
-    println!("{}", "═".repeat(62));
+    println!("\n{}", style::icon("═").repeat(62));
+    println!("  {} Static Analysis Report", style::icon("📊"));
+    println!("{}", style::icon("═").repeat(62));
 
     for func in &report.functions {
         println!();
         println!("  {} {} Function: {}",
-            func.rating.emoji(),
+            style::icon(func.rating.emoji()),
             func.rating,
             func.name,
         );
-        println!("  {}", "─".repeat(40));
+        println!("  {}", style::icon("─").repeat(40));
 
         // Basic counts
         println!("    Lines of code:     {}", func.loc);
+        println!("    Comment lines:     {} ({:.0}%)", func.comment_lines, func.comment_density);
+        println!("    Blank lines:       {}", func.blank_lines);
         println!("    Statements:        {}", func.statement_count);
         println!("    Parameters:        {}", func.parameter_count);
 
@@ -256,10 +390,17 @@ pub fn display_report(report: &AnalysisReport) {
         // Halstead
         println!("    Halstead volume:   {:.1}", func.halstead.volume);
         println!("    Halstead effort:   {:.1}", func.halstead.effort);
+        println!("    Est. time:         {}", format_duration(func.halstead.time_seconds));
+        println!("    Est. bugs:         {:.3}", func.halstead.delivered_bugs);
 
         // Fan-out
         println!("    Fan-out:           {}", func.fan_out);
 
+        // Plugins
+        for (name, value) in &func.plugin_metrics {
+            println!("    {:<18} {}", format!("{}:", name), value);
+        }
+
         // Rating
         println!("    Rating:            {} ({})", func.rating, func.rating.label());
 
@@ -268,49 +409,122 @@ pub fn display_report(report: &AnalysisReport) {
     }
 
     // Program totals
+    display_totals("Program Summary", &report.program_totals);
+
+    println!("\n{}", style::icon("═").repeat(62));
+}
+
+/// Pretty-print a project report: a line per file, the worst functions
+/// across the project and the project totals
+pub fn display_project_report(report: &ProjectReport) {
+    println!("\n{}", style::icon("═").repeat(62));
+    println!("  {} Project Analysis Report", style::icon("📊"));
+    println!("{}", style::icon("═").repeat(62));
+
+    let width = report.files.iter()
+        .map(|file| file.path.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    println!();
+    println!("  {:<width$}  {:>9} {:>5} {:>7} {:>7}  Rating", "File", "Functions", "LOC", "Max CC", "Max Cog");
+    println!("  {}", style::icon("─").repeat(width + 40));
+    for file in &report.files {
+        let totals = &file.report.program_totals;
+        println!("  {:<width$}  {:>9} {:>5} {:>7} {:>7}  {} {}",
+            file.path,
+            totals.total_functions,
+            totals.total_loc,
+            totals.max_cyclomatic,
+            totals.max_cognitive,
+            style::icon(totals.overall_rating.emoji()),
+            totals.overall_rating,
+        );
+    }
+
+    if !report.worst_functions.is_empty() {
+        println!();
+        println!("  {}", style::icon("─").repeat(40));
+        println!("  {} Most Complex Functions", style::icon("🔥"));
+        println!("  {}", style::icon("─").repeat(40));
+        for (rank, ranked) in report.worst_functions.iter().enumerate() {
+            let func = &ranked.function;
+            println!("  {:>3}. {} {} {} ({})  cyclomatic {}, cognitive {}",
+                rank + 1,
+                style::icon(func.rating.emoji()),
+                func.rating,
+                func.name,
+                ranked.file,
+                func.cyclomatic_complexity,
+                func.cognitive_complexity,
+            );
+            display_function_warnings(func);
+        }
+    }
+
+    display_totals("Project Summary", &report.project_totals);
+
+    println!("\n{}", style::icon("═").repeat(62));
+}
+
+/// Print aggregate metrics under `title`
+fn display_totals(title: &str, totals: &ProgramMetrics) {
     println!();
-    println!("  {}", "─".repeat(40));
-    println!("  📋 Program Summary");
-    println!("  {}", "─".repeat(40));
-    println!("    Functions:         {}", report.program_totals.total_functions);
-    println!("    Total LOC:         {}", report.program_totals.total_loc);
-    println!("    Total statements:  {}", report.program_totals.total_statements);
-    println!("    Avg cyclomatic:    {:.1}", report.program_totals.avg_cyclomatic);
-    println!("    Max cyclomatic:    {}", report.program_totals.max_cyclomatic);
-    println!("    Avg cognitive:     {:.1}", report.program_totals.avg_cognitive);
-    println!("    Max cognitive:     {}", report.program_totals.max_cognitive);
+    println!("  {}", style::icon("─").repeat(40));
+    println!("  {} {}", style::icon("📋"), title);
+    println!("  {}", style::icon("─").repeat(40));
+    println!("    Functions:         {}", totals.total_functions);
+    println!("    Total LOC:         {}", totals.total_loc);
+    println!("    Comment lines:     {} ({:.0}%)", totals.total_comment_lines, totals.comment_density);
+    println!("    Blank lines:       {}", totals.total_blank_lines);
+    println!("    Total statements:  {}", totals.total_statements);
+    println!("    Avg cyclomatic:    {:.1}", totals.avg_cyclomatic);
+    println!("    Max cyclomatic:    {}", totals.max_cyclomatic);
+    println!("    Avg cognitive:     {:.1}", totals.avg_cognitive);
+    println!("    Max cognitive:     {}", totals.max_cognitive);
+    println!("    Halstead volume:   {:.1}", totals.total_halstead_volume);
+    println!("    Est. time:         {}", format_duration(totals.estimated_time_seconds));
+    println!("    Est. bugs:         {:.2}", totals.estimated_bugs);
     println!("    Overall rating:    {} {} ({})",
-        report.program_totals.overall_rating.emoji(),
-        report.program_totals.overall_rating,
-        report.program_totals.overall_rating.label(),
+        style::icon(totals.overall_rating.emoji()),
+        totals.overall_rating,
+        totals.overall_rating.label(),
     );
+}
 
-    println!("\n{}", "═".repeat(62));
+/// A Halstead time estimate in the largest unit that keeps it readable
+pub fn format_duration(seconds: f64) -> String {
+    if seconds < 60.0 {
+        format!("{:.0}s", seconds)
+    } else if seconds < 3600.0 {
+        format!("{:.1} min", seconds / 60.0)
+    } else {
+        format!("{:.1} h", seconds / 3600.0)
+    }
 }
 
 /// Generate a simple bar visualization for a complexity value
 fn complexity_bar(value: usize, max_width: usize) -> String {
     // Scale: each block = ~2.5 units, cap at max_width
     let filled = (value).min(max_width);
-    let bar_char = match value {
-        0..=5 => '▪',
-        6..=10 => '▪',
-        11..=20 => '▪',
-        _ => '▪',
-    };
     let color = match value {
-        0..=5 => "\x1b[32m",   // green
-        6..=10 => "\x1b[33m",  // yellow
-        11..=20 => "\x1b[33m", // orange-ish (yellow)
-        _ => "\x1b[31m",       // red
+        0..=5 => Color::Green,
+        6..=20 => Color::Yellow,
+        _ => Color::Red,
     };
-    let reset = "\x1b[0m";
-    format!("{}{}{}{}", color, std::iter::repeat(bar_char).take(filled).collect::<String>(), reset,
+    format!("{}{}", style::paint(&style::icon("▪").repeat(filled), color),
         if value > max_width { format!(" ({})", value) } else { String::new() })
 }
 
 /// Display warnings/suggestions for a function
 fn display_function_warnings(func: &FunctionMetrics) {
+    for warning in function_warnings(func) {
+        println!("    {}  {}", style::icon("⚠️"), warning);
+    }
+}
+
+/// Warnings/suggestions for a function whose metrics cross a threshold
+pub fn function_warnings(func: &FunctionMetrics) -> Vec<String> {
     let mut warnings: Vec<String> = Vec::new();
 
     if func.cyclomatic_complexity > 10 {
@@ -329,7 +543,7 @@ fn display_function_warnings(func: &FunctionMetrics) {
 
     if func.max_nesting_depth > 3 {
         warnings.push(format!(
-            "Deep nesting (depth {}). Consider using early returns or extracting helper functions.",
+            "Deep nesting (depth {}). Consider using early returns (`minilang refactor early-return` can rewrite guard ifs) or extracting helper functions.",
             func.max_nesting_depth,
         ));
     }
@@ -355,11 +569,7 @@ fn display_function_warnings(func: &FunctionMetrics) {
         ));
     }
 
-    if !warnings.is_empty() {
-        for warning in &warnings {
-            println!("    ⚠️  {}", warning);
-        }
-    }
+    warnings
 }
 
 // ==================== TESTS ====================
@@ -371,31 +581,38 @@ mod tests {
     use crate::ast::*;
 
     fn make_program(functions: Vec<Function>) -> Program {
-        Program { functions }
+        Program { functions, tests: Vec::new() }
     }
 
     fn make_function_with_name(name: &str, stmts: Vec<Statement>, span: Span) -> Function {
         Function {
             name: name.to_string(),
+            visibility: Visibility::Public,
+            is_pure: false,
+            attributes: vec![],
             params: vec![],
             return_type: None,
             body: Block {
                 statements: stmts,
                 span: span.clone(),
+                id: NodeId::default(),
             },
             span,
+            id: NodeId::default(),
         }
     }
 
     fn make_let(name: &str) -> Statement {
         Statement::Let(LetStmt {
             name: name.to_string(),
-            typ: Type::Int,
+            typ: Some(Type::Int),
             value: Some(Expression::Literal(LiteralExpr {
                 value: Literal::Integer(0),
                 span: Span::default(),
+                id: NodeId::default(),
             })),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -403,6 +620,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::Boolean(val),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -412,9 +630,11 @@ mod tests {
             then_block: Block {
                 statements: then_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             else_block: None,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -424,8 +644,10 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -533,6 +755,24 @@ mod tests {
         assert_eq!(f.rating, Rating::A);
     }
 
+    #[test]
+    fn test_comment_lines_roll_up() {
+        let source = "# Entry point\nfunc main() {\n\n    let x: int = 0; # zero\n}\n";
+        let start = source.find("func").unwrap();
+        let func = make_function_with_name("main",
+            vec![make_let("x")],
+            Span::new(start, source.len() - 1),
+        );
+        let report = analyze_program(&make_program(vec![func]), source);
+
+        let f = &report.functions[0];
+        assert_eq!((f.loc, f.comment_lines, f.blank_lines), (3, 2, 1));
+        assert_eq!(f.comment_density, 40.0);
+        let totals = &report.program_totals;
+        assert_eq!((totals.total_comment_lines, totals.total_blank_lines), (2, 1));
+        assert_eq!(totals.comment_density, 40.0);
+    }
+
     #[test]
     fn test_analyze_multiple_functions() {
         let source = "func a() {\n}\nfunc b() {\n}\n";
@@ -603,4 +843,81 @@ mod tests {
         assert_eq!(Rating::D.emoji(), "🔴");
         assert_eq!(Rating::F.emoji(), "💀");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_retain_functions() {
+        let source = "func a() {\n}\nfunc b() {\n}\n";
+        let func_a = make_function_with_name("a", vec![], Span::new(0, 14));
+        let func_b = make_function_with_name("b",
+            (0..12).map(|i| make_if(vec![make_let(&format!("x{}", i))])).collect(),
+            Span::new(15, source.len()),
+        );
+        let mut report = analyze_program(&make_program(vec![func_a, func_b]), source);
+
+        let unknown = report.retain_functions(&["a".to_string(), "missing".to_string()]);
+        assert_eq!(unknown, vec!["missing".to_string()]);
+        assert_eq!(report.functions.len(), 1);
+        assert_eq!(report.functions[0].name, "a");
+        // Totals only cover what is left, so b's rating no longer counts
+        assert_eq!(report.program_totals.total_functions, 1);
+        assert_eq!(report.program_totals.max_cyclomatic, 1);
+        assert_eq!(report.program_totals.overall_rating, Rating::A);
+    }
+
+    #[test]
+    fn test_project_report_aggregates_files() {
+        let simple_source = "func a() {\n}\n";
+        let simple = analyze_program(
+            &make_program(vec![make_function_with_name("a", vec![], Span::new(0, simple_source.len()))]),
+            simple_source,
+        );
+        let busy_source = "func b() {\n}\nfunc c() {\n}\n";
+        let busy = analyze_program(&make_program(vec![
+            make_function_with_name("b", vec![make_if(vec![]), make_if(vec![])], Span::new(0, 12)),
+            make_function_with_name("c",
+                (0..12).map(|i| make_if(vec![make_let(&format!("x{}", i))])).collect(),
+                Span::new(13, busy_source.len()),
+            ),
+        ]), busy_source);
+
+        let project = ProjectReport::new(vec![
+            FileReport { path: "simple.mini".to_string(), report: simple },
+            FileReport { path: "busy.mini".to_string(), report: busy },
+        ], 2);
+
+        assert_eq!(project.files.len(), 2);
+        assert_eq!(project.project_totals.total_functions, 3);
+        assert_eq!(project.project_totals.max_cyclomatic, 13);
+        assert_eq!(project.project_totals.overall_rating, Rating::C);
+
+        // Worst first, cut to the requested count
+        let worst: Vec<(&str, &str)> = project.worst_functions.iter()
+            .map(|ranked| (ranked.file.as_str(), ranked.function.name.as_str()))
+            .collect();
+        assert_eq!(worst, vec![("busy.mini", "c"), ("busy.mini", "b")]);
+    }
+
+    #[test]
+    fn test_halstead_estimates_roll_up() {
+        let source = "func a() {\n}\nfunc b() {\n}\n";
+        let report = analyze_program(&make_program(vec![
+            make_function_with_name("a", vec![make_let("x")], Span::new(0, 12)),
+            make_function_with_name("b", vec![make_let("y"), make_let("z")], Span::new(13, source.len())),
+        ]), source);
+
+        let totals = &report.program_totals;
+        let sum = |metric: fn(&HalsteadMetrics) -> f64| report.functions.iter().map(|f| metric(&f.halstead)).sum::<f64>();
+        assert!(totals.estimated_bugs > 0.0);
+        assert!((totals.estimated_bugs - sum(|h| h.delivered_bugs)).abs() < 1e-9);
+        assert!((totals.estimated_time_seconds - sum(|h| h.time_seconds)).abs() < 1e-9);
+        assert!((totals.total_halstead_volume - sum(|h| h.volume)).abs() < 1e-9);
+        assert!((totals.total_halstead_effort - sum(|h| h.effort)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(12.4), "12s");
+        assert_eq!(format_duration(90.0), "1.5 min");
+        assert_eq!(format_duration(5400.0), "1.5 h");
+    }
+}
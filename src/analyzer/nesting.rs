@@ -4,6 +4,7 @@
 // Each if/while/do-while/for/block increases depth by 1.
 
 use crate::ast::*;
+use crate::control_flow::Loop;
 
 /// Calculate the maximum nesting depth of a function body
 pub fn calculate(func: &Function) -> usize {
@@ -43,28 +44,30 @@ fn max_depth_in_statement(stmt: &Statement, current_depth: usize) -> usize {
 
             max
         }
-        Statement::While(while_stmt) => {
+        Statement::While(_) | Statement::DoWhile(_) | Statement::For(_) => {
+            let Some(lp) = Loop::of(stmt) else { return current_depth };
             let nested_depth = current_depth + 1;
-            let body_max = max_depth_in_block(&while_stmt.body, nested_depth);
+            let body_max = max_depth_in_block(lp.body, nested_depth);
             nested_depth.max(body_max)
         }
-        Statement::DoWhile(do_while_stmt) => {
+        Statement::Block(block) => {
             let nested_depth = current_depth + 1;
-            let body_max = max_depth_in_block(&do_while_stmt.body, nested_depth);
-            nested_depth.max(body_max)
+            let block_max = max_depth_in_block(block, nested_depth);
+            nested_depth.max(block_max)
         }
-        Statement::For(for_stmt) => {
+        Statement::Defer(defer_stmt) => {
             let nested_depth = current_depth + 1;
-            let body_max = max_depth_in_block(&for_stmt.body, nested_depth);
+            let body_max = max_depth_in_block(&defer_stmt.body, nested_depth);
             nested_depth.max(body_max)
         }
-        Statement::Block(block) => {
+        Statement::Spawn(spawn_stmt) => {
             let nested_depth = current_depth + 1;
-            let block_max = max_depth_in_block(block, nested_depth);
-            nested_depth.max(block_max)
+            let body_max = max_depth_in_block(&spawn_stmt.body, nested_depth);
+            nested_depth.max(body_max)
         }
         // Non-nesting statements
         Statement::Let(_)
+        | Statement::LetTuple(_)
         | Statement::Const(_)
         | Statement::Display(_)
         | Statement::Return(_)
@@ -85,25 +88,32 @@ mod tests {
     fn make_function(stmts: Vec<Statement>) -> Function {
         Function {
             name: "test".to_string(),
+            visibility: Visibility::Public,
+            is_pure: false,
+            attributes: vec![],
             params: vec![],
             return_type: None,
             body: Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         }
     }
 
     fn make_let(name: &str) -> Statement {
         Statement::Let(LetStmt {
             name: name.to_string(),
-            typ: Type::Int,
+            typ: Some(Type::Int),
             value: Some(Expression::Literal(LiteralExpr {
                 value: Literal::Integer(0),
                 span: Span::default(),
+                id: NodeId::default(),
             })),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -112,8 +122,11 @@ mod tests {
             expressions: vec![Expression::Literal(LiteralExpr {
                 value: Literal::String("hello".to_string()),
                 span: Span::default(),
+                id: NodeId::default(),
             })],
+            newline: true,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -121,6 +134,7 @@ mod tests {
         Expression::Literal(LiteralExpr {
             value: Literal::Boolean(val),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -130,12 +144,15 @@ mod tests {
             then_block: Block {
                 statements: then_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             else_block: else_stmts.map(|stmts| Block {
                 statements: stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             }),
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -145,8 +162,10 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -156,8 +175,10 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
@@ -169,8 +190,11 @@ mod tests {
             body: Block {
                 statements: body_stmts,
                 span: Span::default(),
+                id: NodeId::default(),
             },
             span: Span::default(),
+            id: NodeId::default(),
+            parallel: false,
         })
     }
 
@@ -178,6 +202,7 @@ mod tests {
         Statement::Block(Block {
             statements: stmts,
             span: Span::default(),
+            id: NodeId::default(),
         })
     }
 
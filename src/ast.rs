@@ -1,5 +1,7 @@
 // src/ast.rs - Abstract Syntax Tree definitions with visualization
 
+pub mod pretty;
+
 use std::fmt;
 use serde::{Serialize, Deserialize};
 
@@ -7,16 +9,67 @@ use serde::{Serialize, Deserialize};
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Program {
     pub functions: Vec<Function>,
+    /// `test "name" { ... }` blocks, which only `minilang test` compiles
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tests: Vec<TestBlock>,
+}
+
+/// A test block: `test "name" { ... }`, run with `minilang test`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestBlock {
+    pub name: String,
+    pub body: Block,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+/// Name of the function a test block becomes with `Program::include_tests`.
+/// It is not an identifier, so it cannot clash with the program's functions.
+pub fn test_function_name(test: &str) -> String {
+    format!("test \"{}\"", test)
 }
 
 /// A function definition
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Function {
     pub name: String,
+    pub visibility: Visibility,
+    pub is_pure: bool,
+    pub attributes: Vec<Attribute>,
     pub params: Vec<Parameter>,
     pub return_type: Option<Type>,
     pub body: Block,
     pub span: Span,
+    pub id: NodeId,
+}
+
+/// Whether other modules of a multi-module build can call a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Visibility {
+    #[default]
+    Public,
+    /// Only callable from its own module; `static` in the generated C
+    Private,
+}
+
+/// An attribute written before a function, like `@inline` or `@deprecated("use g")`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attribute {
+    pub name: String,
+    pub argument: Option<String>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+impl Function {
+    /// Look up an attribute by name
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|attr| attr.name == name)
+    }
+    
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attribute(name).is_some()
+    }
 }
 
 /// A function parameter
@@ -25,6 +78,7 @@ pub struct Parameter {
     pub name: String,
     pub typ: Type,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// A block of statements
@@ -32,6 +86,7 @@ pub struct Parameter {
 pub struct Block {
     pub statements: Vec<Statement>,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// All possible statement types in MiniLang
@@ -49,6 +104,31 @@ pub enum Statement {
     Break(BreakStmt),
     Continue(ContinueStmt),
     Const(ConstStmt),
+    Defer(DeferStmt),
+    LetTuple(LetTupleStmt),
+    Spawn(SpawnStmt),
+}
+
+impl Statement {
+    pub fn span(&self) -> &Span {
+        match self {
+            Statement::Const(s) => &s.span,
+            Statement::Let(s) => &s.span,
+            Statement::LetTuple(s) => &s.span,
+            Statement::Display(s) => &s.span,
+            Statement::If(s) => &s.span,
+            Statement::While(s) => &s.span,
+            Statement::DoWhile(s) => &s.span,
+            Statement::For(s) => &s.span,
+            Statement::Return(s) => &s.span,
+            Statement::Expression(s) => &s.span,
+            Statement::Block(s) => &s.span,
+            Statement::Break(s) => &s.span,
+            Statement::Continue(s) => &s.span,
+            Statement::Defer(s) => &s.span,
+            Statement::Spawn(s) => &s.span,
+        }
+    }
 }
 
 /// Constant declaration: const PI: float = 3.14159;
@@ -58,34 +138,69 @@ pub struct ConstStmt {
     pub typ: Type,
     pub value: Expression,  // Constants MUST have a value
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Break statement: break;
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BreakStmt {
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Continue statement: continue;
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContinueStmt {
     pub span: Span,
+    pub id: NodeId,
+}
+
+/// Defer statement: defer { ... } (body runs when the enclosing block exits)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeferStmt {
+    pub body: Block,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+/// Spawn statement: spawn { ... } (body runs on its own thread, joined when
+/// the enclosing block exits)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpawnStmt {
+    pub body: Block,
+    pub span: Span,
+    pub id: NodeId,
 }
 
-/// Variable declaration: let x: int = 42;
+/// Tuple destructuring: let (q, r) = divmod(7, 2);
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LetTupleStmt {
+    pub names: Vec<String>,  // "_" discards that element
+    pub value: Expression,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+/// Variable declaration: let x: int = 42; or let x = 42;
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LetStmt {
     pub name: String,
-    pub typ: Type,
+    /// None for `let x = value;`, where the type comes from the initializer
+    pub typ: Option<Type>,
     pub value: Option<Expression>,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Display statement: display "Hello", x;
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DisplayStmt {
     pub expressions: Vec<Expression>,
+    /// Whether a line break follows the output. `display` and `displayln`
+    /// end the line, `display_raw` does not.
+    pub newline: bool,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// If statement: if condition { ... } else { ... }
@@ -95,6 +210,7 @@ pub struct IfStmt {
     pub then_block: Block,
     pub else_block: Option<Block>,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// While loop: while condition { ... }
@@ -103,6 +219,7 @@ pub struct WhileStmt {
     pub condition: Expression,
     pub body: Block,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Do-while loop: do { ... } while condition;
@@ -111,6 +228,7 @@ pub struct DoWhileStmt {
     pub body: Block,
     pub condition: Expression,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// For loop: for i = 0; i < 10; i = i + 1 { ... }
@@ -120,7 +238,12 @@ pub struct ForStmt {
     pub condition: Option<Expression>,
     pub update: Option<Expression>,
     pub body: Block,
+    /// `parallel for i in start..end { ... }`, parsed as the loop counting
+    /// `i` from `start` up to `end`. Its iterations run on several threads.
+    #[serde(default)]
+    pub parallel: bool,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Return statement: send value;
@@ -128,6 +251,7 @@ pub struct ForStmt {
 pub struct ReturnStmt {
     pub value: Option<Expression>,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Expression statement: x + 1;
@@ -135,6 +259,7 @@ pub struct ReturnStmt {
 pub struct ExprStmt {
     pub expression: Expression,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// All possible expression types
@@ -153,12 +278,14 @@ pub enum Expression {
 pub struct LiteralExpr {
     pub value: Literal,
     pub span: Span,
+    pub id: NodeId,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IdentifierExpr {
     pub name: String,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Literal values
@@ -169,6 +296,7 @@ pub enum Literal {
     String(String),
     Boolean(bool),
     Array(Vec<Expression>),
+    Tuple(Vec<Expression>),
     InterpolatedString(Vec<StringPart>),
 }
 
@@ -185,7 +313,9 @@ pub struct BinaryExpr {
     pub op: BinaryOp,
     pub right: Box<Expression>,
     pub span: Span,
+    pub id: NodeId,
     pub optimization_hint: Option<OptimizationHint>,
+    pub parenthesized: bool,  // written as (left op right); span includes the parens
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -224,6 +354,8 @@ pub struct UnaryExpr {
     pub op: UnaryOp,
     pub operand: Box<Expression>,
     pub span: Span,
+    pub id: NodeId,
+    pub parenthesized: bool,  // written as (op operand); span includes the parens
 }
 
 /// Unary operators
@@ -233,12 +365,65 @@ pub enum UnaryOp {
     Negate,
 }
 
+/// The call the optimizer wraps around a string that never leaves its
+/// function, so the C can build it in a stack buffer: `__stack(format(...))`
+pub const STACK_STRING: &str = "__stack";
+
+/// Builtins that do one int operation and report whether it overflowed:
+/// `add_checked(a, b)` yields `(value, overflowed)`, the value wrapped around
+pub const CHECKED_ARITHMETIC: [(&str, BinaryOp); 3] = [
+    ("add_checked", BinaryOp::Add),
+    ("sub_checked", BinaryOp::Subtract),
+    ("mul_checked", BinaryOp::Multiply),
+];
+
+/// Builtins on channels: `channel(capacity)` makes one, `chan_send(ch, value)`
+/// waits for room and `chan_receive(ch)` waits for a value
+pub const CHANNEL_BUILTINS: [&str; 3] = ["channel", "chan_send", "chan_receive"];
+
+/// Builtins on atomics: `atomic(value)` makes one, `atomic_add(a, amount)`
+/// adds to it and gives the new value, and `atomic_get(a)` reads it
+pub const ATOMIC_BUILTINS: [&str; 3] = ["atomic", "atomic_add", "atomic_get"];
+
+/// Builtins on the clock: `sleep_ms(ms)` waits and `now_ms()` gives the
+/// milliseconds since the program started
+pub const CLOCK_BUILTINS: [&str; 2] = ["sleep_ms", "now_ms"];
+
+/// Builtins on canvases: `canvas(width, height)` makes one full of spaces,
+/// `canvas_set(c, x, y, text)` puts one character in a cell, counting from
+/// the top left, `canvas_fill(c, text)` puts it in every cell and
+/// `canvas_render(c)` displays the rows
+pub const CANVAS_BUILTINS: [&str; 4] = ["canvas", "canvas_set", "canvas_fill", "canvas_render"];
+
+/// Builtins on json values: `json_parse(text)` reads one, `json_get(j, key)`,
+/// `json_at(j, index)` and the rest look inside, `json_set(j, key, value)`
+/// and `json_push(j, value)` change one in place and `json_stringify(j)`
+/// writes one back out
+pub const JSON_BUILTINS: [&str; 13] = [
+    "json_parse", "json_stringify", "json_type", "json_get", "json_has", "json_at", "json_len",
+    "json_int", "json_float", "json_bool", "json_string", "json_set", "json_push",
+];
+
+/// What the json builtin `name` gives; None for json_set and json_push,
+/// which change their json instead
+pub fn json_builtin_type(name: &str) -> Option<Type> {
+    match name {
+        "json_parse" | "json_get" | "json_at" => Some(Type::Json),
+        "json_stringify" | "json_type" | "json_string" => Some(Type::String),
+        "json_has" | "json_bool" => Some(Type::Bool),
+        "json_len" | "json_int" => Some(Type::Int),
+        "json_float" => Some(Type::Float),
+        _ => None,
+    }
+}
+
 /// Function call: func(args)
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CallExpr {
     pub function: String,
     pub args: Vec<Expression>,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Array indexing: arr[index]
@@ -247,6 +432,7 @@ pub struct IndexExpr {
     pub array: Box<Expression>,
     pub index: Box<Expression>,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Assignment: target = value
@@ -255,6 +441,7 @@ pub struct AssignExpr {
     pub target: String,
     pub value: Box<Expression>,
     pub span: Span,
+    pub id: NodeId,
 }
 
 /// Type annotations
@@ -265,8 +452,18 @@ pub enum Type {
     String,
     Bool,
     Array(Box<Type>, usize),  // Array type with size
+    Tuple(Vec<Type>),         // (int, bool) - always two or more elements
+    Channel,                  // chan - a queue of ints shared between threads
+    Atomic,                   // atomic - an int threads can update at once
+    Canvas,                   // canvas - a grid of characters to draw on
+    Json,                     // json - a JSON value, changed in place
 }
 
+/// Identifies an AST node. Parsed programs number every node uniquely;
+/// `NodeId::default()` marks a node that has not been numbered yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct NodeId(pub u32);
+
 /// Source location tracking
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct Span {
@@ -274,6 +471,24 @@ pub struct Span {
     pub end: usize,
 }
 
+impl Type {
+    /// Whether this type is `typ` or holds one in an array or tuple
+    pub fn contains(&self, typ: &Type) -> bool {
+        self == typ || match self {
+            Type::Array(element, _) => element.contains(typ),
+            Type::Tuple(elements) => elements.iter().any(|element| element.contains(typ)),
+            _ => false,
+        }
+    }
+
+    /// Whether a value of this type holds a channel, an atomic, a canvas or
+    /// a json value: handles to something a builtin made, which copies share
+    /// rather than duplicate
+    pub fn contains_handle(&self) -> bool {
+        [Type::Channel, Type::Atomic, Type::Canvas, Type::Json].iter().any(|handle| self.contains(handle))
+    }
+}
+
 impl Span {
     pub fn new(start: usize, end: usize) -> Self {
         Self { start, end }
@@ -287,12 +502,27 @@ impl fmt::Display for Program {
         for function in &self.functions {
             writeln!(f, "{}", function)?;
         }
+        for test in &self.tests {
+            writeln!(f, "test {:?} {{ ... }}", test.name)?;
+        }
         Ok(())
     }
 }
 
 impl fmt::Display for Function {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for attr in &self.attributes {
+            match &attr.argument {
+                Some(argument) => write!(f, "@{}({:?}) ", attr.name, argument)?,
+                None => write!(f, "@{} ", attr.name)?,
+            }
+        }
+        if self.visibility == Visibility::Private {
+            write!(f, "private ")?;
+        }
+        if self.is_pure {
+            write!(f, "pure ")?;
+        }
         write!(f, "func {}(", self.name)?;
         for (i, param) in self.params.iter().enumerate() {
             if i > 0 {
@@ -308,185 +538,619 @@ impl fmt::Display for Function {
     }
 }
 
-// ==================== TREE VISUALIZATION ====================
+/// Renders the type as written in MiniLang source, e.g. `int[3]`
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::Array(element, size) => write!(f, "{}[{}]", element, size),
+            Type::Tuple(elements) => {
+                write!(f, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, ")")
+            }
+            Type::Channel => write!(f, "chan"),
+            Type::Atomic => write!(f, "atomic"),
+            Type::Canvas => write!(f, "canvas"),
+            Type::Json => write!(f, "json"),
+        }
+    }
+}
 
-impl Program {
-    /// Display the AST as a nice tree structure
-    pub fn display_tree(&self) {
-        println!("\n{}", "═".repeat(60));
-        println!("🌳 Abstract Syntax Tree (Visualization)");
-        println!("{}", "═".repeat(60));
-        
-        if self.functions.is_empty() {
-            println!("\n(empty program)");
+impl BinaryOp {
+    /// Operator as written in MiniLang source
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::Greater => ">",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
         }
-        
-        for (i, func) in self.functions.iter().enumerate() {
-            let is_last_func = i == self.functions.len() - 1;
-            let prefix = if is_last_func { "└──" } else { "├──" };
-            
-            println!("\n{} 📦 Function: {}", prefix, func.name);
-            self.display_function(func, if is_last_func { "    " } else { "│   " });
+    }
+    
+    /// Binding strength, matching the parser's precedence levels
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Equal | BinaryOp::NotEqual => 3,
+            BinaryOp::Less | BinaryOp::Greater | BinaryOp::LessEqual | BinaryOp::GreaterEqual => 4,
+            BinaryOp::Add | BinaryOp::Subtract => 5,
+            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => 6,
         }
-        
-        println!("\n{}", "═".repeat(60));
     }
+}
 
-    fn display_function(&self, func: &Function, indent: &str) {
-        // Parameters
-        if !func.params.is_empty() {
-            println!("{}├── 📋 Parameters: {}", indent, func.params.len());
-            for (i, param) in func.params.iter().enumerate() {
-                let is_last = i == func.params.len() - 1;
-                let p_prefix = if is_last { "└──" } else { "├──" };
-                println!("{}│   {} {} : {:?}", indent, p_prefix, param.name, param.typ);
-            }
+impl Expression {
+    const UNARY_PRECEDENCE: u8 = 7;
+    const PRIMARY_PRECEDENCE: u8 = 8;
+    
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Binary(binary) => binary.op.precedence(),
+            Expression::Unary(_) => Self::UNARY_PRECEDENCE,
+            Expression::Assign(_) => 0,
+            _ => Self::PRIMARY_PRECEDENCE,
+        }
+    }
+    
+    fn is_parenthesized(&self) -> bool {
+        match self {
+            Expression::Binary(binary) => binary.parenthesized,
+            Expression::Unary(unary) => unary.parenthesized,
+            _ => false,
+        }
+    }
+    
+    /// Write a sub-expression, adding parens the tree shape needs but the source didn't have
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        if !self.is_parenthesized() && self.precedence() < min_precedence {
+            write!(f, "({})", self)
         } else {
-            println!("{}├── 📋 Parameters: none", indent);
+            write!(f, "{}", self)
         }
-        
-        // Return type
-        if let Some(ret_type) = &func.return_type {
-            println!("{}├── 🔄 Return Type: {:?}", indent, ret_type);
+    }
+}
+
+fn write_string_contents(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    let escaped = text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    write!(f, "{}", escaped)
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, items: &[Expression]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// Renders MiniLang source, keeping the user's parentheses
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_parenthesized() {
+            write!(f, "(")?;
         }
         
-        // Body
-        let body_prefix = if func.return_type.is_some() { "└──" } else { "└──" };
-        println!("{}{} 📝 Body: {} statement(s)", indent, body_prefix, func.body.statements.len());
-        
-        for (i, stmt) in func.body.statements.iter().enumerate() {
-            let is_last = i == func.body.statements.len() - 1;
-            let s_prefix = if is_last { "└──" } else { "├──" };
-            let next_indent = if is_last { "    " } else { "│   " };
-            self.display_statement(
-                stmt, 
-                &format!("{}    {}", indent, s_prefix), 
-                &format!("{}    {}", indent, next_indent)
-            );
-        }
-    }
-
-    fn display_statement(&self, stmt: &Statement, prefix: &str, indent: &str) {
-        match stmt {
-            Statement::Const(const_stmt) => {
-                println!("{} const {} : {:?} =", prefix, const_stmt.name, const_stmt.typ);
-                self.display_expression(&const_stmt.value, &format!("{}└──", indent));
-            }
-            Statement::Let(let_stmt) => {
-                if let Some(value) = &let_stmt.value {
-                    println!("{} let {} : {:?} =", prefix, let_stmt.name, let_stmt.typ);
-                    self.display_expression(value, &format!("{}└──", indent));
-                } else {
-                    println!("{} let {} : {:?}", prefix, let_stmt.name, let_stmt.typ);
+        match self {
+            Expression::Literal(lit_expr) => match &lit_expr.value {
+                Literal::Integer(n) => write!(f, "{}", n)?,
+                Literal::Float(x) => write!(f, "{:?}", x)?,
+                Literal::Boolean(b) => write!(f, "{}", b)?,
+                Literal::String(s) => {
+                    write!(f, "\"")?;
+                    write_string_contents(f, s)?;
+                    write!(f, "\"")?;
+                }
+                Literal::InterpolatedString(parts) => {
+                    write!(f, "\"")?;
+                    for part in parts {
+                        match part {
+                            StringPart::Text(text) => write_string_contents(f, text)?,
+                            StringPart::Expression(expr) => write!(f, "{{{}}}", expr)?,
+                        }
+                    }
+                    write!(f, "\"")?;
                 }
+                Literal::Array(elements) => {
+                    write!(f, "[")?;
+                    write_list(f, elements)?;
+                    write!(f, "]")?;
+                }
+                Literal::Tuple(elements) => {
+                    write!(f, "(")?;
+                    write_list(f, elements)?;
+                    write!(f, ")")?;
+                }
+            },
+            Expression::Identifier(id_expr) => write!(f, "{}", id_expr.name)?,
+            Expression::Binary(binary) => {
+                // Operators are left-associative, so an equal-precedence right operand needs parens
+                let precedence = binary.op.precedence();
+                binary.left.fmt_operand(f, precedence)?;
+                write!(f, " {} ", binary.op.symbol())?;
+                binary.right.fmt_operand(f, precedence + 1)?;
             }
-            Statement::Display(display_stmt) => {
-                println!("{} display ({} expression(s))", prefix, display_stmt.expressions.len());
-                for (i, expr) in display_stmt.expressions.iter().enumerate() {
-                    let is_last = i == display_stmt.expressions.len() - 1;
-                    let e_prefix = if is_last { "└──" } else { "├──" };
-                    self.display_expression(expr, &format!("{}{}", indent, e_prefix));
+            Expression::Unary(unary) => {
+                match unary.op {
+                    UnaryOp::Not => write!(f, "NOT ")?,
+                    UnaryOp::Negate => write!(f, "-")?,
                 }
+                unary.operand.fmt_operand(f, Self::UNARY_PRECEDENCE)?;
             }
-            Statement::If(if_stmt) => {
-                println!("{} if", prefix);
-                println!("{}├── condition:", indent);
-                self.display_expression(&if_stmt.condition, &format!("{}│   └──", indent));
-                
-                let then_count = if_stmt.then_block.statements.len();
-                if if_stmt.else_block.is_some() {
-                    println!("{}├── then: {} statement(s)", indent, then_count);
-                } else {
-                    println!("{}└── then: {} statement(s)", indent, then_count);
+            Expression::Call(call) => {
+                write!(f, "{}(", call.function)?;
+                write_list(f, &call.args)?;
+                write!(f, ")")?;
+            }
+            Expression::Index(index) => {
+                index.array.fmt_operand(f, Self::PRIMARY_PRECEDENCE)?;
+                write!(f, "[{}]", index.index)?;
+            }
+            Expression::Assign(assign) => match (assign.target.strip_prefix("__ARRAY_INDEX__:"), &*assign.value) {
+                // arr[i] = v is encoded as Assign { value: Binary(arr[i], Equal, v) }
+                (Some(_), Expression::Binary(binary)) => write!(f, "{} = {}", binary.left, binary.right)?,
+                _ => write!(f, "{} = {}", assign.target, assign.value)?,
+            },
+        }
+        
+        if self.is_parenthesized() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+// ==================== ENTRY POINTS ====================
+
+impl Program {
+    /// Make `name` the entry point: replace any `main` with one that calls it
+    /// and, if it returns an int, exits with that value. The entry must take
+    /// no parameters, as `TypeChecker::with_entry` checks. Returns false, and
+    /// leaves the program alone, if there is no such function.
+    pub fn set_entry(&mut self, name: &str) -> bool {
+        let Some(entry) = self.functions.iter().find(|f| f.name == name) else {
+            return false;
+        };
+        if name == "main" {
+            return true;
+        }
+
+        // The synthesized nodes point at the entry function's definition
+        let span = entry.span.clone();
+        let call = Expression::Call(CallExpr {
+            function: name.to_string(),
+            args: Vec::new(),
+            span: span.clone(),
+            id: NodeId::default(),
+        });
+        let (statement, return_type) = if entry.return_type == Some(Type::Int) {
+            let statement = Statement::Return(ReturnStmt { value: Some(call), span: span.clone(), id: NodeId::default() });
+            (statement, Some(Type::Int))
+        } else {
+            let statement = Statement::Expression(ExprStmt { expression: call, span: span.clone(), id: NodeId::default() });
+            (statement, None)
+        };
+
+        self.functions.retain(|f| f.name != "main");
+        self.functions.push(Function {
+            name: "main".to_string(),
+            visibility: Visibility::Public,
+            is_pure: false,
+            attributes: Vec::new(),
+            params: Vec::new(),
+            return_type,
+            body: Block { statements: vec![statement], span: span.clone(), id: NodeId::default() },
+            span,
+            id: NodeId::default(),
+        });
+        self.number_nodes();
+        true
+    }
+    
+    /// Turn every test block into a function of no parameters, named by
+    /// `test_function_name`, so the pipeline can check and run it like any
+    /// other function. Normal compilation leaves the tests out.
+    pub fn include_tests(&mut self) {
+        for test in std::mem::take(&mut self.tests) {
+            self.functions.push(Function {
+                name: test_function_name(&test.name),
+                visibility: Visibility::Public,
+                is_pure: false,
+                attributes: Vec::new(),
+                params: Vec::new(),
+                return_type: None,
+                body: test.body,
+                span: test.span,
+                id: test.id,
+            });
+        }
+    }
+}
+
+// ==================== NODE TRAVERSAL ====================
+
+impl Program {
+    /// Give every unnumbered node a fresh id. Existing ids are kept, so
+    /// passes that add nodes can renumber without invalidating maps keyed by id.
+    /// Copies of a subtree keep the original ids on their first occurrence only.
+    pub fn number_nodes(&mut self) {
+        let mut next = 0;
+        self.visit_nodes_mut(&mut |id, _| next = next.max(id.0));
+        let mut seen = std::collections::HashSet::new();
+        self.visit_nodes_mut(&mut |id, _| {
+            if *id == NodeId::default() || !seen.insert(*id) {
+                next += 1;
+                *id = NodeId(next);
+                seen.insert(*id);
+            }
+        });
+    }
+    
+    /// Spans that can't have come from parsing `source_len` bytes of source:
+    /// empty ones (as `Span::default()` is) and ones running past the end.
+    /// The parser asserts there are none in debug builds.
+    pub fn invalid_spans(&self, source_len: usize) -> Vec<Span> {
+        let mut invalid = Vec::new();
+        // The visitor hands out &mut, so walk a copy
+        self.clone().visit_nodes_mut(&mut |_, span| {
+            if span.start >= span.end || span.end > source_len {
+                invalid.push(span.clone());
+            }
+        });
+        invalid
+    }
+    
+    /// Visit the id and span of every node, parents before children
+    pub fn visit_nodes_mut(&mut self, f: &mut impl FnMut(&mut NodeId, &mut Span)) {
+        for function in &mut self.functions {
+            f(&mut function.id, &mut function.span);
+            for attr in &mut function.attributes {
+                f(&mut attr.id, &mut attr.span);
+            }
+            for param in &mut function.params {
+                f(&mut param.id, &mut param.span);
+            }
+            visit_block(&mut function.body, f);
+        }
+        for test in &mut self.tests {
+            f(&mut test.id, &mut test.span);
+            visit_block(&mut test.body, f);
+        }
+    }
+}
+
+impl Expression {
+    pub fn span(&self) -> &Span {
+        match self {
+            Expression::Literal(e) => &e.span,
+            Expression::Identifier(e) => &e.span,
+            Expression::Binary(e) => &e.span,
+            Expression::Unary(e) => &e.span,
+            Expression::Call(e) => &e.span,
+            Expression::Index(e) => &e.span,
+            Expression::Assign(e) => &e.span,
+        }
+    }
+    
+    pub fn id(&self) -> NodeId {
+        match self {
+            Expression::Literal(e) => e.id,
+            Expression::Identifier(e) => e.id,
+            Expression::Binary(e) => e.id,
+            Expression::Unary(e) => e.id,
+            Expression::Call(e) => e.id,
+            Expression::Index(e) => e.id,
+            Expression::Assign(e) => e.id,
+        }
+    }
+    
+    /// The expression as written in `source`, which optimized code may no
+    /// longer look like, or rendered from the tree without a source
+    pub fn source_text(&self, source: Option<&str>) -> String {
+        let span = self.span();
+        source.and_then(|source| source.get(span.start..span.end))
+            .map(str::to_string)
+            .unwrap_or_else(|| self.to_string())
+    }
+    
+    /// Visit the id and span of this expression and everything inside it
+    pub fn visit_nodes_mut(&mut self, f: &mut impl FnMut(&mut NodeId, &mut Span)) {
+        match self {
+            Expression::Literal(lit) => {
+                f(&mut lit.id, &mut lit.span);
+                match &mut lit.value {
+                    Literal::Array(elements) | Literal::Tuple(elements) => {
+                        for element in elements {
+                            element.visit_nodes_mut(f);
+                        }
+                    }
+                    Literal::InterpolatedString(parts) => {
+                        for part in parts {
+                            if let StringPart::Expression(expr) = part {
+                                expr.visit_nodes_mut(f);
+                            }
+                        }
+                    }
+                    _ => {}
                 }
-                
-                if let Some(else_block) = &if_stmt.else_block {
-                    println!("{}└── else: {} statement(s)", indent, else_block.statements.len());
+            }
+            Expression::Identifier(id_expr) => f(&mut id_expr.id, &mut id_expr.span),
+            Expression::Binary(binary) => {
+                f(&mut binary.id, &mut binary.span);
+                binary.left.visit_nodes_mut(f);
+                binary.right.visit_nodes_mut(f);
+            }
+            Expression::Unary(unary) => {
+                f(&mut unary.id, &mut unary.span);
+                unary.operand.visit_nodes_mut(f);
+            }
+            Expression::Call(call) => {
+                f(&mut call.id, &mut call.span);
+                for arg in &mut call.args {
+                    arg.visit_nodes_mut(f);
                 }
             }
-            Statement::While(while_stmt) => {
-                println!("{} while", prefix);
-                println!("{}├── condition:", indent);
-                self.display_expression(&while_stmt.condition, &format!("{}│   └──", indent));
-                println!("{}└── body: {} statement(s)", indent, while_stmt.body.statements.len());
+            Expression::Index(index) => {
+                f(&mut index.id, &mut index.span);
+                index.array.visit_nodes_mut(f);
+                index.index.visit_nodes_mut(f);
+            }
+            Expression::Assign(assign) => {
+                f(&mut assign.id, &mut assign.span);
+                assign.value.visit_nodes_mut(f);
+            }
+        }
+    }
+}
+
+fn visit_block(block: &mut Block, f: &mut impl FnMut(&mut NodeId, &mut Span)) {
+    f(&mut block.id, &mut block.span);
+    for stmt in &mut block.statements {
+        visit_statement(stmt, f);
+    }
+}
+
+fn visit_statement(stmt: &mut Statement, f: &mut impl FnMut(&mut NodeId, &mut Span)) {
+    match stmt {
+        Statement::Let(let_stmt) => {
+            f(&mut let_stmt.id, &mut let_stmt.span);
+            if let Some(value) = &mut let_stmt.value {
+                value.visit_nodes_mut(f);
+            }
+        }
+        Statement::LetTuple(let_tuple) => {
+            f(&mut let_tuple.id, &mut let_tuple.span);
+            let_tuple.value.visit_nodes_mut(f);
+        }
+        Statement::Const(const_stmt) => {
+            f(&mut const_stmt.id, &mut const_stmt.span);
+            const_stmt.value.visit_nodes_mut(f);
+        }
+        Statement::Display(display) => {
+            f(&mut display.id, &mut display.span);
+            for expr in &mut display.expressions {
+                expr.visit_nodes_mut(f);
+            }
+        }
+        Statement::If(if_stmt) => {
+            f(&mut if_stmt.id, &mut if_stmt.span);
+            if_stmt.condition.visit_nodes_mut(f);
+            visit_block(&mut if_stmt.then_block, f);
+            if let Some(else_block) = &mut if_stmt.else_block {
+                visit_block(else_block, f);
+            }
+        }
+        Statement::While(while_stmt) => {
+            f(&mut while_stmt.id, &mut while_stmt.span);
+            while_stmt.condition.visit_nodes_mut(f);
+            visit_block(&mut while_stmt.body, f);
+        }
+        Statement::DoWhile(do_while) => {
+            f(&mut do_while.id, &mut do_while.span);
+            visit_block(&mut do_while.body, f);
+            do_while.condition.visit_nodes_mut(f);
+        }
+        Statement::For(for_stmt) => {
+            f(&mut for_stmt.id, &mut for_stmt.span);
+            if let Some(init) = &mut for_stmt.init {
+                visit_statement(init, f);
+            }
+            if let Some(condition) = &mut for_stmt.condition {
+                condition.visit_nodes_mut(f);
+            }
+            if let Some(update) = &mut for_stmt.update {
+                update.visit_nodes_mut(f);
             }
-            Statement::For(for_stmt) => {
-                println!("{} for", prefix);
-                if for_stmt.init.is_some() {
-                    println!("{}├── init: present", indent);
+            visit_block(&mut for_stmt.body, f);
+        }
+        Statement::Return(ret) => {
+            f(&mut ret.id, &mut ret.span);
+            if let Some(value) = &mut ret.value {
+                value.visit_nodes_mut(f);
+            }
+        }
+        Statement::Expression(expr_stmt) => {
+            f(&mut expr_stmt.id, &mut expr_stmt.span);
+            expr_stmt.expression.visit_nodes_mut(f);
+        }
+        Statement::Block(block) => visit_block(block, f),
+        Statement::Break(break_stmt) => f(&mut break_stmt.id, &mut break_stmt.span),
+        Statement::Continue(continue_stmt) => f(&mut continue_stmt.id, &mut continue_stmt.span),
+        Statement::Defer(defer) => {
+            f(&mut defer.id, &mut defer.span);
+            visit_block(&mut defer.body, f);
+        }
+        Statement::Spawn(spawn) => {
+            f(&mut spawn.id, &mut spawn.span);
+            visit_block(&mut spawn.body, f);
+        }
+    }
+}
+
+// ==================== FREE VARIABLES ====================
+
+impl Block {
+    /// Variables the block uses without declaring them itself, in order of
+    /// first use: what a spawn block has to take from its function
+    pub fn free_variables(&self) -> Vec<String> {
+        let mut scan = FreeVariables { scopes: Vec::new(), found: Vec::new() };
+        scan.block(self);
+        scan.found
+    }
+}
+
+struct FreeVariables {
+    scopes: Vec<Vec<String>>,
+    found: Vec<String>,
+}
+
+impl FreeVariables {
+    fn block(&mut self, block: &Block) {
+        self.scopes.push(Vec::new());
+        for statement in &block.statements {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(name.to_string());
+        }
+    }
+
+    fn use_name(&mut self, name: &str) {
+        let declared = self.scopes.iter().any(|scope| scope.iter().any(|n| n == name));
+        if !declared && !self.found.iter().any(|n| n == name) {
+            self.found.push(name.to_string());
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let(stmt) => {
+                if let Some(value) = &stmt.value {
+                    self.expression(value);
                 }
-                if for_stmt.condition.is_some() {
-                    println!("{}├── condition: present", indent);
+                self.declare(&stmt.name);
+            }
+            Statement::Const(stmt) => {
+                self.expression(&stmt.value);
+                self.declare(&stmt.name);
+            }
+            Statement::LetTuple(stmt) => {
+                self.expression(&stmt.value);
+                for name in &stmt.names {
+                    self.declare(name);
                 }
-                if for_stmt.update.is_some() {
-                    println!("{}├── update: present", indent);
+            }
+            Statement::Display(stmt) => {
+                for expr in &stmt.expressions {
+                    self.expression(expr);
                 }
-                println!("{}└── body: {} statement(s)", indent, for_stmt.body.statements.len());
-            }
-            Statement::DoWhile(do_while) => {
-                println!("{} do-while", prefix);
-                println!("{}├── body: {} statement(s)", indent, do_while.body.statements.len());
-                println!("{}└── condition:", indent);
-                self.display_expression(&do_while.condition, &format!("{}    └──", indent));
-            }
-            Statement::Return(ret_stmt) => {
-                if let Some(value) = &ret_stmt.value {
-                    println!("{} send", prefix);
-                    self.display_expression(value, &format!("{}└──", indent));
-                } else {
-                    println!("{} send (void)", prefix);
+            }
+            Statement::If(stmt) => {
+                self.expression(&stmt.condition);
+                self.block(&stmt.then_block);
+                if let Some(else_block) = &stmt.else_block {
+                    self.block(else_block);
                 }
             }
-            Statement::Expression(expr_stmt) => {
-                println!("{} expression:", prefix);
-                self.display_expression(&expr_stmt.expression, &format!("{}└──", indent));
+            Statement::While(stmt) => {
+                self.expression(&stmt.condition);
+                self.block(&stmt.body);
             }
-            Statement::Block(block) => {
-                println!("{} block ({} statement(s))", prefix, block.statements.len());
+            Statement::DoWhile(stmt) => {
+                self.block(&stmt.body);
+                self.expression(&stmt.condition);
             }
-            Statement::Break(_) => {
-                println!("{} break", prefix);
+            Statement::For(stmt) => {
+                // The loop variable belongs to the loop
+                self.scopes.push(Vec::new());
+                if let Some(init) = &stmt.init {
+                    self.statement(init);
+                }
+                if let Some(condition) = &stmt.condition {
+                    self.expression(condition);
+                }
+                if let Some(update) = &stmt.update {
+                    self.expression(update);
+                }
+                self.block(&stmt.body);
+                self.scopes.pop();
             }
-            Statement::Continue(_) => {
-                println!("{} continue", prefix);
+            Statement::Return(stmt) => {
+                if let Some(value) = &stmt.value {
+                    self.expression(value);
+                }
             }
+            Statement::Expression(stmt) => self.expression(&stmt.expression),
+            Statement::Block(block) => self.block(block),
+            Statement::Defer(stmt) => self.block(&stmt.body),
+            Statement::Spawn(stmt) => self.block(&stmt.body),
+            Statement::Break(_) | Statement::Continue(_) => {}
         }
     }
 
-    fn display_expression(&self, expr: &Expression, prefix: &str) {
+    fn expression(&mut self, expr: &Expression) {
         match expr {
-            Expression::Literal(lit_expr) => {
-                match &lit_expr.value {
-                    Literal::Integer(n) => println!("{} {}", prefix, n),
-                    Literal::Float(f) => println!("{} {}", prefix, f),
-                    Literal::String(s) => println!("{} \"{}\"", prefix, s),
-                    Literal::Boolean(b) => println!("{} {}", prefix, b),
-                    Literal::Array(elements) => {
-                        println!("{} array [{}]", prefix, elements.len());
+            Expression::Identifier(id) => self.use_name(&id.name),
+            Expression::Literal(lit) => match &lit.value {
+                Literal::Array(elements) | Literal::Tuple(elements) => {
+                    for element in elements {
+                        self.expression(element);
                     }
-                    Literal::InterpolatedString(parts) => {
-                        println!("{} interpolated string ({} parts)", prefix, parts.len());
+                }
+                Literal::InterpolatedString(parts) => {
+                    for part in parts {
+                        if let StringPart::Expression(expr) = part {
+                            self.expression(expr);
+                        }
                     }
                 }
+                _ => {}
+            },
+            Expression::Binary(binary) => {
+                self.expression(&binary.left);
+                self.expression(&binary.right);
             }
-            Expression::Identifier(id_expr) => {
-                println!("{} identifier: {}", prefix, id_expr.name);
-            }
-            Expression::Binary(bin) => {
-                println!("{} {:?}", prefix, bin.op);
-            }
-            Expression::Unary(un) => {
-                println!("{} {:?}", prefix, un.op);
-            }
+            Expression::Unary(unary) => self.expression(&unary.operand),
             Expression::Call(call) => {
-                println!("{} call: {}({} args)", prefix, call.function, call.args.len());
+                for arg in &call.args {
+                    self.expression(arg);
+                }
             }
-            Expression::Index(_) => {
-                println!("{} array indexing", prefix);
+            Expression::Index(index) => {
+                self.expression(&index.array);
+                self.expression(&index.index);
             }
             Expression::Assign(assign) => {
-                println!("{} assign to: {}", prefix, assign.target);
+                self.use_name(assign.target.strip_prefix("__ARRAY_INDEX__:").unwrap_or(&assign.target));
+                self.expression(&assign.value);
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,235 @@
+// src/ast/pretty.rs - AST tree rendering for `minilang ast`, tests and tools
+
+use std::fmt::Write;
+use super::*;
+
+/// What `render` shows
+#[derive(Debug, Clone, Default)]
+pub struct PrettyOptions {
+    /// How many levels to show, counting functions as level 1; `None` shows
+    /// the whole tree
+    pub max_depth: Option<usize>,
+    /// Only these functions; empty shows every function
+    pub functions: Vec<String>,
+    /// Follow each node with its byte span
+    pub spans: bool,
+}
+
+/// One line of the tree and the lines below it
+struct Node {
+    label: String,
+    span: Option<Span>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(label: impl Into<String>, span: &Span) -> Self {
+        Node { label: label.into(), span: Some(span.clone()), children: Vec::new() }
+    }
+
+    /// A grouping line, such as `condition:`, with no node of its own
+    fn group(label: impl Into<String>, children: Vec<Node>) -> Self {
+        Node { label: label.into(), span: None, children }
+    }
+
+    fn with(mut self, children: Vec<Node>) -> Self {
+        self.children = children;
+        self
+    }
+}
+
+/// Render `program` as a tree, one node per line
+pub fn render(program: &Program, options: &PrettyOptions) -> String {
+    let functions: Vec<Node> = program.functions.iter()
+        .filter(|func| options.functions.is_empty() || options.functions.contains(&func.name))
+        .map(function)
+        .collect();
+
+    if functions.is_empty() {
+        return "(empty program)\n".to_string();
+    }
+
+    let mut out = String::new();
+    write_nodes(&mut out, &functions, "", 1, options);
+    out
+}
+
+fn write_nodes(out: &mut String, nodes: &[Node], indent: &str, depth: usize, options: &PrettyOptions) {
+    for (i, node) in nodes.iter().enumerate() {
+        let is_last = i == nodes.len() - 1;
+        let branch = if is_last { "└──" } else { "├──" };
+        let cut_off = options.max_depth.is_some_and(|max| depth >= max) && !node.children.is_empty();
+
+        let _ = write!(out, "{}{} {}", indent, branch, node.label);
+        if options.spans {
+            if let Some(span) = &node.span {
+                let _ = write!(out, " [{}..{}]", span.start, span.end);
+            }
+        }
+        if cut_off {
+            out.push_str(" …");
+        }
+        out.push('\n');
+
+        if !cut_off {
+            let child_indent = format!("{}{}", indent, if is_last { "    " } else { "│   " });
+            write_nodes(out, &node.children, &child_indent, depth + 1, options);
+        }
+    }
+}
+
+fn function(func: &Function) -> Node {
+    let mut label = format!("Function: {}", func.name);
+    if func.visibility == Visibility::Private {
+        label.push_str(" (private)");
+    }
+    if func.is_pure {
+        label.push_str(" (pure)");
+    }
+    for attr in &func.attributes {
+        let _ = write!(label, " @{}", attr.name);
+    }
+
+    let mut children = Vec::new();
+    if func.params.is_empty() {
+        children.push(Node::group("Parameters: none", vec![]));
+    } else {
+        let params = func.params.iter()
+            .map(|param| Node::new(format!("{} : {:?}", param.name, param.typ), &param.span))
+            .collect();
+        children.push(Node::group(format!("Parameters: {}", func.params.len()), params));
+    }
+    if let Some(ret_type) = &func.return_type {
+        children.push(Node::group(format!("Return Type: {:?}", ret_type), vec![]));
+    }
+    children.push(block("Body", &func.body));
+
+    Node::new(label, &func.span).with(children)
+}
+
+fn block(label: &str, block: &Block) -> Node {
+    Node::new(format!("{}: {} statement(s)", label, block.statements.len()), &block.span)
+        .with(block.statements.iter().map(statement).collect())
+}
+
+fn statement(stmt: &Statement) -> Node {
+    match stmt {
+        Statement::Const(const_stmt) => {
+            Node::new(format!("const {} : {:?} =", const_stmt.name, const_stmt.typ), &const_stmt.span)
+                .with(vec![expression(&const_stmt.value)])
+        }
+        Statement::Let(let_stmt) => {
+            let annotation = match &let_stmt.typ {
+                Some(typ) => format!(" : {:?}", typ),
+                None => String::new(),
+            };
+            match &let_stmt.value {
+                Some(value) => Node::new(format!("let {}{} =", let_stmt.name, annotation), &let_stmt.span)
+                    .with(vec![expression(value)]),
+                None => Node::new(format!("let {}{}", let_stmt.name, annotation), &let_stmt.span),
+            }
+        }
+        Statement::LetTuple(let_tuple) => {
+            Node::new(format!("let ({}) =", let_tuple.names.join(", ")), &let_tuple.span)
+                .with(vec![expression(&let_tuple.value)])
+        }
+        Statement::Display(display_stmt) => {
+            let keyword = if display_stmt.newline { "display" } else { "display_raw" };
+            Node::new(format!("{} ({} expression(s))", keyword, display_stmt.expressions.len()), &display_stmt.span)
+                .with(display_stmt.expressions.iter().map(expression).collect())
+        }
+        Statement::If(if_stmt) => {
+            let mut children = vec![
+                Node::group("condition:", vec![expression(&if_stmt.condition)]),
+                block("then", &if_stmt.then_block),
+            ];
+            if let Some(else_block) = &if_stmt.else_block {
+                children.push(block("else", else_block));
+            }
+            Node::new("if", &if_stmt.span).with(children)
+        }
+        Statement::While(while_stmt) => {
+            Node::new("while", &while_stmt.span).with(vec![
+                Node::group("condition:", vec![expression(&while_stmt.condition)]),
+                block("body", &while_stmt.body),
+            ])
+        }
+        Statement::DoWhile(do_while) => {
+            Node::new("do-while", &do_while.span).with(vec![
+                block("body", &do_while.body),
+                Node::group("condition:", vec![expression(&do_while.condition)]),
+            ])
+        }
+        Statement::For(for_stmt) => {
+            let mut children = Vec::new();
+            if let Some(init) = &for_stmt.init {
+                children.push(Node::group("init:", vec![statement(init)]));
+            }
+            if let Some(condition) = &for_stmt.condition {
+                children.push(Node::group("condition:", vec![expression(condition)]));
+            }
+            if let Some(update) = &for_stmt.update {
+                children.push(Node::group("update:", vec![expression(update)]));
+            }
+            children.push(block("body", &for_stmt.body));
+            let label = if for_stmt.parallel { "parallel for" } else { "for" };
+            Node::new(label, &for_stmt.span).with(children)
+        }
+        Statement::Return(ret_stmt) => match &ret_stmt.value {
+            Some(value) => Node::new("send", &ret_stmt.span).with(vec![expression(value)]),
+            None => Node::new("send (void)", &ret_stmt.span),
+        },
+        Statement::Expression(expr_stmt) => {
+            Node::new("expression:", &expr_stmt.span).with(vec![expression(&expr_stmt.expression)])
+        }
+        Statement::Block(inner) => block("block", inner),
+        Statement::Break(break_stmt) => Node::new("break", &break_stmt.span),
+        Statement::Continue(continue_stmt) => Node::new("continue", &continue_stmt.span),
+        Statement::Defer(defer_stmt) => {
+            Node::new(format!("defer ({} statement(s))", defer_stmt.body.statements.len()), &defer_stmt.span)
+                .with(defer_stmt.body.statements.iter().map(statement).collect())
+        }
+        Statement::Spawn(spawn_stmt) => {
+            Node::new(format!("spawn ({} statement(s))", spawn_stmt.body.statements.len()), &spawn_stmt.span)
+                .with(spawn_stmt.body.statements.iter().map(statement).collect())
+        }
+    }
+}
+
+fn expression(expr: &Expression) -> Node {
+    match expr {
+        Expression::Literal(lit_expr) => {
+            let span = &lit_expr.span;
+            match &lit_expr.value {
+                Literal::Integer(n) => Node::new(n.to_string(), span),
+                Literal::Float(f) => Node::new(f.to_string(), span),
+                Literal::String(s) => Node::new(format!("\"{}\"", s), span),
+                Literal::Boolean(b) => Node::new(b.to_string(), span),
+                Literal::Array(elements) => Node::new(format!("array [{}]", elements.len()), span)
+                    .with(elements.iter().map(expression).collect()),
+                Literal::Tuple(elements) => Node::new(format!("tuple ({})", elements.len()), span)
+                    .with(elements.iter().map(expression).collect()),
+                Literal::InterpolatedString(parts) => {
+                    Node::new(format!("interpolated string ({} parts)", parts.len()), span)
+                        .with(parts.iter()
+                            .map(|part| match part {
+                                StringPart::Text(text) => Node::group(format!("text \"{}\"", text), vec![]),
+                                StringPart::Expression(expr) => expression(expr),
+                            })
+                            .collect())
+                }
+            }
+        }
+        Expression::Identifier(id_expr) => Node::new(format!("identifier: {}", id_expr.name), &id_expr.span),
+        Expression::Binary(bin) => Node::new(format!("{:?}", bin.op), &bin.span)
+            .with(vec![expression(&bin.left), expression(&bin.right)]),
+        Expression::Unary(un) => Node::new(format!("{:?}", un.op), &un.span)
+            .with(vec![expression(&un.operand)]),
+        Expression::Call(call) => Node::new(format!("call: {}({} args)", call.function, call.args.len()), &call.span)
+            .with(call.args.iter().map(expression).collect()),
+        Expression::Index(index) => Node::new("array indexing", &index.span)
+            .with(vec![expression(&index.array), expression(&index.index)]),
+        Expression::Assign(assign) => Node::new(format!("assign to: {}", assign.target), &assign.span)
+            .with(vec![expression(&assign.value)]),
+    }
+}
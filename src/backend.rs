@@ -0,0 +1,200 @@
+// src/backend.rs - Turning generated C into an executable
+
+use crate::codegen::USER_SYMBOL_PREFIX;
+use crate::errors::{BackendError, CcDiagnostic};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Flags passed to gcc after the input and output paths
+pub const GCC_FLAGS: [&str; 4] = ["-std=c99", "-Wall", "-O2", "-pthread"];
+
+/// Compile `c_file` to `exe` with the C compiler `cc` (e.g. "gcc") and `flags`
+pub fn compile_c(cc: &str, c_file: &Path, exe: &Path, flags: &[&str]) -> Result<(), BackendError> {
+    run(cc, compile_c_args(c_file, exe, flags))
+}
+
+/// The arguments `compile_c` passes to the C compiler
+pub fn compile_c_args(c_file: &Path, exe: &Path, flags: &[&str]) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec![c_file.into(), "-o".into(), exe.into()];
+    args.extend(flags.iter().map(OsString::from));
+    args
+}
+
+/// Compile `c_file` to the object file `object` without linking, for
+/// multi-module programs
+pub fn compile_object(cc: &str, c_file: &Path, object: &Path, flags: &[&str]) -> Result<(), BackendError> {
+    run(cc, compile_object_args(c_file, object, flags))
+}
+
+/// The arguments `compile_object` passes to the C compiler
+pub fn compile_object_args(c_file: &Path, object: &Path, flags: &[&str]) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["-c".into(), c_file.into(), "-o".into(), object.into()];
+    args.extend(flags.iter().map(OsString::from));
+    args
+}
+
+/// Link `objects` into the executable `exe`
+pub fn link<P: AsRef<Path>>(cc: &str, objects: &[P], exe: &Path, flags: &[&str]) -> Result<(), BackendError> {
+    run(cc, link_args(objects, exe, flags))
+}
+
+/// The arguments `link` passes to the C compiler
+pub fn link_args<P: AsRef<Path>>(objects: &[P], exe: &Path, flags: &[&str]) -> Vec<OsString> {
+    let mut args: Vec<OsString> = objects.iter().map(|object| object.as_ref().into()).collect();
+    args.extend(["-o".into(), exe.into()]);
+    args.extend(flags.iter().map(OsString::from));
+    args
+}
+
+/// `cc` followed by `args`, the command line a compile runs
+pub fn invocation(cc: &str, args: &[OsString]) -> Vec<String> {
+    std::iter::once(cc.to_string())
+        .chain(args.iter().map(|arg| arg.to_string_lossy().into_owned()))
+        .collect()
+}
+
+/// Write `invocations` to `path` as a POSIX shell script that stops at the
+/// first failing command, made executable on Unix
+pub fn write_script(path: &Path, invocations: &[Vec<String>]) -> io::Result<()> {
+    let mut script = String::from("#!/bin/sh\nset -e\n");
+    for invocation in invocations {
+        script.push_str(&shell_command(invocation));
+        script.push('\n');
+    }
+    fs::write(path, script)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// Keep what is needed to debug a failed compile in `dir`: a copy of each of
+/// `c_files`, its preprocessed form (`.i`, when `cc -E` manages to produce
+/// it), the exact command line in `command.txt` and the compiler's stderr in
+/// `stderr.txt`
+pub fn save_temps(dir: &Path, cc: &str, c_files: &[&Path], flags: &[&str], error: &BackendError) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    for c_file in c_files {
+        let Some(name) = c_file.file_name() else { continue };
+        let saved = dir.join(name);
+        fs::copy(c_file, &saved)?;
+
+        // Best effort: whatever made the compile fail may break this too
+        let _ = Command::new(cc)
+            .arg("-E")
+            .arg(&saved)
+            .arg("-o")
+            .arg(saved.with_extension("i"))
+            .args(flags)
+            .output();
+    }
+
+    fs::write(dir.join("command.txt"), format!("{}\n", shell_command(error.invocation())))?;
+    if let BackendError::CompileFailed { stderr, .. } = error {
+        fs::write(dir.join("stderr.txt"), stderr)?;
+    }
+    Ok(())
+}
+
+/// Turn the errors and warnings in a C compiler's `stderr` into diagnostics on
+/// the lines of `source`, which gcc names `filename` thanks to the `#line`
+/// directives. Returns None, so the raw output can be shown instead, when
+/// there are no errors or one of them is outside the MiniLang source (e.g.
+/// in the runtime support code, or at link time).
+pub fn map_cc_diagnostics(stderr: &str, filename: &str, source: &str) -> Option<Vec<CcDiagnostic>> {
+    let mut diagnostics = Vec::new();
+    let mut errors = 0;
+
+    for line in stderr.lines() {
+        let Some((location, kind, message)) = split_cc_message(line) else { continue };
+        let is_error = kind != "warning";
+        if is_error {
+            errors += 1;
+        }
+
+        let span = location.strip_prefix(filename)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .and_then(|rest| rest.split(':').next()?.parse::<usize>().ok())
+            .and_then(|line_number| line_span(source, line_number));
+        let message = demangle(message);
+        match (span, is_error) {
+            (Some(span), true) => diagnostics.push(CcDiagnostic::Error { message, span: span.into() }),
+            (Some(span), false) => diagnostics.push(CcDiagnostic::Warning { message, span: span.into() }),
+            (None, true) => return None,
+            (None, false) => {}
+        }
+    }
+
+    (errors > 0).then_some(diagnostics)
+}
+
+/// Show quoted C names (`'ml_total'`, or `‘ml_total’` in UTF-8 locales) as
+/// the MiniLang names they were generated from
+fn demangle(message: &str) -> String {
+    ['\'', '‘'].into_iter().fold(message.to_string(), |message, quote| {
+        message.replace(&format!("{}{}", quote, USER_SYMBOL_PREFIX), &quote.to_string())
+    })
+}
+
+/// Split `file:line:col: error: message` into its location, kind and message
+fn split_cc_message(line: &str) -> Option<(&str, &str, &str)> {
+    ["fatal error", "error", "warning"].into_iter().find_map(|kind| {
+        let (location, message) = line.split_once(&format!(": {}: ", kind))?;
+        Some((location, kind, message))
+    })
+}
+
+/// Byte range of the 1-based `line_number` in `source`, without its indentation
+fn line_span(source: &str, line_number: usize) -> Option<std::ops::Range<usize>> {
+    let start = source.split_inclusive('\n')
+        .take(line_number.checked_sub(1)?)
+        .map(str::len)
+        .sum::<usize>();
+    let line = source.get(start..)?.lines().next()?;
+    let indent = line.len() - line.trim_start().len();
+    Some(start + indent..start + line.trim_end().len())
+}
+
+/// `invocation` as one line that can be pasted into a POSIX shell
+pub fn shell_command(invocation: &[String]) -> String {
+    invocation.iter()
+        .map(|arg| {
+            let plain = !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=+,:@%".contains(c));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn run(cc: &str, args: Vec<OsString>) -> Result<(), BackendError> {
+    let invocation = invocation(cc, &args);
+
+    let output = Command::new(cc)
+        .args(&args)
+        .output()
+        .map_err(|e| BackendError::Spawn {
+            invocation: invocation.clone(),
+            reason: e.to_string(),
+        })?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(BackendError::CompileFailed {
+            invocation,
+            status: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
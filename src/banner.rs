@@ -0,0 +1,206 @@
+// src/banner.rs - The comment block some projects put on top of generated C
+//
+// Teaching environments that archive generated code want to know whose it is
+// and which course it was for. A project asks for the banner in the `[banner]`
+// table of its minilang.toml:
+//
+//     [banner]
+//     course = "CS 101"
+//     student-id = "<your student id>"
+//     license = "CC BY-SA 4.0"
+//     timestamp = "date"
+//
+// Only the small part of TOML that manifests use is read here: tables and
+// `key = value` lines with string, integer and boolean values.
+
+/// Whether and how precisely the banner says when the code was generated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampPolicy {
+    /// No time, so generating the same program twice gives the same C
+    #[default]
+    None,
+    /// The day, e.g. 2024-03-09
+    Date,
+    /// The day and the minute, in UTC
+    DateTime,
+}
+
+/// What `[banner]` asks for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Banner {
+    pub course: Option<String>,
+    /// Written as given: usually a placeholder for students to fill in
+    pub student_id: Option<String>,
+    pub license: Option<String>,
+    pub timestamp: TimestampPolicy,
+    /// Seconds since the Unix epoch that the timestamp shows, set by whoever
+    /// generates the code; without it the banner has no time
+    pub generated_at: Option<u64>,
+}
+
+impl Banner {
+    /// The banner a minilang.toml asks for, or `None` without a `[banner]`
+    /// table. Errors name the line they are on.
+    pub fn from_manifest(text: &str) -> Result<Option<Banner>, String> {
+        let mut banner = None;
+        let mut table = String::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                table = name.trim().to_string();
+                if table == "banner" {
+                    banner.get_or_insert_with(Banner::default);
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected `key = value`", line_number));
+            };
+            let value = parse_value(value.trim())
+                .map_err(|e| format!("line {}: {}", line_number, e))?;
+            let Some(banner) = banner.as_mut().filter(|_| table == "banner") else {
+                continue;
+            };
+
+            let key = key.trim();
+            match (key, value) {
+                ("course", Value::String(text)) => banner.course = Some(text),
+                ("student-id", Value::String(text)) => banner.student_id = Some(text),
+                ("license", Value::String(text)) => banner.license = Some(text),
+                ("timestamp", Value::String(policy)) => {
+                    banner.timestamp = match policy.as_str() {
+                        "none" => TimestampPolicy::None,
+                        "date" => TimestampPolicy::Date,
+                        "datetime" => TimestampPolicy::DateTime,
+                        other => return Err(format!(
+                            "line {}: timestamp must be \"none\", \"date\" or \"datetime\", not \"{}\"",
+                            line_number, other
+                        )),
+                    };
+                }
+                ("course" | "student-id" | "license" | "timestamp", _) => {
+                    return Err(format!("line {}: {} must be a string", line_number, key));
+                }
+                _ => return Err(format!("line {}: unknown banner key '{}'", line_number, key)),
+            }
+        }
+
+        Ok(banner)
+    }
+
+    /// The banner as a C comment for code generated from `source_name`,
+    /// ending in a newline
+    pub fn to_c_comment(&self, source_name: &str) -> String {
+        let mut lines = Vec::new();
+        if let Some(course) = &self.course {
+            lines.push(format!("Course: {}", course));
+        }
+        if let Some(student_id) = &self.student_id {
+            lines.push(format!("Student ID: {}", student_id));
+        }
+        if let Some(license) = &self.license {
+            lines.push(format!("License: {}", license));
+        }
+        let mut generated = format!("Generated by minilang {} from {}", env!("CARGO_PKG_VERSION"), source_name);
+        if let Some(time) = self.timestamp_text() {
+            generated.push_str(" on ");
+            generated.push_str(&time);
+        }
+        lines.push(generated);
+
+        let mut comment = String::from("/*\n");
+        for line in lines.iter().flat_map(|line| line.lines()) {
+            // A `*/` in a value would end the comment early
+            let line = line.replace("*/", "* /");
+            if line.trim().is_empty() {
+                comment.push_str(" *\n");
+            } else {
+                comment.push_str(&format!(" * {}\n", line));
+            }
+        }
+        comment.push_str(" */\n");
+        comment
+    }
+
+    /// `generated_at` as the policy asks, in UTC
+    fn timestamp_text(&self) -> Option<String> {
+        let seconds = self.generated_at?;
+        let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+        let minutes = seconds % 86_400 / 60;
+        match self.timestamp {
+            TimestampPolicy::None => None,
+            TimestampPolicy::Date => Some(format!("{:04}-{:02}-{:02}", year, month, day)),
+            TimestampPolicy::DateTime => Some(format!(
+                "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+                year, month, day, minutes / 60, minutes % 60
+            )),
+        }
+    }
+}
+
+enum Value {
+    String(String),
+    Integer,
+    Boolean,
+}
+
+/// The line up to a `#` that is not inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(body) = text.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        let mut value = String::new();
+        let mut chars = body.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(c @ ('"' | '\\')) => value.push(c),
+                _ => return Err(format!("unsupported escape in {}", text)),
+            }
+        }
+        return Ok(Value::String(value));
+    }
+    match text {
+        "true" | "false" => Ok(Value::Boolean),
+        _ if text.parse::<i64>().is_ok() => Ok(Value::Integer),
+        _ => Err(format!("expected a string, integer or boolean, found `{}`", text)),
+    }
+}
+
+/// Year, month and day of a day counted from 1970-01-01 (Howard Hinnant's
+/// `civil_from_days`)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -293,11 +293,7 @@ fn show_beautiful_error(description: &str, source: &str) {
 }
 
 fn print_final_summary(total: u32, passed: u32, failed: u32) {
-    let success_rate = if total > 0 {
-        (passed * 100) / total
-    } else {
-        0
-    };
+    let success_rate = (passed * 100).checked_div(total).unwrap_or(0);
     
     println!("\n");
     println!("{:=^60}", "");
@@ -0,0 +1,563 @@
+// src/bounds.rs - Range analysis over int expressions
+//
+// Every index compiles to a CHECK_BOUNDS call unless this pass can show the
+// index always lies inside the array, and at -O2 the optimizer replaces
+// comparisons it can decide with `true` or `false`. It knows about integer
+// constants, ints that are never reassigned, `for` loop counters with
+// constant limits, what an `if` condition says inside each branch, and
+// +, -, *, / and % on those.
+
+use crate::ast::*;
+use std::collections::{HashMap, HashSet};
+
+/// Inclusive range of values an int expression can take
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Range {
+    lo: i64,
+    hi: i64,
+}
+
+impl Range {
+    fn new(lo: i64, hi: i64) -> Option<Self> {
+        // Anything outside C's int would already have overflowed
+        let fits = |v: i64| (i32::MIN as i64..=i32::MAX as i64).contains(&v);
+        (lo <= hi && fits(lo) && fits(hi)).then_some(Range { lo, hi })
+    }
+
+    /// Every value a C int can hold
+    fn full() -> Self {
+        Range { lo: i32::MIN as i64, hi: i32::MAX as i64 }
+    }
+
+    fn constant(&self) -> Option<i64> {
+        (self.lo == self.hi).then_some(self.lo)
+    }
+}
+
+/// What the analysis knows about a name in scope
+#[derive(Debug, Clone, Copy)]
+enum Binding {
+    Array(usize),
+    Int(Range),
+    Unknown,
+}
+
+/// What the range analysis proved about a program, keyed by node id
+#[derive(Debug, Clone, Default)]
+pub struct RangeFacts {
+    /// Index expressions that can never go out of bounds
+    pub safe_indexes: HashSet<NodeId>,
+    /// Comparisons that always give the same result
+    pub comparisons: HashMap<NodeId, bool>,
+}
+
+/// Run the range analysis over every function. Only numbered nodes are
+/// recorded.
+pub fn analyze(program: &Program) -> RangeFacts {
+    let mut facts = RangeFacts::default();
+    for function in &program.functions {
+        let mut reassigned = HashSet::new();
+        assigned_in_block(&function.body, &mut reassigned);
+
+        let mut analysis = RangeAnalysis {
+            scopes: vec![HashMap::new()],
+            reassigned,
+            facts: &mut facts,
+        };
+        for param in &function.params {
+            analysis.declare(&param.name, Some(&param.typ), None);
+        }
+        analysis.block(&function.body);
+    }
+    facts
+}
+
+/// Ids of index expressions that can never go out of bounds
+pub fn safe_indexes(program: &Program) -> HashSet<NodeId> {
+    analyze(program).safe_indexes
+}
+
+struct RangeAnalysis<'a> {
+    scopes: Vec<HashMap<String, Binding>>,
+    // Variables assigned anywhere in the function; their initial value says
+    // nothing about later reads
+    reassigned: HashSet<String>,
+    facts: &'a mut RangeFacts,
+}
+
+impl RangeAnalysis<'_> {
+    /// `typ` is None for a `let` without annotation, whose initializer is
+    /// an int exactly when it has a range
+    fn declare(&mut self, name: &str, typ: Option<&Type>, value: Option<&Expression>) {
+        let range = value.and_then(|value| self.range(value));
+        let binding = match (typ, value) {
+            (Some(Type::Array(_, size)), _) => Binding::Array(*size),
+            (None, Some(Expression::Literal(LiteralExpr { value: Literal::Array(elements), .. }))) => {
+                Binding::Array(elements.len())
+            }
+            _ if self.reassigned.contains(name) => Binding::Unknown,
+            (Some(Type::Int), _) => Binding::Int(range.unwrap_or_else(Range::full)),
+            (None, _) => range.map_or(Binding::Unknown, Binding::Int),
+            _ => Binding::Unknown,
+        };
+        self.bind(name, binding);
+    }
+
+    fn bind(&mut self, name: &str, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), binding);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Binding {
+        self.scopes.iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .unwrap_or(Binding::Unknown)
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scoped(|analysis| {
+            for stmt in &block.statements {
+                analysis.statement(stmt);
+            }
+        });
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let(let_stmt) => {
+                if let Some(value) = &let_stmt.value {
+                    self.expression(value);
+                }
+                self.declare(&let_stmt.name, let_stmt.typ.as_ref(), let_stmt.value.as_ref());
+            }
+            Statement::Const(const_stmt) => {
+                self.expression(&const_stmt.value);
+                self.declare(&const_stmt.name, Some(&const_stmt.typ), Some(&const_stmt.value));
+            }
+            Statement::LetTuple(let_tuple) => {
+                self.expression(&let_tuple.value);
+                for name in &let_tuple.names {
+                    self.bind(name, Binding::Unknown);
+                }
+            }
+            Statement::Display(display_stmt) => {
+                for expr in &display_stmt.expressions {
+                    self.expression(expr);
+                }
+            }
+            Statement::If(if_stmt) => {
+                self.expression(&if_stmt.condition);
+                self.scoped(|analysis| {
+                    analysis.assume(&if_stmt.condition, true);
+                    analysis.block(&if_stmt.then_block);
+                });
+                if let Some(else_block) = &if_stmt.else_block {
+                    self.scoped(|analysis| {
+                        analysis.assume(&if_stmt.condition, false);
+                        analysis.block(else_block);
+                    });
+                }
+            }
+            Statement::While(while_stmt) => {
+                self.expression(&while_stmt.condition);
+                self.block(&while_stmt.body);
+            }
+            Statement::DoWhile(do_while) => {
+                self.block(&do_while.body);
+                self.expression(&do_while.condition);
+            }
+            Statement::For(for_stmt) => self.for_statement(for_stmt),
+            Statement::Return(return_stmt) => {
+                if let Some(value) = &return_stmt.value {
+                    self.expression(value);
+                }
+            }
+            Statement::Expression(expr_stmt) => self.expression(&expr_stmt.expression),
+            Statement::Block(block) => self.block(block),
+            Statement::Defer(defer_stmt) => self.block(&defer_stmt.body),
+            Statement::Spawn(spawn_stmt) => self.block(&spawn_stmt.body),
+            Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+
+    fn for_statement(&mut self, for_stmt: &ForStmt) {
+        self.scoped(|analysis| {
+            if let Some(init) = &for_stmt.init {
+                analysis.statement(init);
+            }
+            // The condition and update also see the value that ends the loop
+            if let Some(condition) = &for_stmt.condition {
+                analysis.expression(condition);
+            }
+            if let Some(update) = &for_stmt.update {
+                analysis.expression(update);
+            }
+
+            let counter = analysis.counter_range(for_stmt);
+            analysis.scoped(|analysis| {
+                if let Some((name, range)) = counter {
+                    analysis.bind(&name, Binding::Int(range));
+                }
+                analysis.block(&for_stmt.body);
+            });
+        });
+    }
+
+    /// Values a counting loop's variable takes inside the body. Recognizes
+    /// `for let i: int = A; i < B; i = i + S` (or `let i = A`) with S
+    /// positive and i not assigned in the body.
+    fn counter_range(&self, for_stmt: &ForStmt) -> Option<(String, Range)> {
+        let Some(Statement::Let(LetStmt { name, typ: Some(Type::Int) | None, value: Some(start), .. })) = for_stmt.init.as_deref() else {
+            return None;
+        };
+        let start = self.range(start)?;
+
+        let Some(Expression::Binary(condition)) = &for_stmt.condition else {
+            return None;
+        };
+        if !is_variable(&condition.left, name) {
+            return None;
+        }
+        let limit = self.range(&condition.right)?;
+        let last = match condition.op {
+            BinaryOp::Less => limit.hi - 1,
+            BinaryOp::LessEqual => limit.hi,
+            _ => return None,
+        };
+
+        let Some(Expression::Assign(update)) = &for_stmt.update else {
+            return None;
+        };
+        let step = match &*update.value {
+            Expression::Binary(BinaryExpr { op: BinaryOp::Add, left, right, .. }) if update.target == *name => {
+                if is_variable(left, name) {
+                    self.range(right)?.constant()?
+                } else if is_variable(right, name) {
+                    self.range(left)?.constant()?
+                } else {
+                    return None;
+                }
+            }
+            _ => return None,
+        };
+        // The final increment must not overflow either
+        if step < 1 || last + step > i32::MAX as i64 {
+            return None;
+        }
+
+        let mut assigned = HashSet::new();
+        assigned_in_block(&for_stmt.body, &mut assigned);
+        if assigned.contains(name) {
+            return None;
+        }
+
+        Range::new(start.lo, last).map(|range| (name.clone(), range))
+    }
+
+    /// Narrow the ranges of ints in the current scope to what they must be
+    /// when `condition` evaluates to `holds`
+    fn assume(&mut self, condition: &Expression, holds: bool) {
+        match condition {
+            Expression::Unary(UnaryExpr { op: UnaryOp::Not, operand, .. }) => self.assume(operand, !holds),
+            Expression::Binary(binary) => match (&binary.op, holds) {
+                (BinaryOp::And, true) | (BinaryOp::Or, false) => {
+                    self.assume(&binary.left, holds);
+                    self.assume(&binary.right, holds);
+                }
+                (op, _) => {
+                    let Some(op) = comparison(op, holds) else { return };
+                    self.narrow(&binary.left, &op, &binary.right);
+                    if let Some(flipped) = flip(&op) {
+                        self.narrow(&binary.right, &flipped, &binary.left);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Bind `variable` to the part of its range for which `variable op bound` holds
+    fn narrow(&mut self, variable: &Expression, op: &BinaryOp, bound: &Expression) {
+        let Expression::Identifier(id) = variable else { return };
+        let (Binding::Int(range), Some(bound)) = (self.lookup(&id.name), self.range(bound)) else {
+            return;
+        };
+        let narrowed = match op {
+            BinaryOp::Less => Range::new(range.lo, range.hi.min(bound.hi - 1)),
+            BinaryOp::LessEqual => Range::new(range.lo, range.hi.min(bound.hi)),
+            BinaryOp::Greater => Range::new(range.lo.max(bound.lo + 1), range.hi),
+            BinaryOp::GreaterEqual => Range::new(range.lo.max(bound.lo), range.hi),
+            BinaryOp::Equal => Range::new(range.lo.max(bound.lo), range.hi.min(bound.hi)),
+            _ => None,
+        };
+        // An empty range means the branch never runs; keep what we had
+        if let Some(narrowed) = narrowed {
+            self.bind(&id.name, Binding::Int(narrowed));
+        }
+    }
+
+    /// The result of a comparison when it is the same for every value its
+    /// operands can take
+    fn decide(&self, binary: &BinaryExpr) -> Option<bool> {
+        let left = self.range(&binary.left)?;
+        let right = self.range(&binary.right)?;
+        let less = |a: Range, b: Range| if a.hi < b.lo { Some(true) } else if a.lo >= b.hi { Some(false) } else { None };
+        let less_equal = |a: Range, b: Range| if a.hi <= b.lo { Some(true) } else if a.lo > b.hi { Some(false) } else { None };
+        let equal = if left.hi < right.lo || right.hi < left.lo {
+            Some(false)
+        } else if left.constant().is_some() && left.constant() == right.constant() {
+            Some(true)
+        } else {
+            None
+        };
+        match binary.op {
+            BinaryOp::Less => less(left, right),
+            BinaryOp::LessEqual => less_equal(left, right),
+            BinaryOp::Greater => less(right, left),
+            BinaryOp::GreaterEqual => less_equal(right, left),
+            BinaryOp::Equal => equal,
+            BinaryOp::NotEqual => equal.map(|equal| !equal),
+            _ => None,
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Literal(lit) => match &lit.value {
+                Literal::Array(elements) | Literal::Tuple(elements) => {
+                    for element in elements {
+                        self.expression(element);
+                    }
+                }
+                Literal::InterpolatedString(parts) => {
+                    for part in parts {
+                        if let StringPart::Expression(expr) = part {
+                            self.expression(expr);
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Expression::Identifier(_) => {}
+            Expression::Binary(binary) => {
+                self.expression(&binary.left);
+                self.expression(&binary.right);
+                if binary.id != NodeId::default() {
+                    if let Some(result) = self.decide(binary) {
+                        self.facts.comparisons.insert(binary.id, result);
+                    }
+                }
+            }
+            Expression::Unary(unary) => self.expression(&unary.operand),
+            Expression::Call(call) => {
+                for arg in &call.args {
+                    self.expression(arg);
+                }
+            }
+            Expression::Index(index) => {
+                self.expression(&index.array);
+                self.expression(&index.index);
+                if index.id != NodeId::default() && self.in_bounds(index) {
+                    self.facts.safe_indexes.insert(index.id);
+                }
+            }
+            Expression::Assign(assign) => self.expression(&assign.value),
+        }
+    }
+
+    fn in_bounds(&self, index: &IndexExpr) -> bool {
+        let Expression::Identifier(array) = &*index.array else {
+            return false;
+        };
+        let Binding::Array(size) = self.lookup(&array.name) else {
+            return false;
+        };
+        self.range(&index.index)
+            .is_some_and(|range| range.lo >= 0 && range.hi < size as i64)
+    }
+
+    /// Range of an int expression, if it can be bounded
+    fn range(&self, expr: &Expression) -> Option<Range> {
+        match expr {
+            Expression::Literal(LiteralExpr { value: Literal::Integer(value), .. }) => {
+                Range::new(*value as i64, *value as i64)
+            }
+            Expression::Identifier(id) => match self.lookup(&id.name) {
+                Binding::Int(range) => Some(range),
+                _ => None,
+            },
+            Expression::Unary(UnaryExpr { op: UnaryOp::Negate, operand, .. }) => {
+                let operand = self.range(operand)?;
+                Range::new(-operand.hi, -operand.lo)
+            }
+            Expression::Binary(binary) => {
+                let left = self.range(&binary.left)?;
+                let right = self.range(&binary.right)?;
+                match binary.op {
+                    BinaryOp::Add => Range::new(left.lo + right.lo, left.hi + right.hi),
+                    BinaryOp::Subtract => Range::new(left.lo - right.hi, left.hi - right.lo),
+                    BinaryOp::Multiply => {
+                        let products = [left.lo * right.lo, left.lo * right.hi, left.hi * right.lo, left.hi * right.hi];
+                        Range::new(*products.iter().min()?, *products.iter().max()?)
+                    }
+                    BinaryOp::Divide => {
+                        // C division truncates toward zero, like Rust's
+                        let divisor = right.constant().filter(|&d| d != 0)?;
+                        let (a, b) = (left.lo / divisor, left.hi / divisor);
+                        Range::new(a.min(b), a.max(b))
+                    }
+                    BinaryOp::Modulo => {
+                        // The remainder takes the sign of the dividend
+                        let largest = right.constant().filter(|&d| d != 0)?.abs() - 1;
+                        if left.lo >= 0 {
+                            Range::new(0, left.hi.min(largest))
+                        } else {
+                            Range::new(-largest, largest)
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn is_variable(expr: &Expression, name: &str) -> bool {
+    matches!(expr, Expression::Identifier(id) if id.name == name)
+}
+
+/// A comparison operator, negated when the comparison is known to be false
+fn comparison(op: &BinaryOp, holds: bool) -> Option<BinaryOp> {
+    let (op, negated) = match op {
+        BinaryOp::Less => (BinaryOp::Less, BinaryOp::GreaterEqual),
+        BinaryOp::LessEqual => (BinaryOp::LessEqual, BinaryOp::Greater),
+        BinaryOp::Greater => (BinaryOp::Greater, BinaryOp::LessEqual),
+        BinaryOp::GreaterEqual => (BinaryOp::GreaterEqual, BinaryOp::Less),
+        BinaryOp::Equal => (BinaryOp::Equal, BinaryOp::NotEqual),
+        BinaryOp::NotEqual => (BinaryOp::NotEqual, BinaryOp::Equal),
+        _ => return None,
+    };
+    Some(if holds { op } else { negated })
+}
+
+/// The operator that gives the same result with its operands swapped
+fn flip(op: &BinaryOp) -> Option<BinaryOp> {
+    match op {
+        BinaryOp::Less => Some(BinaryOp::Greater),
+        BinaryOp::LessEqual => Some(BinaryOp::GreaterEqual),
+        BinaryOp::Greater => Some(BinaryOp::Less),
+        BinaryOp::GreaterEqual => Some(BinaryOp::LessEqual),
+        BinaryOp::Equal | BinaryOp::NotEqual => Some(op.clone()),
+        _ => None,
+    }
+}
+
+// ==================== ASSIGNMENT COLLECTION ====================
+
+fn assigned_in_block(block: &Block, names: &mut HashSet<String>) {
+    for stmt in &block.statements {
+        assigned_in_statement(stmt, names);
+    }
+}
+
+fn assigned_in_statement(stmt: &Statement, names: &mut HashSet<String>) {
+    match stmt {
+        Statement::Let(let_stmt) => {
+            if let Some(value) = &let_stmt.value {
+                assigned_in_expression(value, names);
+            }
+        }
+        Statement::Const(const_stmt) => assigned_in_expression(&const_stmt.value, names),
+        Statement::LetTuple(let_tuple) => assigned_in_expression(&let_tuple.value, names),
+        Statement::Display(display_stmt) => {
+            for expr in &display_stmt.expressions {
+                assigned_in_expression(expr, names);
+            }
+        }
+        Statement::If(if_stmt) => {
+            assigned_in_expression(&if_stmt.condition, names);
+            assigned_in_block(&if_stmt.then_block, names);
+            if let Some(else_block) = &if_stmt.else_block {
+                assigned_in_block(else_block, names);
+            }
+        }
+        Statement::While(while_stmt) => {
+            assigned_in_expression(&while_stmt.condition, names);
+            assigned_in_block(&while_stmt.body, names);
+        }
+        Statement::DoWhile(do_while) => {
+            assigned_in_block(&do_while.body, names);
+            assigned_in_expression(&do_while.condition, names);
+        }
+        Statement::For(for_stmt) => {
+            if let Some(init) = &for_stmt.init {
+                assigned_in_statement(init, names);
+            }
+            if let Some(condition) = &for_stmt.condition {
+                assigned_in_expression(condition, names);
+            }
+            if let Some(update) = &for_stmt.update {
+                assigned_in_expression(update, names);
+            }
+            assigned_in_block(&for_stmt.body, names);
+        }
+        Statement::Return(return_stmt) => {
+            if let Some(value) = &return_stmt.value {
+                assigned_in_expression(value, names);
+            }
+        }
+        Statement::Expression(expr_stmt) => assigned_in_expression(&expr_stmt.expression, names),
+        Statement::Block(block) => assigned_in_block(block, names),
+        Statement::Defer(defer_stmt) => assigned_in_block(&defer_stmt.body, names),
+        Statement::Spawn(spawn_stmt) => assigned_in_block(&spawn_stmt.body, names),
+        Statement::Break(_) | Statement::Continue(_) => {}
+    }
+}
+
+fn assigned_in_expression(expr: &Expression, names: &mut HashSet<String>) {
+    match expr {
+        Expression::Literal(lit) => match &lit.value {
+            Literal::Array(elements) | Literal::Tuple(elements) => {
+                for element in elements {
+                    assigned_in_expression(element, names);
+                }
+            }
+            Literal::InterpolatedString(parts) => {
+                for part in parts {
+                    if let StringPart::Expression(expr) = part {
+                        assigned_in_expression(expr, names);
+                    }
+                }
+            }
+            _ => {}
+        },
+        Expression::Identifier(_) => {}
+        Expression::Binary(binary) => {
+            assigned_in_expression(&binary.left, names);
+            assigned_in_expression(&binary.right, names);
+        }
+        Expression::Unary(unary) => assigned_in_expression(&unary.operand, names),
+        Expression::Call(call) => {
+            for arg in &call.args {
+                assigned_in_expression(arg, names);
+            }
+        }
+        Expression::Index(index) => {
+            assigned_in_expression(&index.array, names);
+            assigned_in_expression(&index.index, names);
+        }
+        Expression::Assign(assign) => {
+            names.insert(assign.target.clone());
+            assigned_in_expression(&assign.value, names);
+        }
+    }
+}
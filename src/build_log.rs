@@ -0,0 +1,140 @@
+// src/build_log.rs - Machine-readable record of each compile
+
+use miette::Diagnostic;
+use serde::{Serialize, Deserialize};
+pub use crate::stamp::sha256_hex;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One compile, appended to the build log as a single JSON line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildRecord {
+    /// Seconds since the Unix epoch when the compile started
+    pub timestamp: u64,
+    pub compiler_version: String,
+    pub source: String,
+    pub source_sha256: String,
+    /// `compile`, `run` or `to-c`
+    pub mode: String,
+    pub optimization: u8,
+    pub cc_flags: Vec<String>,
+    pub status: BuildStatus,
+    pub errors: usize,
+    pub warnings: usize,
+    /// The code of each error reported, e.g. `minilang::parser::missing_semicolon`
+    #[serde(default)]
+    pub error_codes: Vec<String>,
+    /// The code of each warning reported
+    #[serde(default)]
+    pub warning_codes: Vec<String>,
+    pub stages: Vec<StageTiming>,
+    pub artifacts: Vec<Artifact>,
+    pub total_ms: f64,
+}
+
+/// How a compile ended
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum BuildStatus {
+    Success,
+    Failed { stage: String },
+    /// Stopped by Ctrl-C during `stage`
+    Interrupted { stage: String },
+}
+
+/// Wall-clock time spent in one pipeline stage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageTiming {
+    pub stage: String,
+    pub ms: f64,
+}
+
+/// A file produced by the compile
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub kind: String,
+    pub path: String,
+    pub sha256: String,
+}
+
+impl BuildRecord {
+    /// Start a record for compiling `source_text` read from `source`
+    pub fn new(source: &Path, source_text: &str, mode: &str, optimization: u8) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            timestamp,
+            compiler_version: env!("CARGO_PKG_VERSION").to_string(),
+            source: source.display().to_string(),
+            source_sha256: sha256_hex(source_text.as_bytes()),
+            mode: mode.to_string(),
+            optimization,
+            cc_flags: Vec::new(),
+            status: BuildStatus::Success,
+            errors: 0,
+            warnings: 0,
+            error_codes: Vec::new(),
+            warning_codes: Vec::new(),
+            stages: Vec::new(),
+            artifacts: Vec::new(),
+            total_ms: 0.0,
+        }
+    }
+
+    pub fn record_stage(&mut self, stage: &str, elapsed: Duration) {
+        self.stages.push(StageTiming {
+            stage: stage.to_string(),
+            ms: elapsed.as_secs_f64() * 1000.0,
+        });
+    }
+
+    /// Mark the compile as failed in `stage` with `errors` diagnostics
+    pub fn fail(&mut self, stage: &str, errors: usize) {
+        self.status = BuildStatus::Failed { stage: stage.to_string() };
+        self.errors = errors;
+    }
+
+    /// Note the codes of reported errors, before failing with them
+    pub fn add_error_codes<D: Diagnostic>(&mut self, errors: &[D]) {
+        self.error_codes.extend(errors.iter().filter_map(|error| error.code()).map(|code| code.to_string()));
+    }
+
+    /// Mark the compile as stopped by Ctrl-C during `stage`
+    pub fn interrupt(&mut self, stage: &str) {
+        self.status = BuildStatus::Interrupted { stage: stage.to_string() };
+    }
+
+    /// Record a produced file, hashing its current contents.
+    /// Files that cannot be read (e.g. already removed) are skipped.
+    pub fn add_artifact(&mut self, kind: &str, path: &Path) {
+        if let Ok(contents) = std::fs::read(path) {
+            self.artifacts.push(Artifact {
+                kind: kind.to_string(),
+                path: path.display().to_string(),
+                sha256: sha256_hex(&contents),
+            });
+        }
+    }
+
+    /// Append this record as one line of JSON to `log`, creating it if needed
+    pub fn append_to(&self, log: &Path) -> std::io::Result<()> {
+        let line = serde_json::to_string(self)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(log)?;
+        // One write per record keeps concurrent appends from interleaving
+        file.write_all(format!("{}\n", line).as_bytes())
+    }
+}
+
+/// Read every record from a build log
+pub fn read_log(log: &Path) -> std::io::Result<Vec<BuildRecord>> {
+    std::fs::read_to_string(log)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(std::io::Error::from))
+        .collect()
+}
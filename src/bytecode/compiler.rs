@@ -0,0 +1,690 @@
+// src/bytecode/compiler.rs - Type-checked AST to stack machine bytecode
+//
+// Mirrors the C code generator: deferred bodies are copied to every exit of
+// their block, operands are evaluated left to right, and `format` and the
+// other builtins give way to program functions of the same name. Values
+// carry their type at runtime, so no types are tracked here beyond the
+// declared ones that give variables their default value.
+
+use super::{FunctionCode, Instruction, Module};
+use crate::ast::*;
+use crate::control_flow::Loop;
+use crate::line_index::LineIndex;
+use crate::optimizer::OverflowMode;
+use crate::stamp::Stamp;
+use std::collections::HashMap;
+
+/// Why programs using threads can't be compiled: the VM runs one
+const NEEDS_THREADS: &str = "spawn blocks, parallel for loops, channels and atomics need threads, which only the C target has";
+
+/// The MiniLang file being compiled, for runtime errors
+struct SourceFile {
+    name: String,
+    text: String,
+    lines: LineIndex,
+}
+
+/// Compiles a checked (and possibly optimized) program into a `Module`
+#[derive(Default)]
+pub struct BytecodeCompiler {
+    source: Option<SourceFile>,
+    overflow: OverflowMode,
+    stamp: Option<Stamp>,
+}
+
+impl BytecodeCompiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `filename` and the line of each statement, for runtime errors
+    pub fn with_source(mut self, filename: &str, source: &str) -> Self {
+        self.source = Some(SourceFile {
+            name: filename.to_string(),
+            text: source.to_string(),
+            lines: LineIndex::new(source),
+        });
+        self
+    }
+
+    /// What int arithmetic does on overflow
+    pub fn with_overflow(mut self, overflow: OverflowMode) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Embed a build stamp for `minilang inspect`
+    pub fn with_stamp(mut self, stamp: Stamp) -> Self {
+        self.stamp = Some(stamp);
+        self
+    }
+
+    pub fn compile(&self, program: &Program) -> Result<Module, String> {
+        let signatures: HashMap<&str, (u16, bool)> = program.functions.iter()
+            .enumerate()
+            .map(|(index, function)| (function.name.as_str(), (index as u16, function.return_type.is_some())))
+            .collect();
+        let entry = signatures.get("main")
+            .map(|&(index, _)| index)
+            .ok_or_else(|| "the program has no main function".to_string())?;
+
+        let mut strings = StringPool::default();
+        let functions = program.functions.iter()
+            .map(|function| {
+                let emitter = Emitter {
+                    signatures: &signatures,
+                    strings: &mut strings,
+                    source: self.source.as_ref().map(|source| source.text.as_str()),
+                    lines: self.source.as_ref().map(|source| &source.lines),
+                    code: Vec::new(),
+                    line_table: Vec::new(),
+                    scopes: Vec::new(),
+                    locals: 0,
+                    local_names: Vec::new(),
+                    defer_scopes: Vec::new(),
+                    loops: Vec::new(),
+                };
+                emitter.function(function)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Module {
+            source_name: self.source.as_ref().map(|source| source.name.clone()).unwrap_or_default(),
+            stamp: self.stamp.as_ref().map(Stamp::to_line),
+            trap_overflow: self.overflow == OverflowMode::Trap,
+            strings: strings.strings,
+            functions,
+            entry,
+        })
+    }
+}
+
+#[derive(Default)]
+struct StringPool {
+    strings: Vec<String>,
+    indexes: HashMap<String, u32>,
+}
+
+impl StringPool {
+    fn index(&mut self, text: &str) -> u32 {
+        if let Some(&index) = self.indexes.get(text) {
+            return index;
+        }
+        let index = self.strings.len() as u32;
+        self.strings.push(text.to_string());
+        self.indexes.insert(text.to_string(), index);
+        index
+    }
+}
+
+/// Jumps out of the innermost loop, patched once its end is known
+struct LoopExits {
+    /// The first defer scope inside the loop
+    defer_start: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// Compiles one function
+struct Emitter<'a> {
+    /// Index and whether it returns a value, by name
+    signatures: &'a HashMap<&'a str, (u16, bool)>,
+    strings: &'a mut StringPool,
+    source: Option<&'a str>,
+    lines: Option<&'a LineIndex>,
+    code: Vec<Instruction>,
+    line_table: Vec<(u32, u32)>,
+    scopes: Vec<HashMap<String, u16>>,
+    locals: u16,
+    local_names: Vec<String>,
+    /// Deferred bodies of each open block, in the order they were deferred
+    defer_scopes: Vec<Vec<&'a Block>>,
+    loops: Vec<LoopExits>,
+}
+
+impl<'a> Emitter<'a> {
+    fn function(mut self, function: &'a Function) -> Result<FunctionCode, String> {
+        self.scopes.push(HashMap::new());
+        for param in &function.params {
+            self.declare(&param.name)?;
+        }
+        self.mark_line(&function.span);
+        self.block(&function.body)?;
+
+        // Falling off the end; a function with a return type does not get
+        // here, but its caller still finds a value
+        match &function.return_type {
+            None => {
+                self.emit(Instruction::ReturnVoid);
+            }
+            Some(typ) => {
+                self.default_value(typ);
+                self.emit(Instruction::Return);
+            }
+        }
+
+        Ok(FunctionCode {
+            name: function.name.clone(),
+            params: function.params.len() as u16,
+            locals: self.locals,
+            local_names: self.local_names,
+            returns_value: function.return_type.is_some(),
+            memo: function.has_attribute("memo"),
+            code: self.code,
+            lines: self.line_table,
+        })
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.code.push(instruction);
+        self.code.len() - 1
+    }
+
+    /// Point the jump at `at` to the next instruction
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len() as u32;
+        match &mut self.code[at] {
+            Instruction::Jump(to) | Instruction::JumpIfFalse(to) => *to = target,
+            _ => unreachable!("only jumps are patched"),
+        }
+    }
+
+    /// The code that follows belongs to the line `span` starts on
+    fn mark_line(&mut self, span: &Span) {
+        let Some(lines) = self.lines else { return };
+        let line = lines.line_of(span.start) as u32;
+        let pc = self.code.len() as u32;
+        match self.line_table.last_mut() {
+            Some((_, last)) if *last == line => {}
+            Some((start, last)) if *start == pc => *last = line,
+            _ => self.line_table.push((pc, line)),
+        }
+    }
+
+    fn declare(&mut self, name: &str) -> Result<u16, String> {
+        let slot = self.locals;
+        self.locals = self.locals.checked_add(1)
+            .ok_or_else(|| "a function has more local variables than bytecode can address".to_string())?;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), slot);
+        }
+        self.local_names.push(name.to_string());
+        Ok(slot)
+    }
+
+    fn lookup(&self, name: &str) -> Result<u16, String> {
+        self.scopes.iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .ok_or_else(|| format!("Undefined variable '{}'", name))
+    }
+
+    fn string(&mut self, text: &str) {
+        let index = self.strings.index(text);
+        self.emit(Instruction::PushStr(index));
+    }
+
+    fn default_value(&mut self, typ: &Type) {
+        match typ {
+            Type::Int => {
+                self.emit(Instruction::PushInt(0));
+            }
+            Type::Float => {
+                self.emit(Instruction::PushFloat(0.0));
+            }
+            Type::String => self.string(""),
+            Type::Bool => {
+                self.emit(Instruction::PushBool(false));
+            }
+            Type::Array(element, size) => {
+                self.default_value(element);
+                self.emit(Instruction::FillArray(*size as u32));
+            }
+            Type::Tuple(elements) => {
+                for element in elements {
+                    self.default_value(element);
+                }
+                self.emit(Instruction::MakeTuple(elements.len() as u16));
+            }
+            Type::Channel | Type::Atomic | Type::Canvas | Type::Json => {
+                unreachable!("chan, atomic, canvas and json variables always have an initializer")
+            }
+        }
+    }
+
+    // ==================== STATEMENTS ====================
+
+    /// A block, running its deferred bodies when control falls off the end
+    fn block(&mut self, block: &'a Block) -> Result<(), String> {
+        self.scopes.push(HashMap::new());
+        self.defer_scopes.push(Vec::new());
+        for statement in &block.statements {
+            self.statement(statement)?;
+        }
+        let ends_in_jump = matches!(
+            block.statements.last(),
+            Some(Statement::Return(_) | Statement::Break(_) | Statement::Continue(_))
+        );
+        if !ends_in_jump {
+            self.deferred_from(self.defer_scopes.len() - 1)?;
+        }
+        self.defer_scopes.pop();
+        self.scopes.pop();
+        Ok(())
+    }
+
+    /// The deferred bodies of every open scope from `start` inward,
+    /// innermost scope first and latest defer first within a scope
+    fn deferred_from(&mut self, start: usize) -> Result<(), String> {
+        let pending: Vec<&'a Block> = self.defer_scopes[start..].iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev().copied())
+            .collect();
+        for body in pending {
+            self.block(body)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &'a Statement) -> Result<(), String> {
+        self.mark_line(statement.span());
+        if let Statement::For(ForStmt { parallel: true, .. }) = statement {
+            return Err(NEEDS_THREADS.to_string());
+        }
+        if let Some(looped) = Loop::of(statement) {
+            return self.loop_statement(&looped, statement.span());
+        }
+        match statement {
+            Statement::Let(let_stmt) => {
+                match (&let_stmt.value, &let_stmt.typ) {
+                    (Some(value), _) => self.expression(value)?,
+                    (None, Some(typ)) => self.default_value(typ),
+                    (None, None) => return Err(format!("Cannot determine the type of '{}'", let_stmt.name)),
+                }
+                let slot = self.declare(&let_stmt.name)?;
+                self.emit(Instruction::StoreLocal(slot));
+            }
+            Statement::Const(const_stmt) => {
+                self.expression(&const_stmt.value)?;
+                let slot = self.declare(&const_stmt.name)?;
+                self.emit(Instruction::StoreLocal(slot));
+            }
+            Statement::LetTuple(let_tuple) => {
+                self.expression(&let_tuple.value)?;
+                for (index, name) in let_tuple.names.iter().enumerate() {
+                    if name == "_" {
+                        continue;
+                    }
+                    self.emit(Instruction::Dup);
+                    self.emit(Instruction::TupleGet(index as u16));
+                    let slot = self.declare(name)?;
+                    self.emit(Instruction::StoreLocal(slot));
+                }
+                self.emit(Instruction::Pop);
+            }
+            Statement::Display(display_stmt) => {
+                for expr in &display_stmt.expressions {
+                    match expr {
+                        Expression::Literal(LiteralExpr { value: Literal::InterpolatedString(parts), .. }) => {
+                            for part in parts {
+                                match part {
+                                    StringPart::Text(text) => self.string(text),
+                                    StringPart::Expression(expr) => self.expression(expr)?,
+                                }
+                                self.emit(Instruction::Print);
+                            }
+                        }
+                        _ => {
+                            self.expression(expr)?;
+                            self.emit(Instruction::Print);
+                        }
+                    }
+                }
+                if display_stmt.newline {
+                    self.string("\n");
+                    self.emit(Instruction::Print);
+                }
+            }
+            Statement::If(if_stmt) => {
+                self.expression(&if_stmt.condition)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.block(&if_stmt.then_block)?;
+                match &if_stmt.else_block {
+                    Some(else_block) => {
+                        let to_end = self.emit(Instruction::Jump(0));
+                        self.patch(to_else);
+                        self.block(else_block)?;
+                        self.patch(to_end);
+                    }
+                    None => self.patch(to_else),
+                }
+            }
+            Statement::Return(ret_stmt) => {
+                // The value is computed before deferred code can change it
+                if let Some(value) = &ret_stmt.value {
+                    self.expression(value)?;
+                }
+                self.deferred_from(0)?;
+                self.emit(if ret_stmt.value.is_some() { Instruction::Return } else { Instruction::ReturnVoid });
+            }
+            Statement::Expression(ExprStmt { expression: Expression::Call(call), .. }) if self.is_builtin(call, "assert") => {
+                // The message is only built when the condition is false
+                self.expression(&call.args[0])?;
+                self.emit(Instruction::Not);
+                let to_end = self.emit(Instruction::JumpIfFalse(0));
+                match call.args.get(1) {
+                    Some(message) => self.expression(message)?,
+                    None => self.string(&call.args[0].source_text(self.source)),
+                }
+                self.emit(Instruction::AssertFailed);
+                self.patch(to_end);
+            }
+            Statement::Expression(expr_stmt) => {
+                self.expression(&expr_stmt.expression)?;
+                if self.pushes_value(&expr_stmt.expression) {
+                    self.emit(Instruction::Pop);
+                }
+            }
+            Statement::Block(block) => self.block(block)?,
+            Statement::Break(_) | Statement::Continue(_) => {
+                let start = self.loops.last()
+                    .map(|exits| exits.defer_start)
+                    .ok_or_else(|| "break or continue outside a loop".to_string())?;
+                self.deferred_from(start)?;
+                let jump = self.emit(Instruction::Jump(0));
+                if let Some(exits) = self.loops.last_mut() {
+                    match statement {
+                        Statement::Break(_) => exits.breaks.push(jump),
+                        _ => exits.continues.push(jump),
+                    }
+                }
+            }
+            Statement::Defer(defer_stmt) => {
+                if let Some(scope) = self.defer_scopes.last_mut() {
+                    scope.push(&defer_stmt.body);
+                }
+            }
+            Statement::Spawn(_) => return Err(NEEDS_THREADS.to_string()),
+            Statement::While(_) | Statement::DoWhile(_) | Statement::For(_) => unreachable!("loops are handled above"),
+        }
+        Ok(())
+    }
+
+    /// init; [jump to body if it runs first]; top: condition; body;
+    /// continue: update; jump to top
+    fn loop_statement(&mut self, looped: &Loop<'a>, span: &Span) -> Result<(), String> {
+        // The init's variables live as long as the loop
+        self.scopes.push(HashMap::new());
+        if let Some(init) = looped.init {
+            self.statement(init)?;
+        }
+        let to_body = looped.body_first.then(|| self.emit(Instruction::Jump(0)));
+
+        let top = self.code.len() as u32;
+        self.mark_line(span);
+        let to_end = match looped.condition {
+            Some(condition) => {
+                self.expression(condition)?;
+                Some(self.emit(Instruction::JumpIfFalse(0)))
+            }
+            None => None,
+        };
+        if let Some(to_body) = to_body {
+            self.patch(to_body);
+        }
+
+        self.loops.push(LoopExits { defer_start: self.defer_scopes.len(), breaks: Vec::new(), continues: Vec::new() });
+        self.block(looped.body)?;
+        let exits = self.loops.pop().unwrap_or(LoopExits { defer_start: 0, breaks: Vec::new(), continues: Vec::new() });
+
+        for jump in exits.continues {
+            self.patch(jump);
+        }
+        self.mark_line(span);
+        if let Some(update) = looped.update {
+            self.expression(update)?;
+            if self.pushes_value(update) {
+                self.emit(Instruction::Pop);
+            }
+        }
+        self.emit(Instruction::Jump(top));
+        for jump in exits.breaks.into_iter().chain(to_end) {
+            self.patch(jump);
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    // ==================== EXPRESSIONS ====================
+
+    /// Whether `expr` leaves a value: everything but calls to functions
+    /// without a return type
+    fn pushes_value(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Call(call) if self.is_builtin(call, "sleep_ms") => false,
+            Expression::Call(call) if CANVAS_BUILTINS[1..].iter().any(|name| self.is_builtin(call, name)) => false,
+            Expression::Call(call) if ["json_set", "json_push"].iter().any(|name| self.is_builtin(call, name)) => false,
+            Expression::Call(call) => self.signatures.get(call.function.as_str()).is_none_or(|&(_, returns)| returns),
+            _ => true,
+        }
+    }
+
+    /// A builtin is called unless the program defines a function of that name
+    fn is_builtin(&self, call: &CallExpr, name: &str) -> bool {
+        call.function == name && !self.signatures.contains_key(name)
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Result<(), String> {
+        match expr {
+            Expression::Literal(literal) => self.literal(&literal.value)?,
+            Expression::Identifier(identifier) => {
+                let slot = self.lookup(&identifier.name)?;
+                self.emit(Instruction::LoadLocal(slot));
+            }
+            Expression::Binary(binary) => self.binary(binary)?,
+            Expression::Unary(unary) => {
+                self.expression(&unary.operand)?;
+                self.emit(match unary.op {
+                    UnaryOp::Not => Instruction::Not,
+                    UnaryOp::Negate => Instruction::Neg,
+                });
+            }
+            Expression::Call(call) => self.call(call)?,
+            Expression::Index(index) => {
+                self.expression(&index.array)?;
+                self.expression(&index.index)?;
+                self.emit(Instruction::Index);
+            }
+            Expression::Assign(assign) => {
+                if assign.target.starts_with("__ARRAY_INDEX__:") {
+                    // arr[index] = value is encoded as Binary(Index(arr, index), =, value)
+                    let Expression::Binary(binary) = &*assign.value else {
+                        return Err(format!("Malformed array assignment to '{}'", assign.target));
+                    };
+                    let Expression::Index(index) = &*binary.left else {
+                        return Err(format!("Malformed array assignment to '{}'", assign.target));
+                    };
+                    self.expression(&index.array)?;
+                    self.expression(&index.index)?;
+                    self.expression(&binary.right)?;
+                    self.emit(Instruction::SetIndex);
+                } else {
+                    self.expression(&assign.value)?;
+                    self.emit(Instruction::Dup);
+                    let slot = self.lookup(&assign.target)?;
+                    self.emit(Instruction::StoreLocal(slot));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &Literal) -> Result<(), String> {
+        match literal {
+            Literal::Integer(value) => {
+                self.emit(Instruction::PushInt(*value));
+            }
+            Literal::Float(value) => {
+                self.emit(Instruction::PushFloat(*value));
+            }
+            Literal::Boolean(value) => {
+                self.emit(Instruction::PushBool(*value));
+            }
+            Literal::String(text) => self.string(text),
+            Literal::Array(elements) => {
+                for element in elements {
+                    self.expression(element)?;
+                }
+                self.emit(Instruction::MakeArray(elements.len() as u32));
+            }
+            Literal::Tuple(elements) => {
+                for element in elements {
+                    self.expression(element)?;
+                }
+                self.emit(Instruction::MakeTuple(elements.len() as u16));
+            }
+            Literal::InterpolatedString(parts) => self.concat(parts)?,
+        }
+        Ok(())
+    }
+
+    /// The parts printed into one string
+    fn concat(&mut self, parts: &[StringPart]) -> Result<(), String> {
+        for part in parts {
+            match part {
+                StringPart::Text(text) => self.string(text),
+                StringPart::Expression(expr) => self.expression(expr)?,
+            }
+        }
+        self.emit(Instruction::Concat(parts.len() as u16));
+        Ok(())
+    }
+
+    fn binary(&mut self, binary: &BinaryExpr) -> Result<(), String> {
+        // The right side only runs when the left does not decide the result
+        if matches!(binary.op, BinaryOp::And | BinaryOp::Or) {
+            self.expression(&binary.left)?;
+            if binary.op == BinaryOp::Or {
+                self.emit(Instruction::Not);
+            }
+            let to_short = self.emit(Instruction::JumpIfFalse(0));
+            self.expression(&binary.right)?;
+            let to_end = self.emit(Instruction::Jump(0));
+            self.patch(to_short);
+            self.emit(Instruction::PushBool(binary.op == BinaryOp::Or));
+            self.patch(to_end);
+            return Ok(());
+        }
+
+        // Strength reduction hints only change how the C computes the same value
+        self.expression(&binary.left)?;
+        self.expression(&binary.right)?;
+        self.emit(match binary.op {
+            BinaryOp::Add => Instruction::Add,
+            BinaryOp::Subtract => Instruction::Sub,
+            BinaryOp::Multiply => Instruction::Mul,
+            BinaryOp::Divide => Instruction::Div,
+            BinaryOp::Modulo => Instruction::Mod,
+            BinaryOp::Equal => Instruction::Eq,
+            BinaryOp::NotEqual => Instruction::Ne,
+            BinaryOp::Less => Instruction::Lt,
+            BinaryOp::Greater => Instruction::Gt,
+            BinaryOp::LessEqual => Instruction::Le,
+            BinaryOp::GreaterEqual => Instruction::Ge,
+            BinaryOp::And | BinaryOp::Or => unreachable!("short-circuited above"),
+        });
+        Ok(())
+    }
+
+    fn call(&mut self, call: &CallExpr) -> Result<(), String> {
+        // Where the C would keep the string makes no difference to the VM
+        if call.function == STACK_STRING {
+            return self.expression(&call.args[0]);
+        }
+        if self.is_builtin(call, "format") {
+            return self.format_call(call);
+        }
+        if CHANNEL_BUILTINS.iter().chain(&ATOMIC_BUILTINS).any(|name| self.is_builtin(call, name)) {
+            return Err(NEEDS_THREADS.to_string());
+        }
+        for arg in &call.args {
+            self.expression(arg)?;
+        }
+        let checked = CHECKED_ARITHMETIC.iter()
+            .find(|(name, _)| self.is_builtin(call, name));
+        if let Some((_, op)) = checked {
+            self.emit(match op {
+                BinaryOp::Add => Instruction::AddChecked,
+                BinaryOp::Subtract => Instruction::SubChecked,
+                _ => Instruction::MulChecked,
+            });
+            return Ok(());
+        }
+        let builtin = ["to_string", "parse_int", "approx_equal", "sleep_ms", "now_ms"].into_iter()
+            .chain(CANVAS_BUILTINS)
+            .chain(JSON_BUILTINS)
+            .find(|name| self.is_builtin(call, name));
+        match builtin {
+            Some("to_string") => self.emit(Instruction::ToString),
+            Some("parse_int") => self.emit(Instruction::ParseInt),
+            Some("sleep_ms") => self.emit(Instruction::SleepMs),
+            Some("now_ms") => self.emit(Instruction::NowMs),
+            Some("canvas") => self.emit(Instruction::MakeCanvas),
+            Some("canvas_set") => self.emit(Instruction::CanvasSet),
+            Some("canvas_fill") => self.emit(Instruction::CanvasFill),
+            Some("canvas_render") => self.emit(Instruction::CanvasRender),
+            Some("json_parse") => self.emit(Instruction::JsonParse),
+            Some("json_stringify") => self.emit(Instruction::JsonStringify),
+            Some("json_type") => self.emit(Instruction::JsonType),
+            Some("json_get") => self.emit(Instruction::JsonGet),
+            Some("json_has") => self.emit(Instruction::JsonHas),
+            Some("json_at") => self.emit(Instruction::JsonAt),
+            Some("json_len") => self.emit(Instruction::JsonLen),
+            Some("json_int") => self.emit(Instruction::JsonInt),
+            Some("json_float") => self.emit(Instruction::JsonFloat),
+            Some("json_bool") => self.emit(Instruction::JsonBool),
+            Some("json_string") => self.emit(Instruction::JsonString),
+            Some("json_set") => self.emit(Instruction::JsonSet),
+            Some("json_push") => self.emit(Instruction::JsonPush),
+            Some(_) => self.emit(Instruction::ApproxEqual),
+            None => {
+                let &(index, _) = self.signatures.get(call.function.as_str()).ok_or_else(|| {
+                    format!("'{}' is not defined in this file; bytecode is compiled from a single file", call.function)
+                })?;
+                self.emit(Instruction::Call(index))
+            }
+        };
+        Ok(())
+    }
+
+    /// format(template, args...): each `{}` in the template takes the next argument
+    fn format_call(&mut self, call: &CallExpr) -> Result<(), String> {
+        let template = match call.args.first() {
+            Some(Expression::Literal(LiteralExpr { value: Literal::String(text), .. })) => vec![StringPart::Text(text.clone())],
+            Some(Expression::Literal(LiteralExpr { value: Literal::InterpolatedString(parts), .. })) => parts.clone(),
+            _ => return Err("format() expects a string literal template".to_string()),
+        };
+
+        let mut args = call.args[1..].iter();
+        let mut parts = Vec::new();
+        for part in template {
+            let StringPart::Text(text) = part else {
+                parts.push(part);
+                continue;
+            };
+            let mut pieces = text.split("{}");
+            if let Some(first) = pieces.next().filter(|piece| !piece.is_empty()) {
+                parts.push(StringPart::Text(first.to_string()));
+            }
+            for piece in pieces {
+                let arg = args.next()
+                    .ok_or_else(|| "format() has more placeholders than arguments".to_string())?;
+                parts.push(StringPart::Expression(arg.clone()));
+                if !piece.is_empty() {
+                    parts.push(StringPart::Text(piece.to_string()));
+                }
+            }
+        }
+        self.concat(&parts)
+    }
+}
@@ -0,0 +1,562 @@
+// src/bytecode/mod.rs - Stack machine bytecode and its file format
+//
+// `minilang compile --target=bytecode` turns a program into a `.mbc` file
+// instead of C, and `minilang vm` runs it. The file needs no C compiler and
+// runs the same on every platform, which suits handing in assignments. It
+// is compiled from the same optimized AST as the C, and the VM behaves like
+// the generated C: 32-bit ints that wrap (or trap with --overflow=trap),
+// bounds-checked arrays passed by reference, and the same display formats
+// and runtime errors. A string declared without a value is empty rather
+// than printing as "(null)".
+//
+// A file is a header (magic, format version, flags, the source's name and
+// the build stamp) followed by a string pool and the functions. Each
+// function holds the names of its locals, for `--watch-var`, its
+// instructions and a table of where each source line's code starts, for
+// runtime errors. Numbers are little-endian.
+
+pub mod compiler;
+pub mod vm;
+
+pub use compiler::BytecodeCompiler;
+
+use crate::errors::BytecodeError;
+
+/// The first bytes of every bytecode file
+pub const MAGIC: &[u8; 4] = b"MLBC";
+
+/// The format version this compiler writes and its VM runs
+pub const FORMAT_VERSION: u16 = 2;
+
+/// The file extension of bytecode files
+pub const EXTENSION: &str = "mbc";
+
+/// Header flag: int arithmetic traps on overflow instead of wrapping
+const FLAG_TRAP_OVERFLOW: u8 = 1;
+
+/// Function flag: `Return` leaves a value for the caller
+const FUNCTION_RETURNS_VALUE: u8 = 1;
+
+/// Function flag: the function is `@memo`, so the VM caches its results
+const FUNCTION_MEMO: u8 = 2;
+
+/// A compiled program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    /// The MiniLang file it was compiled from, for runtime errors
+    pub source_name: String,
+    /// The build stamp line (`stamp::Stamp::to_line`), so `minilang inspect`
+    /// reads bytecode files like executables
+    pub stamp: Option<String>,
+    /// Whether int overflow stops the program (--overflow=trap)
+    pub trap_overflow: bool,
+    /// String constants, referred to by index
+    pub strings: Vec<String>,
+    pub functions: Vec<FunctionCode>,
+    /// The function the program starts in
+    pub entry: u16,
+}
+
+/// One compiled function
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCode {
+    pub name: String,
+    /// The arguments become the first locals
+    pub params: u16,
+    pub locals: u16,
+    /// The variable each local holds, by slot. Every declaration gets a
+    /// slot of its own, so a name can appear more than once.
+    pub local_names: Vec<String>,
+    /// Whether `Return` leaves a value for the caller
+    pub returns_value: bool,
+    /// Whether results are cached by arguments (`@memo`)
+    pub memo: bool,
+    pub code: Vec<Instruction>,
+    /// `(instruction index, source line)` where each line's code starts,
+    /// by instruction index
+    pub lines: Vec<(u32, u32)>,
+}
+
+impl FunctionCode {
+    /// The source line of the instruction at `pc`, or 0 when unknown
+    pub fn line_at(&self, pc: usize) -> u32 {
+        let at = self.lines.partition_point(|&(start, _)| start as usize <= pc);
+        at.checked_sub(1).map_or(0, |index| self.lines[index].1)
+    }
+}
+
+/// A stack machine instruction. Operands are popped in the order they were
+/// pushed, e.g. `Sub` computes `second from top - top`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    PushInt(i32),
+    PushFloat(f64),
+    PushBool(bool),
+    /// Push a string from the pool
+    PushStr(u32),
+    Pop,
+    Dup,
+    LoadLocal(u16),
+    /// Pop into a local, copying arrays so the local owns its elements
+    StoreLocal(u16),
+    /// Pop this many values into a new array
+    MakeArray(u32),
+    /// Pop a value and make an array of this many copies of it
+    FillArray(u32),
+    /// Pop this many values into a tuple
+    MakeTuple(u16),
+    /// Pop an index and an array and push the element
+    Index,
+    /// Pop a value, an index and an array, store the element and push the value
+    SetIndex,
+    /// Pop a tuple and push one of its elements
+    TupleGet(u16),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Neg,
+    Not,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Jump(u32),
+    /// Pop a bool and jump if it is false
+    JumpIfFalse(u32),
+    /// Call a function, which pops its arguments
+    Call(u16),
+    /// Return the value on top of the stack
+    Return,
+    ReturnVoid,
+    /// Pop a value and print it as `display` does
+    Print,
+    /// Pop this many values and push them printed into one string
+    Concat(u16),
+    /// `to_string(int)`
+    ToString,
+    /// `parse_int(string)`
+    ParseInt,
+    /// `approx_equal(a, b, eps)`
+    ApproxEqual,
+    /// `add_checked(a, b)`: push the wrapped sum and whether it overflowed as a tuple
+    AddChecked,
+    /// `sub_checked(a, b)`
+    SubChecked,
+    /// `mul_checked(a, b)`
+    MulChecked,
+    /// Pop a string and stop with it as a failed assert's message
+    AssertFailed,
+    /// `sleep_ms(ms)`: pop the milliseconds to wait
+    SleepMs,
+    /// `now_ms()`
+    NowMs,
+    /// `canvas(width, height)`
+    MakeCanvas,
+    /// `canvas_set(c, x, y, text)`: pop the text, y, x and canvas
+    CanvasSet,
+    /// `canvas_fill(c, text)`
+    CanvasFill,
+    /// `canvas_render(c)`: pop a canvas and print its rows
+    CanvasRender,
+    /// `json_parse(text)`
+    JsonParse,
+    /// `json_stringify(j)`
+    JsonStringify,
+    /// `json_type(j)`
+    JsonType,
+    /// `json_get(j, key)`: pop the key and the object
+    JsonGet,
+    /// `json_has(j, key)`
+    JsonHas,
+    /// `json_at(j, index)`: pop the index and the array
+    JsonAt,
+    /// `json_len(j)`
+    JsonLen,
+    /// `json_int(j)`
+    JsonInt,
+    /// `json_float(j)`
+    JsonFloat,
+    /// `json_bool(j)`
+    JsonBool,
+    /// `json_string(j)`
+    JsonString,
+    /// `json_set(j, key, value)`: pop the value, the key and the object
+    JsonSet,
+    /// `json_push(j, value)`: pop the value and the array
+    JsonPush,
+}
+
+impl Module {
+    /// The module as the bytes of a `.mbc` file
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Writer::default();
+        out.bytes.extend_from_slice(MAGIC);
+        out.u16(FORMAT_VERSION);
+        out.u8(if self.trap_overflow { FLAG_TRAP_OVERFLOW } else { 0 });
+        out.string(&self.source_name);
+        // NUL-terminated, which is where `Stamp::find_all` stops reading
+        out.string(&self.stamp.as_ref().map(|stamp| format!("{}\0", stamp)).unwrap_or_default());
+        out.u16(self.entry);
+
+        out.u32(self.strings.len() as u32);
+        for string in &self.strings {
+            out.string(string);
+        }
+        out.u16(self.functions.len() as u16);
+        for function in &self.functions {
+            out.string(&function.name);
+            out.u16(function.params);
+            out.u16(function.locals);
+            for name in &function.local_names {
+                out.string(name);
+            }
+            let mut flags = 0;
+            if function.returns_value {
+                flags |= FUNCTION_RETURNS_VALUE;
+            }
+            if function.memo {
+                flags |= FUNCTION_MEMO;
+            }
+            out.u8(flags);
+            out.u32(function.code.len() as u32);
+            for instruction in &function.code {
+                out.instruction(instruction);
+            }
+            out.u32(function.lines.len() as u32);
+            for &(pc, line) in &function.lines {
+                out.u32(pc);
+                out.u32(line);
+            }
+        }
+        out.bytes
+    }
+
+    /// Read a `.mbc` file, checking that every index in it is in range so
+    /// the VM can trust them
+    pub fn decode(bytes: &[u8]) -> Result<Module, BytecodeError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(BytecodeError::NotBytecode);
+        }
+        let mut input = Reader { bytes, at: MAGIC.len() };
+        let version = input.u16()?;
+        if version != FORMAT_VERSION {
+            return Err(BytecodeError::UnsupportedVersion { found: version, supported: FORMAT_VERSION });
+        }
+        let flags = input.u8()?;
+        let source_name = input.string()?;
+        let stamp = input.string()?;
+        let stamp = (!stamp.is_empty()).then(|| stamp.trim_end_matches('\0').to_string());
+        let entry = input.u16()?;
+
+        let string_count = input.u32()?;
+        let strings = (0..string_count).map(|_| input.string()).collect::<Result<Vec<_>, _>>()?;
+        let function_count = input.u16()?;
+        let mut functions = Vec::new();
+        for _ in 0..function_count {
+            let name = input.string()?;
+            let params = input.u16()?;
+            let locals = input.u16()?;
+            let local_names = (0..locals).map(|_| input.string()).collect::<Result<Vec<_>, _>>()?;
+            let function_flags = input.u8()?;
+            let returns_value = function_flags & FUNCTION_RETURNS_VALUE != 0;
+            let memo = function_flags & FUNCTION_MEMO != 0;
+            let code_len = input.u32()?;
+            let code = (0..code_len).map(|_| input.instruction()).collect::<Result<Vec<_>, _>>()?;
+            let line_count = input.u32()?;
+            let lines = (0..line_count)
+                .map(|_| Ok((input.u32()?, input.u32()?)))
+                .collect::<Result<Vec<_>, BytecodeError>>()?;
+            functions.push(FunctionCode { name, params, locals, local_names, returns_value, memo, code, lines });
+        }
+        if input.at != bytes.len() {
+            return Err(input.corrupt("unexpected bytes after the last function"));
+        }
+
+        let module = Module {
+            source_name,
+            stamp,
+            trap_overflow: flags & FLAG_TRAP_OVERFLOW != 0,
+            strings,
+            functions,
+            entry,
+        };
+        module.validate().map_err(|reason| BytecodeError::Corrupt { offset: bytes.len(), reason })?;
+        Ok(module)
+    }
+
+    /// Every jump, local, function and string index in range
+    fn validate(&self) -> Result<(), String> {
+        if self.entry as usize >= self.functions.len() {
+            return Err(format!("the entry point is function {} of {}", self.entry, self.functions.len()));
+        }
+        for function in &self.functions {
+            if function.params > function.locals {
+                return Err(format!("'{}' has more parameters than locals", function.name));
+            }
+            if function.local_names.len() != function.locals as usize {
+                return Err(format!("'{}' does not name each of its locals", function.name));
+            }
+            for instruction in &function.code {
+                let in_range = match *instruction {
+                    Instruction::PushStr(index) => (index as usize) < self.strings.len(),
+                    Instruction::LoadLocal(slot) | Instruction::StoreLocal(slot) => slot < function.locals,
+                    Instruction::Jump(target) | Instruction::JumpIfFalse(target) => (target as usize) <= function.code.len(),
+                    Instruction::Call(index) => (index as usize) < self.functions.len(),
+                    _ => true,
+                };
+                if !in_range {
+                    return Err(format!("'{}' has an instruction out of range: {:?}", function.name, instruction));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// ==================== ENCODING ====================
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        use Instruction::*;
+        match *instruction {
+            PushInt(value) => {
+                self.u8(0x01);
+                self.bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            PushFloat(value) => {
+                self.u8(0x02);
+                self.bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+            }
+            PushBool(value) => {
+                self.u8(0x03);
+                self.u8(value as u8);
+            }
+            PushStr(index) => {
+                self.u8(0x04);
+                self.u32(index);
+            }
+            Pop => self.u8(0x05),
+            Dup => self.u8(0x06),
+            LoadLocal(slot) => {
+                self.u8(0x07);
+                self.u16(slot);
+            }
+            StoreLocal(slot) => {
+                self.u8(0x08);
+                self.u16(slot);
+            }
+            MakeArray(count) => {
+                self.u8(0x09);
+                self.u32(count);
+            }
+            FillArray(count) => {
+                self.u8(0x0a);
+                self.u32(count);
+            }
+            MakeTuple(count) => {
+                self.u8(0x0b);
+                self.u16(count);
+            }
+            Index => self.u8(0x0c),
+            SetIndex => self.u8(0x0d),
+            TupleGet(index) => {
+                self.u8(0x0e);
+                self.u16(index);
+            }
+            Add => self.u8(0x10),
+            Sub => self.u8(0x11),
+            Mul => self.u8(0x12),
+            Div => self.u8(0x13),
+            Mod => self.u8(0x14),
+            Neg => self.u8(0x15),
+            Not => self.u8(0x16),
+            Eq => self.u8(0x17),
+            Ne => self.u8(0x18),
+            Lt => self.u8(0x19),
+            Gt => self.u8(0x1a),
+            Le => self.u8(0x1b),
+            Ge => self.u8(0x1c),
+            Jump(target) => {
+                self.u8(0x20);
+                self.u32(target);
+            }
+            JumpIfFalse(target) => {
+                self.u8(0x21);
+                self.u32(target);
+            }
+            Call(index) => {
+                self.u8(0x22);
+                self.u16(index);
+            }
+            Return => self.u8(0x23),
+            ReturnVoid => self.u8(0x24),
+            Print => self.u8(0x30),
+            Concat(count) => {
+                self.u8(0x31);
+                self.u16(count);
+            }
+            ToString => self.u8(0x32),
+            ParseInt => self.u8(0x33),
+            ApproxEqual => self.u8(0x34),
+            AddChecked => self.u8(0x35),
+            SubChecked => self.u8(0x36),
+            MulChecked => self.u8(0x37),
+            AssertFailed => self.u8(0x38),
+            SleepMs => self.u8(0x39),
+            NowMs => self.u8(0x3a),
+            MakeCanvas => self.u8(0x3b),
+            CanvasSet => self.u8(0x3c),
+            CanvasFill => self.u8(0x3d),
+            CanvasRender => self.u8(0x3e),
+            JsonParse => self.u8(0x3f),
+            JsonStringify => self.u8(0x40),
+            JsonType => self.u8(0x41),
+            JsonGet => self.u8(0x42),
+            JsonHas => self.u8(0x43),
+            JsonAt => self.u8(0x44),
+            JsonLen => self.u8(0x45),
+            JsonInt => self.u8(0x46),
+            JsonFloat => self.u8(0x47),
+            JsonBool => self.u8(0x48),
+            JsonString => self.u8(0x49),
+            JsonSet => self.u8(0x4a),
+            JsonPush => self.u8(0x4b),
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn corrupt(&self, reason: &str) -> BytecodeError {
+        BytecodeError::Corrupt { offset: self.at, reason: reason.to_string() }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], BytecodeError> {
+        let bytes = self.bytes.get(self.at..self.at + N)
+            .ok_or_else(|| self.corrupt("the file ends too early"))?;
+        self.at += N;
+        Ok(bytes.try_into().unwrap_or([0; N]))
+    }
+
+    fn u8(&mut self) -> Result<u8, BytecodeError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, BytecodeError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, BytecodeError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn string(&mut self) -> Result<String, BytecodeError> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes.get(self.at..self.at.saturating_add(len))
+            .ok_or_else(|| self.corrupt("the file ends too early"))?;
+        let text = std::str::from_utf8(bytes).map_err(|_| self.corrupt("a string is not valid UTF-8"))?;
+        self.at += len;
+        Ok(text.to_string())
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, BytecodeError> {
+        use Instruction::*;
+        let opcode = self.u8()?;
+        Ok(match opcode {
+            0x01 => PushInt(i32::from_le_bytes(self.take()?)),
+            0x02 => PushFloat(f64::from_bits(u64::from_le_bytes(self.take()?))),
+            0x03 => PushBool(self.u8()? != 0),
+            0x04 => PushStr(self.u32()?),
+            0x05 => Pop,
+            0x06 => Dup,
+            0x07 => LoadLocal(self.u16()?),
+            0x08 => StoreLocal(self.u16()?),
+            0x09 => MakeArray(self.u32()?),
+            0x0a => FillArray(self.u32()?),
+            0x0b => MakeTuple(self.u16()?),
+            0x0c => Index,
+            0x0d => SetIndex,
+            0x0e => TupleGet(self.u16()?),
+            0x10 => Add,
+            0x11 => Sub,
+            0x12 => Mul,
+            0x13 => Div,
+            0x14 => Mod,
+            0x15 => Neg,
+            0x16 => Not,
+            0x17 => Eq,
+            0x18 => Ne,
+            0x19 => Lt,
+            0x1a => Gt,
+            0x1b => Le,
+            0x1c => Ge,
+            0x20 => Jump(self.u32()?),
+            0x21 => JumpIfFalse(self.u32()?),
+            0x22 => Call(self.u16()?),
+            0x23 => Return,
+            0x24 => ReturnVoid,
+            0x30 => Print,
+            0x31 => Concat(self.u16()?),
+            0x32 => ToString,
+            0x33 => ParseInt,
+            0x34 => ApproxEqual,
+            0x35 => AddChecked,
+            0x36 => SubChecked,
+            0x37 => MulChecked,
+            0x38 => AssertFailed,
+            0x39 => SleepMs,
+            0x3a => NowMs,
+            0x3b => MakeCanvas,
+            0x3c => CanvasSet,
+            0x3d => CanvasFill,
+            0x3e => CanvasRender,
+            0x3f => JsonParse,
+            0x40 => JsonStringify,
+            0x41 => JsonType,
+            0x42 => JsonGet,
+            0x43 => JsonHas,
+            0x44 => JsonAt,
+            0x45 => JsonLen,
+            0x46 => JsonInt,
+            0x47 => JsonFloat,
+            0x48 => JsonBool,
+            0x49 => JsonString,
+            0x4a => JsonSet,
+            0x4b => JsonPush,
+            _ => {
+                self.at -= 1;
+                return Err(self.corrupt(&format!("unknown opcode 0x{:02x}", opcode)));
+            }
+        })
+    }
+}
@@ -0,0 +1,908 @@
+// src/bytecode/vm.rs - Running bytecode
+//
+// One value stack and one locals stack shared by every frame, so deep
+// recursion costs heap instead of the host's native stack. Arrays are
+// shared between caller and callee like the C pointers they stand for and
+// copied when stored into a variable, like the C's memcpy.
+//
+// `minilang vm --watch-var NAME` reports each write to a variable of that
+// name, in any function, with where it happened and the value it replaced:
+// a way to follow one misbehaving variable without tracing everything.
+//
+// `now_ms` and `sleep_ms` read a `Clock`. The real one is the host's; the
+// virtual one starts at 0 and only moves when the program sleeps, without
+// waiting, so `minilang test` and `minilang vm --virtual-clock` print the
+// same on every run however slow the machine is.
+
+use super::{Instruction, Module};
+use crate::json::{self, Json, Node};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Calls deeper than this stop the program the way a native stack overflow does
+pub const MAX_CALL_DEPTH: usize = 100_000;
+
+/// Arrays larger than this stop the program with an out of memory error
+const MAX_ARRAY_LEN: usize = 1 << 28;
+
+/// A runtime value
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    Str(Rc<str>),
+    Array(Rc<RefCell<Vec<Value>>>),
+    Tuple(Rc<[Value]>),
+    /// Shared by every copy, like the C's pointer
+    Canvas(Rc<RefCell<Canvas>>),
+    /// Shared the same way, down to the values inside it
+    Json(Json),
+}
+
+/// A grid of characters, row by row from the top left
+#[derive(Debug)]
+pub struct Canvas {
+    width: usize,
+    cells: Vec<char>,
+}
+
+impl Value {
+    /// A copy whose arrays (including those inside tuples) are not shared
+    fn deep_copy(&self) -> Value {
+        match self {
+            Value::Array(elements) => Value::Array(Rc::new(RefCell::new(
+                elements.borrow().iter().map(Value::deep_copy).collect(),
+            ))),
+            Value::Tuple(elements) => Value::Tuple(elements.iter().map(Value::deep_copy).collect()),
+            other => other.clone(),
+        }
+    }
+
+    fn write_to(&self, out: &mut String) {
+        match self {
+            Value::Int(value) => out.push_str(&value.to_string()),
+            Value::Float(value) => out.push_str(&format_float(*value)),
+            Value::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+            Value::Str(text) => out.push_str(text),
+            Value::Array(elements) => {
+                out.push('[');
+                for (index, element) in elements.borrow().iter().enumerate() {
+                    if index > 0 {
+                        out.push_str(", ");
+                    }
+                    element.write_to(out);
+                }
+                out.push(']');
+            }
+            Value::Tuple(elements) => {
+                out.push('(');
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        out.push_str(", ");
+                    }
+                    element.write_to(out);
+                }
+                out.push(')');
+            }
+            // Only --watch-var shows one
+            Value::Canvas(canvas) => {
+                let canvas = canvas.borrow();
+                out.push_str(&format!("canvas {}x{}", canvas.width, canvas.cells.len() / canvas.width));
+            }
+            Value::Json(value) => out.push_str(&json::stringify(value)),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => Rc::ptr_eq(a, b) || *a.borrow() == *b.borrow(),
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Canvas(a), Value::Canvas(b)) => Rc::ptr_eq(a, b),
+            (Value::Json(a), Value::Json(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// `printf("%.6f")`
+fn format_float(value: f64) -> String {
+    match value {
+        _ if value.is_nan() => if value.is_sign_negative() { "-nan" } else { "nan" }.to_string(),
+        _ if value.is_infinite() => if value < 0.0 { "-inf" } else { "inf" }.to_string(),
+        _ => format!("{:.6}", value),
+    }
+}
+
+/// A runtime error that stops the program, like the generated C's
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trap {
+    pub message: String,
+    /// `file:line`, when the module has line information
+    pub location: Option<String>,
+    /// What the native program would exit with
+    pub exit_code: i32,
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Runtime Error: {}", self.message)?;
+        if let Some(location) = &self.location {
+            write!(f, "\n  at {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// What stopped an instruction, before the VM knows where it was
+enum Fault {
+    /// A runtime error of the program
+    Error { message: String, exit_code: i32 },
+    /// A module the compiler could not have made
+    Invalid(&'static str),
+}
+
+fn error(message: impl Into<String>, exit_code: i32) -> Fault {
+    Fault::Error { message: message.into(), exit_code }
+}
+
+fn bounds_error(index: i32, size: usize) -> Fault {
+    error(format!("Array index {} out of bounds (size {})", index, size), 1)
+}
+
+fn arithmetic_error() -> Fault {
+    // SIGFPE
+    error("arithmetic error (division by zero)", 128 + 8)
+}
+
+fn overflow_error() -> Fault {
+    // -ftrapv aborts: SIGABRT
+    error("integer overflow", 128 + 6)
+}
+
+struct Frame {
+    function: usize,
+    pc: usize,
+    /// Where the frame's locals start in the locals stack
+    base: usize,
+    /// The arguments of a call to a `@memo` function, to cache its result under
+    memo_key: Option<Vec<MemoKey>>,
+}
+
+/// One argument of a `@memo` function as part of a cache key. Floats are
+/// compared by their bits, like the C compares their bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum MemoKey {
+    Int(i32),
+    Float(u64),
+    Bool(bool),
+}
+
+impl MemoKey {
+    fn new(value: &Value) -> Option<MemoKey> {
+        match value {
+            Value::Int(value) => Some(MemoKey::Int(*value)),
+            Value::Float(value) => Some(MemoKey::Float(value.to_bits())),
+            Value::Bool(value) => Some(MemoKey::Bool(*value)),
+            _ => None,
+        }
+    }
+}
+
+/// Where `now_ms` and `sleep_ms` get the time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    /// The host's clock: `sleep_ms` really waits
+    Real,
+    /// Starts at 0 and moves only by what `sleep_ms` asks for, at once
+    Virtual,
+}
+
+/// Run `module`, printing to `out`. Returns the exit code of `main`.
+pub fn run(module: &Module, out: &mut dyn Write) -> Result<i32, Trap> {
+    run_watching(module, out, &[], &mut io::sink())
+}
+
+/// `run`, describing every write to a variable named in `watched`, or to
+/// an element of one, on a line of `log`. The program's output so far is
+/// written to `out` before each line, so the two interleave correctly when
+/// they share a terminal.
+pub fn run_watching(module: &Module, out: &mut dyn Write, watched: &[String], log: &mut dyn Write) -> Result<i32, Trap> {
+    run_with_clock(module, out, Clock::Real, watched, log)
+}
+
+/// `run_watching` on `clock`
+pub fn run_with_clock(module: &Module, out: &mut dyn Write, clock: Clock, watched: &[String], log: &mut dyn Write) -> Result<i32, Trap> {
+    let watch = (!watched.is_empty()).then(|| Watch {
+        slots: module.functions.iter()
+            .map(|function| function.local_names.iter().map(|name| watched.contains(name)).collect())
+            .collect(),
+        written: Vec::new(),
+        log,
+    });
+    let mut vm = Vm {
+        module,
+        out,
+        stack: Vec::new(),
+        locals: Vec::new(),
+        frames: Vec::new(),
+        printed: String::new(),
+        memos: HashMap::new(),
+        watch,
+        clock,
+        started: Instant::now(),
+        slept: 0,
+    };
+    let result = vm.execute();
+    let flushed = vm.flush();
+    match (result, flushed) {
+        (Err(trap), _) => Err(trap),
+        (Ok(_), Err(error)) => Err(Trap { message: format!("cannot write output: {}", error), location: None, exit_code: 1 }),
+        (Ok(code), Ok(())) => Ok(code),
+    }
+}
+
+struct Vm<'a> {
+    module: &'a Module,
+    out: &'a mut dyn Write,
+    stack: Vec<Value>,
+    locals: Vec<Value>,
+    frames: Vec<Frame>,
+    /// Output not yet written to `out`
+    printed: String,
+    /// Results of `@memo` functions, by function and arguments. Unlike the
+    /// C's fixed-size tables nothing is evicted.
+    memos: HashMap<(usize, Vec<MemoKey>), Value>,
+    watch: Option<Watch<'a>>,
+    clock: Clock,
+    /// When the program started, for the real clock
+    started: Instant,
+    /// Milliseconds slept so far, the whole of the virtual clock
+    slept: u64,
+}
+
+/// What `--watch-var` needs while the program runs
+struct Watch<'a> {
+    /// Whether each local of each function is watched, by function and slot
+    slots: Vec<Vec<bool>>,
+    /// Whether each local on the locals stack has a value yet, so its first
+    /// write is not reported as replacing the default
+    written: Vec<bool>,
+    log: &'a mut dyn Write,
+}
+
+impl Vm<'_> {
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.write_all(self.printed.as_bytes())?;
+        self.printed.clear();
+        self.out.flush()
+    }
+
+    fn execute(&mut self) -> Result<i32, Trap> {
+        self.enter(self.module.entry as usize).map_err(|fault| self.trap(fault))?;
+        loop {
+            match self.step() {
+                Ok(Some(code)) => return Ok(code),
+                Ok(None) => {}
+                Err(fault) => return Err(self.trap(fault)),
+            }
+            if self.printed.len() >= 8192 {
+                // Output so far survives a later runtime error, as with stdio
+                self.flush().map_err(|error| Trap { message: format!("cannot write output: {}", error), location: None, exit_code: 1 })?;
+            }
+        }
+    }
+
+    /// Where the current instruction came from
+    fn trap(&self, fault: Fault) -> Trap {
+        let location = self.frames.last().and_then(|frame| {
+            let function = &self.module.functions[frame.function];
+            let line = function.line_at(frame.pc.saturating_sub(1));
+            (line > 0).then(|| format!("{}:{}", self.module.source_name, line))
+        });
+        match fault {
+            Fault::Error { message, exit_code } => Trap { message, location, exit_code },
+            Fault::Invalid(reason) => Trap { message: format!("invalid bytecode: {}", reason), location, exit_code: 1 },
+        }
+    }
+
+    /// Start a call of `function`, whose arguments are on the stack
+    fn enter(&mut self, function: usize) -> Result<(), Fault> {
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            // SIGSEGV
+            return Err(error("invalid memory access (stack overflow?)", 128 + 11));
+        }
+        let code = &self.module.functions[function];
+        let params = code.params as usize;
+        if self.stack.len() < params {
+            return Err(Fault::Invalid("a call is missing arguments"));
+        }
+        let memo_key = if code.memo {
+            let key = self.stack[self.stack.len() - params..].iter()
+                .map(MemoKey::new)
+                .collect::<Option<Vec<_>>>()
+                .ok_or(Fault::Invalid("a @memo function has an argument that is not a number or bool"))?;
+            if let Some(result) = self.memos.get(&(function, key.clone())) {
+                let result = result.clone();
+                self.stack.truncate(self.stack.len() - params);
+                self.stack.push(result);
+                return Ok(());
+            }
+            Some(key)
+        } else {
+            None
+        };
+        let base = self.locals.len();
+        self.locals.extend(self.stack.drain(self.stack.len() - params..));
+        self.locals.resize(base + code.locals as usize, Value::Int(0));
+        if let Some(watch) = &mut self.watch {
+            watch.written.resize(base + params, true);
+            watch.written.resize(base + code.locals as usize, false);
+        }
+        self.frames.push(Frame { function, pc: 0, base, memo_key });
+        Ok(())
+    }
+
+    /// Leave the current function. Returns the exit code once `main` returns.
+    fn leave(&mut self, value: Option<Value>) -> Option<i32> {
+        let frame = self.frames.pop()?;
+        self.locals.truncate(frame.base);
+        if let (Some(key), Some(value)) = (frame.memo_key, &value) {
+            self.memos.insert((frame.function, key), value.clone());
+        }
+        if let Some(watch) = &mut self.watch {
+            watch.written.truncate(frame.base);
+        }
+        if self.frames.is_empty() {
+            return Some(match value {
+                Some(Value::Int(code)) => code,
+                _ => 0,
+            });
+        }
+        if let Some(value) = value {
+            self.stack.push(value);
+        }
+        None
+    }
+
+    fn pop(&mut self) -> Result<Value, Fault> {
+        self.stack.pop().ok_or(Fault::Invalid("the stack is empty"))
+    }
+
+    fn pop_n(&mut self, count: usize) -> Result<Vec<Value>, Fault> {
+        if self.stack.len() < count {
+            return Err(Fault::Invalid("the stack is empty"));
+        }
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+
+    fn pop_int(&mut self) -> Result<i32, Fault> {
+        match self.pop()? {
+            Value::Int(value) => Ok(value),
+            _ => Err(Fault::Invalid("expected an int")),
+        }
+    }
+
+    fn pop_float(&mut self) -> Result<f64, Fault> {
+        match self.pop()? {
+            Value::Float(value) => Ok(value),
+            _ => Err(Fault::Invalid("expected a float")),
+        }
+    }
+
+    fn pop_bool(&mut self) -> Result<bool, Fault> {
+        match self.pop()? {
+            Value::Bool(value) => Ok(value),
+            _ => Err(Fault::Invalid("expected a bool")),
+        }
+    }
+
+    fn pop_array(&mut self) -> Result<Rc<RefCell<Vec<Value>>>, Fault> {
+        match self.pop()? {
+            Value::Array(elements) => Ok(elements),
+            _ => Err(Fault::Invalid("expected an array")),
+        }
+    }
+
+    /// Pop the one-character string a canvas cell is set to
+    fn pop_cell(&mut self) -> Result<char, Fault> {
+        let text = match self.pop()? {
+            Value::Str(text) => text,
+            _ => return Err(Fault::Invalid("expected a string")),
+        };
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(cell), None) => Ok(cell),
+            _ => Err(error(format!("a canvas cell holds one character, not \"{}\"", text), 1)),
+        }
+    }
+
+    fn pop_canvas(&mut self) -> Result<Rc<RefCell<Canvas>>, Fault> {
+        match self.pop()? {
+            Value::Canvas(canvas) => Ok(canvas),
+            _ => Err(Fault::Invalid("expected a canvas")),
+        }
+    }
+
+    fn pop_str(&mut self) -> Result<Rc<str>, Fault> {
+        match self.pop()? {
+            Value::Str(text) => Ok(text),
+            _ => Err(Fault::Invalid("expected a string")),
+        }
+    }
+
+    /// Pop the value json_set or json_push stores, as a json value of its own
+    fn pop_json_item(&mut self) -> Result<Json, Fault> {
+        let node = match self.pop()? {
+            Value::Int(value) => Node::Number(value as f64),
+            Value::Float(value) => Node::Number(value),
+            Value::Bool(value) => Node::Bool(value),
+            Value::Str(text) => Node::String(text.to_string()),
+            Value::Json(value) => return Ok(json::deep_copy(&value)),
+            _ => return Err(Fault::Invalid("expected a value json can hold")),
+        };
+        Ok(node.into_json())
+    }
+
+    fn pop_json(&mut self) -> Result<Json, Fault> {
+        match self.pop()? {
+            Value::Json(value) => Ok(value),
+            _ => Err(Fault::Invalid("expected a json value")),
+        }
+    }
+
+    /// Pop a json value, which must be a `kind`, like the C's
+    /// `_minilang_json_expect`
+    fn pop_json_kind(&mut self, kind: &str) -> Result<Json, Fault> {
+        let value = self.pop_json()?;
+        let found = value.borrow().kind();
+        if found != kind {
+            return Err(error(format!("expected a json {}, found {}", kind, found), 1));
+        }
+        Ok(value)
+    }
+
+    /// Run one instruction. Returns the exit code once `main` returns.
+    fn step(&mut self) -> Result<Option<i32>, Fault> {
+        let frame = self.frames.last_mut().ok_or(Fault::Invalid("no function is running"))?;
+        let function = &self.module.functions[frame.function];
+        let Some(&instruction) = function.code.get(frame.pc) else {
+            return Err(Fault::Invalid("ran past the end of a function"));
+        };
+        frame.pc += 1;
+        let base = frame.base;
+        let current = frame.function;
+
+        match instruction {
+            Instruction::PushInt(value) => self.stack.push(Value::Int(value)),
+            Instruction::PushFloat(value) => self.stack.push(Value::Float(value)),
+            Instruction::PushBool(value) => self.stack.push(Value::Bool(value)),
+            Instruction::PushStr(index) => {
+                self.stack.push(Value::Str(self.module.strings[index as usize].as_str().into()));
+            }
+            Instruction::Pop => {
+                self.pop()?;
+            }
+            Instruction::Dup => {
+                let top = self.stack.last().cloned().ok_or(Fault::Invalid("the stack is empty"))?;
+                self.stack.push(top);
+            }
+            Instruction::LoadLocal(slot) => self.stack.push(self.locals[base + slot as usize].clone()),
+            Instruction::StoreLocal(slot) => {
+                let value = self.pop()?.deep_copy();
+                if self.watch.as_ref().is_some_and(|watch| watch.slots[current][slot as usize]) {
+                    self.report_local_write(current, base, slot as usize, &value)?;
+                }
+                self.locals[base + slot as usize] = value;
+            }
+            Instruction::MakeArray(count) => {
+                let elements = self.pop_n(count as usize)?;
+                self.stack.push(Value::Array(Rc::new(RefCell::new(elements))));
+            }
+            Instruction::FillArray(count) => {
+                let value = self.pop()?;
+                if count as usize > MAX_ARRAY_LEN {
+                    return Err(error("out of memory", 1));
+                }
+                let elements = (0..count).map(|_| value.deep_copy()).collect();
+                self.stack.push(Value::Array(Rc::new(RefCell::new(elements))));
+            }
+            Instruction::MakeTuple(count) => {
+                let elements = self.pop_n(count as usize)?;
+                self.stack.push(Value::Tuple(elements.into()));
+            }
+            Instruction::Index => {
+                let index = self.pop_int()?;
+                let array = self.pop_array()?;
+                let elements = array.borrow();
+                let element = usize::try_from(index).ok()
+                    .and_then(|at| elements.get(at))
+                    .ok_or_else(|| bounds_error(index, elements.len()))?;
+                self.stack.push(element.clone());
+            }
+            Instruction::SetIndex => {
+                let value = self.pop()?;
+                let index = self.pop_int()?;
+                let array = self.pop_array()?;
+                let mut elements = array.borrow_mut();
+                let size = elements.len();
+                let element = usize::try_from(index).ok()
+                    .and_then(|at| elements.get_mut(at))
+                    .ok_or_else(|| bounds_error(index, size))?;
+                let old = std::mem::replace(element, value.deep_copy());
+                drop(elements);
+                if self.watch.is_some() {
+                    self.report_element_write(current, base, &array, index, &old, &value)?;
+                }
+                self.stack.push(value);
+            }
+            Instruction::TupleGet(index) => match self.pop()? {
+                Value::Tuple(elements) => {
+                    let element = elements.get(index as usize).ok_or(Fault::Invalid("tuple index out of range"))?;
+                    self.stack.push(element.clone());
+                }
+                _ => return Err(Fault::Invalid("expected a tuple")),
+            },
+            Instruction::Add | Instruction::Sub | Instruction::Mul | Instruction::Div | Instruction::Mod => {
+                let right = self.pop()?;
+                let left = self.pop()?;
+                let result = self.arithmetic(instruction, left, right)?;
+                self.stack.push(result);
+            }
+            Instruction::Neg => match self.pop()? {
+                Value::Int(value) if self.module.trap_overflow => {
+                    self.stack.push(Value::Int(value.checked_neg().ok_or_else(overflow_error)?));
+                }
+                Value::Int(value) => self.stack.push(Value::Int(value.wrapping_neg())),
+                Value::Float(value) => self.stack.push(Value::Float(-value)),
+                _ => return Err(Fault::Invalid("expected a number")),
+            },
+            Instruction::Not => {
+                let value = self.pop_bool()?;
+                self.stack.push(Value::Bool(!value));
+            }
+            Instruction::Eq | Instruction::Ne => {
+                let right = self.pop()?;
+                let left = self.pop()?;
+                self.stack.push(Value::Bool((left == right) == (instruction == Instruction::Eq)));
+            }
+            Instruction::Lt | Instruction::Gt | Instruction::Le | Instruction::Ge => {
+                let right = self.pop()?;
+                let left = self.pop()?;
+                let ordering = match (&left, &right) {
+                    (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+                    (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+                    _ => return Err(Fault::Invalid("expected numbers to compare")),
+                };
+                // NaN compares false both ways, as in C
+                let result = ordering.is_some_and(|ordering| match instruction {
+                    Instruction::Lt => ordering.is_lt(),
+                    Instruction::Gt => ordering.is_gt(),
+                    Instruction::Le => ordering.is_le(),
+                    _ => ordering.is_ge(),
+                });
+                self.stack.push(Value::Bool(result));
+            }
+            Instruction::Jump(target) => self.jump(target),
+            Instruction::JumpIfFalse(target) => {
+                if !self.pop_bool()? {
+                    self.jump(target);
+                }
+            }
+            Instruction::Call(index) => self.enter(index as usize)?,
+            Instruction::Return => {
+                let value = self.pop()?;
+                return Ok(self.leave(Some(value)));
+            }
+            Instruction::ReturnVoid => return Ok(self.leave(None)),
+            Instruction::Print => {
+                let value = self.pop()?;
+                value.write_to(&mut self.printed);
+            }
+            Instruction::Concat(count) => {
+                let parts = self.pop_n(count as usize)?;
+                let mut text = String::new();
+                for part in &parts {
+                    part.write_to(&mut text);
+                }
+                self.stack.push(Value::Str(text.into()));
+            }
+            Instruction::ToString => {
+                let value = self.pop_int()?;
+                self.stack.push(Value::Str(value.to_string().into()));
+            }
+            Instruction::ParseInt => {
+                let text = match self.pop()? {
+                    Value::Str(text) => text,
+                    _ => return Err(Fault::Invalid("expected a string")),
+                };
+                let value = parse_int(&text)
+                    .ok_or_else(|| error(format!("cannot parse \"{}\" as int", text), 1))?;
+                self.stack.push(Value::Int(value));
+            }
+            Instruction::ApproxEqual => {
+                let eps = self.pop_float()?;
+                let b = self.pop_float()?;
+                let a = self.pop_float()?;
+                self.stack.push(Value::Bool((a - b).abs() <= eps));
+            }
+            Instruction::AssertFailed => {
+                let message = match self.pop()? {
+                    Value::Str(message) => message,
+                    _ => return Err(Fault::Invalid("expected a string")),
+                };
+                return Err(error(format!("assertion failed: {}", message), 1));
+            }
+            Instruction::AddChecked | Instruction::SubChecked | Instruction::MulChecked => {
+                let b = self.pop_int()?;
+                let a = self.pop_int()?;
+                // Never traps, whatever --overflow says; overflowing is the answer
+                let (value, overflowed) = match instruction {
+                    Instruction::AddChecked => a.overflowing_add(b),
+                    Instruction::SubChecked => a.overflowing_sub(b),
+                    _ => a.overflowing_mul(b),
+                };
+                self.stack.push(Value::Tuple([Value::Int(value), Value::Bool(overflowed)].into()));
+            }
+            Instruction::SleepMs => {
+                // Negative waits are no wait, as in the C
+                let ms = self.pop_int()?.max(0) as u64;
+                if self.clock == Clock::Real {
+                    // Show what was printed before the pause, e.g. an animation frame
+                    self.flush().map_err(|e| error(format!("cannot write output: {}", e), 1))?;
+                    thread::sleep(Duration::from_millis(ms));
+                }
+                self.slept += ms;
+            }
+            Instruction::NowMs => {
+                let ms = match self.clock {
+                    Clock::Real => self.started.elapsed().as_millis() as u64,
+                    Clock::Virtual => self.slept,
+                };
+                // Wraps after about 24 days, like the C's int
+                self.stack.push(Value::Int(ms as i32));
+            }
+            Instruction::MakeCanvas => {
+                let height = self.pop_int()?;
+                let width = self.pop_int()?;
+                if width < 1 || height < 1 {
+                    return Err(error(format!("a canvas must be at least 1x1, not {}x{}", width, height), 1));
+                }
+                let size = (width as usize).checked_mul(height as usize).filter(|&size| size <= MAX_ARRAY_LEN)
+                    .ok_or_else(|| error("out of memory", 1))?;
+                let canvas = Canvas { width: width as usize, cells: vec![' '; size] };
+                self.stack.push(Value::Canvas(Rc::new(RefCell::new(canvas))));
+            }
+            Instruction::CanvasSet => {
+                let cell = self.pop_cell()?;
+                let y = self.pop_int()?;
+                let x = self.pop_int()?;
+                let canvas = self.pop_canvas()?;
+                let mut canvas = canvas.borrow_mut();
+                let (width, height) = (canvas.width, canvas.cells.len() / canvas.width);
+                if x < 0 || x as usize >= width || y < 0 || y as usize >= height {
+                    return Err(error(format!("cell ({}, {}) is outside the {}x{} canvas", x, y, width, height), 1));
+                }
+                canvas.cells[y as usize * width + x as usize] = cell;
+            }
+            Instruction::CanvasFill => {
+                let cell = self.pop_cell()?;
+                self.pop_canvas()?.borrow_mut().cells.fill(cell);
+            }
+            Instruction::JsonParse => {
+                let text = self.pop_str()?;
+                let value = json::parse(&text).map_err(|at| error(format!("invalid json at byte {}", at), 1))?;
+                self.stack.push(Value::Json(value));
+            }
+            Instruction::JsonStringify => {
+                let value = self.pop_json()?;
+                self.stack.push(Value::Str(json::stringify(&value).into()));
+            }
+            Instruction::JsonType => {
+                let value = self.pop_json()?;
+                let kind = value.borrow().kind();
+                self.stack.push(Value::Str(kind.into()));
+            }
+            Instruction::JsonGet | Instruction::JsonHas => {
+                let key = self.pop_str()?;
+                let object = self.pop_json_kind("object")?;
+                let found = match &*object.borrow() {
+                    Node::Object(members) => members.iter().find(|(name, _)| **name == *key).map(|(_, item)| item.clone()),
+                    _ => None,
+                };
+                self.stack.push(match (instruction, found) {
+                    (Instruction::JsonHas, found) => Value::Bool(found.is_some()),
+                    (_, Some(item)) => Value::Json(item),
+                    (_, None) => return Err(error(format!("json object has no key \"{}\"", key), 1)),
+                });
+            }
+            Instruction::JsonAt => {
+                let index = self.pop_int()?;
+                let array = self.pop_json_kind("array")?;
+                let item = match &*array.borrow() {
+                    Node::Array(items) => usize::try_from(index).ok().and_then(|at| items.get(at)).cloned()
+                        .ok_or_else(|| error(format!("json index {} out of bounds (size {})", index, items.len()), 1))?,
+                    _ => unreachable!("checked to be an array"),
+                };
+                self.stack.push(Value::Json(item));
+            }
+            Instruction::JsonLen => {
+                let value = self.pop_json()?;
+                let len = match &*value.borrow() {
+                    Node::Array(items) => items.len(),
+                    Node::Object(members) => members.len(),
+                    other => return Err(error(format!("expected a json array or object, found {}", other.kind()), 1)),
+                };
+                self.stack.push(Value::Int(len as i32));
+            }
+            Instruction::JsonInt => {
+                let value = self.pop_json_kind("number")?;
+                let Node::Number(number) = *value.borrow() else { unreachable!("checked to be a number") };
+                if !((-2147483648.0..=2147483647.0).contains(&number) && number.fract() == 0.0) {
+                    return Err(error(format!("json number {} is not an int", json::format_number(number)), 1));
+                }
+                self.stack.push(Value::Int(number as i32));
+            }
+            Instruction::JsonFloat => {
+                let value = self.pop_json_kind("number")?;
+                let Node::Number(number) = *value.borrow() else { unreachable!("checked to be a number") };
+                self.stack.push(Value::Float(number));
+            }
+            Instruction::JsonBool => {
+                let value = self.pop_json_kind("bool")?;
+                let Node::Bool(value) = *value.borrow() else { unreachable!("checked to be a bool") };
+                self.stack.push(Value::Bool(value));
+            }
+            Instruction::JsonString => {
+                let value = self.pop_json_kind("string")?;
+                let Node::String(text) = &*value.borrow() else { unreachable!("checked to be a string") };
+                self.stack.push(Value::Str(text.as_str().into()));
+            }
+            Instruction::JsonSet => {
+                let item = self.pop_json_item()?;
+                let key = self.pop_str()?;
+                let object = self.pop_json_kind("object")?;
+                let mut object = object.borrow_mut();
+                if let Node::Object(members) = &mut *object {
+                    json::set(members, &key, item);
+                }
+            }
+            Instruction::JsonPush => {
+                let item = self.pop_json_item()?;
+                let array = self.pop_json_kind("array")?;
+                let mut array = array.borrow_mut();
+                if let Node::Array(items) = &mut *array {
+                    items.push(item);
+                }
+            }
+            Instruction::CanvasRender => {
+                let canvas = self.pop_canvas()?;
+                let canvas = canvas.borrow();
+                for row in canvas.cells.chunks(canvas.width) {
+                    self.printed.extend(row);
+                    self.printed.push('\n');
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Report that the local in `slot` of the running `function` becomes `value`
+    fn report_local_write(&mut self, function: usize, base: usize, slot: usize, value: &Value) -> Result<(), Fault> {
+        let name = &self.module.functions[function].local_names[slot];
+        let mut line = format!("{} = {}", name, watch_text(value));
+        if self.watch.as_ref().is_some_and(|watch| watch.written[base + slot]) {
+            line.push_str(&format!(" (was {})", watch_text(&self.locals[base + slot])));
+        }
+        if let Some(watch) = &mut self.watch {
+            watch.written[base + slot] = true;
+        }
+        self.report(function, &line)
+    }
+
+    /// Report a write to an element of `array` if a watched local of the
+    /// running function holds it. Arrays passed to a call are shared, so
+    /// this also sees writes through a parameter of the same name.
+    fn report_element_write(&mut self, function: usize, base: usize, array: &Rc<RefCell<Vec<Value>>>, index: i32, old: &Value, value: &Value) -> Result<(), Fault> {
+        let Some(watch) = &self.watch else { return Ok(()) };
+        let holder = watch.slots[function].iter().enumerate().find(|&(slot, &watched)| {
+            watched && matches!(&self.locals[base + slot], Value::Array(held) if Rc::ptr_eq(held, array))
+        });
+        let Some((slot, _)) = holder else { return Ok(()) };
+        let name = &self.module.functions[function].local_names[slot];
+        let line = format!("{}[{}] = {} (was {})", name, index, watch_text(value), watch_text(old));
+        self.report(function, &line)
+    }
+
+    /// Write a watch line for the running `function`, after the output so far
+    fn report(&mut self, function: usize, what: &str) -> Result<(), Fault> {
+        self.flush().map_err(|e| error(format!("cannot write output: {}", e), 1))?;
+        let code = &self.module.functions[function];
+        let pc = self.frames.last().map_or(0, |frame| frame.pc);
+        let line = code.line_at(pc.saturating_sub(1));
+        let location = if line > 0 {
+            format!("{}:{} in {}", self.module.source_name, line, code.name)
+        } else {
+            code.name.clone()
+        };
+        let Some(watch) = &mut self.watch else { return Ok(()) };
+        writeln!(watch.log, "watch: {}: {}", location, what)
+            .map_err(|e| error(format!("cannot write output: {}", e), 1))
+    }
+
+    fn jump(&mut self, target: u32) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.pc = target as usize;
+        }
+    }
+
+    fn arithmetic(&self, instruction: Instruction, left: Value, right: Value) -> Result<Value, Fault> {
+        match (left, right) {
+            (Value::Int(a), Value::Int(b)) => {
+                if matches!(instruction, Instruction::Div | Instruction::Mod) && (b == 0 || (a == i32::MIN && b == -1)) {
+                    // Both trap in the hardware divide
+                    return Err(arithmetic_error());
+                }
+                let result = if self.module.trap_overflow {
+                    match instruction {
+                        Instruction::Add => a.checked_add(b),
+                        Instruction::Sub => a.checked_sub(b),
+                        Instruction::Mul => a.checked_mul(b),
+                        Instruction::Div => a.checked_div(b),
+                        _ => a.checked_rem(b),
+                    }
+                    .ok_or_else(overflow_error)?
+                } else {
+                    match instruction {
+                        Instruction::Add => a.wrapping_add(b),
+                        Instruction::Sub => a.wrapping_sub(b),
+                        Instruction::Mul => a.wrapping_mul(b),
+                        Instruction::Div => a.wrapping_div(b),
+                        _ => a.wrapping_rem(b),
+                    }
+                };
+                Ok(Value::Int(result))
+            }
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(match instruction {
+                Instruction::Add => a + b,
+                Instruction::Sub => a - b,
+                Instruction::Mul => a * b,
+                Instruction::Div => a / b,
+                _ => a % b,
+            })),
+            _ => Err(Fault::Invalid("expected two numbers of the same type")),
+        }
+    }
+}
+
+/// An optional sign followed by decimal digits, in range of an int
+/// A value in a watch line: as `display` prints it, but with strings quoted
+fn watch_text(value: &Value) -> String {
+    match value {
+        Value::Str(text) => format!("{:?}", text),
+        other => {
+            let mut text = String::new();
+            other.write_to(&mut text);
+            text
+        }
+    }
+}
+
+fn parse_int(text: &str) -> Option<i32> {
+    let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse::<i64>().ok().and_then(|value| i32::try_from(value).ok())
+}
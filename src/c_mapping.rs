@@ -0,0 +1,100 @@
+// src/c_mapping.rs - MiniLang statements side by side with the C they became
+//
+// `compile --show-c-mapping` pairs each statement with the C lines generated
+// for it, using the `#line` table the code generator records. The bookkeeping
+// the generator adds for that table (the directives and the `_minilang_line`
+// trackers) is left out, so only the C that does the statement's work shows.
+
+use crate::codegen::SourceMapEntry;
+use crate::style::{self, Color};
+
+/// The widest the MiniLang column gets before lines are cut short
+const MAX_SOURCE_WIDTH: usize = 48;
+
+/// Colors cycled through so neighbouring statements stand apart
+const PALETTE: [Color; 4] = [Color::Cyan, Color::Yellow, Color::Magenta, Color::Green];
+
+/// One MiniLang statement and the C generated for it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MappedStatement {
+    /// 1-based line the statement starts on
+    pub source_line: usize,
+    /// 1-based C line numbers and their text
+    pub c_lines: Vec<(usize, String)>,
+}
+
+/// Split `c_code` into the runs of lines that follow each entry of its
+/// source map. A run ends at the next `#line` directive or at the blank line
+/// after a function; lines that only close a block are dropped.
+pub fn map_statements(c_code: &str, map: &[SourceMapEntry]) -> Vec<MappedStatement> {
+    let lines: Vec<&str> = c_code.lines().collect();
+    map.iter()
+        .map(|entry| {
+            let c_lines = lines.iter()
+                .enumerate()
+                .skip(entry.c_line - 1)
+                .take_while(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with("#line "))
+                .filter(|(_, line)| {
+                    let line = line.trim();
+                    line != "}" && !line.starts_with("_minilang_line = ")
+                })
+                .map(|(index, line)| (index + 1, line.trim_end().to_string()))
+                .collect();
+            MappedStatement { source_line: entry.source_line, c_lines }
+        })
+        .collect()
+}
+
+/// Lay out `statements` in two columns, MiniLang from `source` on the left
+/// and C on the right, each statement in its own color
+pub fn render(source: &str, statements: &[MappedStatement]) -> String {
+    let source_lines: Vec<&str> = source.lines().collect();
+    let text_of = |line: usize| source_lines.get(line - 1).map_or("", |text| text.trim_end());
+    let width = statements.iter()
+        .map(|statement| text_of(statement.source_line).chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(MAX_SOURCE_WIDTH);
+    let number_width = statements.iter()
+        .flat_map(|statement| statement.c_lines.iter().map(|(line, _)| *line).chain([statement.source_line]))
+        .max()
+        .unwrap_or(0)
+        .to_string()
+        .len();
+    let bar = style::icon("│");
+
+    let mut output = format!(
+        "{:>n$} {} {:<width$} {} {:>n$} {} C\n",
+        "", bar, "MiniLang", bar, "", bar, n = number_width, width = width,
+    );
+    for (index, statement) in statements.iter().enumerate() {
+        let color = PALETTE[index % PALETTE.len()];
+        let text = fit(text_of(statement.source_line), width);
+        let mut left = format!("{:>n$} {} {}", statement.source_line, bar, text, n = number_width);
+        // A statement that produced no C of its own still gets its row
+        let rights: Vec<String> = match statement.c_lines.as_slice() {
+            [] => vec![String::new()],
+            c_lines => c_lines.iter()
+                .map(|(line, text)| format!("{:>n$} {} {}", line, bar, text, n = number_width))
+                .collect(),
+        };
+        for right in rights {
+            let padded = format!("{:<w$}", left, w = number_width + 3 + width);
+            let row = format!("{} {} {}", style::paint(&padded, color), bar, style::paint(&right, color));
+            output.push_str(row.trim_end());
+            output.push('\n');
+            left = String::new();
+        }
+    }
+    output
+}
+
+/// `text` cut to `width` characters, marking the cut
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let ellipsis = style::icon("…");
+    let kept: String = text.chars().take(width.saturating_sub(ellipsis.chars().count())).collect();
+    format!("{}{}", kept, ellipsis)
+}
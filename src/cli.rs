@@ -1,7 +1,16 @@
 // src/cli.rs - Subcommand structure
 
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use crate::analyzer::CyclomaticMode;
+use crate::ast::Literal;
+use crate::banner::{Banner, TimestampPolicy};
+use crate::edition::Version;
+use crate::optimizer::OverflowMode;
+use crate::parser::Defines;
+use crate::repro::{self, Failure};
+use crate::runtime::RuntimeMode;
+use crate::style::ColorChoice;
+use std::path::{Path, PathBuf};
 
 /// MiniLang Compiler - A compiler for the MiniLang programming language
 #[derive(Parser, Debug)]
@@ -17,42 +26,263 @@ pub struct Cli {
     
     #[arg(long = "keep-c", global = true)]
     pub keep_c: bool,
+
+    /// When the C compiler fails, keep the generated C, its preprocessed form and
+    /// the exact compiler command in <name>-temps next to the output
+    #[arg(long = "save-temps", global = true)]
+    pub save_temps: bool,
+
+    /// Directory for executables and kept C files (defaults to the source directory)
+    #[arg(long = "out-dir", global = true)]
+    pub out_dir: Option<PathBuf>,
+
+    /// Append a JSON line describing each compile to this file
+    #[arg(long = "build-log", global = true)]
+    pub build_log: Option<PathBuf>,
+
+    /// Record which errors and warnings each compile reports (no source) in
+    /// DIR/usage.jsonl, for `minilang report usage`
+    #[arg(long = "log-usage", value_name = "DIR", global = true)]
+    pub log_usage: Option<PathBuf>,
     
     #[arg(short = 'd', long = "detail", global = true)]
     pub detail: bool,
 
     #[arg(short = 'O', long = "opt", default_value = "1", global = true)]
     pub optimization: u8,
+
+    /// Bounds-check every array index, even ones proven in range
+    #[arg(long = "always-bounds-check", global = true)]
+    pub always_bounds_check: bool,
+
+    /// What int arithmetic does on overflow: wrap around, or abort (and reject
+    /// constant expressions that overflow)
+    #[arg(long = "overflow", value_enum, default_value_t = OverflowMode::Wrap, global = true)]
+    pub overflow: OverflowMode,
+
+    /// Simplify float arithmetic in ways that can change -0.0, NaN and infinity results
+    #[arg(long = "fast-math", global = true)]
+    pub fast_math: bool,
+
+    /// Who frees the strings a program builds at runtime: reference counting,
+    /// an arena released at exit, or nothing
+    #[arg(long = "runtime", value_enum, default_value_t = RuntimeMode::Arena, global = true)]
+    pub runtime: RuntimeMode,
+
+    /// Make the program print allocation statistics to stderr when it exits
+    #[arg(long = "profile", global = true)]
+    pub profile: bool,
+
+    /// Start the program at this function instead of main. It takes no
+    /// parameters, and an int result becomes the exit status.
+    #[arg(long = "entry", value_name = "NAME", global = true)]
+    pub entry: Option<String>,
+
+    /// Hide progress bars and per-file details
+    #[arg(short = 'q', long = "quiet", global = true)]
+    pub quiet: bool,
+
+    /// When to use colors (NO_COLOR is honored in auto mode)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    pub color: ColorChoice,
+
+    /// Define a constant for `@if` blocks; a bare NAME means NAME=true
+    #[arg(short = 'D', long = "define", value_name = "NAME=VALUE", value_parser = parse_define, global = true)]
+    pub defines: Vec<(String, Literal)>,
+
+    /// Language version for files without a `minilang X.Y;` header
+    /// (default: the latest)
+    #[arg(long = "edition", value_name = "VERSION", global = true)]
+    pub edition: Option<Version>,
+
+    /// Leave out the banner comment a project's minilang.toml asks for
+    #[arg(long = "no-banner", global = true)]
+    pub no_banner: bool,
+}
+
+impl Cli {
+    /// The `--define` options, with later definitions of a name winning
+    pub fn defines(&self) -> Defines {
+        self.defines.iter().cloned().collect()
+    }
+
+    /// The `--edition` option, or the latest version without it
+    pub fn edition(&self) -> Version {
+        self.edition.unwrap_or_default()
+    }
+
+    /// The options besides `-O` that change the generated code, as written on
+    /// the command line, for build stamps
+    pub fn codegen_flags(&self) -> Vec<String> {
+        let overflow = match self.overflow {
+            OverflowMode::Wrap => "wrap",
+            OverflowMode::Trap => "trap",
+        };
+        let mut flags = vec![format!("--overflow={}", overflow)];
+        if self.fast_math {
+            flags.push("--fast-math".to_string());
+        }
+        if self.always_bounds_check {
+            flags.push("--always-bounds-check".to_string());
+        }
+        if self.runtime != RuntimeMode::Arena {
+            flags.push(format!("--runtime={}", self.runtime.name()));
+        }
+        if self.profile {
+            flags.push("--profile".to_string());
+        }
+        if let Some(entry) = &self.entry {
+            flags.push(format!("--entry={}", entry));
+        }
+        for (name, value) in &self.defines {
+            let value = match value {
+                Literal::String(text) => text.clone(),
+                Literal::Integer(n) => n.to_string(),
+                Literal::Float(f) => f.to_string(),
+                Literal::Boolean(b) => b.to_string(),
+                other => format!("{:?}", other),
+            };
+            flags.push(format!("-D{}={}", name, value));
+        }
+        flags
+    }
+}
+
+/// The artifact `compile` produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Emit {
+    /// A linked executable
+    Exe,
+    /// An object file per module, for linking separately
+    Obj,
+    /// C source per module
+    C,
+}
+
+/// What `compile` translates the program into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Target {
+    /// C, built into a native executable
+    #[default]
+    Native,
+    /// A portable .mbc bytecode file for `minilang vm`
+    Bytecode,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    /// Compile source file to executable
+    /// Compile source files to an executable. Several files are compiled as
+    /// separate modules that may call each other's functions, then linked.
     Compile {
-        file: PathBuf,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
         
         #[arg(long = "to-c")]
         to_c: bool,
+
+        /// What to produce: a linked executable, C source, or one object file per module
+        #[arg(long = "emit", value_enum, conflicts_with = "to_c")]
+        emit: Option<Emit>,
+
+        /// Compile to native code through C, or to a bytecode file that
+        /// `minilang vm` runs anywhere without a C compiler
+        #[arg(long = "target", value_enum, default_value_t = Target::Native, conflicts_with_all = ["to_c", "emit", "dry_run", "show_c_mapping"])]
+        target: Target,
+
+        /// Write the generated C and print the gcc commands and the files
+        /// they would produce, without running gcc
+        #[arg(long = "dry-run", conflicts_with = "to_c")]
+        dry_run: bool,
+
+        /// With --dry-run, also save the gcc commands as a shell script
+        #[arg(long = "script", value_name = "FILE", requires = "dry_run")]
+        script: Option<PathBuf>,
+
+        /// Print each statement next to the C generated for it
+        #[arg(long = "show-c-mapping")]
+        show_c_mapping: bool,
     },
     
-    /// Compile and run the program
+    /// Compile and run the program. Arguments after `--` are passed to it.
     Run {
         file: PathBuf,
+
+        /// Feed this file to the program's standard input (it reads the
+        /// terminal or whatever minilang's own stdin is otherwise)
+        #[arg(long = "stdin", value_name = "FILE")]
+        stdin: Option<PathBuf>,
+
+        /// Arguments for the program
+        #[arg(last = true)]
+        program_args: Vec<String>,
     },
 
-    /// Check for compilation errors without generating code
+    /// Check for compilation errors without generating code.
+    /// Accepts files, directories (searched for .mini files) and glob patterns.
     Check {
-        file: PathBuf,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+
+    /// Run the `test "name" { ... }` blocks of source files on the bytecode VM.
+    /// Accepts files, directories (searched for .mini files) and glob patterns.
+    Test {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Only run the tests whose name contains this text
+        #[arg(long = "filter", value_name = "TEXT")]
+        filter: Option<String>,
     },
 
     /// Display the Abstract Syntax Tree
     Ast {
         file: PathBuf,
+
+        /// Show this many levels, counting functions as level 1
+        #[arg(long = "depth", value_name = "N")]
+        depth: Option<usize>,
+
+        /// Only show this function (repeat for several)
+        #[arg(long = "function", value_name = "NAME")]
+        functions: Vec<String>,
+
+        /// Follow each node with its byte span in the source
+        #[arg(long = "spans")]
+        spans: bool,
     },
 
     /// Display all tokens from lexical analysis
     Tokens {
         file: PathBuf,
+
+        /// Output tokens and comments as JSON, with spans and line/column positions
+        #[arg(long = "json")]
+        json: bool,
+    },
+
+    /// List every function, parameter, variable and constant with where it
+    /// is defined and everywhere it is referenced
+    Xref {
+        file: PathBuf,
+
+        /// Output the listing as JSON, with line/column positions
+        #[arg(long = "json")]
+        json: bool,
+    },
+
+    /// Show the build stamp of an executable or generated C file: the
+    /// compiler version, options and source hash it was built with
+    Inspect {
+        file: PathBuf,
+
+        /// Check whether this MiniLang file is the source it was built from
+        #[arg(long = "source", value_name = "FILE")]
+        source: Option<PathBuf>,
+
+        /// Output the stamps as JSON
+        #[arg(long = "json")]
+        json: bool,
     },
 
     /// Show compilation statistics
@@ -72,12 +302,513 @@ pub enum Commands {
         dry_run: bool,
     },
 
-    /// Run static analysis and complexity metrics
+    /// Create a new MiniLang project
+    Init {
+        name: String,
+    },
+
+    /// Manage the git pre-commit hook
+    Hook {
+        #[command(subcommand)]
+        action: HookAction,
+    },
+
+    /// Show the compiler's version and update it from GitHub releases
+    #[command(name = "self")]
+    Toolchain {
+        #[command(subcommand)]
+        action: SelfAction,
+    },
+
+    /// Browse and run the sample programs built into the compiler
+    Examples {
+        #[command(subcommand)]
+        action: ExamplesAction,
+    },
+
+    /// Run static analysis and complexity metrics.
+    /// Accepts files, directories (searched for .mini files) and glob patterns;
+    /// more than one file gives a single combined report.
     Analyze {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Output as JSON instead of formatted text
+        #[arg(long = "json", conflicts_with = "html")]
+        json: bool,
+
+        /// Output a standalone HTML report instead of formatted text
+        #[arg(long = "html")]
+        html: bool,
+
+        /// How many of the most complex functions a combined report lists
+        #[arg(long = "worst", value_name = "N", default_value_t = 10)]
+        worst: usize,
+
+        /// Whether cyclomatic complexity counts each AND / OR as a decision
+        /// (strict, as lizard and radon do) or only branches and loops (classic)
+        #[arg(long = "cyclomatic", value_enum, default_value_t = CyclomaticMode::Strict)]
+        cyclomatic: CyclomaticMode,
+
+        /// Only report on this function (repeat for several)
+        #[arg(long = "only-function", value_name = "NAME")]
+        only_functions: Vec<String>,
+    },
+
+    /// Rewrite source code, printing the change as a diff
+    Refactor {
+        #[command(subcommand)]
+        action: RefactorAction,
+    },
+
+    /// Summarize logs written by the compiler
+    Report {
+        #[command(subcommand)]
+        action: ReportAction,
+    },
+
+    /// Shrink a program to the functions and statements a failure needs.
+    /// COMMAND is run on each candidate and exits with 0 while the failure
+    /// still shows; `{}` in it stands for the candidate's path, which is
+    /// added at the end otherwise. The result goes to -o (default
+    /// <file>.reduced.mini).
+    Reduce {
+        file: PathBuf,
+
+        /// The shell command that checks a candidate
+        #[arg(long = "check", value_name = "COMMAND")]
+        check: String,
+
+        /// Seconds a check may run before the candidate counts as not failing
+        #[arg(long = "timeout", value_name = "SECS", default_value_t = 10)]
+        timeout: u64,
+    },
+
+    /// Run a bytecode file made with `compile --target=bytecode`
+    Vm {
         file: PathBuf,
 
+        /// Print every write to variables with this name, in any function,
+        /// to stderr with its line and the value it replaced (repeatable)
+        #[arg(long = "watch-var", value_name = "NAME")]
+        watch: Vec<String>,
+
+        /// Start now_ms() at 0 and have sleep_ms() advance it without
+        /// waiting, so timed programs print the same on every run
+        #[arg(long)]
+        virtual_clock: bool,
+    },
+
+    /// Turn programs that show a compiler bug into regression tests
+    Repro {
+        #[command(subcommand)]
+        action: ReproAction,
+    },
+
+    /// Stay running and answer `check` from typed programs kept in memory,
+    /// redoing only files that changed
+    Daemon {
+        /// Socket to listen on (default: .minilang/daemon.sock here, or
+        /// $MINILANG_DAEMON)
+        #[arg(long = "socket", value_name = "PATH")]
+        socket: Option<PathBuf>,
+
+        /// Ask the running daemon to exit
+        #[arg(long = "stop", conflicts_with = "status")]
+        stop: bool,
+
+        /// Show how many files the running daemon holds and how often it
+        /// answered from memory
+        #[arg(long = "status")]
+        status: bool,
+    },
+}
+#[derive(Subcommand, Debug)]
+pub enum HookAction {
+    /// Install a pre-commit hook that checks staged .mini files
+    Install {
+        /// Replace an existing hook that was not installed by minilang
+        #[arg(long = "force")]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SelfAction {
+    /// Show the version, commit and target this compiler was built from
+    Version {
+        /// Also ask GitHub for the latest release; exits with 1 when it is newer
+        #[arg(long = "check")]
+        check: bool,
+    },
+    /// Replace this executable with the latest release's build for this
+    /// platform (or the release tagged TAG)
+    Update {
+        #[arg(long = "tag", value_name = "TAG")]
+        tag: Option<String>,
+
+        /// Install the build even when the release publishes no checksum for it
+        #[arg(long = "no-verify")]
+        no_verify: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReportAction {
+    /// Which errors and warnings come up most in --log-usage logs. Accepts
+    /// usage directories and log files, e.g. one per student.
+    Usage {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
         /// Output as JSON instead of formatted text
         #[arg(long = "json")]
         json: bool,
+
+        /// How many error and warning codes to list
+        #[arg(long = "top", value_name = "N", default_value_t = 10)]
+        top: usize,
     },
-}
\ No newline at end of file
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReproAction {
+    /// Shrink a program to the fewest lines that still show the failure and
+    /// add it to the corpus that `cargo test` checks
+    Save {
+        file: PathBuf,
+
+        /// The bug the program shows
+        #[arg(long = "failure", value_enum)]
+        failure: Failure,
+
+        /// The corpus directory
+        #[arg(long = "corpus", value_name = "DIR", default_value = repro::DEFAULT_CORPUS)]
+        corpus: PathBuf,
+
+        /// Save the case as NAME.mini instead of under the file's name
+        #[arg(long = "name", value_name = "NAME")]
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RefactorAction {
+    /// Turn `if` statements that wrap the rest of a function into early returns
+    EarlyReturn {
+        file: PathBuf,
+
+        /// Apply the change to the file instead of only showing it
+        #[arg(long = "write")]
+        write: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExamplesAction {
+    /// List the available examples
+    List,
+    
+    /// Print the source of an example
+    Show {
+        name: String,
+    },
+    
+    /// Compile and run an example
+    Run {
+        name: String,
+    },
+}
+
+// ==================== DEFINES ====================
+
+/// Parse a `--define NAME=VALUE` argument. Values are read as booleans,
+/// integers or floats when they look like one and as strings otherwise.
+pub fn parse_define(arg: &str) -> Result<(String, Literal), String> {
+    let (name, value) = match arg.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (arg.trim(), None),
+    };
+    
+    let mut chars = name.chars();
+    let valid_start = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    if !valid_start || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("'{}' is not a valid name", name));
+    }
+    
+    let literal = match value {
+        None | Some("true") => Literal::Boolean(true),
+        Some("false") => Literal::Boolean(false),
+        Some(value) => {
+            if let Ok(int) = value.parse::<i32>() {
+                Literal::Integer(int)
+            } else if let Ok(float) = value.parse::<f64>() {
+                Literal::Float(float)
+            } else {
+                let unquoted = value.strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                Literal::String(unquoted.to_string())
+            }
+        }
+    };
+    
+    Ok((name.to_string(), literal))
+}
+
+// ==================== INPUT EXPANSION ====================
+
+/// Expand `check` inputs into the list of files to check.
+///
+/// Directories are searched recursively for `.mini` files and glob patterns
+/// (`*`, `?` and `**`) are matched against the files under their fixed prefix.
+/// Plain paths are kept even if missing so the caller can report them.
+/// The result is deduplicated and keeps the order of the inputs.
+pub fn expand_inputs(inputs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    
+    for input in inputs {
+        let text = input.to_string_lossy();
+        let mut found = if is_glob(&text) {
+            expand_glob(&text)
+        } else if input.is_dir() {
+            let mut found = Vec::new();
+            collect_files(input, &mut found);
+            found.retain(|path| path.extension().is_some_and(|ext| ext == "mini"));
+            found
+        } else {
+            vec![input.clone()]
+        };
+        found.sort();
+        
+        for file in found {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+    
+    files
+}
+
+fn is_glob(text: &str) -> bool {
+    text.contains(['*', '?'])
+}
+
+/// Match the files under the pattern's literal directory prefix
+fn expand_glob(pattern: &str) -> Vec<PathBuf> {
+    let components: Vec<&str> = pattern.split('/').collect();
+    let literal = components.iter().take_while(|c| !is_glob(c)).count();
+    
+    let base = if literal == 0 {
+        PathBuf::from(".")
+    } else {
+        PathBuf::from(components[..literal].join("/"))
+    };
+    
+    let mut candidates = Vec::new();
+    collect_files(&base, &mut candidates);
+    
+    candidates
+        .into_iter()
+        .filter(|path| {
+            let relative = path.strip_prefix(&base).unwrap_or(path);
+            let parts: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+            match_components(&components[literal..], &parts)
+        })
+        .collect()
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+/// Match path components, where `**` spans any number of directories
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_components(rest, &path[skip..])),
+        Some((first, rest)) => {
+            !path.is_empty() && match_segment(first.as_bytes(), path[0].as_bytes()) && match_components(rest, &path[1..])
+        }
+    }
+}
+
+/// Match one file name against `*` and `?` wildcards
+fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_segment(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+    }
+}
+
+// ==================== GIT HOOK ====================
+
+/// First line after the shebang, used to recognize hooks we installed
+const HOOK_MARKER: &str = "# minilang pre-commit hook";
+
+/// Shell script run by git before each commit. It runs `check` on the staged
+/// `.mini` files, which includes the lint rules of installed plugins; there
+/// is no `lint` or `fmt` command to run besides. Set `MINILANG` to use a
+/// binary that is not on the PATH.
+///
+/// The checks run on a copy of the index, so they see what is being
+/// committed: not edits made after `git add`, nor the unstaged hunks of a
+/// file staged with `git add -p`.
+pub fn pre_commit_hook_script() -> String {
+    format!(r#"#!/bin/sh
+{marker}
+# Installed by `minilang hook install`; remove this file to disable it.
+
+MINILANG="${{MINILANG:-minilang}}"
+
+if [ -z "$(git diff --cached --name-only --diff-filter=ACMR -- '*.mini')" ]; then
+    exit 0
+fi
+
+# Check the staged contents, not the working tree
+snapshot=$(mktemp -d) || exit 1
+trap 'rm -rf "$snapshot"' EXIT
+git diff --cached --name-only --diff-filter=ACMR -z -- '*.mini' > "$snapshot/staged"
+git checkout-index --all --prefix="$snapshot/tree/" || exit 1
+cd "$snapshot/tree" || exit 1
+
+xargs -0 "$MINILANG" check < "$snapshot/staged"
+"#, marker = HOOK_MARKER)
+}
+
+/// Write the pre-commit hook into `hooks_dir`, returning its path.
+/// An existing hook is only replaced if we installed it or `force` is set.
+pub fn install_pre_commit_hook(hooks_dir: &Path, force: bool) -> Result<PathBuf, String> {
+    let hook_path = hooks_dir.join("pre-commit");
+    
+    if let Ok(existing) = std::fs::read_to_string(&hook_path) {
+        if !existing.contains(HOOK_MARKER) && !force {
+            return Err(format!(
+                "'{}' already exists and was not installed by minilang (use --force to replace it)",
+                hook_path.display()
+            ));
+        }
+    }
+    
+    std::fs::create_dir_all(hooks_dir)
+        .map_err(|e| format!("cannot create '{}': {}", hooks_dir.display(), e))?;
+    std::fs::write(&hook_path, pre_commit_hook_script())
+        .map_err(|e| format!("cannot write '{}': {}", hook_path.display(), e))?;
+    
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&hook_path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("cannot make '{}' executable: {}", hook_path.display(), e))?;
+    }
+    
+    Ok(hook_path)
+}
+
+// ==================== PROJECT SCAFFOLDING ====================
+
+/// Create a new project named after the last component of `dir`, returning
+/// the files written. Fails if `dir` already exists and is not empty.
+///
+/// Tests live in `tests/` as `<name>.mini` programs next to a
+/// `<name>.expected` file holding the output they should print.
+pub fn init_project(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let name = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("'{}' is not a valid project directory", dir.display()))?;
+    
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(format!(
+            "'{}' is not a valid project name (use letters, digits, '_' and '-')",
+            name
+        ));
+    }
+    
+    if let Ok(mut entries) = std::fs::read_dir(dir) {
+        if entries.next().is_some() {
+            return Err(format!("'{}' already exists and is not empty", dir.display()));
+        }
+    }
+    
+    let files = [
+        (PathBuf::from("minilang.toml"), format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nentry = \"main.mini\"\n\n[build]\nopt-level = 1\n",
+            name
+        )),
+        // Pin the language version, so later syntax changes leave the project alone
+        (PathBuf::from("main.mini"), format!(
+            "minilang {};\n\nfunc main() {{\n    display \"Hello from {}!\";\n}}\n",
+            Version::LATEST, name
+        )),
+        (PathBuf::from("tests/hello.mini"), String::from(
+            "func main() {\n    display \"Hello, tests!\";\n}\n"
+        )),
+        (PathBuf::from("tests/hello.expected"), String::from("Hello, tests!\n")),
+        (PathBuf::from(".gitignore"), String::from(
+            "# Generated by the minilang compiler\n*.c\n/main\n/tests/*\n!/tests/*.mini\n!/tests/*.expected\n/.minilang/\n"
+        )),
+    ];
+    
+    let mut written = Vec::new();
+    for (relative, contents) in files {
+        let path = dir.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("cannot create '{}': {}", parent.display(), e))?;
+        }
+        std::fs::write(&path, contents)
+            .map_err(|e| format!("cannot write '{}': {}", path.display(), e))?;
+        written.push(path);
+    }
+    
+    Ok(written)
+}
+
+/// The nearest minilang.toml in the directory of `source` or one above it
+pub fn find_manifest(source: &Path) -> Option<PathBuf> {
+    let source = std::path::absolute(source).ok()?;
+    source.ancestors()
+        .skip(1)
+        .map(|dir| dir.join("minilang.toml"))
+        .find(|manifest| manifest.is_file())
+}
+
+/// The banner that the project `source` belongs to asks for, if any, with
+/// its time taken from `SOURCE_DATE_EPOCH` when set (for reproducible
+/// builds) or the clock
+pub fn project_banner(source: &Path) -> Result<Option<Banner>, String> {
+    let Some(manifest) = find_manifest(source) else { return Ok(None) };
+    let text = std::fs::read_to_string(&manifest)
+        .map_err(|e| format!("cannot read '{}': {}", manifest.display(), e))?;
+    let Some(mut banner) = Banner::from_manifest(&text)
+        .map_err(|e| format!("{}: {}", manifest.display(), e))? else { return Ok(None) };
+
+    if banner.timestamp != TimestampPolicy::None {
+        banner.generated_at = std::env::var("SOURCE_DATE_EPOCH").ok()
+            .and_then(|epoch| epoch.parse().ok())
+            .or_else(|| {
+                std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok()
+                    .map(|elapsed| elapsed.as_secs())
+            });
+    }
+    Ok(Some(banner))
+}
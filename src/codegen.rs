@@ -1,15 +1,1014 @@
 // src/codegen.rs - C code generator for MiniLang
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use serde::{Serialize, Deserialize};
 use crate::ast::*;
+use crate::banner::Banner;
+use crate::bounds;
+use crate::effects;
+use crate::line_index::LineIndex;
+use crate::peephole::{self, PeepholeStats};
+use crate::runtime::{self, RuntimeMode};
+use crate::stamp::Stamp;
+
+/// Maps a line of generated C back to the MiniLang line it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceMapEntry {
+    pub c_line: usize,
+    pub source_line: usize,
+}
+
+/// The MiniLang file being compiled, for `#line` directives
+struct SourceFile {
+    name: String,
+    text: String,
+    lines: LineIndex,
+}
+
+/// A printf call being assembled from the parts of a display statement
+#[derive(Default)]
+struct DisplayPrintf {
+    format: String,
+    args: Vec<String>,
+}
+
+impl DisplayPrintf {
+    /// Add already-escaped literal text to the format
+    fn push_text(&mut self, text: &str) {
+        self.format.push_str(&text.replace('%', "%%"));
+    }
+    
+    fn push_value(&mut self, format: &str, value: String) {
+        self.format.push_str(format);
+        self.args.push(value);
+    }
+    
+    /// The finished call, if anything was added, leaving this empty
+    fn take(&mut self) -> Option<String> {
+        if self.format.is_empty() {
+            return None;
+        }
+        let format = std::mem::take(&mut self.format);
+        let args = std::mem::take(&mut self.args);
+        Some(if args.is_empty() {
+            format!("printf(\"{}\");", format)
+        } else {
+            format!("printf(\"{}\", {});", format, args.join(", "))
+        })
+    }
+}
+
+/// Prefix for every user symbol in the generated C
+pub(crate) const USER_SYMBOL_PREFIX: &str = "ml_";
+
+/// Runtime support for `assert`, only added to programs that use it. A
+/// failed assert stops the program like an out of bounds index does.
+const ASSERT_SUPPORT: &str = r#"static inline void _minilang_assert_failed(const char* what, const char* file, int line) {
+    fprintf(stderr, "Runtime Error: assertion failed: %s\n", what);
+    fprintf(stderr, "  at %s:%d\n", file, line);
+    exit(1);
+}
+
+// One line, so __LINE__ is the assert's; `what` is only evaluated on failure
+#define ASSERT(condition, what) ((condition) ? (void)0 : _minilang_assert_failed(what, __FILE__, __LINE__))
+
+"#;
+
+/// Runtime support for `@memo` functions, only added to programs that have
+/// one. Each keeps a direct-mapped cache of this many results; a call whose
+/// slot holds other arguments runs the body and takes the slot over.
+const MEMO_SUPPORT: &str = r#"#define _MINILANG_MEMO_SLOTS 4096
+
+// FNV-1a over the bytes of one argument, to pick a cache slot
+static inline unsigned long long _minilang_memo_mix(unsigned long long hash, const void* key, size_t size) {
+    const unsigned char* bytes = key;
+    for (size_t i = 0; i < size; i++) {
+        hash = (hash ^ bytes[i]) * 1099511628211ULL;
+    }
+    return hash;
+}
+
+"#;
+
+/// Runtime support for canvases, only added to programs that use them. Like
+/// channels, they live until the program exits.
+const CANVAS_SUPPORT: &str = r#"// A grid of cells, each holding one UTF-8 character of up to 4 bytes,
+// zero-padded, row by row from the top left
+typedef struct {
+    int width, height;
+    char (*cells)[4];
+} _minilang_canvas_state;
+typedef _minilang_canvas_state* _minilang_canvas;
+
+static inline _minilang_canvas _minilang_canvas_new(int width, int height, const char* file, int line) {
+    if (width < 1 || height < 1) {
+        fprintf(stderr, "Runtime Error: a canvas must be at least 1x1, not %dx%d\n", width, height);
+        fprintf(stderr, "  at %s:%d\n", file, line);
+        exit(1);
+    }
+    _minilang_canvas canvas = malloc(sizeof *canvas);
+    char (*cells)[4] = calloc((size_t)width * height, sizeof *cells);
+    if (canvas == NULL || cells == NULL) {
+        fprintf(stderr, "Runtime Error: out of memory\n");
+        exit(1);
+    }
+    for (size_t i = 0; i < (size_t)width * height; i++) {
+        cells[i][0] = ' ';
+    }
+    canvas->width = width;
+    canvas->height = height;
+    canvas->cells = cells;
+    return canvas;
+}
+
+// Put `text` in `cell`, if it is exactly one character
+static inline void _minilang_canvas_put(char cell[4], const char* text, const char* file, int line) {
+    unsigned char lead = (unsigned char)text[0];
+    size_t size = lead < 0x80 ? 1 : (lead >> 5) == 6 ? 2 : (lead >> 4) == 14 ? 3 : (lead >> 3) == 30 ? 4 : 0;
+    if (size == 0 || strlen(text) != size) {
+        fprintf(stderr, "Runtime Error: a canvas cell holds one character, not \"%s\"\n", text);
+        fprintf(stderr, "  at %s:%d\n", file, line);
+        exit(1);
+    }
+    memset(cell, 0, 4);
+    memcpy(cell, text, size);
+}
+
+static inline void _minilang_canvas_set(_minilang_canvas canvas, int x, int y, const char* text, const char* file, int line) {
+    if (x < 0 || x >= canvas->width || y < 0 || y >= canvas->height) {
+        fprintf(stderr, "Runtime Error: cell (%d, %d) is outside the %dx%d canvas\n", x, y, canvas->width, canvas->height);
+        fprintf(stderr, "  at %s:%d\n", file, line);
+        exit(1);
+    }
+    _minilang_canvas_put(canvas->cells[(size_t)y * canvas->width + x], text, file, line);
+}
+
+static inline void _minilang_canvas_fill(_minilang_canvas canvas, const char* text, const char* file, int line) {
+    _minilang_canvas_put(canvas->cells[0], text, file, line);
+    for (size_t i = 1; i < (size_t)canvas->width * canvas->height; i++) {
+        memcpy(canvas->cells[i], canvas->cells[0], 4);
+    }
+}
+
+// Display each row on a line of its own
+static inline void _minilang_canvas_render(_minilang_canvas canvas) {
+    for (int y = 0; y < canvas->height; y++) {
+        for (int x = 0; x < canvas->width; x++) {
+            const char* cell = canvas->cells[(size_t)y * canvas->width + x];
+            size_t size = 1;
+            while (size < 4 && cell[size] != 0) {
+                size++;
+            }
+            fwrite(cell, 1, size, stdout);
+        }
+        putchar('\n');
+    }
+}
+
+#define CANVAS_NEW(width, height) _minilang_canvas_new(width, height, __FILE__, __LINE__)
+#define CANVAS_SET(canvas, x, y, text) _minilang_canvas_set(canvas, x, y, text, __FILE__, __LINE__)
+#define CANVAS_FILL(canvas, text) _minilang_canvas_fill(canvas, text, __FILE__, __LINE__)
+
+"#;
+
+/// Runtime support for json values, only added to programs that use them.
+/// Parsing, printing and the error messages match the VM's (src/json.rs).
+/// Like canvases, values live until the program exits.
+const JSON_SUPPORT: &str = r#"// A JSON value. Numbers are doubles and objects keep their keys in the
+// order they were added, in `keys` next to `items`.
+typedef struct _minilang_json_node* _minilang_json;
+struct _minilang_json_node {
+    int kind;
+    bool boolean;
+    double number;
+    char* text;
+    size_t count, capacity;
+    char** keys;
+    _minilang_json* items;
+};
+
+enum { _MINILANG_JSON_NULL, _MINILANG_JSON_BOOL, _MINILANG_JSON_NUMBER, _MINILANG_JSON_STRING, _MINILANG_JSON_ARRAY, _MINILANG_JSON_OBJECT };
+static const char* const _minilang_json_kinds[] = {"null", "bool", "number", "string", "array", "object"};
+
+// Nesting deeper than this is rejected instead of overflowing the stack
+#define _MINILANG_JSON_MAX_DEPTH 512
+
+static void* _minilang_json_check(void* memory) {
+    if (memory == NULL) {
+        fprintf(stderr, "Runtime Error: out of memory\n");
+        exit(1);
+    }
+    return memory;
+}
+
+static void _minilang_json_fail(const char* file, int line) {
+    fprintf(stderr, "  at %s:%d\n", file, line);
+    exit(1);
+}
+
+static char* _minilang_json_copy_text(const char* text) {
+    size_t length = strlen(text);
+    return memcpy(_minilang_json_check(malloc(length + 1)), text, length + 1);
+}
+
+static _minilang_json _minilang_json_new(int kind) {
+    _minilang_json json = _minilang_json_check(calloc(1, sizeof *json));
+    json->kind = kind;
+    return json;
+}
+
+// Add `item` to an array, or under `key` to an object, replacing the value
+// already there
+static void _minilang_json_add(_minilang_json json, const char* key, _minilang_json item) {
+    for (size_t i = 0; key != NULL && i < json->count; i++) {
+        if (strcmp(json->keys[i], key) == 0) {
+            json->items[i] = item;
+            return;
+        }
+    }
+    if (json->count == json->capacity) {
+        json->capacity = json->capacity ? json->capacity * 2 : 4;
+        json->items = _minilang_json_check(realloc(json->items, json->capacity * sizeof *json->items));
+        if (key != NULL) {
+            json->keys = _minilang_json_check(realloc(json->keys, json->capacity * sizeof *json->keys));
+        }
+    }
+    if (key != NULL) {
+        json->keys[json->count] = _minilang_json_copy_text(key);
+    }
+    json->items[json->count++] = item;
+}
+
+typedef struct {
+    const char* text;
+    size_t at;
+    int depth;
+} _minilang_json_parser;
+
+static inline bool _minilang_json_digit(char c) {
+    return c >= '0' && c <= '9';
+}
+
+static void _minilang_json_space(_minilang_json_parser* parser) {
+    while (parser->text[parser->at] == ' ' || parser->text[parser->at] == '\t'
+           || parser->text[parser->at] == '\n' || parser->text[parser->at] == '\r') {
+        parser->at++;
+    }
+}
+
+// The four hex digits at `digits`, or -1
+static long _minilang_json_hex(const char* digits) {
+    long value = 0;
+    for (int i = 0; i < 4; i++) {
+        char c = digits[i];
+        int digit = _minilang_json_digit(c) ? c - '0' : c >= 'a' && c <= 'f' ? c - 'a' + 10 : c >= 'A' && c <= 'F' ? c - 'A' + 10 : -1;
+        if (digit < 0) {
+            return -1;
+        }
+        value = value * 16 + digit;
+    }
+    return value;
+}
+
+// Parse a string from its opening quote. A bad escape fails at its
+// backslash; \u0000 is one, since MiniLang strings end at a NUL.
+static char* _minilang_json_string(_minilang_json_parser* parser) {
+    size_t length = 0, capacity = 16;
+    char* text = _minilang_json_check(malloc(capacity));
+    parser->at++;
+    for (;;) {
+        unsigned char c = (unsigned char)parser->text[parser->at];
+        size_t start = parser->at;
+        char bytes[4];
+        size_t size = 1;
+        if (c == '"') {
+            parser->at++;
+            break;
+        }
+        if (c < 0x20) {
+            free(text);
+            return NULL;
+        }
+        if (c != '\\') {
+            bytes[0] = (char)c;
+            parser->at++;
+        } else {
+            char escape = parser->text[parser->at + 1];
+            const char* escapes = "\"\\/bfnrt";
+            const char* simple = escape != 0 ? strchr(escapes, escape) : NULL;
+            if (simple != NULL) {
+                bytes[0] = "\"\\/\b\f\n\r\t"[simple - escapes];
+                parser->at += 2;
+            } else if (escape == 'u') {
+                long code = _minilang_json_hex(parser->text + parser->at + 2);
+                parser->at += 6;
+                if (code >= 0xD800 && code <= 0xDBFF && parser->text[parser->at] == '\\' && parser->text[parser->at + 1] == 'u') {
+                    long low = _minilang_json_hex(parser->text + parser->at + 2);
+                    if (low >= 0xDC00 && low <= 0xDFFF) {
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                        parser->at += 6;
+                    } else {
+                        code = -1;
+                    }
+                } else if (code >= 0xD800 && code <= 0xDFFF) {
+                    code = -1;
+                }
+                if (code <= 0) {
+                    parser->at = start;
+                    free(text);
+                    return NULL;
+                }
+                if (code < 0x80) {
+                    bytes[0] = (char)code;
+                } else if (code < 0x800) {
+                    bytes[0] = (char)(0xC0 | (code >> 6));
+                    bytes[1] = (char)(0x80 | (code & 0x3F));
+                    size = 2;
+                } else if (code < 0x10000) {
+                    bytes[0] = (char)(0xE0 | (code >> 12));
+                    bytes[1] = (char)(0x80 | ((code >> 6) & 0x3F));
+                    bytes[2] = (char)(0x80 | (code & 0x3F));
+                    size = 3;
+                } else {
+                    bytes[0] = (char)(0xF0 | (code >> 18));
+                    bytes[1] = (char)(0x80 | ((code >> 12) & 0x3F));
+                    bytes[2] = (char)(0x80 | ((code >> 6) & 0x3F));
+                    bytes[3] = (char)(0x80 | (code & 0x3F));
+                    size = 4;
+                }
+            } else {
+                free(text);
+                return NULL;
+            }
+        }
+        if (length + size + 1 > capacity) {
+            capacity *= 2;
+            text = _minilang_json_check(realloc(text, capacity));
+        }
+        memcpy(text + length, bytes, size);
+        length += size;
+    }
+    text[length] = 0;
+    return text;
+}
+
+static _minilang_json _minilang_json_number(_minilang_json_parser* parser) {
+    const char* text = parser->text;
+    size_t start = parser->at;
+    if (text[parser->at] == '-') {
+        parser->at++;
+    }
+    if (text[parser->at] == '0') {
+        parser->at++;
+    } else if (text[parser->at] >= '1' && text[parser->at] <= '9') {
+        while (_minilang_json_digit(text[parser->at])) {
+            parser->at++;
+        }
+    } else {
+        return NULL;
+    }
+    if (text[parser->at] == '.') {
+        parser->at++;
+        if (!_minilang_json_digit(text[parser->at])) {
+            return NULL;
+        }
+        while (_minilang_json_digit(text[parser->at])) {
+            parser->at++;
+        }
+    }
+    if (text[parser->at] == 'e' || text[parser->at] == 'E') {
+        parser->at++;
+        if (text[parser->at] == '+' || text[parser->at] == '-') {
+            parser->at++;
+        }
+        if (!_minilang_json_digit(text[parser->at])) {
+            return NULL;
+        }
+        while (_minilang_json_digit(text[parser->at])) {
+            parser->at++;
+        }
+    }
+    double number = strtod(text + start, NULL);
+    // Too large for a double
+    if (!(number - number == 0.0)) {
+        parser->at = start;
+        return NULL;
+    }
+    _minilang_json json = _minilang_json_new(_MINILANG_JSON_NUMBER);
+    json->number = number;
+    return json;
+}
+
+// Parse a value, leaving `at` where it went wrong on failure
+static _minilang_json _minilang_json_value(_minilang_json_parser* parser) {
+    _minilang_json_space(parser);
+    char open = parser->text[parser->at];
+    if (open == '{' || open == '[') {
+        if (parser->depth == _MINILANG_JSON_MAX_DEPTH) {
+            return NULL;
+        }
+        parser->depth++;
+        char close = open == '{' ? '}' : ']';
+        _minilang_json json = _minilang_json_new(open == '{' ? _MINILANG_JSON_OBJECT : _MINILANG_JSON_ARRAY);
+        parser->at++;
+        _minilang_json_space(parser);
+        if (parser->text[parser->at] == close) {
+            parser->at++;
+        } else {
+            for (;;) {
+                char* key = NULL;
+                if (close == '}') {
+                    _minilang_json_space(parser);
+                    if (parser->text[parser->at] != '"') {
+                        return NULL;
+                    }
+                    key = _minilang_json_string(parser);
+                    if (key == NULL) {
+                        return NULL;
+                    }
+                    _minilang_json_space(parser);
+                    if (parser->text[parser->at] != ':') {
+                        return NULL;
+                    }
+                    parser->at++;
+                }
+                _minilang_json item = _minilang_json_value(parser);
+                if (item == NULL) {
+                    return NULL;
+                }
+                _minilang_json_add(json, key, item);
+                free(key);
+                _minilang_json_space(parser);
+                if (parser->text[parser->at] == ',') {
+                    parser->at++;
+                    continue;
+                }
+                if (parser->text[parser->at] != close) {
+                    return NULL;
+                }
+                parser->at++;
+                break;
+            }
+        }
+        parser->depth--;
+        return json;
+    }
+    if (open == '"') {
+        char* text = _minilang_json_string(parser);
+        if (text == NULL) {
+            return NULL;
+        }
+        _minilang_json json = _minilang_json_new(_MINILANG_JSON_STRING);
+        json->text = text;
+        return json;
+    }
+    if (open == '-' || _minilang_json_digit(open)) {
+        return _minilang_json_number(parser);
+    }
+    const char* words[] = {"null", "true", "false"};
+    for (int i = 0; i < 3; i++) {
+        size_t length = strlen(words[i]);
+        if (strncmp(parser->text + parser->at, words[i], length) == 0) {
+            parser->at += length;
+            _minilang_json json = _minilang_json_new(i == 0 ? _MINILANG_JSON_NULL : _MINILANG_JSON_BOOL);
+            json->boolean = i == 1;
+            return json;
+        }
+    }
+    return NULL;
+}
+
+static _minilang_json _minilang_json_parse(const char* text, const char* file, int line) {
+    _minilang_json_parser parser = {text, 0, 0};
+    _minilang_json json = _minilang_json_value(&parser);
+    if (json != NULL) {
+        _minilang_json_space(&parser);
+    }
+    if (json == NULL || text[parser.at] != 0) {
+        fprintf(stderr, "Runtime Error: invalid json at byte %zu\n", parser.at);
+        _minilang_json_fail(file, line);
+    }
+    return json;
+}
+
+// A copy sharing nothing with `json`, which is how json_set and json_push
+// store a json value, so no value can end up inside itself
+static _minilang_json _minilang_json_copy(_minilang_json json) {
+    _minilang_json copy = _minilang_json_new(json->kind);
+    copy->boolean = json->boolean;
+    copy->number = json->number;
+    if (json->text != NULL) {
+        copy->text = _minilang_json_copy_text(json->text);
+    }
+    for (size_t i = 0; i < json->count; i++) {
+        _minilang_json_add(copy, json->kind == _MINILANG_JSON_OBJECT ? json->keys[i] : NULL, _minilang_json_copy(json->items[i]));
+    }
+    return copy;
+}
+
+static _minilang_json _minilang_json_from_int(int value) {
+    _minilang_json json = _minilang_json_new(_MINILANG_JSON_NUMBER);
+    json->number = value;
+    return json;
+}
+
+static _minilang_json _minilang_json_from_float(double value) {
+    _minilang_json json = _minilang_json_new(_MINILANG_JSON_NUMBER);
+    json->number = value;
+    return json;
+}
+
+static _minilang_json _minilang_json_from_bool(bool value) {
+    _minilang_json json = _minilang_json_new(_MINILANG_JSON_BOOL);
+    json->boolean = value;
+    return json;
+}
+
+static _minilang_json _minilang_json_from_string(const char* value) {
+    _minilang_json json = _minilang_json_new(_MINILANG_JSON_STRING);
+    json->text = _minilang_json_copy_text(value);
+    return json;
+}
+
+typedef struct {
+    char* data;
+    size_t length, capacity;
+} _minilang_json_buffer;
+
+static void _minilang_json_append(_minilang_json_buffer* buffer, const char* text, size_t length) {
+    if (buffer->length + length + 1 > buffer->capacity) {
+        while (buffer->length + length + 1 > buffer->capacity) {
+            buffer->capacity = buffer->capacity ? buffer->capacity * 2 : 64;
+        }
+        buffer->data = _minilang_json_check(realloc(buffer->data, buffer->capacity));
+    }
+    memcpy(buffer->data + buffer->length, text, length);
+    buffer->length += length;
+    buffer->data[buffer->length] = 0;
+}
+
+// Whole numbers below 10^15 in full, anything else in the fewest %g digits
+// that read back as the same double, and null for infinities and NaN, which
+// JSON has no spelling for
+static void _minilang_json_format_number(char out[32], double number) {
+    if (!(number - number == 0.0)) {
+        strcpy(out, "null");
+        return;
+    }
+    if (number > -1e15 && number < 1e15 && number == (double)(long long)number) {
+        snprintf(out, 32, "%lld", (long long)number);
+        return;
+    }
+    for (int precision = 1; precision <= 17; precision++) {
+        snprintf(out, 32, "%.*g", precision, number);
+        if (strtod(out, NULL) == number) {
+            return;
+        }
+    }
+}
+
+static void _minilang_json_quote(_minilang_json_buffer* buffer, const char* text) {
+    _minilang_json_append(buffer, "\"", 1);
+    for (const char* c = text; *c != 0; c++) {
+        const char* escaped = NULL;
+        char code[8];
+        switch (*c) {
+            case '"': escaped = "\\\""; break;
+            case '\\': escaped = "\\\\"; break;
+            case '\n': escaped = "\\n"; break;
+            case '\r': escaped = "\\r"; break;
+            case '\t': escaped = "\\t"; break;
+            case '\b': escaped = "\\b"; break;
+            case '\f': escaped = "\\f"; break;
+            default:
+                if ((unsigned char)*c < 0x20) {
+                    snprintf(code, sizeof code, "\\u%04x", (unsigned char)*c);
+                    escaped = code;
+                }
+        }
+        if (escaped != NULL) {
+            _minilang_json_append(buffer, escaped, strlen(escaped));
+        } else {
+            _minilang_json_append(buffer, c, 1);
+        }
+    }
+    _minilang_json_append(buffer, "\"", 1);
+}
+
+static void _minilang_json_write(_minilang_json_buffer* buffer, _minilang_json json) {
+    char number[32];
+    switch (json->kind) {
+        case _MINILANG_JSON_NULL:
+            _minilang_json_append(buffer, "null", 4);
+            break;
+        case _MINILANG_JSON_BOOL:
+            _minilang_json_append(buffer, json->boolean ? "true" : "false", json->boolean ? 4 : 5);
+            break;
+        case _MINILANG_JSON_NUMBER:
+            _minilang_json_format_number(number, json->number);
+            _minilang_json_append(buffer, number, strlen(number));
+            break;
+        case _MINILANG_JSON_STRING:
+            _minilang_json_quote(buffer, json->text);
+            break;
+        default:
+            _minilang_json_append(buffer, json->kind == _MINILANG_JSON_ARRAY ? "[" : "{", 1);
+            for (size_t i = 0; i < json->count; i++) {
+                if (i > 0) {
+                    _minilang_json_append(buffer, ",", 1);
+                }
+                if (json->kind == _MINILANG_JSON_OBJECT) {
+                    _minilang_json_quote(buffer, json->keys[i]);
+                    _minilang_json_append(buffer, ":", 1);
+                }
+                _minilang_json_write(buffer, json->items[i]);
+            }
+            _minilang_json_append(buffer, json->kind == _MINILANG_JSON_ARRAY ? "]" : "}", 1);
+    }
+}
+
+// A MiniLang string holding `text`
+static const char* _minilang_json_result(const char* text, size_t length) {
+    char* result = _minilang_string_alloc(length + 1);
+    memcpy(result, text, length + 1);
+    return result;
+}
+
+static const char* _minilang_json_stringify(_minilang_json json) {
+    _minilang_json_buffer buffer = {NULL, 0, 0};
+    _minilang_json_write(&buffer, json);
+    const char* result = _minilang_json_result(buffer.data, buffer.length);
+    free(buffer.data);
+    return result;
+}
+
+static const char* _minilang_json_type(_minilang_json json) {
+    const char* kind = _minilang_json_kinds[json->kind];
+    return _minilang_json_result(kind, strlen(kind));
+}
+
+static _minilang_json _minilang_json_expect(_minilang_json json, int kind, const char* file, int line) {
+    if (json->kind != kind) {
+        fprintf(stderr, "Runtime Error: expected a json %s, found %s\n", _minilang_json_kinds[kind], _minilang_json_kinds[json->kind]);
+        _minilang_json_fail(file, line);
+    }
+    return json;
+}
+
+// Where `key` is in an object, or -1
+static long _minilang_json_find(_minilang_json json, const char* key, const char* file, int line) {
+    _minilang_json_expect(json, _MINILANG_JSON_OBJECT, file, line);
+    for (size_t i = 0; i < json->count; i++) {
+        if (strcmp(json->keys[i], key) == 0) {
+            return (long)i;
+        }
+    }
+    return -1;
+}
+
+static _minilang_json _minilang_json_get(_minilang_json json, const char* key, const char* file, int line) {
+    long index = _minilang_json_find(json, key, file, line);
+    if (index < 0) {
+        fprintf(stderr, "Runtime Error: json object has no key \"%s\"\n", key);
+        _minilang_json_fail(file, line);
+    }
+    return json->items[index];
+}
+
+static _minilang_json _minilang_json_at(_minilang_json json, int index, const char* file, int line) {
+    _minilang_json_expect(json, _MINILANG_JSON_ARRAY, file, line);
+    if (index < 0 || (size_t)index >= json->count) {
+        fprintf(stderr, "Runtime Error: json index %d out of bounds (size %zu)\n", index, json->count);
+        _minilang_json_fail(file, line);
+    }
+    return json->items[index];
+}
+
+static int _minilang_json_len(_minilang_json json, const char* file, int line) {
+    if (json->kind != _MINILANG_JSON_ARRAY && json->kind != _MINILANG_JSON_OBJECT) {
+        fprintf(stderr, "Runtime Error: expected a json array or object, found %s\n", _minilang_json_kinds[json->kind]);
+        _minilang_json_fail(file, line);
+    }
+    return (int)json->count;
+}
+
+static int _minilang_json_int(_minilang_json json, const char* file, int line) {
+    double number = _minilang_json_expect(json, _MINILANG_JSON_NUMBER, file, line)->number;
+    if (!(number >= -2147483648.0 && number <= 2147483647.0 && number == (double)(int)number)) {
+        char text[32];
+        _minilang_json_format_number(text, number);
+        fprintf(stderr, "Runtime Error: json number %s is not an int\n", text);
+        _minilang_json_fail(file, line);
+    }
+    return (int)number;
+}
+
+static void _minilang_json_set(_minilang_json json, const char* key, _minilang_json item, const char* file, int line) {
+    _minilang_json_add(_minilang_json_expect(json, _MINILANG_JSON_OBJECT, file, line), key, item);
+}
+
+static void _minilang_json_push(_minilang_json json, _minilang_json item, const char* file, int line) {
+    _minilang_json_add(_minilang_json_expect(json, _MINILANG_JSON_ARRAY, file, line), NULL, item);
+}
+
+static const char* _minilang_json_string_of(_minilang_json json, const char* file, int line) {
+    const char* text = _minilang_json_expect(json, _MINILANG_JSON_STRING, file, line)->text;
+    return _minilang_json_result(text, strlen(text));
+}
+
+#define JSON_PARSE(text) _minilang_json_parse(text, __FILE__, __LINE__)
+#define JSON_STRINGIFY(json) _minilang_json_stringify(json)
+#define JSON_TYPE(json) _minilang_json_type(json)
+#define JSON_GET(json, key) _minilang_json_get(json, key, __FILE__, __LINE__)
+#define JSON_HAS(json, key) (_minilang_json_find(json, key, __FILE__, __LINE__) >= 0)
+#define JSON_AT(json, index) _minilang_json_at(json, index, __FILE__, __LINE__)
+#define JSON_LEN(json) _minilang_json_len(json, __FILE__, __LINE__)
+#define JSON_INT(json) _minilang_json_int(json, __FILE__, __LINE__)
+#define JSON_FLOAT(json) (_minilang_json_expect(json, _MINILANG_JSON_NUMBER, __FILE__, __LINE__)->number)
+#define JSON_BOOL(json) (_minilang_json_expect(json, _MINILANG_JSON_BOOL, __FILE__, __LINE__)->boolean)
+#define JSON_STRING(json) _minilang_json_string_of(json, __FILE__, __LINE__)
+#define JSON_SET(json, key, item) _minilang_json_set(json, key, item, __FILE__, __LINE__)
+#define JSON_PUSH(json, item) _minilang_json_push(json, item, __FILE__, __LINE__)
+
+"#;
+
+/// Runtime support for `now_ms` and `sleep_ms`, only added to programs that
+/// use them. The POSIX monotonic clock doesn't jump when the time of day is
+/// set.
+const CLOCK_SUPPORT: &str = r#"// When the program started, which now_ms counts from
+static struct timespec _minilang_started;
+
+__attribute__((constructor)) static void _minilang_start_clock(void) {
+    clock_gettime(CLOCK_MONOTONIC, &_minilang_started);
+}
+
+// Wraps around after about 24 days
+static inline int _minilang_now_ms(void) {
+    struct timespec now;
+    clock_gettime(CLOCK_MONOTONIC, &now);
+    long long ms = (long long)(now.tv_sec - _minilang_started.tv_sec) * 1000
+        + (now.tv_nsec - _minilang_started.tv_nsec) / 1000000;
+    return (int)ms;
+}
+
+static inline void _minilang_sleep_ms(int ms) {
+    // Show what was printed before the pause, e.g. an animation frame
+    fflush(stdout);
+    if (ms <= 0) {
+        return;
+    }
+    struct timespec wait = {ms / 1000, (long)(ms % 1000) * 1000000L};
+    // A signal cuts the wait short, leaving the rest in `wait`
+    while (nanosleep(&wait, &wait) != 0) {
+    }
+}
+
+"#;
+
+/// Runtime support for atomics, only added to programs that use them. Like
+/// channels, they live until the program exits.
+const ATOMIC_SUPPORT: &str = r#"typedef int* _minilang_atomic;
+
+static inline _minilang_atomic _minilang_atomic_new(int value) {
+    _minilang_atomic atomic = malloc(sizeof *atomic);
+    if (atomic == NULL) {
+        fprintf(stderr, "Runtime Error: out of memory\n");
+        exit(1);
+    }
+    *atomic = value;
+    return atomic;
+}
+
+// Wraps around on overflow, whatever --overflow says
+static inline int _minilang_atomic_add(_minilang_atomic atomic, int amount) {
+    return __atomic_add_fetch(atomic, amount, __ATOMIC_SEQ_CST);
+}
+
+static inline int _minilang_atomic_get(_minilang_atomic atomic) {
+    return __atomic_load_n(atomic, __ATOMIC_SEQ_CST);
+}
+
+"#;
+
+/// Runtime support for `spawn` blocks, `parallel for` loops and channels,
+/// only added to programs that use them. Channels live until the program
+/// exits.
+const THREAD_SUPPORT: &str = r#"// Start `body` on its own thread, with its own copy of the `size` bytes at `env`
+static inline pthread_t _minilang_spawn(void* (*body)(void*), const void* env, size_t size) {
+    void* copy = NULL;
+    if (size > 0) {
+        copy = malloc(size);
+        if (copy == NULL) {
+            fprintf(stderr, "Runtime Error: out of memory\n");
+            exit(1);
+        }
+        memcpy(copy, env, size);
+    }
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, body, copy) != 0) {
+        fprintf(stderr, "Runtime Error: cannot start a thread\n");
+        exit(1);
+    }
+    return thread;
+}
+
+// A queue of ints with room for `capacity` of them: sends wait while it is
+// full and receives while it is empty
+typedef struct {
+    pthread_mutex_t lock;
+    pthread_cond_t changed;
+    int* items;
+    int capacity, head, count;
+} _minilang_channel_state;
+
+typedef _minilang_channel_state* _minilang_channel;
+
+static inline _minilang_channel _minilang_channel_new(int capacity, const char* file, int line) {
+    if (capacity < 1) {
+        fprintf(stderr, "Runtime Error: channel capacity must be at least 1, not %d\n", capacity);
+        fprintf(stderr, "  at %s:%d\n", file, line);
+        exit(1);
+    }
+    _minilang_channel channel = malloc(sizeof *channel);
+    int* items = malloc(capacity * sizeof(int));
+    if (channel == NULL || items == NULL) {
+        fprintf(stderr, "Runtime Error: out of memory\n");
+        exit(1);
+    }
+    pthread_mutex_init(&channel->lock, NULL);
+    pthread_cond_init(&channel->changed, NULL);
+    channel->items = items;
+    channel->capacity = capacity;
+    channel->head = 0;
+    channel->count = 0;
+    return channel;
+}
+
+#define CHANNEL_NEW(capacity) _minilang_channel_new(capacity, __FILE__, __LINE__)
+
+// The share of a parallel for's iterations one thread runs, from `from` up
+// to but not including `to`
+typedef struct {
+    int from, to;
+} _minilang_range;
+
+#define _MINILANG_MAX_THREADS 64
+
+// Run `body` on one thread per processor (or MINILANG_THREADS of them), each
+// with a copy of `env` whose leading range is its share of from..to, and
+// wait for them all
+static inline void _minilang_parallel_for(void* (*body)(void*), void* env, size_t size, int from, int to) {
+    if (from >= to) {
+        return;
+    }
+    long long total = (long long)to - from;
+    const char* setting = getenv("MINILANG_THREADS");
+    long long count = setting != NULL ? atoll(setting) : sysconf(_SC_NPROCESSORS_ONLN);
+    if (count < 1) {
+        count = 1;
+    }
+    if (count > _MINILANG_MAX_THREADS) {
+        count = _MINILANG_MAX_THREADS;
+    }
+    if (count > total) {
+        count = total;
+    }
+    pthread_t threads[_MINILANG_MAX_THREADS];
+    _minilang_range* range = env;
+    for (long long i = 0; i < count; i++) {
+        range->from = (int)(from + total * i / count);
+        range->to = (int)(from + total * (i + 1) / count);
+        threads[i] = _minilang_spawn(body, env, size);
+    }
+    for (long long i = 0; i < count; i++) {
+        pthread_join(threads[i], NULL);
+    }
+}
+
+static inline void _minilang_channel_send(_minilang_channel channel, int value) {
+    pthread_mutex_lock(&channel->lock);
+    while (channel->count == channel->capacity) {
+        pthread_cond_wait(&channel->changed, &channel->lock);
+    }
+    channel->items[(channel->head + channel->count) % channel->capacity] = value;
+    channel->count++;
+    pthread_cond_broadcast(&channel->changed);
+    pthread_mutex_unlock(&channel->lock);
+}
+
+static inline int _minilang_channel_receive(_minilang_channel channel) {
+    pthread_mutex_lock(&channel->lock);
+    while (channel->count == 0) {
+        pthread_cond_wait(&channel->changed, &channel->lock);
+    }
+    int value = channel->items[channel->head];
+    channel->head = (channel->head + 1) % channel->capacity;
+    channel->count--;
+    pthread_cond_broadcast(&channel->changed);
+    pthread_mutex_unlock(&channel->lock);
+    return value;
+}
+
+"#;
 
 /// C Code Generator
 pub struct CodeGenerator {
     output: String,
     indent_level: usize,
-    _temp_counter: usize,
-    array_sizes: HashMap<String, usize>,
+    temp_counter: usize,
+    // By C name, so a variable that shadows another keeps a type of its own
     variable_types: HashMap<String, Type>,
+    // What each open block must do on the way out, innermost last: run its
+    // deferred bodies and, with --runtime=rc, release its string variables
+    defer_scopes: Vec<Vec<Cleanup>>,
+    // Index into defer_scopes where each enclosing loop body starts
+    loop_scope_starts: Vec<usize>,
+    // The C name of each variable declared in an open block, one map per
+    // block. A variable shadowing one still in scope gets a name of its own,
+    // so a deferred body replayed under it still reaches the outer one
+    local_names: Vec<HashMap<String, String>>,
+    current_return_type: Option<Type>,
+    // `main` returns int in C, so a bare `send` there must return 0
+    in_main: bool,
+    function_return_types: HashMap<String, Option<Type>>,
+    // Struct typedefs for tuple types, spliced in ahead of the function declarations
+    tuple_typedefs: String,
+    declared_tuples: HashSet<String>,
+    // add_checked and friends return a tuple struct, so their helpers go
+    // in with the typedefs, each once
+    checked_helpers: HashSet<String>,
+    // Set by with_source: emit #line directives and report traps by MiniLang line
+    source: Option<SourceFile>,
+    source_map: Vec<SourceMapEntry>,
+    // Set by with_line_tracking: statements store their line for the trap handler
+    line_tracking: bool,
+    // Set by with_peephole: tidy the finished C text
+    peephole: bool,
+    peephole_stats: PeepholeStats,
+    // Index expressions proven in range, which skip CHECK_BOUNDS
+    safe_indexes: HashSet<NodeId>,
+    always_bounds_check: bool,
+    bounds_checks_elided: usize,
+    // Functions whose calls can display or write the caller's arrays
+    effectful_functions: HashSet<String>,
+    // Declarations of the temporaries that keep the current statement's
+    // operands in order, emitted ahead of it
+    pending_temps: Vec<String>,
+    // Set when compiling one module of a multi-module program: the functions
+    // the other modules define
+    externals: Option<Vec<Function>>,
+    // Set by with_stamp: written as a comment and as a string the executable keeps
+    stamp: Option<Stamp>,
+    // Set by with_banner: a comment block above everything else
+    banner: Option<Banner>,
+    // Set by with_runtime and with_profile: who frees heap objects, and
+    // whether to count allocations
+    runtime: RuntimeMode,
+    profile: bool,
+    // With --runtime=rc, string literals become static objects with a
+    // header, named by their index here
+    string_literals: Vec<String>,
+    // Whether the current function drains the pool, and so needs its mark
+    drains_pool: bool,
+    // Set once a string is built on the stack, to add the runtime for it
+    uses_stack_strings: bool,
+    // Set by the first assert, to add ASSERT_SUPPORT
+    uses_asserts: bool,
+    // Set by the first @memo function, to add MEMO_SUPPORT
+    uses_memo: bool,
+    // Set by the first spawn block, parallel for or channel, to include
+    // pthreads, add THREAD_SUPPORT and lock the heap
+    uses_threads: bool,
+    // Set by the first atomic, to add ATOMIC_SUPPORT
+    uses_atomics: bool,
+    // Set by the first canvas, to add CANVAS_SUPPORT
+    uses_canvas: bool,
+    // Set by the first json value, to add JSON_SUPPORT
+    uses_json: bool,
+    // Set by the first now_ms or sleep_ms, to include time.h and add
+    // CLOCK_SUPPORT
+    uses_clock: bool,
+    // Spawn and parallel for bodies lifted out of the current function, each
+    // a C function of its own, to go in ahead of it
+    spawned_functions: String,
+    spawn_counter: usize,
+}
+
+/// Something an open block does when control leaves it
+#[derive(Clone)]
+enum Cleanup {
+    // A deferred body, with the C names its variables had where it was deferred
+    Defer(Block, HashMap<String, String>),
+    // Drop a string variable's reference, with --runtime=rc
+    Release(String),
+    // Wait for a spawn block's thread, named by its pthread_t variable
+    Join(String),
+}
+
+impl Default for CodeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CodeGenerator {
@@ -17,22 +1016,158 @@ impl CodeGenerator {
         Self {
             output: String::new(),
             indent_level: 0,
-            _temp_counter: 0,
-            array_sizes: HashMap::new(),
+            temp_counter: 0,
             variable_types: HashMap::new(),
+            defer_scopes: Vec::new(),
+            loop_scope_starts: Vec::new(),
+            local_names: Vec::new(),
+            current_return_type: None,
+            in_main: false,
+            function_return_types: HashMap::new(),
+            tuple_typedefs: String::new(),
+            declared_tuples: HashSet::new(),
+            checked_helpers: HashSet::new(),
+            source: None,
+            source_map: Vec::new(),
+            line_tracking: true,
+            peephole: false,
+            peephole_stats: PeepholeStats::default(),
+            safe_indexes: HashSet::new(),
+            always_bounds_check: false,
+            bounds_checks_elided: 0,
+            effectful_functions: HashSet::new(),
+            pending_temps: Vec::new(),
+            externals: None,
+            stamp: None,
+            banner: None,
+            runtime: RuntimeMode::default(),
+            profile: false,
+            string_literals: Vec::new(),
+            drains_pool: false,
+            uses_stack_strings: false,
+            uses_asserts: false,
+            uses_memo: false,
+            uses_threads: false,
+            uses_atomics: false,
+            uses_clock: false,
+            uses_canvas: false,
+            uses_json: false,
+            spawned_functions: String::new(),
+            spawn_counter: 0,
         }
     }
     
-    /// Generate C code from a MiniLang program
+    /// Map the generated C back to `source`: statements get `#line` directives,
+    /// so C compiler messages and bounds errors name MiniLang lines, and crashes
+    /// from signals report the last MiniLang line that started executing
+    /// unless `with_line_tracking(false)` turned that off.
+    pub fn with_source(mut self, filename: &str, source: &str) -> Self {
+        self.source = Some(SourceFile {
+            name: filename.to_string(),
+            text: source.to_string(),
+            lines: LineIndex::new(source),
+        });
+        self
+    }
+    
+    /// Have every statement record its line for crashes to report. The
+    /// stores are volatile, so the C compiler keeps every one of them even
+    /// in hot loops; optimized builds turn this off, and their crashes name
+    /// only the file.
+    pub fn with_line_tracking(mut self, enabled: bool) -> Self {
+        self.line_tracking = enabled;
+        self
+    }
+    
+    /// Run the peephole pass over the finished C, for more readable output
+    pub fn with_peephole(mut self, enabled: bool) -> Self {
+        self.peephole = enabled;
+        self
+    }
+    
+    /// What the peephole pass changed during `generate`
+    pub fn peephole_stats(&self) -> &PeepholeStats {
+        &self.peephole_stats
+    }
+    
+    /// Keep the bounds check on every index, even ones proven in range
+    pub fn with_always_bounds_check(mut self, enabled: bool) -> Self {
+        self.always_bounds_check = enabled;
+        self
+    }
+    
+    /// Compile the program as one module of a multi-module build, to be linked
+    /// with the modules defining `functions`. Their public functions get
+    /// prototypes, and every public function here keeps external linkage (even
+    /// `@inline` ones) so the other modules can call it.
+    pub fn with_externals(mut self, functions: &[Function]) -> Self {
+        self.externals = Some(functions.iter()
+            .filter(|function| function.visibility == Visibility::Public)
+            .cloned()
+            .collect());
+        self
+    }
+    
+    /// Record what the code was built from at the top of the C and in a
+    /// string that survives into the executable, for `minilang inspect`
+    pub fn with_stamp(mut self, stamp: Stamp) -> Self {
+        self.stamp = Some(stamp);
+        self
+    }
+    
+    /// Start the C with a project's banner comment
+    pub fn with_banner(mut self, banner: Banner) -> Self {
+        self.banner = Some(banner);
+        self
+    }
+    
+    /// Choose who frees the strings the program builds at runtime
+    pub fn with_runtime(mut self, runtime: RuntimeMode) -> Self {
+        self.runtime = runtime;
+        self
+    }
+    
+    /// Count allocations and print the totals to stderr when the program exits
+    pub fn with_profile(mut self, enabled: bool) -> Self {
+        self.profile = enabled;
+        self
+    }
+    
+    /// How many index expressions were emitted without a bounds check
+    pub fn bounds_checks_elided(&self) -> usize {
+        self.bounds_checks_elided
+    }
+    
+    /// Generated C line to MiniLang line table, filled by `generate` when a
+    /// source was attached with `with_source`
+    pub fn source_map(&self) -> &[SourceMapEntry] {
+        &self.source_map
+    }
+    
+    /// Generate C code from a MiniLang program. Each call starts fresh, so
+    /// one generator can be reused for any number of programs.
     pub fn generate(&mut self, program: &Program) -> Result<String, String> {
+        self.reset();
+        let defines_main = program.functions.iter().any(|f| f.name == "main");
+        
         // Add C headers
-        self.emit_headers();
+        let includes_end = self.emit_headers(defines_main);
         
         // Add runtime support functions
-        self.emit_runtime_support();
+        let heap_at = self.emit_runtime_support(defines_main);
+        let typedefs_at = self.output.len();
         
-        // Forward declare all functions
-        for function in &program.functions {
+        let externals = self.externals.clone().unwrap_or_default();
+        for function in externals.iter().chain(&program.functions) {
+            self.function_return_types.insert(function.name.clone(), function.return_type.clone());
+        }
+        if !self.always_bounds_check {
+            self.safe_indexes = bounds::safe_indexes(program);
+        }
+        self.effectful_functions = effects::effectful_functions(program, &externals);
+        
+        // Forward declare all functions, including the other modules' ones
+        for function in externals.iter().chain(&program.functions) {
             self.emit_function_declaration(function);
         }
         self.emit_line("");
@@ -43,25 +1178,139 @@ impl CodeGenerator {
             self.emit_line("");
         }
         
+        // Tuple structs are discovered while emitting, so they go in last
+        let mut typedefs = std::mem::take(&mut self.tuple_typedefs);
+        if self.uses_stack_strings {
+            typedefs.insert_str(0, &runtime::stack_string_support(self.runtime));
+        }
+        if self.uses_asserts {
+            typedefs.insert_str(0, ASSERT_SUPPORT);
+        }
+        if self.uses_memo {
+            typedefs.insert_str(0, MEMO_SUPPORT);
+        }
+        if self.uses_atomics {
+            typedefs.insert_str(0, ATOMIC_SUPPORT);
+        }
+        if self.uses_clock {
+            typedefs.insert_str(0, CLOCK_SUPPORT);
+        }
+        if self.uses_canvas {
+            typedefs.insert_str(0, CANVAS_SUPPORT);
+        }
+        if self.uses_json {
+            typedefs.insert_str(0, JSON_SUPPORT);
+        }
+        if self.uses_threads {
+            if self.runtime == RuntimeMode::Rc {
+                return Err("spawn blocks, parallel for loops and channels need --runtime=arena or --runtime=none: reference counts are not shared safely between threads".to_string());
+            }
+            typedefs.insert_str(0, THREAD_SUPPORT);
+        }
+        for (i, text) in self.string_literals.iter().enumerate() {
+            typedefs.push_str(&format!(
+                "{} _minilang_str_{} = {{{{_MINILANG_STATIC_REFS, 0, 0, 0}}, \"{}\"}};\n",
+                runtime::static_string_type(text.len()), i, self.escape_string(text)
+            ));
+        }
+        if !self.string_literals.is_empty() {
+            typedefs.push('\n');
+        }
+        self.output.insert_str(typedefs_at, &typedefs);
+        // Whether allocation needs a lock is only known now
+        let heap = runtime::heap_support(self.runtime, self.profile, defines_main, self.uses_threads);
+        self.output.insert_str(heap_at, &heap);
+        if self.uses_threads {
+            self.output.insert_str(includes_end, "#include <pthread.h>\n#include <unistd.h>\n");
+            // A crash reports the line its own thread was on
+            self.output = self.output.replacen(
+                "static volatile int _minilang_line",
+                "static __thread volatile int _minilang_line",
+                1,
+            );
+        }
+        if self.uses_clock {
+            self.output.insert_str(includes_end, "#include <time.h>\n");
+            // clock_gettime and nanosleep are POSIX, not C99
+            self.output.insert_str(0, "#define _POSIX_C_SOURCE 200809L\n");
+        }
+        
+        if self.peephole {
+            let (output, stats) = peephole::optimize(&self.output);
+            self.output = output;
+            self.peephole_stats = stats;
+        }
+        self.emit_stamp();
+        if let Some(banner) = &self.banner {
+            let source_name = self.source.as_ref().map_or("MiniLang source", |source| source.name.as_str());
+            self.output.insert_str(0, &banner.to_c_comment(source_name));
+        }
+        
+        // Each directive renumbers the line after it
+        self.source_map = self.output.lines()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                let rest = line.trim_start().strip_prefix("#line ")?;
+                let source_line = rest.split_whitespace().next()?.parse().ok()?;
+                Some(SourceMapEntry { c_line: index + 2, source_line })
+            })
+            .collect();
+        
         Ok(self.output.clone())
     }
     
-    /// Emit C headers
-    fn emit_headers(&mut self) {
+    /// Drop everything left from a previous `generate`, keeping only the
+    /// options set with the `with_*` builders
+    fn reset(&mut self) {
+        *self = Self {
+            source: self.source.take(),
+            line_tracking: self.line_tracking,
+            peephole: self.peephole,
+            always_bounds_check: self.always_bounds_check,
+            externals: self.externals.take(),
+            stamp: self.stamp.take(),
+            banner: self.banner.take(),
+            runtime: self.runtime,
+            profile: self.profile,
+            ..Self::new()
+        };
+    }
+    
+    /// Emit C headers, returning where the includes end
+    fn emit_headers(&mut self, defines_main: bool) -> usize {
         self.emit_line("#include <stdio.h>");
+        self.emit_line("#include <stdarg.h>");
         self.emit_line("#include <stdlib.h>");
         self.emit_line("#include <string.h>");
         self.emit_line("#include <stdbool.h>");
+        if self.source.is_some() && defines_main {
+            self.emit_line("#include <signal.h>");
+        }
+        let includes_end = self.output.len();
         self.emit_line("");
         self.emit_line("// Generated from MiniLang source");
         self.emit_line("");
+        includes_end
+    }
+    
+    /// Put the stamp on the first line of the C and, as a string gcc keeps
+    /// even though nothing reads it, at the end. Done after the peephole
+    /// pass, which would take `((used))` for redundant parentheses.
+    fn emit_stamp(&mut self) {
+        let Some(stamp) = &self.stamp else { return };
+        let line = stamp.to_line();
+        let escaped = self.escape_string(&line);
+        self.output.insert_str(0, &format!("// {}\n", line));
+        self.emit_line(&format!("__attribute__((used)) static const char _minilang_stamp[] = \"{}\";", escaped));
     }
     
-    /// Emit runtime support functions
-    fn emit_runtime_support(&mut self) {
-        // Array bounds checking
+    /// Emit runtime support functions, returning where the heap's support
+    /// goes once `generate` knows whether it needs a lock
+    fn emit_runtime_support(&mut self, defines_main: bool) -> usize {
+        // Array bounds checking. The runtime is static, so every module of a
+        // multi-module program carries its own copy without clashing at link time.
         self.emit_line("// Runtime support");
-        self.emit_line("void _minilang_check_bounds(int index, int size, const char* file, int line) {");
+        self.emit_line("static inline void _minilang_check_bounds(int index, int size, const char* file, int line) {");
         self.indent_level += 1;
         self.emit_line("if (index < 0 || index >= size) {");
         self.indent_level += 1;
@@ -75,10 +1324,138 @@ impl CodeGenerator {
         self.emit_line("");
         self.emit_line("#define CHECK_BOUNDS(idx, size) _minilang_check_bounds(idx, size, __FILE__, __LINE__)");
         self.emit_line("");
+        
+        if self.source.is_some() && self.line_tracking {
+            // Statements record their line so a crash can say where it happened
+            self.emit_line("static volatile int _minilang_line = 0;");
+        }
+        if let (Some(source), true) = (&self.source, defines_main) {
+            // main installs the handler, so only its module needs one
+            let name = format!("\"{}\"", self.escape_string(&source.name));
+            self.emit_line("static void _minilang_trap(int sig) {");
+            self.indent_level += 1;
+            // gcc turns division by a known zero into a trapping instruction (SIGILL)
+            self.emit_line("const char* what = sig == SIGSEGV ? \"invalid memory access (stack overflow?)\"");
+            self.emit_line("    : \"arithmetic error (division by zero)\";");
+            self.emit_line("fprintf(stderr, \"Runtime Error: %s\\n\", what);");
+            if self.line_tracking {
+                self.emit_line(&format!("fprintf(stderr, \"  at %s:%d\\n\", {}, _minilang_line);", name));
+            } else {
+                self.emit_line(&format!("fprintf(stderr, \"  in %s (build with -O0 to see the line)\\n\", {});", name));
+            }
+            self.emit_line("_Exit(128 + sig);");
+            self.indent_level -= 1;
+            self.emit_line("}");
+            self.emit_line("");
+        }
+        
+        // Strings are immutable and assignment only rebinds the pointer, so a
+        // built string may be shared by any number of variables
+        let heap_at = self.output.len();
+        
+        // Division and modulo by 2^k, truncating toward zero like C's / and %
+        self.emit_line("static inline int _minilang_div_pow2(int x, int shift) {");
+        self.indent_level += 1;
+        self.emit_line("return (x < 0 ? x + ((1 << shift) - 1) : x) >> shift;");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("static inline int _minilang_mod_pow2(int x, int mask) {");
+        self.indent_level += 1;
+        self.emit_line("int r = x & mask;");
+        self.emit_line("return (x < 0 && r != 0) ? r - (mask + 1) : r;");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        
+        // approx_equal(a, b, eps)
+        self.emit_line("static inline bool _minilang_approx_equal(double a, double b, double eps) {");
+        self.indent_level += 1;
+        self.emit_line("double diff = a - b;");
+        self.emit_line("return (diff < 0 ? -diff : diff) <= eps;");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        
+        // String formatting (format(...) and interpolated strings used as values)
+        self.emit_line("static inline const char* _minilang_format(const char* fmt, ...) {");
+        self.indent_level += 1;
+        self.emit_line("va_list args;");
+        self.emit_line("va_start(args, fmt);");
+        self.emit_line("int length = vsnprintf(NULL, 0, fmt, args);");
+        self.emit_line("va_end(args);");
+        self.emit_line("char* buffer = _minilang_string_alloc(length + 1);");
+        self.emit_line("va_start(args, fmt);");
+        self.emit_line("vsnprintf(buffer, length + 1, fmt, args);");
+        self.emit_line("va_end(args);");
+        self.emit_line("return buffer;");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        
+        // to_string(int) and parse_int(string). parse_int accepts an optional
+        // sign followed by decimal digits and nothing else; anything that is
+        // not an int stops the program like an out of bounds index does.
+        self.emit_line("static inline const char* _minilang_int_to_string(int value) {");
+        self.indent_level += 1;
+        self.emit_line("return _minilang_format(\"%d\", value);");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("static inline int _minilang_parse_int(const char* text, const char* file, int line) {");
+        self.indent_level += 1;
+        self.emit_line("const char* p = text;");
+        self.emit_line("bool negative = *p == '-';");
+        self.emit_line("if (*p == '-' || *p == '+') p++;");
+        self.emit_line("bool valid = *p != '\\0';");
+        self.emit_line("long long value = 0;");
+        self.emit_line("for (; valid && *p != '\\0'; p++) {");
+        self.indent_level += 1;
+        self.emit_line("valid = *p >= '0' && *p <= '9';");
+        self.emit_line("value = value * 10 + (*p - '0');");
+        self.emit_line("valid = valid && value <= (negative ? 2147483648LL : 2147483647LL);");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("if (!valid) {");
+        self.indent_level += 1;
+        self.emit_line("fprintf(stderr, \"Runtime Error: cannot parse \\\"%s\\\" as int\\n\", text);");
+        self.emit_line("fprintf(stderr, \"  at %s:%d\\n\", file, line);");
+        self.emit_line("exit(1);");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("return (int)(negative ? -value : value);");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("#define PARSE_INT(text) _minilang_parse_int(text, __FILE__, __LINE__)");
+        self.emit_line("");
+        heap_at
     }
     
     /// Emit function forward declaration
     fn emit_function_declaration(&mut self, function: &Function) {
+        if let Some(return_type) = &function.return_type {
+            self.register_tuple_type(return_type);
+        }
+        for param in &function.params {
+            self.register_tuple_type(&param.typ);
+        }
+        // The prototype needs the channel and atomic types even if this
+        // module never uses them otherwise
+        let signature: Vec<&Type> = function.params.iter().map(|param| &param.typ).chain(&function.return_type).collect();
+        if signature.iter().any(|typ| typ.contains(&Type::Channel)) {
+            self.uses_threads = true;
+        }
+        if signature.iter().any(|typ| typ.contains(&Type::Atomic)) {
+            self.uses_atomics = true;
+        }
+        if signature.iter().any(|typ| typ.contains(&Type::Canvas)) {
+            self.uses_canvas = true;
+        }
+        if signature.iter().any(|typ| typ.contains(&Type::Json)) {
+            self.uses_json = true;
+        }
+        
         let return_type = if function.name == "main" {
             "int".to_string()
         } else {
@@ -89,7 +1466,21 @@ impl CodeGenerator {
             .collect::<Vec<_>>()
             .join(", ");
         
-        self.emit_line(&format!("{} {}({});", return_type, self.c_identifier(&function.name), params));
+        self.emit_line(&format!("{}{} {}({});", self.function_linkage(function), return_type, self.c_identifier(&function.name), params));
+    }
+    
+    /// Private functions stay inside their module. `@inline` functions that
+    /// were not inlined by the optimizer are left to the C compiler, unless
+    /// other modules may call them.
+    fn function_linkage(&self, function: &Function) -> &'static str {
+        let private = function.visibility == Visibility::Private;
+        if function.has_attribute("inline") && (private || self.externals.is_none()) {
+            "static inline "
+        } else if private {
+            "static "
+        } else {
+            ""
+        }
     }
     
     /// Emit function definition
@@ -108,40 +1499,233 @@ impl CodeGenerator {
                 .join(", ")
         };
         
-        self.emit_line(&format!("{} {}({}) {{", return_type, self.c_identifier(&function.name), params));
+        // A @memo function's name belongs to the cache in front of its body
+        let memo = function.has_attribute("memo");
+        let (linkage, name) = if memo {
+            ("static ", format!("_minilang_memo_{}", self.c_identifier(&function.name)))
+        } else {
+            (self.function_linkage(function), self.c_identifier(&function.name))
+        };
+        
+        let function_at = self.output.len();
+        self.emit_line_directive(&function.span);
+        self.emit_line(&format!("{}{} {}({}) {{", linkage, return_type, name, params));
         self.indent_level += 1;
+        if function.name == "main" && self.source.is_some() {
+            self.emit_line("signal(SIGFPE, _minilang_trap);");
+            self.emit_line("signal(SIGILL, _minilang_trap);");
+            self.emit_line("signal(SIGSEGV, _minilang_trap);");
+        }
+        if function.name == "main" && self.profile {
+            self.emit_line("atexit(_minilang_profile_report);");
+        }
+        self.current_return_type = function.return_type.clone();
+        self.in_main = function.name == "main";
+        self.local_names.push(HashMap::new());
+        for param in &function.params {
+            let name = self.declare_variable(&param.name);
+            self.variable_types.insert(name, param.typ.clone());
+        }
+        
+        // The caller keeps its reference to a string argument, but the
+        // parameter may be reassigned, so it holds one of its own
+        let mark_at = self.output.len();
+        self.drains_pool = false;
+        self.defer_scopes.push(Vec::new());
+        for param in &function.params {
+            if self.is_counted(&param.typ) {
+                let name = self.variable_identifier(&param.name);
+                self.emit_line(&format!("_minilang_retain({});", name));
+                self.own_string(name);
+            }
+        }
         
         // Generate body
         self.emit_block(&function.body)?;
         
         // Add implicit return for void functions
         if function.return_type.is_none() {
-            if function.name == "main" {
-                self.emit_line("return 0;");
-            } else {
-                self.emit_line("return;");
-            }
+            self.emit_deferred_from(0)?;
+            self.emit_line(self.bare_return());
+        }
+        self.defer_scopes.pop();
+        self.local_names.pop();
+        if self.drains_pool {
+            let indent = "    ".repeat(self.indent_level);
+            self.output.insert_str(mark_at, &format!("{}size_t _minilang_mark = _minilang_pool_len;\n", indent));
         }
         
         self.indent_level -= 1;
         self.emit_line("}");
         
+        if memo {
+            self.emit_memo_cache(function, &return_type, &params, &name);
+        }
+        let spawned = std::mem::take(&mut self.spawned_functions);
+        self.output.insert_str(function_at, &spawned);
         Ok(())
     }
     
-    /// Emit a block
+    /// Emit a `@memo` function under its own name: look the arguments up in
+    /// a static table, and call `body` and keep its result on a miss.
+    /// Recursive calls in the body come back here, so each value is computed
+    /// once while it stays cached. Arguments are compared by their bytes, so
+    /// -0.0 and 0.0 are different keys. Each thread has a table of its own,
+    /// so spawned threads never read an entry another is writing.
+    fn emit_memo_cache(&mut self, function: &Function, return_type: &str, params: &str, body: &str) {
+        self.uses_memo = true;
+        let args: Vec<String> = function.params.iter().map(|p| self.c_identifier(&p.name)).collect();
+        let mut fields = String::from("bool used; ");
+        for (i, param) in function.params.iter().enumerate() {
+            fields.push_str(&format!("{} _{}; ", self.c_type(&Some(param.typ.clone())), i));
+        }
+        fields.push_str(&format!("{} result;", return_type));
+        
+        self.emit_line("");
+        self.emit_line_directive(&function.span);
+        self.emit_line(&format!("{}{} {}({}) {{", self.function_linkage(function), return_type, self.c_identifier(&function.name), params));
+        self.indent_level += 1;
+        self.emit_line(&format!("static __thread struct {{ {} }} cache[_MINILANG_MEMO_SLOTS];", fields));
+        self.emit_line("unsigned long long hash = 14695981039346656037ULL;");
+        for arg in &args {
+            self.emit_line(&format!("hash = _minilang_memo_mix(hash, &{0}, sizeof {0});", arg));
+        }
+        self.emit_line("size_t slot = (size_t)(hash % _MINILANG_MEMO_SLOTS);");
+        let hit: String = args.iter()
+            .enumerate()
+            .map(|(i, arg)| format!(" && memcmp(&cache[slot]._{0}, &{1}, sizeof {1}) == 0", i, arg))
+            .collect();
+        self.emit_line(&format!("if (cache[slot].used{}) {{", hit));
+        self.indent_level += 1;
+        self.emit_line("return cache[slot].result;");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line(&format!("{} result = {}({});", return_type, body, args.join(", ")));
+        self.emit_line("cache[slot].used = true;");
+        for (i, arg) in args.iter().enumerate() {
+            self.emit_line(&format!("cache[slot]._{} = {};", i, arg));
+        }
+        self.emit_line("cache[slot].result = result;");
+        self.emit_line("return result;");
+        self.indent_level -= 1;
+        self.emit_line("}");
+    }
+    
+    /// Emit a block, running its deferred bodies when control falls off the end
     fn emit_block(&mut self, block: &Block) -> Result<(), String> {
+        self.defer_scopes.push(Vec::new());
+        self.local_names.push(HashMap::new());
         for statement in &block.statements {
             self.emit_statement(statement)?;
         }
+        
+        let ends_in_jump = matches!(
+            block.statements.last(),
+            Some(Statement::Return(_) | Statement::Break(_) | Statement::Continue(_))
+        );
+        let scope = self.defer_scopes.len() - 1;
+        if !ends_in_jump {
+            self.emit_deferred_from(scope)?;
+        }
+        self.defer_scopes.pop();
+        self.local_names.pop();
+        Ok(())
+    }
+    
+    /// Emit the cleanup of every open scope from `start` inward, innermost
+    /// scope first and latest first within a scope: deferred bodies, and
+    /// releases of the string variables declared before them
+    fn emit_deferred_from(&mut self, start: usize) -> Result<(), String> {
+        let pending: Vec<Cleanup> = self.defer_scopes[start..].iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev().cloned())
+            .collect();
+        
+        for cleanup in &pending {
+            match cleanup {
+                Cleanup::Defer(body, names) => {
+                    // Its names mean what they did at the defer, not what
+                    // a later declaration in between made them
+                    let outer_names = std::mem::replace(&mut self.local_names, vec![names.clone()]);
+                    self.emit_line("{");
+                    self.indent_level += 1;
+                    self.emit_block(body)?;
+                    self.indent_level -= 1;
+                    self.emit_line("}");
+                    self.local_names = outer_names;
+                }
+                Cleanup::Release(name) => self.emit_line(&format!("_minilang_release({});", name)),
+                Cleanup::Join(thread) => self.emit_line(&format!("pthread_join({}, NULL);", thread)),
+            }
+        }
         Ok(())
     }
     
+    /// Check whether any cleanup is pending from scope `start` inward
+    fn has_deferred_from(&self, start: usize) -> bool {
+        self.defer_scopes[start..].iter().any(|scope| !scope.is_empty())
+    }
+    
+    /// Whether variables of `typ` hold a counted reference: strings, with --runtime=rc
+    fn is_counted(&self, typ: &Type) -> bool {
+        self.runtime == RuntimeMode::Rc && *typ == Type::String
+    }
+    
+    /// Release the string variable `name` (already a C identifier) when its block ends
+    fn own_string(&mut self, name: String) {
+        if let Some(scope) = self.defer_scopes.last_mut() {
+            scope.push(Cleanup::Release(name));
+        }
+    }
+    
+    /// Free the pool entries this function made that no variable took
+    fn emit_drain(&mut self) {
+        self.drains_pool = true;
+        self.emit_line("_minilang_drain(_minilang_mark);");
+    }
+    
+    /// Emit a loop body, recording where its defer scopes begin for
+    /// break/continue. With --runtime=rc, `drain` frees what the loop's
+    /// header built on the previous check before the body runs.
+    fn emit_loop_body(&mut self, body: &Block, drain: bool) -> Result<(), String> {
+        if drain {
+            self.emit_drain();
+        }
+        self.loop_scope_starts.push(self.defer_scopes.len());
+        let result = self.emit_block(body);
+        self.loop_scope_starts.pop();
+        result
+    }
+    
+    /// Point the following C line at the MiniLang line containing `span`
+    fn emit_line_directive(&mut self, span: &Span) {
+        let directive = self.source.as_ref().map(|source| {
+            format!("#line {} \"{}\"\n", source.lines.line_of(span.start), self.escape_string(&source.name))
+        });
+        if let Some(directive) = directive {
+            self.output.push_str(&directive);
+        }
+    }
+    
     /// Emit a statement
     fn emit_statement(&mut self, statement: &Statement) -> Result<(), String> {
-        match statement {
+        let span = statement.span();
+        if let Some(line) = self.source.as_ref().map(|source| source.lines.line_of(span.start)) {
+            // The tracker goes before the directive so the statement's own
+            // C line is the one numbered as its MiniLang line
+            if self.line_tracking {
+                self.emit_line(&format!("_minilang_line = {};", line));
+            }
+            self.emit_line_directive(span);
+        }
+        
+        // Nested statements declare their own temporaries
+        let outer_temps = std::mem::take(&mut self.pending_temps);
+        let temps_at = self.output.len();
+        let result = match statement {
             Statement::Const(const_stmt) => self.emit_const_statement(const_stmt),
             Statement::Let(let_stmt) => self.emit_let_statement(let_stmt),
+            Statement::LetTuple(let_tuple) => self.emit_let_tuple_statement(let_tuple),
             Statement::Display(display_stmt) => self.emit_display_statement(display_stmt),
             Statement::If(if_stmt) => self.emit_if_statement(if_stmt),
             Statement::While(while_stmt) => self.emit_while_statement(while_stmt),
@@ -158,58 +1742,299 @@ impl CodeGenerator {
                 Ok(())
             }
             Statement::Break(_) => {
+                let start = self.loop_scope_starts.last().copied().unwrap_or(0);
+                self.emit_deferred_from(start)?;
                 self.emit_line("break;");
                 Ok(())
             }
 
             Statement::Continue(_) => {
+                let start = self.loop_scope_starts.last().copied().unwrap_or(0);
+                self.emit_deferred_from(start)?;
                 self.emit_line("continue;");
                 Ok(())
             }
+            Statement::Defer(defer_stmt) => {
+                // Nothing is emitted here; the body is replayed at each exit of the block
+                let names = self.local_names.iter().flatten()
+                    .map(|(name, c_name)| (name.clone(), c_name.clone()))
+                    .collect();
+                if let Some(scope) = self.defer_scopes.last_mut() {
+                    scope.push(Cleanup::Defer(defer_stmt.body.clone(), names));
+                }
+                Ok(())
+            }
+            Statement::Spawn(spawn_stmt) => self.emit_spawn_statement(spawn_stmt),
+        };
+        
+        let temps = std::mem::replace(&mut self.pending_temps, outer_temps);
+        let indent = "    ".repeat(self.indent_level);
+        let declarations: String = temps.iter().map(|decl| format!("{}{}\n", indent, decl)).collect();
+        self.output.insert_str(temps_at, &declarations);
+        if result.is_ok() && self.runtime == RuntimeMode::Rc && self.statement_allocates(statement) {
+            self.emit_drain();
+        }
+        result
+    }
+    
+    /// Whether the statement's own expressions, not its nested blocks, may
+    /// put objects in the pool
+    fn statement_allocates(&self, statement: &Statement) -> bool {
+        let allocates = |expr: &Expression| self.expression_allocates(expr);
+        match statement {
+            Statement::Const(stmt) => allocates(&stmt.value),
+            Statement::Let(stmt) => stmt.value.as_ref().is_some_and(allocates),
+            Statement::LetTuple(stmt) => allocates(&stmt.value),
+            Statement::Display(stmt) => stmt.expressions.iter().any(allocates),
+            Statement::Expression(stmt) => allocates(&stmt.expression),
+            Statement::If(stmt) => allocates(&stmt.condition),
+            Statement::While(stmt) => allocates(&stmt.condition),
+            Statement::DoWhile(stmt) => allocates(&stmt.condition),
+            Statement::For(stmt) => self.for_header_allocates(stmt),
+            Statement::Return(_) | Statement::Break(_) | Statement::Continue(_)
+            | Statement::Block(_) | Statement::Defer(_) | Statement::Spawn(_) => false,
+        }
+    }
+    
+    fn for_header_allocates(&self, stmt: &ForStmt) -> bool {
+        stmt.init.as_deref().is_some_and(|init| self.statement_allocates(init))
+            || stmt.condition.as_ref().is_some_and(|condition| self.expression_allocates(condition))
+            || stmt.update.as_ref().is_some_and(|update| self.expression_allocates(update))
+    }
+    
+    /// Whether evaluating `expr` may build a string or call a function that returns one
+    fn expression_allocates(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Call(call) => {
+                !(self.is_builtin(call, "approx_equal") || self.is_builtin(call, "parse_int") || self.is_builtin(call, "assert")
+                    || self.is_checked_arithmetic(call) || CLOCK_BUILTINS.iter().any(|name| self.is_builtin(call, name)))
+                    || call.args.iter().any(|arg| self.expression_allocates(arg))
+            }
+            Expression::Binary(binary) => self.expression_allocates(&binary.left) || self.expression_allocates(&binary.right),
+            Expression::Unary(unary) => self.expression_allocates(&unary.operand),
+            Expression::Index(index) => self.expression_allocates(&index.array) || self.expression_allocates(&index.index),
+            Expression::Assign(assign) => self.expression_allocates(&assign.value),
+            Expression::Literal(lit) => match &lit.value {
+                Literal::Array(elements) | Literal::Tuple(elements) => {
+                    elements.iter().any(|element| self.expression_allocates(element))
+                }
+                Literal::InterpolatedString(_) => true,
+                _ => false,
+            },
+            Expression::Identifier(_) => false,
         }
     }
 
     /// Emit const statement (as C const)
+    /// Emit a spawn block: its body becomes a thread function of its own,
+    /// started here with a copy of each variable it uses, and the thread is
+    /// joined when the enclosing block exits
+    fn emit_spawn_statement(&mut self, stmt: &SpawnStmt) -> Result<(), String> {
+        self.uses_threads = true;
+        let function = format!("_minilang_spawn_{}", self.spawn_counter);
+        let thread = format!("_minilang_thread_{}", self.spawn_counter);
+        self.spawn_counter += 1;
+        
+        let captures = self.thread_captures(&stmt.body, None);
+        self.emit_thread_function(&function, &captures, false, &stmt.span, |this| this.emit_block(&stmt.body))?;
+        let env = self.thread_env(&function, &captures, false);
+        self.emit_line(&format!("pthread_t {} = _minilang_spawn({}, {});", thread, function, env));
+        if let Some(scope) = self.defer_scopes.last_mut() {
+            scope.push(Cleanup::Join(thread));
+        }
+        Ok(())
+    }
+    
+    /// Emit a parallel for: the loop goes in a thread function, which each
+    /// of several threads runs over its share of the range, and the
+    /// statement waits for them all
+    fn emit_parallel_for(&mut self, stmt: &ForStmt) -> Result<(), String> {
+        let Some((variable, from, to)) = counted_range(stmt) else {
+            // The optimizer rewrote the header. Running every iteration on
+            // this thread, in order, is one of the orders they may run in.
+            return self.emit_for_statement(&ForStmt { parallel: false, ..stmt.clone() });
+        };
+        self.uses_threads = true;
+        let function = format!("_minilang_parallel_{}", self.spawn_counter);
+        self.spawn_counter += 1;
+        
+        let (assignments, bounds) = self.sequence_operands(&[from, to])?;
+        let captures = self.thread_captures(&stmt.body, Some(variable));
+        self.emit_thread_function(&function, &captures, true, &stmt.span, |this| {
+            this.local_names.push(HashMap::new());
+            let index = this.declare_variable(variable);
+            this.variable_types.insert(index.clone(), Type::Int);
+            this.emit_line(&format!("for (int {0} = range.from; {0} < range.to; {0}++) {{", index));
+            this.indent_level += 1;
+            let body = this.emit_loop_body(&stmt.body, false);
+            this.indent_level -= 1;
+            this.emit_line("}");
+            this.local_names.pop();
+            body
+        })?;
+        let env = self.thread_env(&function, &captures, true);
+        let call = format!("_minilang_parallel_for({}, {}, {}, {})", function, env, bounds[0], bounds[1]);
+        self.emit_line(&format!("{};", Self::sequenced(&assignments, call)));
+        Ok(())
+    }
+    
+    /// The variables of the enclosing function a thread body uses, except
+    /// `own` (a parallel for's loop variable), with their types
+    fn thread_captures(&self, body: &Block, own: Option<&str>) -> Vec<(String, Type)> {
+        body.free_variables().into_iter()
+            .filter(|name| Some(name.as_str()) != own)
+            .filter_map(|name| self.variable_type(&name).map(|typ| (name, typ)))
+            .collect()
+    }
+    
+    /// Emit the C function a thread runs, to go in ahead of the current
+    /// function. It takes a struct holding `captures` (and, when `ranged`,
+    /// the share of a parallel for's range to run first), copies them into
+    /// locals, frees the struct and runs `body`. The type checker only lets
+    /// a thread read the variables, and arrays only while their function
+    /// waits for it, so arrays are passed as pointers and the rest copied.
+    fn emit_thread_function(
+        &mut self,
+        function: &str,
+        captures: &[(String, Type)],
+        ranged: bool,
+        span: &Span,
+        body: impl FnOnce(&mut Self) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let outer_output = std::mem::take(&mut self.output);
+        let outer_indent = std::mem::replace(&mut self.indent_level, 0);
+        let outer_scopes = std::mem::take(&mut self.defer_scopes);
+        let outer_loops = std::mem::take(&mut self.loop_scope_starts);
+        let outer_in_main = std::mem::replace(&mut self.in_main, false);
+        let has_env = ranged || !captures.is_empty();
+        let env = format!("{}_env", function);
+        let fields: Vec<(String, String)> = captures.iter()
+            .map(|(name, typ)| (self.c_type(&Some(typ.clone())), self.variable_identifier(name)))
+            .collect();
+        if has_env {
+            self.emit_line("typedef struct {");
+            if ranged {
+                self.emit_line("    _minilang_range range;");
+            }
+            for (c_type, name) in &fields {
+                self.emit_line(&format!("    {} {};", c_type, name));
+            }
+            self.emit_line(&format!("}} {};", env));
+            self.emit_line("");
+        }
+        self.emit_line_directive(span);
+        self.emit_line(&format!("static void* {}(void* arg) {{", function));
+        self.indent_level += 1;
+        if has_env {
+            self.emit_line(&format!("{}* env = arg;", env));
+            if ranged {
+                self.emit_line("_minilang_range range = env->range;");
+            }
+            for (c_type, name) in &fields {
+                self.emit_line(&format!("{} {} = env->{};", c_type, name, name));
+            }
+            self.emit_line("free(arg);");
+        } else {
+            self.emit_line("(void)arg;");
+        }
+        let result = body(self);
+        self.emit_line("return NULL;");
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        let lifted = std::mem::replace(&mut self.output, outer_output);
+        self.indent_level = outer_indent;
+        self.defer_scopes = outer_scopes;
+        self.loop_scope_starts = outer_loops;
+        self.in_main = outer_in_main;
+        result?;
+        self.spawned_functions.push_str(&lifted);
+        Ok(())
+    }
+    
+    /// The env and size arguments that hand `captures` to a thread function
+    fn thread_env(&self, function: &str, captures: &[(String, Type)], ranged: bool) -> String {
+        if !ranged && captures.is_empty() {
+            return "NULL, 0".to_string();
+        }
+        // The range is filled in for each thread
+        let values: Vec<String> = ranged.then(|| "{0, 0}".to_string()).into_iter()
+            .chain(captures.iter().map(|(name, _)| self.variable_identifier(name)))
+            .collect();
+        format!("&({0}_env){{{1}}}, sizeof({0}_env)", function, values.join(", "))
+    }
+    
     fn emit_const_statement(&mut self, stmt: &ConstStmt) -> Result<(), String> {
+        // Declared like a `let` array: C can't pass a `const` array to the
+        // non-const parameters of pure functions without a warning, and the
+        // type checker already rejects writes to its elements
+        if let Type::Array(..) = stmt.typ {
+            return self.emit_let_statement(&LetStmt {
+                name: stmt.name.clone(),
+                typ: Some(stmt.typ.clone()),
+                value: Some(stmt.value.clone()),
+                span: stmt.span.clone(),
+                id: stmt.id,
+            });
+        }
+
         let c_type = self.c_type(&Some(stmt.typ.clone()));
-        let name = self.c_identifier(&stmt.name);
         let value = self.expression_to_string(&stmt.value)?;
+        let name = self.declare_variable(&stmt.name);
         
-        self.emit_line(&format!("const {} {} = {};", c_type, name, value));
+        if self.is_counted(&stmt.typ) {
+            self.emit_line(&format!("const {} {} = _minilang_retain({});", c_type, name, value));
+            self.own_string(name.clone());
+        } else {
+            self.emit_line(&format!("const {} {} = {};", c_type, name, value));
+        }
         
         // Track the type
-        self.variable_types.insert(stmt.name.clone(), stmt.typ.clone());
+        self.variable_types.insert(name, stmt.typ.clone());
         
         Ok(())
     }
     
     /// Emit let statement
     fn emit_let_statement(&mut self, stmt: &LetStmt) -> Result<(), String> {
-        let decl = self.c_type_declaration(&stmt.typ, &self.c_identifier(&stmt.name));
+        let typ = self.let_type(stmt)?;
+        self.register_tuple_type(&typ);
+        let name = self.new_variable_name(&stmt.name);
+        let decl = self.c_type_declaration(&typ, &name);
 
-        // Cache array size and type
-        if let Type::Array(_elem_type, size) = &stmt.typ {
-            self.array_sizes.insert(stmt.name.clone(), *size);
-            self.variable_types.insert(stmt.name.clone(), stmt.typ.clone());
-        } else {
-            self.variable_types.insert(stmt.name.clone(), stmt.typ.clone());
-        }
+        // Array sizes travel with the type, for bounds checks and display
+        self.variable_types.insert(name.clone(), typ.clone());
         
         if let Some(value) = &stmt.value {
-            let value_code = self.expression_to_string(value)?;
+            let value_code = self.expression_to_string_as(value, &typ)?;
             
             // Special handling for array initialization
-            if let Type::Array(_elem_type, _size) = &stmt.typ {
+            if let Type::Array(elem_type, size) = &typ {
                 if let Expression::Literal(lit_expr) = value {
                     if let Literal::Array(elements) = &lit_expr.value {
-                        // Initialize array with literal values
-                        self.emit_line(&format!("{} = {{", decl));
-                        self.indent_level += 1;
+                        // C would silently zero-fill a short initializer
+                        if elements.len() != *size {
+                            return Err(format!(
+                                "Array '{}' is declared with {} elements but initialized with {}",
+                                stmt.name, size, elements.len()
+                            ));
+                        }
                         
-                        let elem_strs: Vec<String> = elements.iter()
-                            .map(|e| self.expression_to_string(e))
-                            .collect::<Result<Vec<_>, _>>()?;
+                        // Initialize array with literal values. An initializer
+                        // list has no order of its own, so the elements that
+                        // must come first are stored ahead of the declaration
+                        let elements: Vec<&Expression> = elements.iter().collect();
+                        let (assignments, mut elem_strs) = self.sequence_operands(&elements)?;
+                        if self.is_counted(elem_type) {
+                            elem_strs = elem_strs.into_iter().map(|elem| format!("_minilang_pin({})", elem)).collect();
+                        }
+                        for assignment in assignments {
+                            self.emit_line(&format!("{};", assignment));
+                        }
                         
+                        self.bind_variable(&stmt.name, &name);
+                        self.emit_line(&format!("{} = {{", decl));
+                        self.indent_level += 1;
                         self.emit_line(&elem_strs.join(", "));
                         
                         self.indent_level -= 1;
@@ -219,87 +2044,216 @@ impl CodeGenerator {
                 }
             }
             
-            self.emit_line(&format!("{} = {};", decl, value_code));
+            self.bind_variable(&stmt.name, &name);
+            if self.is_counted(&typ) {
+                self.emit_line(&format!("{} = _minilang_retain({});", decl, value_code));
+                self.own_string(name);
+            } else {
+                self.emit_line(&format!("{} = {};", decl, value_code));
+            }
         } else {
             // Default initialization
-            match &stmt.typ {
-                Type::Array(_, _size) => {
-                    self.emit_line(&format!("{} = {{}};", decl));
-                }
-                _ => {
-                    self.emit_line(&format!("{} = 0;", decl));
-                }
+            self.bind_variable(&stmt.name, &name);
+            let default = self.default_initializer(&typ);
+            self.emit_line(&format!("{} = {};", decl, default));
+            if self.is_counted(&typ) {
+                self.own_string(name);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// The declared type of a `let`, or for `let x = value;` the type of the value
+    fn let_type(&self, stmt: &LetStmt) -> Result<Type, String> {
+        stmt.typ.clone()
+            .or_else(|| stmt.value.as_ref().and_then(|value| self.expression_type(value)))
+            .ok_or_else(|| format!("Cannot determine the type of '{}'", stmt.name))
+    }
+    
+    /// Emit tuple destructuring through a temporary struct
+    fn emit_let_tuple_statement(&mut self, stmt: &LetTupleStmt) -> Result<(), String> {
+        let element_types = match self.expression_type(&stmt.value) {
+            Some(Type::Tuple(types)) => types,
+            _ => return Err(format!("Cannot determine the tuple type destructured into ({})", stmt.names.join(", "))),
+        };
+        let tuple_type = Some(Type::Tuple(element_types.clone()));
+        let value = self.expression_to_string(&stmt.value)?;
+        let temp = self.next_temp("tmp");
+        self.emit_line(&format!("{} {} = {};", self.c_type(&tuple_type), temp, value));
+        
+        for (i, (name, typ)) in stmt.names.iter().zip(element_types).enumerate() {
+            if name == "_" {
+                continue;
+            }
+            let c_name = self.declare_variable(name);
+            let decl = self.c_type_declaration(&typ, &c_name);
+            if let Type::Array(_, _) = &typ {
+                // C arrays can't be initialized from another array
+                self.emit_line(&format!("{};", decl));
+                self.emit_line(&format!("memcpy({}, {}._{}, sizeof {});", c_name, temp, i, c_name));
+            } else if self.is_counted(&typ) {
+                self.emit_line(&format!("{} = _minilang_retain({}._{});", decl, temp, i));
+                self.own_string(c_name.clone());
+            } else {
+                self.emit_line(&format!("{} = {}._{};", decl, temp, i));
             }
+            self.variable_types.insert(c_name, typ);
         }
         
         Ok(())
     }
     
     /// Emit display statement
+    /// Emit a display statement as few printf calls as possible. Literal text
+    /// goes into the format string and values become arguments; a part that
+    /// may print or fail on its own starts a new call so output stays in order.
     fn emit_display_statement(&mut self, stmt: &DisplayStmt) -> Result<(), String> {
+        let mut printf = DisplayPrintf::default();
         for expr in &stmt.expressions {
-            // Check if this is an interpolated string literal
-            if let Expression::Literal(lit_expr) = expr {
-                if let Literal::InterpolatedString(parts) = &lit_expr.value {
-                    // Handle interpolated string specially
+            match expr {
+                Expression::Literal(LiteralExpr { value: Literal::InterpolatedString(parts), .. }) => {
                     for part in parts {
                         match part {
-                            StringPart::Text(text) => {
-                                self.emit_line(&format!("printf(\"%s\", \"{}\");", 
-                                    self.escape_string(text)));
-                            }
-                            StringPart::Expression(expr) => {
-                                self.emit_display_expression(expr)?;
-                            }
+                            StringPart::Text(text) => printf.push_text(&self.escape_string(text)),
+                            StringPart::Expression(expr) => self.add_display_part(expr, &mut printf)?,
                         }
                     }
-                    continue; // Skip normal processing
                 }
+                Expression::Literal(LiteralExpr { value: Literal::String(text), .. }) => {
+                    printf.push_text(&self.escape_string(text));
+                }
+                _ => self.add_display_part(expr, &mut printf)?,
             }
-            
-            self.emit_display_expression(expr)?;
         }
-        Ok(())
-    }
-
-    fn emit_display_expression(&mut self, expr: &Expression) -> Result<(), String> {
+        if stmt.newline {
+            printf.push_text("\\n");
+        }
+        self.flush_display(&mut printf);
+        Ok(())
+    }
+
+    fn add_display_part(&mut self, expr: &Expression, printf: &mut DisplayPrintf) -> Result<(), String> {
+        if let Some(Type::Tuple(element_types)) = self.expression_type(expr) {
+            // Evaluate once into a temporary, then print it as (a, b)
+            self.flush_display(printf);
+            let value = self.expression_to_string(expr)?;
+            let temp = self.next_temp("tmp");
+            let tuple_type = Some(Type::Tuple(element_types.clone()));
+            self.emit_line("{");
+            self.indent_level += 1;
+            self.emit_line(&format!("{} {} = {};", self.c_type(&tuple_type), temp, value));
+            self.emit_tuple_display(&temp, &element_types);
+            self.indent_level -= 1;
+            self.emit_line("}");
+            return Ok(());
+        }
+        
+        if let Some(Type::Array(elem_type, size)) = self.expression_type(expr) {
+            // Print every element as [a, b, c]; named arrays are read in place
+            self.flush_display(printf);
+            let value = self.expression_to_string(expr)?;
+            if let Expression::Identifier(_) = expr {
+                self.emit_array_display(&value, &elem_type, size);
+                return Ok(());
+            }
+            let temp = self.next_temp("tmp");
+            let decl = match expr {
+                Expression::Literal(_) => self.c_type_declaration(&Type::Array(elem_type.clone(), size), &temp),
+                _ => format!("{} {}", self.c_type(&Some(Type::Array(elem_type.clone(), size))), temp),
+            };
+            self.emit_line("{");
+            self.indent_level += 1;
+            self.emit_line(&format!("{} = {};", decl, value));
+            self.emit_array_display(&temp, &elem_type, size);
+            self.indent_level -= 1;
+            self.emit_line("}");
+            return Ok(());
+        }
+        
+        // Calls may display, and indexing may stop on a bounds error, so
+        // everything before them has to be printed first
+        if Self::has_display_effects(expr) {
+            self.flush_display(printf);
+        }
+        
         let expr_str = self.expression_to_string(expr)?;
         if self.is_bool_expression(expr){
-            self.emit_line(&format!("printf(\"%s\", {} ? \"true\" : \"false\");", expr_str));
+            printf.push_value("%s", format!("{} ? \"true\" : \"false\"", expr_str));
         } else{
-            let format = self.get_printf_format(expr);
-            self.emit_line(&format!("printf(\"{}\", {});", format, expr_str));
+            let format = self.get_printf_format(expr).to_string();
+            printf.push_value(&format, expr_str);
         }
         Ok(())
     }
-
-    fn is_bool_expression(&self, expr: &Expression) -> bool {
-        match expr{
-            Expression::Literal(lit) => matches!(lit.value, Literal::Boolean(_)),
-            Expression::Identifier(id) => {
-                self.variable_types.get(&id.name)
-                    .map(|t| matches!(t, Type::Bool))
-                    .unwrap_or(false)
-            }
+    
+    fn flush_display(&mut self, printf: &mut DisplayPrintf) {
+        if let Some(line) = printf.take() {
+            self.emit_line(&line);
+        }
+    }
+    
+    fn has_display_effects(expr: &Expression) -> bool {
+        match expr {
+            Expression::Call(_) | Expression::Index(_) | Expression::Assign(_) => true,
             Expression::Binary(binary) => {
-                matches!(binary.op,
-                    BinaryOp::Equal | BinaryOp::NotEqual |
-                    BinaryOp::Less | BinaryOp::Greater |
-                    BinaryOp::LessEqual | BinaryOp::GreaterEqual |
-                    BinaryOp::And | BinaryOp::Or
-                )
+                Self::has_display_effects(&binary.left) || Self::has_display_effects(&binary.right)
             }
-            Expression::Unary(unary) => {
-                matches!(unary.op, UnaryOp::Not)
+            Expression::Unary(unary) => Self::has_display_effects(&unary.operand),
+            Expression::Literal(lit) => match &lit.value {
+                Literal::Array(elements) | Literal::Tuple(elements) => {
+                    elements.iter().any(Self::has_display_effects)
+                }
+                Literal::InterpolatedString(parts) => parts.iter().any(|part| {
+                    matches!(part, StringPart::Expression(expr) if Self::has_display_effects(expr))
+                }),
+                _ => false,
+            },
+            Expression::Identifier(_) => false,
+        }
+    }
+
+    fn emit_tuple_display(&mut self, value: &str, element_types: &[Type]) {
+        self.emit_line("printf(\"(\");");
+        for (i, typ) in element_types.iter().enumerate() {
+            if i > 0 {
+                self.emit_line("printf(\", \");");
             }
-            Expression::Call(call) => {
-                self.variable_types.get(&call.function)
-                    .map(|t| matches!(t, Type::Bool))
-                    .unwrap_or(false)
+            self.emit_value_display(&format!("{}._{}", value, i), typ);
+        }
+        self.emit_line("printf(\")\");");
+    }
+
+    fn emit_array_display(&mut self, value: &str, elem_type: &Type, size: usize) {
+        let index = self.next_temp("i");
+        self.emit_line("printf(\"[\");");
+        self.emit_line(&format!("for (int {0} = 0; {0} < {1}; {0}++) {{", index, size));
+        self.indent_level += 1;
+        self.emit_line(&format!("if ({} > 0) printf(\", \");", index));
+        self.emit_value_display(&format!("{}[{}]", value, index), elem_type);
+        self.indent_level -= 1;
+        self.emit_line("}");
+        self.emit_line("printf(\"]\");");
+    }
+
+    /// Print one tuple field or array element
+    fn emit_value_display(&mut self, value: &str, typ: &Type) {
+        match typ {
+            Type::Tuple(inner) => self.emit_tuple_display(value, inner),
+            Type::Array(elem_type, size) => self.emit_array_display(value, elem_type, *size),
+            Type::Bool => {
+                self.emit_line(&format!("printf(\"%s\", {} ? \"true\" : \"false\");", value));
+            }
+            _ => {
+                let format = self.type_printf_format(typ);
+                self.emit_line(&format!("printf(\"{}\", {});", format, value));
             }
-            _ => false,
         }
     }
+
+    fn is_bool_expression(&self, expr: &Expression) -> bool {
+        matches!(self.expression_type(expr), Some(Type::Bool))
+    }
     
     /// Emit if statement
     fn emit_if_statement(&mut self, stmt: &IfStmt) -> Result<(), String> {
@@ -327,7 +2281,8 @@ impl CodeGenerator {
         
         self.emit_line(&format!("while ({}) {{", condition));
         self.indent_level += 1;
-        self.emit_block(&stmt.body)?;
+        let drain = self.runtime == RuntimeMode::Rc && self.expression_allocates(&stmt.condition);
+        self.emit_loop_body(&stmt.body, drain)?;
         self.indent_level -= 1;
         self.emit_line("}");
         
@@ -338,7 +2293,8 @@ impl CodeGenerator {
     fn emit_do_while_statement(&mut self, stmt: &DoWhileStmt) -> Result<(), String> {
         self.emit_line("do {");
         self.indent_level += 1;
-        self.emit_block(&stmt.body)?;
+        let drain = self.runtime == RuntimeMode::Rc && self.expression_allocates(&stmt.condition);
+        self.emit_loop_body(&stmt.body, drain)?;
         self.indent_level -= 1;
         
         let condition = self.expression_to_string(&stmt.condition)?;
@@ -349,6 +2305,29 @@ impl CodeGenerator {
     
     /// Emit for statement
     fn emit_for_statement(&mut self, stmt: &ForStmt) -> Result<(), String> {
+        if stmt.parallel {
+            return self.emit_parallel_for(stmt);
+        }
+        if let Some(Statement::Let(let_stmt)) = stmt.init.as_deref() {
+            if self.is_counted(&self.let_type(let_stmt)?) {
+                // A counted variable is released after the loop, so it is
+                // declared in a block around it rather than in the header
+                self.emit_line("{");
+                self.indent_level += 1;
+                self.defer_scopes.push(Vec::new());
+                self.emit_let_statement(let_stmt)?;
+                self.emit_for_statement(&ForStmt { init: None, ..stmt.clone() })?;
+                let scope = self.defer_scopes.len() - 1;
+                self.emit_deferred_from(scope)?;
+                self.defer_scopes.pop();
+                self.indent_level -= 1;
+                self.emit_line("}");
+                return Ok(());
+            }
+        }
+        
+        // The header's variable is scoped to the loop
+        self.local_names.push(HashMap::new());
         self.emit("for (");
         
         // Init
@@ -356,14 +2335,15 @@ impl CodeGenerator {
             // Special handling for init - it's already a statement
             match &**init {
                 Statement::Let(let_stmt) => {
-                    let c_type = self.c_type(&Some(let_stmt.typ.clone()));
-                    let name = self.c_identifier(&let_stmt.name);
-                    if let Some(value) = &let_stmt.value {
-                        let value_code = self.expression_to_string(value)?;
-                        self.output.push_str(&format!("{} {} = {}", c_type, name, value_code));
-                    } else {
-                        self.output.push_str(&format!("{} {} = 0", c_type, name));
-                    }
+                    let typ = self.let_type(let_stmt)?;
+                    let c_type = self.c_type(&Some(typ.clone()));
+                    let value_code = match &let_stmt.value {
+                        Some(value) => self.expression_to_string(value)?,
+                        None => "0".to_string(),
+                    };
+                    let name = self.declare_variable(&let_stmt.name);
+                    self.variable_types.insert(name.clone(), typ);
+                    self.output.push_str(&format!("{} {} = {}", c_type, name, value_code));
                 }
                 Statement::Expression(expr_stmt) => {
                     let expr_str = self.expression_to_string(&expr_stmt.expression)?;
@@ -390,43 +2370,102 @@ impl CodeGenerator {
         self.output.push_str(") {\n");
         
         self.indent_level += 1;
-        self.emit_block(&stmt.body)?;
+        let drain = self.runtime == RuntimeMode::Rc && self.for_header_allocates(stmt);
+        self.emit_loop_body(&stmt.body, drain)?;
         self.indent_level -= 1;
         self.emit_line("}");
+        self.local_names.pop();
         
         Ok(())
     }
     
     /// Emit return statement
     fn emit_return_statement(&mut self, stmt: &ReturnStmt) -> Result<(), String> {
+        if self.has_deferred_from(0) {
+            // Evaluate the return value before deferred code can change it
+            if let Some(value) = &stmt.value {
+                let value_str = self.return_value_to_string(value)?;
+                let temp = self.next_temp("ret");
+                let c_type = self.c_type(&self.current_return_type);
+                // A returned string outlives the variables released here
+                let counted = self.current_return_type.as_ref().is_some_and(|typ| self.is_counted(typ));
+                if counted {
+                    self.emit_line(&format!("{} {} = _minilang_retain({});", c_type, temp, value_str));
+                } else {
+                    self.emit_line(&format!("{} {} = {};", c_type, temp, value_str));
+                }
+                self.emit_deferred_from(0)?;
+                if counted {
+                    self.emit_line(&format!("_minilang_release({});", temp));
+                }
+                self.emit_line(&format!("return {};", temp));
+            } else {
+                self.emit_deferred_from(0)?;
+                self.emit_line(self.bare_return());
+            }
+            return Ok(());
+        }
+        
         if let Some(value) = &stmt.value {
-            let value_str = self.expression_to_string(value)?;
+            let value_str = self.return_value_to_string(value)?;
             self.emit_line(&format!("return {};", value_str));
         } else {
-            self.emit_line("return;");
+            self.emit_line(self.bare_return());
         }
         Ok(())
     }
     
+    fn bare_return(&self) -> &'static str {
+        if self.in_main { "return 0;" } else { "return;" }
+    }
+    
+    fn return_value_to_string(&mut self, value: &Expression) -> Result<String, String> {
+        match self.current_return_type.clone() {
+            Some(return_type) => self.expression_to_string_as(value, &return_type),
+            None => self.expression_to_string(value),
+        }
+    }
+    
     /// Emit expression statement
     fn emit_expression_statement(&mut self, stmt: &ExprStmt) -> Result<(), String> {
         // Skip standalone identifiers (they're leftovers from array assignment placeholders)
         if let Expression::Identifier(_) = &stmt.expression {
             return Ok(());
         }
+        if let Expression::Call(call) = &stmt.expression {
+            if self.is_builtin(call, "assert") {
+                return self.emit_assert(call);
+            }
+        }
         
         let expr_str = self.expression_to_string(&stmt.expression)?;
         self.emit_line(&format!("{};", expr_str));
         Ok(())
     }
     
+    /// Emit assert(condition[, message]), which stops the program with the
+    /// message, or the condition as written, when the condition is false
+    fn emit_assert(&mut self, call: &CallExpr) -> Result<(), String> {
+        let condition = self.expression_to_string(&call.args[0])?;
+        let message = match call.args.get(1) {
+            Some(message) => self.expression_to_string(message)?,
+            None => {
+                let text = call.args[0].source_text(self.source.as_ref().map(|source| source.text.as_str()));
+                format!("\"{}\"", self.escape_string(&text))
+            }
+        };
+        self.uses_asserts = true;
+        self.emit_line(&format!("ASSERT({}, {});", condition, message));
+        Ok(())
+    }
+    
     /// Convert expression to C code string
     fn expression_to_string(&mut self, expr: &Expression) -> Result<String, String> {
         match expr {
             Expression::Literal(lit_expr) => self.literal_to_string(&lit_expr.value),
             
             Expression::Identifier(id_expr) => {
-                Ok(self.c_identifier(&id_expr.name))
+                Ok(self.variable_identifier(&id_expr.name))
             }
             
             Expression::Binary(binary) => {
@@ -436,18 +2475,25 @@ impl CodeGenerator {
                         OptimizationHint::ShiftLeft(n)=>{
                             Ok(format!("({} << {})", left , n))
                         }
+                        // A bare shift/mask floors negative dividends; the helpers
+                        // keep MiniLang's truncating / and % semantics
                         OptimizationHint::ShiftRight(n)=>{
-                            Ok(format!("({} >> {})", left , n))
+                            Ok(format!("_minilang_div_pow2({}, {})", left , n))
                         }
                         OptimizationHint::BitwiseAnd(mask)=>{
-                            Ok(format!("({} & {})", left , mask))
+                            Ok(format!("_minilang_mod_pow2({}, {})", left , mask))
                         }
                     }
-                } else{
+                } else if matches!(binary.op, BinaryOp::And | BinaryOp::Or) {
+                    // && and || already evaluate their left side first
                     let left = self.expression_to_string(&binary.left)?;
                     let right = self.expression_to_string(&binary.right)?;
                     let op = self.binary_op_to_string(&binary.op);
                     Ok(format!("({} {} {})", left, op, right))
+                } else {
+                    let (assignments, operands) = self.sequence_operands(&[&binary.left, &binary.right])?;
+                    let op = self.binary_op_to_string(&binary.op);
+                    Ok(Self::sequenced(&assignments, format!("({} {} {})", operands[0], op, operands[1])))
                 }
             }
             
@@ -460,29 +2506,121 @@ impl CodeGenerator {
                 Ok(format!("({}{})", op, operand))
             }
             
+            Expression::Call(call) if call.function == STACK_STRING => self.stack_string_to_string(&call.args[0]),
+            
+            Expression::Call(call) if self.is_builtin(call, "format") => self.format_call_to_string(call, None),
+            
+            Expression::Call(call) if self.is_builtin(call, "approx_equal") => {
+                let args: Vec<&Expression> = call.args.iter().collect();
+                let (assignments, args) = self.sequence_operands(&args)?;
+                Ok(Self::sequenced(&assignments, format!("_minilang_approx_equal({})", args.join(", "))))
+            }
+            
+            Expression::Call(call) if self.is_builtin(call, "to_string") => {
+                let value = self.expression_to_string(&call.args[0])?;
+                Ok(format!("_minilang_int_to_string({})", value))
+            }
+            
+            Expression::Call(call) if self.is_builtin(call, "parse_int") => {
+                let text = self.expression_to_string(&call.args[0])?;
+                Ok(format!("PARSE_INT({})", text))
+            }
+            
+            Expression::Call(call) if CANVAS_BUILTINS.iter().any(|name| self.is_builtin(call, name)) => {
+                self.uses_canvas = true;
+                let helper = match call.function.as_str() {
+                    "canvas" => "CANVAS_NEW",
+                    "canvas_set" => "CANVAS_SET",
+                    "canvas_fill" => "CANVAS_FILL",
+                    _ => "_minilang_canvas_render",
+                };
+                let args: Vec<&Expression> = call.args.iter().collect();
+                let (assignments, args) = self.sequence_operands(&args)?;
+                Ok(Self::sequenced(&assignments, format!("{}({})", helper, args.join(", "))))
+            }
+            
+            Expression::Call(call) if JSON_BUILTINS.iter().any(|name| self.is_builtin(call, name)) => {
+                self.uses_json = true;
+                let args: Vec<&Expression> = call.args.iter().collect();
+                let (assignments, mut args) = self.sequence_operands(&args)?;
+                // json_set and json_push store a json value of their own
+                if let ("json_set" | "json_push", Some(value)) = (call.function.as_str(), call.args.last()) {
+                    let helper = match self.expression_type(value) {
+                        Some(Type::Int) => "_minilang_json_from_int",
+                        Some(Type::Float) => "_minilang_json_from_float",
+                        Some(Type::Bool) => "_minilang_json_from_bool",
+                        Some(Type::String) => "_minilang_json_from_string",
+                        _ => "_minilang_json_copy",
+                    };
+                    if let Some(last) = args.last_mut() {
+                        *last = format!("{}({})", helper, last);
+                    }
+                }
+                let helper = call.function.to_uppercase();
+                Ok(Self::sequenced(&assignments, format!("{}({})", helper, args.join(", "))))
+            }
+            
+            Expression::Call(call) if CLOCK_BUILTINS.iter().any(|name| self.is_builtin(call, name)) => {
+                self.uses_clock = true;
+                let args: Vec<&Expression> = call.args.iter().collect();
+                let (assignments, args) = self.sequence_operands(&args)?;
+                Ok(Self::sequenced(&assignments, format!("_minilang_{}({})", call.function, args.join(", "))))
+            }
+            
+            Expression::Call(call) if ATOMIC_BUILTINS.iter().any(|name| self.is_builtin(call, name)) => {
+                self.uses_atomics = true;
+                let helper = match call.function.as_str() {
+                    "atomic" => "_minilang_atomic_new",
+                    "atomic_add" => "_minilang_atomic_add",
+                    _ => "_minilang_atomic_get",
+                };
+                let args: Vec<&Expression> = call.args.iter().collect();
+                let (assignments, args) = self.sequence_operands(&args)?;
+                Ok(Self::sequenced(&assignments, format!("{}({})", helper, args.join(", "))))
+            }
+            
+            Expression::Call(call) if self.is_channel_builtin(call) => {
+                self.uses_threads = true;
+                let helper = match call.function.as_str() {
+                    "channel" => "CHANNEL_NEW",
+                    "chan_send" => "_minilang_channel_send",
+                    _ => "_minilang_channel_receive",
+                };
+                let args: Vec<&Expression> = call.args.iter().collect();
+                let (assignments, args) = self.sequence_operands(&args)?;
+                Ok(Self::sequenced(&assignments, format!("{}({})", helper, args.join(", "))))
+            }
+            
+            Expression::Call(call) if self.is_checked_arithmetic(call) => {
+                let helper = self.checked_arithmetic_helper(&call.function);
+                let args: Vec<&Expression> = call.args.iter().collect();
+                let (assignments, args) = self.sequence_operands(&args)?;
+                Ok(Self::sequenced(&assignments, format!("{}({})", helper, args.join(", "))))
+            }
+            
             Expression::Call(call) => {
-                let args: Vec<String> = call.args.iter()
-                    .map(|arg| self.expression_to_string(arg))
-                    .collect::<Result<Vec<_>, _>>()?;
-                
-                Ok(format!("{}({})", self.c_identifier(&call.function), args.join(", ")))
+                let args: Vec<&Expression> = call.args.iter().collect();
+                let (assignments, args) = self.sequence_operands(&args)?;
+                let call = format!("{}({})", self.c_identifier(&call.function), args.join(", "));
+                Ok(Self::sequenced(&assignments, call))
             }
             
             Expression::Index(index) => {
                 let array = self.expression_to_string(&index.array)?;
                 let idx = self.expression_to_string(&index.index)?;
+                
+                if self.safe_indexes.contains(&index.id) {
+                    self.bounds_checks_elided += 1;
+                    return Ok(format!("{}[{}]", array, idx));
+                }
 
-                // Get actual array size
-                let array_size = if let Expression::Identifier(id) = &*index.array {
-                    self.array_sizes.get(&id.name).copied().unwrap_or(10)
-                } else {
-                    10  // fallback
+                // Get actual array size, which parameters only carry in their type
+                let array_size = match self.expression_type(&index.array) {
+                    Some(Type::Array(_, size)) => size,
+                    _ => 10,  // fallback
                 };
                 
-                Ok(format!("({}, {})", 
-                    format!("CHECK_BOUNDS({}, {})", idx, array_size), 
-                    format!("{}[{}]", array, idx)
-                ))
+                Ok(format!("(CHECK_BOUNDS({}, {}), {}[{}])", idx, array_size, array, idx))
             }
             
             Expression::Assign(assign) => {
@@ -493,39 +2631,317 @@ impl CodeGenerator {
                         if let Expression::Index(index_expr) = &*binary.left {
                             // Generate: arr[index] = value
                             let array = self.expression_to_string(&index_expr.array)?;
-                            let index = self.expression_to_string(&index_expr.index)?;
-                            let value = self.expression_to_string(&binary.right)?;
+                            let (assignments, operands) = self.sequence_operands(&[&index_expr.index, &binary.right])?;
                             
                             // Return without bounds checking wrapper in the assignment target
-                            return Ok(format!("({}[{}] = {})", array, index, value));
+                            let pinned = self.expression_type(&binary.left).is_some_and(|typ| self.is_counted(&typ));
+                            let assignment = if pinned {
+                                format!("({}[{}] = _minilang_pin({}))", array, operands[0], operands[1])
+                            } else {
+                                format!("({}[{}] = {})", array, operands[0], operands[1])
+                            };
+                            return Ok(Self::sequenced(&assignments, assignment));
                         }
                     }
                 }
                 
                 // Regular assignment
                 let value = self.expression_to_string(&assign.value)?;
-                Ok(format!("({} = {})", self.c_identifier(&assign.target), value))
+                let counted = self.variable_type(&assign.target).is_some_and(|typ| self.is_counted(&typ));
+                let target = self.variable_identifier(&assign.target);
+                if counted {
+                    return Ok(format!("_minilang_assign(&{}, {})", target, value));
+                }
+                Ok(format!("({} = {})", target, value))
+            }
+        }
+    }
+    
+    /// The C for each operand, evaluated left to right as MiniLang requires,
+    /// along with the assignments to run first. C leaves the order of call
+    /// arguments and most binary operands unspecified, so when one operand
+    /// has side effects another could observe, the operands up to it are
+    /// stored in temporaries first.
+    fn sequence_operands(&mut self, operands: &[&Expression]) -> Result<(Vec<String>, Vec<String>), String> {
+        let in_temps = self.operands_in_temps(operands);
+        let mut assignments = Vec::new();
+        let mut values = Vec::new();
+        for (operand, in_temp) in operands.iter().zip(in_temps) {
+            let value = self.expression_to_string(operand)?;
+            values.push(if in_temp { self.store_in_temp(operand, value, &mut assignments) } else { value });
+        }
+        Ok((assignments, values))
+    }
+    
+    /// Which operands go in a temporary: those that call or index, and so
+    /// may see or cause an effect, up to the last one with side effects.
+    /// Literals and plain variables can't be changed by a call.
+    fn operands_in_temps(&self, operands: &[&Expression]) -> Vec<bool> {
+        let observable: Vec<bool> = operands.iter().map(|operand| Self::has_display_effects(operand)).collect();
+        let last_effect = operands.iter().rposition(|operand| self.has_side_effects(operand));
+        (0..operands.len())
+            .map(|i| match last_effect {
+                // The last one with effects can stay in place unless something after it looks
+                Some(last) => observable[i] && (i < last || (i == last && observable[i + 1..].contains(&true))),
+                None => false,
+            })
+            .collect()
+    }
+    
+    /// Assign `value` to a new temporary declared ahead of the statement and
+    /// return the temporary. Arrays can't be copied, so they stay in place;
+    /// C only passes their address anyway.
+    fn store_in_temp(&mut self, operand: &Expression, value: String, assignments: &mut Vec<String>) -> String {
+        match self.expression_type(operand) {
+            Some(typ) if !matches!(typ, Type::Array(..)) => {
+                self.register_tuple_type(&typ);
+                let temp = self.next_temp("arg");
+                self.pending_temps.push(format!("{};", self.c_type_declaration(&typ, &temp)));
+                assignments.push(format!("{} = {}", temp, value));
+                temp
+            }
+            _ => value,
+        }
+    }
+    
+    /// `value` after `assignments`, in order, as one comma expression
+    fn sequenced(assignments: &[String], value: String) -> String {
+        if assignments.is_empty() {
+            value
+        } else {
+            format!("({}, {})", assignments.join(", "), value)
+        }
+    }
+    
+    /// Whether evaluating `expr` can print or write memory someone else can
+    /// read: an assignment, or a call to a function that does either
+    fn has_side_effects(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Assign(_) => true,
+            Expression::Call(call) => {
+                self.effectful_functions.contains(&call.function)
+                    || call.args.iter().any(|arg| self.has_side_effects(arg))
+            }
+            Expression::Binary(binary) => self.has_side_effects(&binary.left) || self.has_side_effects(&binary.right),
+            Expression::Unary(unary) => self.has_side_effects(&unary.operand),
+            Expression::Index(index) => self.has_side_effects(&index.array) || self.has_side_effects(&index.index),
+            Expression::Literal(lit) => match &lit.value {
+                Literal::Array(elements) | Literal::Tuple(elements) => {
+                    elements.iter().any(|element| self.has_side_effects(element))
+                }
+                Literal::InterpolatedString(parts) => parts.iter().any(|part| {
+                    matches!(part, StringPart::Expression(expr) if self.has_side_effects(expr))
+                }),
+                _ => false,
+            },
+            Expression::Identifier(_) => false,
+        }
+    }
+    
+    /// Convert an expression to C, using the declared type for tuple literals
+    /// rather than whatever element types can be inferred locally
+    fn expression_to_string_as(&mut self, expr: &Expression, typ: &Type) -> Result<String, String> {
+        if let (Expression::Literal(LiteralExpr { value: Literal::Tuple(elements), .. }), Type::Tuple(_)) = (expr, typ) {
+            return self.tuple_literal_to_string(elements, typ);
+        }
+        self.expression_to_string(expr)
+    }
+    
+    /// Emit a tuple literal as a C99 compound literal of its struct type
+    fn tuple_literal_to_string(&mut self, elements: &[Expression], typ: &Type) -> Result<String, String> {
+        self.register_tuple_type(typ);
+        let element_types = match typ {
+            Type::Tuple(types) => types.clone(),
+            _ => Vec::new(),
+        };
+        
+        let operands: Vec<&Expression> = elements.iter().collect();
+        let in_temps = self.operands_in_temps(&operands);
+        let mut assignments = Vec::new();
+        let mut elem_strs = Vec::new();
+        for (i, element) in elements.iter().enumerate() {
+            let mut value = match element_types.get(i) {
+                Some(element_type) => self.expression_to_string_as(element, element_type)?,
+                None => self.expression_to_string(element)?,
+            };
+            if element_types.get(i).is_some_and(|typ| self.is_counted(typ)) {
+                value = format!("_minilang_pin({})", value);
+            }
+            elem_strs.push(if in_temps[i] { self.store_in_temp(element, value, &mut assignments) } else { value });
+        }
+        let tuple = format!("(({}){{{}}})", self.c_type(&Some(typ.clone())), elem_strs.join(", "));
+        Ok(Self::sequenced(&assignments, tuple))
+    }
+    
+    /// Build a string the optimizer found never leaves its function in a
+    /// stack buffer, declared ahead of the statement
+    fn stack_string_to_string(&mut self, expr: &Expression) -> Result<String, String> {
+        let buffer = self.next_temp("buf");
+        let value = match expr {
+            Expression::Literal(LiteralExpr { value: Literal::InterpolatedString(parts), .. }) => {
+                self.interpolation_to_string(parts, Some(&buffer))?
+            }
+            Expression::Call(call) if self.is_builtin(call, "format") => self.format_call_to_string(call, Some(&buffer))?,
+            Expression::Call(call) if self.is_builtin(call, "to_string") => {
+                let value = self.expression_to_string(&call.args[0])?;
+                format!("_minilang_format_into(&{}, \"%d\", {})", buffer, value)
+            }
+            // Folded to a literal since, or a function of another module
+            _ => return self.expression_to_string(expr),
+        };
+        self.pending_temps.push(format!("_minilang_stack_string {};", buffer));
+        self.uses_stack_strings = true;
+        Ok(value)
+    }
+    
+    /// Lower interpolated parts to a single _minilang_format (snprintf) call,
+    /// or _minilang_format_into when the result goes in the stack buffer `into`
+    fn interpolation_to_string(&mut self, parts: &[StringPart], into: Option<&str>) -> Result<String, String> {
+        let mut format = String::new();
+        let mut args = String::new();
+        
+        let expressions: Vec<&Expression> = parts.iter()
+            .filter_map(|part| match part {
+                StringPart::Expression(expr) => Some(expr),
+                StringPart::Text(_) => None,
+            })
+            .collect();
+        let (assignments, values) = self.sequence_operands(&expressions)?;
+        let mut values = values.into_iter();
+        
+        for part in parts {
+            match part {
+                StringPart::Text(text) => {
+                    format.push_str(&self.escape_string(text).replace('%', "%%"));
+                }
+                StringPart::Expression(expr) => {
+                    let value = values.next().unwrap_or_default();
+                    if self.is_bool_expression(expr) {
+                        format.push_str("%s");
+                        args.push_str(&format!(", {} ? \"true\" : \"false\"", value));
+                    } else {
+                        format.push_str(self.get_printf_format(expr));
+                        args.push_str(&format!(", {}", value));
+                    }
+                }
+            }
+        }
+        
+        let call = match into {
+            Some(buffer) => format!("_minilang_format_into(&{}, \"{}\"{})", buffer, format, args),
+            None => format!("_minilang_format(\"{}\"{})", format, args),
+        };
+        Ok(Self::sequenced(&assignments, call))
+    }
+    
+    /// Builtins like `format` yield to a program function with the same name
+    fn is_builtin(&self, call: &CallExpr, name: &str) -> bool {
+        call.function == name && !self.function_return_types.contains_key(name)
+    }
+    
+    /// A call of `channel`, `chan_send` or `chan_receive`
+    fn is_channel_builtin(&self, call: &CallExpr) -> bool {
+        CHANNEL_BUILTINS.iter().any(|name| self.is_builtin(call, name))
+    }
+    
+    /// A call of `add_checked` or another builtin in `CHECKED_ARITHMETIC`
+    fn is_checked_arithmetic(&self, call: &CallExpr) -> bool {
+        CHECKED_ARITHMETIC.iter().any(|(name, _)| self.is_builtin(call, name))
+    }
+    
+    /// Name of the C function behind a checked arithmetic builtin, emitted
+    /// after the (int, bool) struct it returns the first time it is called.
+    /// `add_checked` becomes a call of `__builtin_add_overflow`, which stores
+    /// the wrapped result and is not affected by -ftrapv.
+    fn checked_arithmetic_helper(&mut self, builtin: &str) -> String {
+        let result = vec![Type::Int, Type::Bool];
+        self.register_tuple_type(&Type::Tuple(result.clone()));
+        let helper = format!("_minilang_{}", builtin);
+        if self.checked_helpers.insert(helper.clone()) {
+            let op = builtin.trim_end_matches("_checked");
+            let tuple = self.tuple_struct_name(&result);
+            self.tuple_typedefs.push_str(&format!(
+                "static inline {0} {1}(int a, int b) {{\n    {0} result;\n    result._1 = __builtin_{2}_overflow(a, b, &result._0);\n    return result;\n}}\n\n",
+                tuple, helper, op
+            ));
+        }
+        helper
+    }
+    
+    /// Substitute format(...) arguments into the template's `{}` placeholders
+    fn format_call_to_string(&mut self, call: &CallExpr, into: Option<&str>) -> Result<String, String> {
+        let template_parts = match call.args.first() {
+            Some(Expression::Literal(LiteralExpr { value: Literal::String(text), .. })) => {
+                vec![StringPart::Text(text.clone())]
+            }
+            Some(Expression::Literal(LiteralExpr { value: Literal::InterpolatedString(parts), .. })) => {
+                parts.clone()
+            }
+            _ => return Err("format() expects a string literal template".to_string()),
+        };
+        
+        let mut args = call.args[1..].iter();
+        let mut parts = Vec::new();
+        for part in template_parts {
+            let StringPart::Text(text) = part else {
+                parts.push(part);
+                continue;
+            };
+            
+            let mut pieces = text.split("{}");
+            if let Some(first) = pieces.next().filter(|piece| !piece.is_empty()) {
+                parts.push(StringPart::Text(first.to_string()));
+            }
+            for piece in pieces {
+                let arg = args.next()
+                    .ok_or_else(|| "format() has more placeholders than arguments".to_string())?;
+                parts.push(StringPart::Expression(arg.clone()));
+                if !piece.is_empty() {
+                    parts.push(StringPart::Text(piece.to_string()));
+                }
             }
         }
+        
+        self.interpolation_to_string(&parts, into)
     }
     
     /// Convert literal to C string
     fn literal_to_string(&mut self, lit: &Literal) -> Result<String, String> {
         match lit {
+            // -2147483648 would be the negation of a long
+            Literal::Integer(i32::MIN) => Ok("(-2147483647 - 1)".to_string()),
             Literal::Integer(n) => Ok(n.to_string()),
-            Literal::Float(f) => Ok(format!("{:.6}", f)),
+            // Shortest round-trip form, so folded values keep their exact bits
+            Literal::Float(f) => Ok(format!("{:?}", f)),
+            Literal::String(s) if self.runtime == RuntimeMode::Rc => {
+                let index = match self.string_literals.iter().position(|text| text == s) {
+                    Some(index) => index,
+                    None => {
+                        self.string_literals.push(s.clone());
+                        self.string_literals.len() - 1
+                    }
+                };
+                Ok(format!("_minilang_str_{}.text", index))
+            }
             Literal::String(s) => Ok(format!("\"{}\"", self.escape_string(s))),
             Literal::Boolean(b) => Ok(if *b { "true".to_string() } else { "false".to_string() }),
-            Literal::InterpolatedString(_parts) => {
-                // This shouldn't be called directly; handled in emit_display_statement
-                Err("Interpolated strings should be handled in display statement".to_string())
+            // display prints the parts directly; anywhere else the string is built on the heap
+            Literal::InterpolatedString(parts) => self.interpolation_to_string(parts, None),
+            Literal::Tuple(elements) => {
+                let element_types = elements.iter()
+                    .map(|e| self.expression_type(e).unwrap_or(Type::Int))
+                    .collect();
+                self.tuple_literal_to_string(elements, &Type::Tuple(element_types))
             }
             Literal::Array(elements) => {
                 // This shouldn't be called for array literals in declarations
                 // but we'll handle it anyway
-                let elem_strs: Vec<String> = elements.iter()
+                let mut elem_strs: Vec<String> = elements.iter()
                     .map(|e| self.expression_to_string(e))
                     .collect::<Result<Vec<_>, _>>()?;
+                if elements.first().and_then(|e| self.expression_type(e)).is_some_and(|typ| self.is_counted(&typ)) {
+                    elem_strs = elem_strs.into_iter().map(|elem| format!("_minilang_pin({})", elem)).collect();
+                }
                 Ok(format!("{{{}}}", elem_strs.join(", ")))
             }
         }
@@ -561,6 +2977,11 @@ impl CodeGenerator {
                 // For function parameters, arrays become pointers
                 format!("{}*", self.c_type(&Some(*elem_type.clone())))
             }
+            Some(Type::Tuple(elements)) => self.tuple_struct_name(elements),
+            Some(Type::Channel) => "_minilang_channel".to_string(),
+            Some(Type::Atomic) => "_minilang_atomic".to_string(),
+            Some(Type::Canvas) => "_minilang_canvas".to_string(),
+            Some(Type::Json) => "_minilang_json".to_string(),
             None => "void".to_string(),
         }
     }
@@ -577,20 +2998,195 @@ impl CodeGenerator {
         }
     }
     
-    /// Convert identifier to valid C identifier
-    fn c_identifier(&self, name: &str) -> String {
-        // Prefix with underscore if it's a C keyword
-        let c_keywords = ["auto", "break", "case", "char", "const", "continue", 
-                          "default", "do", "double", "else", "enum", "extern",
-                          "float", "for", "goto", "if", "int", "long", 
-                          "register", "return", "short", "signed", "sizeof", 
-                          "static", "struct", "switch", "typedef", "union",
-                          "unsigned", "void", "volatile", "while"];
-        
-        if c_keywords.contains(&name) {
-            format!("_{}", name)
+    /// Zero value for an uninitialized variable, valid in C99
+    fn default_initializer(&mut self, typ: &Type) -> String {
+        match typ {
+            Type::Int => "0".to_string(),
+            Type::Float => "0.0".to_string(),
+            // The empty string, as in the VM, so reading it never sees NULL
+            Type::String => self.literal_to_string(&Literal::String(String::new())).unwrap_or_default(),
+            Type::Bool => "false".to_string(),
+            // Every string element is spelled out, since C zeroes the rest to NULL
+            Type::Array(elem_type, size) if Self::holds_string(elem_type) => {
+                let element = self.default_initializer(elem_type);
+                format!("{{{}}}", vec![element; *size].join(", "))
+            }
+            // Remaining elements are zeroed by C's aggregate initialization rules
+            Type::Array(elem_type, _) => format!("{{{}}}", self.default_initializer(elem_type)),
+            Type::Tuple(elements) if elements.iter().any(Self::holds_string) => {
+                let fields: Vec<String> = elements.iter().map(|t| self.default_initializer(t)).collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+            Type::Tuple(_) => "{0}".to_string(),
+            Type::Channel | Type::Atomic | Type::Canvas | Type::Json => "NULL".to_string(),
+        }
+    }
+    
+    /// Whether a value of `typ` contains a string, which zeroing would leave NULL
+    fn holds_string(typ: &Type) -> bool {
+        match typ {
+            Type::String => true,
+            Type::Array(elem_type, _) => Self::holds_string(elem_type),
+            Type::Tuple(elements) => elements.iter().any(Self::holds_string),
+            _ => false,
+        }
+    }
+    
+    /// Name of the C struct backing a tuple type, e.g. _minilang_tuple_int_bool
+    fn tuple_struct_name(&self, elements: &[Type]) -> String {
+        let tags: Vec<String> = elements.iter().map(|t| self.type_tag(t)).collect();
+        format!("_minilang_tuple_{}", tags.join("_"))
+    }
+    
+    /// Identifier-safe spelling of a type, used to name tuple structs
+    fn type_tag(&self, typ: &Type) -> String {
+        match typ {
+            Type::Int => "int".to_string(),
+            Type::Float => "float".to_string(),
+            Type::String => "string".to_string(),
+            Type::Bool => "bool".to_string(),
+            Type::Array(elem_type, size) => format!("arr{}_{}", size, self.type_tag(elem_type)),
+            Type::Tuple(elements) => {
+                let tags: Vec<String> = elements.iter().map(|t| self.type_tag(t)).collect();
+                format!("tup_{}_end", tags.join("_"))
+            }
+            Type::Channel => "chan".to_string(),
+            Type::Atomic => "atomic".to_string(),
+            Type::Canvas => "canvas".to_string(),
+            Type::Json => "json".to_string(),
+        }
+    }
+    
+    /// Emit the struct typedef for a tuple type (and any tuples nested in it) on first use
+    fn register_tuple_type(&mut self, typ: &Type) {
+        match typ {
+            Type::Array(elem_type, _) => self.register_tuple_type(elem_type),
+            Type::Tuple(elements) => {
+                for element in elements {
+                    self.register_tuple_type(element);
+                }
+                
+                let name = self.tuple_struct_name(elements);
+                if self.declared_tuples.insert(name.clone()) {
+                    let mut typedef = String::from("typedef struct {\n");
+                    for (i, element) in elements.iter().enumerate() {
+                        typedef.push_str(&format!("    {};\n", self.c_type_declaration(element, &format!("_{}", i))));
+                    }
+                    typedef.push_str(&format!("}} {};\n\n", name));
+                    self.tuple_typedefs.push_str(&typedef);
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    /// Best-effort static type of an expression from what codegen has seen so far
+    fn expression_type(&self, expr: &Expression) -> Option<Type> {
+        match expr {
+            Expression::Literal(lit_expr) => match &lit_expr.value {
+                Literal::Integer(_) => Some(Type::Int),
+                Literal::Float(_) => Some(Type::Float),
+                Literal::String(_) | Literal::InterpolatedString(_) => Some(Type::String),
+                Literal::Boolean(_) => Some(Type::Bool),
+                Literal::Array(elements) => {
+                    let elem_type = elements.first()
+                        .and_then(|e| self.expression_type(e))
+                        .unwrap_or(Type::Int);
+                    Some(Type::Array(Box::new(elem_type), elements.len()))
+                }
+                Literal::Tuple(elements) => elements.iter()
+                    .map(|e| self.expression_type(e))
+                    .collect::<Option<Vec<_>>>()
+                    .map(Type::Tuple),
+            },
+            Expression::Identifier(id) => self.variable_type(&id.name),
+            Expression::Call(call) if call.function == STACK_STRING => Some(Type::String),
+            Expression::Call(call) if self.is_builtin(call, "format") => Some(Type::String),
+            Expression::Call(call) if self.is_builtin(call, "approx_equal") => Some(Type::Bool),
+            Expression::Call(call) if self.is_builtin(call, "to_string") => Some(Type::String),
+            Expression::Call(call) if self.is_builtin(call, "parse_int") => Some(Type::Int),
+            Expression::Call(call) if self.is_builtin(call, "now_ms") => Some(Type::Int),
+            Expression::Call(call) if self.is_checked_arithmetic(call) => Some(Type::Tuple(vec![Type::Int, Type::Bool])),
+            Expression::Call(call) if self.is_builtin(call, "channel") => Some(Type::Channel),
+            Expression::Call(call) if self.is_builtin(call, "atomic") => Some(Type::Atomic),
+            Expression::Call(call) if self.is_builtin(call, "canvas") => Some(Type::Canvas),
+            Expression::Call(call) if JSON_BUILTINS.iter().any(|name| self.is_builtin(call, name)) => json_builtin_type(&call.function),
+            Expression::Call(call) if self.is_builtin(call, "atomic_add") || self.is_builtin(call, "atomic_get") => Some(Type::Int),
+            Expression::Call(call) if self.is_builtin(call, "chan_receive") => Some(Type::Int),
+            Expression::Call(call) => self.function_return_types.get(&call.function).cloned().flatten(),
+            Expression::Binary(binary) => match binary.op {
+                BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply |
+                BinaryOp::Divide | BinaryOp::Modulo => self.expression_type(&binary.left),
+                _ => Some(Type::Bool),
+            },
+            Expression::Unary(unary) => match unary.op {
+                UnaryOp::Not => Some(Type::Bool),
+                UnaryOp::Negate => self.expression_type(&unary.operand),
+            },
+            Expression::Index(index) => match self.expression_type(&index.array) {
+                Some(Type::Array(elem_type, _)) => Some(*elem_type),
+                _ => None,
+            },
+            Expression::Assign(assign) => {
+                if assign.target.starts_with("__ARRAY_INDEX__:") {
+                    None
+                } else {
+                    self.variable_type(&assign.target)
+                }
+            }
+        }
+    }
+    
+    fn next_temp(&mut self, kind: &str) -> String {
+        let name = format!("_minilang_{}_{}", kind, self.temp_counter);
+        self.temp_counter += 1;
+        name
+    }
+    
+    /// The C name of the variable `name` in scope here
+    fn variable_identifier(&self, name: &str) -> String {
+        self.local_names.iter().rev()
+            .find_map(|scope| scope.get(name).cloned())
+            .unwrap_or_else(|| self.c_identifier(name))
+    }
+    
+    /// The type of the variable `name` in scope here
+    fn variable_type(&self, name: &str) -> Option<Type> {
+        self.variable_types.get(&self.variable_identifier(name)).cloned()
+    }
+    
+    /// The C name for a new variable `name`: its mangled name, or a fresh
+    /// one when that would shadow a variable still in scope
+    fn new_variable_name(&mut self, name: &str) -> String {
+        if self.local_names.iter().any(|scope| scope.contains_key(name)) {
+            format!("{}_{}", self.next_temp("shadow"), name)
         } else {
+            self.c_identifier(name)
+        }
+    }
+    
+    /// Bring `name` into scope as `c_name` for the rest of the current block
+    fn bind_variable(&mut self, name: &str, c_name: &str) {
+        if let Some(scope) = self.local_names.last_mut() {
+            scope.insert(name.to_string(), c_name.to_string());
+        }
+    }
+    
+    /// Declare the variable `name` in the current block, returning its C name
+    fn declare_variable(&mut self, name: &str) -> String {
+        let c_name = self.new_variable_name(name);
+        self.bind_variable(name, &c_name);
+        c_name
+    }
+    
+    /// Mangle a MiniLang identifier into C. Every name except `main` gets
+    /// the `ml_` prefix, so user symbols can't collide with C keywords, libc
+    /// (`printf`, `exit`, ...) or the `_minilang_` runtime.
+    fn c_identifier(&self, name: &str) -> String {
+        if name == "main" {
             name.to_string()
+        } else {
+            format!("{}{}", USER_SYMBOL_PREFIX, name)
         }
     }
     
@@ -604,69 +3200,26 @@ impl CodeGenerator {
     }
     
     /// Get printf format for expression type
-    fn get_printf_format(&self, expr: &Expression) -> &str {
-        match expr {
-            Expression::Literal(lit_expr) => match &lit_expr.value {
-                Literal::Integer(_) => "%d",
-                Literal::Float(_) => "%.6f",
-                Literal::String(_) => "%s",
-                Literal::Boolean(_) => "%d",  // 1 or 0
-                _ => "%d",
-            },
-            Expression::Identifier(id) => {
-                // Look up the variable's type
-                if let Some(typ) = self.variable_types.get(&id.name) {
-                    match typ {
-                        Type::Int => "%d",
-                        Type::Float => "%.6f",
-                        Type::String => "%s",
-                        Type::Bool => "%d",
-                        Type::Array(_, _) => "%p", // pointer for array
-                    }
-                } else {
-                    "%d"  // fallback
-                }
-            },
-            Expression::Binary(binary) => {
-                // Check if it's a comparison/logical operator
-                match binary.op {
-                    BinaryOp::Equal | BinaryOp::NotEqual | 
-                    BinaryOp::Less | BinaryOp::Greater |
-                    BinaryOp::LessEqual | BinaryOp::GreaterEqual |
-                    BinaryOp::And | BinaryOp::Or => "%d",  // boolean result
-                    _ => {
-                        // For arithmetic, check if any operand is float
-                        if self.is_float_expr(&binary.left) || self.is_float_expr(&binary.right) {
-                            "%.6f"
-                        } else {
-                            "%d"
-                        }
-                    }
-                }
-            },
-            Expression::Unary(unary) => {
-                match unary.op {
-                    UnaryOp::Not => "%d",  // boolean
-                    UnaryOp::Negate => self.get_printf_format(&unary.operand),
-                }
-            },
-            _ => "%d",
+    fn get_printf_format(&self, expr: &Expression) -> &'static str {
+        match self.expression_type(expr) {
+            Some(typ) => self.type_printf_format(&typ),
+            None => "%d",  // fallback
         }
     }
 
-    fn is_float_expr(&self, expr: &Expression) -> bool {
-        match expr {
-            Expression::Literal(lit) => matches!(lit.value, Literal::Float(_)),
-            Expression::Identifier(id) => {
-                self.variable_types.get(&id.name)
-                    .map(|t| matches!(t, Type::Float))
-                    .unwrap_or(false)
-            },
-            _ => false,
+    /// printf format for a value of a known type
+    fn type_printf_format(&self, typ: &Type) -> &'static str {
+        match typ {
+            Type::Int => "%d",
+            Type::Float => "%.6f",
+            Type::String => "%s",
+            Type::Bool => "%d",
+            // Arrays and tuples are printed element by element instead, and
+            // channels not at all
+            Type::Array(_, _) | Type::Tuple(_) | Type::Channel | Type::Atomic | Type::Canvas | Type::Json => "%p",
         }
     }
-    
-    
+
     /// Emit a line with proper indentation
     fn emit_line(&mut self, line: &str) {
         self.emit(line);
@@ -680,4 +3233,20 @@ impl CodeGenerator {
         }
         self.output.push_str(text);
     }
-}
\ No newline at end of file
+}
+
+/// The variable, start and end of a loop counting up by one from `start`
+/// while below `end`, the form `parallel for` is parsed into
+fn counted_range(stmt: &ForStmt) -> Option<(&str, &Expression, &Expression)> {
+    let Some(Statement::Let(LetStmt { name, value: Some(from), .. })) = stmt.init.as_deref() else { return None };
+    let Some(Expression::Binary(BinaryExpr { left, op: BinaryOp::Less, right: to, .. })) = &stmt.condition else { return None };
+    let Some(Expression::Assign(AssignExpr { target, value: step, .. })) = &stmt.update else { return None };
+    let is_variable = |expr: &Expression| matches!(expr, Expression::Identifier(id) if id.name == *name);
+    let steps_by_one = match &**step {
+        Expression::Binary(BinaryExpr { left, op: BinaryOp::Add, right, .. }) => {
+            is_variable(left) && matches!(&**right, Expression::Literal(LiteralExpr { value: Literal::Integer(1), .. }))
+        }
+        _ => false,
+    };
+    (is_variable(left) && target == name && steps_by_one).then_some((name.as_str(), from, &**to))
+}
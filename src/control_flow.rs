@@ -0,0 +1,132 @@
+// src/control_flow.rs - Loops of every kind, and where control can go
+//
+// while, do-while and for loops differ only in what runs around the body and
+// when the condition is first checked. The type checker (missing `send`,
+// unreachable code), the optimizer (dead code) and the analyzer (nesting and
+// complexity) all look at loops through `Loop` and ask `completes` whether
+// control can get past a statement, so they agree on what each loop does:
+// a do-while body always runs once, and a loop whose condition is `true` or
+// missing only ends through `break`.
+
+use crate::ast::*;
+
+/// The parts of a while, do-while or for loop
+#[derive(Debug, Clone, Copy)]
+pub struct Loop<'a> {
+    /// Runs once before everything else (for loops)
+    pub init: Option<&'a Statement>,
+    /// Missing only in a for loop without one, which never ends by itself
+    pub condition: Option<&'a Expression>,
+    /// Runs after the body and before the condition (for loops)
+    pub update: Option<&'a Expression>,
+    pub body: &'a Block,
+    /// The body runs before the condition is first checked (do-while)
+    pub body_first: bool,
+}
+
+impl<'a> Loop<'a> {
+    /// The loop `stmt` is, if it is one
+    pub fn of(stmt: &'a Statement) -> Option<Self> {
+        match stmt {
+            Statement::While(while_stmt) => Some(Loop {
+                init: None,
+                condition: Some(&while_stmt.condition),
+                update: None,
+                body: &while_stmt.body,
+                body_first: false,
+            }),
+            Statement::DoWhile(do_while) => Some(Loop {
+                init: None,
+                condition: Some(&do_while.condition),
+                update: None,
+                body: &do_while.body,
+                body_first: true,
+            }),
+            Statement::For(for_stmt) => Some(Loop {
+                init: for_stmt.init.as_deref(),
+                condition: for_stmt.condition.as_ref(),
+                update: for_stmt.update.as_ref(),
+                body: &for_stmt.body,
+                body_first: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The condition's value when it is a literal; a missing one is always true
+    pub fn constant_condition(&self) -> Option<bool> {
+        match self.condition {
+            None => Some(true),
+            Some(Expression::Literal(LiteralExpr { value: Literal::Boolean(value), .. })) => Some(*value),
+            Some(_) => None,
+        }
+    }
+
+    /// Whether the body can run at all
+    pub fn body_may_run(&self) -> bool {
+        self.body_first || self.constant_condition() != Some(false)
+    }
+
+    /// Whether control can continue after the loop: through a `break`, or
+    /// when the condition is checked and can be false
+    pub fn completes(&self) -> bool {
+        if breaks_out(self.body) {
+            return true;
+        }
+        if self.constant_condition() == Some(true) {
+            return false;
+        }
+        // A do-while only checks its condition once the body gets to the end
+        !self.body_first || block_completes(self.body) || continues(self.body)
+    }
+}
+
+/// Whether control can reach the statement after `stmt`. `send`, `break`
+/// and `continue` go elsewhere, and so does anything all of whose paths do.
+pub fn completes(stmt: &Statement) -> bool {
+    if let Some(lp) = Loop::of(stmt) {
+        return lp.completes();
+    }
+    match stmt {
+        Statement::Return(_) | Statement::Break(_) | Statement::Continue(_) => false,
+        Statement::If(if_stmt) => match &if_stmt.else_block {
+            Some(else_block) => block_completes(&if_stmt.then_block) || block_completes(else_block),
+            None => true,
+        },
+        Statement::Block(block) => block_completes(block),
+        _ => true,
+    }
+}
+
+/// Whether control can fall off the end of `block`
+pub fn block_completes(block: &Block) -> bool {
+    block.statements.iter().all(completes)
+}
+
+/// Whether `block` has a `break` for the loop it is the body of
+fn breaks_out(block: &Block) -> bool {
+    jumps_out(block, &|stmt| matches!(stmt, Statement::Break(_)))
+}
+
+/// Whether `block` has a `continue` for the loop it is the body of
+fn continues(block: &Block) -> bool {
+    jumps_out(block, &|stmt| matches!(stmt, Statement::Continue(_)))
+}
+
+/// Whether a statement matching `is_jump` appears in `block` outside any
+/// nested loop, whose own break and continue stay inside it
+fn jumps_out(block: &Block, is_jump: &impl Fn(&Statement) -> bool) -> bool {
+    block.statements.iter().any(|stmt| {
+        if is_jump(stmt) {
+            return true;
+        }
+        match stmt {
+            Statement::If(if_stmt) => {
+                jumps_out(&if_stmt.then_block, is_jump)
+                    || if_stmt.else_block.as_ref().is_some_and(|else_block| jumps_out(else_block, is_jump))
+            }
+            Statement::Block(inner) => jumps_out(inner, is_jump),
+            _ => false,
+        }
+    })
+}
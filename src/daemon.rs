@@ -0,0 +1,472 @@
+// src/daemon.rs - A compiler that stays running between commands
+//
+// `minilang daemon` listens on a Unix socket, by default
+// .minilang/daemon.sock in the directory it was started in, and answers
+// check and compile requests, one JSON object per line each way. It keeps
+// the typed AST of every file it has checked and reuses it while the file,
+// the defines, the edition and the way diagnostics are drawn stay the same,
+// so watch loops, editors and graders that run the compiler thousands of
+// times only lex, parse and type check what changed. `minilang check` hands
+// its files to a daemon listening in the current directory and checks them
+// itself when there is none.
+
+use crate::ast::{Literal, Program};
+use crate::codegen::CodeGenerator;
+use crate::edition::Version;
+use crate::errors::SemanticError;
+use crate::lexer::Lexer;
+use crate::optimizer::{Optimizer, OverflowMode};
+use crate::parser::{Defines, Parser};
+use crate::runtime::RuntimeMode;
+use crate::stamp::sha256_hex;
+use crate::style::{self, Style};
+use crate::type_checker::TypeChecker;
+use miette::{Diagnostic, MietteHandler, MietteHandlerOpts, NamedSource, Report, ReportHandler};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Where a daemon listens, relative to the directory it serves
+pub const SOCKET: &str = ".minilang/daemon.sock";
+
+/// Names the socket `minilang check` uses instead of the default, or turns
+/// the daemon off for it with `off`
+pub const SOCKET_ENV: &str = "MINILANG_DAEMON";
+
+/// What the front end needs besides the source, and how the terminal that
+/// will show the diagnostics draws them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckOptions {
+    /// `-D` constants, sorted by name
+    pub defines: Vec<(String, Literal)>,
+    pub edition: Version,
+    pub color: bool,
+    pub unicode: bool,
+}
+
+impl CheckOptions {
+    pub fn new(defines: &Defines, edition: Version, style: Style) -> Self {
+        let mut defines: Vec<_> = defines.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+        defines.sort_by(|a, b| a.0.cmp(&b.0));
+        CheckOptions { defines, edition, color: style.color, unicode: style.unicode }
+    }
+
+    fn handler(&self) -> MietteHandler {
+        MietteHandlerOpts::new().color(self.color).unicode(self.unicode).build()
+    }
+
+    fn icon(&self, glyph: &'static str) -> &'static str {
+        if self.unicode { glyph } else { style::ascii_fallback(glyph) }
+    }
+}
+
+/// The options of `minilang compile` that change the generated C
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct CompileOptions {
+    pub optimization: u8,
+    pub overflow: OverflowMode,
+    pub fast_math: bool,
+    pub runtime: RuntimeMode,
+    pub always_bounds_check: bool,
+}
+
+/// What checking one file found
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckOutcome {
+    /// `ok`, or where it stopped: `unreadable`, `lexer error`, `parse error`
+    /// or `type error`
+    pub status: String,
+    pub errors: usize,
+    pub warnings: usize,
+    /// The errors as miette draws them, empty when there are none
+    pub diagnostics: String,
+    /// The warnings, drawn the same way
+    pub warning_diagnostics: String,
+}
+
+impl CheckOutcome {
+    fn failed(status: &str, diagnostics: String) -> Self {
+        CheckOutcome {
+            status: status.to_string(),
+            errors: 1,
+            warnings: 0,
+            diagnostics,
+            warning_diagnostics: String::new(),
+        }
+    }
+}
+
+/// A request, as a client writes it on one line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum Request {
+    /// Check `file`, naming it `name` in diagnostics
+    Check { file: PathBuf, name: String, options: CheckOptions },
+    /// Check `file` and, when it passes, give the C it compiles to
+    Compile { file: PathBuf, name: String, options: CheckOptions, compile: CompileOptions },
+    Status,
+    Stop,
+}
+
+/// The daemon's answer to a request, on one line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum Response {
+    Checked { outcome: CheckOutcome },
+    /// `c_code` is missing when the file did not compile, and `outcome`
+    /// says why
+    Compiled { outcome: CheckOutcome, c_code: Option<String> },
+    Status { files: usize, hits: u64, misses: u64 },
+    Stopping,
+    /// The request line could not be read
+    Error { message: String },
+}
+
+/// One checked file as the cache keeps it
+pub struct Entry {
+    /// Hash of everything the outcome depends on
+    key: String,
+    source: String,
+    pub outcome: CheckOutcome,
+    /// The typed program, when it passed
+    pub program: Option<Program>,
+}
+
+/// Checked files by path, shared by every connection to a daemon
+#[derive(Default)]
+pub struct Cache {
+    entries: Mutex<HashMap<PathBuf, Arc<Entry>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Cache {
+    /// The checked file, from memory when nothing it depends on changed.
+    /// The front end runs without the lock held, so connections checking
+    /// different files do not wait for each other.
+    pub fn load(&self, file: &Path, name: &str, options: &CheckOptions) -> Arc<Entry> {
+        let source = match read_source(file, options) {
+            Ok(source) => source,
+            Err(outcome) => {
+                self.entries.lock().unwrap().remove(file);
+                return Arc::new(Entry { key: String::new(), source: String::new(), outcome, program: None });
+            }
+        };
+        let settings = serde_json::to_string(&(name, options)).expect("options serialize");
+        let key = sha256_hex(format!("{}\n{}", settings, source).as_bytes());
+
+        if let Some(entry) = self.entries.lock().unwrap().get(file).filter(|entry| entry.key == key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Arc::clone(entry);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let (outcome, program) = check_source(name, &source, options);
+        let entry = Arc::new(Entry { key, source, outcome, program });
+        self.entries.lock().unwrap().insert(file.to_path_buf(), Arc::clone(&entry));
+        entry
+    }
+
+    /// Check `file` and generate C from its cached typed program
+    pub fn compile(&self, file: &Path, name: &str, options: &CheckOptions, compile: &CompileOptions) -> (CheckOutcome, Option<String>) {
+        let entry = self.load(file, name, options);
+        let Some(program) = &entry.program else {
+            return (entry.outcome.clone(), None);
+        };
+        let mut program = program.clone();
+        if compile.optimization > 0 {
+            let mut optimizer = Optimizer::new(compile.optimization)
+                .with_fast_math(compile.fast_math)
+                .with_overflow(compile.overflow);
+            optimizer.optimize(&mut program);
+            if !optimizer.errors().is_empty() {
+                let outcome = CheckOutcome {
+                    status: "optimize error".to_string(),
+                    errors: optimizer.errors().len(),
+                    diagnostics: render_errors(optimizer.errors().to_vec(), name, &entry.source, options),
+                    ..entry.outcome.clone()
+                };
+                return (outcome, None);
+            }
+        }
+        let mut codegen = CodeGenerator::new()
+            .with_source(name, &entry.source)
+            .with_peephole(compile.optimization >= 1)
+            .with_line_tracking(compile.optimization == 0)
+            .with_always_bounds_check(compile.always_bounds_check)
+            .with_runtime(compile.runtime);
+        match codegen.generate(&program) {
+            Ok(c_code) => (entry.outcome.clone(), Some(c_code)),
+            Err(e) => {
+                let message = format!("{} Code generation failed: {}", options.icon("❌"), e);
+                (CheckOutcome { warnings: entry.outcome.warnings, ..CheckOutcome::failed("codegen error", message) }, None)
+            }
+        }
+    }
+
+    /// The answer to one request
+    pub fn respond(&self, request: &Request) -> Response {
+        match request {
+            Request::Check { file, name, options } => Response::Checked {
+                outcome: self.load(file, name, options).outcome.clone(),
+            },
+            Request::Compile { file, name, options, compile } => {
+                let (outcome, c_code) = self.compile(file, name, options, compile);
+                Response::Compiled { outcome, c_code }
+            }
+            Request::Status => Response::Status {
+                files: self.entries.lock().unwrap().len(),
+                hits: self.hits.load(Ordering::Relaxed),
+                misses: self.misses.load(Ordering::Relaxed),
+            },
+            Request::Stop => Response::Stopping,
+        }
+    }
+}
+
+/// Check `file` once, without a daemon
+pub fn check_file(file: &Path, name: &str, options: &CheckOptions) -> CheckOutcome {
+    match read_source(file, options) {
+        Ok(source) => check_source(name, &source, options).0,
+        Err(outcome) => outcome,
+    }
+}
+
+fn read_source(file: &Path, options: &CheckOptions) -> Result<String, CheckOutcome> {
+    fs::read_to_string(file).map_err(|e| {
+        CheckOutcome::failed("unreadable", format!("{} Error reading file: {}", options.icon("❌"), e))
+    })
+}
+
+/// Run the front end on `source`, drawing diagnostics instead of printing
+/// them. The program comes back only when it passed.
+pub fn check_source(name: &str, source: &str, options: &CheckOptions) -> (CheckOutcome, Option<Program>) {
+    let handler = options.handler();
+    let named_source = NamedSource::new(name, source.to_string());
+    let failed = |status, report: Report| {
+        CheckOutcome::failed(status, render(&handler, report.with_source_code(named_source.clone())))
+    };
+
+    let mut lexer = Lexer::new(source);
+    let tokens = match lexer.tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => return (failed("lexer error", Report::from(e)), None),
+    };
+
+    let mut parser = Parser::new(tokens, source.to_string())
+        .with_defines(options.defines.iter().cloned().collect())
+        .with_edition(options.edition);
+    let program = match parser.parse_program() {
+        Ok(program) => program,
+        Err(e) => return (failed("parse error", Report::from(e)), None),
+    };
+
+    let mut type_checker = TypeChecker::new().with_comments(&lexer.comments());
+    let checked = type_checker.check_program(&program);
+    let warnings = type_checker.get_warnings();
+    let warning_diagnostics = warnings
+        .iter()
+        .map(|warning| render(&handler, warning.report(source, name)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    match checked {
+        Ok(()) => {
+            let outcome = CheckOutcome {
+                status: "ok".to_string(),
+                errors: 0,
+                warnings: warnings.len(),
+                diagnostics: String::new(),
+                warning_diagnostics,
+            };
+            (outcome, Some(program))
+        }
+        Err(errors) => {
+            let outcome = CheckOutcome {
+                status: "type error".to_string(),
+                errors: errors.len(),
+                warnings: warnings.len(),
+                diagnostics: render_errors(errors, name, source, options),
+                warning_diagnostics,
+            };
+            (outcome, None)
+        }
+    }
+}
+
+fn render_errors(errors: Vec<SemanticError>, name: &str, source: &str, options: &CheckOptions) -> String {
+    let handler = options.handler();
+    let named_source = NamedSource::new(name, source.to_string());
+    errors
+        .into_iter()
+        .map(|error| render(&handler, Report::from(error).with_source_code(named_source.clone())))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A report as `handler` draws it, rather than the process-wide miette hook,
+/// which in a daemon knows nothing of the client's terminal
+fn render(handler: &MietteHandler, report: Report) -> String {
+    struct Drawn<'a>(&'a MietteHandler, &'a dyn Diagnostic);
+
+    impl fmt::Debug for Drawn<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.debug(self.1, f)
+        }
+    }
+
+    format!("{:?}", Drawn(handler, report.as_ref()))
+}
+
+/// The socket `minilang check` run in `dir` talks to: $MINILANG_DAEMON when
+/// set, else the default one, and none when MINILANG_DAEMON is `off`
+pub fn client_socket(dir: &Path) -> Option<PathBuf> {
+    match std::env::var_os(SOCKET_ENV) {
+        Some(value) if value == "off" => None,
+        Some(value) if !value.is_empty() => Some(PathBuf::from(value)),
+        _ => Some(dir.join(SOCKET)),
+    }
+}
+
+/// The path to send for `file`, which the daemon may resolve from a
+/// different directory
+pub fn request_path(file: &Path) -> PathBuf {
+    fs::canonicalize(file).unwrap_or_else(|_| std::env::current_dir().unwrap_or_default().join(file))
+}
+
+#[cfg(unix)]
+pub use unix::{serve, Client};
+
+#[cfg(unix)]
+mod unix {
+    use super::{request_path, Cache, CheckOptions, CheckOutcome, Request, Response};
+    use std::fs;
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Listen on `socket` until a stop request, answering each connection on
+    /// its own thread. `ready` is called once the socket accepts connections.
+    pub fn serve(socket: &Path, ready: impl FnOnce()) -> io::Result<()> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("a daemon is already listening on {}", socket.display()),
+                ));
+            }
+            // Left behind by a daemon that did not get to clean up
+            fs::remove_file(socket)?;
+        }
+        if let Some(dir) = socket.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let listener = UnixListener::bind(socket)?;
+        ready();
+
+        let cache = Arc::new(Cache::default());
+        let (stop, stopped) = std::sync::mpsc::channel();
+        for stream in listener.incoming() {
+            if stopped.try_recv().is_ok() {
+                break;
+            }
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let cache = Arc::clone(&cache);
+            let stop = stop.clone();
+            let socket = socket.to_path_buf();
+            thread::spawn(move || {
+                if let Ok(true) = answer(stream, &cache) {
+                    let _ = stop.send(());
+                    // Wake the accept loop so it sees the request
+                    let _ = UnixStream::connect(&socket);
+                }
+            });
+        }
+        fs::remove_file(socket)
+    }
+
+    /// Answer requests until the client hangs up, telling whether one of
+    /// them was a stop request
+    fn answer(stream: UnixStream, cache: &Cache) -> io::Result<bool> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let response = match serde_json::from_str::<Request>(&line?) {
+                Ok(request) => cache.respond(&request),
+                Err(e) => Response::Error { message: format!("bad request: {}", e) },
+            };
+            writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+            writer.flush()?;
+            if response == Response::Stopping {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// A connection to a running daemon
+    pub struct Client {
+        reader: BufReader<UnixStream>,
+        writer: UnixStream,
+    }
+
+    impl Client {
+        /// Connect to the daemon on `socket`, or `None` when none is listening
+        pub fn connect(socket: &Path) -> Option<Client> {
+            let writer = UnixStream::connect(socket).ok()?;
+            let reader = BufReader::new(writer.try_clone().ok()?);
+            Some(Client { reader, writer })
+        }
+
+        pub fn request(&mut self, request: &Request) -> io::Result<Response> {
+            writeln!(self.writer, "{}", serde_json::to_string(request)?)?;
+            self.writer.flush()?;
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the daemon closed the connection"));
+            }
+            Ok(serde_json::from_str(&line)?)
+        }
+
+        /// Have the daemon check `file`, or `None` when it did not answer
+        pub fn check(&mut self, file: &Path, name: &str, options: &CheckOptions) -> Option<CheckOutcome> {
+            let request = Request::Check { file: request_path(file), name: name.to_string(), options: options.clone() };
+            match self.request(&request) {
+                Ok(Response::Checked { outcome }) => Some(outcome),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// The daemon needs Unix domain sockets
+#[cfg(not(unix))]
+pub fn serve(_socket: &Path, _ready: impl FnOnce()) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "the daemon needs Unix domain sockets"))
+}
+
+/// Without Unix domain sockets there is never a daemon to connect to
+#[cfg(not(unix))]
+pub struct Client {
+    never: std::convert::Infallible,
+}
+
+#[cfg(not(unix))]
+impl Client {
+    pub fn connect(_socket: &Path) -> Option<Client> {
+        None
+    }
+
+    pub fn request(&mut self, _request: &Request) -> std::io::Result<Response> {
+        match self.never {}
+    }
+
+    pub fn check(&mut self, _file: &Path, _name: &str, _options: &CheckOptions) -> Option<CheckOutcome> {
+        match self.never {}
+    }
+}
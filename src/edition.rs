@@ -0,0 +1,135 @@
+// src/edition.rs - Language versions and the syntax each one added
+//
+// A file may start with `minilang 1.1;` to say which version of the language
+// it was written for. `--edition` picks the version for files without such a
+// header, and files with neither get the latest. Syntax newer than a file's
+// version is a parse error naming the version it needs, so course material
+// written against an older version keeps being checked against it as the
+// language grows.
+
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A version of the language, written `MAJOR.MINOR`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl Version {
+    /// Functions, variables, arrays, `if`/`while`/`do`/`for` and `display`
+    pub const V1_0: Version = Version { major: 1, minor: 0 };
+    /// Adds pure functions, `defer` and tuples
+    pub const V1_1: Version = Version { major: 1, minor: 1 };
+    /// Adds function attributes, `@if` blocks, visibility and `let` without a type
+    pub const V1_2: Version = Version { major: 1, minor: 2 };
+    /// Adds `test` blocks, `spawn` blocks, channels, `parallel for`, atomics,
+    /// canvases and json values
+    pub const V1_3: Version = Version { major: 1, minor: 3 };
+
+    /// Every version this compiler understands, oldest first
+    pub const ALL: [Version; 4] = [Self::V1_0, Self::V1_1, Self::V1_2, Self::V1_3];
+
+    /// What files without a header are checked against by default
+    pub const LATEST: Version = Self::V1_3;
+
+    /// Whether code written for `self` may use `feature`
+    pub fn supports(self, feature: Feature) -> bool {
+        self >= feature.since()
+    }
+
+    /// The supported versions as a readable list, e.g. "1.0, 1.1 or 1.2"
+    pub fn supported_list() -> String {
+        let names: Vec<String> = Self::ALL.iter().map(|version| version.to_string()).collect();
+        match names.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+            None => String::new(),
+        }
+    }
+}
+
+impl Default for Version {
+    fn default() -> Self {
+        Self::LATEST
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    /// Parse `MAJOR.MINOR`, accepting only versions this compiler knows
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parsed = text.trim().split_once('.').and_then(|(major, minor)| {
+            let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+            if !digits(major) || !digits(minor) {
+                return None;
+            }
+            Some(Version { major: major.parse().ok()?, minor: minor.parse().ok()? })
+        });
+        match parsed {
+            Some(version) if Self::ALL.contains(&version) => Ok(version),
+            Some(version) => Err(format!(
+                "MiniLang {} is not supported; this compiler knows {}",
+                version,
+                Self::supported_list()
+            )),
+            None => Err(format!("'{}' is not a version; write it as MAJOR.MINOR, e.g. {}", text.trim(), Self::LATEST)),
+        }
+    }
+}
+
+/// Syntax that only some versions accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    PureFunctions,
+    Defer,
+    Tuples,
+    Attributes,
+    ConditionalBlocks,
+    Visibility,
+    InferredTypes,
+    TestBlocks,
+    Spawn,
+    ParallelFor,
+    Canvas,
+    Json,
+}
+
+impl Feature {
+    /// The first version with this feature
+    pub fn since(self) -> Version {
+        match self {
+            Feature::PureFunctions | Feature::Defer | Feature::Tuples => Version::V1_1,
+            Feature::Attributes | Feature::ConditionalBlocks | Feature::Visibility | Feature::InferredTypes => Version::V1_2,
+            Feature::TestBlocks | Feature::Spawn | Feature::ParallelFor | Feature::Canvas
+            | Feature::Json => Version::V1_3,
+        }
+    }
+
+    /// How error messages name the feature
+    pub fn description(self) -> &'static str {
+        match self {
+            Feature::PureFunctions => "pure functions",
+            Feature::Defer => "defer blocks",
+            Feature::Tuples => "tuples",
+            Feature::Attributes => "function attributes",
+            Feature::ConditionalBlocks => "@if blocks",
+            Feature::Visibility => "public and private functions",
+            Feature::InferredTypes => "let without a type",
+            Feature::TestBlocks => "test blocks",
+            Feature::Spawn => "spawn blocks and channels",
+            Feature::ParallelFor => "parallel for loops and atomics",
+            Feature::Canvas => "canvases",
+            Feature::Json => "json values",
+        }
+    }
+}
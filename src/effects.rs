@@ -0,0 +1,165 @@
+// src/effects.rs - Which functions have side effects
+//
+// MiniLang evaluates call arguments and binary operands left to right, while
+// C leaves their order unspecified. The code generator only has to force the
+// order when one operand can change what another sees or prints, so it needs
+// to know which calls can do that: the ones that display or write into an
+// array the caller passed, directly or through another call.
+
+use crate::ast::*;
+use std::collections::{HashMap, HashSet};
+
+/// Names of the functions whose calls can display, write caller-visible
+/// memory or send and receive on channels, with the channel, clock and
+/// canvas builtins and the json ones that change a value. `externals` are
+/// other modules' functions, whose bodies are not compiled here; only the
+/// ones declared `pure` are taken to have no effects.
+pub fn effectful_functions(program: &Program, externals: &[Function]) -> HashSet<String> {
+    let mut effectful: HashSet<String> = externals.iter()
+        .filter(|function| !function.is_pure)
+        .map(|function| function.name.clone())
+        .collect();
+    let defined: HashSet<&str> = program.functions.iter().chain(externals).map(|function| function.name.as_str()).collect();
+    // What one receive gets depends on which sends and receives came first,
+    // what now_ms gives on whether a sleep came first, and what a canvas or
+    // json value holds on what was set before
+    effectful.extend(CHANNEL_BUILTINS.iter().chain(&CLOCK_BUILTINS).chain(&CANVAS_BUILTINS).chain(&["json_set", "json_push"])
+        .filter(|name| !defined.contains(*name))
+        .map(|name| name.to_string()));
+
+    let mut calls = HashMap::new();
+    for function in program.functions.iter().filter(|function| !function.is_pure) {
+        let mut scan = Scan {
+            params: function.params.iter().map(|param| param.name.as_str()).collect(),
+            direct: false,
+            calls: HashSet::new(),
+        };
+        scan.block(&function.body);
+        if scan.direct {
+            effectful.insert(function.name.clone());
+        }
+        calls.insert(function.name.as_str(), scan.calls);
+    }
+
+    // Calling a function with effects has them too, which may take several rounds
+    loop {
+        let before = effectful.len();
+        for (name, callees) in &calls {
+            if callees.iter().any(|callee| effectful.contains(callee)) {
+                effectful.insert(name.to_string());
+            }
+        }
+        if effectful.len() == before {
+            return effectful;
+        }
+    }
+}
+
+/// What one function body does by itself, and whom it calls
+struct Scan<'a> {
+    params: HashSet<&'a str>,
+    direct: bool,
+    calls: HashSet<String>,
+}
+
+impl Scan<'_> {
+    fn block(&mut self, block: &Block) {
+        for statement in &block.statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Const(stmt) => self.expression(&stmt.value),
+            Statement::Let(stmt) => {
+                if let Some(value) = &stmt.value {
+                    self.expression(value);
+                }
+            }
+            Statement::LetTuple(stmt) => self.expression(&stmt.value),
+            Statement::Display(stmt) => {
+                self.direct = true;
+                stmt.expressions.iter().for_each(|expr| self.expression(expr));
+            }
+            Statement::If(stmt) => {
+                self.expression(&stmt.condition);
+                self.block(&stmt.then_block);
+                if let Some(else_block) = &stmt.else_block {
+                    self.block(else_block);
+                }
+            }
+            Statement::While(stmt) => {
+                self.expression(&stmt.condition);
+                self.block(&stmt.body);
+            }
+            Statement::DoWhile(stmt) => {
+                self.block(&stmt.body);
+                self.expression(&stmt.condition);
+            }
+            Statement::For(stmt) => {
+                if let Some(init) = &stmt.init {
+                    self.statement(init);
+                }
+                if let Some(condition) = &stmt.condition {
+                    self.expression(condition);
+                }
+                if let Some(update) = &stmt.update {
+                    self.expression(update);
+                }
+                self.block(&stmt.body);
+            }
+            Statement::Return(stmt) => {
+                if let Some(value) = &stmt.value {
+                    self.expression(value);
+                }
+            }
+            Statement::Expression(stmt) => self.expression(&stmt.expression),
+            Statement::Block(block) => self.block(block),
+            Statement::Defer(stmt) => self.block(&stmt.body),
+            Statement::Spawn(stmt) => self.block(&stmt.body),
+            Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Literal(lit) => match &lit.value {
+                Literal::Array(elements) | Literal::Tuple(elements) => {
+                    elements.iter().for_each(|element| self.expression(element));
+                }
+                Literal::InterpolatedString(parts) => {
+                    for part in parts {
+                        if let StringPart::Expression(expr) = part {
+                            self.expression(expr);
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Expression::Identifier(_) => {}
+            Expression::Binary(binary) => {
+                self.expression(&binary.left);
+                self.expression(&binary.right);
+            }
+            Expression::Unary(unary) => self.expression(&unary.operand),
+            Expression::Call(call) => {
+                self.calls.insert(call.function.clone());
+                call.args.iter().for_each(|arg| self.expression(arg));
+            }
+            Expression::Index(index) => {
+                self.expression(&index.array);
+                self.expression(&index.index);
+            }
+            Expression::Assign(assign) => {
+                // Arrays are passed by reference, so this writes the caller's data
+                if let Some(array) = assign.target.strip_prefix("__ARRAY_INDEX__:") {
+                    if self.params.contains(array) {
+                        self.direct = true;
+                    }
+                }
+                self.expression(&assign.value);
+            }
+        }
+    }
+}
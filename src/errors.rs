@@ -17,6 +17,111 @@ pub enum CompilerError {
     #[error(transparent)]
     #[diagnostic(transparent)]
     Semantic(#[from] SemanticError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Backend(#[from] BackendError),
+}
+
+/// Failures of the C compiler that turns generated code into an executable
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum BackendError {
+    #[error("C compilation failed ({}): {}", describe_status(.status), .invocation.join(" "))]
+    #[diagnostic(
+        code(minilang::backend::compile_failed),
+        help("{stderr}")
+    )]
+    CompileFailed {
+        /// The compiler followed by its arguments
+        invocation: Vec<String>,
+        /// Exit code, or None if the compiler was killed by a signal
+        status: Option<i32>,
+        stderr: String,
+    },
+
+    #[error("could not run the C compiler: {}", .invocation.join(" "))]
+    #[diagnostic(
+        code(minilang::backend::spawn_failed),
+        help("{reason}\nMake sure GCC is installed: gcc --version")
+    )]
+    Spawn {
+        invocation: Vec<String>,
+        reason: String,
+    },
+}
+
+/// A C compiler message placed on the MiniLang line it was reported at, via
+/// the `#line` directives in the generated code
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum CcDiagnostic {
+    #[error("C compiler error: {message}")]
+    #[diagnostic(
+        code(minilang::backend::c_error),
+        help("MiniLang accepted this line, but the C generated for it does not compile. This is a compiler bug; --save-temps keeps the generated C for a report")
+    )]
+    Error {
+        message: String,
+        #[label("in the C generated for this line")]
+        span: SourceSpan,
+    },
+
+    #[error("C compiler warning: {message}")]
+    #[diagnostic(
+        code(minilang::backend::c_warning),
+        severity(warning)
+    )]
+    Warning {
+        message: String,
+        #[label("in the C generated for this line")]
+        span: SourceSpan,
+    },
+}
+
+impl BackendError {
+    /// The compiler followed by its arguments, as it was run
+    pub fn invocation(&self) -> &[String] {
+        match self {
+            BackendError::CompileFailed { invocation, .. } | BackendError::Spawn { invocation, .. } => invocation,
+        }
+    }
+}
+
+/// Problems reading a bytecode file made by `compile --target=bytecode`
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum BytecodeError {
+    #[error("not a MiniLang bytecode file")]
+    #[diagnostic(
+        code(minilang::bytecode::not_bytecode),
+        help("Bytecode files are made with: minilang compile --target=bytecode <file>")
+    )]
+    NotBytecode,
+
+    #[error("bytecode format version {found} is not supported (this VM runs version {supported})")]
+    #[diagnostic(
+        code(minilang::bytecode::unsupported_version),
+        help("Compile the program again with this version of minilang")
+    )]
+    UnsupportedVersion {
+        found: u16,
+        supported: u16,
+    },
+
+    #[error("the bytecode file is damaged at byte {offset}: {reason}")]
+    #[diagnostic(
+        code(minilang::bytecode::corrupt),
+        help("Compile the program again; the file may have been cut short or edited")
+    )]
+    Corrupt {
+        offset: usize,
+        reason: String,
+    },
+}
+
+fn describe_status(status: &Option<i32>) -> String {
+    match status {
+        Some(code) => format!("exit status {}", code),
+        None => "killed by a signal".to_string(),
+    }
 }
 
 /// Lexer-specific errors with beautiful diagnostics
@@ -113,7 +218,7 @@ pub enum ParserError {
     #[error("missing type annotation")]
     #[diagnostic(
         code(minilang::parser::missing_type),
-        help("Variables require type annotations: let name: type = value;")
+        help("A variable declared without a value needs a type annotation: let name: type; (or give it a value to infer the type from: let name = value;)")
     )]
     MissingType {
         #[label("type annotation expected here")]
@@ -128,6 +233,41 @@ pub enum ParserError {
     UnexpectedEof {
         expected: String,
     },
+    
+    #[error("invalid compile-time condition")]
+    #[diagnostic(
+        code(minilang::parser::invalid_condition),
+        help("{reason}")
+    )]
+    InvalidCondition {
+        reason: String,
+        #[label("cannot be evaluated at compile time")]
+        span: SourceSpan,
+    },
+    
+    #[error("this feature requires version {required}")]
+    #[diagnostic(
+        code(minilang::parser::feature_requires_version),
+        help("This file is read as MiniLang {version}. Start it with `minilang {required};` to use {feature}")
+    )]
+    FeatureRequiresVersion {
+        feature: String,
+        required: String,
+        version: String,
+        #[label("{feature} (version {required} and later)")]
+        span: SourceSpan,
+    },
+    
+    #[error("unsupported language version")]
+    #[diagnostic(
+        code(minilang::parser::unsupported_version),
+        help("{reason}")
+    )]
+    UnsupportedVersion {
+        reason: String,
+        #[label("in this version declaration")]
+        span: SourceSpan,
+    },
 }
 
 
@@ -210,6 +350,50 @@ pub enum SemanticError {
         span: SourceSpan,
     },
 
+    #[error("'main' cannot return {found}")]
+    #[diagnostic(
+        code(minilang::semantic::invalid_main_return),
+        help("The value main sends is the program's exit status, so main returns int or nothing: func main() -> int")
+    )]
+    InvalidMainReturnType {
+        found: String,
+        #[label("main declared here")]
+        span: SourceSpan,
+    },
+
+    #[error("entry point '{name}' {problem}")]
+    #[diagnostic(
+        code(minilang::semantic::invalid_entry),
+        help("--entry calls the function with no arguments and exits with its result, so it takes no parameters and returns int or nothing")
+    )]
+    InvalidEntry {
+        name: String,
+        problem: String,
+        #[label("entry point declared here")]
+        span: SourceSpan,
+    },
+
+    #[error("'main' cannot be private")]
+    #[diagnostic(
+        code(minilang::semantic::private_main),
+        help("main is where the program starts, so it has to stay visible to the linker. Remove `private`")
+    )]
+    PrivateMain {
+        #[label("main declared here")]
+        span: SourceSpan,
+    },
+
+    #[error("function '{name}' is private to another module")]
+    #[diagnostic(
+        code(minilang::semantic::private_function),
+        help("Only public functions can be called from other modules. Remove `private` from '{name}' to share it")
+    )]
+    PrivateFunction {
+        name: String,
+        #[label("private function")]
+        span: SourceSpan,
+    },
+
     #[error("break/continue outside loop")]
     #[diagnostic(
         code(minilang::semantic::break_outside_loop),
@@ -220,6 +404,172 @@ pub enum SemanticError {
         #[label("not inside a loop")]
         span: SourceSpan,
     },
+
+    #[error("'{statement}' cannot leave a defer block")]
+    #[diagnostic(
+        code(minilang::semantic::jump_out_of_defer),
+        help("Deferred code runs while the enclosing block is already exiting, so it cannot 'send', 'break' or 'continue' out of it")
+    )]
+    JumpOutOfDefer {
+        statement: String,
+        #[label("jumps out of defer")]
+        span: SourceSpan,
+    },
+
+    #[error("{what} in a spawn block")]
+    #[diagnostic(
+        code(minilang::semantic::spawn_violation),
+        help("{reason}")
+    )]
+    SpawnViolation {
+        what: String,
+        reason: String,
+        #[label("not allowed in a spawn block")]
+        span: SourceSpan,
+    },
+
+    #[error("{what} in a parallel for")]
+    #[diagnostic(
+        code(minilang::semantic::parallel_violation),
+        help("{reason}")
+    )]
+    ParallelViolation {
+        what: String,
+        reason: String,
+        #[label("not allowed in a parallel for")]
+        span: SourceSpan,
+    },
+
+    #[error("side effect in pure function '{name}'")]
+    #[diagnostic(
+        code(minilang::semantic::purity_violation),
+        help("{reason}\nRemove the 'pure' qualifier or move the side effect out of this function")
+    )]
+    PurityViolation {
+        name: String,
+        reason: String,
+        #[label("not allowed in a pure function")]
+        span: SourceSpan,
+    },
+
+    #[error("arrays cannot be tuple elements")]
+    #[diagnostic(
+        code(minilang::semantic::array_in_tuple),
+        help("A tuple is copied whole, while an array is passed by reference, so {found} cannot be built. Pass the array alongside the tuple instead")
+    )]
+    ArrayInTuple {
+        found: String,
+        #[label("tuple with an array element")]
+        span: SourceSpan,
+    },
+
+    #[error("array '{name}' initializer has {found} elements, expected {declared}")]
+    #[diagnostic(
+        code(minilang::semantic::array_length_mismatch),
+        help("Array '{name}' is declared with {declared} elements. Make the literal match, or write the type as '[]' to take the size from the literal")
+    )]
+    ArrayLengthMismatch {
+        name: String,
+        declared: usize,
+        found: usize,
+        #[label("initializer length differs from declared size")]
+        span: SourceSpan,
+    },
+
+    #[error("array argument to '{name}' has {found} elements, expected {expected}")]
+    #[diagnostic(
+        code(minilang::semantic::array_too_small),
+        help("'{name}' declares this parameter with {expected} elements and may read all of them. Pass an array of that size")
+    )]
+    ArrayTooSmall {
+        name: String,
+        expected: usize,
+        found: usize,
+        #[label("array is too small for this parameter")]
+        span: SourceSpan,
+    },
+
+    #[error("reserved identifier '{name}'")]
+    #[diagnostic(
+        code(minilang::semantic::reserved_identifier),
+        help("Names starting with '__' are reserved for the compiler. Rename '{name}'")
+    )]
+    ReservedIdentifier {
+        name: String,
+        #[label("reserved name declared here")]
+        span: SourceSpan,
+    },
+
+    #[error("constant expression '{expression}' overflows int")]
+    #[diagnostic(
+        code(minilang::semantic::constant_overflow),
+        help("With --overflow trap this would abort at runtime. Use smaller values, or --overflow wrap for two's complement wrapping")
+    )]
+    ConstantOverflow {
+        expression: String,
+        #[label("result does not fit in a 32-bit int")]
+        span: SourceSpan,
+    },
+
+    #[error("cannot infer the type of '{name}'")]
+    #[diagnostic(
+        code(minilang::semantic::cannot_infer_type),
+        help("{reason}. Add a type annotation: let {name}: type = value;")
+    )]
+    CannotInferType {
+        name: String,
+        reason: String,
+        #[label("type annotation needed")]
+        span: SourceSpan,
+    },
+
+    #[error("{keyword} condition must be a bool, found {found}")]
+    #[diagnostic(
+        code(minilang::semantic::non_bool_condition),
+        help("Numbers are not true or false in MiniLang. Write `{replacement}` as the {keyword} condition")
+    )]
+    NonBoolCondition {
+        keyword: String,
+        found: String,
+        replacement: String,
+        #[label("not a bool")]
+        span: SourceSpan,
+    },
+
+    #[error("const array '{name}' may be modified")]
+    #[diagnostic(
+        code(minilang::semantic::constant_array_modified),
+        help("{reason}")
+    )]
+    ConstantArrayModified {
+        name: String,
+        reason: String,
+        #[label("const array used where it could change")]
+        span: SourceSpan,
+    },
+
+    #[error("invalid attribute '@{name}'")]
+    #[diagnostic(
+        code(minilang::semantic::invalid_attribute),
+        help("{reason}")
+    )]
+    InvalidAttribute {
+        name: String,
+        reason: String,
+        #[label("attribute not allowed here")]
+        span: SourceSpan,
+    },
+}
+
+impl SemanticError {
+    /// The source the labelled span should be replaced with, for errors
+    /// whose fix is mechanical
+    pub fn fix(&self) -> Option<(SourceSpan, &str)> {
+        match self {
+            SemanticError::NonBoolCondition { replacement, span, .. } => Some((*span, replacement)),
+            _ => None,
+        }
+    }
 }
 
 
@@ -232,6 +582,11 @@ pub enum CompilerWarning {
         defined_at: SourceSpan,
     },
     
+    UnusedConstant {
+        name: String,
+        span: SourceSpan,
+    },
+    
     UnreachableCode {
         span: SourceSpan,
         reason: String,
@@ -242,78 +597,307 @@ pub enum CompilerWarning {
         span: SourceSpan,
         original_span: SourceSpan,
     },
+    
+    FloatEquality {
+        op: String,
+        span: SourceSpan,
+    },
+    
+    DeprecatedCall {
+        name: String,
+        note: Option<String>,
+        span: SourceSpan,
+    },
+    
+    SideEffectInCondition {
+        keyword: String,
+        effect: String,
+        span: SourceSpan,
+    },
+    
+    EmptyBlock {
+        construct: String,
+        suggestion: String,
+        span: SourceSpan,
+    },
+    
+    EmptyFunction {
+        name: String,
+        span: SourceSpan,
+    },
+    
+    LoopInvariant {
+        expression: String,
+        reason: String,
+        span: SourceSpan,
+    },
+    
+    /// Found by a compiler plugin's lint rule
+    Plugin {
+        plugin: String,
+        code: String,
+        message: String,
+        label: String,
+        help: Option<String>,
+        span: SourceSpan,
+    },
 }
 
 impl CompilerWarning {
+    /// The warning's code, e.g. `minilang::warning::unused_variable`
+    pub fn code(&self) -> String {
+        self.diagnostic().code().map(|code| code.to_string()).unwrap_or_default()
+    }
+    
     pub fn display(&self, source: &str, filename: &str) {
-        use miette::{NamedSource, Report, Diagnostic};
-        use thiserror::Error;
-        
-        #[derive(Error, Debug, Diagnostic)]
-        enum Warning {
-            #[error("unused variable '{name}'")]
-            #[diagnostic(
-                code(minilang::warning::unused_variable),
-                severity(warning),
-                help("Consider removing this variable or using it")
-            )]
-            UnusedVariable {
-                name: String,
-                #[label("defined here but never used")]
-                span: SourceSpan,
-            },
-            
-            #[error("unreachable code")]
-            #[diagnostic(
-                code(minilang::warning::unreachable_code),
-                severity(warning),
-                help("{reason}")
-            )]
-            UnreachableCode {
-                #[label("this code will never execute")]
-                span: SourceSpan,
-                reason: String,
-            },
-            
-            #[error("variable '{name}' shadows previous declaration")]
-            #[diagnostic(
-                code(minilang::warning::shadowed_variable),
-                severity(warning),
-                help("Consider using a different name")
-            )]
-            ShadowedVariable {
-                name: String,
-                #[label("shadows here")]
-                span: SourceSpan,
-                #[label("original defined here")]
-                original: SourceSpan,
-            },
-        }
+        eprintln!("{:?}", self.report(source, filename));
+    }
+    
+    /// The warning with its source attached, for rendering elsewhere
+    pub fn report(&self, source: &str, filename: &str) -> miette::Report {
+        use miette::{NamedSource, Report};
         
+        let named_source = NamedSource::new(filename, source.to_string());
+        Report::new_boxed(self.diagnostic()).with_source_code(named_source)
+    }
+    
+    /// The warning as miette renders it
+    fn diagnostic(&self) -> Box<dyn Diagnostic + Send + Sync> {
         let warning = match self {
             CompilerWarning::UnusedVariable { name, span, .. } => {
-                Warning::UnusedVariable {
+                WarningDiagnostic::UnusedVariable {
+                    name: name.clone(),
+                    span: *span,
+                }
+            },
+            CompilerWarning::UnusedConstant { name, span } => {
+                WarningDiagnostic::UnusedConstant {
                     name: name.clone(),
                     span: *span,
                 }
             },
             CompilerWarning::UnreachableCode { span, reason } => {
-                Warning::UnreachableCode {
+                WarningDiagnostic::UnreachableCode {
                     span: *span,
                     reason: reason.clone(),
                 }
             },
             CompilerWarning::ShadowedVariable { name, span, original_span } => {
-                Warning::ShadowedVariable {
+                WarningDiagnostic::ShadowedVariable {
                     name: name.clone(),
                     span: *span,
                     original: *original_span,
                 }
             },
+            CompilerWarning::FloatEquality { op, span } => {
+                WarningDiagnostic::FloatEquality {
+                    op: op.clone(),
+                    span: *span,
+                }
+            },
+            CompilerWarning::DeprecatedCall { name, note, span } => {
+                WarningDiagnostic::DeprecatedCall {
+                    name: name.clone(),
+                    note: note.clone().unwrap_or_else(|| format!("'{}' is marked @deprecated and may be removed", name)),
+                    span: *span,
+                }
+            },
+            CompilerWarning::SideEffectInCondition { keyword, effect, span } => {
+                WarningDiagnostic::SideEffectInCondition {
+                    keyword: keyword.clone(),
+                    effect: effect.clone(),
+                    span: *span,
+                }
+            },
+            CompilerWarning::EmptyBlock { construct, suggestion, span } => {
+                WarningDiagnostic::EmptyBlock {
+                    construct: construct.clone(),
+                    suggestion: suggestion.clone(),
+                    span: *span,
+                }
+            },
+            CompilerWarning::EmptyFunction { name, span } => {
+                WarningDiagnostic::EmptyFunction {
+                    name: name.clone(),
+                    span: *span,
+                }
+            },
+            CompilerWarning::LoopInvariant { expression, reason, span } => {
+                WarningDiagnostic::LoopInvariant {
+                    expression: expression.clone(),
+                    reason: reason.clone(),
+                    span: *span,
+                }
+            },
+            CompilerWarning::Plugin { plugin, code, message, label, help, span } => {
+                return Box::new(PluginWarning {
+                    code: format!("{}::{}", plugin, code),
+                    message: message.clone(),
+                    label: label.clone(),
+                    help: help.clone(),
+                    span: *span,
+                });
+            },
         };
-        
-        let named_source = NamedSource::new(filename, source.to_string());
-        let report = Report::from(warning).with_source_code(named_source);
-        eprintln!("{:?}", report);
+        Box::new(warning)
+    }
+}
+
+/// A plugin's warning, whose code is only known at runtime
+#[derive(Error, Debug)]
+#[error("{message}")]
+struct PluginWarning {
+    code: String,
+    message: String,
+    label: String,
+    help: Option<String>,
+    span: SourceSpan,
+}
+
+impl Diagnostic for PluginWarning {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(&self.code))
+    }
+    
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(miette::Severity::Warning)
+    }
+    
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.help.as_ref().map(|help| Box::new(help) as Box<dyn std::fmt::Display>)
+    }
+    
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        Some(Box::new(std::iter::once(miette::LabeledSpan::new_with_span(Some(self.label.clone()), self.span))))
     }
 }
+
+/// How each CompilerWarning is reported
+#[derive(Error, Debug, Diagnostic)]
+enum WarningDiagnostic {
+    #[error("unused variable '{name}'")]
+    #[diagnostic(
+        code(minilang::warning::unused_variable),
+        severity(warning),
+        help("Consider removing this variable or using it")
+    )]
+    UnusedVariable {
+        name: String,
+        #[label("defined here but never used")]
+        span: SourceSpan,
+    },
+    
+    #[error("constant '{name}' is never read")]
+    #[diagnostic(
+        code(minilang::warning::unused_constant),
+        severity(warning),
+        help("Remove it, or start its name with '_' if it is kept on purpose")
+    )]
+    UnusedConstant {
+        name: String,
+        #[label("defined here but never read")]
+        span: SourceSpan,
+    },
+    
+    #[error("unreachable code")]
+    #[diagnostic(
+        code(minilang::warning::unreachable_code),
+        severity(warning),
+        help("{reason}")
+    )]
+    UnreachableCode {
+        #[label("this code will never execute")]
+        span: SourceSpan,
+        reason: String,
+    },
+    
+    #[error("variable '{name}' shadows previous declaration")]
+    #[diagnostic(
+        code(minilang::warning::shadowed_variable),
+        severity(warning),
+        help("Consider using a different name")
+    )]
+    ShadowedVariable {
+        name: String,
+        #[label("shadows here")]
+        span: SourceSpan,
+        #[label("original defined here")]
+        original: SourceSpan,
+    },
+    
+    #[error("exact float comparison with '{op}'")]
+    #[diagnostic(
+        code(minilang::warning::float_equality),
+        severity(warning),
+        help("Floating-point results carry rounding error, so exact comparison is fragile. Use approx_equal(a, b, eps) instead")
+    )]
+    FloatEquality {
+        op: String,
+        #[label("compares floats exactly")]
+        span: SourceSpan,
+    },
+    
+    #[error("call to deprecated function '{name}'")]
+    #[diagnostic(
+        code(minilang::warning::deprecated_call),
+        severity(warning),
+        help("{note}")
+    )]
+    DeprecatedCall {
+        name: String,
+        note: String,
+        #[label("deprecated")]
+        span: SourceSpan,
+    },
+    
+    #[error("'{keyword}' condition {effect}, which has side effects")]
+    #[diagnostic(
+        code(minilang::warning::side_effect_in_condition),
+        severity(warning),
+        help("A condition may run many times, or not at all after AND/OR, so the effect is easy to miss. Store the result in a variable before the '{keyword}' instead")
+    )]
+    SideEffectInCondition {
+        keyword: String,
+        effect: String,
+        #[label("side effect here")]
+        span: SourceSpan,
+    },
+    
+    #[error("empty '{construct}' body")]
+    #[diagnostic(
+        code(minilang::warning::empty_block),
+        severity(warning),
+        help("{suggestion}")
+    )]
+    EmptyBlock {
+        construct: String,
+        suggestion: String,
+        #[label("does nothing")]
+        span: SourceSpan,
+    },
+    
+    #[error("function '{name}' has no statements")]
+    #[diagnostic(
+        code(minilang::warning::empty_function),
+        severity(warning),
+        help("Remove '{name}' if nothing needs it, or add a '# TODO' comment to its body if it is still to be written")
+    )]
+    EmptyFunction {
+        name: String,
+        #[label("empty body")]
+        span: SourceSpan,
+    },
+    
+    #[error("'{expression}' gives the same value on every pass of the inner loop")]
+    #[diagnostic(
+        code(minilang::warning::loop_invariant),
+        severity(warning),
+        help("Nothing it reads changes inside the loop. Compute it once before the loop and store it in a variable")
+    )]
+    LoopInvariant {
+        expression: String,
+        reason: String,
+        #[label("{reason}")]
+        span: SourceSpan,
+    },
+}
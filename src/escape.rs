@@ -0,0 +1,242 @@
+// src/escape.rs - Escape analysis for strings built at runtime
+//
+// Interpolation, format() and to_string() allocate their result. Most of
+// those strings are printed or read once and never seen again, so they can
+// live in a buffer on the C stack instead of the heap. A string escapes its
+// function, and must stay on the heap, when it is returned, passed to a
+// function, stored in an array, tuple or reassignable variable, read by a
+// spawn block's thread, or bound to a variable that does any of those.
+//
+// A string stays put when it is:
+// - displayed, or an argument of format(), parse_int() or an interpolation,
+//   which copy or read it before the statement ends
+// - the value of a `let` or `const` whose variable stays put: declared once
+//   in the function, never assigned, and only used in the ways above
+
+use crate::ast::*;
+use std::collections::{HashMap, HashSet};
+
+/// The interpolations, format() and to_string() calls in `function` whose
+/// result never leaves it, by node id. `functions` are the names the program
+/// defines, which take precedence over builtins of the same name.
+pub fn stack_strings(function: &Function, functions: &HashSet<&str>) -> HashSet<NodeId> {
+    let mut scan = Scan {
+        functions,
+        declared: HashMap::new(),
+        assigned: HashSet::new(),
+        escaping: HashSet::new(),
+        sites: Vec::new(),
+        in_spawn: false,
+    };
+    for param in &function.params {
+        // A parameter's string belongs to the caller
+        scan.escaping.insert(param.name.clone());
+    }
+    scan.block(&function.body);
+
+    let stays = |name: &str| {
+        scan.declared.get(name) == Some(&1) && !scan.assigned.contains(name) && !scan.escaping.contains(name)
+    };
+    scan.sites.iter()
+        .filter(|(_, binding)| binding.as_deref().is_none_or(stays))
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+/// Where a value goes once the expression producing it is evaluated
+#[derive(Clone)]
+enum Use {
+    /// Read before the statement ends, and then dropped
+    Consumed,
+    /// Kept in a variable of this name
+    Bound(String),
+    /// Anywhere else
+    Escapes,
+}
+
+struct Scan<'a> {
+    functions: &'a HashSet<&'a str>,
+    // How many times each name is declared in the function
+    declared: HashMap<String, usize>,
+    assigned: HashSet<String>,
+    // Variables read somewhere their value could be kept
+    escaping: HashSet<String>,
+    // Allocations that stay put, provided the variable they are bound to does
+    sites: Vec<(NodeId, Option<String>)>,
+    // Whether the scan is inside a spawn block, whose thread takes its own
+    // copy of every variable it reads
+    in_spawn: bool,
+}
+
+impl Scan<'_> {
+    fn block(&mut self, block: &Block) {
+        for statement in &block.statements {
+            self.statement(statement);
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        *self.declared.entry(name.to_string()).or_default() += 1;
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let(stmt) => {
+                self.declare(&stmt.name);
+                if let Some(value) = &stmt.value {
+                    self.expression(value, Use::Bound(stmt.name.clone()));
+                }
+            }
+            Statement::Const(stmt) => {
+                self.declare(&stmt.name);
+                self.expression(&stmt.value, Use::Bound(stmt.name.clone()));
+            }
+            Statement::LetTuple(stmt) => {
+                for name in &stmt.names {
+                    self.declare(name);
+                }
+                self.expression(&stmt.value, Use::Escapes);
+            }
+            Statement::Display(stmt) => {
+                for expr in &stmt.expressions {
+                    match expr {
+                        // Printed part by part, without building the string
+                        Expression::Literal(LiteralExpr { value: Literal::InterpolatedString(parts), .. }) => {
+                            self.parts(parts);
+                        }
+                        _ => self.expression(expr, Use::Consumed),
+                    }
+                }
+            }
+            Statement::If(stmt) => {
+                self.expression(&stmt.condition, Use::Consumed);
+                self.block(&stmt.then_block);
+                if let Some(else_block) = &stmt.else_block {
+                    self.block(else_block);
+                }
+            }
+            Statement::While(stmt) => {
+                self.expression(&stmt.condition, Use::Consumed);
+                self.block(&stmt.body);
+            }
+            Statement::DoWhile(stmt) => {
+                self.block(&stmt.body);
+                self.expression(&stmt.condition, Use::Consumed);
+            }
+            Statement::For(stmt) => {
+                if let Some(init) = &stmt.init {
+                    self.statement(init);
+                }
+                if let Some(condition) = &stmt.condition {
+                    self.expression(condition, Use::Consumed);
+                }
+                if let Some(update) = &stmt.update {
+                    self.expression(update, Use::Consumed);
+                }
+                self.block(&stmt.body);
+            }
+            Statement::Return(stmt) => {
+                if let Some(value) = &stmt.value {
+                    self.expression(value, Use::Escapes);
+                }
+            }
+            Statement::Expression(stmt) => self.expression(&stmt.expression, Use::Consumed),
+            Statement::Block(block) => self.block(block),
+            Statement::Defer(stmt) => self.block(&stmt.body),
+            Statement::Spawn(stmt) => {
+                let outer = std::mem::replace(&mut self.in_spawn, true);
+                self.block(&stmt.body);
+                self.in_spawn = outer;
+            }
+            Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+
+    fn parts(&mut self, parts: &[StringPart]) {
+        for part in parts {
+            if let StringPart::Expression(expr) = part {
+                self.expression(expr, Use::Consumed);
+            }
+        }
+    }
+
+    fn is_builtin(&self, call: &CallExpr, name: &str) -> bool {
+        call.function == name && !self.functions.contains(name)
+    }
+
+    /// Record `expr`, which ends up as `usage` says, and scan inside it
+    fn expression(&mut self, expr: &Expression, usage: Use) {
+        let allocates = match expr {
+            Expression::Literal(LiteralExpr { value: Literal::InterpolatedString(_), .. }) => true,
+            Expression::Call(call) => self.is_builtin(call, "format") || self.is_builtin(call, "to_string"),
+            _ => false,
+        };
+        if allocates {
+            match usage {
+                Use::Consumed => self.sites.push((expr.id(), None)),
+                Use::Bound(name) => self.sites.push((expr.id(), Some(name))),
+                Use::Escapes => {}
+            }
+        } else if let Expression::Identifier(id_expr) = expr {
+            if self.in_spawn || !matches!(usage, Use::Consumed) {
+                self.escaping.insert(id_expr.name.clone());
+            }
+        }
+        self.children(expr);
+    }
+
+    fn children(&mut self, expr: &Expression) {
+        match expr {
+            // Already on the stack; what it is built from still counts
+            Expression::Call(call) if call.function == STACK_STRING => {
+                for arg in &call.args {
+                    self.children(arg);
+                }
+            }
+            // The template must stay a literal for the code generator, so
+            // only what it interpolates can be a site
+            Expression::Call(call) if self.is_builtin(call, "format") => {
+                if let Some((template, args)) = call.args.split_first() {
+                    self.children(template);
+                    for arg in args {
+                        self.expression(arg, Use::Consumed);
+                    }
+                }
+            }
+            Expression::Call(call) => {
+                let reads = ["parse_int", "to_string", "approx_equal"]
+                    .iter()
+                    .any(|name| self.is_builtin(call, name));
+                let usage = if reads { Use::Consumed } else { Use::Escapes };
+                for arg in &call.args {
+                    self.expression(arg, usage.clone());
+                }
+            }
+            Expression::Literal(lit) => match &lit.value {
+                Literal::InterpolatedString(parts) => self.parts(parts),
+                Literal::Array(elements) | Literal::Tuple(elements) => {
+                    for element in elements {
+                        self.expression(element, Use::Escapes);
+                    }
+                }
+                _ => {}
+            },
+            Expression::Assign(assign) => {
+                if !assign.target.starts_with("__ARRAY_INDEX__:") {
+                    self.assigned.insert(assign.target.clone());
+                }
+                self.expression(&assign.value, Use::Escapes);
+            }
+            Expression::Binary(binary) => {
+                self.expression(&binary.left, Use::Escapes);
+                self.expression(&binary.right, Use::Escapes);
+            }
+            Expression::Unary(unary) => self.expression(&unary.operand, Use::Escapes),
+            Expression::Index(index) => {
+                self.expression(&index.array, Use::Escapes);
+                self.expression(&index.index, Use::Escapes);
+            }
+            Expression::Identifier(_) => {}
+        }
+    }
+}
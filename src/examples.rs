@@ -0,0 +1,50 @@
+// src/examples.rs - Sample programs embedded in the binary
+
+/// A sample program shipped with the compiler
+#[derive(Debug, Clone, Copy)]
+pub struct Example {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    pub source: &'static str,
+}
+
+/// The samples, shared with the web playground's examples directory
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        name: "hello",
+        title: "Hello World",
+        description: "Basic MiniLang program with display statement",
+        source: include_str!("../web/examples/hello.mini"),
+    },
+    Example {
+        name: "fibonacci",
+        title: "Fibonacci",
+        description: "Recursive Fibonacci implementation",
+        source: include_str!("../web/examples/fibonacci.mini"),
+    },
+    Example {
+        name: "factorial",
+        title: "Factorial",
+        description: "Recursive factorial calculation",
+        source: include_str!("../web/examples/factorial.mini"),
+    },
+    Example {
+        name: "bubble_sort",
+        title: "Bubble Sort",
+        description: "Array sorting with bubble sort algorithm",
+        source: include_str!("../web/examples/bubble_sort.mini"),
+    },
+    Example {
+        name: "prime_numbers",
+        title: "Prime Numbers",
+        description: "Find prime numbers with is_prime function",
+        source: include_str!("../web/examples/prime_numbers.mini"),
+    },
+];
+
+/// Look up an example by name, with or without the `.mini` extension
+pub fn find_example(name: &str) -> Option<&'static Example> {
+    let name = name.strip_suffix(".mini").unwrap_or(name);
+    EXAMPLES.iter().find(|example| example.name == name)
+}
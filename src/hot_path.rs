@@ -0,0 +1,316 @@
+// src/hot_path.rs - Work an inner loop repeats for nothing
+//
+// The body of a loop nested in another runs the most often, so anything it
+// recomputes with the same result every time is worth moving out. This finds
+// two obvious cases: a call to an expensive function of the program whose
+// arguments do not change inside the loop, and the same index computation
+// written more than once in the loop. A call is expensive when the cost
+// model (analyzer::cost) gives its function a loop over its input, recursion
+// or at least MIN_CALL_OPS operations. Only functions without side effects
+// qualify, so computing the value once before the loop gives the same result.
+
+use crate::analyzer::cost::{self, Estimate};
+use crate::ast::*;
+use crate::control_flow::Loop;
+use std::collections::{HashMap, HashSet};
+
+/// Calls to functions estimated below this many operations are cheap enough
+/// to leave in the loop
+pub const MIN_CALL_OPS: u64 = 10;
+
+/// Something an inner loop computes on every pass with the same result
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotSpot {
+    /// The expression as written, e.g. `count_primes(100)`
+    pub expression: String,
+    /// Why it is worth hoisting, e.g. `about 6n + 2 operations per call`
+    pub reason: String,
+    pub span: Span,
+}
+
+/// The hot spots of every function in `program`, in source order.
+/// `effectful` names the functions whose calls have side effects, as
+/// `effects::effectful_functions` finds them.
+pub fn find(program: &Program, effectful: &HashSet<String>) -> Vec<HotSpot> {
+    let estimates = cost::calculate_all(program)
+        .into_iter()
+        .map(|estimate| (estimate.name.clone(), estimate))
+        .collect();
+    let mut finder = Finder { estimates, effectful, spots: Vec::new() };
+    for function in &program.functions {
+        finder.block(&function.body, 0);
+    }
+    finder.spots.sort_by_key(|spot| spot.span.start);
+    finder.spots
+}
+
+struct Finder<'a> {
+    estimates: HashMap<String, Estimate>,
+    effectful: &'a HashSet<String>,
+    spots: Vec<HotSpot>,
+}
+
+impl Finder<'_> {
+    /// Look for loops in `block`, which is inside `depth` loops
+    fn block(&mut self, block: &Block, depth: usize) {
+        for statement in &block.statements {
+            self.statement(statement, depth);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement, depth: usize) {
+        if let Some(looped) = Loop::of(statement) {
+            if depth >= 1 {
+                self.inner_loop(&looped);
+            }
+            self.block(looped.body, depth + 1);
+            return;
+        }
+        match statement {
+            Statement::If(if_stmt) => {
+                self.block(&if_stmt.then_block, depth);
+                if let Some(else_block) = &if_stmt.else_block {
+                    self.block(else_block, depth);
+                }
+            }
+            Statement::Block(block) => self.block(block, depth),
+            Statement::Defer(defer_stmt) => self.block(&defer_stmt.body, depth),
+            Statement::Spawn(spawn_stmt) => self.block(&spawn_stmt.body, depth),
+            _ => {}
+        }
+    }
+
+    /// Report what a loop inside another loop recomputes on every pass
+    fn inner_loop(&mut self, looped: &Loop) {
+        let mut changed = HashSet::new();
+        if let Some(init) = looped.init {
+            changed_in_statement(init, self.effectful, &mut changed);
+        }
+        if let Some(update) = looped.update {
+            changed_in_expression(update, self.effectful, &mut changed);
+        }
+        changed_in_block(looped.body, self.effectful, &mut changed);
+
+        // Everything evaluated on each pass, except nested loops, which are
+        // checked on their own
+        let mut per_pass = Vec::new();
+        per_pass.extend(looped.condition);
+        per_pass.extend(looped.update);
+        pass_expressions(looped.body, &mut per_pass);
+
+        let invariant = Invariance { changed: &changed, effectful: self.effectful };
+        let mut indexes: Vec<&Expression> = Vec::new();
+        for expr in per_pass {
+            self.expensive_calls(expr, &invariant);
+            invariant_indexes(expr, &invariant, &mut indexes);
+        }
+
+        // The same computation written more than once, reported where it
+        // first appears
+        let mut counts: Vec<(String, usize, Span)> = Vec::new();
+        for index in indexes {
+            let text = index.to_string();
+            match counts.iter_mut().find(|(seen, _, _)| *seen == text) {
+                Some((_, count, _)) => *count += 1,
+                None => counts.push((text, 1, index.span().clone())),
+            }
+        }
+        for (expression, count, span) in counts.into_iter().filter(|(_, count, _)| *count > 1) {
+            self.spots.push(HotSpot {
+                reason: format!("computed {} times per pass", count),
+                expression,
+                span,
+            });
+        }
+    }
+
+    /// Report the outermost invariant calls to expensive functions in `expr`
+    fn expensive_calls(&mut self, expr: &Expression, invariant: &Invariance) {
+        if let Expression::Call(call) = expr {
+            if let Some(estimate) = self.estimates.get(&call.function) {
+                let expensive = estimate.recursive
+                    || estimate.cost.degree() > 0
+                    || estimate.cost.terms.first().is_some_and(|&ops| ops >= MIN_CALL_OPS);
+                if expensive && invariant.holds(expr) {
+                    let reason = if estimate.recursive {
+                        "recursive, so each call may do a lot of work".to_string()
+                    } else {
+                        format!("about {} operations per call", estimate.cost)
+                    };
+                    self.spots.push(HotSpot { expression: expr.to_string(), reason, span: call.span.clone() });
+                    return;
+                }
+            }
+        }
+        for child in children(expr) {
+            self.expensive_calls(child, invariant);
+        }
+    }
+}
+
+/// What stays the same on every pass of a loop
+struct Invariance<'a> {
+    /// Variables the loop assigns, declares or may write through a call
+    changed: &'a HashSet<String>,
+    effectful: &'a HashSet<String>,
+}
+
+impl Invariance<'_> {
+    /// Whether `expr` gives the same value on every pass: it reads nothing the
+    /// loop changes, assigns nothing and only calls functions without effects
+    fn holds(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Identifier(identifier) => !self.changed.contains(&identifier.name),
+            Expression::Assign(_) => false,
+            Expression::Call(call) if self.effectful.contains(&call.function) => false,
+            _ => children(expr).into_iter().all(|child| self.holds(child)),
+        }
+    }
+}
+
+/// Invariant index computations in `expr`: the index of `a[i * w + j]` when
+/// it is more than a name or a literal
+fn invariant_indexes<'a>(expr: &'a Expression, invariant: &Invariance, out: &mut Vec<&'a Expression>) {
+    if let Expression::Index(index) = expr {
+        let computed = !matches!(&*index.index, Expression::Identifier(_) | Expression::Literal(_));
+        if computed && invariant.holds(&index.index) {
+            out.push(&index.index);
+        }
+    }
+    for child in children(expr) {
+        invariant_indexes(child, invariant, out);
+    }
+}
+
+/// The expressions directly inside `expr`
+fn children(expr: &Expression) -> Vec<&Expression> {
+    match expr {
+        Expression::Literal(literal) => match &literal.value {
+            Literal::Array(elements) | Literal::Tuple(elements) => elements.iter().collect(),
+            Literal::InterpolatedString(parts) => parts.iter()
+                .filter_map(|part| match part {
+                    StringPart::Expression(expr) => Some(expr),
+                    StringPart::Text(_) => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        },
+        Expression::Identifier(_) => Vec::new(),
+        Expression::Binary(binary) => vec![&binary.left, &binary.right],
+        Expression::Unary(unary) => vec![&unary.operand],
+        Expression::Call(call) => call.args.iter().collect(),
+        Expression::Index(index) => vec![&index.array, &index.index],
+        Expression::Assign(assign) => vec![&assign.value],
+    }
+}
+
+/// The expressions `block` evaluates each time it runs, leaving out the
+/// loops inside it
+fn pass_expressions<'a>(block: &'a Block, out: &mut Vec<&'a Expression>) {
+    for statement in &block.statements {
+        if Loop::of(statement).is_some() {
+            continue;
+        }
+        match statement {
+            Statement::Let(let_stmt) => out.extend(&let_stmt.value),
+            Statement::Const(const_stmt) => out.push(&const_stmt.value),
+            Statement::LetTuple(let_tuple) => out.push(&let_tuple.value),
+            Statement::Display(display_stmt) => out.extend(&display_stmt.expressions),
+            Statement::If(if_stmt) => {
+                out.push(&if_stmt.condition);
+                pass_expressions(&if_stmt.then_block, out);
+                if let Some(else_block) = &if_stmt.else_block {
+                    pass_expressions(else_block, out);
+                }
+            }
+            Statement::Return(ret_stmt) => out.extend(&ret_stmt.value),
+            Statement::Expression(expr_stmt) => out.push(&expr_stmt.expression),
+            Statement::Block(block) => pass_expressions(block, out),
+            Statement::Defer(defer_stmt) => pass_expressions(&defer_stmt.body, out),
+            Statement::Spawn(spawn_stmt) => pass_expressions(&spawn_stmt.body, out),
+            Statement::While(_) | Statement::DoWhile(_) | Statement::For(_)
+            | Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+}
+
+/// Names `block` may change: declarations, assignments, array elements
+/// written and arrays passed to calls with side effects
+fn changed_in_block(block: &Block, effectful: &HashSet<String>, out: &mut HashSet<String>) {
+    for statement in &block.statements {
+        changed_in_statement(statement, effectful, out);
+    }
+}
+
+fn changed_in_statement(statement: &Statement, effectful: &HashSet<String>, out: &mut HashSet<String>) {
+    if let Some(looped) = Loop::of(statement) {
+        if let Some(init) = looped.init {
+            changed_in_statement(init, effectful, out);
+        }
+        for expr in looped.condition.into_iter().chain(looped.update) {
+            changed_in_expression(expr, effectful, out);
+        }
+        changed_in_block(looped.body, effectful, out);
+        return;
+    }
+    match statement {
+        Statement::Let(let_stmt) => {
+            out.insert(let_stmt.name.clone());
+            if let Some(value) = &let_stmt.value {
+                changed_in_expression(value, effectful, out);
+            }
+        }
+        Statement::Const(const_stmt) => {
+            out.insert(const_stmt.name.clone());
+            changed_in_expression(&const_stmt.value, effectful, out);
+        }
+        Statement::LetTuple(let_tuple) => {
+            out.extend(let_tuple.names.iter().cloned());
+            changed_in_expression(&let_tuple.value, effectful, out);
+        }
+        Statement::Display(display_stmt) => {
+            for expr in &display_stmt.expressions {
+                changed_in_expression(expr, effectful, out);
+            }
+        }
+        Statement::If(if_stmt) => {
+            changed_in_expression(&if_stmt.condition, effectful, out);
+            changed_in_block(&if_stmt.then_block, effectful, out);
+            if let Some(else_block) = &if_stmt.else_block {
+                changed_in_block(else_block, effectful, out);
+            }
+        }
+        Statement::Return(ret_stmt) => {
+            if let Some(value) = &ret_stmt.value {
+                changed_in_expression(value, effectful, out);
+            }
+        }
+        Statement::Expression(expr_stmt) => changed_in_expression(&expr_stmt.expression, effectful, out),
+        Statement::Block(block) => changed_in_block(block, effectful, out),
+        Statement::Defer(defer_stmt) => changed_in_block(&defer_stmt.body, effectful, out),
+        Statement::Spawn(spawn_stmt) => changed_in_block(&spawn_stmt.body, effectful, out),
+        Statement::While(_) | Statement::DoWhile(_) | Statement::For(_)
+        | Statement::Break(_) | Statement::Continue(_) => {}
+    }
+}
+
+fn changed_in_expression(expr: &Expression, effectful: &HashSet<String>, out: &mut HashSet<String>) {
+    match expr {
+        Expression::Assign(assign) => {
+            let target = assign.target.strip_prefix("__ARRAY_INDEX__:").unwrap_or(&assign.target);
+            out.insert(target.to_string());
+        }
+        // Arrays are passed by reference, so the call may write them
+        Expression::Call(call) if effectful.contains(&call.function) => {
+            for arg in &call.args {
+                if let Expression::Identifier(identifier) = arg {
+                    out.insert(identifier.name.clone());
+                }
+            }
+        }
+        _ => {}
+    }
+    for child in children(expr) {
+        changed_in_expression(child, effectful, out);
+    }
+}
@@ -0,0 +1,45 @@
+// src/interrupt.rs - Ctrl-C handling while compiling and running programs
+
+use std::io;
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::thread;
+use std::time::Duration;
+
+/// Exit status after Ctrl-C, 128 + SIGINT as shells report it
+pub const EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static INSTALL: Once = Once::new();
+
+/// From now on, record Ctrl-C instead of exiting on it, so the caller can
+/// stop its child processes and clean up first. Later calls do nothing.
+pub fn install() {
+    INSTALL.call_once(|| {
+        // Without a handler Ctrl-C still ends the process, just less tidily
+        let _ = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst));
+    });
+}
+
+/// Whether Ctrl-C has been pressed since `install`
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Wait for `child` to exit, killing it on Ctrl-C. Returns `None` when it
+/// was interrupted, including when the terminal's SIGINT reached it first.
+pub fn wait(child: &mut Child) -> io::Result<Option<ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((!interrupted()).then_some(status));
+        }
+        if interrupted() {
+            // It may exit on its own in between; either way it is gone after wait
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
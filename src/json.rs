@@ -0,0 +1,365 @@
+// src/json.rs - JSON values as the bytecode VM holds them
+//
+// The parser, the printer and where parsing fails all follow the C runtime
+// the code generator emits (JSON_SUPPORT in codegen.rs) step for step, so a
+// program prints the same and stops at the same byte on either backend.
+// Numbers are doubles and objects keep their keys in the order they were
+// added.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Nesting deeper than this is rejected instead of overflowing the stack
+pub const MAX_DEPTH: usize = 512;
+
+/// A json value, shared by every copy of the MiniLang value that holds it
+pub type Json = Rc<RefCell<Node>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Node {
+    /// What json_type gives, and what errors call the value
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Node::Null => "null",
+            Node::Bool(_) => "bool",
+            Node::Number(_) => "number",
+            Node::String(_) => "string",
+            Node::Array(_) => "array",
+            Node::Object(_) => "object",
+        }
+    }
+
+    pub fn into_json(self) -> Json {
+        Rc::new(RefCell::new(self))
+    }
+}
+
+/// A copy sharing nothing with `json`, which is how json_set and json_push
+/// store a json value, so no value can end up inside itself
+pub fn deep_copy(json: &Json) -> Json {
+    let node = match &*json.borrow() {
+        Node::Array(items) => Node::Array(items.iter().map(deep_copy).collect()),
+        Node::Object(members) => Node::Object(members.iter().map(|(key, item)| (key.clone(), deep_copy(item))).collect()),
+        other => other.clone(),
+    };
+    node.into_json()
+}
+
+/// Put `item` under `key`, replacing the value already there
+pub fn set(members: &mut Vec<(String, Json)>, key: &str, item: Json) {
+    match members.iter_mut().find(|(existing, _)| existing == key) {
+        Some((_, slot)) => *slot = item,
+        None => members.push((key.to_string(), item)),
+    }
+}
+
+/// Parse a whole JSON document, or give the byte offset where it went wrong
+pub fn parse(text: &str) -> Result<Json, usize> {
+    let mut parser = JsonParser { text: text.as_bytes(), at: 0, depth: 0 };
+    let json = parser.value().ok_or(parser.at)?;
+    parser.space();
+    if parser.at < parser.text.len() {
+        return Err(parser.at);
+    }
+    Ok(json)
+}
+
+struct JsonParser<'a> {
+    text: &'a [u8],
+    at: usize,
+    depth: usize,
+}
+
+impl JsonParser<'_> {
+    /// The byte at `at`, with 0 past the end like the C string's terminator
+    fn byte(&self, at: usize) -> u8 {
+        self.text.get(at).copied().unwrap_or(0)
+    }
+
+    fn space(&mut self) {
+        while matches!(self.byte(self.at), b' ' | b'\t' | b'\n' | b'\r') {
+            self.at += 1;
+        }
+    }
+
+    fn digits(&mut self) {
+        while self.byte(self.at).is_ascii_digit() {
+            self.at += 1;
+        }
+    }
+
+    /// Parse a value, leaving `at` where it went wrong on failure
+    fn value(&mut self) -> Option<Json> {
+        self.space();
+        let open = self.byte(self.at);
+        if open == b'{' || open == b'[' {
+            if self.depth == MAX_DEPTH {
+                return None;
+            }
+            self.depth += 1;
+            let close = if open == b'{' { b'}' } else { b']' };
+            let mut items = Vec::new();
+            let mut members = Vec::new();
+            self.at += 1;
+            self.space();
+            if self.byte(self.at) == close {
+                self.at += 1;
+            } else {
+                loop {
+                    let mut key = None;
+                    if close == b'}' {
+                        self.space();
+                        if self.byte(self.at) != b'"' {
+                            return None;
+                        }
+                        key = Some(self.string()?);
+                        self.space();
+                        if self.byte(self.at) != b':' {
+                            return None;
+                        }
+                        self.at += 1;
+                    }
+                    let item = self.value()?;
+                    match key {
+                        Some(key) => set(&mut members, &key, item),
+                        None => items.push(item),
+                    }
+                    self.space();
+                    if self.byte(self.at) == b',' {
+                        self.at += 1;
+                        continue;
+                    }
+                    if self.byte(self.at) != close {
+                        return None;
+                    }
+                    self.at += 1;
+                    break;
+                }
+            }
+            self.depth -= 1;
+            let node = if close == b'}' { Node::Object(members) } else { Node::Array(items) };
+            return Some(node.into_json());
+        }
+        if open == b'"' {
+            return Some(Node::String(self.string()?).into_json());
+        }
+        if open == b'-' || open.is_ascii_digit() {
+            return self.number();
+        }
+        for (word, node) in [("null", Node::Null), ("true", Node::Bool(true)), ("false", Node::Bool(false))] {
+            if self.text[self.at..].starts_with(word.as_bytes()) {
+                self.at += word.len();
+                return Some(node.into_json());
+            }
+        }
+        None
+    }
+
+    fn number(&mut self) -> Option<Json> {
+        let start = self.at;
+        if self.byte(self.at) == b'-' {
+            self.at += 1;
+        }
+        match self.byte(self.at) {
+            b'0' => self.at += 1,
+            b'1'..=b'9' => self.digits(),
+            _ => return None,
+        }
+        if self.byte(self.at) == b'.' {
+            self.at += 1;
+            if !self.byte(self.at).is_ascii_digit() {
+                return None;
+            }
+            self.digits();
+        }
+        if matches!(self.byte(self.at), b'e' | b'E') {
+            self.at += 1;
+            if matches!(self.byte(self.at), b'+' | b'-') {
+                self.at += 1;
+            }
+            if !self.byte(self.at).is_ascii_digit() {
+                return None;
+            }
+            self.digits();
+        }
+        let written = std::str::from_utf8(&self.text[start..self.at]).ok()?;
+        match written.parse::<f64>() {
+            Ok(number) if number.is_finite() => Some(Node::Number(number).into_json()),
+            _ => {
+                self.at = start;
+                None
+            }
+        }
+    }
+
+    /// Parse a string from its opening quote. A bad escape fails at its
+    /// backslash; `\u0000` is one, since MiniLang strings end at a NUL.
+    fn string(&mut self) -> Option<String> {
+        let mut text = Vec::new();
+        self.at += 1;
+        loop {
+            let byte = self.byte(self.at);
+            let start = self.at;
+            if byte == b'"' {
+                self.at += 1;
+                break;
+            }
+            if byte < 0x20 {
+                return None;
+            }
+            if byte != b'\\' {
+                text.push(byte);
+                self.at += 1;
+                continue;
+            }
+            let escape = self.byte(self.at + 1);
+            if let Some(index) = b"\"\\/bfnrt".iter().position(|&simple| simple == escape) {
+                text.push(b"\"\\/\x08\x0c\n\r\t"[index]);
+                self.at += 2;
+                continue;
+            }
+            if escape != b'u' {
+                return None;
+            }
+            let mut code = self.hex(self.at + 2);
+            self.at += 6;
+            if (0xD800..=0xDBFF).contains(&code) && self.byte(self.at) == b'\\' && self.byte(self.at + 1) == b'u' {
+                let low = self.hex(self.at + 2);
+                if (0xDC00..=0xDFFF).contains(&low) {
+                    code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                    self.at += 6;
+                } else {
+                    code = -1;
+                }
+            } else if (0xD800..=0xDFFF).contains(&code) {
+                code = -1;
+            }
+            match u32::try_from(code).ok().filter(|&code| code != 0).and_then(char::from_u32) {
+                Some(ch) => text.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+                None => {
+                    self.at = start;
+                    return None;
+                }
+            }
+        }
+        // Escapes make whole characters and everything else is copied from a &str
+        String::from_utf8(text).ok()
+    }
+
+    /// The four hex digits at `at`, or -1
+    fn hex(&self, at: usize) -> i64 {
+        let mut value = 0;
+        for offset in 0..4 {
+            match (self.byte(at + offset) as char).to_digit(16) {
+                Some(digit) => value = value * 16 + digit as i64,
+                None => return -1,
+            }
+        }
+        value
+    }
+}
+
+/// The compact JSON text of a value, as json_stringify gives it
+pub fn stringify(json: &Json) -> String {
+    let mut out = String::new();
+    write(&mut out, json);
+    out
+}
+
+fn write(out: &mut String, json: &Json) {
+    match &*json.borrow() {
+        Node::Null => out.push_str("null"),
+        Node::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+        Node::Number(number) => out.push_str(&format_number(*number)),
+        Node::String(text) => out.push_str(&quote(text)),
+        Node::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write(out, item);
+            }
+            out.push(']');
+        }
+        Node::Object(members) => {
+            out.push('{');
+            for (i, (key, item)) in members.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&quote(key));
+                out.push(':');
+                write(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// `text` as a JSON string, quoted and escaped
+pub fn quote(text: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            ch if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Whole numbers below 10^15 in full, anything else in the fewest `%g`
+/// digits that read back as the same double, and `null` for infinities and
+/// NaN, which JSON has no spelling for
+pub fn format_number(number: f64) -> String {
+    if !number.is_finite() {
+        return "null".to_string();
+    }
+    if number > -1e15 && number < 1e15 && number == (number as i64) as f64 {
+        return (number as i64).to_string();
+    }
+    (1..=17)
+        .map(|precision| format_g(number, precision))
+        .find(|text| text.parse::<f64>() == Ok(number))
+        .unwrap_or_else(|| format_g(number, 17))
+}
+
+/// C's `%.{precision}g`
+fn format_g(number: f64, precision: usize) -> String {
+    let scientific = format!("{:.*e}", precision - 1, number);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    if exponent < -4 || exponent >= precision as i32 {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim_zeros(mantissa), sign, exponent.abs())
+    } else {
+        trim_zeros(&format!("{:.*}", (precision as i32 - 1 - exponent) as usize, number)).to_string()
+    }
+}
+
+/// Drop the trailing zeros of a fraction, and its point if nothing is left
+fn trim_zeros(text: &str) -> &str {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text
+    }
+}
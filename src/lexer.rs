@@ -20,6 +20,12 @@ pub enum Token {
     
     #[token("display")]
     Display,
+
+    #[token("displayln")]
+    DisplayLn,
+
+    #[token("display_raw")]
+    DisplayRaw,
     
     #[token("send")]
     Send,
@@ -59,6 +65,9 @@ pub enum Token {
 
     #[token("continue")]
     Continue,
+
+    #[token("defer")]
+    Defer,
     
     // ===== TYPE KEYWORDS =====
     #[token("int")]
@@ -161,6 +170,44 @@ pub enum Token {
     
     #[token("->")]
     Arrow,
+    
+    #[token("..")]
+    DotDot,
+    
+    #[token("@")]
+    At,
+}
+
+impl Token {
+    /// The token's variant name, such as `Identifier` or `LeftParen`
+    pub fn kind(&self) -> String {
+        let debug = format!("{:?}", self);
+        match debug.find('(') {
+            Some(paren) => debug[..paren].to_string(),
+            None => debug,
+        }
+    }
+
+    /// Broad class of the token, for syntax highlighting
+    pub fn category(&self) -> &'static str {
+        match self {
+            Token::TypeInt | Token::TypeFloat | Token::TypeString | Token::TypeBool => "type",
+            Token::Integer(_) | Token::Float(_) | Token::String(_) | Token::True | Token::False => "literal",
+            Token::Identifier(_) => "identifier",
+            Token::And | Token::Or | Token::Not
+            | Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Percent
+            | Token::Assign | Token::Equal | Token::NotEqual
+            | Token::LessThan | Token::GreaterThan | Token::LessEqual | Token::GreaterEqual
+            | Token::Arrow | Token::DotDot => "operator",
+            Token::LeftParen | Token::RightParen | Token::LeftBrace | Token::RightBrace
+            | Token::LeftBracket | Token::RightBracket
+            | Token::Comma | Token::Colon | Token::Semicolon | Token::At => "punctuation",
+            Token::Let | Token::Const | Token::Func
+            | Token::Display | Token::DisplayLn | Token::DisplayRaw | Token::Send
+            | Token::If | Token::Else | Token::While | Token::Do | Token::For
+            | Token::Break | Token::Continue | Token::Defer => "keyword",
+        }
+    }
 }
 
 /// Token with its location in source
@@ -170,6 +217,16 @@ pub struct TokenWithSpan {
     pub span: std::ops::Range<usize>,
 }
 
+/// A comment in the source. Comments never reach the token stream, so they
+/// are collected separately for tools that care about them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// Covers the markers as well as the text
+    pub span: std::ops::Range<usize>,
+    /// `## ... ##` rather than `# ...`
+    pub block: bool,
+}
+
 /// The lexer structure
 pub struct Lexer {
     source: String,
@@ -203,20 +260,21 @@ impl Lexer {
                         
                         // Find closing ##
                         let mut prev_was_hash = false;
-                        while let Some((_, ch)) = chars.next() {
+                        for (_, ch) in chars.by_ref() {
                             if prev_was_hash && ch == '#' {
-                                result.push(' ');
+                                // The first `#` of the pair is already blanked
                                 result.push(' ');
                                 break;
                             }
                             
                             prev_was_hash = ch == '#';
                             
-                            // Preserve newlines for line counting
+                            // Preserve newlines for line counting, and byte
+                            // offsets so token spans index the original source
                             if ch == '\n' {
                                 result.push('\n');
                             } else {
-                                result.push(' ');
+                                result.push_str(&" ".repeat(ch.len_utf8()));
                             }
                         }
                     } else {
@@ -359,6 +417,41 @@ impl Lexer {
         processed_pos.min(self.source.len().saturating_sub(1))
     }
     
+    /// All comments in the source, in order. A `#` inside a string literal
+    /// does not start a comment, and an unclosed `##` runs to the end.
+    pub fn comments(&self) -> Vec<Comment> {
+        let bytes = self.source.as_bytes();
+        let mut comments = Vec::new();
+        let mut in_string = false;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' if in_string => i += 1,
+                b'"' => in_string = !in_string,
+                b'#' if !in_string => {
+                    let start = i;
+                    let block = bytes.get(i + 1) == Some(&b'#');
+                    let end = if block {
+                        // The closing `##` may not reuse the opening one
+                        (start + 3..bytes.len())
+                            .find(|&j| bytes[j - 1] == b'#' && bytes[j] == b'#')
+                            .map_or(bytes.len(), |j| j + 1)
+                    } else {
+                        self.source[start..].find('\n').map_or(bytes.len(), |len| start + len)
+                    };
+                    comments.push(Comment { span: start..end, block });
+                    i = end;
+                    continue;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        comments
+    }
+
     /// Get the original source for error reporting
     pub fn source(&self) -> &str {
         &self.source
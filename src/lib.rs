@@ -1,27 +1,76 @@
 // lib.rs - Module declarations and exports
+//
+// The compiler pipeline (lexing through code generation, the bytecode VM and
+// the analyses) uses std only for strings and collections. Modules that run
+// processes, touch the filesystem or talk to a terminal are part of the `cli`
+// feature, on by default, so the wasm build and other embedders can leave
+// them out with `--no-default-features`.
 
 pub mod lexer;
+pub mod line_index;
 pub mod errors;
-pub mod test_utils;
 pub mod ast;
+pub mod edition;
 pub mod parser;
 pub mod symbol_table;
 pub mod type_checker;
 pub mod codegen;
-pub mod cli;
+pub mod bytecode;
+pub mod c_mapping;
+pub mod bounds;
+pub mod escape;
+pub mod effects;
+pub mod hot_path;
+pub mod control_flow;
+pub mod peephole;
 pub mod optimizer;
 pub mod analyzer;
+pub mod plugin;
+pub mod refactor;
+pub mod xref;
+pub mod examples;
+pub mod style;
+pub mod stamp;
+pub mod banner;
+pub mod runtime;
+pub mod json;
+pub mod test_runner;
+
+#[cfg(feature = "cli")]
+pub mod test_utils;
+#[cfg(feature = "cli")]
+pub mod backend;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "cli")]
+pub mod build_log;
+#[cfg(feature = "cli")]
+pub mod toolchain;
+#[cfg(feature = "cli")]
+pub mod usage;
+#[cfg(feature = "cli")]
+pub mod reduce;
+#[cfg(feature = "cli")]
+pub mod repro;
+#[cfg(feature = "cli")]
+pub mod daemon;
+
+#[cfg(all(feature = "cli", not(target_arch = "wasm32")))]
+pub mod interrupt;
 
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
 
 // Re-export main types for easier use
-pub use errors::{CompilerError, LexerError, ParserError, SemanticError};
-pub use lexer::{Token, Lexer, TokenWithSpan};
+pub use errors::{BackendError, BytecodeError, CcDiagnostic, CompilerError, LexerError, ParserError, SemanticError};
+pub use lexer::{Comment, Token, Lexer, TokenWithSpan};
+pub use line_index::{LineCol, LineIndex};
 pub use ast::{Program, Function, Statement, Expression, Literal, Type, BinaryOp, UnaryOp, StringPart, OptimizationHint};
-pub use parser::Parser;
+pub use parser::{Parser, Defines};
+pub use edition::Version;
 pub use symbol_table::{SymbolTable, Symbol, SymbolType};
 pub use type_checker::TypeChecker;
 pub use codegen::CodeGenerator;
-pub use optimizer::{Optimizer, OptimizationStats};
+pub use optimizer::{Optimizer, OptimizationStats, OverflowMode, Pass};
+pub use plugin::CompilerPlugin;
 pub use analyzer::{AnalysisReport, FunctionMetrics, analyze_program, display_report};
\ No newline at end of file
@@ -0,0 +1,42 @@
+// src/line_index.rs - Byte offset to line/column lookup
+
+use serde::{Serialize, Deserialize};
+
+/// The start of every line in a source, for turning byte offsets into
+/// line and column numbers without rescanning the text
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+/// A 1-based line and column. Columns count characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { line_starts }
+    }
+
+    /// 1-based line containing a byte offset
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+
+    /// Line and column of a byte offset into `source`, the text the index
+    /// was built from
+    pub fn line_col(&self, source: &str, offset: usize) -> LineCol {
+        let line = self.line_of(offset);
+        let line_start = self.line_starts[line - 1];
+        let column = source.get(line_start..offset)
+            .map_or(offset - line_start, |text| text.chars().count())
+            + 1;
+        LineCol { line, column }
+    }
+}
@@ -1,96 +1,383 @@
 // src/main.rs - With subcommand support
 
 use minilang_compiler::{
-    Lexer, LexerError, 
-    Parser, ParserError, 
-    TypeChecker, SemanticError,
-    CodeGenerator,
-    Optimizer,
-    cli::{Cli, Commands},
+    Lexer, LexerError, LineCol, LineIndex,
+    Parser, ParserError, Defines, Version,
+    TypeChecker, SemanticError, errors::CompilerWarning,
+    CodeGenerator, codegen::SourceMapEntry, c_mapping,
+    bytecode::{self, BytecodeCompiler},
+    Optimizer, Pass,
+    Program, Function,
+    ast::pretty::{self, PrettyOptions},
+    cli::{self, Cli, Commands, Emit, Target, ExamplesAction, HookAction, RefactorAction, ReportAction, ReproAction, SelfAction},
     analyzer,
+    refactor,
+    reduce::{self, Check},
+    repro::{self, Failure},
+    daemon::{self, CheckOptions, CheckOutcome},
+    plugin,
+    xref,
+    examples::{self, Example},
+    build_log::BuildRecord,
+    backend::{self, GCC_FLAGS},
+    interrupt,
+    stamp::Stamp,
+    banner::Banner,
+    test_runner::{TestOutcome, TestRunner},
+    toolchain,
+    usage::{self, UsageRecord},
+    BackendError,
+    style,
 };
 use clap::Parser as ClapParser;
+use indicatif::{ProgressBar, ProgressStyle};
 use miette::{NamedSource, Report};
-use std::{fs, time::Instant};
-use std::process::{self, Command};
+use std::{fs, thread, time::{Duration, Instant}};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::process::{self, Command, Stdio};
 use std::path::{Path, PathBuf};
 
 fn main() {
     let args = Cli::parse();
+    style::init(args.color);
     
     match &args.command {
-        Commands::Compile { file, to_c } => {
-            handle_compile(file, &args, *to_c, false);
+        Commands::Compile { files, target: Target::Bytecode, .. } => {
+            let [file] = files.as_slice() else {
+                eprintln!("{} Error: --target bytecode compiles a single file; bytecode has no linker for modules", style::icon("❌"));
+                process::exit(1);
+            };
+            let output = CodeOutput { bytecode: true, ..CodeOutput::default() };
+            handle_compile(file, &args, output, None, None);
+        }
+        Commands::Compile { files, to_c, emit, dry_run, script, show_c_mapping, .. } => {
+            let emit = if *to_c { Emit::C } else { emit.unwrap_or(Emit::Exe) };
+            let dry_run = dry_run.then_some(DryRun { script: script.as_deref() });
+            if dry_run.is_some() && emit == Emit::C {
+                eprintln!("{} Error: --dry-run shows the gcc commands, but --emit c does not run gcc", style::icon("❌"));
+                process::exit(1);
+            }
+            match (files.as_slice(), emit) {
+                ([file], Emit::Exe | Emit::C) => {
+                    let output = CodeOutput { to_c_only: emit == Emit::C, show_c_mapping: *show_c_mapping, bytecode: false };
+                    handle_compile(file, &args, output, None, dry_run)
+                }
+                _ => handle_compile_modules(files, &args, emit, dry_run, *show_c_mapping),
+            }
+        }
+        Commands::Run { file, stdin, program_args } => {
+            let input = ProgramInput { args: program_args, stdin: stdin.as_deref() };
+            handle_compile(file, &args, CodeOutput::default(), Some(input), None);
+        }
+        Commands::Check { files } => {
+            handle_check(files, args.quiet, &args.defines(), args.edition());
         }
-        Commands::Run { file } => {
-            handle_compile(file, &args, false, true);
+        Commands::Test { files, filter } => {
+            handle_test(files, filter.as_deref(), &args);
         }
-        Commands::Check { file } => {
-            handle_check(file);
+        Commands::Ast { file, depth, functions, spans } => {
+            let options = PrettyOptions {
+                max_depth: *depth,
+                functions: functions.clone(),
+                spans: *spans,
+            };
+            handle_ast(file, &options, &args.defines(), args.edition());
         }
-        Commands::Ast { file } => {
-            handle_ast(file);
+        Commands::Tokens { file, json } => {
+            handle_tokens(file, *json);
         }
-        Commands::Tokens { file } => {
-            handle_tokens(file);
+        Commands::Xref { file, json } => {
+            handle_xref(file, *json, &args.defines(), args.edition());
+        }
+        Commands::Inspect { file, source, json } => {
+            handle_inspect(file, source.as_deref(), *json);
         }
         Commands::Stats { file, show_time } => {
-            handle_stats(file, *show_time);
+            handle_stats(file, *show_time, &args.defines(), args.edition());
         }
         Commands::Clean { directory, dry_run } => {
-            handle_clean(directory, *dry_run);
+            handle_clean(directory, args.out_dir.as_deref(), *dry_run);
+        }
+        Commands::Init { name } => {
+            handle_init(name);
+        }
+        Commands::Hook { action: HookAction::Install { force } } => {
+            handle_hook_install(*force);
+        }
+        Commands::Toolchain { action: SelfAction::Version { check } } => {
+            handle_self_version(*check);
+        }
+        Commands::Toolchain { action: SelfAction::Update { tag, no_verify } } => {
+            handle_self_update(tag.as_deref(), !*no_verify);
+        }
+        Commands::Examples { action } => {
+            handle_examples(action, &args);
+        }
+        Commands::Analyze { files, json, html, worst, cyclomatic, only_functions } => {
+            let format = if *json {
+                ReportFormat::Json
+            } else if *html {
+                ReportFormat::Html
+            } else {
+                ReportFormat::Text
+            };
+            handle_analyze(files, format, *worst, *cyclomatic, only_functions, &args.defines(), args.edition());
+        }
+        Commands::Refactor { action: RefactorAction::EarlyReturn { file, write } } => {
+            handle_refactor_early_return(file, *write, &args.defines(), args.edition());
         }
-        Commands::Analyze { file, json } => {
-            handle_analyze(file, *json);
+        Commands::Report { action: ReportAction::Usage { paths, json, top } } => {
+            handle_report_usage(paths, *json, *top);
+        }
+        Commands::Reduce { file, check, timeout } => {
+            let check = Check { command: check.clone(), timeout: Duration::from_secs(*timeout) };
+            handle_reduce(file, &check, args.output.as_deref(), args.quiet);
+        }
+        Commands::Vm { file, watch, virtual_clock } => {
+            handle_vm(file, watch, *virtual_clock);
+        }
+        Commands::Repro { action: ReproAction::Save { file, failure, corpus, name } } => {
+            handle_repro_save(file, *failure, corpus, name.as_deref(), args.quiet);
+        }
+        Commands::Daemon { socket, stop, status } => {
+            handle_daemon(socket.as_deref(), *stop, *status);
         }
     }
 }
 
-fn handle_compile(file: &PathBuf, args: &Cli, to_c_only: bool, should_run: bool) {
+/// What `run` hands to the compiled program
+#[derive(Default)]
+struct ProgramInput<'a> {
+    args: &'a [String],
+    /// Read instead of inheriting minilang's stdin
+    stdin: Option<&'a Path>,
+}
+
+/// `compile --dry-run`: write the C but only show the gcc commands
+struct DryRun<'a> {
+    /// Also save the commands here as a shell script
+    script: Option<&'a Path>,
+}
+
+/// What a single-file compile does with the C it generates
+#[derive(Default, Clone, Copy)]
+struct CodeOutput {
+    /// Stop after writing the C
+    to_c_only: bool,
+    /// Print each statement next to its C
+    show_c_mapping: bool,
+    /// Write a bytecode file for `minilang vm` instead of C
+    bytecode: bool,
+}
+
+fn handle_compile(file: &Path, args: &Cli, output: CodeOutput, run: Option<ProgramInput>, dry_run: Option<DryRun>) {
+    let source = read_source(file);
+    let filename = file.to_str().unwrap_or("unknown.mini");
+    
+    compile_source(&source, filename, file, args, output, run, dry_run);
+}
+
+/// `compile --show-c-mapping`: each statement of `filename` next to its C
+fn print_c_mapping(source: &str, filename: &str, c_code: &str, map: &[SourceMapEntry]) {
+    let statements = c_mapping::map_statements(c_code, map);
+    println!("\n{} {}", style::icon("📋"), filename);
+    println!("{}", style::icon("─").repeat(60));
+    print!("{}", c_mapping::render(source, &statements));
+}
+
+/// Read a source file named on the command line, exiting if it can't be read
+fn read_source(file: &Path) -> String {
     if !file.exists() {
-        eprintln!("❌ Error: File '{}' not found", file.display());
+        eprintln!("{} Error: File '{}' not found", style::icon("❌"), file.display());
         process::exit(1);
     }
     
-    let source = match fs::read_to_string(file) {
+    match fs::read_to_string(file) {
         Ok(content) => content,
         Err(e) => {
-            eprintln!("❌ Error reading file: {}", e);
+            eprintln!("{} Error reading file: {}", style::icon("❌"), e);
             process::exit(1);
         }
+    }
+}
+
+/// Directory that receives build artifacts: --out-dir, or next to the source
+fn artifact_dir(file: &Path, out_dir: &Option<PathBuf>) -> PathBuf {
+    match out_dir {
+        Some(dir) => dir.clone(),
+        None => file.parent().unwrap_or(Path::new(".")).to_path_buf(),
+    }
+}
+
+fn determine_output_path(file: &Path, out_dir: &Option<PathBuf>, custom_name: &Option<String>) -> PathBuf {
+    let dir = artifact_dir(file, out_dir);
+    
+    if let Some(ref name) = custom_name {
+        dir.join(name)
+    } else {
+        dir.join(file.file_stem().unwrap_or(file.as_os_str()))
+    }
+}
+
+fn handle_check(inputs: &[PathBuf], quiet: bool, defines: &Defines, edition: Version) {
+    let files = cli::expand_inputs(inputs);
+    
+    // A single plain file gets the detailed stage-by-stage report
+    if inputs.len() == 1 && files.len() == 1 && files[0] == inputs[0] {
+        handle_check_file(&files[0], defines, edition);
+        return;
+    }
+    
+    if files.is_empty() {
+        eprintln!("{} Error: No .mini files matched", style::icon("❌"));
+        process::exit(1);
+    }
+    
+    let progress = if quiet || !std::io::stdout().is_terminal() {
+        ProgressBar::hidden()
+    } else {
+        let bar = ProgressBar::new(files.len() as u64);
+        let mut bar_style = ProgressStyle::with_template("{spinner} [{bar:30}] {pos}/{len} {wide_msg}")
+            .unwrap()
+            .progress_chars("=> ");
+        if !style::current().unicode {
+            bar_style = bar_style.tick_chars("|/-\\ ");
+        }
+        bar.set_style(bar_style);
+        bar
     };
     
-    let filename = file.to_str().unwrap_or("unknown.mini");
+    let results = check_files_parallel(&files, &progress, defines, edition);
+    progress.finish_and_clear();
     
-    compile_source(&source, filename, file, args, to_c_only, should_run);
+    for (file, result) in files.iter().zip(&results) {
+        if !result.diagnostics.is_empty() {
+            eprintln!("{}", file.display());
+            eprintln!("{}", result.diagnostics);
+        }
+    }
+    
+    if !quiet {
+        let width = files.iter()
+            .map(|file| file.display().to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+        
+        println!("{:<width$}  {:<12} {:>6} {:>8} {:>9}", "File", "Result", "Errors", "Warnings", "Time");
+        println!("{}", "=".repeat(width + 40));
+        for (file, result) in files.iter().zip(&results) {
+            println!("{:<width$}  {:<12} {:>6} {:>8} {:>7.1}ms",
+                file.display(),
+                result.status,
+                result.errors,
+                result.warnings,
+                result.elapsed.as_secs_f64() * 1000.0,
+            );
+        }
+        println!();
+    }
+    
+    let failed = results.iter().filter(|result| result.errors > 0).count();
+    if failed == 0 {
+        println!("{} {} file(s) checked, all passed", style::icon("✅"), files.len());
+    } else {
+        println!("{} {} file(s) checked, {} failed", style::icon("❌"), files.len(), failed);
+        process::exit(1);
+    }
+}
+
+/// Outcome of checking one file in a multi-file run
+struct FileCheck {
+    status: String,
+    errors: usize,
+    warnings: usize,
+    elapsed: Duration,
+    diagnostics: String,
 }
 
-fn determine_output_path(file: &PathBuf, custom_name: &Option<String>) -> PathBuf {
-    let source_dir = file.parent().unwrap_or(Path::new("."));
+/// Check files on a pool of worker threads, returning results in input order.
+/// Each finished file advances `progress` and logs its status above the bar.
+/// With a daemon running, each worker hands its files to it instead.
+fn check_files_parallel(files: &[PathBuf], progress: &ProgressBar, defines: &Defines, edition: Version) -> Vec<FileCheck> {
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(files.len());
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<FileCheck>>> = files.iter().map(|_| Mutex::new(None)).collect();
+    let socket = daemon::client_socket(Path::new("."));
+    
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                let mut client = socket.as_deref().and_then(daemon::Client::connect);
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(index) else {
+                        break;
+                    };
+                    progress.set_message(file.display().to_string());
+                    let result = check_file_quiet(file, defines, edition, client.as_mut());
+                    let mark = if result.errors > 0 { style::icon("❌") } else { style::icon("✅") };
+                    progress.println(format!("{} {}", mark, file.display()));
+                    progress.inc(1);
+                    *results[index].lock().unwrap() = Some(result);
+                }
+            });
+        }
+    });
     
-    if let Some(ref name) = custom_name {
-        source_dir.join(name)
-    } else {
-        file.with_extension("")
+    results
+        .into_iter()
+        .map(|slot| slot.into_inner().unwrap().expect("every file is checked"))
+        .collect()
+}
+
+/// Run the front end on one file, rendering diagnostics instead of printing
+/// them, through the daemon when one is connected
+fn check_file_quiet(file: &Path, defines: &Defines, edition: Version, client: Option<&mut daemon::Client>) -> FileCheck {
+    let start = Instant::now();
+    let filename = file.to_str().unwrap_or("unknown.mini");
+    let options = CheckOptions::new(defines, edition, style::current());
+    let outcome = client
+        .and_then(|client| client.check(file, filename, &options))
+        .unwrap_or_else(|| daemon::check_file(file, filename, &options));
+    FileCheck {
+        status: outcome.status,
+        errors: outcome.errors,
+        warnings: outcome.warnings,
+        elapsed: start.elapsed(),
+        diagnostics: outcome.diagnostics,
     }
 }
 
-fn handle_check(file: &PathBuf) {
+fn handle_check_file(file: &PathBuf, defines: &Defines, edition: Version) {
     if !file.exists() {
-        eprintln!("❌ Error: File '{}' not found", file.display());
+        eprintln!("{} Error: File '{}' not found", style::icon("❌"), file.display());
         process::exit(1);
     }
     
+    let filename = file.to_str().unwrap_or("unknown.mini");
+    let options = CheckOptions::new(defines, edition, style::current());
+    let from_daemon = daemon::client_socket(Path::new("."))
+        .and_then(|socket| daemon::Client::connect(&socket))
+        .and_then(|mut client| client.check(file, filename, &options));
+    if let Some(outcome) = from_daemon {
+        report_check_outcome(file, &outcome);
+        return;
+    }
+    
     let source = match fs::read_to_string(file) {
         Ok(content) => content,
         Err(e) => {
-            eprintln!("❌ Error reading file: {}", e);
+            eprintln!("{} Error reading file: {}", style::icon("❌"), e);
             process::exit(1);
         }
     };
     
-    let filename = file.to_str().unwrap_or("unknown.mini");
-    
     println!("Checking: {}", file.display());
     println!("{}", "=".repeat(50));
     
@@ -98,63 +385,232 @@ fn handle_check(file: &PathBuf) {
     let mut lexer = Lexer::new(&source);
     let tokens = match lexer.tokenize() {
         Ok(tokens) => {
-            println!("✅");
+            println!("{}", style::icon("✅"));
             tokens
         }
         Err(e) => {
-            println!("❌");
+            println!("{}", style::icon("❌"));
             display_beautiful_error_lexer(e, &source, filename);
             process::exit(1);
         }
     };
     
     print!("Parser.......... ");
-    let mut parser = Parser::new(tokens, source.to_string());
+    let mut parser = Parser::new(tokens, source.to_string()).with_defines(defines.clone()).with_edition(edition);
     let program = match parser.parse_program() {
         Ok(prog) => {
-            println!("✅");
+            println!("{}", style::icon("✅"));
             prog
         }
         Err(e) => {
-            println!("❌");
+            println!("{}", style::icon("❌"));
             display_beautiful_error_parser(e, &source, filename);
             process::exit(1);
         }
     };
     
     print!("Type Checker.... ");
-    let mut type_checker = TypeChecker::new();
+    let mut type_checker = TypeChecker::new().with_comments(&lexer.comments());
     match type_checker.check_program(&program) {
         Ok(()) => {
-            println!("✅");
+            println!("{}", style::icon("✅"));
             let warnings = type_checker.get_warnings();
             if !warnings.is_empty() {
-                println!("\n⚠️  {} warning(s) found:", warnings.len());
+                println!("\n{}  {} warning(s) found:", style::icon("⚠️"), warnings.len());
                 for warning in warnings {
                     warning.display(&source, filename);
                 }
             }
         }
         Err(errors) => {
-            println!("❌");
+            println!("{}", style::icon("❌"));
             display_beautiful_error_semantic(errors, &source, filename);
             process::exit(1);
         }
     }
     
-    println!("\n✅ All checks passed! No errors found.");
+    println!("\n{} All checks passed! No errors found.", style::icon("✅"));
+}
+
+/// The report of `handle_check_file` for a file the daemon checked
+fn report_check_outcome(file: &Path, outcome: &CheckOutcome) {
+    if outcome.status == "unreadable" {
+        eprintln!("{}", outcome.diagnostics);
+        process::exit(1);
+    }
+    
+    println!("Checking: {}", file.display());
+    println!("{}", "=".repeat(50));
+    
+    let failed_stage = match outcome.status.as_str() {
+        "lexer error" => Some(0),
+        "parse error" => Some(1),
+        "type error" => Some(2),
+        _ => None,
+    };
+    for (stage, label) in ["Lexer........... ", "Parser.......... ", "Type Checker.... "].into_iter().enumerate() {
+        if Some(stage) == failed_stage {
+            println!("{}{}", label, style::icon("❌"));
+            // Type errors end with a blank line, as display_beautiful_error_semantic prints them
+            let gap = if stage == 2 { "\n" } else { "" };
+            eprintln!("{}{}", outcome.diagnostics, gap);
+            process::exit(1);
+        }
+        println!("{}{}", label, style::icon("✅"));
+    }
+    
+    if outcome.warnings > 0 {
+        println!("\n{}  {} warning(s) found:", style::icon("⚠️"), outcome.warnings);
+        eprintln!("{}", outcome.warning_diagnostics);
+    }
+    println!("\n{} All checks passed! No errors found.", style::icon("✅"));
+}
+
+/// `minilang daemon`: serve requests until stopped, or with --stop or
+/// --status talk to the daemon already running
+fn handle_daemon(socket: Option<&Path>, stop: bool, status: bool) {
+    let socket = socket
+        .map(Path::to_path_buf)
+        .or_else(|| daemon::client_socket(Path::new(".")))
+        .unwrap_or_else(|| PathBuf::from(daemon::SOCKET));
+    
+    if stop || status {
+        let Some(mut client) = daemon::Client::connect(&socket) else {
+            eprintln!("{} Error: No daemon is listening on {}", style::icon("❌"), socket.display());
+            process::exit(1);
+        };
+        let request = if stop { daemon::Request::Stop } else { daemon::Request::Status };
+        match client.request(&request) {
+            Ok(daemon::Response::Stopping) => println!("Daemon on {} stopped", socket.display()),
+            Ok(daemon::Response::Status { files, hits, misses }) => {
+                println!("Daemon on {}", socket.display());
+                println!("   Files in memory: {}", files);
+                println!("   Answered from memory: {}", hits);
+                println!("   Checked afresh: {}", misses);
+            }
+            Ok(other) => {
+                eprintln!("{} Error: Unexpected answer from the daemon: {:?}", style::icon("❌"), other);
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("{} Error: The daemon did not answer: {}", style::icon("❌"), e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    
+    let served = daemon::serve(&socket, || {
+        println!("Daemon listening on {} (stop it with `minilang daemon --stop`)", socket.display());
+    });
+    if let Err(e) = served {
+        eprintln!("{} Error: {}", style::icon("❌"), e);
+        process::exit(1);
+    }
+}
+
+/// `minilang test`: run the test blocks of every file, exiting with 1
+/// unless all of them compile and pass
+fn handle_test(inputs: &[PathBuf], filter: Option<&str>, args: &Cli) {
+    let files = cli::expand_inputs(inputs);
+    if files.is_empty() {
+        eprintln!("{} Error: No .mini files matched", style::icon("❌"));
+        process::exit(1);
+    }
+    
+    let mut passed = 0;
+    let mut failures: Vec<TestOutcome> = Vec::new();
+    let mut broken_files = 0;
+    for file in &files {
+        let source = read_source(file);
+        let filename = file.to_str().unwrap_or("unknown.mini");
+        let Some((program, names)) = check_tests(&source, filename, args) else {
+            broken_files += 1;
+            continue;
+        };
+        
+        let runner = TestRunner::new()
+            .with_source(filename, &source)
+            .with_optimization(args.optimization)
+            .with_overflow(args.overflow);
+        for name in names.iter().filter(|name| filter.is_none_or(|text| name.contains(text))) {
+            print!("test {} \"{}\" ... ", file.display(), name);
+            match runner.run(&program, name) {
+                Ok(outcome) if outcome.passed() => {
+                    println!("{}", style::paint("ok", style::Color::Green));
+                    passed += 1;
+                }
+                Ok(outcome) => {
+                    println!("{}", style::paint("FAILED", style::Color::Red));
+                    failures.push(outcome);
+                }
+                Err(e) => {
+                    println!("{}", style::paint("FAILED", style::Color::Red));
+                    eprintln!("{} Could not compile the test: {}", style::icon("❌"), e);
+                    broken_files += 1;
+                }
+            }
+        }
+    }
+    
+    for failure in &failures {
+        println!("\n---- \"{}\" ----", failure.name);
+        print!("{}", failure.output);
+        if let Some(trap) = &failure.failure {
+            println!("{}", trap);
+        }
+    }
+    
+    println!();
+    if failures.is_empty() && broken_files == 0 {
+        println!("{} {} test(s) passed", style::icon("✅"), passed);
+    } else {
+        println!("{} {} test(s) passed, {} failed", style::icon("❌"), passed, failures.len());
+        if broken_files > 0 {
+            println!("   {} file(s) or test(s) did not compile", broken_files);
+        }
+        process::exit(1);
+    }
+}
+
+/// Parse and type check a file with its test blocks included as functions,
+/// showing any errors. Returns the program and its test names in source order.
+fn check_tests(source: &str, filename: &str, args: &Cli) -> Option<(Program, Vec<String>)> {
+    let mut lexer = Lexer::new(source);
+    let tokens = match lexer.tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            display_beautiful_error_lexer(e, source, filename);
+            return None;
+        }
+    };
+    let mut program = match Parser::new(tokens, source.to_string()).with_defines(args.defines()).with_edition(args.edition()).parse_program() {
+        Ok(program) => program,
+        Err(e) => {
+            display_beautiful_error_parser(e, source, filename);
+            return None;
+        }
+    };
+    let names: Vec<String> = program.tests.iter().map(|test| test.name.clone()).collect();
+    program.include_tests();
+    
+    if let Err(errors) = TypeChecker::new().with_comments(&lexer.comments()).check_program(&program) {
+        display_beautiful_error_semantic(errors, source, filename);
+        return None;
+    }
+    Some((program, names))
 }
 
-fn handle_ast(file: &PathBuf) {
+fn handle_ast(file: &PathBuf, options: &PrettyOptions, defines: &Defines, edition: Version) {
     if !file.exists() {
-        eprintln!("❌ Error: File '{}' not found", file.display());
+        eprintln!("{} Error: File '{}' not found", style::icon("❌"), file.display());
         process::exit(1);
     }
     
     let source = match fs::read_to_string(file) {
         Ok(content) => content,
         Err(e) => {
-            eprintln!("❌ Error reading file: {}", e);
+            eprintln!("{} Error reading file: {}", style::icon("❌"), e);
             process::exit(1);
         }
     };
@@ -170,7 +626,7 @@ fn handle_ast(file: &PathBuf) {
         }
     };
     
-    let mut parser = Parser::new(tokens, source.to_string());
+    let mut parser = Parser::new(tokens, source.to_string()).with_defines(defines.clone()).with_edition(edition);
     let program = match parser.parse_program() {
         Ok(prog) => prog,
         Err(e) => {
@@ -179,28 +635,35 @@ fn handle_ast(file: &PathBuf) {
         }
     };
     
-    program.display_tree();
+    for name in &options.functions {
+        if !program.functions.iter().any(|func| &func.name == name) {
+            eprintln!("{}  No function named '{}'", style::icon("⚠️"), name);
+        }
+    }
+    
+    println!("\n{}", style::icon("═").repeat(60));
+    println!("{} Abstract Syntax Tree (Visualization)", style::icon("🌳"));
+    println!("{}\n", style::icon("═").repeat(60));
+    print!("{}", pretty::render(&program, options));
+    println!("\n{}", style::icon("═").repeat(60));
 }
 
-fn handle_tokens(file: &PathBuf) {
+fn handle_tokens(file: &PathBuf, json: bool) {
     if !file.exists() {
-        eprintln!("❌ Error: File '{}' not found", file.display());
+        eprintln!("{} Error: File '{}' not found", style::icon("❌"), file.display());
         process::exit(1);
     }
     
     let source = match fs::read_to_string(file) {
         Ok(content) => content,
         Err(e) => {
-            eprintln!("❌ Error reading file: {}", e);
+            eprintln!("{} Error reading file: {}", style::icon("❌"), e);
             process::exit(1);
         }
     };
     
     let filename = file.to_str().unwrap_or("unknown.mini");
     
-    println!("Tokens for: {}", file.display());
-    println!("{}", "=".repeat(60));
-    
     let mut lexer = Lexer::new(&source);
     let tokens = match lexer.tokenize() {
         Ok(tokens) => tokens,
@@ -209,33 +672,190 @@ fn handle_tokens(file: &PathBuf) {
             process::exit(1);
         }
     };
+    let lines = LineIndex::new(&source);
+    
+    if json {
+        let entry = |kind: String, category: &'static str, span: std::ops::Range<usize>| TokenEntry {
+            kind,
+            category,
+            lexeme: source[span.clone()].to_string(),
+            start: lines.line_col(&source, span.start),
+            end: lines.line_col(&source, span.end),
+            span,
+        };
+        let dump = TokenDump {
+            file: filename.to_string(),
+            tokens: tokens.iter()
+                .map(|t| entry(t.token.kind(), t.token.category(), t.span.clone()))
+                .collect(),
+            comments: lexer.comments().into_iter()
+                .map(|c| {
+                    let kind = if c.block { "BlockComment" } else { "Comment" };
+                    entry(kind.to_string(), "comment", c.span)
+                })
+                .collect(),
+        };
+        print_json(&dump);
+        return;
+    }
     
+    println!("Tokens for: {}", file.display());
+    println!("{}", "=".repeat(60));
     println!("Total tokens: {}\n", tokens.len());
     
     for (i, token_with_span) in tokens.iter().enumerate() {
-        let line_num = source[..token_with_span.span.start]
-            .chars()
-            .filter(|&c| c == '\n')
-            .count() + 1;
+        let position = lines.line_col(&source, token_with_span.span.start);
         
-        println!("{:4} | Line {:3} | {:?}", 
+        println!("{:4} | Line {:3}, Col {:3} | {:?}", 
             i + 1, 
-            line_num, 
+            position.line, 
+            position.column,
             token_with_span.token
         );
     }
 }
 
-fn handle_stats(file: &PathBuf, show_time: bool) {
+/// `minilang xref`: every symbol with its definition and references
+fn handle_xref(file: &Path, json: bool, defines: &Defines, edition: Version) {
+    let source = read_source(file);
+    let filename = file.to_str().unwrap_or("unknown.mini");
+    
+    let tokens = match Lexer::new(&source).tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            display_beautiful_error_lexer(e, &source, filename);
+            process::exit(1);
+        }
+    };
+    let mut parser = Parser::new(tokens, source.to_string()).with_defines(defines.clone()).with_edition(edition);
+    let program = match parser.parse_program() {
+        Ok(program) => program,
+        Err(e) => {
+            display_beautiful_error_parser(e, &source, filename);
+            process::exit(1);
+        }
+    };
+    // References are only complete once every name resolves
+    let mut type_checker = TypeChecker::new();
+    if let Err(errors) = type_checker.check_program(&program) {
+        display_beautiful_error_semantic(errors, &source, filename);
+        process::exit(1);
+    }
+    
+    let entries = xref::build(&program, type_checker.symbol_table(), &source);
+    if json {
+        print_json(&XrefDump { file: filename.to_string(), symbols: entries });
+        return;
+    }
+    println!("Cross-reference for: {}", file.display());
+    println!("{}", "=".repeat(60));
+    print!("{}", xref::render(&entries));
+}
+
+/// `minilang xref --json`
+#[derive(serde::Serialize)]
+struct XrefDump {
+    file: String,
+    symbols: Vec<xref::XrefEntry>,
+}
+
+/// `minilang tokens --json`: every token and comment with its position
+#[derive(serde::Serialize)]
+struct TokenDump {
+    file: String,
+    tokens: Vec<TokenEntry>,
+    comments: Vec<TokenEntry>,
+}
+
+#[derive(serde::Serialize)]
+struct TokenEntry {
+    kind: String,
+    category: &'static str,
+    /// The source text, before escapes in strings are processed
+    lexeme: String,
+    /// Byte offsets into the source
+    span: std::ops::Range<usize>,
+    start: LineCol,
+    end: LineCol,
+}
+
+/// `minilang inspect --json`
+#[derive(serde::Serialize)]
+struct InspectReport {
+    file: String,
+    /// One per module the artifact was built from
+    stamps: Vec<Stamp>,
+    /// Whether the `--source` file is what one of them was built from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_matches: Option<bool>,
+}
+
+fn handle_inspect(file: &Path, source: Option<&Path>, json: bool) {
+    let bytes = match fs::read(file) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("{} Error reading {}: {}", style::icon("❌"), file.display(), e);
+            process::exit(1);
+        }
+    };
+    
+    let stamps = Stamp::find_all(&bytes);
+    if stamps.is_empty() {
+        eprintln!("{} No build stamp in {}", style::icon("❌"), file.display());
+        eprintln!("   Stamps are written by minilang compile into generated C and executables");
+        process::exit(1);
+    }
+    
+    let source_matches = source.map(|path| {
+        let text = read_source(path);
+        stamps.iter().any(|stamp| stamp.matches_source(&text))
+    });
+    
+    if json {
+        print_json(&InspectReport { file: file.display().to_string(), stamps, source_matches });
+    } else {
+        println!("Build stamp: {}", file.display());
+        println!("{}", "=".repeat(60));
+        for (i, stamp) in stamps.iter().enumerate() {
+            if stamps.len() > 1 {
+                println!("\nModule {} of {}", i + 1, stamps.len());
+            }
+            let current = env!("CARGO_PKG_VERSION");
+            if stamp.compiler_version == current {
+                println!("Compiler version: {}", stamp.compiler_version);
+            } else {
+                println!("Compiler version: {} (this is {})", stamp.compiler_version, current);
+            }
+            println!("Optimization:     -O{}", stamp.optimization);
+            println!("Flags:            {}", stamp.flags.join(" "));
+            println!("Source SHA-256:   {}", stamp.source_sha256);
+        }
+        
+        if let (Some(path), Some(matches)) = (source, source_matches) {
+            println!();
+            if matches {
+                println!("{} {} is the source this was built from", style::icon("✅"), path.display());
+            } else {
+                println!("{} {} has changed since this was built (or is a different file)", style::icon("❌"), path.display());
+            }
+        }
+    }
+    
+    if source_matches == Some(false) {
+        process::exit(1);
+    }
+}
+
+fn handle_stats(file: &PathBuf, show_time: bool, defines: &Defines, edition: Version) {
     if !file.exists() {
-        eprintln!("❌ Error: File '{}' not found", file.display());
+        eprintln!("{} Error: File '{}' not found", style::icon("❌"), file.display());
         process::exit(1);
     }
     
     let source = match fs::read_to_string(file) {
         Ok(content) => content,
         Err(e) => {
-            eprintln!("❌ Error reading file: {}", e);
+            eprintln!("{} Error reading file: {}", style::icon("❌"), e);
             process::exit(1);
         }
     };
@@ -269,6 +889,7 @@ fn handle_stats(file: &PathBuf, show_time: bool) {
         minilang_compiler::Token::Let | minilang_compiler::Token::Func |
         minilang_compiler::Token::If | minilang_compiler::Token::While |
         minilang_compiler::Token::For | minilang_compiler::Token::Display |
+        minilang_compiler::Token::DisplayLn | minilang_compiler::Token::DisplayRaw |
         minilang_compiler::Token::Send | minilang_compiler::Token::Do
     )).count();
     
@@ -291,7 +912,7 @@ fn handle_stats(file: &PathBuf, show_time: bool) {
     println!("   Literals: {}", literals);
     
     let start = Instant::now();
-    let mut parser = Parser::new(tokens, source.to_string());
+    let mut parser = Parser::new(tokens, source.to_string()).with_defines(defines.clone()).with_edition(edition);
     let program = match parser.parse_program() {
         Ok(prog) => prog,
         Err(e) => {
@@ -310,19 +931,25 @@ fn handle_stats(file: &PathBuf, show_time: bool) {
     println!("   Total statements: {}", total_stmts);
     
     for func in &program.functions {
-        println!("   • {} ({} params, {} statements)", 
+        println!("   {} {} ({} params, {} statements)", style::icon("•"), 
             func.name, 
             func.params.len(),
             func.body.statements.len()
         );
     }
     
+    println!("\nEstimated Operations (n = input size):");
+    for estimate in analyzer::cost::calculate_all(&program) {
+        println!("   {} {}: {}  {}", style::icon("•"), estimate.name, estimate.cost, estimate.big_o());
+    }
+    println!("   Heuristic only: loops bounded by a parameter are assumed to run n times");
+    
     let start = Instant::now();
-    let mut type_checker = TypeChecker::new();
+    let mut type_checker = TypeChecker::new().with_comments(&lexer.comments());
     let type_check_result = type_checker.check_program(&program);
     let type_time = start.elapsed();
     
-    println!("\n✅ Type Checking:");
+    println!("\n{} Type Checking:", style::icon("✅"));
     match type_check_result {
         Ok(()) => {
             println!("   Status: Passed");
@@ -355,25 +982,29 @@ fn handle_stats(file: &PathBuf, show_time: bool) {
     }
 }
 
-fn handle_clean(directory: &PathBuf, dry_run: bool) {
+/// Remove C files and executables built from the sources in `directory`,
+/// looking in `out_dir` as well when artifacts were sent there
+fn handle_clean(directory: &Path, out_dir: Option<&Path>, dry_run: bool) {
     println!("Cleaning generated files in: {}", directory.display());
     println!("{}", "=".repeat(60));
     
     if !directory.exists() {
-        eprintln!("❌ Error: Directory '{}' not found", directory.display());
+        eprintln!("{} Error: Directory '{}' not found", style::icon("❌"), directory.display());
         process::exit(1);
     }
     
     let mut files_to_delete = Vec::new();
     let mut total_size = 0u64;
     
-    if let Ok(entries) = fs::read_dir(directory) {
+    let scan_dirs = std::iter::once(directory).chain(out_dir);
+    for entries in scan_dirs.filter_map(|dir| fs::read_dir(dir).ok()) {
         for entry in entries.flatten() {
             let path = entry.path();
             
-            if path.is_file() {
+            if path.is_file() && !files_to_delete.contains(&path) {
                 let should_delete = 
                     path.extension() == Some(std::ffi::OsStr::new("c")) ||
+                    path.extension() == Some(std::ffi::OsStr::new(bytecode::EXTENSION)) ||
                     (path.extension().is_none() && 
                      path.file_stem().is_some() &&
                      directory.join(format!("{}.mini", 
@@ -390,7 +1021,7 @@ fn handle_clean(directory: &PathBuf, dry_run: bool) {
     }
     
     if files_to_delete.is_empty() {
-        println!("✅ No generated files found. Directory is clean!");
+        println!("{} No generated files found. Directory is clean!", style::icon("✅"));
         return;
     }
     
@@ -399,7 +1030,7 @@ fn handle_clean(directory: &PathBuf, dry_run: bool) {
         total_size / 1024);
     
     for file in &files_to_delete {
-        println!("   • {}", file.display());
+        println!("   {} {}", style::icon("•"), file.display());
     }
     
     if dry_run {
@@ -413,84 +1044,573 @@ fn handle_clean(directory: &PathBuf, dry_run: bool) {
             match fs::remove_file(&file) {
                 Ok(()) => {
                     deleted += 1;
-                    println!("   ✅ Deleted: {}", file.display());
+                    println!("   {} Deleted: {}", style::icon("✅"), file.display());
                 }
                 Err(e) => {
-                    println!("   ❌ Failed to delete {}: {}", file.display(), e);
+                    println!("   {} Failed to delete {}: {}", style::icon("❌"), file.display(), e);
                 }
             }
         }
         
-        println!("\n✅ Cleaned {} file(s)", deleted);
+        println!("\n{} Cleaned {} file(s)", style::icon("✅"), deleted);
     }
 }
 
-fn handle_analyze(file: &PathBuf, json_output: bool) {
-    if !file.exists() {
-        eprintln!("❌ Error: File '{}' not found", file.display());
-        process::exit(1);
-    }
-
-    let source = match fs::read_to_string(file) {
-        Ok(content) => content,
+fn handle_init(name: &str) {
+    let dir = Path::new(name);
+    
+    match cli::init_project(dir) {
+        Ok(files) => {
+            println!("{} Created project '{}'", style::icon("✅"), dir.display());
+            for file in &files {
+                println!("   {} {}", style::icon("•"), file.display());
+            }
+            println!("\nGet started with:");
+            println!("   cd {}", dir.display());
+            println!("   minilang run main.mini");
+        }
         Err(e) => {
-            eprintln!("❌ Error reading file: {}", e);
+            eprintln!("{} Error: {}", style::icon("❌"), e);
             process::exit(1);
         }
-    };
-
-    let filename = file.to_str().unwrap_or("unknown.mini");
+    }
+}
 
-    let mut lexer = Lexer::new(&source);
-    let tokens = match lexer.tokenize() {
-        Ok(tokens) => tokens,
+fn handle_hook_install(force: bool) {
+    // Ask git so worktrees and core.hooksPath are respected
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output();
+    
+    let hooks_dir = match output {
+        Ok(output) if output.status.success() => {
+            PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())
+        }
+        Ok(_) => {
+            eprintln!("{} Error: Not inside a git repository", style::icon("❌"));
+            process::exit(1);
+        }
         Err(e) => {
-            display_beautiful_error_lexer(e, &source, filename);
+            eprintln!("{} Error: Could not run git: {}", style::icon("❌"), e);
             process::exit(1);
         }
     };
-
-    let mut parser = Parser::new(tokens, source.to_string());
-    let program = match parser.parse_program() {
-        Ok(prog) => prog,
+    
+    match cli::install_pre_commit_hook(&hooks_dir, force) {
+        Ok(path) => {
+            println!("{} Installed pre-commit hook: {}", style::icon("✅"), path.display());
+            println!("   Staged .mini files will be checked before each commit");
+        }
         Err(e) => {
-            display_beautiful_error_parser(e, &source, filename);
+            eprintln!("{} Error: {}", style::icon("❌"), e);
             process::exit(1);
         }
-    };
-
-    let mut type_checker = TypeChecker::new();
-    if let Err(errors) = type_checker.check_program(&program) {
-        eprintln!("⚠️  Type checking found {} error(s):", errors.len());
-        display_beautiful_error_semantic(errors, &source, filename);
-        eprintln!("Proceeding with analysis anyway...\n");
     }
+}
+
+fn handle_self_version(check: bool) {
+    println!("minilang {}", toolchain::VERSION);
+    println!("Commit: {}", toolchain::GIT_HASH);
+    println!("Target: {}", toolchain::TARGET);
+    let plugins = plugin::installed();
+    if !plugins.is_empty() {
+        let names: Vec<String> = plugins.iter()
+            .map(|plugin| format!("{} (API {})", plugin.name(), plugin.api_version()))
+            .collect();
+        println!("Plugins: {}", names.join(", "));
+    }
+    for (name, version) in plugin::rejected() {
+        println!("{} Plugin '{}' needs plugin API {}, but this compiler provides {}; it is not used",
+            style::icon("⚠️"), name, version, plugin::API_VERSION);
+    }
+    if !check {
+        return;
+    }
+    
+    match toolchain::fetch_release(None) {
+        Ok(release) if toolchain::is_newer(release.version(), toolchain::VERSION) => {
+            println!("\n{} {} is available; update with: minilang self update", style::icon("⚠️"), release.tag);
+            process::exit(1);
+        }
+        Ok(release) => {
+            println!("\n{} Up to date (latest release: {})", style::icon("✅"), release.tag);
+        }
+        Err(e) => {
+            eprintln!("{} Error: Could not check for updates: {}", style::icon("❌"), e);
+            process::exit(1);
+        }
+    }
+}
 
-    let report = analyzer::analyze_program(&program, &source);
+fn handle_self_update(tag: Option<&str>, verify: bool) {
+    let fail = |message: String| -> ! {
+        eprintln!("{} Error: {}", style::icon("❌"), message);
+        process::exit(1);
+    };
+    
+    let release = toolchain::fetch_release(tag).unwrap_or_else(|e| fail(e));
+    if tag.is_none() && !toolchain::is_newer(release.version(), toolchain::VERSION) {
+        println!("{} Already up to date (minilang {})", style::icon("✅"), toolchain::VERSION);
+        return;
+    }
+    let Some(asset) = release.binary_for(toolchain::TARGET) else {
+        let names: Vec<&str> = release.assets.iter().map(|asset| asset.name.as_str()).collect();
+        fail(format!(
+            "Release {} has no build for {} (it has: {})",
+            release.tag,
+            toolchain::TARGET,
+            if names.is_empty() { "nothing".to_string() } else { names.join(", ") }
+        ));
+    };
+    let exe = std::env::current_exe().unwrap_or_else(|e| fail(format!("Cannot find this executable: {}", e)));
+    
+    println!("Downloading {} ({})...", asset.name, release.tag);
+    let bytes = toolchain::download_binary(&release, asset, verify).unwrap_or_else(|e| fail(e));
+    toolchain::replace_executable(&exe, &bytes).unwrap_or_else(|e| fail(e));
+    println!("{} Updated {} from {} to {}", style::icon("✅"), exe.display(), toolchain::VERSION, release.version());
+}
 
-    if json_output {
-        match serde_json::to_string_pretty(&report) {
-            Ok(json) => println!("{}", json),
-            Err(e) => {
-                eprintln!("❌ Failed to serialize report: {}", e);
+fn handle_examples(action: &ExamplesAction, args: &Cli) {
+    let lookup = |name: &str| -> &'static Example {
+        examples::find_example(name).unwrap_or_else(|| {
+            eprintln!("{} Error: No example named '{}'", style::icon("❌"), name);
+            eprintln!("   Run 'minilang examples list' to see the available examples");
+            process::exit(1);
+        })
+    };
+    
+    match action {
+        ExamplesAction::List => {
+            println!("Available examples:");
+            println!("{}", "=".repeat(60));
+            let width = examples::EXAMPLES.iter().map(|example| example.name.len()).max().unwrap_or(0);
+            for example in examples::EXAMPLES {
+                println!("   {:<width$}  {}", example.name, example.description);
+            }
+            println!("
+Run one with: minilang examples run <name>");
+        }
+        ExamplesAction::Show { name } => {
+            let source = lookup(name).source;
+            print!("{}", source);
+            if !source.ends_with('\n') {
+                println!();
+            }
+        }
+        ExamplesAction::Run { name } => {
+            let example = lookup(name);
+            
+            // The normal pipeline works on files, so stage the source in a temp directory
+            let dir = std::env::temp_dir().join("minilang-examples");
+            let file = dir.join(format!("{}.mini", example.name));
+            if let Err(e) = fs::create_dir_all(&dir).and_then(|_| fs::write(&file, example.source)) {
+                eprintln!("{} Error writing example: {}", style::icon("❌"), e);
                 process::exit(1);
             }
+            
+            let filename = format!("{}.mini", example.name);
+            compile_source(example.source, &filename, &file, args, CodeOutput::default(), Some(ProgramInput::default()), None);
+        }
+    }
+}
+
+/// How `analyze` prints its report
+#[derive(Clone, Copy, PartialEq)]
+enum ReportFormat {
+    Text,
+    Json,
+    Html,
+}
+
+fn handle_analyze(inputs: &[PathBuf], format: ReportFormat, worst: usize, mode: analyzer::CyclomaticMode, only_functions: &[String], defines: &Defines, edition: Version) {
+    let files = cli::expand_inputs(inputs);
+
+    // A single plain file gets the function-by-function report
+    if inputs.len() == 1 && files.len() == 1 && files[0] == inputs[0] {
+        handle_analyze_file(&files[0], format, worst, mode, only_functions, defines, edition);
+        return;
+    }
+
+    if files.is_empty() {
+        eprintln!("{} Error: No .mini files matched", style::icon("❌"));
+        process::exit(1);
+    }
+
+    let mut reports = Vec::new();
+    let mut failed = 0;
+    for file in &files {
+        match analyze_file(file, mode, defines, edition) {
+            Some(report) => reports.push(analyzer::FileReport { path: file.display().to_string(), report }),
+            None => failed += 1,
+        }
+    }
+
+    if !only_functions.is_empty() {
+        let mut unknown: Vec<String> = only_functions.to_vec();
+        for file in &mut reports {
+            let missing = file.report.retain_functions(only_functions);
+            unknown.retain(|name| missing.contains(name));
         }
+        if !unknown.is_empty() {
+            eprintln!("{} Error: No function named {} in any of the {} files", style::icon("❌"), unknown.join(", "), files.len());
+            process::exit(1);
+        }
+        reports.retain(|file| !file.report.functions.is_empty());
+    }
+
+    let report = analyzer::ProjectReport::new(reports, worst);
+    match format {
+        ReportFormat::Json => print_json(&report),
+        ReportFormat::Html => print!("{}", analyzer::html::render(&report)),
+        ReportFormat::Text => {
+            println!("Analyzing: {} files", report.files.len());
+            analyzer::display_project_report(&report);
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{} {} of {} files could not be analyzed", style::icon("❌"), failed, files.len());
+        process::exit(1);
+    }
+}
+
+fn handle_analyze_file(file: &Path, format: ReportFormat, worst: usize, mode: analyzer::CyclomaticMode, only_functions: &[String], defines: &Defines, edition: Version) {
+    let Some(mut report) = analyze_file(file, mode, defines, edition) else { process::exit(1) };
+
+    if !only_functions.is_empty() {
+        let available: Vec<String> = report.functions.iter().map(|f| f.name.clone()).collect();
+        let unknown = report.retain_functions(only_functions);
+        if !unknown.is_empty() {
+            eprintln!("{} Error: No function named {} in {}", style::icon("❌"), unknown.join(", "), file.display());
+            eprintln!("   Functions: {}", available.join(", "));
+            process::exit(1);
+        }
+    }
+
+    match format {
+        ReportFormat::Json => print_json(&report),
+        ReportFormat::Html => {
+            let file_report = analyzer::FileReport { path: file.display().to_string(), report };
+            print!("{}", analyzer::html::render(&analyzer::ProjectReport::new(vec![file_report], worst)));
+        }
+        ReportFormat::Text => {
+            println!("Analyzing: {}", file.display());
+            analyzer::display_report(&report);
+        }
+    }
+}
+
+/// Analyze one file, reporting problems on stderr. Type errors are shown but
+/// do not stop the analysis; a file that does not parse gives `None`.
+fn analyze_file(file: &Path, mode: analyzer::CyclomaticMode, defines: &Defines, edition: Version) -> Option<analyzer::AnalysisReport> {
+    if !file.exists() {
+        eprintln!("{} Error: File '{}' not found", style::icon("❌"), file.display());
+        return None;
+    }
+
+    let source = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("{} Error reading file: {}", style::icon("❌"), e);
+            return None;
+        }
+    };
+
+    let filename = file.to_str().unwrap_or("unknown.mini");
+
+    let mut lexer = Lexer::new(&source);
+    let tokens = match lexer.tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            display_beautiful_error_lexer(e, &source, filename);
+            return None;
+        }
+    };
+
+    let mut parser = Parser::new(tokens, source.to_string()).with_defines(defines.clone()).with_edition(edition);
+    let program = match parser.parse_program() {
+        Ok(prog) => prog,
+        Err(e) => {
+            display_beautiful_error_parser(e, &source, filename);
+            return None;
+        }
+    };
+
+    let mut type_checker = TypeChecker::new();
+    if let Err(errors) = type_checker.check_program(&program) {
+        eprintln!("{}  Type checking found {} error(s):", style::icon("⚠️"), errors.len());
+        display_beautiful_error_semantic(errors, &source, filename);
+        eprintln!("Proceeding with analysis anyway...\n");
+    }
+
+    Some(analyzer::analyze_program_with(&program, &source, mode))
+}
+
+fn print_json<T: serde::Serialize>(report: &T) {
+    match serde_json::to_string_pretty(report) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("{} Failed to serialize report: {}", style::icon("❌"), e);
+            process::exit(1);
+        }
+    }
+}
+
+fn handle_refactor_early_return(file: &Path, write: bool, defines: &Defines, edition: Version) {
+    let source = read_source(file);
+    let filename = file.to_str().unwrap_or("unknown.mini");
+
+    let tokens = match Lexer::new(&source).tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            display_beautiful_error_lexer(e, &source, filename);
+            process::exit(1);
+        }
+    };
+    let program = match Parser::new(tokens, source.clone()).with_defines(defines.clone()).with_edition(edition).parse_program() {
+        Ok(program) => program,
+        Err(e) => {
+            display_beautiful_error_parser(e, &source, filename);
+            process::exit(1);
+        }
+    };
+    // Rewrites are only kept when the result still compiles, which needs a
+    // program that compiles to begin with
+    if let Err(errors) = TypeChecker::new().check_program(&program) {
+        display_beautiful_error_semantic(errors, &source, filename);
+        eprintln!("{} Fix the errors above before refactoring", style::icon("❌"));
+        process::exit(1);
+    }
+
+    let refactoring = refactor::early_return(&source, defines);
+    if refactoring.functions.is_empty() {
+        eprintln!("No guard-style if statements to turn into early returns in {}", file.display());
+        return;
+    }
+
+    // The diff goes to stdout on its own so it can be saved as a patch
+    print!("{}", refactor::unified_diff(&source, &refactoring.source, filename));
+    if write {
+        if let Err(e) = fs::write(file, &refactoring.source) {
+            eprintln!("{} Error writing {}: {}", style::icon("❌"), file.display(), e);
+            process::exit(1);
+        }
+        eprintln!("{} Rewrote {} in {}", style::icon("✅"), refactoring.functions.join(", "), file.display());
     } else {
-        println!("Analyzing: {}", file.display());
-        analyzer::display_report(&report);
+        eprintln!("Would rewrite {}. Rerun with --write to apply", refactoring.functions.join(", "));
     }
 }
 
+fn handle_report_usage(paths: &[PathBuf], json: bool, top: usize) {
+    let records = usage::read_usage(paths).unwrap_or_else(|e| {
+        eprintln!("{} Error: {}", style::icon("❌"), e);
+        process::exit(1);
+    });
+    let mut report = usage::summarize(&records);
+    report.errors.truncate(top);
+    report.warnings.truncate(top);
+    
+    if json {
+        print_json(&report);
+        return;
+    }
+    
+    println!("Usage Report");
+    println!("{}", "=".repeat(60));
+    println!("Compiles: {} ({} succeeded, {} failed)", report.compiles, report.succeeded, report.failed);
+    if !report.failed_stages.is_empty() {
+        let stages: Vec<String> = report.failed_stages.iter()
+            .map(|stage| format!("{} {}", stage.stage, stage.compiles))
+            .collect();
+        println!("Failed in: {}", stages.join(", "));
+    }
+    
+    for (title, counts) in [("Most frequent errors", &report.errors), ("Most frequent warnings", &report.warnings)] {
+        println!("\n{}:", title);
+        if counts.is_empty() {
+            println!("   none");
+            continue;
+        }
+        println!("   {:>5}  {:>8}  code", "times", "compiles");
+        for count in counts {
+            println!("   {:>5}  {:>8}  {}", count.occurrences, count.compiles, count.code);
+        }
+    }
+}
+
+fn handle_reduce(file: &Path, check: &Check, output: Option<&str>, quiet: bool) {
+    let source = read_source(file);
+    let output = output.map(PathBuf::from).unwrap_or_else(|| {
+        let stem = file.file_stem().map_or("program".into(), |stem| stem.to_string_lossy().into_owned());
+        file.with_file_name(format!("{}.reduced.mini", stem))
+    });
+    let file_name = file.file_name().map_or("program.mini".into(), |name| name.to_string_lossy().into_owned());
+    
+    if !reduce::parses(&source) {
+        eprintln!("{} Error: {} does not parse (see `minilang check`); reduce only works on programs that do", style::icon("❌"), file.display());
+        process::exit(1);
+    }
+    let passes = |candidate: &str| check.passes(candidate, &file_name).unwrap_or_else(|e| {
+        eprintln!("{} Error: could not run the check: {}", style::icon("❌"), e);
+        process::exit(1);
+    });
+    if !passes(&source) {
+        eprintln!("{} Error: the check does not pass on {}: `{}` must exit with 0 while the failure shows", style::icon("❌"), file.display(), check.command_line(file));
+        process::exit(1);
+    }
+    
+    let progress = if quiet || !std::io::stderr().is_terminal() {
+        ProgressBar::hidden()
+    } else {
+        let mut spinner_style = ProgressStyle::with_template("{spinner} Reducing {wide_msg}").unwrap();
+        if !style::current().unicode {
+            spinner_style = spinner_style.tick_chars("|/-\\ ");
+        }
+        ProgressBar::new_spinner().with_style(spinner_style)
+    };
+    let reduction = reduce::reduce(&source, |candidate| {
+        progress.set_message(format!("{} ({} lines)", file.display(), candidate.lines().count()));
+        progress.tick();
+        passes(candidate)
+    });
+    progress.finish_and_clear();
+    
+    if let Err(e) = fs::write(&output, &reduction.source) {
+        eprintln!("{} Error: could not write {}: {}", style::icon("❌"), output.display(), e);
+        process::exit(1);
+    }
+    eprintln!(
+        "{} Reduced {} from {} to {} lines in {} checks: {}",
+        style::icon("✅"),
+        file.display(),
+        source.lines().count(),
+        reduction.source.lines().count(),
+        reduction.tests,
+        output.display(),
+    );
+}
+
+fn handle_repro_save(file: &Path, failure: Failure, corpus: &Path, name: Option<&str>, quiet: bool) {
+    let source = read_source(file);
+    let name = name.map(str::to_string).unwrap_or_else(|| {
+        file.file_stem().map_or("repro".to_string(), |stem| stem.to_string_lossy().into_owned())
+    });
+    if corpus.join(format!("{}.mini", name)).exists() {
+        eprintln!("{} Error: '{}' is already in {}; choose another --name", style::icon("❌"), name, corpus.display());
+        process::exit(1);
+    }
+    
+    if !repro::reproduces(failure, &source, "gcc") {
+        eprintln!("{} Error: {} does not show the '{}' failure", style::icon("❌"), file.display(), failure.name());
+        process::exit(1);
+    }
+    
+    let progress = if quiet || !std::io::stderr().is_terminal() {
+        ProgressBar::hidden()
+    } else {
+        let mut spinner_style = ProgressStyle::with_template("{spinner} Shrinking {wide_msg}").unwrap();
+        if !style::current().unicode {
+            spinner_style = spinner_style.tick_chars("|/-\\ ");
+        }
+        ProgressBar::new_spinner().with_style(spinner_style)
+    };
+    let reduction = repro::minimize(&source, |candidate| {
+        progress.set_message(format!("{} ({} lines)", file.display(), candidate.lines().count()));
+        progress.tick();
+        repro::reproduces(failure, candidate, "gcc")
+    });
+    progress.finish_and_clear();
+    
+    let path = repro::save(corpus, &name, failure, &reduction.source).unwrap_or_else(|e| {
+        eprintln!("{} Error: could not save the case: {}", style::icon("❌"), e);
+        process::exit(1);
+    });
+    eprintln!(
+        "{} Shrank {} from {} to {} lines in {} tries and saved it as {}",
+        style::icon("✅"),
+        file.display(),
+        source.lines().count(),
+        reduction.source.lines().count(),
+        reduction.tests,
+        path.display(),
+    );
+    eprintln!("   `cargo test --test repro_tests` fails until the bug is fixed");
+}
+
+/// Append the record to --build-log, and what it reported to --log-usage,
+/// when those were requested
+fn write_build_log(args: &Cli, record: &mut BuildRecord, started: Instant) {
+    record.total_ms = started.elapsed().as_secs_f64() * 1000.0;
+    
+    if let Some(log) = &args.build_log {
+        if let Err(e) = record.append_to(log) {
+            eprintln!("{}  Could not write build log {}: {}", style::icon("⚠️"), log.display(), e);
+        }
+    }
+    if let Some(dir) = &args.log_usage {
+        if let Err(e) = UsageRecord::from_build(record).append_to_dir(dir) {
+            eprintln!("{}  Could not write usage log in {}: {}", style::icon("⚠️"), dir.display(), e);
+        }
+    }
+}
+
+/// Log a compile that failed in `stage` and exit
+fn fail_build(args: &Cli, record: &mut BuildRecord, started: Instant, stage: &str, errors: usize) -> ! {
+    record.fail(stage, errors);
+    write_build_log(args, record, started);
+    process::exit(1);
+}
+
+/// The banner `file`'s project asks for, unless `--no-banner` is given. A
+/// minilang.toml that cannot be read fails the build.
+fn banner_for(file: &Path, args: &Cli, record: &mut BuildRecord, started: Instant) -> Option<Banner> {
+    if args.no_banner {
+        return None;
+    }
+    match cli::project_banner(file) {
+        Ok(banner) => banner,
+        Err(e) => {
+            eprintln!("{} Error: {}", style::icon("❌"), e);
+            fail_build(args, record, started, "codegen", 1);
+        }
+    }
+}
+
+/// After Ctrl-C: log the build as interrupted in `stage`, say what was
+/// stopped and exit the way an interrupted process does
+fn exit_interrupted(args: &Cli, record: &mut BuildRecord, started: Instant, stage: &str, summary: &str) -> ! {
+    record.interrupt(stage);
+    write_build_log(args, record, started);
+    eprintln!("\n{}  Interrupted after {:.1}s: {}", style::icon("⚠️"), started.elapsed().as_secs_f64(), summary);
+    process::exit(interrupt::EXIT_CODE);
+}
+
 fn compile_source(
     source: &str, 
     filename: &str, 
-    file: &PathBuf,
+    file: &Path,
     args: &Cli,
-    to_c_only: bool,
-    should_run: bool,
+    output: CodeOutput,
+    run: Option<ProgramInput>,
+    dry_run: Option<DryRun>,
 ) {
     let show_details = args.detail;
+    let to_c_only = output.to_c_only;
+    
+    let started = Instant::now();
+    let mode = if to_c_only {
+        "to-c"
+    } else if output.bytecode {
+        "bytecode"
+    } else if run.is_some() {
+        "run"
+    } else if dry_run.is_some() {
+        "dry-run"
+    } else {
+        "compile"
+    };
+    let mut record = BuildRecord::new(file, source, mode, args.optimization);
+    interrupt::install();
 
     if show_details {
         println!("\nCompiling");
@@ -503,18 +1623,22 @@ fn compile_source(
     }
     
     let mut lexer = Lexer::new(source);
+    let stage_start = Instant::now();
+    let lexed = lexer.tokenize();
+    record.record_stage("lex", stage_start.elapsed());
     
-    let tokens = match lexer.tokenize() {
+    let tokens = match lexed {
         Ok(tokens) => {
             if show_details {
-                println!("   ✅ Successfully tokenized!");
+                println!("   {} Successfully tokenized!", style::icon("✅"));
                 println!("   Found {} tokens", tokens.len());
                 
                 let keywords = tokens.iter().filter(|t| matches!(
                     t.token,
                     minilang_compiler::Token::Let | minilang_compiler::Token::Func |
                     minilang_compiler::Token::If | minilang_compiler::Token::While |
-                    minilang_compiler::Token::For | minilang_compiler::Token::Display
+                    minilang_compiler::Token::For | minilang_compiler::Token::Display |
+                    minilang_compiler::Token::DisplayLn | minilang_compiler::Token::DisplayRaw
                 )).count();
                 
                 let identifiers = tokens.iter().filter(|t| matches!(
@@ -527,8 +1651,9 @@ fn compile_source(
             tokens
         }
         Err(e) => {
+            record.add_error_codes(std::slice::from_ref(&e));
             display_beautiful_error_lexer(e, source, filename);
-            process::exit(1);
+            fail_build(args, &mut record, started, "lex", 1);
         }
     };
     
@@ -537,12 +1662,15 @@ fn compile_source(
         println!("Parser: Building Abstract Syntax Tree...");
     }
     
-    let mut parser = Parser::new(tokens, source.to_string());
+    let mut parser = Parser::new(tokens, source.to_string()).with_defines(args.defines()).with_edition(args.edition());
+    let stage_start = Instant::now();
+    let parsed = parser.parse_program();
+    record.record_stage("parse", stage_start.elapsed());
     
-    let mut program = match parser.parse_program() {
+    let mut program = match parsed {
         Ok(prog) => {
             if show_details {
-                println!("   ✅ Successfully parsed!");
+                println!("   {} Successfully parsed!", style::icon("✅"));
                 println!("   Found {} function(s)", prog.functions.len());
                 
                 for func in &prog.functions {
@@ -552,7 +1680,7 @@ fn compile_source(
                         String::from(" (void)")
                     };
                     
-                    println!("      • {}({} params){}", 
+                    println!("      {} {}({} params){}", style::icon("•"), 
                         func.name, 
                         func.params.len(),
                         return_type
@@ -567,8 +1695,9 @@ fn compile_source(
             prog
         }
         Err(e) => {
+            record.add_error_codes(std::slice::from_ref(&e));
             display_beautiful_error_parser(e, source, filename);
-            process::exit(1);
+            fail_build(args, &mut record, started, "parse", 1);
         }
     };
     
@@ -577,18 +1706,27 @@ fn compile_source(
         println!("Semantic Analyzer: Type checking...");
     }
     
-    let mut type_checker = TypeChecker::new();
+    let type_checker = TypeChecker::new().with_comments(&lexer.comments());
+    let mut type_checker = match &args.entry {
+        Some(entry) => type_checker.with_entry(entry),
+        None => type_checker,
+    };
+    let stage_start = Instant::now();
+    let checked = type_checker.check_program(&program);
+    record.record_stage("type_check", stage_start.elapsed());
+    record.warnings = type_checker.get_warnings().len();
+    record.warning_codes = type_checker.get_warnings().iter().map(CompilerWarning::code).collect();
     
-    match type_checker.check_program(&program) {
+    match checked {
         Ok(()) => {
             if show_details {
-                println!("   ✅ Type checking passed!");
+                println!("   {} Type checking passed!", style::icon("✅"));
             }
             
             let warnings = type_checker.get_warnings();
             if !warnings.is_empty() {
                 if show_details {
-                    println!("   ⚠️ {} warning(s) found", warnings.len());
+                    println!("   {} {} warning(s) found", style::icon("⚠️"), warnings.len());
                 }
                 for warning in warnings {
                     warning.display(source, filename);
@@ -598,10 +1736,16 @@ fn compile_source(
             }
         }
         Err(errors) => {
+            record.add_error_codes(&errors);
+            let count = errors.len();
             display_beautiful_error_semantic(errors, source, filename);
-            process::exit(1);
+            fail_build(args, &mut record, started, "type_check", count);
         }
     }
+    
+    if let Some(entry) = &args.entry {
+        program.set_entry(entry);
+    }
 
     if args.optimization > 0 {
         if show_details {
@@ -609,11 +1753,23 @@ fn compile_source(
             println!("Optimizer: Running optimization passes (level {})...", args.optimization);
         }
         
-        let mut optimizer = Optimizer::new(args.optimization);
+        let mut optimizer = Optimizer::new(args.optimization)
+            .with_fast_math(args.fast_math)
+            .with_overflow(args.overflow);
+        let stage_start = Instant::now();
         let opt_stats = optimizer.optimize(&mut program);
+        record.record_stage("optimize", stage_start.elapsed());
+        
+        if !optimizer.errors().is_empty() {
+            let errors = optimizer.errors().to_vec();
+            record.add_error_codes(&errors);
+            let count = errors.len();
+            display_beautiful_error_semantic(errors, source, filename);
+            fail_build(args, &mut record, started, "optimize", count);
+        }
         
         if show_details {
-            println!("  ✅ Optimization complete!");
+            println!("  {} Optimization complete!", style::icon("✅"));
             if opt_stats.constants_folded > 0 {
                 println!("   Constants folded: {}", opt_stats.constants_folded);
             }
@@ -626,11 +1782,38 @@ fn compile_source(
             if opt_stats.strength_reductions > 0 {
                 println!("   Strength reductions: {}", opt_stats.strength_reductions);
             }
+            if opt_stats.comparisons_decided > 0 {
+                println!("   Comparisons decided by ranges: {}", opt_stats.comparisons_decided);
+            }
+            if opt_stats.functions_inlined > 0 {
+                println!("   Functions inlined: {}", opt_stats.functions_inlined);
+            }
+            if opt_stats.functions_specialized > 0 {
+                println!("   Functions specialized: {}", opt_stats.functions_specialized);
+            }
+            if opt_stats.functions_removed > 0 {
+                println!("   Unused functions removed: {}", opt_stats.functions_removed);
+            }
+            if opt_stats.allocations_elided > 0 {
+                println!("   Strings kept on the stack: {}", opt_stats.allocations_elided);
+            }
+            if opt_stats.plugin_changes > 0 {
+                println!("   Plugin changes: {}", opt_stats.plugin_changes);
+            }
+            if opt_stats.iterations > 1 {
+                println!("   Passes repeated: {} rounds until stable", opt_stats.iterations);
+            }
             
             if opt_stats.constants_folded == 0 && 
                opt_stats.dead_code_removed == 0 && 
                opt_stats.constants_propagated == 0 &&
-               opt_stats.strength_reductions == 0 {
+               opt_stats.strength_reductions == 0 &&
+               opt_stats.comparisons_decided == 0 &&
+               opt_stats.functions_inlined == 0 &&
+               opt_stats.functions_removed == 0 &&
+               opt_stats.functions_specialized == 0 &&
+               opt_stats.allocations_elided == 0 &&
+               opt_stats.plugin_changes == 0 {
                 println!("  No optimizations applied");
             }
         }
@@ -639,74 +1822,144 @@ fn compile_source(
         println!("Optimizer: Skipped (optimization level 0)");
     }
     
+    if output.bytecode {
+        write_bytecode(&program, source, filename, file, args, &mut record, started);
+        return;
+    }
+    
     if show_details {
         println!("\n_______________________________________");
         println!("Code Generator: Generating C code...");
     }
     
-    let mut codegen = CodeGenerator::new();
+    let mut codegen = CodeGenerator::new()
+        .with_source(filename, source)
+        .with_peephole(args.optimization >= 1)
+        .with_line_tracking(args.optimization == 0)
+        .with_always_bounds_check(args.always_bounds_check)
+        .with_runtime(args.runtime)
+        .with_profile(args.profile)
+        .with_stamp(Stamp::new(source, args.optimization, args.codegen_flags()));
+    if let Some(banner) = banner_for(file, args, &mut record, started) {
+        codegen = codegen.with_banner(banner);
+    }
+    let stage_start = Instant::now();
+    let generated = codegen.generate(&program);
+    record.record_stage("codegen", stage_start.elapsed());
     
-    let c_code = match codegen.generate(&program) {
+    let c_code = match generated {
         Ok(code) => code,
         Err(e) => {
-            eprintln!("❌ Code generation failed: {}", e);
-            process::exit(1);
+            eprintln!("{} Code generation failed: {}", style::icon("❌"), e);
+            fail_build(args, &mut record, started, "codegen", 1);
         }
     };
     
     if show_details {
-        println!("   ✅ C code generated successfully!");
+        println!("   {} C code generated successfully!", style::icon("✅"));
         println!("   {} lines of C code", c_code.lines().count());
         
+        let peephole = codegen.peephole_stats();
+        if peephole.parens_removed + peephole.returns_removed + peephole.printfs_merged > 0 {
+            println!("   Peephole: {} parentheses, {} returns removed, {} printf calls merged",
+                peephole.parens_removed, peephole.returns_removed, peephole.printfs_merged);
+        }
+        
+        if codegen.bounds_checks_elided() > 0 {
+            println!("   {} array accesses proven in bounds", codegen.bounds_checks_elided());
+        }
+        
         let headers: Vec<&str> = c_code.lines()
             .filter(|line| line.starts_with("#include"))
             .collect();
         println!("   {} system headers included", headers.len());
     }
     
-    let c_output_path = file.with_extension("c");
-    
-    if let Err(e) = fs::write(&c_output_path, &c_code) {
-        eprintln!("❌ Failed to save C code: {}", e);
-        process::exit(1);
+    if output.show_c_mapping {
+        print_c_mapping(source, filename, &c_code, codegen.source_map());
     }
     
-    if show_details && args.keep_c {
-        println!("   Saved to: {}", c_output_path.display());
+    let out_dir = artifact_dir(file, &args.out_dir);
+    if let Err(e) = fs::create_dir_all(&out_dir) {
+        eprintln!("{} Failed to create output directory {}: {}", style::icon("❌"), out_dir.display(), e);
+        fail_build(args, &mut record, started, "output", 1);
     }
     
+    let c_file_name = file.with_extension("c");
+    let c_file_name = c_file_name.file_name().unwrap_or(std::ffi::OsStr::new("output.c"));
+    let c_output_path = out_dir.join(c_file_name);
+    
     if to_c_only {
-        println!("\n✅ Conversion to C successful!");
+        if let Err(e) = fs::write(&c_output_path, &c_code) {
+            eprintln!("{} Failed to save C code: {}", style::icon("❌"), e);
+            fail_build(args, &mut record, started, "output", 1);
+        }
+        record.add_artifact("c", &c_output_path);
+        write_build_log(args, &mut record, started);
+        println!("\n{} Conversion to C successful!", style::icon("✅"));
         println!("   Output: {}", c_output_path.display());
         return;
     }
     
+    // gcc flags, then the one for the --overflow mode
+    let cc_flags: Vec<&str> = GCC_FLAGS.iter().copied().chain([args.overflow.cc_flag()]).collect();
+    let exe_output_path = determine_output_path(file, &args.out_dir, &args.output);
+    
+    if let Some(dry_run) = dry_run {
+        // The commands must work when run later, so the C goes next to the
+        // executable instead of into a temporary directory
+        if let Err(e) = fs::write(&c_output_path, &c_code) {
+            eprintln!("{} Failed to save C code: {}", style::icon("❌"), e);
+            fail_build(args, &mut record, started, "output", 1);
+        }
+        record.cc_flags = cc_flags.iter().map(|flag| flag.to_string()).collect();
+        record.add_artifact("c", &c_output_path);
+        
+        write_build_log(args, &mut record, started);
+        
+        let command = backend::invocation("gcc", &backend::compile_c_args(&c_output_path, &exe_output_path, &cc_flags));
+        print_dry_run(&dry_run, &[command]);
+        println!("   C File: {}", c_output_path.display());
+        println!("   Executable: {}", exe_output_path.display());
+        return;
+    }
+    
+    // gcc reads the C file from a private per-invocation directory, so read-only
+    // source directories work and parallel builds never share an intermediate
+    let build_dir = match tempfile::Builder::new().prefix("minilang-").tempdir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{} Failed to create temporary directory: {}", style::icon("❌"), e);
+            fail_build(args, &mut record, started, "output", 1);
+        }
+    };
+    let temp_c_path = build_dir.path().join(c_file_name);
+    
+    if let Err(e) = fs::write(&temp_c_path, &c_code) {
+        eprintln!("{} Failed to save C code: {}", style::icon("❌"), e);
+        drop(build_dir);
+        fail_build(args, &mut record, started, "output", 1);
+    }
+    
     if show_details {
         println!("\n_______________________________________");
         println!("GCC: Compiling to native executable...");
     }
     
-    let exe_output_path = determine_output_path(file, &args.output);
+    record.cc_flags = cc_flags.iter().map(|flag| flag.to_string()).collect();
+    let stage_start = Instant::now();
+    let gcc_result = backend::compile_c("gcc", &temp_c_path, &exe_output_path, &cc_flags);
+    record.record_stage("cc", stage_start.elapsed());
     
-    let gcc_result = Command::new("gcc")
-        .arg(&c_output_path)
-        .arg("-o")
-        .arg(&exe_output_path)
-        .arg("-std=c99")
-        .arg("-Wall")
-        .arg("-O2")
-        .output();
+    if interrupt::interrupted() {
+        drop(build_dir);
+        exit_interrupted(args, &mut record, started, "cc", "gcc stopped and temporary build files removed");
+    }
     
     match gcc_result {
-        Ok(output) => {
-            if !output.status.success() {
-                eprintln!("❌ GCC compilation failed:");
-                eprintln!("{}", String::from_utf8_lossy(&output.stderr));
-                process::exit(1);
-            }
-            
+        Ok(()) => {
             if show_details {
-                println!("   ✅ Native compilation successful!");
+                println!("   {} Native compilation successful!", style::icon("✅"));
                 
                 if let Ok(metadata) = fs::metadata(&exe_output_path) {
                     let size_kb = metadata.len() / 1024;
@@ -715,21 +1968,33 @@ fn compile_source(
             }
         }
         Err(e) => {
-            eprintln!("❌ Could not run GCC: {}", e);
-            eprintln!("   Make sure GCC is installed: gcc --version");
-            process::exit(1);
+            record.add_error_codes(std::slice::from_ref(&e));
+            report_cc_failure(&e, source, filename);
+            save_failed_build(args, file, &[&temp_c_path], &cc_flags, &e);
+            drop(build_dir);
+            fail_build(args, &mut record, started, "cc", 1);
         }
     }
     
-    if !args.keep_c {
-        if let Err(_) = fs::remove_file(&c_output_path) {
-            if show_details {
-                println!("   ⚠️  Could not delete temporary C file");
-            }
+    if args.keep_c {
+        match fs::copy(&temp_c_path, &c_output_path) {
+            Ok(_) if show_details => println!("   Saved C code to: {}", c_output_path.display()),
+            Ok(_) => {}
+            Err(e) => eprintln!("{}  Could not keep C file {}: {}", style::icon("⚠️"), c_output_path.display(), e),
         }
-    } 
+    }
+    
+    if build_dir.close().is_err() && show_details {
+        println!("   {}  Could not delete temporary build directory", style::icon("⚠️"));
+    }
     
-    if should_run {
+    record.add_artifact("executable", &exe_output_path);
+    if args.keep_c {
+        record.add_artifact("c", &c_output_path);
+    }
+    write_build_log(args, &mut record, started);
+    
+    if let Some(input) = run {
         if show_details {
             println!("\n Running");
             println!("{}", "=".repeat(60));
@@ -741,27 +2006,51 @@ fn compile_source(
             exe_output_path.clone()
         };
         
-        let run_result = Command::new(&exec_path).output();
+        let stdin = match input.stdin {
+            Some(path) => match fs::File::open(path) {
+                Ok(file) => Stdio::from(file),
+                Err(e) => {
+                    eprintln!("{} Error opening stdin file '{}': {}", style::icon("❌"), path.display(), e);
+                    process::exit(1);
+                }
+            },
+            None => Stdio::inherit(),
+        };
+        
+        let run_started = Instant::now();
+        let run_result = Command::new(&exec_path)
+            .args(input.args)
+            .stdin(stdin)
+            .spawn()
+            .and_then(|mut child| interrupt::wait(&mut child));
         
         match run_result {
-            Ok(output) => {
-                print!("{}", String::from_utf8_lossy(&output.stdout));
-                
-                if !output.stderr.is_empty() {
-                    eprint!("{}", String::from_utf8_lossy(&output.stderr));
-                }
-                
-                if !output.status.success() {
-                    process::exit(output.status.code().unwrap_or(1));
+            Ok(Some(status)) => {
+                if !status.success() {
+                    // The runtime reports traps it catches itself; anything that
+                    // kills the program outright only leaves a signal behind
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::process::ExitStatusExt;
+                        if let Some(signal) = status.signal() {
+                            eprintln!("Runtime Error: program killed by signal {} while running {}", signal, filename);
+                            process::exit(128 + signal);
+                        }
+                    }
+                    process::exit(status.code().unwrap_or(1));
                 }
             }
+            Ok(None) => {
+                eprintln!("\n{}  Interrupted: stopped {} after {:.1}s", style::icon("⚠️"), exec_path.display(), run_started.elapsed().as_secs_f64());
+                process::exit(interrupt::EXIT_CODE);
+            }
             Err(e) => {
-                eprintln!("❌ Failed to run program: {}", e);
+                eprintln!("{} Failed to run program: {}", style::icon("❌"), e);
                 process::exit(1);
             }
         }
     } else {
-        println!("\n✅ Compilation successful!");
+        println!("\n{} Compilation successful!", style::icon("✅"));
         if !to_c_only {
             println!("   Executable: {}", exe_output_path.display());
         }
@@ -771,6 +2060,447 @@ fn compile_source(
     }
 }
 
+/// `compile --target=bytecode`: the checked, optimized program as a `.mbc` file
+fn write_bytecode(program: &Program, source: &str, filename: &str, file: &Path, args: &Cli, record: &mut BuildRecord, started: Instant) {
+    if args.detail {
+        println!("\n_______________________________________");
+        println!("Bytecode Compiler: Generating bytecode...");
+    }
+    
+    let compiler = BytecodeCompiler::new()
+        .with_source(filename, source)
+        .with_overflow(args.overflow)
+        .with_stamp(Stamp::new(source, args.optimization, args.codegen_flags()));
+    let stage_start = Instant::now();
+    let compiled = compiler.compile(program);
+    record.record_stage("codegen", stage_start.elapsed());
+    
+    let module = match compiled {
+        Ok(module) => module,
+        Err(e) => {
+            eprintln!("{} Bytecode generation failed: {}", style::icon("❌"), e);
+            fail_build(args, record, started, "codegen", 1);
+        }
+    };
+    let bytes = module.encode();
+    
+    if args.detail {
+        let instructions: usize = module.functions.iter().map(|function| function.code.len()).sum();
+        println!("   {} Bytecode generated successfully!", style::icon("✅"));
+        println!("   {} instructions in {} function(s), {} bytes", instructions, module.functions.len(), bytes.len());
+    }
+    
+    let out_dir = artifact_dir(file, &args.out_dir);
+    let output_path = match &args.output {
+        Some(name) => out_dir.join(name),
+        None => out_dir.join(file.with_extension(bytecode::EXTENSION).file_name().unwrap_or(std::ffi::OsStr::new("output.mbc"))),
+    };
+    if let Err(e) = fs::create_dir_all(&out_dir).and_then(|()| fs::write(&output_path, &bytes)) {
+        eprintln!("{} Failed to save bytecode to {}: {}", style::icon("❌"), output_path.display(), e);
+        fail_build(args, record, started, "output", 1);
+    }
+    record.add_artifact("bytecode", &output_path);
+    write_build_log(args, record, started);
+    
+    println!("\n{} Compilation successful!", style::icon("✅"));
+    println!("   Bytecode: {}", output_path.display());
+    println!("   Run it with: minilang vm {}", output_path.display());
+}
+
+/// `minilang vm`: run a bytecode file, exiting with the program's exit code
+fn handle_vm(file: &Path, watch: &[String], virtual_clock: bool) {
+    let bytes = match fs::read(file) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("{} Error reading file '{}': {}", style::icon("❌"), file.display(), e);
+            process::exit(1);
+        }
+    };
+    let module = match bytecode::Module::decode(&bytes) {
+        Ok(module) => module,
+        Err(e) => {
+            eprintln!("{} {}: {:?}", style::icon("❌"), file.display(), Report::new(e));
+            process::exit(1);
+        }
+    };
+    
+    for name in watch {
+        if !module.functions.iter().any(|function| function.local_names.contains(name)) {
+            eprintln!("{} Warning: no function has a variable named '{}'", style::icon("⚠️"), name);
+        }
+    }
+    
+    let stdout = std::io::stdout();
+    let stderr = std::io::stderr();
+    let clock = if virtual_clock { bytecode::vm::Clock::Virtual } else { bytecode::vm::Clock::Real };
+    match bytecode::vm::run_with_clock(&module, &mut stdout.lock(), clock, watch, &mut stderr.lock()) {
+        Ok(code) => process::exit(code),
+        Err(trap) => {
+            eprintln!("{}", trap);
+            process::exit(trap.exit_code);
+        }
+    }
+}
+
+/// One file of a multi-module compile
+struct Module {
+    file: PathBuf,
+    filename: String,
+    source: String,
+    program: Program,
+    record: BuildRecord,
+}
+
+/// Compile each file as a separate module: modules see each other's function
+/// signatures, become one C file and one object file each, and are linked
+/// into a single executable unless `emit` asks for the C or objects instead
+fn handle_compile_modules(files: &[PathBuf], args: &Cli, emit: Emit, dry_run: Option<DryRun>, show_c_mapping: bool) {
+    let show_details = args.detail;
+    let started = Instant::now();
+    let mode = match emit {
+        _ if dry_run.is_some() => "dry-run",
+        Emit::Exe => "compile",
+        Emit::Obj => "obj",
+        Emit::C => "to-c",
+    };
+    
+    if args.entry.is_some() {
+        eprintln!("{} Error: --entry only applies when compiling a single file", style::icon("❌"));
+        process::exit(1);
+    }
+    interrupt::install();
+    
+    if emit == Emit::Obj && files.len() > 1 && args.output.is_some() {
+        eprintln!("{} Error: -o names a single file, but --emit obj writes one object per module", style::icon("❌"));
+        process::exit(1);
+    }
+    
+    // Each module's C and object file is named after it
+    let mut stems = std::collections::HashSet::new();
+    for file in files {
+        let stem = file.file_stem().unwrap_or(file.as_os_str());
+        if !stems.insert(stem) {
+            eprintln!("{} Error: More than one module is named '{}'", style::icon("❌"), stem.to_string_lossy());
+            process::exit(1);
+        }
+    }
+    
+    let mut modules = Vec::new();
+    for file in files {
+        let source = read_source(file);
+        let filename = file.to_str().unwrap_or("unknown.mini").to_string();
+        let mut record = BuildRecord::new(file, &source, mode, args.optimization);
+        
+        let stage_start = Instant::now();
+        let lexed = Lexer::new(&source).tokenize();
+        record.record_stage("lex", stage_start.elapsed());
+        let tokens = match lexed {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                record.add_error_codes(std::slice::from_ref(&e));
+                display_beautiful_error_lexer(e, &source, &filename);
+                fail_build(args, &mut record, started, "lex", 1);
+            }
+        };
+        
+        let stage_start = Instant::now();
+        let parsed = Parser::new(tokens, source.clone()).with_defines(args.defines()).with_edition(args.edition()).parse_program();
+        record.record_stage("parse", stage_start.elapsed());
+        let program = match parsed {
+            Ok(program) => program,
+            Err(e) => {
+                record.add_error_codes(std::slice::from_ref(&e));
+                display_beautiful_error_parser(e, &source, &filename);
+                fail_build(args, &mut record, started, "parse", 1);
+            }
+        };
+        
+        modules.push(Module { file: file.clone(), filename, source, program, record });
+    }
+    
+    let main_module = modules.iter().position(|m| m.program.functions.iter().any(|f| f.name == "main"));
+    if emit == Emit::Exe && main_module.is_none() {
+        eprintln!("{} Error: None of the modules defines a main function", style::icon("❌"));
+        process::exit(1);
+    }
+    
+    // What each module can call in the others
+    let externals: Vec<Vec<Function>> = (0..modules.len())
+        .map(|index| modules.iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .flat_map(|(_, module)| module.program.functions.iter().cloned())
+            .collect())
+        .collect();
+    
+    // A function only other modules call looks unused from inside its own
+    let passes: Vec<Pass> = Pass::for_level(args.optimization)
+        .into_iter()
+        .filter(|pass| *pass != Pass::DeadFunctions)
+        .collect();
+    
+    let out_dir = artifact_dir(&modules[0].file, &args.out_dir);
+    if let Err(e) = fs::create_dir_all(&out_dir) {
+        eprintln!("{} Failed to create output directory {}: {}", style::icon("❌"), out_dir.display(), e);
+        fail_build(args, &mut modules[0].record, started, "output", 1);
+    }
+    let build_dir = match tempfile::Builder::new().prefix("minilang-").tempdir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{} Failed to create temporary directory: {}", style::icon("❌"), e);
+            fail_build(args, &mut modules[0].record, started, "output", 1);
+        }
+    };
+    
+    let cc_flags: Vec<&str> = GCC_FLAGS.iter().copied().chain([args.overflow.cc_flag()]).collect();
+    let mut objects = Vec::new();
+    let mut c_files = Vec::new();
+    let mut outputs = Vec::new();
+    let mut commands = Vec::new();
+    
+    for (index, module) in modules.iter_mut().enumerate() {
+        let Module { file, filename, source, program, record } = module;
+        
+        let mut type_checker = TypeChecker::new()
+            .with_externals(&externals[index])
+            .with_comments(&Lexer::new(source).comments());
+        let stage_start = Instant::now();
+        let checked = type_checker.check_program(program);
+        record.record_stage("type_check", stage_start.elapsed());
+        record.warnings = type_checker.get_warnings().len();
+        record.warning_codes = type_checker.get_warnings().iter().map(CompilerWarning::code).collect();
+        for warning in type_checker.get_warnings() {
+            warning.display(source, filename);
+        }
+        if let Err(errors) = checked {
+            record.add_error_codes(&errors);
+            let count = errors.len();
+            display_beautiful_error_semantic(errors, source, filename);
+            fail_build(args, record, started, "type_check", count);
+        }
+        
+        if args.optimization > 0 {
+            let mut optimizer = Optimizer::with_passes(&passes)
+                .with_fast_math(args.fast_math)
+                .with_overflow(args.overflow);
+            let stage_start = Instant::now();
+            optimizer.optimize(program);
+            record.record_stage("optimize", stage_start.elapsed());
+            
+            if !optimizer.errors().is_empty() {
+                let errors = optimizer.errors().to_vec();
+                record.add_error_codes(&errors);
+                let count = errors.len();
+                display_beautiful_error_semantic(errors, source, filename);
+                fail_build(args, record, started, "optimize", count);
+            }
+        }
+        
+        let mut codegen = CodeGenerator::new()
+            .with_source(filename, source)
+            .with_peephole(args.optimization >= 1)
+            .with_line_tracking(args.optimization == 0)
+            .with_always_bounds_check(args.always_bounds_check)
+            .with_runtime(args.runtime)
+            .with_profile(args.profile)
+            .with_externals(&externals[index])
+            .with_stamp(Stamp::new(source, args.optimization, args.codegen_flags()));
+        if let Some(banner) = banner_for(file, args, record, started) {
+            codegen = codegen.with_banner(banner);
+        }
+        let stage_start = Instant::now();
+        let generated = codegen.generate(program);
+        record.record_stage("codegen", stage_start.elapsed());
+        let c_code = match generated {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{} Code generation failed: {}", style::icon("❌"), e);
+                fail_build(args, record, started, "codegen", 1);
+            }
+        };
+        if show_c_mapping {
+            print_c_mapping(source, filename, &c_code, codegen.source_map());
+        }
+        
+        let stem = file.file_stem().unwrap_or(file.as_os_str()).to_os_string();
+        let c_name = Path::new(&stem).with_extension("c");
+        let c_output_path = out_dir.join(&c_name);
+        // A dry run's commands are run later, so its files can't be temporary
+        let keep = emit == Emit::C || dry_run.is_some();
+        let c_path = if keep { c_output_path.clone() } else { build_dir.path().join(&c_name) };
+        if let Err(e) = fs::write(&c_path, &c_code) {
+            eprintln!("{} Failed to save C code: {}", style::icon("❌"), e);
+            fail_build(args, record, started, "output", 1);
+        }
+        
+        if emit == Emit::C {
+            record.add_artifact("c", &c_path);
+            outputs.push(c_path);
+            continue;
+        }
+        
+        let object = match (emit, &args.output) {
+            (Emit::Obj, Some(name)) => out_dir.join(name),
+            (Emit::Obj, None) => out_dir.join(Path::new(&stem).with_extension("o")),
+            _ if dry_run.is_some() => out_dir.join(Path::new(&stem).with_extension("o")),
+            _ => build_dir.path().join(Path::new(&stem).with_extension("o")),
+        };
+        record.cc_flags = cc_flags.iter().map(|flag| flag.to_string()).collect();
+        
+        if dry_run.is_some() {
+            commands.push(backend::invocation("gcc", &backend::compile_object_args(&c_path, &object, &cc_flags)));
+            record.add_artifact("c", &c_path);
+            if emit == Emit::Obj {
+                outputs.push(object.clone());
+            }
+            objects.push(object);
+            c_files.push(c_path);
+            continue;
+        }
+        let stage_start = Instant::now();
+        let cc_result = backend::compile_object("gcc", &c_path, &object, &cc_flags);
+        record.record_stage("cc", stage_start.elapsed());
+        if interrupt::interrupted() {
+            drop(build_dir);
+            exit_interrupted(args, record, started, "cc", "gcc stopped and temporary build files removed");
+        }
+        if let Err(e) = cc_result {
+            record.add_error_codes(std::slice::from_ref(&e));
+            report_cc_failure(&e, source, filename);
+            save_failed_build(args, file, &[&c_path], &cc_flags, &e);
+            fail_build(args, record, started, "cc", 1);
+        }
+        
+        if show_details {
+            println!("{} {}: {} function(s), {} lines of C", style::icon("✅"), filename, program.functions.len(), c_code.lines().count());
+        }
+        
+        if args.keep_c {
+            match fs::copy(&c_path, &c_output_path) {
+                Ok(_) => record.add_artifact("c", &c_output_path),
+                Err(e) => eprintln!("{}  Could not keep C file {}: {}", style::icon("⚠️"), c_output_path.display(), e),
+            }
+        }
+        if emit == Emit::Obj {
+            record.add_artifact("object", &object);
+            outputs.push(object.clone());
+        }
+        objects.push(object);
+        c_files.push(c_path);
+    }
+    
+    if let Some(main_module) = main_module.filter(|_| emit == Emit::Exe) {
+        let exe_output_path = determine_output_path(&modules[main_module].file, &args.out_dir, &args.output);
+        let record = &mut modules[main_module].record;
+        
+        if dry_run.is_some() {
+            commands.push(backend::invocation("gcc", &backend::link_args(&objects, &exe_output_path, &[])));
+            outputs.push(exe_output_path);
+        } else {
+            let stage_start = Instant::now();
+            let link_result = backend::link("gcc", &objects, &exe_output_path, &[]);
+            record.record_stage("link", stage_start.elapsed());
+            if interrupt::interrupted() {
+                drop(build_dir);
+                exit_interrupted(args, record, started, "link", "gcc stopped and temporary build files removed");
+            }
+            if let Err(e) = link_result {
+                modules[main_module].record.add_error_codes(std::slice::from_ref(&e));
+                report_backend_error(&e);
+                let c_files: Vec<&Path> = c_files.iter().map(PathBuf::as_path).collect();
+                save_failed_build(args, &modules[main_module].file, &c_files, &cc_flags, &e);
+                fail_build(args, &mut modules[main_module].record, started, "link", 1);
+            }
+            record.add_artifact("executable", &exe_output_path);
+            outputs.push(exe_output_path);
+        }
+    }
+    
+    if build_dir.close().is_err() && show_details {
+        println!("   {}  Could not delete temporary build directory", style::icon("⚠️"));
+    }
+    
+    for module in &mut modules {
+        write_build_log(args, &mut module.record, started);
+    }
+    
+    if let Some(dry_run) = dry_run {
+        print_dry_run(&dry_run, &commands);
+        for c_file in &c_files {
+            println!("   C File: {}", c_file.display());
+        }
+    } else {
+        println!("\n{} Compiled {} module(s)!", style::icon("✅"), modules.len());
+    }
+    for output in &outputs {
+        println!("   Output: {}", output.display());
+    }
+}
+
+/// Show the gcc commands a dry run stands in for and save them with --script
+fn print_dry_run(dry_run: &DryRun, commands: &[Vec<String>]) {
+    println!("\nDry run - gcc was not run");
+    for command in commands {
+        println!("   {}", backend::shell_command(command));
+    }
+    if let Some(script) = dry_run.script {
+        match backend::write_script(script, commands) {
+            Ok(()) => println!("   Script: {}", script.display()),
+            Err(e) => {
+                eprintln!("{} Failed to write script {}: {}", style::icon("❌"), script.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// With --save-temps, keep what a failed gcc run needs to be reproduced in
+/// <name>-temps next to the output, and say where it went
+fn save_failed_build(args: &Cli, file: &Path, c_files: &[&Path], cc_flags: &[&str], error: &BackendError) {
+    if !args.save_temps {
+        eprintln!("   Rerun with --save-temps to keep the generated C for debugging");
+        return;
+    }
+    
+    let stem = file.file_stem().unwrap_or(file.as_os_str()).to_string_lossy();
+    let dir = artifact_dir(file, &args.out_dir).join(format!("{}-temps", stem));
+    match backend::save_temps(&dir, "gcc", c_files, cc_flags, error) {
+        Ok(()) => eprintln!("   Generated C, preprocessed C and the gcc command saved in {}", dir.display()),
+        Err(e) => eprintln!("{}  Could not save intermediate files to {}: {}", style::icon("⚠️"), dir.display(), e),
+    }
+}
+
+/// Print why gcc failed on the C generated for `source`, on the MiniLang
+/// lines it points at when every error can be traced back to one
+fn report_cc_failure(error: &BackendError, source: &str, filename: &str) {
+    let BackendError::CompileFailed { stderr, .. } = error else {
+        return report_backend_error(error);
+    };
+    let Some(diagnostics) = backend::map_cc_diagnostics(stderr, filename, source) else {
+        return report_backend_error(error);
+    };
+    
+    eprintln!("{} GCC compilation failed:", style::icon("❌"));
+    for diagnostic in diagnostics {
+        let named_source = NamedSource::new(filename, source.to_string());
+        eprintln!("{:?}", Report::from(diagnostic).with_source_code(named_source));
+    }
+}
+
+/// Print why gcc failed on a module or while linking
+fn report_backend_error(error: &BackendError) {
+    match error {
+        BackendError::CompileFailed { stderr, .. } => {
+            eprintln!("{} GCC compilation failed:", style::icon("❌"));
+            eprintln!("{}", stderr);
+        }
+        BackendError::Spawn { reason, .. } => {
+            eprintln!("{} Could not run GCC: {}", style::icon("❌"), reason);
+            eprintln!("   Make sure GCC is installed: gcc --version");
+        }
+    }
+}
+
 fn display_beautiful_error_lexer(error: LexerError, source: &str, filename: &str) {
     let named_source = NamedSource::new(filename, source.to_string());
     let report = Report::from(error).with_source_code(named_source);
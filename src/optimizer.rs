@@ -1,6 +1,13 @@
 // src/optimizer.rs - Code optimization passes
 
+use crate::analyzer::fanout;
+use crate::bounds;
+use crate::control_flow::{self, Loop};
+use crate::escape;
 use crate::ast::*;
+use crate::errors::SemanticError;
+use crate::plugin::{self, CompilerPlugin};
+use serde::{Serialize, Deserialize};
 use std::collections::{HashMap,HashSet};
 
 /// Statistics about optimizations performed
@@ -10,66 +17,683 @@ pub struct OptimizationStats {
     pub dead_code_removed: usize,
     pub constants_propagated: usize,
     pub strength_reductions: usize,
+    pub functions_inlined: usize,
+    pub functions_removed: usize,
+    pub functions_specialized: usize,
+    pub comparisons_decided: usize,
+    /// Strings built in a stack buffer instead of on the heap
+    pub allocations_elided: usize,
+    /// Changes plugin passes reported making
+    pub plugin_changes: usize,
+    /// Times the pass list ran before the program stopped changing
+    pub iterations: usize,
 }
 
+impl OptimizationStats {
+    /// The counter a pass adds to
+    pub fn for_pass(&self, pass: Pass) -> usize {
+        match pass {
+            Pass::Specialize => self.functions_specialized,
+            Pass::Inline => self.functions_inlined,
+            Pass::StrengthReduce => self.strength_reductions,
+            Pass::ConstFold => self.constants_folded,
+            Pass::ConstProp => self.constants_propagated,
+            Pass::RangeFold => self.comparisons_decided,
+            Pass::DCE => self.dead_code_removed,
+            Pass::DeadFunctions => self.functions_removed,
+            Pass::StackStrings => self.allocations_elided,
+        }
+    }
+}
+
+/// A single optimization pass. Passes run in the order they are given and
+/// may be repeated, e.g. folding again after propagation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Pass {
+    /// Clone functions whose call sites all pass the same literal
+    Specialize,
+    /// Substitute calls to simple `@inline` functions
+    Inline,
+    /// Replace multiplications, divisions and similar with cheaper forms
+    StrengthReduce,
+    /// Evaluate operators whose operands are literals
+    ConstFold,
+    /// Replace reads of never-reassigned variables with their literal value
+    ConstProp,
+    /// Replace int comparisons that range analysis can decide, like `i < 10`
+    /// for a loop counter that stays below 5, with `true` or `false`
+    RangeFold,
+    /// Remove unreachable statements and branches with constant conditions
+    DCE,
+    /// Remove functions that cannot be reached from `main`
+    DeadFunctions,
+    /// Build strings that never leave their function in a stack buffer
+    /// instead of on the heap
+    StackStrings,
+}
+
+impl Pass {
+    /// The passes behind an optimization level: none at 0, a basic
+    /// pipeline at 1 and the aggressive one at 2 or above
+    pub fn for_level(level: u8) -> Vec<Pass> {
+        use Pass::*;
+        match level {
+            0 => vec![],
+            1 => vec![Inline, StrengthReduce, ConstFold, DCE],
+            _ => vec![
+                Specialize, Inline, StrengthReduce, ConstFold, ConstProp,
+                ConstFold, StrengthReduce, ConstFold, RangeFold, DCE, DeadFunctions,
+                StackStrings,
+            ],
+        }
+    }
+}
+
+/// What int arithmetic does when the result does not fit, as chosen with `--overflow`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowMode {
+    /// Wrap around in two's complement
+    #[default]
+    Wrap,
+    /// Abort at runtime; constant expressions that overflow are compile errors
+    Trap,
+}
+
+impl OverflowMode {
+    /// The gcc flag that gives the generated C these semantics
+    pub fn cc_flag(&self) -> &'static str {
+        match self {
+            OverflowMode::Wrap => "-fwrapv",
+            OverflowMode::Trap => "-ftrapv",
+        }
+    }
+}
+
+/// How many times `optimize` runs the pass list unless told otherwise
+pub const DEFAULT_MAX_ITERATIONS: usize = 10;
+
 /// The optimizer - performs multiple optimization passes on the AST
 pub struct Optimizer {
-    optimization_level: u8,  // 0 = none, 1 = basic, 2 = aggressive
+    passes: Vec<Pass>,
+    // Plugin passes, run after the built-in ones in every round
+    plugins: Vec<&'static dyn CompilerPlugin>,
+    max_iterations: usize,
+    // Allow float rewrites that are wrong for -0.0, NaN or infinities
+    fast_math: bool,
+    overflow: OverflowMode,
+    // Constant expressions that overflow in trap mode, reported once per span
+    errors: Vec<SemanticError>,
+    reported: HashSet<(usize, usize)>,
     stats: OptimizationStats,
     // Track constant variables for propagation
     constant_values: HashMap<String, Literal>,
+    // Elements of `const` arrays, for folding lookups at constant indexes
+    constant_arrays: HashMap<String, Vec<Literal>>,
+    // `@inline` functions whose body is a single `send`: parameter names and result
+    inline_bodies: HashMap<String, (Vec<String>, Expression)>,
 }
 
 impl Optimizer {
+    /// The passes of an optimization level, followed by the installed
+    /// plugins' passes at level 1 and above
     pub fn new(level: u8) -> Self {
+        let plugins = if level > 0 { plugin::installed() } else { Vec::new() };
+        Self::with_passes(&Pass::for_level(level)).with_plugins(&plugins)
+    }
+
+    /// An optimizer that runs exactly these passes, in order
+    pub fn with_passes(passes: &[Pass]) -> Self {
         Self {
-            optimization_level: level,
+            passes: passes.to_vec(),
+            plugins: Vec::new(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            fast_math: false,
+            overflow: OverflowMode::default(),
+            errors: Vec::new(),
+            reported: HashSet::new(),
             stats: OptimizationStats::default(),
             constant_values: HashMap::new(),
+            constant_arrays: HashMap::new(),
+            inline_bodies: HashMap::new(),
         }
     }
 
+    /// Run these plugins' passes after the pass list in every round
+    pub fn with_plugins(mut self, plugins: &[&'static dyn CompilerPlugin]) -> Self {
+        self.plugins = plugins.to_vec();
+        self
+    }
+    
+    /// Cap the number of times the pass list is repeated (at least once)
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations.max(1);
+        self
+    }
+
+    /// Also apply float identities that only hold when -0.0, NaN and
+    /// infinities can be ignored, like `x + 0.0 -> x` and `x * 0.0 -> 0.0`
+    pub fn with_fast_math(mut self, fast_math: bool) -> Self {
+        self.fast_math = fast_math;
+        self
+    }
+
+    /// Fold int arithmetic with these overflow semantics
+    pub fn with_overflow(mut self, overflow: OverflowMode) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Constant expressions the last `optimize` found to overflow in trap mode
+    pub fn errors(&self) -> &[SemanticError] {
+        &self.errors
+    }
+
     fn is_power_of_two(&self, n: i32) -> bool {
         n > 0 && (n & (n - 1)) == 0
     }
     
-    /// Run all optimization passes on the program
+    /// Run the selected passes on the program, repeating the whole list
+    /// until it stops changing anything or the iteration cap is reached.
+    /// The returned stats cover this call only, so optimizing an already
+    /// optimized program reports no changes.
     pub fn optimize(&mut self, program: &mut Program) -> OptimizationStats {
-        // If optimization is disabled, return immediately
-        if self.optimization_level == 0 {
+        self.stats = OptimizationStats::default();
+        self.errors.clear();
+        self.reported.clear();
+        if self.passes.is_empty() && self.plugins.is_empty() {
             return self.stats.clone();
         }
         
-        // Run optimization passes on each function
-        for function in &mut program.functions {
-            self.optimize_function(function);
+        let mut fixpoint = false;
+        while self.stats.iterations < self.max_iterations {
+            let before = program.clone();
+            self.run_passes(program);
+            self.stats.iterations += 1;
+            if *program == before {
+                fixpoint = true;
+                break;
+            }
         }
         
+        // Rewritten and copied nodes need ids of their own
+        program.number_nodes();
+        
+        debug_assert!(
+            !fixpoint || self.is_fixpoint(program),
+            "optimizer passes {:?} are not idempotent",
+            self.passes
+        );
+        
         self.stats.clone()
     }
     
-    /// Optimize a single function
-    fn optimize_function(&mut self, function: &mut Function) {
-        self.constant_values.clear();
-
-        if self.optimization_level >= 1 {
-            self.apply_strength_reduction_to_block(&mut function.body);
+    fn run_passes(&mut self, program: &mut Program) {
+        for pass in self.passes.clone() {
+            self.run_pass(pass, program);
+        }
+        for plugin in &self.plugins {
+            self.stats.plugin_changes += plugin.optimize(program);
+        }
+    }
+    
+    /// Whether a fresh run of the pass list would leave the program as it is
+    fn is_fixpoint(&self, program: &Program) -> bool {
+        let mut copy = program.clone();
+        Optimizer::with_passes(&self.passes)
+            .with_plugins(&self.plugins)
+            .with_fast_math(self.fast_math)
+            .with_overflow(self.overflow)
+            .run_passes(&mut copy);
+        copy.number_nodes();
+        copy == *program
+    }
+    
+    /// Run one pass over the whole program. Function-level passes skip
+    /// functions marked @noopt.
+    fn run_pass(&mut self, pass: Pass, program: &mut Program) {
+        match pass {
+            Pass::Specialize => self.specialize_constant_arguments(program),
+            Pass::DeadFunctions => self.eliminate_dead_functions(program),
+            Pass::Inline => {
+                self.collect_inline_bodies(program);
+                self.for_each_function(program, |optimizer, body| optimizer.inline_calls_in_block(body));
+            }
+            Pass::StrengthReduce => {
+                self.for_each_function(program, |optimizer, body| optimizer.apply_strength_reduction_to_block(body));
+            }
+            Pass::ConstFold => {
+                self.for_each_function(program, |optimizer, body| optimizer.fold_constants_in_block(body));
+            }
+            Pass::ConstProp => {
+                self.for_each_function(program, |optimizer, body| {
+                    optimizer.constant_values.clear();
+                    optimizer.constant_arrays.clear();
+                    optimizer.track_constants_in_block(body);
+                    optimizer.propagate_constants_in_block(body);
+                });
+            }
+            Pass::RangeFold => self.fold_decided_comparisons(program),
+            Pass::StackStrings => self.move_strings_to_stack(program),
+            Pass::DCE => {
+                self.for_each_function(program, |optimizer, body| optimizer.eliminate_dead_code_in_block(body));
+            }
+        }
+    }
+    
+    /// Replace comparisons the range analysis decided with their result.
+    /// Their operands are plain int arithmetic, so nothing with side effects is lost.
+    fn fold_decided_comparisons(&mut self, program: &mut Program) {
+        // The analysis reports by node id, so nodes added by earlier passes need one
+        program.number_nodes();
+        let decided = bounds::analyze(program).comparisons;
+        if decided.is_empty() {
+            return;
         }
         
-        self.fold_constants_in_block(&mut function.body);
+        self.for_each_function(program, |optimizer, body| {
+            for_each_expression_in_block(body, &mut |expr| {
+                let Expression::Binary(binary) = expr else { return };
+                let Some(&result) = decided.get(&binary.id) else { return };
+                *expr = Expression::Literal(LiteralExpr {
+                    value: Literal::Boolean(result),
+                    span: binary.span.clone(),
+                    id: NodeId::default(),
+                });
+                optimizer.stats.comparisons_decided += 1;
+            });
+        });
+    }
+    
+    /// Wrap the strings escape analysis found never leave their function in
+    /// a `__stack` call, which the C builds in a buffer on the stack
+    fn move_strings_to_stack(&mut self, program: &mut Program) {
+        // The analysis reports by node id, so nodes added by earlier passes need one
+        program.number_nodes();
+        let names: HashSet<String> = program.functions.iter().map(|f| f.name.clone()).collect();
+        let names: HashSet<&str> = names.iter().map(String::as_str).collect();
+        for function in &mut program.functions {
+            if function.has_attribute("noopt") {
+                continue;
+            }
+            let sites = escape::stack_strings(function, &names);
+            if sites.is_empty() {
+                continue;
+            }
+            for_each_expression_in_block(&mut function.body, &mut |expr| {
+                if !sites.contains(&expr.id()) {
+                    return;
+                }
+                let span = expr.span().clone();
+                let value = std::mem::replace(expr, Expression::Identifier(IdentifierExpr {
+                    name: String::new(),
+                    span: span.clone(),
+                    id: NodeId::default(),
+                }));
+                *expr = Expression::Call(CallExpr {
+                    function: STACK_STRING.to_string(),
+                    args: vec![value],
+                    span,
+                    id: NodeId::default(),
+                });
+                self.stats.allocations_elided += 1;
+            });
+        }
+    }
+    
+    fn for_each_function(&mut self, program: &mut Program, mut f: impl FnMut(&mut Self, &mut Block)) {
+        for function in &mut program.functions {
+            if !function.has_attribute("noopt") {
+                f(self, &mut function.body);
+            }
+        }
+    }
+    
+    /// Clone functions whose call sites all pass the same literal for a parameter.
+    /// The clone drops that parameter and binds it with a `let` at the top of the
+    /// body, where constant propagation and folding can use it. Calls are
+    /// redirected to the clone; the original is left for dead function elimination.
+    fn specialize_constant_arguments(&mut self, program: &mut Program) {
+        let candidates: HashMap<String, usize> = program.functions.iter()
+            .filter(|f| f.name != "main" && !f.params.is_empty())
+            .filter(|f| !f.has_attribute("noopt") && !f.has_attribute("inline"))
+            .map(|f| (f.name.clone(), f.params.len()))
+            .collect();
+        
+        // For each parameter: None until a call is seen, then Some(literal)
+        // while every call agrees, and Some(None) once they differ
+        let mut arguments: HashMap<String, Vec<Option<Option<Literal>>>> = HashMap::new();
+        for function in program.functions.iter_mut().filter(|f| !f.has_attribute("noopt")) {
+            for_each_call_in_block(&mut function.body, &mut |call| {
+                let Some(&count) = candidates.get(&call.function) else { return };
+                if call.args.len() != count {
+                    return;
+                }
+                let seen = arguments.entry(call.function.clone()).or_insert_with(|| vec![None; count]);
+                for (slot, arg) in seen.iter_mut().zip(&call.args) {
+                    let value = match arg {
+                        Expression::Literal(LiteralExpr {
+                            value: value @ (Literal::Integer(_) | Literal::Float(_) | Literal::Boolean(_) | Literal::String(_)),
+                            ..
+                        }) => Some(value.clone()),
+                        _ => None,
+                    };
+                    *slot = match slot.take() {
+                        None => Some(value),
+                        Some(previous) if previous == value => Some(previous),
+                        Some(_) => Some(None),
+                    };
+                }
+            });
+        }
         
-        if self.optimization_level >= 2 {
-            self.track_constants_in_block(&function.body);
+        let mut taken: HashSet<String> = program.functions.iter().map(|f| f.name.clone()).collect();
+        // Original name -> (clone name, constant parameter positions)
+        let mut clones: HashMap<String, (String, Vec<usize>)> = HashMap::new();
+        let mut specialized = Vec::new();
+        for function in &program.functions {
+            let Some(seen) = arguments.get(&function.name) else { continue };
+            let constants: Vec<(usize, Literal)> = seen.iter()
+                .enumerate()
+                .filter_map(|(i, slot)| slot.clone().flatten().map(|value| (i, value)))
+                .collect();
+            if constants.is_empty() {
+                continue;
+            }
             
-            self.propagate_constants_in_block(&mut function.body);
+            let mut name = format!("{}__spec", function.name);
+            let mut suffix = 1;
+            while !taken.insert(name.clone()) {
+                suffix += 1;
+                name = format!("{}__spec{}", function.name, suffix);
+            }
             
-            self.fold_constants_in_block(&mut function.body);
-
-            self.apply_strength_reduction_to_block(&mut function.body);
-
-            self.fold_constants_in_block(&mut function.body);
+            let mut clone = function.clone();
+            clone.name = name.clone();
+            clone.id = NodeId::default();
+            let bindings = constants.iter().map(|(i, value)| {
+                let param = &function.params[*i];
+                Statement::Let(LetStmt {
+                    name: param.name.clone(),
+                    typ: Some(param.typ.clone()),
+                    value: Some(Expression::Literal(LiteralExpr {
+                        value: value.clone(),
+                        span: param.span.clone(),
+                        id: NodeId::default(),
+                    })),
+                    span: param.span.clone(),
+                    id: NodeId::default(),
+                })
+            });
+            clone.body.statements.splice(0..0, bindings);
+            let positions: Vec<usize> = constants.iter().map(|(i, _)| *i).collect();
+            clone.params = clone.params.into_iter()
+                .enumerate()
+                .filter(|(i, _)| !positions.contains(i))
+                .map(|(_, param)| param)
+                .collect();
+            
+            clones.insert(function.name.clone(), (name, positions));
+            specialized.push(clone);
+        }
+        
+        if specialized.is_empty() {
+            return;
+        }
+        self.stats.functions_specialized += specialized.len();
+        program.functions.extend(specialized);
+        
+        for function in program.functions.iter_mut().filter(|f| !f.has_attribute("noopt")) {
+            for_each_call_in_block(&mut function.body, &mut |call| {
+                let Some((name, positions)) = clones.get(&call.function) else { return };
+                if call.args.len() != candidates[&call.function] {
+                    return;
+                }
+                call.function = name.clone();
+                let args = std::mem::take(&mut call.args);
+                call.args = args.into_iter()
+                    .enumerate()
+                    .filter(|(i, _)| !positions.contains(i))
+                    .map(|(_, arg)| arg)
+                    .collect();
+            });
+        }
+    }
+    
+    /// Drop functions that cannot be reached from `main` through the call graph.
+    /// Programs without a `main` are left alone.
+    fn eliminate_dead_functions(&mut self, program: &mut Program) {
+        if !program.functions.iter().any(|f| f.name == "main") {
+            return;
+        }
+        
+        let call_graph: HashMap<&str, HashSet<String>> = program.functions.iter()
+            .map(|f| (f.name.as_str(), fanout::called_functions(f)))
+            .collect();
+        
+        let mut reachable = HashSet::new();
+        let mut pending = vec!["main".to_string()];
+        while let Some(name) = pending.pop() {
+            if let Some(callees) = call_graph.get(name.as_str()) {
+                if reachable.insert(name) {
+                    pending.extend(callees.iter().cloned());
+                }
+            }
+        }
+        
+        let before = program.functions.len();
+        program.functions.retain(|f| reachable.contains(&f.name));
+        self.stats.functions_removed += before - program.functions.len();
+    }
+    
+    /// Remember the `@inline` functions simple enough to substitute at call sites
+    fn collect_inline_bodies(&mut self, program: &Program) {
+        self.inline_bodies.clear();
+        for function in &program.functions {
+            if !function.has_attribute("inline") {
+                continue;
+            }
+            if let [Statement::Return(ReturnStmt { value: Some(value), .. })] = function.body.statements.as_slice() {
+                // Assignments inside the body would write to the caller's variables
+                if Self::contains_assignment(value) {
+                    continue;
+                }
+                let params = function.params.iter().map(|p| p.name.clone()).collect();
+                self.inline_bodies.insert(function.name.clone(), (params, value.clone()));
+            }
+        }
+    }
+    
+    fn inline_calls_in_block(&mut self, block: &mut Block) {
+        for statement in &mut block.statements {
+            self.inline_calls_in_statement(statement);
+        }
+    }
+    
+    fn inline_calls_in_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Const(const_stmt) => {
+                self.inline_calls_in_expression(&mut const_stmt.value);
+            }
+            Statement::LetTuple(let_tuple) => {
+                self.inline_calls_in_expression(&mut let_tuple.value);
+            }
+            Statement::Let(let_stmt) => {
+                if let Some(ref mut value) = let_stmt.value {
+                    self.inline_calls_in_expression(value);
+                }
+            }
+            Statement::Display(display_stmt) => {
+                for expr in &mut display_stmt.expressions {
+                    self.inline_calls_in_expression(expr);
+                }
+            }
+            Statement::If(if_stmt) => {
+                self.inline_calls_in_expression(&mut if_stmt.condition);
+                self.inline_calls_in_block(&mut if_stmt.then_block);
+                if let Some(ref mut else_block) = if_stmt.else_block {
+                    self.inline_calls_in_block(else_block);
+                }
+            }
+            Statement::While(while_stmt) => {
+                self.inline_calls_in_expression(&mut while_stmt.condition);
+                self.inline_calls_in_block(&mut while_stmt.body);
+            }
+            Statement::DoWhile(do_while) => {
+                self.inline_calls_in_block(&mut do_while.body);
+                self.inline_calls_in_expression(&mut do_while.condition);
+            }
+            Statement::For(for_stmt) => {
+                if let Some(ref mut init) = for_stmt.init {
+                    self.inline_calls_in_statement(init);
+                }
+                if let Some(ref mut condition) = for_stmt.condition {
+                    self.inline_calls_in_expression(condition);
+                }
+                if let Some(ref mut update) = for_stmt.update {
+                    self.inline_calls_in_expression(update);
+                }
+                self.inline_calls_in_block(&mut for_stmt.body);
+            }
+            Statement::Return(return_stmt) => {
+                if let Some(ref mut value) = return_stmt.value {
+                    self.inline_calls_in_expression(value);
+                }
+            }
+            Statement::Expression(expr_stmt) => {
+                self.inline_calls_in_expression(&mut expr_stmt.expression);
+            }
+            Statement::Block(block) => {
+                self.inline_calls_in_block(block);
+            }
+            Statement::Defer(defer_stmt) => {
+                self.inline_calls_in_block(&mut defer_stmt.body);
+            }
+            Statement::Spawn(spawn_stmt) => {
+                self.inline_calls_in_block(&mut spawn_stmt.body);
+            }
+            Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+    
+    /// Replace calls to simple `@inline` functions with their result expression.
+    /// Arguments may be evaluated any number of times afterwards, so calls
+    /// are only inlined when every argument is free of side effects.
+    fn inline_calls_in_expression(&mut self, expr: &mut Expression) {
+        match expr {
+            Expression::Binary(binary) => {
+                self.inline_calls_in_expression(&mut binary.left);
+                self.inline_calls_in_expression(&mut binary.right);
+            }
+            Expression::Unary(unary) => {
+                self.inline_calls_in_expression(&mut unary.operand);
+            }
+            Expression::Call(call) => {
+                for arg in &mut call.args {
+                    self.inline_calls_in_expression(arg);
+                }
+                
+                let Some((params, body)) = self.inline_bodies.get(&call.function) else {
+                    return;
+                };
+                if params.len() != call.args.len() || !call.args.iter().all(Self::is_side_effect_free) {
+                    return;
+                }
+                
+                let bindings: HashMap<&str, &Expression> = params.iter()
+                    .map(String::as_str)
+                    .zip(&call.args)
+                    .collect();
+                let mut inlined = body.clone();
+                Self::substitute_parameters(&mut inlined, &bindings);
+                *expr = inlined;
+                self.stats.functions_inlined += 1;
+            }
+            Expression::Index(index) => {
+                self.inline_calls_in_expression(&mut index.array);
+                self.inline_calls_in_expression(&mut index.index);
+            }
+            Expression::Assign(assign) => {
+                self.inline_calls_in_expression(&mut assign.value);
+            }
+            Expression::Literal(LiteralExpr { value: Literal::Tuple(elements), .. }) => {
+                for element in elements {
+                    self.inline_calls_in_expression(element);
+                }
+            }
+            Expression::Literal(_) | Expression::Identifier(_) => {}
+        }
+    }
+    
+    fn is_side_effect_free(expr: &Expression) -> bool {
+        match expr {
+            Expression::Literal(LiteralExpr { value: Literal::Tuple(_), .. }) => false,
+            Expression::Literal(_) | Expression::Identifier(_) => true,
+            Expression::Binary(binary) => {
+                Self::is_side_effect_free(&binary.left) && Self::is_side_effect_free(&binary.right)
+            }
+            Expression::Unary(unary) => Self::is_side_effect_free(&unary.operand),
+            Expression::Index(index) => {
+                Self::is_side_effect_free(&index.array) && Self::is_side_effect_free(&index.index)
+            }
+            Expression::Call(_) | Expression::Assign(_) => false,
+        }
+    }
+    
+    fn contains_assignment(expr: &Expression) -> bool {
+        match expr {
+            Expression::Assign(_) => true,
+            Expression::Binary(binary) => {
+                Self::contains_assignment(&binary.left) || Self::contains_assignment(&binary.right)
+            }
+            Expression::Unary(unary) => Self::contains_assignment(&unary.operand),
+            Expression::Call(call) => call.args.iter().any(Self::contains_assignment),
+            Expression::Index(index) => {
+                Self::contains_assignment(&index.array) || Self::contains_assignment(&index.index)
+            }
+            Expression::Literal(LiteralExpr { value: Literal::Tuple(elements), .. }) => {
+                elements.iter().any(Self::contains_assignment)
+            }
+            Expression::Literal(_) | Expression::Identifier(_) => false,
+        }
+    }
+    
+    /// Replace parameter names in an inlined body with the call's arguments
+    fn substitute_parameters(expr: &mut Expression, bindings: &HashMap<&str, &Expression>) {
+        match expr {
+            Expression::Identifier(ident) => {
+                if let Some(arg) = bindings.get(ident.name.as_str()) {
+                    *expr = (*arg).clone();
+                }
+            }
+            Expression::Binary(binary) => {
+                Self::substitute_parameters(&mut binary.left, bindings);
+                Self::substitute_parameters(&mut binary.right, bindings);
+            }
+            Expression::Unary(unary) => {
+                Self::substitute_parameters(&mut unary.operand, bindings);
+            }
+            Expression::Call(call) => {
+                for arg in &mut call.args {
+                    Self::substitute_parameters(arg, bindings);
+                }
+            }
+            Expression::Index(index) => {
+                Self::substitute_parameters(&mut index.array, bindings);
+                Self::substitute_parameters(&mut index.index, bindings);
+            }
+            Expression::Assign(assign) => {
+                Self::substitute_parameters(&mut assign.value, bindings);
+            }
+            Expression::Literal(LiteralExpr { value: Literal::Tuple(elements), .. }) => {
+                for element in elements {
+                    Self::substitute_parameters(element, bindings);
+                }
+            }
+            Expression::Literal(_) => {}
         }
-        self.eliminate_dead_code_in_block(&mut function.body);
     }
     
     /// Fold constants in a block of statements
@@ -92,6 +716,9 @@ impl Optimizer {
                 // Fold constants in const value
                 self.fold_constants_in_expression(&mut const_stmt.value);
             }
+            Statement::LetTuple(let_tuple) => {
+                self.fold_constants_in_expression(&mut let_tuple.value);
+            }
             Statement::Display(display_stmt) => {
                 // Fold constants in each expression
                 for expr in &mut display_stmt.expressions {
@@ -137,6 +764,12 @@ impl Optimizer {
             Statement::Block(block) => {
                 self.fold_constants_in_block(block);
             }
+            Statement::Defer(defer_stmt) => {
+                self.fold_constants_in_block(&mut defer_stmt.body);
+            }
+            Statement::Spawn(spawn_stmt) => {
+                self.fold_constants_in_block(&mut spawn_stmt.body);
+            }
             Statement::Break(_) | Statement::Continue(_) => {
                 // Nothing to optimize
             }
@@ -158,6 +791,7 @@ impl Optimizer {
                     *expr = Expression::Literal(LiteralExpr {
                         value: folded,
                         span: binary.span.clone(),
+                        id: NodeId::default(),
                     });
                     self.stats.constants_folded += 1;
                 }
@@ -171,6 +805,7 @@ impl Optimizer {
                     *expr = Expression::Literal(LiteralExpr {
                         value: folded,
                         span: unary.span.clone(),
+                        id: NodeId::default(),
                     });
                     self.stats.constants_folded += 1;
                 }
@@ -188,6 +823,11 @@ impl Optimizer {
             Expression::Assign(assign) => {
                 self.fold_constants_in_expression(&mut assign.value);
             }
+            Expression::Literal(LiteralExpr { value: Literal::Tuple(elements), .. }) => {
+                for element in elements {
+                    self.fold_constants_in_expression(element);
+                }
+            }
             Expression::Literal(_) | Expression::Identifier(_) => {
                 // Already constants or variables, nothing to fold
             }
@@ -195,7 +835,7 @@ impl Optimizer {
     }
     
     /// Try to fold a binary operation if both operands are constants
-    fn try_fold_binary(&self, binary: &BinaryExpr) -> Option<Literal> {
+    fn try_fold_binary(&mut self, binary: &BinaryExpr) -> Option<Literal> {
         // Extract literal values from both sides
         let left_lit = if let Expression::Literal(lit) = &*binary.left {
             Some(&lit.value)
@@ -211,36 +851,45 @@ impl Optimizer {
         
         // If both are literals, try to fold
         match (left_lit, &binary.op, right_lit) {
-            // Integer arithmetic with overflow checking
-            (Some(Literal::Integer(l)), BinaryOp::Add, Some(Literal::Integer(r))) => {
-                l.checked_add(*r).map(Literal::Integer)
-            }
-            (Some(Literal::Integer(l)), BinaryOp::Subtract, Some(Literal::Integer(r))) => {
-                l.checked_sub(*r).map(Literal::Integer)
-            }
-            (Some(Literal::Integer(l)), BinaryOp::Multiply, Some(Literal::Integer(r))) => {
-                l.checked_mul(*r).map(Literal::Integer)
-            }
-            (Some(Literal::Integer(l)), BinaryOp::Divide, Some(Literal::Integer(r))) if *r != 0 => {
-                l.checked_div(*r).map(Literal::Integer)
-            }
-            (Some(Literal::Integer(l)), BinaryOp::Modulo, Some(Literal::Integer(r))) if *r != 0 => {
-                l.checked_rem(*r).map(Literal::Integer)
+            // Integer arithmetic. Division and modulo truncate toward zero
+            // (-7 / 2 == -3, -7 % 2 == -1), matching the generated C.
+            (Some(Literal::Integer(l)), op @ (BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo), Some(Literal::Integer(r))) => {
+                let (value, overflowed) = match op {
+                    BinaryOp::Add => l.overflowing_add(*r),
+                    BinaryOp::Subtract => l.overflowing_sub(*r),
+                    BinaryOp::Multiply => l.overflowing_mul(*r),
+                    BinaryOp::Divide if *r != 0 => l.overflowing_div(*r),
+                    BinaryOp::Modulo if *r != 0 => l.overflowing_rem(*r),
+                    _ => return None,
+                };
+                if !overflowed {
+                    return Some(Literal::Integer(value));
+                }
+                match self.overflow {
+                    // i32::MIN / -1 and i32::MIN % -1 crash even with -fwrapv,
+                    // so those are left for runtime
+                    OverflowMode::Wrap if matches!(op, BinaryOp::Divide | BinaryOp::Modulo) => None,
+                    OverflowMode::Wrap => Some(Literal::Integer(value)),
+                    OverflowMode::Trap => {
+                        self.report_overflow(format!("{} {} {}", l, op.symbol(), r), &binary.span);
+                        None
+                    }
+                }
             }
             
-            // Rest of the cases remain the same...
-            // Float arithmetic
+            // Float arithmetic; overflow to infinity is left for runtime.
+            // Float comparisons are never folded.
             (Some(Literal::Float(l)), BinaryOp::Add, Some(Literal::Float(r))) => {
-                Some(Literal::Float(l + r))
+                Self::finite_float(l + r)
             }
             (Some(Literal::Float(l)), BinaryOp::Subtract, Some(Literal::Float(r))) => {
-                Some(Literal::Float(l - r))
+                Self::finite_float(l - r)
             }
             (Some(Literal::Float(l)), BinaryOp::Multiply, Some(Literal::Float(r))) => {
-                Some(Literal::Float(l * r))
+                Self::finite_float(l * r)
             }
             (Some(Literal::Float(l)), BinaryOp::Divide, Some(Literal::Float(r))) if *r != 0.0 => {
-                Some(Literal::Float(l / r))
+                Self::finite_float(l / r)
             }
             
             // Integer comparisons
@@ -276,8 +925,21 @@ impl Optimizer {
         }
     }
     
+    fn report_overflow(&mut self, expression: String, span: &Span) {
+        if self.reported.insert((span.start, span.end)) {
+            self.errors.push(SemanticError::ConstantOverflow {
+                expression,
+                span: (span.start..span.end).into(),
+            });
+        }
+    }
+    
+    fn finite_float(value: f64) -> Option<Literal> {
+        value.is_finite().then_some(Literal::Float(value))
+    }
+    
     /// Try to fold a unary operation
-    fn try_fold_unary(&self, unary: &UnaryExpr) -> Option<Literal> {
+    fn try_fold_unary(&mut self, unary: &UnaryExpr) -> Option<Literal> {
         let operand_lit = if let Expression::Literal(lit) = &*unary.operand {
             Some(&lit.value)
         } else {
@@ -285,7 +947,13 @@ impl Optimizer {
         };
         
         match (&unary.op, operand_lit) {
-            (UnaryOp::Negate, Some(Literal::Integer(n))) => Some(Literal::Integer(-n)),
+            (UnaryOp::Negate, Some(Literal::Integer(n))) => match (n.overflowing_neg(), self.overflow) {
+                ((value, false), _) | ((value, true), OverflowMode::Wrap) => Some(Literal::Integer(value)),
+                ((_, true), OverflowMode::Trap) => {
+                    self.report_overflow(format!("-({})", n), &unary.span);
+                    None
+                }
+            },
             (UnaryOp::Negate, Some(Literal::Float(f))) => Some(Literal::Float(-f)),
             (UnaryOp::Not, Some(Literal::Boolean(b))) => Some(Literal::Boolean(!b)),
             _ => None
@@ -301,6 +969,17 @@ impl Optimizer {
             match statement {
                 Statement::Const(const_stmt) => {
                     match &const_stmt.value {
+                        Expression::Literal(LiteralExpr { value: Literal::Array(elements), .. }) => {
+                            let values: Option<Vec<Literal>> = elements.iter()
+                                .map(|element| match element {
+                                    Expression::Literal(lit) => Some(lit.value.clone()),
+                                    _ => None,
+                                })
+                                .collect();
+                            if let Some(values) = values {
+                                self.constant_arrays.insert(const_stmt.name.clone(), values);
+                            }
+                        }
                         Expression::Literal(lit) => {
                             self.constant_values.insert(
                                 const_stmt.name.clone(),
@@ -318,15 +997,19 @@ impl Optimizer {
                         _ => {}
                     }
                 }
-                Statement::Let(let_stmt) => {
-                    // Only track if this variable is NEVER reassigned
-                    if !reassigned.contains(&let_stmt.name) {
-                        if let Some(Expression::Literal(lit)) = &let_stmt.value {
+                // Only track if this variable is NEVER reassigned
+                Statement::Let(let_stmt) if !reassigned.contains(&let_stmt.name) => {
+                    // Array elements can still be assigned, and an array
+                    // literal is not a value C accepts in place of a name
+                    match &let_stmt.value {
+                        Some(Expression::Literal(LiteralExpr { value: Literal::Array(_), .. })) => {}
+                        Some(Expression::Literal(lit)) => {
                             self.constant_values.insert(
                                 let_stmt.name.clone(),
                                 lit.value.clone(),
                             );
                         }
+                        _ => {}
                     }
                 }
                 _ => {
@@ -391,6 +1074,16 @@ impl Optimizer {
                     self.collect_assigned_variables_in_statement(stmt, assigned);
                 }
             }
+            Statement::Defer(defer_stmt) => {
+                for stmt in &defer_stmt.body.statements {
+                    self.collect_assigned_variables_in_statement(stmt, assigned);
+                }
+            }
+            Statement::Spawn(spawn_stmt) => {
+                for stmt in &spawn_stmt.body.statements {
+                    self.collect_assigned_variables_in_statement(stmt, assigned);
+                }
+            }
             // Let, Const, Display, Return, Break, Continue
             // don't reassign existing variables
             _ => {}
@@ -441,6 +1134,9 @@ impl Optimizer {
             Statement::Const(const_stmt) => {
                 self.propagate_in_expression(&mut const_stmt.value);
             }
+            Statement::LetTuple(let_tuple) => {
+                self.propagate_in_expression(&mut let_tuple.value);
+            }
             Statement::Let(let_stmt) => {
                 if let Some(ref mut value) = let_stmt.value {
                     self.propagate_in_expression(value);
@@ -489,6 +1185,12 @@ impl Optimizer {
             Statement::Block(block) => {
                 self.propagate_constants_in_block(block);
             }
+            Statement::Defer(defer_stmt) => {
+                self.propagate_constants_in_block(&mut defer_stmt.body);
+            }
+            Statement::Spawn(spawn_stmt) => {
+                self.propagate_constants_in_block(&mut spawn_stmt.body);
+            }
             Statement::Break(_) | Statement::Continue(_) => {
                 // Nothing to propagate
             }
@@ -503,6 +1205,7 @@ impl Optimizer {
                     *expr = Expression::Literal(LiteralExpr {
                         value: constant_value.clone(),
                         span: id.span.clone(),
+                        id: NodeId::default(),
                     });
                     self.stats.constants_propagated += 1;
                 }
@@ -522,16 +1225,39 @@ impl Optimizer {
             Expression::Index(index) => {
                 self.propagate_in_expression(&mut index.array);
                 self.propagate_in_expression(&mut index.index);
+                if let Some(element) = self.constant_array_element(index) {
+                    *expr = Expression::Literal(LiteralExpr {
+                        value: element,
+                        span: index.span.clone(),
+                        id: NodeId::default(),
+                    });
+                    self.stats.constants_propagated += 1;
+                }
             }
             Expression::Assign(assign) => {
                 self.propagate_in_expression(&mut assign.value);
             }
+            Expression::Literal(LiteralExpr { value: Literal::Tuple(elements), .. }) => {
+                for element in elements {
+                    self.propagate_in_expression(element);
+                }
+            }
             Expression::Literal(_) => {
                 // Already a literal, nothing to propagate
             }
         }
     }
     
+    /// The element `TABLE[i]` reads when `TABLE` is a `const` array and `i` a
+    /// literal inside its bounds. Out of range indexes are left for the
+    /// runtime bounds check to report.
+    fn constant_array_element(&self, index: &IndexExpr) -> Option<Literal> {
+        let Expression::Identifier(array) = index.array.as_ref() else { return None };
+        let Expression::Literal(LiteralExpr { value: Literal::Integer(i), .. }) = index.index.as_ref() else { return None };
+        let elements = self.constant_arrays.get(&array.name)?;
+        usize::try_from(*i).ok().and_then(|i| elements.get(i)).cloned()
+    }
+    
     /// Eliminate dead code in a block of statements
     fn eliminate_dead_code_in_block(&mut self, block: &mut Block) {
         let mut new_statements = Vec::new();
@@ -539,17 +1265,12 @@ impl Optimizer {
         
         for statement in block.statements.drain(..) {
             if is_dead {
-                // Everything after a return/break/continue is dead
+                // Control never gets past the previous statement
                 self.stats.dead_code_removed += 1;
                 continue;
             }
             
-            // Check if this statement makes subsequent code dead
             match &statement {
-                Statement::Return(_) | Statement::Break(_) | Statement::Continue(_) => {
-                    is_dead = true;
-                    new_statements.push(statement);
-                }
                 Statement::If(if_stmt) => {
                     // Check if condition is a constant
                     if let Expression::Literal(lit) = &if_stmt.condition {
@@ -591,30 +1312,23 @@ impl Optimizer {
                         new_statements.push(Statement::If(modified_if));
                     }
                 }
+                Statement::While(_) | Statement::DoWhile(_) | Statement::For(_)
+                    if Loop::of(&statement).is_some_and(|lp| !lp.body_may_run() && Self::init_is_removable(lp.init)) =>
+                {
+                    // The condition is false from the start; a do-while still runs once
+                    self.stats.dead_code_removed += 1;
+                }
                 Statement::While(while_stmt) => {
-                    // Check if condition is constant false
-                    if let Expression::Literal(lit) = &while_stmt.condition {
-                        if let Literal::Boolean(false) = &lit.value {
-                            // While false - entire loop is dead
-                            self.stats.dead_code_removed += 1;
-                            continue; // Skip this statement entirely
-                        }
-                    }
-                    
-                    // Otherwise recurse into the body
                     let mut modified_while = while_stmt.clone();
                     self.eliminate_dead_code_in_block(&mut modified_while.body);
                     new_statements.push(Statement::While(modified_while));
                 }
                 Statement::DoWhile(do_while) => {
-                    // Do-while always executes at least once
                     let mut modified = do_while.clone();
                     self.eliminate_dead_code_in_block(&mut modified.body);
                     new_statements.push(Statement::DoWhile(modified));
                 }
                 Statement::For(for_stmt) => {
-                    // Check if we can determine the loop never executes
-                    // This is complex, so for now just recurse
                     let mut modified = for_stmt.clone();
                     self.eliminate_dead_code_in_block(&mut modified.body);
                     new_statements.push(Statement::For(modified));
@@ -624,15 +1338,41 @@ impl Optimizer {
                     self.eliminate_dead_code_in_block(&mut modified);
                     new_statements.push(Statement::Block(modified));
                 }
+                Statement::Defer(defer_stmt) => {
+                    let mut modified = defer_stmt.clone();
+                    self.eliminate_dead_code_in_block(&mut modified.body);
+                    new_statements.push(Statement::Defer(modified));
+                }
+                Statement::Spawn(spawn_stmt) => {
+                    let mut modified = spawn_stmt.clone();
+                    self.eliminate_dead_code_in_block(&mut modified.body);
+                    new_statements.push(Statement::Spawn(modified));
+                }
                 _ => {
                     // Keep the statement as is
                     new_statements.push(statement);
                 }
             }
+            
+            // Everything after a send, break, continue or endless loop is dead
+            if new_statements.last().is_some_and(|last| !control_flow::completes(last)) {
+                is_dead = true;
+            }
         }
         
         block.statements = new_statements;
     }
+    
+    /// Whether a loop's init can go along with a loop that never runs: it
+    /// only declares its counter, so nothing else can see it
+    fn init_is_removable(init: Option<&Statement>) -> bool {
+        match init {
+            None => true,
+            Some(Statement::Let(let_stmt)) => let_stmt.value.as_ref().is_none_or(Self::is_side_effect_free),
+            Some(Statement::Expression(expr_stmt)) => Self::is_side_effect_free(&expr_stmt.expression),
+            Some(_) => false,
+        }
+    }
 
     fn apply_strength_reduction_to_block(&mut self, block: &mut Block) {
         for statement in &mut block.statements {
@@ -645,6 +1385,9 @@ impl Optimizer {
             Statement::Const(const_stmt) => {
                 self.apply_strength_reduction_to_expression(&mut const_stmt.value);
             }
+            Statement::LetTuple(let_tuple) => {
+                self.apply_strength_reduction_to_expression(&mut let_tuple.value);
+            }
             Statement::Let(let_stmt) => {
                 if let Some(ref mut value) = let_stmt.value {
                     self.apply_strength_reduction_to_expression(value);
@@ -693,6 +1436,12 @@ impl Optimizer {
             Statement::Block(block) => {
                 self.apply_strength_reduction_to_block(block);
             }
+            Statement::Defer(defer_stmt) => {
+                self.apply_strength_reduction_to_block(&mut defer_stmt.body);
+            }
+            Statement::Spawn(spawn_stmt) => {
+                self.apply_strength_reduction_to_block(&mut spawn_stmt.body);
+            }
             Statement::Break(_) | Statement::Continue(_) => {
                 // No expressions to optimize
             }
@@ -729,6 +1478,11 @@ impl Optimizer {
             Expression::Assign(assign) => {
                 self.apply_strength_reduction_to_expression(&mut assign.value);
             }
+            Expression::Literal(LiteralExpr { value: Literal::Tuple(elements), .. }) => {
+                for element in elements {
+                    self.apply_strength_reduction_to_expression(element);
+                }
+            }
             Expression::Literal(_) | Expression::Identifier(_) => {
                 // No optimization needed for literals and identifiers
             }
@@ -736,9 +1490,9 @@ impl Optimizer {
     }
 
     fn try_strength_reduce_binary(&mut self, binary: &mut BinaryExpr) -> Option<Expression> {
-        // Only apply at optimization level 1 or higher
-        if self.optimization_level < 1 {
-            return None;
+        if let Some(reduced) = self.try_reduce_float_identity(binary) {
+            self.stats.strength_reductions += 1;
+            return Some(reduced);
         }
         
         match binary.op {
@@ -768,6 +1522,7 @@ impl Optimizer {
                         return Some(Expression::Literal(LiteralExpr {
                             value: Literal::Integer(0),
                             span: binary.span.clone(),
+                            id: NodeId::default(),
                         }));
                     } else if n == 1 {
                         // x * 1 = x
@@ -780,12 +1535,12 @@ impl Optimizer {
                             op: UnaryOp::Negate,
                             operand: Box::new(other_side.clone()),
                             span: binary.span.clone(),
+                            parenthesized: false,
+                            id: NodeId::default(),
                         }));
                     } else if self.is_power_of_two(n) {
                         // x * 2^k -> mark for shift optimization
-                        let shift_amount = n.trailing_zeros();
-                        binary.optimization_hint = Some(OptimizationHint::ShiftLeft(shift_amount));
-                        self.stats.strength_reductions += 1;
+                        self.set_hint(binary, OptimizationHint::ShiftLeft(n.trailing_zeros()));
                         // Return None to keep the expression but with hint
                         return None;
                     }
@@ -807,13 +1562,37 @@ impl Optimizer {
                                 op: UnaryOp::Negate,
                                 operand: Box::new((*binary.left).clone()),
                                 span: binary.span.clone(),
+                                parenthesized: false,
+                                id: NodeId::default(),
                             }));
                         } else if *n != 0 && self.is_power_of_two(*n) {
                             // x / 2^k -> mark for shift optimization
-                            let shift_amount = (*n).trailing_zeros();
-                            binary.optimization_hint = Some(OptimizationHint::ShiftRight(shift_amount));
-                            self.stats.strength_reductions += 1;
+                            // (codegen rounds negative x toward zero before shifting)
+                            let hint = OptimizationHint::ShiftRight((*n).trailing_zeros());
+                            self.set_hint(binary, hint);
                             return None;
+                        } else if let Some(m) = n.checked_neg().filter(|m| self.is_power_of_two(*m)) {
+                            // x / -2^k -> -(x / 2^k); division truncates, so the sign factors out
+                            self.stats.strength_reductions += 1;
+                            return Some(Expression::Unary(UnaryExpr {
+                                op: UnaryOp::Negate,
+                                operand: Box::new(Expression::Binary(BinaryExpr {
+                                    left: binary.left.clone(),
+                                    op: BinaryOp::Divide,
+                                    right: Box::new(Expression::Literal(LiteralExpr {
+                                        value: Literal::Integer(m),
+                                        span: lit.span.clone(),
+                                        id: NodeId::default(),
+                                    })),
+                                    span: binary.span.clone(),
+                                    optimization_hint: Some(OptimizationHint::ShiftRight(m.trailing_zeros())),
+                                    parenthesized: false,
+                                    id: NodeId::default(),
+                                })),
+                                span: binary.span.clone(),
+                                parenthesized: false,
+                                id: NodeId::default(),
+                            }));
                         }
                     }
                 }
@@ -823,10 +1602,10 @@ impl Optimizer {
                 // Check for modulo patterns
                 if let Expression::Literal(lit) = &*binary.right {
                     if let Literal::Integer(n) = &lit.value {
-                        if self.is_power_of_two(*n) {
-                            // x % 2^k -> x & (2^k - 1)
-                            binary.optimization_hint = Some(OptimizationHint::BitwiseAnd(*n - 1));
-                            self.stats.strength_reductions += 1;
+                        // The remainder takes the dividend's sign, so x % -2^k == x % 2^k
+                        if let Some(m) = n.checked_abs().filter(|m| self.is_power_of_two(*m)) {
+                            // x % 2^k -> x & (2^k - 1), sign-corrected by codegen
+                            self.set_hint(binary, OptimizationHint::BitwiseAnd(m - 1));
                             return None;
                         }
                     }
@@ -870,11 +1649,12 @@ impl Optimizer {
                 }
                 
                 // Check for x - x = 0
-                if self.expressions_equal(&*binary.left, &*binary.right) {
+                if self.expressions_equal(&binary.left, &binary.right) {
                     self.stats.strength_reductions += 1;
                     return Some(Expression::Literal(LiteralExpr {
                         value: Literal::Integer(0),
                         span: binary.span.clone(),
+                        id: NodeId::default(),
                     }));
                 }
             }
@@ -887,13 +1667,66 @@ impl Optimizer {
         None
     }
 
+    /// Float identities. By default only rewrites that give bit-identical
+    /// results for every input, including -0.0, NaN and infinities:
+    /// `x * 1.0`, `x / 1.0`, `x - 0.0` and `x + -0.0` are `x`, and
+    /// `x * -1.0`, `x / -1.0` are `-x`. With fast math, `x + 0.0` and
+    /// `x - -0.0` become `x` (wrong for x = -0.0) and `x * 0.0` becomes
+    /// `0.0` (wrong for NaN, infinities and negative x).
+    fn try_reduce_float_identity(&self, binary: &BinaryExpr) -> Option<Expression> {
+        let float = |expr: &Expression| match expr {
+            Expression::Literal(LiteralExpr { value: Literal::Float(f), .. }) => Some(*f),
+            _ => None,
+        };
+        let is_zero = |f: f64, negative: bool| f == 0.0 && f.is_sign_negative() == negative;
+        let negate = |operand: &Expression| Expression::Unary(UnaryExpr {
+            op: UnaryOp::Negate,
+            operand: Box::new(operand.clone()),
+            span: binary.span.clone(),
+            parenthesized: false,
+            id: NodeId::default(),
+        });
+        
+        // (literal, other operand) with the literal on either side where the
+        // operator commutes, and only on the right otherwise
+        let (f, other) = match (float(&binary.left), float(&binary.right), &binary.op) {
+            (_, Some(f), _) => (f, &*binary.left),
+            (Some(f), None, BinaryOp::Add | BinaryOp::Multiply) => (f, &*binary.right),
+            _ => return None,
+        };
+        
+        match binary.op {
+            BinaryOp::Multiply | BinaryOp::Divide if f == 1.0 => Some(other.clone()),
+            BinaryOp::Multiply | BinaryOp::Divide if f == -1.0 => Some(negate(other)),
+            BinaryOp::Multiply if self.fast_math && f == 0.0 && Self::is_side_effect_free(other) => {
+                Some(Expression::Literal(LiteralExpr {
+                    value: Literal::Float(0.0),
+                    span: binary.span.clone(),
+                    id: NodeId::default(),
+                }))
+            }
+            BinaryOp::Add if is_zero(f, true) || (self.fast_math && is_zero(f, false)) => Some(other.clone()),
+            BinaryOp::Subtract if is_zero(f, false) || (self.fast_math && is_zero(f, true)) => Some(other.clone()),
+            _ => None,
+        }
+    }
+
+    /// Attach a codegen hint, counting it only the first time so that
+    /// running the pass again over its own output reports nothing new
+    fn set_hint(&mut self, binary: &mut BinaryExpr, hint: OptimizationHint) {
+        if binary.optimization_hint.as_ref() != Some(&hint) {
+            binary.optimization_hint = Some(hint);
+            self.stats.strength_reductions += 1;
+        }
+    }
+
     fn expressions_equal(&self, left: &Expression, right: &Expression) -> bool {
         match (left, right) {
             (Expression::Identifier(l), Expression::Identifier(r)) => l.name == r.name,
             (Expression::Literal(l), Expression::Literal(r)) => {
                 match (&l.value, &r.value) {
                     (Literal::Integer(a), Literal::Integer(b)) => a == b,
-                    (Literal::Float(a), Literal::Float(b)) => (a - b).abs() < f64::EPSILON,
+                    (Literal::Float(a), Literal::Float(b)) => a.to_bits() == b.to_bits(),
                     (Literal::Boolean(a), Literal::Boolean(b)) => a == b,
                     (Literal::String(a), Literal::String(b)) => a == b,
                     _ => false,
@@ -903,4 +1736,111 @@ impl Optimizer {
         }
     }
     
-}
\ No newline at end of file
+}
+
+/// Call `f` on every call expression in a block, innermost calls first
+pub fn for_each_call_in_block(block: &mut Block, f: &mut impl FnMut(&mut CallExpr)) {
+    for_each_expression_in_block(block, &mut |expr| {
+        if let Expression::Call(call) = expr {
+            f(call);
+        }
+    });
+}
+
+/// Visit every expression in a block, children before their parents
+pub fn for_each_expression_in_block(block: &mut Block, f: &mut impl FnMut(&mut Expression)) {
+    for statement in &mut block.statements {
+        for_each_expression_in_statement(statement, f);
+    }
+}
+
+fn for_each_expression_in_statement(statement: &mut Statement, f: &mut impl FnMut(&mut Expression)) {
+    match statement {
+        Statement::Const(const_stmt) => for_each_expression(&mut const_stmt.value, f),
+        Statement::LetTuple(let_tuple) => for_each_expression(&mut let_tuple.value, f),
+        Statement::Let(let_stmt) => {
+            if let Some(value) = &mut let_stmt.value {
+                for_each_expression(value, f);
+            }
+        }
+        Statement::Display(display_stmt) => {
+            for expr in &mut display_stmt.expressions {
+                for_each_expression(expr, f);
+            }
+        }
+        Statement::If(if_stmt) => {
+            for_each_expression(&mut if_stmt.condition, f);
+            for_each_expression_in_block(&mut if_stmt.then_block, f);
+            if let Some(else_block) = &mut if_stmt.else_block {
+                for_each_expression_in_block(else_block, f);
+            }
+        }
+        Statement::While(while_stmt) => {
+            for_each_expression(&mut while_stmt.condition, f);
+            for_each_expression_in_block(&mut while_stmt.body, f);
+        }
+        Statement::DoWhile(do_while) => {
+            for_each_expression_in_block(&mut do_while.body, f);
+            for_each_expression(&mut do_while.condition, f);
+        }
+        Statement::For(for_stmt) => {
+            if let Some(init) = &mut for_stmt.init {
+                for_each_expression_in_statement(init, f);
+            }
+            if let Some(condition) = &mut for_stmt.condition {
+                for_each_expression(condition, f);
+            }
+            if let Some(update) = &mut for_stmt.update {
+                for_each_expression(update, f);
+            }
+            for_each_expression_in_block(&mut for_stmt.body, f);
+        }
+        Statement::Return(return_stmt) => {
+            if let Some(value) = &mut return_stmt.value {
+                for_each_expression(value, f);
+            }
+        }
+        Statement::Expression(expr_stmt) => for_each_expression(&mut expr_stmt.expression, f),
+        Statement::Block(block) => for_each_expression_in_block(block, f),
+        Statement::Defer(defer_stmt) => for_each_expression_in_block(&mut defer_stmt.body, f),
+        Statement::Spawn(spawn_stmt) => for_each_expression_in_block(&mut spawn_stmt.body, f),
+        Statement::Break(_) | Statement::Continue(_) => {}
+    }
+}
+
+fn for_each_expression(expr: &mut Expression, f: &mut impl FnMut(&mut Expression)) {
+    match expr {
+        Expression::Call(call) => {
+            for arg in &mut call.args {
+                for_each_expression(arg, f);
+            }
+        }
+        Expression::Binary(binary) => {
+            for_each_expression(&mut binary.left, f);
+            for_each_expression(&mut binary.right, f);
+        }
+        Expression::Unary(unary) => for_each_expression(&mut unary.operand, f),
+        Expression::Index(index) => {
+            for_each_expression(&mut index.array, f);
+            for_each_expression(&mut index.index, f);
+        }
+        Expression::Assign(assign) => for_each_expression(&mut assign.value, f),
+        Expression::Literal(lit) => match &mut lit.value {
+            Literal::Array(elements) | Literal::Tuple(elements) => {
+                for element in elements {
+                    for_each_expression(element, f);
+                }
+            }
+            Literal::InterpolatedString(parts) => {
+                for part in parts {
+                    if let StringPart::Expression(expr) = part {
+                        for_each_expression(expr, f);
+                    }
+                }
+            }
+            _ => {}
+        },
+        Expression::Identifier(_) => {}
+    }
+    f(expr);
+}
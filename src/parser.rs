@@ -1,42 +1,160 @@
 // src/parser.rs - Recursive descent parser
 
 use crate::ast::*;
+use crate::edition::{Feature, Version};
 use crate::errors::ParserError;
+use crate::json;
+use miette::SourceSpan;
 use crate::lexer::{Token, TokenWithSpan};
-use std::collections::VecDeque;
+use std::collections::HashMap;
+
+mod cursor;
+
+pub use cursor::{Cursor, Mark};
+
+/// Configuration constants visible to `@if` conditions, from `--define NAME=value`
+pub type Defines = HashMap<String, Literal>;
+
+/// Contextual words that may come before `func`
+const QUALIFIERS: [&str; 3] = ["public", "private", "pure"];
 
 /// The parser struct
 pub struct Parser {
-    tokens: VecDeque<TokenWithSpan>,
-    current: usize,
-    _source: String,
+    cursor: Cursor,
+    source: String,
+    defines: Defines,
+    /// The language version syntax is checked against
+    version: Version,
 }
 
 impl Parser {
     /// Create a new parser from tokens
     pub fn new(tokens: Vec<TokenWithSpan>, source: String) -> Self {
         Self {
-            tokens: tokens.into(),
-            current: 0,
-            _source : source,
+            cursor: Cursor::new(tokens),
+            source,
+            defines: Defines::new(),
+            version: Version::LATEST,
         }
     }
     
+    /// Set the configuration constants used to resolve `@if` blocks
+    pub fn with_defines(mut self, defines: Defines) -> Self {
+        self.defines = defines;
+        self
+    }
+    
+    /// Set the language version for a file without a `minilang X.Y;` header
+    pub fn with_edition(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+    
     /// Parse a complete program
     pub fn parse_program(&mut self) -> Result<Program, ParserError> {
+        if let Some(version) = self.parse_version_header()? {
+            self.version = version;
+        }
+        
         let mut functions = Vec::new();
+        let mut tests = Vec::new();
         
         while !self.is_at_end() {
-            functions.push(self.parse_function()?);
+            if self.at_test_block() {
+                tests.push(self.parse_test_block()?);
+            } else {
+                functions.push(self.parse_function()?);
+            }
         }
         
-        Ok(Program { functions })
+        let mut program = Program { functions, tests };
+        program.number_nodes();
+        debug_assert_eq!(program.invalid_spans(self.source.len()), vec![], "parser produced nodes without a source span");
+        Ok(program)
+    }
+    
+    /// Parse an optional `minilang 1.2;` at the top of the file. `minilang` is
+    /// not a keyword, but no function definition starts with a name and a number.
+    fn parse_version_header(&mut self) -> Result<Option<Version>, ParserError> {
+        match (self.peek(), self.cursor.peek_n(1)) {
+            (
+                Some(TokenWithSpan { token: Token::Identifier(word), .. }),
+                Some(TokenWithSpan { token: Token::Float(_) | Token::Integer(_), .. }),
+            ) if word == "minilang" => {}
+            _ => return Ok(None),
+        }
+        self.advance();
+        let number = self.advance().map(|t| t.span.clone()).unwrap_or_default();
+        let version = self.source[number.clone()].parse().map_err(|reason| ParserError::UnsupportedVersion {
+            reason,
+            span: number.into(),
+        })?;
+        self.expect_token(Token::Semicolon)?;
+        Ok(Some(version))
+    }
+    
+    /// Fail unless the file's version has `feature`, used at `span`
+    fn require(&self, feature: Feature, span: std::ops::Range<usize>) -> Result<(), ParserError> {
+        if self.version.supports(feature) {
+            return Ok(());
+        }
+        Err(ParserError::FeatureRequiresVersion {
+            feature: feature.description().to_string(),
+            required: feature.since().to_string(),
+            version: self.version.to_string(),
+            span: span.into(),
+        })
     }
     
     /// Parse a function definition
     fn parse_function(&mut self) -> Result<Function, ParserError> {
         let start = self.current_span().start;
         
+        // Attributes come first: @name or @name("argument")
+        let mut attributes = Vec::new();
+        while self.match_token(&Token::At) {
+            let attr_start = self.previous_span().start;
+            self.require(Feature::Attributes, attr_start..self.current_span().end)?;
+            let name = self.expect_identifier()?;
+            let argument = if self.match_token(&Token::LeftParen) {
+                let argument = match self.advance().map(|t| t.token.clone()) {
+                    Some(Token::String(text)) => text,
+                    other => {
+                        return Err(ParserError::UnexpectedToken {
+                            expected: "string argument".to_string(),
+                            found: other.map(|t| format!("{:?}", t)).unwrap_or_else(|| "end of file".to_string()),
+                            span: self.previous_span().into(),
+                        });
+                    }
+                };
+                self.expect_token(Token::RightParen)?;
+                Some(argument)
+            } else {
+                None
+            };
+            attributes.push(Attribute {
+                name,
+                argument,
+                span: Span::new(attr_start, self.previous_span().end),
+                id: NodeId::default(),
+            });
+        }
+        
+        // Optional `public`/`private` and `pure` qualifiers. They are contextual,
+        // so they stay usable as names.
+        let qualifier = self.qualifier(&["public", "private"]);
+        if qualifier.is_some() {
+            self.require(Feature::Visibility, self.previous_span())?;
+        }
+        let visibility = match qualifier {
+            Some("private") => Visibility::Private,
+            _ => Visibility::Public,
+        };
+        let is_pure = self.qualifier(&["pure"]).is_some();
+        if is_pure {
+            self.require(Feature::PureFunctions, self.previous_span())?;
+        }
+        
         // Expect 'func' keyword
         self.expect_token(Token::Func)?;
         
@@ -62,10 +180,45 @@ impl Parser {
         
         Ok(Function {
             name,
+            visibility,
+            is_pure,
+            attributes,
             params,
             return_type,
             body,
             span: Span::new(start, end),
+            id: NodeId::default(),
+        })
+    }
+    
+    /// Whether a `test "name" { ... }` block starts here. `test` is not a
+    /// keyword, but no function definition starts with a name and a string.
+    fn at_test_block(&self) -> bool {
+        matches!(
+            (self.peek(), self.cursor.peek_n(1)),
+            (
+                Some(TokenWithSpan { token: Token::Identifier(word), .. }),
+                Some(TokenWithSpan { token: Token::String(_), .. }),
+            ) if word == "test"
+        )
+    }
+    
+    /// Parse a test block: `test "name" { ... }`
+    fn parse_test_block(&mut self) -> Result<TestBlock, ParserError> {
+        let start = self.current_span().start;
+        self.advance();
+        self.require(Feature::TestBlocks, start..self.current_span().end)?;
+        let name = match self.advance().map(|t| t.token.clone()) {
+            Some(Token::String(name)) => name,
+            _ => unreachable!("at_test_block saw a string"),
+        };
+        let body = self.parse_block()?;
+        
+        Ok(TestBlock {
+            name,
+            body,
+            span: Span::new(start, self.previous_span().end),
+            id: NodeId::default(),
         })
     }
     
@@ -89,6 +242,7 @@ impl Parser {
                 name,
                 typ,
                 span: Span::new(start, end),
+                id: NodeId::default(),
             });
             
             if !self.match_token(&Token::Comma) {
@@ -101,11 +255,75 @@ impl Parser {
     
     /// Parse a type annotation
     fn parse_type(&mut self) -> Result<Type, ParserError> {
+        let typ = self.parse_element_type()?;
+        
+        // Check for array type
+        if self.match_token(&Token::LeftBracket) {
+            match self.parse_array_size()? {
+                Some(size) => Ok(Type::Array(Box::new(typ), size)),
+                None => Err(ParserError::UnexpectedToken {
+                    expected: "array size".to_string(),
+                    found: "RightBracket".to_string(),
+                    span: self.previous_span().into(),
+                }),
+            }
+        } else {
+            Ok(typ)
+        }
+    }
+    
+    /// Parse the size inside `[...]`; `None` for `[]`
+    fn parse_array_size(&mut self) -> Result<Option<usize>, ParserError> {
+        if self.match_token(&Token::RightBracket) {
+            return Ok(None);
+        }
+        let size = self.expect_integer()?;
+        self.expect_token(Token::RightBracket)?;
+        Ok(Some(size as usize))
+    }
+    
+    /// Parse a type without an array suffix
+    fn parse_element_type(&mut self) -> Result<Type, ParserError> {
         let typ = match self.advance() {
+            Some(TokenWithSpan { token: Token::LeftParen, span }) => {
+                let span = span.clone();
+                self.require(Feature::Tuples, span)?;
+                // Tuple type: (int, bool) - needs at least two elements
+                let mut elements = vec![self.parse_type()?];
+                self.expect_token(Token::Comma)?;
+                elements.push(self.parse_type()?);
+                while self.match_token(&Token::Comma) {
+                    elements.push(self.parse_type()?);
+                }
+                self.expect_token(Token::RightParen)?;
+                Type::Tuple(elements)
+            }
             Some(TokenWithSpan { token: Token::TypeInt, .. }) => Type::Int,
             Some(TokenWithSpan { token: Token::TypeFloat, .. }) => Type::Float,
             Some(TokenWithSpan { token: Token::TypeString, .. }) => Type::String,
             Some(TokenWithSpan { token: Token::TypeBool, .. }) => Type::Bool,
+            // `chan`, `atomic`, `canvas` and `json` are not keywords, so
+            // older programs may still use them as names
+            Some(TokenWithSpan { token: Token::Identifier(word), span }) if word == "chan" => {
+                let span = span.clone();
+                self.require(Feature::Spawn, span)?;
+                Type::Channel
+            }
+            Some(TokenWithSpan { token: Token::Identifier(word), span }) if word == "atomic" => {
+                let span = span.clone();
+                self.require(Feature::ParallelFor, span)?;
+                Type::Atomic
+            }
+            Some(TokenWithSpan { token: Token::Identifier(word), span }) if word == "canvas" => {
+                let span = span.clone();
+                self.require(Feature::Canvas, span)?;
+                Type::Canvas
+            }
+            Some(TokenWithSpan { token: Token::Identifier(word), span }) if word == "json" => {
+                let span = span.clone();
+                self.require(Feature::Json, span)?;
+                Type::Json
+            }
             Some(token) => {
                 return Err(ParserError::UnexpectedToken {
                     expected: "type".to_string(),
@@ -118,14 +336,7 @@ impl Parser {
             }),
         };
         
-        // Check for array type
-        if self.match_token(&Token::LeftBracket) {
-            let size = self.expect_integer()?;
-            self.expect_token(Token::RightBracket)?;
-            Ok(Type::Array(Box::new(typ), size as usize))
-        } else {
-            Ok(typ)
-        }
+        Ok(typ)
     }
     
     /// Parse a block of statements
@@ -145,40 +356,80 @@ impl Parser {
         Ok(Block {
             statements,
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
 
     /// Parse a string literal and detect interpolation
-    fn parse_string_literal(&mut self, string_value: &str) -> Result<Literal, ParserError> {
+    fn parse_string_literal(&mut self, string_value: &str, token_span: std::ops::Range<usize>) -> Result<Literal, ParserError> {
+        self.parse_string_template(string_value, false, token_span)
+    }
+    
+    /// Source offsets of the top-level `{` of each interpolation in a string token.
+    /// Escapes never produce braces, so the n-th one lines up with the n-th `{`
+    /// in the unescaped value.
+    fn interpolation_offsets(&self, token_span: &std::ops::Range<usize>) -> Vec<usize> {
+        let raw = self.source.get(token_span.clone()).unwrap_or("");
+        let mut offsets = Vec::new();
+        let mut depth = 0;
+        let mut escaped = false;
+        for (i, ch) in raw.char_indices() {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '{' => {
+                    if depth == 0 {
+                        offsets.push(token_span.start + i);
+                    }
+                    depth += 1;
+                }
+                '}' if depth > 0 => depth -= 1,
+                _ => {}
+            }
+        }
+        offsets
+    }
+    
+    /// Parse a string that may contain `{expr}` interpolations. With `allow_placeholders`
+    /// (the template of a `format(...)` call) an empty `{}` is kept in the text as a
+    /// positional placeholder instead of being rejected.
+    fn parse_string_template(
+        &mut self,
+        string_value: &str,
+        allow_placeholders: bool,
+        token_span: std::ops::Range<usize>,
+    ) -> Result<Literal, ParserError> {
         // No {} means regular string
         if !string_value.contains('{') {
             return Ok(Literal::String(string_value.to_string()));
         }
         
         // Has {} so we need to parse it
+        let mut brace_offsets = self.interpolation_offsets(&token_span).into_iter();
         let mut parts = Vec::new();
         let mut current_text = String::new();
         let mut chars = string_value.chars().peekable();
         
         while let Some(ch) = chars.next() {
             if ch == '{' {
-                // Save text before {
-                if !current_text.is_empty() {
-                    parts.push(StringPart::Text(current_text.clone()));
-                    current_text.clear();
-                }
+                // Where the expression text starts in the source
+                let expr_start = brace_offsets.next()
+                    .map(|offset| offset + 1)
+                    .unwrap_or(token_span.start);
                 
                 // Get everything between { and }
                 let mut expr_text = String::new();
                 let mut depth = 1;
+                let mut closed = false;
                 
-                while let Some(ch) = chars.next() {
+                for ch in chars.by_ref() {
                     if ch == '{' {
                         depth += 1;
                         expr_text.push(ch);
                     } else if ch == '}' {
                         depth -= 1;
                         if depth == 0 {
+                            closed = true;
                             break;
                         }
                         expr_text.push(ch);
@@ -187,14 +438,25 @@ impl Parser {
                     }
                 }
                 
-                if expr_text.is_empty() {
+                // Only a closed, empty {} is a placeholder
+                if !closed || expr_text.is_empty() {
+                    if closed && allow_placeholders {
+                        current_text.push_str("{}");
+                        continue;
+                    }
                     return Err(ParserError::InvalidExpression {
-                        span: (0..1).into(),
+                        span: (expr_start - 1..expr_start + 1).into(),
                     });
                 }
                 
+                // Save text before {
+                if !current_text.is_empty() {
+                    parts.push(StringPart::Text(current_text.clone()));
+                    current_text.clear();
+                }
+                
                 // Parse the expression using the standalone function
-                let expr = self.parse_interpolation_expression(&expr_text)?;
+                let expr = self.parse_interpolation_expression(&expr_text, expr_start)?;
                 parts.push(StringPart::Expression(expr));
                 
             } else {
@@ -207,18 +469,26 @@ impl Parser {
             parts.push(StringPart::Text(current_text));
         }
         
+        // Only placeholders, nothing interpolated
+        if let [StringPart::Text(text)] = parts.as_slice() {
+            return Ok(Literal::String(text.clone()));
+        }
+        
         Ok(Literal::InterpolatedString(parts))
     }
 
     /// Helper to parse expressions inside string interpolation
-    fn parse_interpolation_expression(&mut self, expr_text: &str) -> Result<Expression, ParserError> {
+    /// `expr_start` is the source offset of `expr_text`, used to place its spans
+    fn parse_interpolation_expression(&mut self, expr_text: &str, expr_start: usize) -> Result<Expression, ParserError> {
         let trimmed = expr_text.trim();
         
         // Simple case: just a variable name like {name}
         if trimmed.chars().all(|c| c.is_alphanumeric() || c == '_') && !trimmed.is_empty() {
+            let start = expr_start + (expr_text.len() - expr_text.trim_start().len());
             return Ok(Expression::Identifier(IdentifierExpr {
                 name: trimmed.to_string(),
-                span: Span::default(),
+                span: Span::new(start, start + trimmed.len()),
+                id: NodeId::default(),
             }));
         }
         
@@ -226,27 +496,68 @@ impl Parser {
         // We create a NEW parser instance, not using self
         let mut lexer = crate::Lexer::new(expr_text);
         let tokens = lexer.tokenize().map_err(|_| ParserError::InvalidExpression {
-            span: (0..1).into(),
+            span: (expr_start..expr_start + expr_text.len()).into(),
         })?;
         
-        let mut parser = Parser::new(tokens, expr_text.to_string());
-        parser.parse_expression()
+        let mut parser = Parser::new(tokens, expr_text.to_string()).with_edition(self.version);
+        let mut expr = parser.parse_expression()
+            .map_err(|e| Self::relocate_error(e, expr_start, expr_text.len()))?;
+        
+        // The sub-parser only saw expr_text, so move its spans into place
+        expr.visit_nodes_mut(&mut |_, span| {
+            span.start += expr_start;
+            span.end += expr_start;
+        });
+        Ok(expr)
     }
 
+    /// Place an error from the interpolation sub-parser in the enclosing
+    /// source. Running out of tokens there means the `{...}` ended too early.
+    fn relocate_error(error: ParserError, expr_start: usize, expr_len: usize) -> ParserError {
+        let shift = |span: SourceSpan| SourceSpan::from((span.offset() + expr_start, span.len()));
+        match error {
+            ParserError::UnexpectedToken { expected, found, span } => ParserError::UnexpectedToken { expected, found, span: shift(span) },
+            ParserError::MissingSemicolon { span } => ParserError::MissingSemicolon { span: shift(span) },
+            ParserError::MissingClosingBrace { span } => ParserError::MissingClosingBrace { span: shift(span) },
+            ParserError::InvalidExpression { span } => ParserError::InvalidExpression { span: shift(span) },
+            ParserError::MissingType { span } => ParserError::MissingType { span: shift(span) },
+            ParserError::InvalidCondition { reason, span } => ParserError::InvalidCondition { reason, span: shift(span) },
+            ParserError::FeatureRequiresVersion { feature, required, version, span } => {
+                ParserError::FeatureRequiresVersion { feature, required, version, span: shift(span) }
+            }
+            ParserError::UnsupportedVersion { reason, span } => ParserError::UnsupportedVersion { reason, span: shift(span) },
+            ParserError::UnexpectedEof { .. } => ParserError::InvalidExpression {
+                span: (expr_start..expr_start + expr_len).into(),
+            },
+        }
+    }
         
     /// Parse a statement
     fn parse_statement(&mut self) -> Result<Statement, ParserError> {
         // Check what kind of statement this is
+        if self.match_token(&Token::At) {
+            self.require(Feature::ConditionalBlocks, self.previous_span().start..self.current_span().end)?;
+            return Ok(Statement::Block(self.parse_conditional_block()?));
+        }
+        
         if self.match_token(&Token::Const) {
             return Ok(Statement::Const(self.parse_const_statement()?));
         }
 
         if self.match_token(&Token::Let) {
+            if self.check(&Token::LeftParen) {
+                self.require(Feature::Tuples, self.previous_span().start..self.current_span().end)?;
+                return Ok(Statement::LetTuple(self.parse_let_tuple_statement()?));
+            }
             return Ok(Statement::Let(self.parse_let_statement()?));
         }
         
-        if self.match_token(&Token::Display) {
-            return Ok(Statement::Display(self.parse_display_statement()?));
+        if self.match_token(&Token::Display) || self.match_token(&Token::DisplayLn) {
+            return Ok(Statement::Display(self.parse_display_statement(true)?));
+        }
+
+        if self.match_token(&Token::DisplayRaw) {
+            return Ok(Statement::Display(self.parse_display_statement(false)?));
         }
         
         if self.match_token(&Token::If) {
@@ -276,20 +587,36 @@ impl Parser {
         if self.match_token(&Token::Continue) {
             return Ok(Statement::Continue(self.parse_continue_statement()?));
         }
+
+        if self.match_token(&Token::Defer) {
+            self.require(Feature::Defer, self.previous_span())?;
+            return Ok(Statement::Defer(self.parse_defer_statement()?));
+        }
+        
+        if self.at_spawn_block() {
+            self.advance();
+            self.require(Feature::Spawn, self.previous_span())?;
+            return Ok(Statement::Spawn(self.parse_spawn_statement()?));
+        }
         
-        if self.match_token(&Token::LeftBrace) {
-            self.current -= 1; // Put back the brace
+        if self.at_parallel_for() {
+            self.advance();
+            self.require(Feature::ParallelFor, self.previous_span())?;
+            return Ok(Statement::For(self.parse_parallel_for_statement()?));
+        }
+        
+        if self.check(&Token::LeftBrace) {
             return Ok(Statement::Block(self.parse_block()?));
         }
 
-        let checkpoint = self.current;
+        let checkpoint = self.cursor.mark();
     
         if let Some(TokenWithSpan { token: Token::Identifier(_), .. }) = self.peek() {
             self.advance(); // consume identifier
             
             // Check for direct assignment: x = ...
             if self.check(&Token::Assign) {
-                self.current = checkpoint;
+                self.cursor.reset(checkpoint);
                 return self.parse_assignment_statement();
             }
             
@@ -314,13 +641,13 @@ impl Parser {
                 
                 // Now check for =
                 if self.check(&Token::Assign) {
-                    self.current = checkpoint;
+                    self.cursor.reset(checkpoint);
                     return self.parse_assignment_statement();
                 }
             }
             
             // Not an assignment, reset
-            self.current = checkpoint;
+            self.cursor.reset(checkpoint);
         }
         
         // Otherwise, try to parse as expression statement
@@ -333,11 +660,18 @@ impl Parser {
         
         let name = self.expect_identifier()?;
         self.expect_token(Token::Colon)?;
-        let typ = self.parse_type()?;
+        let element_type = self.parse_element_type()?;
+        let array_size = if self.match_token(&Token::LeftBracket) {
+            Some(self.parse_array_size()?)
+        } else {
+            None
+        };
+        let size_span = self.previous_span();
         
         // Constants MUST be initialized
         self.expect_token(Token::Assign)?;
         let value = self.parse_expression()?;
+        let typ = Self::declared_type(element_type, array_size, size_span, Some(&value))?;
         
         self.expect_token(Token::Semicolon)?;
         let end = self.previous_span().end;
@@ -347,6 +681,7 @@ impl Parser {
             typ,
             value,
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
     
@@ -355,8 +690,35 @@ impl Parser {
         let start = self.previous_span().start;
         
         let name = self.expect_identifier()?;
-        self.expect_token(Token::Colon)?;
-        let typ = self.parse_type()?;
+        
+        // Without an annotation the type checker infers the type from the initializer
+        if !self.match_token(&Token::Colon) {
+            if !self.match_token(&Token::Assign) {
+                return Err(ParserError::MissingType {
+                    span: self.current_span().into(),
+                });
+            }
+            self.require(Feature::InferredTypes, start..self.previous_span().end)?;
+            let value = self.parse_expression()?;
+            self.expect_token(Token::Semicolon)?;
+            let end = self.previous_span().end;
+            
+            return Ok(LetStmt {
+                name,
+                typ: None,
+                value: Some(value),
+                span: Span::new(start, end),
+                id: NodeId::default(),
+            });
+        }
+        
+        let element_type = self.parse_element_type()?;
+        let array_size = if self.match_token(&Token::LeftBracket) {
+            Some(self.parse_array_size()?)
+        } else {
+            None
+        };
+        let size_span = self.previous_span();
         
         let value = if self.match_token(&Token::Assign) {
             Some(self.parse_expression()?)
@@ -364,6 +726,8 @@ impl Parser {
             None
         };
         
+        let typ = Some(Self::declared_type(element_type, array_size, size_span, value.as_ref())?);
+        
         self.expect_token(Token::Semicolon)?;
         let end = self.previous_span().end;
         
@@ -372,11 +736,63 @@ impl Parser {
             typ,
             value,
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
     
-    /// Parse display statement
-    fn parse_display_statement(&mut self) -> Result<DisplayStmt, ParserError> {
+    /// The type written in a `let` or `const`, where `int[]` takes its size
+    /// from the array literal initializer
+    fn declared_type(
+        element_type: Type,
+        array_size: Option<Option<usize>>,
+        size_span: std::ops::Range<usize>,
+        value: Option<&Expression>,
+    ) -> Result<Type, ParserError> {
+        match array_size {
+            None => Ok(element_type),
+            Some(Some(size)) => Ok(Type::Array(Box::new(element_type), size)),
+            Some(None) => match value {
+                Some(Expression::Literal(LiteralExpr { value: Literal::Array(elements), .. })) => {
+                    Ok(Type::Array(Box::new(element_type), elements.len()))
+                }
+                _ => Err(ParserError::UnexpectedToken {
+                    expected: "array size, or an array literal initializer to infer it from".to_string(),
+                    found: "RightBracket".to_string(),
+                    span: size_span.into(),
+                }),
+            },
+        }
+    }
+    
+    /// Parse tuple destructuring: let (a, b) = value;
+    fn parse_let_tuple_statement(&mut self) -> Result<LetTupleStmt, ParserError> {
+        let start = self.previous_span().start;
+        
+        self.expect_token(Token::LeftParen)?;
+        let mut names = vec![self.expect_identifier()?];
+        self.expect_token(Token::Comma)?;
+        names.push(self.expect_identifier()?);
+        while self.match_token(&Token::Comma) {
+            names.push(self.expect_identifier()?);
+        }
+        self.expect_token(Token::RightParen)?;
+        
+        self.expect_token(Token::Assign)?;
+        let value = self.parse_expression()?;
+        
+        self.expect_token(Token::Semicolon)?;
+        let end = self.previous_span().end;
+        
+        Ok(LetTupleStmt {
+            names,
+            value,
+            span: Span::new(start, end),
+            id: NodeId::default(),
+        })
+    }
+    
+    /// Parse display statement, ending the line when `newline` is set
+    fn parse_display_statement(&mut self, newline: bool) -> Result<DisplayStmt, ParserError> {
         let start = self.previous_span().start;
         let mut expressions = Vec::new();
         
@@ -393,7 +809,9 @@ impl Parser {
         
         Ok(DisplayStmt {
             expressions,
+            newline,
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
     
@@ -413,6 +831,7 @@ impl Parser {
                 Some(Block {
                     statements: vec![Statement::If(nested_if)],
                     span,
+                    id: NodeId::default(),
                 })
             } else {
                 Some(self.parse_block()?)
@@ -430,9 +849,129 @@ impl Parser {
             then_block,
             else_block,
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
     
+    /// Parse `@if COND { } else { }` and keep only the branch selected by the
+    /// defines. The other branch must still parse, but is never type checked.
+    fn parse_conditional_block(&mut self) -> Result<Block, ParserError> {
+        let start = self.previous_span().start;
+        self.expect_token(Token::If)?;
+        
+        let condition = self.parse_expression()?;
+        let enabled = self.evaluate_condition(&condition)?;
+        let then_block = self.parse_block()?;
+        
+        let else_block = if self.match_token(&Token::Else) {
+            if self.match_token(&Token::At) {
+                Some(self.parse_conditional_block()?)
+            } else {
+                Some(self.parse_block()?)
+            }
+        } else {
+            None
+        };
+        
+        let end = else_block.as_ref()
+            .map(|b| b.span.end)
+            .unwrap_or(then_block.span.end);
+        
+        let chosen = if enabled { Some(then_block) } else { else_block };
+        Ok(chosen.unwrap_or(Block {
+            statements: Vec::new(),
+            span: Span::new(start, end),
+            id: NodeId::default(),
+        }))
+    }
+    
+    /// Decide an `@if` condition. Undefined names are `false`, which compares
+    /// as 0 with numbers and as unequal to any string.
+    fn evaluate_condition(&self, expr: &Expression) -> Result<bool, ParserError> {
+        match self.evaluate_constant(expr)? {
+            Literal::Boolean(value) => Ok(value),
+            Literal::Integer(value) => Ok(value != 0),
+            Literal::Float(value) => Ok(value != 0.0),
+            _ => Err(self.invalid_condition(expr, "a condition must be a bool or a number")),
+        }
+    }
+    
+    fn evaluate_constant(&self, expr: &Expression) -> Result<Literal, ParserError> {
+        match expr {
+            Expression::Literal(lit) => match &lit.value {
+                Literal::Integer(_) | Literal::Float(_) | Literal::String(_) | Literal::Boolean(_) => {
+                    Ok(lit.value.clone())
+                }
+                _ => Err(self.invalid_condition(expr, "only numbers, strings and booleans are allowed")),
+            },
+            Expression::Identifier(ident) => {
+                Ok(self.defines.get(&ident.name).cloned().unwrap_or(Literal::Boolean(false)))
+            }
+            Expression::Unary(unary) => match unary.op {
+                UnaryOp::Not => Ok(Literal::Boolean(!self.evaluate_condition(&unary.operand)?)),
+                UnaryOp::Negate => match self.evaluate_constant(&unary.operand)? {
+                    Literal::Integer(value) => Ok(Literal::Integer(value.wrapping_neg())),
+                    Literal::Float(value) => Ok(Literal::Float(-value)),
+                    _ => Err(self.invalid_condition(expr, "only numbers can be negated")),
+                },
+            },
+            Expression::Binary(binary) => match binary.op {
+                BinaryOp::And => Ok(Literal::Boolean(
+                    self.evaluate_condition(&binary.left)? && self.evaluate_condition(&binary.right)?,
+                )),
+                BinaryOp::Or => Ok(Literal::Boolean(
+                    self.evaluate_condition(&binary.left)? || self.evaluate_condition(&binary.right)?,
+                )),
+                BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::Greater
+                | BinaryOp::LessEqual | BinaryOp::GreaterEqual => {
+                    let left = self.evaluate_constant(&binary.left)?;
+                    let right = self.evaluate_constant(&binary.right)?;
+                    let ordering = match (&left, &right) {
+                        (Literal::String(a), Literal::String(b)) => a.partial_cmp(b),
+                        _ => match (Self::numeric_value(&left), Self::numeric_value(&right)) {
+                            (Some(a), Some(b)) => a.partial_cmp(&b),
+                            _ if matches!(binary.op, BinaryOp::Equal | BinaryOp::NotEqual) => None,
+                            _ => return Err(self.invalid_condition(expr, "cannot order a string against a number")),
+                        },
+                    };
+                    let Some(ordering) = ordering else {
+                        return Ok(Literal::Boolean(binary.op == BinaryOp::NotEqual));
+                    };
+                    Ok(Literal::Boolean(match binary.op {
+                        BinaryOp::Equal => ordering.is_eq(),
+                        BinaryOp::NotEqual => ordering.is_ne(),
+                        BinaryOp::Less => ordering.is_lt(),
+                        BinaryOp::Greater => ordering.is_gt(),
+                        BinaryOp::LessEqual => ordering.is_le(),
+                        _ => ordering.is_ge(),
+                    }))
+                }
+                _ => Err(self.invalid_condition(expr, "only comparisons, AND, OR and NOT are allowed")),
+            },
+            _ => Err(self.invalid_condition(
+                expr,
+                "only defined names, literals and comparisons are allowed",
+            )),
+        }
+    }
+    
+    fn numeric_value(literal: &Literal) -> Option<f64> {
+        match literal {
+            Literal::Integer(value) => Some(*value as f64),
+            Literal::Float(value) => Some(*value),
+            Literal::Boolean(value) => Some(if *value { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+    
+    fn invalid_condition(&self, expr: &Expression, reason: &str) -> ParserError {
+        let span = expr.span().clone();
+        ParserError::InvalidCondition {
+            reason: reason.to_string(),
+            span: (span.start..span.end).into(),
+        }
+    }
+    
     /// Parse while statement
     fn parse_while_statement(&mut self) -> Result<WhileStmt, ParserError> {
         let start = self.previous_span().start;
@@ -445,6 +984,7 @@ impl Parser {
             condition,
             body,
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
     
@@ -462,6 +1002,7 @@ impl Parser {
             body,
             condition,
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
     
@@ -477,34 +1018,39 @@ impl Parser {
         } else {
             // Could be assignment (i = 0) or expression
             // Try parsing as assignment first
-            let checkpoint = self.current;
+            let checkpoint = self.cursor.mark();
+            let init_start = self.current_span().start;
             
             if let Some(TokenWithSpan { token: Token::Identifier(_), .. }) = self.peek() {
                 self.advance();
                 if self.check(&Token::Assign) {
                     // It's an assignment
-                    self.current = checkpoint;
+                    self.cursor.reset(checkpoint);
                     let name = self.expect_identifier()?;
                     self.expect_token(Token::Assign)?;
                     let value = self.parse_expression()?;
+                    let value_end = value.span().end;
                     self.expect_token(Token::Semicolon)?;
                     
                     Some(Box::new(Statement::Expression(ExprStmt {
                         expression: Expression::Assign(AssignExpr {
                             target: name,
                             value: Box::new(value),
-                            span: Span::default(),
+                            span: Span::new(init_start, value_end),
+                            id: NodeId::default(),
                         }),
-                        span: Span::default(),
+                        span: Span::new(init_start, self.previous_span().end),
+                        id: NodeId::default(),
                     })))
                 } else {
                     // Not assignment, parse as expression
-                    self.current = checkpoint;
+                    self.cursor.reset(checkpoint);
                     let expr = self.parse_expression()?;
                     self.expect_token(Token::Semicolon)?;
                     Some(Box::new(Statement::Expression(ExprStmt {
                         expression: expr,
-                        span: Span::default(),
+                        span: Span::new(init_start, self.previous_span().end),
+                        id: NodeId::default(),
                     })))
                 }
             } else {
@@ -512,7 +1058,8 @@ impl Parser {
                 self.expect_token(Token::Semicolon)?;
                 Some(Box::new(Statement::Expression(ExprStmt {
                     expression: expr,
-                    span: Span::default(),
+                    span: Span::new(init_start, self.previous_span().end),
+                    id: NodeId::default(),
                 })))
             }
         };
@@ -530,7 +1077,8 @@ impl Parser {
             None
         } else {
             // Check if it's an assignment
-            let checkpoint = self.current;
+            let checkpoint = self.cursor.mark();
+            let update_start = self.current_span().start;
             
             if let Some(TokenWithSpan { token: Token::Identifier(name), .. }) = self.peek() {
                 let name = name.clone();
@@ -540,15 +1088,17 @@ impl Parser {
                     // It's an assignment in update
                     self.advance(); // consume =
                     let value = self.parse_expression()?;
+                    let value_end = value.span().end;
                     
                     Some(Expression::Assign(AssignExpr {
                         target: name,
                         value: Box::new(value),
-                        span: Span::default(),
+                        span: Span::new(update_start, value_end),
+                        id: NodeId::default(),
                     }))
                 } else {
                     // Not assignment, parse as normal expression
-                    self.current = checkpoint;
+                    self.cursor.reset(checkpoint);
                     Some(self.parse_expression()?)
                 }
             } else {
@@ -566,6 +1116,79 @@ impl Parser {
             update,
             body,
             span: Span::new(start, end),
+            id: NodeId::default(),
+            parallel: false,
+        })
+    }
+    
+    /// Whether a `parallel for` loop starts here; `parallel` is not a keyword
+    fn at_parallel_for(&self) -> bool {
+        matches!(
+            (self.peek(), self.cursor.peek_n(1)),
+            (
+                Some(TokenWithSpan { token: Token::Identifier(word), .. }),
+                Some(TokenWithSpan { token: Token::For, .. }),
+            ) if word == "parallel"
+        )
+    }
+    
+    /// Parse `parallel for i in start..end { ... }` into the loop counting
+    /// `i` up from `start` while it is below `end`
+    fn parse_parallel_for_statement(&mut self) -> Result<ForStmt, ParserError> {
+        let start = self.previous_span().start;
+        self.expect_token(Token::For)?;
+        let name_span = self.current_span();
+        let name = self.expect_identifier()?;
+        let name_span = Span::new(name_span.start, name_span.end);
+        self.expect_word("in")?;
+        let from = self.parse_expression()?;
+        self.expect_token(Token::DotDot)?;
+        let to = self.parse_expression()?;
+        let body = self.parse_block()?;
+        let end = body.span.end;
+        
+        let variable = || Box::new(Expression::Identifier(IdentifierExpr {
+            name: name.clone(),
+            span: name_span.clone(),
+            id: NodeId::default(),
+        }));
+        let binary = |left, op, right: Expression| {
+            let span = Span::new(name_span.start, right.span().end);
+            Expression::Binary(BinaryExpr {
+                left,
+                op,
+                right: Box::new(right),
+                span,
+                id: NodeId::default(),
+                optimization_hint: None,
+                parenthesized: false,
+            })
+        };
+        let one = Expression::Literal(LiteralExpr {
+            value: Literal::Integer(1),
+            span: name_span.clone(),
+            id: NodeId::default(),
+        });
+        
+        Ok(ForStmt {
+            init: Some(Box::new(Statement::Let(LetStmt {
+                name: name.clone(),
+                typ: Some(Type::Int),
+                span: Span::new(name_span.start, from.span().end),
+                value: Some(from),
+                id: NodeId::default(),
+            }))),
+            condition: Some(binary(variable(), BinaryOp::Less, to)),
+            update: Some(Expression::Assign(AssignExpr {
+                target: name.clone(),
+                value: Box::new(binary(variable(), BinaryOp::Add, one)),
+                span: name_span.clone(),
+                id: NodeId::default(),
+            })),
+            body,
+            span: Span::new(start, end),
+            id: NodeId::default(),
+            parallel: true,
         })
     }
     
@@ -585,6 +1208,7 @@ impl Parser {
         Ok(ReturnStmt {
             value,
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
 
@@ -596,6 +1220,7 @@ impl Parser {
         
         Ok(BreakStmt {
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
 
@@ -607,6 +1232,45 @@ impl Parser {
         
         Ok(ContinueStmt {
             span: Span::new(start, end),
+            id: NodeId::default(),
+        })
+    }
+    
+    /// Parse defer statement
+    fn parse_defer_statement(&mut self) -> Result<DeferStmt, ParserError> {
+        let start = self.previous_span().start;
+        let body = self.parse_block()?;
+        let end = body.span.end;
+
+        Ok(DeferStmt {
+            body,
+            span: Span::new(start, end),
+            id: NodeId::default(),
+        })
+    }
+    
+    /// Whether a `spawn { ... }` block starts here. Like `test`, `spawn` is
+    /// not a keyword: no expression statement starts with a name and a `{`.
+    fn at_spawn_block(&self) -> bool {
+        matches!(
+            (self.peek(), self.cursor.peek_n(1)),
+            (
+                Some(TokenWithSpan { token: Token::Identifier(word), .. }),
+                Some(TokenWithSpan { token: Token::LeftBrace, .. }),
+            ) if word == "spawn"
+        )
+    }
+    
+    /// Parse spawn statement
+    fn parse_spawn_statement(&mut self) -> Result<SpawnStmt, ParserError> {
+        let start = self.previous_span().start;
+        let body = self.parse_block()?;
+        let end = body.span.end;
+
+        Ok(SpawnStmt {
+            body,
+            span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
     
@@ -620,6 +1284,7 @@ impl Parser {
         Ok(ExprStmt {
             expression,
             span: Span::new(start, end),
+            id: NodeId::default(),
         })
     }
 
@@ -636,8 +1301,10 @@ impl Parser {
             self.advance(); // consume [
             let index = self.parse_expression()?;
             self.expect_token(Token::RightBracket)?;
+            let index_end = self.previous_span().end;
             self.expect_token(Token::Assign)?;
             let value = self.parse_expression()?;
+            let value_end = self.previous_span().end;
             self.expect_token(Token::Semicolon)?;
             let end = self.previous_span().end;
             
@@ -647,9 +1314,11 @@ impl Parser {
                 array: Box::new(Expression::Identifier(IdentifierExpr {
                     name: name.clone(),
                     span: Span::new(start, start + name.len()),
+                    id: NodeId::default(),
                 })),
                 index: Box::new(index),
-                span: Span::new(start, end),
+                span: Span::new(start, index_end),
+                id: NodeId::default(),
             });
             
             // Create a special marker assignment
@@ -660,17 +1329,22 @@ impl Parser {
                         left: Box::new(array_expr),
                         op: BinaryOp::Equal, // Reusing Equal as assignment marker
                         right: Box::new(value),
-                        span: Span::new(start, end),
+                        span: Span::new(start, value_end),
                         optimization_hint: None,
+                        parenthesized: false,
+                        id: NodeId::default(),
                     })),
-                    span: Span::new(start, end),
+                    span: Span::new(start, value_end),
+                    id: NodeId::default(),
                 }),
                 span: Span::new(start, end),
+                id: NodeId::default(),
             }))
         } else {
             // Simple assignment: x = value;
             self.expect_token(Token::Assign)?;
             let value = self.parse_expression()?;
+            let value_end = self.previous_span().end;
             self.expect_token(Token::Semicolon)?;
             let end = self.previous_span().end;
             
@@ -678,161 +1352,66 @@ impl Parser {
                 expression: Expression::Assign(AssignExpr {
                     target: name,
                     value: Box::new(value),
-                    span: Span::new(start, end),
+                    span: Span::new(start, value_end),
+                    id: NodeId::default(),
                 }),
                 span: Span::new(start, end),
+                id: NodeId::default(),
             }))
         }
     }
     
     /// Parse an expression (with precedence)
     fn parse_expression(&mut self) -> Result<Expression, ParserError> {
-        self.parse_logical_or()
+        self.parse_binary(0)
     }
     
-    /// Parse logical OR expression
-    fn parse_logical_or(&mut self) -> Result<Expression, ParserError> {
-        let mut left = self.parse_logical_and()?;
-        
-        while self.match_token(&Token::Or) {
-            let start_span = self.get_expression_span(&left).start;
-            let op = BinaryOp::Or;
-            let right = self.parse_logical_and()?;
-            let end_span = self.get_expression_span(&right).end;
-            
-            left = Expression::Binary(BinaryExpr {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-                span: Span::new(start_span, end_span),
-                optimization_hint: None,
-            });
+    /// Binary operator for a token. Precedence lives in BinaryOp::precedence,
+    /// so a new operator is one entry here and one there.
+    fn binary_operator(token: &Token) -> Option<BinaryOp> {
+        match token {
+            Token::Or => Some(BinaryOp::Or),
+            Token::And => Some(BinaryOp::And),
+            Token::Equal => Some(BinaryOp::Equal),
+            Token::NotEqual => Some(BinaryOp::NotEqual),
+            Token::LessThan => Some(BinaryOp::Less),
+            Token::GreaterThan => Some(BinaryOp::Greater),
+            Token::LessEqual => Some(BinaryOp::LessEqual),
+            Token::GreaterEqual => Some(BinaryOp::GreaterEqual),
+            Token::Plus => Some(BinaryOp::Add),
+            Token::Minus => Some(BinaryOp::Subtract),
+            Token::Star => Some(BinaryOp::Multiply),
+            Token::Slash => Some(BinaryOp::Divide),
+            Token::Percent => Some(BinaryOp::Modulo),
+            _ => None,
         }
-        
-        Ok(left)
-    }
-    
-    /// Parse logical AND expression
-    fn parse_logical_and(&mut self) -> Result<Expression, ParserError> {
-        let mut left = self.parse_equality()?;
-        
-        while self.match_token(&Token::And) {
-            let start_span = self.get_expression_span(&left).start;
-            let op = BinaryOp::And;
-            let right = self.parse_equality()?;
-            let end_span = self.get_expression_span(&right).end;
-            
-            left = Expression::Binary(BinaryExpr {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-                span: Span::new(start_span, end_span),
-                optimization_hint: None,
-            });
-        }
-        
-        Ok(left)
-    }
-    
-    /// Parse equality expression
-    fn parse_equality(&mut self) -> Result<Expression, ParserError> {
-        let mut left = self.parse_comparison()?;
-        
-        while let Some(op) = self.match_tokens(&[Token::Equal, Token::NotEqual]) {
-            let start_span = self.get_expression_span(&left).start;
-            let op = match op {
-                Token::Equal => BinaryOp::Equal,
-                Token::NotEqual => BinaryOp::NotEqual,
-                _ => unreachable!(),
-            };
-            let right = self.parse_comparison()?;
-            let end_span = self.get_expression_span(&right).end;
-            
-            left = Expression::Binary(BinaryExpr {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-                span: Span::new(start_span, end_span),
-                optimization_hint: None,
-            });
-        }
-        
-        Ok(left)
-    }
-    
-    /// Parse comparison expression
-    fn parse_comparison(&mut self) -> Result<Expression, ParserError> {
-        let mut left = self.parse_addition()?;
-        
-        while let Some(op) = self.match_tokens(&[
-            Token::LessThan,
-            Token::GreaterThan,
-            Token::LessEqual,
-            Token::GreaterEqual,
-        ]) {
-            let op = match op {
-                Token::LessThan => BinaryOp::Less,
-                Token::GreaterThan => BinaryOp::Greater,
-                Token::LessEqual => BinaryOp::LessEqual,
-                Token::GreaterEqual => BinaryOp::GreaterEqual,
-                _ => unreachable!(),
-            };
-            let right = self.parse_addition()?;
-            let start_span = self.get_expression_span(&left).start;
-            let end_span = self.get_expression_span(&right).end;
-            
-            left = Expression::Binary(BinaryExpr {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-                span: Span::new(start_span,end_span),
-                optimization_hint: None,
-            });
-        }
-        
-        Ok(left)
     }
     
-    /// Parse addition/subtraction expression
-    fn parse_addition(&mut self) -> Result<Expression, ParserError> {
-        let mut left = self.parse_multiplication()?;
-        
-        while let Some(op) = self.match_tokens(&[Token::Plus, Token::Minus]) {
-            let op = match op {
-                Token::Plus => BinaryOp::Add,
-                Token::Minus => BinaryOp::Subtract,
-                _ => unreachable!(),
-            };
-            let right = self.parse_multiplication()?;
-            let start_span = self.get_expression_span(&left).start;
-            let end_span = self.get_expression_span(&right).end;
-            
-            left = Expression::Binary(BinaryExpr {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-                span: Span::new(start_span, end_span),
-                optimization_hint: None,
-            });
+    /// Prefix operator for a token; these bind tighter than any binary operator
+    fn unary_operator(token: &Token) -> Option<UnaryOp> {
+        match token {
+            Token::Not => Some(UnaryOp::Not),
+            Token::Minus => Some(UnaryOp::Negate),
+            _ => None,
         }
-        
-        Ok(left)
     }
     
-    /// Parse multiplication/division expression
-    fn parse_multiplication(&mut self) -> Result<Expression, ParserError> {
+    /// Parse binary operators binding tighter than `min_precedence` (Pratt parsing)
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expression, ParserError> {
         let mut left = self.parse_unary()?;
         
-        while let Some(op) = self.match_tokens(&[Token::Star, Token::Slash, Token::Percent]) {
-            let op = match op {
-                Token::Star => BinaryOp::Multiply,
-                Token::Slash => BinaryOp::Divide,
-                Token::Percent => BinaryOp::Modulo,
-                _ => unreachable!(),
-            };
-            let right = self.parse_unary()?;
-            let start_span = self.get_expression_span(&left).start;
-            let end_span = self.get_expression_span(&right).end;
+        while let Some(op) = self.peek().and_then(|t| Self::binary_operator(&t.token)) {
+            let precedence = op.precedence();
+            if precedence <= min_precedence {
+                break;
+            }
+            self.advance();
+            
+            // All binary operators are left-associative: the right operand
+            // only takes operators that bind strictly tighter
+            let right = self.parse_binary(precedence)?;
+            let start_span = left.span().start;
+            let end_span = right.span().end;
             
             left = Expression::Binary(BinaryExpr {
                 left: Box::new(left),
@@ -840,6 +1419,8 @@ impl Parser {
                 right: Box::new(right),
                 span: Span::new(start_span, end_span),
                 optimization_hint: None,
+                parenthesized: false,
+                id: NodeId::default(),
             });
         }
         
@@ -848,27 +1429,18 @@ impl Parser {
     
     /// Parse unary expression
     fn parse_unary(&mut self) -> Result<Expression, ParserError> {
-        if self.match_token(&Token::Not) {
-            let op = UnaryOp::Not;
-            let operand = self.parse_unary()?;
-            let span = Span::default();
-            
-            return Ok(Expression::Unary(UnaryExpr {
-                op,
-                operand: Box::new(operand),
-                span,
-            }));
-        }
-        
-        if self.match_token(&Token::Minus) {
-            let op = UnaryOp::Negate;
+        if let Some(op) = self.peek().and_then(|t| Self::unary_operator(&t.token)) {
+            let start = self.current_span().start;
+            self.advance();
             let operand = self.parse_unary()?;
-            let span = Span::default();
+            let end = operand.span().end;
             
             return Ok(Expression::Unary(UnaryExpr {
                 op,
                 operand: Box::new(operand),
-                span,
+                span: Span::new(start, end),
+                parenthesized: false,
+                id: NodeId::default(),
             }));
         }
         
@@ -888,7 +1460,12 @@ impl Parser {
                     self.previous_span().start
                 };
                 
-                let args = self.parse_arguments()?;
+                let is_format = matches!(&expr, Expression::Identifier(id_expr) if id_expr.name == "format");
+                let args = if is_format {
+                    self.parse_format_arguments()?
+                } else {
+                    self.parse_arguments()?
+                };
                 self.expect_token(Token::RightParen)?;
                 let end_span = self.previous_span().end;
                 
@@ -897,6 +1474,7 @@ impl Parser {
                         function: id_expr.name,
                         args,
                         span: Span::new(start_span, end_span),
+                        id: NodeId::default(),
                     });
                 } else {
                     return Err(ParserError::InvalidExpression {
@@ -919,6 +1497,7 @@ impl Parser {
                     array: Box::new(expr),
                     index: Box::new(index),
                     span: Span::new(start_span, end_span),
+                    id: NodeId::default(),
                 });
             } else {
                 break;
@@ -944,8 +1523,43 @@ impl Parser {
         Ok(args)
     }
     
+    /// Parse the arguments of `format(...)`, whose leading string literal may use `{}` placeholders
+    fn parse_format_arguments(&mut self) -> Result<Vec<Expression>, ParserError> {
+        let template = match self.peek() {
+            Some(TokenWithSpan { token: Token::String(s), span }) => Some((s.clone(), span.clone())),
+            _ => None,
+        };
+        let Some((template, span)) = template else {
+            return self.parse_arguments();
+        };
+        self.advance();
+        
+        let value = self.parse_string_template(&template, true, span.clone())?;
+        let mut args = vec![Expression::Literal(LiteralExpr {
+            value,
+            span: Span::new(span.start, span.end),
+            id: NodeId::default(),
+        })];
+        while self.match_token(&Token::Comma) {
+            args.push(self.parse_expression()?);
+        }
+        
+        Ok(args)
+    }
+    
     /// Parse primary expression
     fn parse_primary(&mut self) -> Result<Expression, ParserError> {
+        let before = self.cursor.mark();
+        
+        if let Some(TokenWithSpan { token: Token::Identifier(word), span }) = self.peek() {
+            if word == "json" && self.json_literal_ahead() {
+                let span = span.clone();
+                self.advance();
+                self.require(Feature::Json, span.clone())?;
+                return self.parse_json_literal(span.start);
+            }
+        }
+        
         // Literals
         if let Some(token) = self.advance() {
             match &token.token {
@@ -954,6 +1568,7 @@ impl Parser {
                     return Ok(Expression::Literal(LiteralExpr {
                         value: Literal::Integer(*n),
                         span: Span::new(span.start, span.end),
+                        id: NodeId::default(),
                     }));
                 }
                 Token::Float(f) => {
@@ -961,15 +1576,17 @@ impl Parser {
                     return Ok(Expression::Literal(LiteralExpr {
                         value: Literal::Float(*f),
                         span: Span::new(span.start, span.end),
+                        id: NodeId::default(),
                     }));
                 }
                 Token::String(s) => {
                     let span = token.span.clone();
                     let s_clone = s.clone();
-                    let literal = self.parse_string_literal(&s_clone)?; 
+                    let literal = self.parse_string_literal(&s_clone, span.clone())?; 
                     return Ok(Expression::Literal(LiteralExpr {
                         value: literal,
                         span: Span::new(span.start, span.end),
+                        id: NodeId::default(),
                     }));
                 }
                 Token::True => {
@@ -977,6 +1594,7 @@ impl Parser {
                     return Ok(Expression::Literal(LiteralExpr {
                         value: Literal::Boolean(true),
                         span: Span::new(span.start, span.end),
+                        id: NodeId::default(),
                     }));
                 }
                 Token::False => {
@@ -984,6 +1602,7 @@ impl Parser {
                     return Ok(Expression::Literal(LiteralExpr {
                         value: Literal::Boolean(false),
                         span: Span::new(span.start, span.end),
+                        id: NodeId::default(),
                     }));
                 }
                 Token::Identifier(name) => {
@@ -991,13 +1610,43 @@ impl Parser {
                     return Ok(Expression::Identifier(IdentifierExpr {
                         name: name.clone(),
                         span: Span::new(span.start, span.end),
+                        id: NodeId::default(),
                     }));
                 }
                 Token::LeftParen => {
-                    // Grouped expression
-                    let expr = self.parse_expression()?;
+                    let start = token.span.start;
+                    // Grouped expression, or a tuple literal if a comma follows
+                    let mut expr = self.parse_expression()?;
+                    if !self.check(&Token::Comma) {
+                        self.expect_token(Token::RightParen)?;
+                        let end = self.previous_span().end;
+                        // Keep the user's grouping; parens around atoms carry no meaning
+                        match &mut expr {
+                            Expression::Binary(binary) => {
+                                binary.parenthesized = true;
+                                binary.span = Span::new(start, end);
+                            }
+                            Expression::Unary(unary) => {
+                                unary.parenthesized = true;
+                                unary.span = Span::new(start, end);
+                            }
+                            _ => {}
+                        }
+                        return Ok(expr);
+                    }
+                    
+                    self.require(Feature::Tuples, start..self.current_span().end)?;
+                    let mut elements = vec![expr];
+                    while self.match_token(&Token::Comma) {
+                        elements.push(self.parse_expression()?);
+                    }
                     self.expect_token(Token::RightParen)?;
-                    return Ok(expr);
+                    let end = self.previous_span().end;
+                    return Ok(Expression::Literal(LiteralExpr {
+                        value: Literal::Tuple(elements),
+                        span: Span::new(start, end),
+                        id: NodeId::default(),
+                    }));
                 }
                 Token::LeftBracket => {
                     let start = token.span.start;
@@ -1018,13 +1667,14 @@ impl Parser {
                     return Ok(Expression::Literal(LiteralExpr {
                         value: Literal::Array(elements),
                         span: Span::new(start, end),
+                        id: NodeId::default(),
                     }));
                 }
                 _ => {}
             }
             
             // Put the token back
-            self.current -= 1;
+            self.cursor.reset(before);
         }
         
         Err(ParserError::InvalidExpression {
@@ -1032,16 +1682,122 @@ impl Parser {
         })
     }
     
+    /// Whether the `json` about to be read starts an object, `{}` or
+    /// `{"key": ...`, rather than being a variable before the block of an
+    /// `if` or a `while`
+    fn json_literal_ahead(&self) -> bool {
+        let token = |n| self.cursor.peek_n(n).map(|t: &TokenWithSpan| &t.token);
+        matches!(token(1), Some(Token::LeftBrace))
+            && (matches!(token(2), Some(Token::RightBrace))
+                || matches!((token(2), token(3)), (Some(Token::String(_)), Some(Token::Colon))))
+    }
+
+    /// Parse `json {...}`, a constant JSON object written in the source.
+    /// Strings in it are not interpolated. It becomes a json_parse of the
+    /// object's text, so each evaluation makes a new value.
+    fn parse_json_literal(&mut self, start: usize) -> Result<Expression, ParserError> {
+        let mut text = String::new();
+        self.parse_json_value(&mut text)?;
+        let end = self.previous_span().end;
+        let span = Span::new(start, end);
+        Ok(Expression::Call(CallExpr {
+            function: "json_parse".to_string(),
+            args: vec![Expression::Literal(LiteralExpr {
+                value: Literal::String(text),
+                span: span.clone(),
+                id: NodeId::default(),
+            })],
+            span,
+            id: NodeId::default(),
+        }))
+    }
+
+    /// Parse one value of a json literal, appending its JSON text to `text`
+    fn parse_json_value(&mut self, text: &mut String) -> Result<(), ParserError> {
+        let Some(token) = self.advance().cloned() else {
+            return Err(ParserError::UnexpectedEof {
+                expected: "json value".to_string(),
+            });
+        };
+        match &token.token {
+            Token::LeftBrace | Token::LeftBracket => {
+                let object = token.token == Token::LeftBrace;
+                let close = if object { Token::RightBrace } else { Token::RightBracket };
+                text.push(if object { '{' } else { '[' });
+                let mut first = true;
+                while !self.match_token(&close) {
+                    if !first {
+                        self.expect_token(Token::Comma)?;
+                        text.push(',');
+                    }
+                    first = false;
+                    if object {
+                        match self.advance().cloned() {
+                            Some(TokenWithSpan { token: Token::String(key), .. }) => text.push_str(&json::quote(&key)),
+                            found => return Err(Self::not_json(found, "json key")),
+                        }
+                        self.expect_token(Token::Colon)?;
+                        text.push(':');
+                    }
+                    self.parse_json_value(text)?;
+                }
+                text.push(if object { '}' } else { ']' });
+            }
+            Token::String(value) => text.push_str(&json::quote(value)),
+            Token::Integer(n) => text.push_str(&n.to_string()),
+            // The shortest text that reads back as the same double
+            Token::Float(f) => text.push_str(&format!("{:?}", f)),
+            Token::True => text.push_str("true"),
+            Token::False => text.push_str("false"),
+            Token::Identifier(word) if word == "null" => text.push_str("null"),
+            _ => return Err(Self::not_json(Some(token), "json value")),
+        }
+        Ok(())
+    }
+
+    fn not_json(found: Option<TokenWithSpan>, expected: &str) -> ParserError {
+        match found {
+            Some(token) => ParserError::UnexpectedToken {
+                expected: expected.to_string(),
+                found: format!("{:?}", token.token),
+                span: miette::SourceSpan::from(token.span.start..token.span.end),
+            },
+            None => ParserError::UnexpectedEof {
+                expected: expected.to_string(),
+            },
+        }
+    }
+
     // ==================== HELPER METHODS ====================
     
+    /// Consume one of the contextual `words` if it qualifies a function
+    /// definition, i.e. only qualifiers stand between it and `func`
+    fn qualifier<'a>(&mut self, words: &[&'a str]) -> Option<&'a str> {
+        let word = match self.peek() {
+            Some(TokenWithSpan { token: Token::Identifier(name), .. }) => words.iter().find(|w| *w == name)?,
+            _ => return None,
+        };
+        let qualifies = (1..).map(|n| self.cursor.peek_n(n)).find_map(|next| match next {
+            Some(TokenWithSpan { token: Token::Identifier(name), .. }) if QUALIFIERS.contains(&name.as_str()) => None,
+            Some(TokenWithSpan { token: Token::Func, .. }) => Some(true),
+            _ => Some(false),
+        })?;
+        if qualifies {
+            self.advance();
+            Some(word)
+        } else {
+            None
+        }
+    }
+    
     /// Check if we're at the end of tokens
     fn is_at_end(&self) -> bool {
-        self.current >= self.tokens.len()
+        self.cursor.is_at_end()
     }
     
     /// Get current token without consuming
     fn peek(&self) -> Option<&TokenWithSpan> {
-        self.tokens.get(self.current)
+        self.cursor.peek()
     }
     
     /// Get current span
@@ -1053,26 +1809,19 @@ impl Parser {
     
     /// Get previous span
     fn previous_span(&self) -> std::ops::Range<usize> {
-        if self.current > 0 {
-            self.tokens.get(self.current - 1)
-                .map(|t| t.span.clone())
-                .unwrap_or(0..0)
-        } else {
-            0..0
-        }
+        self.cursor.previous()
+            .map(|t| t.span.clone())
+            .unwrap_or(0..0)
     }
     
     /// Advance to next token
     fn advance(&mut self) -> Option<&TokenWithSpan> {
-        if !self.is_at_end() {
-            self.current += 1;
-        }
-        self.tokens.get(self.current - 1)
+        self.cursor.advance()
     }
     
     /// Check if current token matches
     fn check(&self, token: &Token) -> bool {
-        self.peek().map_or(false, |t| std::mem::discriminant(&t.token) == std::mem::discriminant(token))
+        self.peek().is_some_and(|t| std::mem::discriminant(&t.token) == std::mem::discriminant(token))
     }
     
     /// Consume token if it matches
@@ -1085,17 +1834,6 @@ impl Parser {
         }
     }
     
-    /// Match any of the given tokens
-    fn match_tokens(&mut self, tokens: &[Token]) -> Option<Token> {
-        for token in tokens {
-            if self.check(token) {
-                let matched = self.advance()?.token.clone();
-                return Some(matched);
-            }
-        }
-        None
-    }
-    
     /// Expect a specific token
     fn expect_token(&mut self, expected: Token) -> Result<(), ParserError> {
         if self.check(&expected) {
@@ -1114,6 +1852,23 @@ impl Parser {
         }
     }
     
+    /// Expect a word that is only a keyword in this place, like `in`
+    fn expect_word(&mut self, word: &str) -> Result<(), ParserError> {
+        match self.peek() {
+            Some(TokenWithSpan { token: Token::Identifier(found), .. }) if found == word => {
+                self.advance();
+                Ok(())
+            }
+            _ => Err(ParserError::UnexpectedToken {
+                expected: format!("'{}'", word),
+                found: self.peek()
+                    .map(|t| format!("{:?}", t.token))
+                    .unwrap_or_else(|| "end of input".to_string()),
+                span: miette::SourceSpan::from(self.current_span()),
+            }),
+        }
+    }
+    
     /// Expect an identifier
     fn expect_identifier(&mut self) -> Result<String, ParserError> {
         match self.advance() {
@@ -1143,17 +1898,4 @@ impl Parser {
             }),
         }
     }
-
-    /// Get span from an expression
-    fn get_expression_span(&self, expr: &Expression) -> Span {
-        match expr {
-            Expression::Literal(lit_expr) => lit_expr.span.clone(),
-            Expression::Identifier(id_expr) => id_expr.span.clone(),
-            Expression::Binary(binary) => binary.span.clone(),
-            Expression::Unary(unary) => unary.span.clone(),
-            Expression::Call(call) => call.span.clone(),
-            Expression::Index(index) => index.span.clone(),
-            Expression::Assign(assign) => assign.span.clone(),
-        }
-    }
 }
\ No newline at end of file
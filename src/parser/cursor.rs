@@ -0,0 +1,56 @@
+// src/parser/cursor.rs - Read position over the token stream
+
+use crate::lexer::TokenWithSpan;
+
+/// A saved cursor position, from `Cursor::mark`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mark(usize);
+
+/// Walks the parser's tokens with lookahead and backtracking. Reading past
+/// the end yields `None` rather than repeating the last token.
+pub struct Cursor {
+    tokens: Vec<TokenWithSpan>,
+    position: usize,
+}
+
+impl Cursor {
+    pub fn new(tokens: Vec<TokenWithSpan>) -> Self {
+        Self { tokens, position: 0 }
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    /// The next token, without consuming it
+    pub fn peek(&self) -> Option<&TokenWithSpan> {
+        self.peek_n(0)
+    }
+
+    /// The token `n` places after the next one (`peek_n(0)` is `peek()`)
+    pub fn peek_n(&self, n: usize) -> Option<&TokenWithSpan> {
+        self.tokens.get(self.position + n)
+    }
+
+    /// The most recently consumed token
+    pub fn previous(&self) -> Option<&TokenWithSpan> {
+        self.position.checked_sub(1).and_then(|i| self.tokens.get(i))
+    }
+
+    /// Consume and return the next token
+    pub fn advance(&mut self) -> Option<&TokenWithSpan> {
+        let token = self.tokens.get(self.position)?;
+        self.position += 1;
+        Some(token)
+    }
+
+    /// Remember the current position, to `reset` to later
+    pub fn mark(&self) -> Mark {
+        Mark(self.position)
+    }
+
+    /// Go back (or forward) to a position from `mark`
+    pub fn reset(&mut self, mark: Mark) {
+        self.position = mark.0.min(self.tokens.len());
+    }
+}
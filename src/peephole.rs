@@ -0,0 +1,386 @@
+// src/peephole.rs - Peephole clean-up of generated C text
+//
+// The code generator parenthesizes every operator and prints each displayed
+// value with its own printf. That is easy to get right but hard to read, so
+// this pass tidies the text line by line without changing what it does.
+
+use serde::{Serialize, Deserialize};
+
+/// What the peephole pass changed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PeepholeStats {
+    pub parens_removed: usize,
+    pub returns_removed: usize,
+    pub printfs_merged: usize,
+}
+
+/// Run every peephole rule over generated C
+pub fn optimize(code: &str) -> (String, PeepholeStats) {
+    let mut stats = PeepholeStats::default();
+
+    let lines: Vec<String> = code.lines()
+        .map(|line| {
+            let (line, removed) = remove_redundant_parens(line);
+            stats.parens_removed += removed;
+            line
+        })
+        .collect();
+    let lines = remove_redundant_returns(lines, &mut stats);
+    let lines = merge_printfs(lines, &mut stats);
+
+    let mut output = lines.join("\n");
+    if code.ends_with('\n') {
+        output.push('\n');
+    }
+    (output, stats)
+}
+
+// ==================== PARENTHESES ====================
+
+/// Drop parentheses around a whole argument, condition, initializer or
+/// returned value, e.g. `if ((a < b))` or `return (x * 2);`
+fn remove_redundant_parens(line: &str) -> (String, usize) {
+    // GCC attributes need their double parentheses
+    if line.trim_start().starts_with('#') || line.contains("__attribute__") {
+        return (line.to_string(), 0);
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    let Some(matches) = match_parens(&chars) else {
+        return (line.to_string(), 0);
+    };
+
+    let mut remove = vec![false; chars.len()];
+    let mut removed = 0;
+    for (open, close) in matches.iter().enumerate().filter_map(|(i, m)| m.map(|close| (i, close))) {
+        if chars[open] != '(' || has_top_level_comma(&chars, open, close, &matches) {
+            continue;
+        }
+        let before = previous_token(&chars, open, &remove);
+        let after = chars[close + 1..].iter().find(|c| !c.is_whitespace()).copied();
+
+        let whole_operand = matches!(before, Some(Before::OpenParen | Before::Comma | Before::Assign | Before::Return));
+        let ends_operand = matches!(after, Some(')' | ',' | ';'));
+        // `return (x);` and `= (x);` need the statement to end right after
+        let ends_statement = after == Some(';');
+        let allowed = match before {
+            Some(Before::Return | Before::Assign) => ends_statement,
+            _ => ends_operand,
+        };
+
+        if whole_operand && allowed && !is_call_or_cast(&chars, open, &remove) {
+            remove[open] = true;
+            remove[close] = true;
+            removed += 1;
+        }
+    }
+
+    if removed == 0 {
+        return (line.to_string(), 0);
+    }
+    let cleaned = chars.iter()
+        .zip(&remove)
+        .filter(|(_, &gone)| !gone)
+        .map(|(c, _)| *c)
+        .collect();
+    (cleaned, removed)
+}
+
+/// For each '(' the index of its ')', skipping string and character
+/// literals. None if the line's parentheses do not balance.
+fn match_parens(chars: &[char]) -> Option<Vec<Option<usize>>> {
+    let mut matches = vec![None; chars.len()];
+    let mut stack = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            quote @ ('"' | '\'') => {
+                i += 1;
+                while i < chars.len() && chars[i] != quote {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            '(' => stack.push(i),
+            ')' => matches[stack.pop()?] = Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    stack.is_empty().then_some(matches)
+}
+
+/// A comma directly inside the group would become an argument separator
+fn has_top_level_comma(chars: &[char], open: usize, close: usize, matches: &[Option<usize>]) -> bool {
+    let mut i = open + 1;
+    while i < close {
+        match chars[i] {
+            '(' => i = matches[i].unwrap_or(i),
+            '"' | '\'' => {
+                let quote = chars[i];
+                i += 1;
+                while i < close && chars[i] != quote {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            ',' => return true,
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
+
+enum Before {
+    OpenParen,
+    Comma,
+    Assign,
+    Return,
+}
+
+/// What precedes a '(' that decides whether its group is a whole operand
+fn previous_token(chars: &[char], open: usize, remove: &[bool]) -> Option<Before> {
+    let mut i = open;
+    while i > 0 {
+        i -= 1;
+        if remove[i] || chars[i].is_whitespace() {
+            continue;
+        }
+        return match chars[i] {
+            '(' => Some(Before::OpenParen),
+            ',' => Some(Before::Comma),
+            // Plain or compound assignment, but not a comparison
+            '=' if i == 0 || !matches!(chars[i - 1], '=' | '!' | '<' | '>') => Some(Before::Assign),
+            'n' if chars[..=i].iter().collect::<String>().ends_with("return")
+                && (i < 6 || !is_identifier_char(chars[i - 6])) => Some(Before::Return),
+            _ => None,
+        };
+    }
+    None
+}
+
+/// Whether the '(' before this group belongs to a call or cast, like
+/// `f((x))`, in which case only the inner group is redundant
+fn is_call_or_cast(chars: &[char], open: usize, remove: &[bool]) -> bool {
+    // The group itself is an argument list when an identifier precedes it
+    let mut i = open;
+    while i > 0 {
+        i -= 1;
+        if remove[i] {
+            continue;
+        }
+        return is_identifier_char(chars[i]) && !chars[..=i].iter().collect::<String>().ends_with("return");
+    }
+    false
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// ==================== RETURNS ====================
+
+/// Drop `return;` at the very end of a function and returns that directly
+/// follow another return in the same block
+fn remove_redundant_returns(lines: Vec<String>, stats: &mut PeepholeStats) -> Vec<String> {
+    let mut kept: Vec<String> = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let statement = line.trim_start();
+        let indent = line.len() - statement.len();
+
+        if statement.starts_with("return ") || statement == "return;" {
+            let ends_function = statement == "return;"
+                && indent == 4
+                && lines.get(i + 1).map(String::as_str) == Some("}");
+            let follows_return = kept.last().is_some_and(|previous| {
+                let previous_statement = previous.trim_start();
+                previous.len() - previous_statement.len() == indent
+                    && (previous_statement.starts_with("return ") || previous_statement == "return;")
+            });
+            if ends_function || follows_return {
+                stats.returns_removed += 1;
+                continue;
+            }
+        }
+        kept.push(line.clone());
+    }
+    kept
+}
+
+// ==================== PRINTF MERGING ====================
+
+/// A `printf` statement split into its format string and arguments
+struct Printf<'a> {
+    indent: &'a str,
+    format: String,
+    args: Vec<&'a str>,
+}
+
+/// Join runs of adjacent printf calls into one call. Only calls whose
+/// arguments cannot have side effects are merged, since C leaves the
+/// evaluation order of arguments unspecified.
+fn merge_printfs(lines: Vec<String>, stats: &mut PeepholeStats) -> Vec<String> {
+    let mut merged = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(first) = parse_printf(&lines[i]) else {
+            merged.push(lines[i].clone());
+            i += 1;
+            continue;
+        };
+
+        let mut combined = first;
+        let mut j = i + 1;
+        while let Some(next) = lines.get(j).and_then(|line| parse_printf(line)) {
+            if next.indent != combined.indent {
+                break;
+            }
+            combined.format.push_str(&next.format);
+            combined.args.extend(next.args);
+            j += 1;
+        }
+
+        if j - i > 1 {
+            stats.printfs_merged += j - i - 1;
+        }
+        merged.push(render_printf(&combined));
+        i = j;
+    }
+    merged
+}
+
+fn parse_printf(line: &str) -> Option<Printf<'_>> {
+    let statement = line.trim_start();
+    let indent = &line[..line.len() - statement.len()];
+    let rest = statement.strip_prefix("printf(\"")?.strip_suffix(");")?;
+
+    // The format literal ends at the first unescaped quote
+    let mut end = None;
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => {
+                end = Some(i);
+                break;
+            }
+            _ => escaped = false,
+        }
+    }
+    let end = end?;
+    let format = &rest[..end];
+    let tail = &rest[end + 1..];
+
+    let args = if tail.is_empty() {
+        Vec::new()
+    } else {
+        split_args(tail.strip_prefix(", ")?)?
+    };
+    if !args.iter().all(|arg| is_side_effect_free(arg)) {
+        return None;
+    }
+
+    // A string literal printed with %s becomes part of the format itself
+    if format == "%s" && args.len() == 1 {
+        if let Some(text) = string_literal(args[0]) {
+            return Some(Printf { indent, format: text.replace('%', "%%"), args: Vec::new() });
+        }
+    }
+
+    Some(Printf { indent, format: format.to_string(), args })
+}
+
+/// The contents of an argument that is exactly one string literal
+fn string_literal(arg: &str) -> Option<&str> {
+    let body = arg.strip_prefix('"')?;
+    let mut escaped = false;
+    for (i, c) in body.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return (i + 1 == body.len()).then(|| &body[..i]),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Split top-level comma separated arguments
+fn split_args(text: &str) -> Option<Vec<&str>> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut args = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut k = 0;
+    while k < chars.len() {
+        let (offset, c) = chars[k];
+        match c {
+            '"' | '\'' => {
+                k += 1;
+                while k < chars.len() && chars[k].1 != c {
+                    if chars[k].1 == '\\' {
+                        k += 1;
+                    }
+                    k += 1;
+                }
+            }
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(text[start..offset].trim());
+                start = offset + 1;
+            }
+            _ => {}
+        }
+        k += 1;
+    }
+    if depth != 0 {
+        return None;
+    }
+    args.push(text[start..].trim());
+    Some(args)
+}
+
+/// No calls and no assignments outside string literals
+fn is_side_effect_free(arg: &str) -> bool {
+    let chars: Vec<char> = arg.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '"' | '\'' => {
+                let quote = chars[i];
+                i += 1;
+                while i < chars.len() && chars[i] != quote {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            '(' if i > 0 && is_identifier_char(chars[i - 1]) => return false,
+            '=' => {
+                let previous = if i > 0 { chars[i - 1] } else { ' ' };
+                let next = chars.get(i + 1).copied().unwrap_or(' ');
+                if next != '=' && !matches!(previous, '=' | '!' | '<' | '>') {
+                    return false;
+                }
+                i += 1;
+            }
+            '+' | '-' if chars.get(i + 1) == Some(&chars[i]) => return false,
+            _ => {}
+        }
+        i += 1;
+    }
+    true
+}
+
+fn render_printf(printf: &Printf) -> String {
+    if printf.args.is_empty() {
+        return format!("{}printf(\"{}\");", printf.indent, printf.format);
+    }
+    format!("{}printf(\"{}\", {});", printf.indent, printf.format, printf.args.join(", "))
+}
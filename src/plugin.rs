@@ -0,0 +1,127 @@
+// src/plugin.rs - Compiler plugins: extra optimizer passes, lints and metrics
+//
+// A plugin implements `CompilerPlugin` to add to three stages without
+// changing them: an optimizer pass that runs after the built-in ones in
+// every round, lint rules whose findings are reported as warnings once a
+// program type checks, and per-function metrics the analyzer shows after
+// its own. Plugins are compiled in. Each lives in src/plugin/ behind a
+// `plugin-<name>` Cargo feature, so a course adds its checks with
+// `cargo build --features plugin-<name>` and no changes to the optimizer or
+// analyzer. Programs embedding the library can pass their own plugins to
+// `Optimizer::with_plugins`, `TypeChecker::with_plugins` and
+// `analyzer::analyze_program_with_plugins` instead.
+//
+// The API is versioned like a crate. A plugin says which `API_VERSION` it
+// was written against; it is used when the major versions match and its
+// minor version is not newer than this compiler's. Adding a hook with a
+// default method is a minor change, anything that could break an existing
+// plugin is a major one.
+
+use crate::ast::{Function, Program, Span};
+use std::fmt;
+
+#[cfg(feature = "plugin-style-guide")]
+pub mod style_guide;
+
+/// The version of this API, which plugins compare theirs against
+pub const API_VERSION: ApiVersion = ApiVersion { major: 1, minor: 0 };
+
+/// A version of the plugin API, written `MAJOR.MINOR`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ApiVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        ApiVersion { major, minor }
+    }
+
+    /// Whether a plugin written against this version works with `host`
+    pub fn works_with(self, host: ApiVersion) -> bool {
+        self.major == host.major && self.minor <= host.minor
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Something a lint rule found, reported as a warning with the code
+/// `<plugin>::<code>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    /// Short snake_case name of the rule, e.g. `snake_case`
+    pub code: String,
+    pub message: String,
+    /// The label under the highlighted source
+    pub label: String,
+    pub help: Option<String>,
+    pub span: Span,
+}
+
+/// A number a plugin measured for one function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    /// Short snake_case name, shown as `<plugin>.<name>`
+    pub name: String,
+    pub value: f64,
+}
+
+/// A compiler plugin. Every hook has a default that does nothing, so a
+/// plugin implements only the ones it needs.
+pub trait CompilerPlugin: Sync {
+    /// Short snake_case name, which prefixes its warning codes and metrics
+    fn name(&self) -> &'static str;
+
+    /// The `API_VERSION` the plugin was written against
+    fn api_version(&self) -> ApiVersion;
+
+    /// Rewrite the program as an extra optimizer pass, returning how many
+    /// changes were made. It runs in every round until nothing changes, so
+    /// a second run on its own output must change nothing, and it should
+    /// leave functions marked `@noopt` alone.
+    fn optimize(&self, _program: &mut Program) -> usize {
+        0
+    }
+
+    /// Extra lint rules, run on programs without type errors
+    fn lint(&self, _program: &Program) -> Vec<Lint> {
+        Vec::new()
+    }
+
+    /// Extra metrics for one function in the analyzer's report
+    fn metrics(&self, _function: &Function) -> Vec<Metric> {
+        Vec::new()
+    }
+}
+
+/// The plugins whose features this build was compiled with
+fn compiled() -> Vec<&'static dyn CompilerPlugin> {
+    vec![
+        #[cfg(feature = "plugin-style-guide")]
+        &style_guide::StyleGuide,
+    ]
+}
+
+/// The compiled-in plugins that work with this API version, which the
+/// optimizer, type checker and analyzer use unless given others
+pub fn installed() -> Vec<&'static dyn CompilerPlugin> {
+    compiled()
+        .into_iter()
+        .filter(|plugin| plugin.api_version().works_with(API_VERSION))
+        .collect()
+}
+
+/// Compiled-in plugins written for an API version this compiler does not
+/// provide, with that version
+pub fn rejected() -> Vec<(&'static str, ApiVersion)> {
+    compiled()
+        .into_iter()
+        .map(|plugin| (plugin.name(), plugin.api_version()))
+        .filter(|(_, version)| !version.works_with(API_VERSION))
+        .collect()
+}
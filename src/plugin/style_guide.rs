@@ -0,0 +1,80 @@
+// src/plugin/style_guide.rs - A sample plugin enforcing a course style guide
+//
+// Built with `--features plugin-style-guide`. It warns about function and
+// parameter names that are not snake_case, and counts the magic numbers in
+// each function: int and float literals other than 0 and 1, which a style
+// guide would have named as constants.
+
+use super::{ApiVersion, CompilerPlugin, Lint, Metric};
+use crate::ast::{Expression, Function, Literal, LiteralExpr, Program, Span};
+use crate::optimizer::for_each_expression_in_block;
+
+pub struct StyleGuide;
+
+impl CompilerPlugin for StyleGuide {
+    fn name(&self) -> &'static str {
+        "style_guide"
+    }
+
+    fn api_version(&self) -> ApiVersion {
+        ApiVersion::new(1, 0)
+    }
+
+    fn lint(&self, program: &Program) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        for function in &program.functions {
+            // Function spans cover the whole definition, so point at its start
+            let start = Span { start: function.span.start, end: function.span.start };
+            let names = std::iter::once(("function", &function.name, &start))
+                .chain(function.params.iter().map(|param| ("parameter", &param.name, &param.span)));
+            for (kind, name, span) in names {
+                if !is_snake_case(name) {
+                    lints.push(snake_case_lint(kind, name, span));
+                }
+            }
+        }
+        lints
+    }
+
+    fn metrics(&self, function: &Function) -> Vec<Metric> {
+        // The expression walker is the optimizer's, which needs a body it may change
+        let mut body = function.body.clone();
+        let mut count = 0;
+        for_each_expression_in_block(&mut body, &mut |expr| {
+            if let Expression::Literal(LiteralExpr { value, .. }) = expr {
+                let magic = match value {
+                    Literal::Integer(n) => !matches!(n, 0 | 1),
+                    Literal::Float(x) => *x != 0.0 && *x != 1.0,
+                    _ => false,
+                };
+                count += magic as usize;
+            }
+        });
+        vec![Metric { name: "magic_numbers".to_string(), value: count as f64 }]
+    }
+}
+
+fn is_snake_case(name: &str) -> bool {
+    name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn snake_case_lint(kind: &str, name: &str, span: &Span) -> Lint {
+    let mut suggestion = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !suggestion.ends_with('_') {
+                suggestion.push('_');
+            }
+            suggestion.push(c.to_ascii_lowercase());
+        } else {
+            suggestion.push(c);
+        }
+    }
+    Lint {
+        code: "snake_case".to_string(),
+        message: format!("{} '{}' is not in snake_case", kind, name),
+        label: "named here".to_string(),
+        help: Some(format!("The course style guide names {}s like '{}'", kind, suggestion)),
+        span: span.clone(),
+    }
+}
@@ -0,0 +1,223 @@
+// src/reduce.rs - Shrinking a program while a failure still shows
+//
+// `minilang reduce <file> --check '<command>'` finds a small program that
+// still makes the command exit with 0, for triaging optimizer and codegen
+// bugs. The command gets each candidate as a file and decides whether it
+// still shows the failure, e.g. by compiling it and grepping the output.
+//
+// Candidates are made by hierarchical delta debugging: ddmin first over the
+// functions, then over the statements directly in their bodies, then over
+// the statements one block deeper, and so on, cutting each removed node out
+// of the text. Only candidates that still parse are handed to the command.
+// Passes repeat until one removes nothing.
+
+use crate::ast::{Block, Program, Span, Statement};
+use crate::backend;
+use crate::control_flow::Loop;
+use crate::{Lexer, Parser};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Where the candidate's path goes in a check command; without it the
+/// path is added at the end
+pub const PLACEHOLDER: &str = "{}";
+
+/// The result of shrinking a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reduction {
+    pub source: String,
+    /// How many candidates were checked
+    pub tests: usize,
+}
+
+/// Shrink `source`, which must parse and satisfy `interesting`, by removing
+/// functions and statements. Removing any one of those kept makes the
+/// program stop parsing or stop being interesting.
+pub fn reduce(source: &str, mut interesting: impl FnMut(&str) -> bool) -> Reduction {
+    let mut seen: HashMap<String, bool> = HashMap::new();
+    let mut test = |candidate: String| {
+        *seen.entry(candidate).or_insert_with_key(|candidate| parses(candidate) && interesting(candidate))
+    };
+
+    let mut current = source.to_string();
+    loop {
+        let before = current.clone();
+        for depth in 0.. {
+            let Some(program) = parse(&current) else { break };
+            let spans = nodes_at(&program, depth);
+            if spans.is_empty() {
+                break;
+            }
+            let without = |kept: &[usize]| {
+                let removed: Vec<&Span> = (0..spans.len())
+                    .filter(|index| !kept.contains(index))
+                    .map(|index| &spans[index])
+                    .collect();
+                cut(&current, &removed)
+            };
+            let kept = ddmin((0..spans.len()).collect(), &mut |kept| test(without(kept)));
+            current = without(&kept);
+        }
+        if current == before {
+            break;
+        }
+    }
+
+    Reduction { source: current, tests: seen.len() }
+}
+
+/// Zeller's ddmin: the smallest subsequence of `items` found to satisfy
+/// `test`, which `items` itself must. Chunks are removed while the rest
+/// still passes, halving the chunk size whenever none can go.
+pub fn ddmin<T: Clone>(mut items: Vec<T>, test: &mut impl FnMut(&[T]) -> bool) -> Vec<T> {
+    if !items.is_empty() && test(&[]) {
+        return Vec::new();
+    }
+    let mut chunks = 2;
+    while items.len() >= 2 {
+        let size = items.len().div_ceil(chunks);
+        let complement = (0..items.len()).step_by(size)
+            .map(|start| [&items[..start], &items[(start + size).min(items.len())..]].concat())
+            .find(|candidate| test(candidate));
+        match complement {
+            Some(candidate) => {
+                items = candidate;
+                chunks = (chunks - 1).max(2);
+            }
+            None if chunks >= items.len() => break,
+            None => chunks = (chunks * 2).min(items.len()),
+        }
+    }
+    items
+}
+
+/// Whether `source` lexes and parses
+pub fn parses(source: &str) -> bool {
+    parse(source).is_some()
+}
+
+fn parse(source: &str) -> Option<Program> {
+    let tokens = Lexer::new(source).tokenize().ok()?;
+    Parser::new(tokens, source.to_string()).parse_program().ok()
+}
+
+/// The spans of the functions at depth 0, the statements of their bodies at
+/// depth 1, and those of blocks nested `depth - 1` deep below that
+fn nodes_at(program: &Program, depth: usize) -> Vec<Span> {
+    let mut spans = Vec::new();
+    for function in &program.functions {
+        match depth {
+            0 => spans.push(function.span.clone()),
+            _ => statements_at(&function.body, depth - 1, &mut spans),
+        }
+    }
+    spans
+}
+
+fn statements_at(block: &Block, depth: usize, out: &mut Vec<Span>) {
+    for statement in &block.statements {
+        if depth == 0 {
+            out.push(statement.span().clone());
+        } else {
+            for inner in blocks_in(statement) {
+                statements_at(inner, depth - 1, out);
+            }
+        }
+    }
+}
+
+/// The blocks directly inside `statement`
+fn blocks_in(statement: &Statement) -> Vec<&Block> {
+    if let Some(looped) = Loop::of(statement) {
+        return vec![looped.body];
+    }
+    match statement {
+        Statement::If(if_stmt) => std::iter::once(&if_stmt.then_block).chain(&if_stmt.else_block).collect(),
+        Statement::Block(block) => vec![block],
+        Statement::Defer(defer_stmt) => vec![&defer_stmt.body],
+        Statement::Spawn(spawn_stmt) => vec![&spawn_stmt.body],
+        _ => Vec::new(),
+    }
+}
+
+/// `source` without the text of `spans`, which do not overlap. A node that
+/// had its lines to itself takes them along.
+fn cut(source: &str, spans: &[&Span]) -> String {
+    let mut spans = spans.to_vec();
+    spans.sort_by_key(|span| std::cmp::Reverse(span.start));
+
+    let mut text = source.to_string();
+    for span in spans {
+        let line_start = text[..span.start].rfind('\n').map_or(0, |at| at + 1);
+        let line_end = text[span.end..].find('\n').map_or(text.len(), |at| span.end + at + 1);
+        let alone = text[line_start..span.start].trim().is_empty() && text[span.end..line_end].trim().is_empty();
+        if alone {
+            text.replace_range(line_start..line_end, "");
+        } else {
+            text.replace_range(span.start..span.end, "");
+        }
+    }
+    text
+}
+
+// ==================== CHECK COMMANDS ====================
+
+/// A shell command that tells whether a candidate still shows the failure
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub command: String,
+    /// Runs longer than this count as not showing it
+    pub timeout: Duration,
+}
+
+impl Check {
+    /// The command line for the candidate at `path`
+    pub fn command_line(&self, path: &Path) -> String {
+        let quoted = backend::shell_command(&[path.to_string_lossy().into_owned()]);
+        if self.command.contains(PLACEHOLDER) {
+            self.command.replace(PLACEHOLDER, &quoted)
+        } else {
+            format!("{} {}", self.command, quoted)
+        }
+    }
+
+    /// Write `source` to a temporary file called `file_name` and run the
+    /// command on it with `sh -c`: whether it exits with 0 in time
+    pub fn passes(&self, source: &str, file_name: &str) -> io::Result<bool> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(file_name);
+        fs::write(&path, source)?;
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(self.command_line(&path))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        Ok(wait_timeout(&mut child, self.timeout)?.is_some_and(|status| status.success()))
+    }
+}
+
+/// Wait for `child` to exit, killing it after `timeout`. Returns `None` when
+/// it had to be killed.
+pub fn wait_timeout(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if started.elapsed() > timeout {
+            // It may exit on its own in between; either way it is gone after wait
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,298 @@
+// src/refactor.rs - Source-to-source refactorings
+
+use crate::ast::*;
+use crate::control_flow;
+use crate::{Defines, Lexer, Parser, TypeChecker};
+use std::collections::HashSet;
+
+/// Lines of unchanged context around each hunk of a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// The outcome of a refactoring: the rewritten source and the functions it touched
+#[derive(Debug, Clone, PartialEq)]
+pub struct Refactoring {
+    pub source: String,
+    pub functions: Vec<String>,
+}
+
+/// Turn guard-style `if` statements that wrap the rest of a function body
+/// into early returns:
+///
+/// - `if c { work }` at the end of a function without a return type becomes
+///   `if NOT c { send; }` followed by `work`
+/// - `if c { work } else { ...send }` becomes `if NOT c { ...send }` followed by `work`
+/// - `if c { ...send } else { work }` becomes `if c { ...send }` followed by `work`
+///
+/// When both branches return, the shorter one is kept as the guard.
+///
+/// Rewrites repeat until no guard is left, so nested guards are flattened one
+/// level at a time. Comments and formatting inside the blocks are kept. A
+/// rewrite that would no longer compile (e.g. because a variable declared
+/// inside the block clashes with one outside it) leaves its function as it was.
+pub fn early_return(source: &str, defines: &Defines) -> Refactoring {
+    let mut source = source.to_string();
+    let mut functions: Vec<String> = Vec::new();
+    let mut skipped = HashSet::new();
+
+    while let Some(program) = compile(&source, defines) {
+        let rewrite = program.functions.iter()
+            .filter(|function| !skipped.contains(&function.name))
+            .find_map(|function| guard_rewrite(&source, function).map(|edit| (&function.name, edit)));
+        let Some((name, (range, replacement))) = rewrite else { break };
+
+        let mut rewritten = source.clone();
+        rewritten.replace_range(range, &replacement);
+        if compile(&rewritten, defines).is_some() {
+            source = rewritten;
+            if !functions.contains(name) {
+                functions.push(name.clone());
+            }
+        } else {
+            skipped.insert(name.clone());
+        }
+    }
+
+    Refactoring { source, functions }
+}
+
+/// Parse and type check `source`, returning the program when both succeed
+fn compile(source: &str, defines: &Defines) -> Option<Program> {
+    let tokens = Lexer::new(source).tokenize().ok()?;
+    let program = Parser::new(tokens, source.to_string())
+        .with_defines(defines.clone())
+        .parse_program()
+        .ok()?;
+    TypeChecker::new().check_program(&program).ok()?;
+    Some(program)
+}
+
+/// The edit turning the trailing `if` of `function` into a guard, if it is one
+fn guard_rewrite(source: &str, function: &Function) -> Option<(std::ops::Range<usize>, String)> {
+    let Some(Statement::If(if_stmt)) = function.body.statements.last() else { return None };
+
+    // Anything after the `if` (such as an `@if` branch that was not
+    // selected) would be skipped by the early return
+    let after = &source[if_stmt.span.end..function.body.span.end - 1];
+    if after.lines().any(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#')) {
+        return None;
+    }
+
+    let line_start = source[..if_stmt.span.start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &source[line_start..if_stmt.span.start];
+    if !indent.chars().all(char::is_whitespace) {
+        return None;
+    }
+
+    let then_block = &if_stmt.then_block;
+    let replacement = match &if_stmt.else_block {
+        None if function.return_type.is_none() && !then_block.statements.is_empty() => {
+            let unit = indent_unit(source, then_block, indent);
+            format!(
+                "if {} {{\n{indent}{unit}send;\n{indent}}}\n{}",
+                negate(&if_stmt.condition),
+                dedent_block(source, then_block, indent)?,
+            )
+        }
+        // `else if` chains have no braces of their own to reuse
+        Some(else_block) if source.as_bytes()[else_block.span.start] != b'{' => return None,
+        // When both branches return, the shorter one makes the better guard
+        Some(else_block) if always_returns(then_block)
+            && !else_block.statements.is_empty()
+            && (!always_returns(else_block) || then_block.statements.len() <= else_block.statements.len()) =>
+        {
+            format!(
+                "{}\n{}",
+                &source[if_stmt.span.start..then_block.span.end],
+                dedent_block(source, else_block, indent)?,
+            )
+        }
+        Some(else_block) if always_returns(else_block) && !then_block.statements.is_empty() => {
+            format!(
+                "if {} {}\n{}",
+                negate(&if_stmt.condition),
+                &source[else_block.span.start..else_block.span.end],
+                dedent_block(source, then_block, indent)?,
+            )
+        }
+        _ => return None,
+    };
+
+    Some((if_stmt.span.start..if_stmt.span.end, replacement))
+}
+
+/// Whether control never falls off the end of `block`
+fn always_returns(block: &Block) -> bool {
+    !control_flow::block_completes(block)
+}
+
+/// `condition` inverted, dropping a `NOT` rather than adding a second one
+fn negate(condition: &Expression) -> String {
+    match condition {
+        Expression::Unary(UnaryExpr { op: UnaryOp::Not, operand, .. }) => {
+            let mut operand = operand.as_ref().clone();
+            match &mut operand {
+                Expression::Binary(binary) => binary.parenthesized = false,
+                Expression::Unary(unary) => unary.parenthesized = false,
+                _ => {}
+            }
+            operand.to_string()
+        }
+        _ => Expression::Unary(UnaryExpr {
+            op: UnaryOp::Not,
+            operand: Box::new(condition.clone()),
+            span: Span::new(0, 0),
+            id: NodeId::default(),
+            parenthesized: false,
+        }).to_string(),
+    }
+}
+
+/// The extra indentation of the statements in `block` relative to `indent`,
+/// four spaces when the block is written on one line
+fn indent_unit<'a>(source: &'a str, block: &Block, indent: &str) -> &'a str {
+    block.statements.first()
+        .map(|statement| {
+            let start = statement.span().start;
+            let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+            &source[line_start..start]
+        })
+        .filter(|statement_indent| {
+            statement_indent.len() > indent.len()
+                && statement_indent.starts_with(indent)
+                && statement_indent.chars().all(char::is_whitespace)
+        })
+        .map_or("    ", |statement_indent| &statement_indent[indent.len()..])
+}
+
+/// The inside of `block` moved out one level, so its lines start at `indent`
+fn dedent_block(source: &str, block: &Block, indent: &str) -> Option<String> {
+    let inner = &source[block.span.start + 1..block.span.end - 1];
+    if !inner.contains('\n') {
+        return Some(format!("{}{}", indent, inner.trim()));
+    }
+
+    let unit = indent_unit(source, block, indent);
+    let nested = format!("{}{}", indent, unit);
+    // Drop the rest of the `{` line and the indentation before the `}`
+    let inner = inner.trim_end_matches([' ', '\t']);
+    let (first, rest) = inner.split_once('\n')?;
+    if !first.trim().is_empty() {
+        return None;
+    }
+
+    let lines: Vec<String> = rest.trim_end_matches('\n')
+        .lines()
+        .map(|line| match line.strip_prefix(nested.as_str()) {
+            Some(body) => format!("{}{}", indent, body),
+            None if line.trim().is_empty() => String::new(),
+            // Continuation lines that were not indented with the block
+            None => line.to_string(),
+        })
+        .collect();
+    Some(lines.join("\n"))
+}
+
+// ==================== DIFF ====================
+
+enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A unified diff from `old` to `new`, labelled with `path` the way git
+/// labels it (`a/path`, `b/path`). Empty when nothing changed.
+pub fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    // Changed lines whose context overlaps share a hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if matches!(line, DiffLine::Same(_)) {
+            continue;
+        }
+        let start = i.saturating_sub(DIFF_CONTEXT);
+        let end = (i + DIFF_CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path, path);
+    for (start, end) in hunks {
+        let old_start = lines[..start].iter().filter(|line| !matches!(line, DiffLine::Added(_))).count();
+        let new_start = lines[..start].iter().filter(|line| !matches!(line, DiffLine::Removed(_))).count();
+        let hunk = &lines[start..end];
+        let old_len = hunk.iter().filter(|line| !matches!(line, DiffLine::Added(_))).count();
+        let new_len = hunk.iter().filter(|line| !matches!(line, DiffLine::Removed(_))).count();
+
+        diff.push_str(&format!("@@ -{} +{} @@\n", hunk_range(old_start, old_len), hunk_range(new_start, new_len)));
+        for line in hunk {
+            let (marker, text) = match line {
+                DiffLine::Same(text) => (' ', text),
+                DiffLine::Removed(text) => ('-', text),
+                DiffLine::Added(text) => ('+', text),
+            };
+            diff.push(marker);
+            diff.push_str(text);
+            diff.push('\n');
+        }
+    }
+    diff
+}
+
+/// `start,len` as a hunk header writes it: 1-based, with `,1` left out
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Line by line edit script from `old` to `new`, via their longest common subsequence
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    // common[i][j]: length of the LCS of old_middle[i..] and new_middle[j..]
+    let mut common = vec![vec![0usize; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            common[i][j] = if old_middle[i] == new_middle[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<DiffLine> = old[..prefix].iter().map(|line| DiffLine::Same(line)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            lines.push(DiffLine::Same(old_middle[i]));
+            i += 1;
+            j += 1;
+        } else if j == new_middle.len() || (i < old_middle.len() && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(DiffLine::Removed(old_middle[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new_middle[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|line| DiffLine::Same(line)));
+    lines
+}
@@ -0,0 +1,297 @@
+// src/repro.rs - Minimal reproductions of compiler bugs as regression tests
+//
+// `minilang repro save <file> --failure <kind>` shrinks a program that shows
+// a compiler bug to the fewest lines that still show it and stores the result
+// in a corpus directory, tests/repro by default. tests/repro_tests.rs checks
+// every program there, so once the bug is fixed the case keeps it fixed.
+//
+// Shrinking is delta debugging (reduce::ddmin) over lines: drop ever smaller
+// chunks of lines and keep each removal after which the failure still shows.
+// A candidate that no longer parses or type checks simply does not show the
+// failure, so it is never kept unless the failure is in the front end itself.
+
+use crate::backend::{self, GCC_FLAGS};
+use crate::reduce::{self, ddmin, Reduction};
+use crate::{CodeGenerator, Lexer, Optimizer, OverflowMode, Parser, TypeChecker};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Once;
+use std::thread;
+use std::time::Duration;
+
+/// Where `repro save` stores cases unless told otherwise
+pub const DEFAULT_CORPUS: &str = "tests/repro";
+
+/// How long a compiled program may run before it is taken to hang: removing
+/// lines easily leaves a loop that never ends
+pub const RUN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The first line of every corpus file, followed by the failure's name
+const HEADER: &str = "# repro: ";
+
+/// The bug a program shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Failure {
+    /// The compiler panics, or fails to generate code for a program it accepted
+    Ice,
+    /// The program prints something else, or exits differently, at -O2 than at -O0
+    WrongOutput,
+    /// The type checker accepts the program but the C compiler rejects the C
+    Divergence,
+}
+
+impl Failure {
+    /// The name used on the command line and in corpus files
+    pub fn name(self) -> &'static str {
+        match self {
+            Failure::Ice => "ice",
+            Failure::WrongOutput => "wrong-output",
+            Failure::Divergence => "divergence",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Failure> {
+        [Failure::Ice, Failure::WrongOutput, Failure::Divergence]
+            .into_iter()
+            .find(|failure| failure.name() == name)
+    }
+}
+
+/// Whether `source` shows `failure`. `cc` is the C compiler used to build
+/// and run it; an ICE is found without one.
+pub fn reproduces(failure: Failure, source: &str, cc: &str) -> bool {
+    match failure {
+        Failure::Ice => {
+            let built = quietly(|| [0, 2].map(|level| build(source, level)));
+            match built {
+                Some(levels) => levels.iter().any(|built| matches!(built, Build::Internal)),
+                None => true,
+            }
+        }
+        Failure::Divergence => [0, 2].into_iter().any(|level| match quietly(|| build(source, level)) {
+            Some(Build::C(c_code)) => compile(cc, &c_code).is_err(),
+            _ => false,
+        }),
+        Failure::WrongOutput => {
+            let [Some(unoptimized), Some(optimized)] = [0, 2].map(|level| run(cc, source, level)) else {
+                return false;
+            };
+            unoptimized != optimized
+        }
+    }
+}
+
+/// What the compiler made of a program
+enum Build {
+    /// The front end or the optimizer reported errors in it
+    Rejected,
+    C(String),
+    /// Code generation failed after the program was accepted
+    Internal,
+}
+
+/// Compile `source` to C at `-O{level}` the way `minilang compile` does
+fn build(source: &str, level: u8) -> Build {
+    let Ok(tokens) = Lexer::new(source).tokenize() else { return Build::Rejected };
+    let Ok(mut program) = Parser::new(tokens, source.to_string()).parse_program() else { return Build::Rejected };
+    if TypeChecker::new().check_program(&program).is_err() {
+        return Build::Rejected;
+    }
+    if level > 0 {
+        let mut optimizer = Optimizer::new(level);
+        optimizer.optimize(&mut program);
+        if !optimizer.errors().is_empty() {
+            return Build::Rejected;
+        }
+    }
+    let generated = CodeGenerator::new()
+        .with_source("repro.mini", source)
+        .with_peephole(level >= 1)
+        .with_line_tracking(level == 0)
+        .generate(&program);
+    match generated {
+        Ok(c_code) => Build::C(c_code),
+        Err(_) => Build::Internal,
+    }
+}
+
+/// Build `c_code` with `cc` in a temporary directory, returning the
+/// directory and the executable in it
+fn compile(cc: &str, c_code: &str) -> io::Result<(tempfile::TempDir, PathBuf)> {
+    let dir = tempfile::tempdir()?;
+    let c_file = dir.path().join("repro.c");
+    let exe = dir.path().join("repro");
+    fs::write(&c_file, c_code)?;
+    let flags: Vec<&str> = GCC_FLAGS.iter().copied().chain([OverflowMode::default().cc_flag()]).collect();
+    backend::compile_c(cc, &c_file, &exe, &flags).map_err(io::Error::other)?;
+    Ok((dir, exe))
+}
+
+/// Build and run `source` at `-O{level}`: its stdout and exit code, or
+/// `None` when it does not build or runs past RUN_TIMEOUT
+fn run(cc: &str, source: &str, level: u8) -> Option<(String, Option<i32>)> {
+    let Some(Build::C(c_code)) = quietly(|| build(source, level)) else { return None };
+    let (_dir, exe) = compile(cc, &c_code).ok()?;
+    let mut child = Command::new(&exe)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // Read stdout on the side so a chatty program cannot fill the pipe and stall
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut text = String::new();
+        io::Read::read_to_string(&mut stdout, &mut text).map(|_| text)
+    });
+    let status = reduce::wait_timeout(&mut child, RUN_TIMEOUT).ok()??;
+    let text = reader.join().ok()?.ok()?;
+    Some((text, status.code()))
+}
+
+thread_local! {
+    /// Set while `quietly` runs, to keep expected panics off stderr
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f`, returning `None` if it panics. The panic message is not printed:
+/// shrinking a program that crashes the compiler makes it crash many times.
+fn quietly<T>(f: impl FnOnce() -> T) -> Option<T> {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !QUIET.with(Cell::get) {
+                previous(info);
+            }
+        }));
+    });
+
+    QUIET.with(|quiet| quiet.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    QUIET.with(|quiet| quiet.set(false));
+    result.ok()
+}
+
+// ==================== SHRINKING ====================
+
+/// The smallest selection of `source`'s lines, in their order, found to
+/// still satisfy `reproduces`, which `source` itself must. Removing any one
+/// of the lines kept, or a block's opening line together with its closing
+/// one, makes the failure go away.
+pub fn minimize(source: &str, mut reproduces: impl FnMut(&str) -> bool) -> Reduction {
+    let mut lines: Vec<&str> = source.lines().collect();
+    // Candidates come up again as the chunks get smaller
+    let mut seen: HashMap<String, bool> = HashMap::new();
+    let mut test = |lines: &[&str]| {
+        let candidate = join(lines);
+        *seen.entry(candidate).or_insert_with_key(|candidate| reproduces(candidate))
+    };
+
+    loop {
+        lines = ddmin(lines, &mut test);
+        // ddmin only removes neighbouring lines together, but unwrapping a
+        // block takes its `{` line and its `}` line at once
+        let unwrapped = block_pairs(&lines).into_iter()
+            .map(|(open, close)| lines.iter()
+                .enumerate()
+                .filter(|&(index, _)| index != open && index != close)
+                .map(|(_, line)| *line)
+                .collect::<Vec<_>>())
+            .find(|candidate| test(candidate));
+        match unwrapped {
+            Some(candidate) => lines = candidate,
+            None => break,
+        }
+    }
+
+    Reduction { source: join(&lines), tests: seen.len() }
+}
+
+/// The indexes of each line ending in `{` and the line whose `}` closes it.
+/// Braces are matched a line at a time, which is how MiniLang is written.
+fn block_pairs(lines: &[&str]) -> Vec<(usize, usize)> {
+    let mut open = Vec::new();
+    let mut pairs = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if line.starts_with('}') {
+            if let Some(start) = open.pop() {
+                pairs.push((start, index));
+            }
+        }
+        if line.ends_with('{') {
+            open.push(index);
+        }
+    }
+    pairs
+}
+
+fn join(lines: &[&str]) -> String {
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+// ==================== CORPUS ====================
+
+/// A program in the corpus and the bug it once showed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub path: PathBuf,
+    pub failure: Failure,
+    /// The whole file, header included: it is a comment
+    pub source: String,
+}
+
+/// The contents of a corpus file for `source`, which shows `failure`
+pub fn case_file(failure: Failure, source: &str) -> String {
+    format!("{}{}\n{}", HEADER, failure.name(), source)
+}
+
+/// Store `source` in `corpus` as `<name>.mini`, creating the directory.
+/// Fails rather than replace a case that is already there.
+pub fn save(corpus: &Path, name: &str, failure: Failure, source: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(corpus)?;
+    let path = corpus.join(format!("{}.mini", name));
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+    io::Write::write_all(&mut file, case_file(failure, source).as_bytes())?;
+    Ok(path)
+}
+
+/// Every case in `corpus`, sorted by path. A missing directory has none.
+pub fn read_corpus(corpus: &Path) -> io::Result<Vec<Case>> {
+    let entries = match fs::read_dir(corpus) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "mini") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    paths.into_iter()
+        .map(|path| {
+            let source = fs::read_to_string(&path)?;
+            let failure = source.lines().next()
+                .and_then(|line| line.strip_prefix(HEADER))
+                .and_then(|name| Failure::from_name(name.trim()))
+                .ok_or_else(|| io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} does not start with '{}<failure>'", path.display(), HEADER),
+                ))?;
+            Ok(Case { path, failure, source })
+        })
+        .collect()
+}
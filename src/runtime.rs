@@ -0,0 +1,324 @@
+// src/runtime.rs - Heap management in the generated C
+//
+// Strings built at runtime (interpolation, format, to_string) live on the
+// heap. `--runtime` picks who frees them:
+//
+// - arena (default): every allocation is kept in a list and freed at exit.
+//   Nothing is freed while the program runs, so a loop that builds strings
+//   grows the heap on every iteration.
+// - rc: reference counting. A header in front of each object counts the
+//   variables holding it. New objects, and objects whose count drops to
+//   zero, wait in a pool until the statement that made them finishes, so
+//   temporaries live long enough to be used; the function then frees those
+//   no variable took. Objects stored into arrays or tuples, whose copies the
+//   C does not track, are pinned and freed at exit like the arena's.
+//   Literals carry a static header so variables can hold either kind.
+// - none: plain malloc, never freed.
+//
+// `--profile` counts allocations and frees and prints them at exit. The
+// pool and the counters are weak symbols, so all modules of a program share
+// one of each.
+//
+// Programs with spawn blocks allocate from several threads, so the arena and
+// the counters take a lock. Reference counts would need one on every
+// assignment, so rc does not support threads.
+
+use serde::{Serialize, Deserialize};
+
+/// Who frees heap objects in the generated C, as chosen with `--runtime`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeMode {
+    /// Reference counting: objects are freed when no variable holds them
+    Rc,
+    /// Keep every object until the program exits
+    #[default]
+    Arena,
+    /// Never free anything
+    None,
+}
+
+impl RuntimeMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuntimeMode::Rc => "rc",
+            RuntimeMode::Arena => "arena",
+            RuntimeMode::None => "none",
+        }
+    }
+}
+
+/// The C that allocates (and frees) heap objects: `_minilang_string_alloc`
+/// in every mode, plus the reference counting calls in rc mode. With
+/// `profile`, allocations are counted; `report` adds the function that
+/// prints the counts, for the module defining `main`. With `threads`,
+/// allocating takes a lock wherever it touches shared state.
+pub(crate) fn heap_support(mode: RuntimeMode, profile: bool, report: bool, threads: bool) -> String {
+    let mut c = String::new();
+    if profile || mode == RuntimeMode::Rc {
+        // A macro, because the peephole pass would unwrap the inner parentheses
+        c.push_str("#define _MINILANG_WEAK __attribute__((weak))\n\n");
+    }
+    if profile {
+        c.push_str(STATS);
+        if report {
+            c.push_str(&REPORT.replace("{mode}", mode.name()));
+        }
+    }
+    let count = if profile { "    _minilang_count_alloc(size);\n" } else { "" };
+    // Without counters, plain malloc needs no lock
+    let locked = threads && (mode == RuntimeMode::Arena || profile);
+    let (lock, unlock) = if locked {
+        c.push_str("static pthread_mutex_t _minilang_heap_lock = PTHREAD_MUTEX_INITIALIZER;\n\n");
+        ("    pthread_mutex_lock(&_minilang_heap_lock);\n", "    pthread_mutex_unlock(&_minilang_heap_lock);\n")
+    } else {
+        ("", "")
+    };
+    match mode {
+        RuntimeMode::Arena => c.push_str(&ARENA.replace("{count}", count).replace("{lock}", lock).replace("{unlock}", unlock)),
+        RuntimeMode::None => c.push_str(&NONE.replace("{count}", &format!("{}{}{}", lock, count, unlock))),
+        RuntimeMode::Rc => {
+            let count_free = if profile { "            _minilang_count_free(object->size);\n" } else { "" };
+            c.push_str(&RC.replace("{count}", count).replace("{count_free}", count_free));
+        }
+    }
+    c
+}
+
+/// The C for strings built in a stack buffer, which `--runtime=rc` must
+/// see as static like literals. Strings too long for the buffer go on the heap.
+pub(crate) fn stack_string_support(mode: RuntimeMode) -> String {
+    let (header, mark) = match mode {
+        RuntimeMode::Rc => (
+            "    _minilang_object header;\n",
+            "        stack->header.refs = _MINILANG_STATIC_REFS;\n",
+        ),
+        _ => ("", ""),
+    };
+    STACK_STRING.replace("{header}", header).replace("{mark}", mark)
+}
+
+/// The C type of a string literal with room for `len` bytes and the header
+/// reference counting expects, in rc mode
+pub(crate) fn static_string_type(len: usize) -> String {
+    format!("static struct {{ _minilang_object header; char text[{}]; }}", len + 1)
+}
+
+const STATS: &str = r#"typedef struct {
+    size_t allocated, allocated_bytes;
+    size_t freed, freed_bytes;
+    size_t live, live_bytes;
+    size_t peak, peak_bytes;
+} _minilang_stats_t;
+
+_MINILANG_WEAK _minilang_stats_t _minilang_stats = {0};
+
+static inline void _minilang_count_alloc(size_t size) {
+    _minilang_stats.allocated++;
+    _minilang_stats.allocated_bytes += size;
+    _minilang_stats.live++;
+    _minilang_stats.live_bytes += size;
+    if (_minilang_stats.live_bytes > _minilang_stats.peak_bytes) {
+        _minilang_stats.peak = _minilang_stats.live;
+        _minilang_stats.peak_bytes = _minilang_stats.live_bytes;
+    }
+}
+
+static inline void _minilang_count_free(size_t size) {
+    _minilang_stats.freed++;
+    _minilang_stats.freed_bytes += size;
+    _minilang_stats.live--;
+    _minilang_stats.live_bytes -= size;
+}
+
+"#;
+
+const REPORT: &str = r#"static void _minilang_profile_report(void) {
+    fflush(stdout);
+    fprintf(stderr, "\n--- runtime profile (--runtime={mode}) ---\n");
+    fprintf(stderr, "allocations:  %zu (%zu bytes)\n", _minilang_stats.allocated, _minilang_stats.allocated_bytes);
+    fprintf(stderr, "freed:        %zu (%zu bytes)\n", _minilang_stats.freed, _minilang_stats.freed_bytes);
+    fprintf(stderr, "peak live:    %zu (%zu bytes)\n", _minilang_stats.peak, _minilang_stats.peak_bytes);
+    fprintf(stderr, "live at exit: %zu (%zu bytes)\n", _minilang_stats.live, _minilang_stats.live_bytes);
+}
+
+"#;
+
+const ARENA: &str = r#"static char** _minilang_arena = NULL;
+static size_t _minilang_arena_len = 0;
+static size_t _minilang_arena_cap = 0;
+
+static void _minilang_arena_free(void) {
+    for (size_t i = 0; i < _minilang_arena_len; i++) {
+        free(_minilang_arena[i]);
+    }
+    free(_minilang_arena);
+}
+
+static char* _minilang_string_alloc(size_t size) {
+{lock}    if (_minilang_arena_len == _minilang_arena_cap) {
+        if (_minilang_arena_cap == 0) {
+            atexit(_minilang_arena_free);
+        }
+        _minilang_arena_cap = _minilang_arena_cap ? _minilang_arena_cap * 2 : 16;
+        _minilang_arena = realloc(_minilang_arena, _minilang_arena_cap * sizeof(char*));
+    }
+    char* buffer = malloc(size);
+    if (_minilang_arena == NULL || buffer == NULL) {
+        fprintf(stderr, "Runtime Error: out of memory\n");
+        exit(1);
+    }
+    _minilang_arena[_minilang_arena_len++] = buffer;
+{count}{unlock}    return buffer;
+}
+
+"#;
+
+const NONE: &str = r#"static char* _minilang_string_alloc(size_t size) {
+    char* buffer = malloc(size);
+    if (buffer == NULL) {
+        fprintf(stderr, "Runtime Error: out of memory\n");
+        exit(1);
+    }
+{count}    return buffer;
+}
+
+"#;
+
+const RC: &str = r#"typedef struct {
+    int refs;
+    unsigned char pooled;
+    unsigned char pinned;
+    size_t size;
+} _minilang_object;
+
+#define _MINILANG_STATIC_REFS (-1)
+#define _MINILANG_HEADER(text) ((_minilang_object*)(text) - 1)
+
+_MINILANG_WEAK _minilang_object** _minilang_pool = NULL;
+_MINILANG_WEAK size_t _minilang_pool_len = 0;
+_MINILANG_WEAK size_t _minilang_pool_cap = 0;
+
+static _minilang_object** _minilang_pinned = NULL;
+static size_t _minilang_pinned_len = 0;
+static size_t _minilang_pinned_cap = 0;
+
+static void _minilang_out_of_memory(void) {
+    fprintf(stderr, "Runtime Error: out of memory\n");
+    exit(1);
+}
+
+static void _minilang_pinned_free(void) {
+    for (size_t i = 0; i < _minilang_pinned_len; i++) {
+        free(_minilang_pinned[i]);
+    }
+    free(_minilang_pinned);
+}
+
+static void _minilang_pool_push(_minilang_object* object) {
+    if (object->pooled) {
+        return;
+    }
+    if (_minilang_pool_len == _minilang_pool_cap) {
+        _minilang_pool_cap = _minilang_pool_cap ? _minilang_pool_cap * 2 : 64;
+        _minilang_pool = realloc(_minilang_pool, _minilang_pool_cap * sizeof(_minilang_object*));
+        if (_minilang_pool == NULL) {
+            _minilang_out_of_memory();
+        }
+    }
+    object->pooled = 1;
+    _minilang_pool[_minilang_pool_len++] = object;
+}
+
+static char* _minilang_string_alloc(size_t size) {
+    _minilang_object* object = malloc(sizeof(_minilang_object) + size);
+    if (object == NULL) {
+        _minilang_out_of_memory();
+    }
+    object->refs = 0;
+    object->pooled = 0;
+    object->pinned = 0;
+    object->size = size;
+    _minilang_pool_push(object);
+{count}    return (char*)(object + 1);
+}
+
+static inline const char* _minilang_retain(const char* text) {
+    if (text != NULL && _MINILANG_HEADER(text)->refs != _MINILANG_STATIC_REFS) {
+        _MINILANG_HEADER(text)->refs++;
+    }
+    return text;
+}
+
+static inline void _minilang_release(const char* text) {
+    if (text != NULL && _MINILANG_HEADER(text)->refs > 0 && --_MINILANG_HEADER(text)->refs == 0) {
+        _minilang_pool_push(_MINILANG_HEADER(text));
+    }
+}
+
+static inline const char* _minilang_assign(const char** slot, const char* text) {
+    _minilang_retain(text);
+    _minilang_release(*slot);
+    *slot = text;
+    return text;
+}
+
+static inline const char* _minilang_pin(const char* text) {
+    if (text == NULL) {
+        return text;
+    }
+    _minilang_object* object = _MINILANG_HEADER(text);
+    if (object->refs == _MINILANG_STATIC_REFS || object->pinned) {
+        return text;
+    }
+    if (_minilang_pinned_len == _minilang_pinned_cap) {
+        if (_minilang_pinned_cap == 0) {
+            atexit(_minilang_pinned_free);
+        }
+        _minilang_pinned_cap = _minilang_pinned_cap ? _minilang_pinned_cap * 2 : 16;
+        _minilang_pinned = realloc(_minilang_pinned, _minilang_pinned_cap * sizeof(_minilang_object*));
+        if (_minilang_pinned == NULL) {
+            _minilang_out_of_memory();
+        }
+    }
+    object->pinned = 1;
+    _minilang_pinned[_minilang_pinned_len++] = object;
+    return text;
+}
+
+static inline void _minilang_drain(size_t mark) {
+    for (size_t i = mark; i < _minilang_pool_len; i++) {
+        _minilang_object* object = _minilang_pool[i];
+        object->pooled = 0;
+        if (object->refs == 0 && !object->pinned) {
+{count_free}            free(object);
+        }
+    }
+    if (_minilang_pool_len > mark) {
+        _minilang_pool_len = mark;
+    }
+}
+
+"#;
+
+const STACK_STRING: &str = r#"typedef struct {
+{header}    char text[64];
+} _minilang_stack_string;
+
+static const char* _minilang_format_into(_minilang_stack_string* stack, const char* fmt, ...) {
+    va_list args;
+    va_start(args, fmt);
+    int length = vsnprintf(stack->text, sizeof stack->text, fmt, args);
+    va_end(args);
+    if (length < (int)sizeof stack->text) {
+{mark}        return stack->text;
+    }
+    char* buffer = _minilang_string_alloc(length + 1);
+    va_start(args, fmt);
+    vsnprintf(buffer, length + 1, fmt, args);
+    va_end(args);
+    return buffer;
+}
+
+"#;
@@ -0,0 +1,69 @@
+// src/stamp.rs - Build stamps embedded in generated C and executables
+
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+/// Starts every stamp, followed by its JSON on the same line
+pub const MARKER: &str = "minilang-stamp ";
+
+/// What an artifact was built from. `minilang compile` writes one into each
+/// C file as a comment and into each executable as a string, and
+/// `minilang inspect` reads them back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub compiler_version: String,
+    pub optimization: u8,
+    /// The options besides `-O` that change the generated code
+    pub flags: Vec<String>,
+    pub source_sha256: String,
+}
+
+impl Stamp {
+    /// Stamp for compiling `source` with this compiler
+    pub fn new(source: &str, optimization: u8, flags: Vec<String>) -> Self {
+        Self {
+            compiler_version: env!("CARGO_PKG_VERSION").to_string(),
+            optimization,
+            flags,
+            source_sha256: sha256_hex(source.as_bytes()),
+        }
+    }
+
+    /// The stamp as embedded: the marker and the JSON, with no newline
+    pub fn to_line(&self) -> String {
+        format!("{}{}", MARKER, serde_json::to_string(self).unwrap_or_default())
+    }
+
+    /// Every stamp in `bytes`, which may be C source or a binary, in order.
+    /// A program linked from several modules carries one per module.
+    pub fn find_all(bytes: &[u8]) -> Vec<Stamp> {
+        let marker = MARKER.as_bytes();
+        let mut stamps = Vec::new();
+        let mut rest = bytes;
+
+        while let Some(at) = rest.windows(marker.len()).position(|window| window == marker) {
+            let after = &rest[at + marker.len()..];
+            let end = after.iter().position(|&b| b == b'\n' || b == b'\0').unwrap_or(after.len());
+            // The escaped copy in a C string literal is not valid JSON and is skipped
+            if let Ok(stamp) = serde_json::from_slice::<Stamp>(&after[..end]) {
+                stamps.push(stamp);
+            }
+            rest = &after[end..];
+        }
+
+        stamps
+    }
+
+    /// Whether `source` is the text this was built from
+    pub fn matches_source(&self, source: &str) -> bool {
+        self.source_sha256 == sha256_hex(source.as_bytes())
+    }
+}
+
+/// Hex-encoded SHA-256 of some bytes
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,151 @@
+// src/style.rs - Terminal color and glyph policy
+
+#[cfg(feature = "cli")]
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// When to use colors, as chosen with `--color`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and NO_COLOR is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Terminal colors used in CLI output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Green,
+    Yellow,
+    Red,
+    Magenta,
+    Cyan,
+}
+
+impl Color {
+    fn code(&self) -> &'static str {
+        match self {
+            Color::Green => "\x1b[32m",
+            Color::Yellow => "\x1b[33m",
+            Color::Red => "\x1b[31m",
+            Color::Magenta => "\x1b[35m",
+            Color::Cyan => "\x1b[36m",
+        }
+    }
+}
+
+/// The resolved output policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub color: bool,
+    pub unicode: bool,
+}
+
+static STYLE: OnceLock<Style> = OnceLock::new();
+
+impl Style {
+    /// Resolve the policy from a color choice and the environment
+    #[cfg(feature = "cli")]
+    pub fn detect(choice: ColorChoice) -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty());
+
+        Style {
+            color: color_enabled(choice, no_color, std::io::stdout().is_terminal() && !dumb),
+            unicode: locale_supports_unicode(locale.as_deref()),
+        }
+    }
+}
+
+/// Whether to color output. An explicit choice wins over NO_COLOR,
+/// which wins over terminal detection.
+pub fn color_enabled(choice: ColorChoice, no_color: bool, is_terminal: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => !no_color && is_terminal,
+    }
+}
+
+/// Whether the locale can show emoji and box drawing. An unset locale
+/// is assumed to be a modern UTF-8 terminal.
+pub fn locale_supports_unicode(locale: Option<&str>) -> bool {
+    match locale {
+        None => true,
+        Some(locale) => {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        }
+    }
+}
+
+/// Set the policy for the rest of the process and apply it to diagnostics.
+/// Only the first call has an effect.
+#[cfg(feature = "cli")]
+pub fn init(choice: ColorChoice) {
+    let style = *STYLE.get_or_init(|| Style::detect(choice));
+
+    let _ = miette::set_hook(Box::new(move |_| {
+        Box::new(
+            miette::MietteHandlerOpts::new()
+                .color(style.color)
+                .unicode(style.unicode)
+                .build(),
+        )
+    }));
+}
+
+/// The active policy, detected automatically if `init` was never called.
+/// Without the `cli` feature there is no terminal to detect, so text is plain.
+pub fn current() -> Style {
+    #[cfg(feature = "cli")]
+    let detect = || Style::detect(ColorChoice::Auto);
+    #[cfg(not(feature = "cli"))]
+    let detect = || Style { color: false, unicode: true };
+    *STYLE.get_or_init(detect)
+}
+
+/// Wrap text in a color when colors are enabled
+pub fn paint(text: &str, color: Color) -> String {
+    if current().color {
+        format!("{}{}\x1b[0m", color.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// A glyph, or its ASCII stand-in on terminals without Unicode
+pub fn icon(glyph: &'static str) -> &'static str {
+    if current().unicode {
+        glyph
+    } else {
+        ascii_fallback(glyph)
+    }
+}
+
+/// ASCII stand-in for a glyph used in CLI output
+pub fn ascii_fallback(glyph: &'static str) -> &'static str {
+    match glyph {
+        "✅" => "[ok]",
+        "❌" => "[x]",
+        "⚠️" => "[!]",
+        "•" => "-",
+        "═" => "=",
+        "─" => "-",
+        "│" => "|",
+        "…" => "...",
+        "▪" => "#",
+        "📊" | "📋" | "🔥" | "🌳" => "::",
+        "🟢" | "🟡" | "🟠" => "*",
+        "🔴" | "💀" => "!",
+        _ if glyph.is_ascii() => glyph,
+        _ => "*",
+    }
+}
@@ -28,21 +28,49 @@ pub struct FunctionSignature {
     pub name: String,
     pub params: Vec<Type>,
     pub return_type: Option<Type>,
+    pub is_pure: bool,
+    pub is_inline: bool,
+    /// Set by `@deprecated`, with its optional note
+    pub deprecated: bool,
+    pub deprecation_note: Option<String>,
 }
 
-/// Symbol table with scope management
+/// Symbol table with scope management. Symbols outlive their scope, with
+/// every place they were referenced, for cross-reference listings.
 pub struct SymbolTable {
-    scopes: Vec<HashMap<String, Symbol>>,
+    /// Each open scope's names, as indexes into `symbols`
+    scopes: Vec<HashMap<String, usize>>,
+    /// Every symbol ever declared, in declaration order
+    symbols: Vec<Symbol>,
+    /// Where each of `symbols` was referenced, as source offsets
+    references: Vec<Vec<usize>>,
     functions: HashMap<String, FunctionSignature>,
+    function_references: HashMap<String, Vec<usize>>,
     current_scope: usize,
 }
 
+/// A symbol and the source offsets it was referenced at, in source order
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolReferences<'a> {
+    pub symbol: &'a Symbol,
+    pub references: Vec<usize>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SymbolTable {
     /// Create a new symbol table
     pub fn new() -> Self {
         Self {
             scopes: vec![HashMap::new()], // Global scope
+            symbols: Vec::new(),
+            references: Vec::new(),
             functions: HashMap::new(),
+            function_references: HashMap::new(),
             current_scope: 0,
         }
     }
@@ -70,7 +98,9 @@ impl SymbolTable {
             if scope.contains_key(&name) {
                 return Err(format!("Symbol '{}' already defined in this scope", name));
             }
-            scope.insert(name, symbol);
+            scope.insert(name, self.symbols.len());
+            self.symbols.push(symbol);
+            self.references.push(Vec::new());
             Ok(())
         } else {
             Err("No active scope".to_string())
@@ -79,13 +109,52 @@ impl SymbolTable {
     
     /// Look up a symbol (searches all scopes from current to global)
     pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.lookup_index(name).map(|index| &self.symbols[index])
+    }
+    
+    /// Index in `symbols` of the symbol `name` currently refers to
+    fn lookup_index(&self, name: &str) -> Option<usize> {
         // Search from current scope to global
-        for scope in self.scopes.iter().rev() {
-            if let Some(symbol) = scope.get(name) {
-                return Some(symbol);
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+    
+    /// Record that `name`, as currently in scope, is referenced at `offset`
+    pub fn record_reference(&mut self, name: &str, offset: usize) {
+        if let Some(index) = self.lookup_index(name) {
+            let references = &mut self.references[index];
+            // Expressions may be checked more than once
+            if !references.contains(&offset) {
+                references.push(offset);
             }
         }
-        None
+    }
+    
+    /// Record that function `name` is called at `offset`
+    pub fn record_function_reference(&mut self, name: &str, offset: usize) {
+        let references = self.function_references.entry(name.to_string()).or_default();
+        if !references.contains(&offset) {
+            references.push(offset);
+        }
+    }
+    
+    /// Every symbol declared so far, including those of scopes already
+    /// exited, with the offsets it was referenced at
+    pub fn all_symbols(&self) -> Vec<SymbolReferences<'_>> {
+        self.symbols.iter()
+            .zip(&self.references)
+            .map(|(symbol, references)| {
+                let mut references = references.clone();
+                references.sort_unstable();
+                SymbolReferences { symbol, references }
+            })
+            .collect()
+    }
+    
+    /// The offsets function `name` is called at, in source order
+    pub fn function_references(&self, name: &str) -> Vec<usize> {
+        let mut references = self.function_references.get(name).cloned().unwrap_or_default();
+        references.sort_unstable();
+        references
     }
     
     /// Check if symbol exists in current scope only
@@ -163,11 +232,11 @@ impl SymbolTable {
         let len2 = s2_chars.len();
         let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
         
-        for i in 0..=len1 {
-            matrix[i][0] = i;
+        for (i, row) in matrix.iter_mut().enumerate() {
+            row[0] = i;
         }
-        for j in 0..=len2 {
-            matrix[0][j] = j;
+        for (j, cell) in matrix[0].iter_mut().enumerate() {
+            *cell = j;
         }
         
         for i in 1..=len1 {
@@ -194,7 +263,7 @@ impl SymbolTable {
     /// Get all symbols in current scope (for unused variable checking)
     pub fn current_scope_symbols(&self) -> Vec<(String, Symbol)> {
         if let Some(scope) = self.scopes.last() {
-            scope.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+            scope.iter().map(|(k, &v)| (k.clone(), self.symbols[v].clone())).collect()
         } else {
             Vec::new()
         }
@@ -0,0 +1,93 @@
+// src/test_runner.rs - Running `test "name" { ... }` blocks for `minilang test`
+//
+// Each test becomes the entry point of its own copy of the program, which is
+// optimized, compiled to bytecode and run on the VM. A failed assert or any
+// other runtime error fails only that test, and nothing needs a C compiler,
+// so a file's tests run in-process one after another. They run on the
+// virtual clock, so `sleep_ms` returns at once.
+
+use crate::ast::{test_function_name, Program};
+use crate::bytecode::vm::{self, Clock};
+use crate::bytecode::BytecodeCompiler;
+use crate::optimizer::{Optimizer, OverflowMode};
+use std::io;
+
+/// What running one test gave
+#[derive(Debug)]
+pub struct TestOutcome {
+    pub name: String,
+    /// What the test displayed
+    pub output: String,
+    /// The runtime error that stopped the test, if it failed
+    pub failure: Option<vm::Trap>,
+}
+
+impl TestOutcome {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Runs the tests of a program that went through `Program::include_tests`
+/// and type checking
+#[derive(Default)]
+pub struct TestRunner {
+    source: Option<(String, String)>,
+    optimization: u8,
+    overflow: OverflowMode,
+}
+
+impl TestRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report runtime errors at `filename`'s lines
+    pub fn with_source(mut self, filename: &str, source: &str) -> Self {
+        self.source = Some((filename.to_string(), source.to_string()));
+        self
+    }
+
+    /// Optimize each test's program at this level first
+    pub fn with_optimization(mut self, level: u8) -> Self {
+        self.optimization = level;
+        self
+    }
+
+    /// What int arithmetic does on overflow
+    pub fn with_overflow(mut self, overflow: OverflowMode) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Run the test named `name`. Errors are what stopped it from being
+    /// compiled, which a checked program should not have.
+    pub fn run(&self, program: &Program, name: &str) -> Result<TestOutcome, String> {
+        let mut program = program.clone();
+        if !program.set_entry(&test_function_name(name)) {
+            return Err(format!("the program has no test named \"{}\"", name));
+        }
+        if self.optimization > 0 {
+            let mut optimizer = Optimizer::new(self.optimization).with_overflow(self.overflow);
+            optimizer.optimize(&mut program);
+            if let Some(error) = optimizer.errors().first() {
+                return Err(error.to_string());
+            }
+        }
+
+        let mut compiler = BytecodeCompiler::new().with_overflow(self.overflow);
+        if let Some((filename, source)) = &self.source {
+            compiler = compiler.with_source(filename, source);
+        }
+        let module = compiler.compile(&program)?;
+
+        let mut output = Vec::new();
+        // Tests that time things get the same answer on every run
+        let failure = vm::run_with_clock(&module, &mut output, Clock::Virtual, &[], &mut io::sink()).err();
+        Ok(TestOutcome {
+            name: name.to_string(),
+            output: String::from_utf8_lossy(&output).into_owned(),
+            failure,
+        })
+    }
+}
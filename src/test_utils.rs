@@ -1,6 +1,10 @@
 // test_utils.rs - Helper functions for testing
 
-use crate::{Token, Lexer};
+use crate::{Token, Lexer, Parser, TypeChecker, Optimizer, CodeGenerator, OverflowMode};
+use crate::backend::{self, GCC_FLAGS};
+use crate::runtime::RuntimeMode;
+use std::process::Command;
+use std::sync::OnceLock;
 
 /// Quick tokenize helper for tests
 pub fn tokenize(input: &str) -> Result<Vec<Token>, crate::LexerError> {
@@ -13,4 +17,89 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, crate::LexerError> {
 pub fn expect_error(input: &str) -> crate::LexerError {
     let mut lexer = Lexer::new(input);
     lexer.tokenize().expect_err("Expected an error")
-}
\ No newline at end of file
+}
+
+/// What a compiled program did when it ran
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` when it was killed by a signal
+    pub exit_code: Option<i32>,
+}
+
+/// A C compiler that can build a program here: `$CC` if set, else `gcc` or
+/// `cc`. Found once per test binary; `None` when there is none.
+pub fn c_compiler() -> Option<&'static str> {
+    static FOUND: OnceLock<Option<String>> = OnceLock::new();
+
+    FOUND.get_or_init(|| {
+        let candidates = std::env::var("CC").ok().into_iter()
+            .chain(["gcc".to_string(), "cc".to_string()]);
+        candidates.into_iter().find(|cc| compiles_hello(cc))
+    }).as_deref()
+}
+
+fn compiles_hello(cc: &str) -> bool {
+    let Ok(dir) = tempfile::tempdir() else { return false };
+    let c_file = dir.path().join("probe.c");
+    let exe = dir.path().join("probe");
+
+    std::fs::write(&c_file, "int main(void) { return 0; }\n").is_ok()
+        && backend::compile_c(cc, &c_file, &exe, &GCC_FLAGS).is_ok()
+        && Command::new(&exe).status().is_ok_and(|status| status.success())
+}
+
+/// Compile `source` at `-O{level}` the way `minilang compile` does, run it
+/// and capture what it did. Returns `None`, after saying so on stderr, when
+/// no C compiler is available, so callers can skip; panics if any stage of
+/// the build fails.
+pub fn compile_and_run(source: &str, level: u8) -> Option<RunOutput> {
+    compile_and_run_with_runtime(source, level, RuntimeMode::default(), false)
+}
+
+/// `compile_and_run` with `--runtime` and `--profile` set
+pub fn compile_and_run_with_runtime(source: &str, level: u8, runtime: RuntimeMode, profile: bool) -> Option<RunOutput> {
+    let Some(cc) = c_compiler() else {
+        eprintln!("skipped: no working C compiler found (set CC to choose one)");
+        return None;
+    };
+
+    let tokens = Lexer::new(source).tokenize().expect("lexing failed");
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().expect("parsing failed");
+    if let Err(errors) = TypeChecker::new().check_program(&program) {
+        panic!("type checking failed: {:?}", errors);
+    }
+
+    if level > 0 {
+        let mut optimizer = Optimizer::new(level);
+        optimizer.optimize(&mut program);
+        assert!(optimizer.errors().is_empty(), "optimizing failed: {:?}", optimizer.errors());
+    }
+
+    let c_code = CodeGenerator::new()
+        .with_source("test.mini", source)
+        .with_peephole(level >= 1)
+        .with_line_tracking(level == 0)
+        .with_runtime(runtime)
+        .with_profile(profile)
+        .generate(&program)
+        .expect("code generation failed");
+
+    let dir = tempfile::tempdir().expect("no temp dir");
+    let c_file = dir.path().join("test.c");
+    let exe = dir.path().join("test");
+    std::fs::write(&c_file, c_code).expect("could not write C file");
+
+    let cc_flags: Vec<&str> = GCC_FLAGS.iter().copied().chain([OverflowMode::default().cc_flag()]).collect();
+    if let Err(e) = backend::compile_c(cc, &c_file, &exe, &cc_flags) {
+        panic!("{}", e);
+    }
+
+    let output = Command::new(&exe).output().expect("could not run the program");
+    Some(RunOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        exit_code: output.status.code(),
+    })
+}
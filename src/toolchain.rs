@@ -0,0 +1,195 @@
+// src/toolchain.rs - Which compiler this is, and updating it from GitHub releases
+//
+// `minilang self version --check` compares this build with the latest release
+// and `minilang self update` replaces the running executable with the
+// release's build for this target. Nothing is checked or downloaded unless
+// one of those commands is run. Requests go through `curl`, like builds go
+// through gcc, so the compiler carries no HTTP or TLS code of its own.
+
+use crate::stamp::sha256_hex;
+use serde::Deserialize;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The compiler's version, from Cargo.toml
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The commit the compiler was built from (see build.rs)
+pub const GIT_HASH: &str = env!("MINILANG_GIT_HASH");
+
+/// The target triple the compiler was built for, e.g. x86_64-unknown-linux-gnu
+pub const TARGET: &str = env!("MINILANG_TARGET");
+
+/// Where releases are published, as `owner/name` on GitHub
+pub fn repository() -> &'static str {
+    let url = env!("CARGO_PKG_REPOSITORY");
+    url.strip_prefix("https://github.com/").unwrap_or(url).trim_end_matches('/')
+}
+
+/// A published release, as the GitHub API describes it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Release {
+    #[serde(rename = "tag_name")]
+    pub tag: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+/// A file attached to a release
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    #[serde(rename = "browser_download_url")]
+    pub url: String,
+}
+
+impl Release {
+    /// The version the tag names, without a leading `v`
+    pub fn version(&self) -> &str {
+        self.tag.strip_prefix('v').unwrap_or(&self.tag)
+    }
+
+    /// The executable for `target`: an asset named `minilang-<target>`, with
+    /// `.exe` on Windows
+    pub fn binary_for(&self, target: &str) -> Option<&Asset> {
+        let name = format!("minilang-{}", target);
+        self.assets.iter().find(|asset| asset.name == name || asset.name == format!("{}.exe", name))
+    }
+
+    /// The `<asset>.sha256` published next to `asset`, if there is one
+    pub fn checksum_for(&self, asset: &Asset) -> Option<&Asset> {
+        let name = format!("{}.sha256", asset.name);
+        self.assets.iter().find(|other| other.name == name)
+    }
+}
+
+/// Read a release from the GitHub API's JSON
+pub fn parse_release(json: &str) -> Result<Release, String> {
+    serde_json::from_str(json).map_err(|e| format!("unexpected reply from GitHub: {}", e))
+}
+
+/// Whether `candidate` is a later version than `current`. Versions compare
+/// numerically part by part, so 0.10.0 is later than 0.9.1.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    let parts = |version: &str| -> Vec<u64> {
+        let mut parts: Vec<u64> = version.trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map_while(|part| part.parse().ok())
+            .collect();
+        // 0.1 and 0.1.0 are the same version
+        while parts.last() == Some(&0) {
+            parts.pop();
+        }
+        parts
+    };
+    parts(candidate) > parts(current)
+}
+
+/// Fetch the latest release, or the one tagged `tag`
+pub fn fetch_release(tag: Option<&str>) -> Result<Release, String> {
+    let url = match tag {
+        Some(tag) => format!("https://api.github.com/repos/{}/releases/tags/{}", repository(), tag),
+        None => format!("https://api.github.com/repos/{}/releases/latest", repository()),
+    };
+    let body = download(&url)?;
+    parse_release(&String::from_utf8_lossy(&body))
+}
+
+/// Download `asset` and check it against the release's checksum. A release
+/// without one is refused unless `verify` is off, since the binary is run
+/// before it is installed.
+pub fn download_binary(release: &Release, asset: &Asset, verify: bool) -> Result<Vec<u8>, String> {
+    let checksum = release.checksum_for(asset);
+    if checksum.is_none() && verify {
+        return Err(format!(
+            "Release {} publishes no {}.sha256 to check {} against (use --no-verify to install it unchecked)",
+            release.tag, asset.name, asset.name
+        ));
+    }
+    let bytes = download(&asset.url)?;
+    if let Some(checksum) = checksum {
+        let listed = String::from_utf8_lossy(&download(&checksum.url)?).to_string();
+        let expected = listed.split_whitespace().next().unwrap_or_default().to_lowercase();
+        let actual = sha256_hex(&bytes);
+        if expected != actual {
+            return Err(format!("{} does not match {} (expected {}, got {})", asset.name, checksum.name, expected, actual));
+        }
+    }
+    Ok(bytes)
+}
+
+/// Replace the executable at `exe` with `bytes`, after checking that they run
+/// here: `self version` must succeed. The old file is only touched once the
+/// new one is in place next to it, so a failure leaves it as it was.
+pub fn replace_executable(exe: &Path, bytes: &[u8]) -> Result<(), String> {
+    let dir = exe.parent().ok_or_else(|| format!("'{}' has no parent directory", exe.display()))?;
+    let staged = tempfile::Builder::new()
+        .prefix(".minilang-update")
+        .tempfile_in(dir)
+        .map_err(|e| format!("cannot write to '{}': {}", dir.display(), e))?;
+    fs::write(staged.path(), bytes).map_err(|e| format!("cannot write '{}': {}", staged.path().display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(staged.path(), fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("cannot make '{}' executable: {}", staged.path().display(), e))?;
+    }
+
+    // A file still open for writing cannot be run, so close it first
+    let staged = staged.into_temp_path();
+    let works = Command::new(&staged).args(["self", "version"]).output().is_ok_and(|output| output.status.success());
+    if !works {
+        return Err("the downloaded compiler does not run on this machine".to_string());
+    }
+
+    // Windows cannot replace a running executable, but it can rename it
+    let moved_aside = if cfg!(windows) {
+        let old = exe.with_extension("old.exe");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old).map_err(|e| format!("cannot move '{}' aside: {}", exe.display(), e))?;
+        Some(old)
+    } else {
+        None
+    };
+    staged.persist(exe).map_err(|e| {
+        if let Some(old) = moved_aside {
+            let _ = fs::rename(old, exe);
+        }
+        format!("cannot replace '{}': {}", exe.display(), e.error)
+    })
+}
+
+/// The body of a GET request. API requests send GITHUB_TOKEN along when it is
+/// set: many machines behind one address soon run into GitHub's limit for
+/// anonymous requests. The token goes to curl on stdin, as arguments can be
+/// read by every user of the machine.
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    let mut headers = String::new();
+    if url.starts_with("https://api.github.com/") {
+        headers.push_str("Accept: application/vnd.github+json\n");
+        if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+            headers.push_str(&format!("Authorization: Bearer {}\n", token));
+        }
+    }
+    let mut child = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--user-agent", &format!("minilang/{}", VERSION)])
+        .args(["--header", "@-"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run curl: {}", e))?;
+    // Dropping stdin closes it, which ends curl's list of headers
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(headers.as_bytes()).map_err(|e| format!("cannot pass headers to curl: {}", e))?;
+    }
+    let output = child.wait_with_output().map_err(|e| format!("cannot run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("downloading {} failed: {}", url, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(output.stdout)
+}
@@ -3,8 +3,13 @@
 use crate::ast::*;
 use crate::symbol_table::{SymbolTable, Symbol, SymbolType as SymType, FunctionSignature};
 use crate::errors::SemanticError;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::errors::CompilerWarning;
+use crate::effects;
+use crate::hot_path;
+use crate::control_flow;
+use crate::lexer::Comment;
+use crate::plugin::{self, CompilerPlugin};
 
 /// Type checker with semantic analysis
 pub struct TypeChecker {
@@ -14,8 +19,35 @@ pub struct TypeChecker {
     variable_usage: Vec<HashMap<String, bool>>,
     current_function: Option<String>,
     current_return_type: Option<Type>,
+    current_function_pure: bool,
     loop_depth: usize,
+    in_defer: bool,
+    /// Scope level of the innermost spawn body being checked: variables
+    /// declared below it belong to the function the thread was spawned from
+    spawn_scope: Option<usize>,
+    /// Scope level of the innermost parallel for body being checked, whose
+    /// iterations share the variables declared below it
+    parallel_scope: Option<usize>,
+    /// The loop depth inside that body, which 'break' cannot leave
+    parallel_loop_depth: usize,
     has_return: bool,
+    /// Private functions of other modules, which calls may not reach
+    private_externals: HashSet<String>,
+    /// The function `--entry` runs instead of main
+    entry: Option<String>,
+    /// Functions whose calls display or write the caller's arrays
+    effectful_functions: HashSet<String>,
+    /// Where the source's comments are, so a body holding only a comment
+    /// does not count as empty
+    comments: Vec<std::ops::Range<usize>>,
+    /// Plugins whose lint rules run once the program type checks
+    plugins: Vec<&'static dyn CompilerPlugin>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TypeChecker {
@@ -27,9 +59,58 @@ impl TypeChecker {
             variable_usage: vec![HashMap::new()],
             current_function: None,
             current_return_type: None,
+            current_function_pure: false,
             loop_depth: 0,
+            in_defer: false,
+            spawn_scope: None,
+            parallel_scope: None,
+            parallel_loop_depth: 0,
             has_return: false,
+            private_externals: HashSet::new(),
+            entry: None,
+            effectful_functions: HashSet::new(),
+            comments: Vec::new(),
+            plugins: plugin::installed(),
+        }
+    }
+    
+    /// Make the public functions defined in other modules callable from this
+    /// one. Only their signatures are used; each module's bodies are checked on
+    /// their own, and a function defined here with the same name as a public
+    /// one is a duplicate definition. Private ones are only remembered, to
+    /// explain why calling them fails.
+    pub fn with_externals(mut self, functions: &[Function]) -> Self {
+        for function in functions {
+            if function.visibility == Visibility::Private {
+                self.private_externals.insert(function.name.clone());
+            } else {
+                let _ = self.symbol_table.register_function(Self::signature(function));
+                if !function.is_pure {
+                    self.effectful_functions.insert(function.name.clone());
+                }
+            }
         }
+        self
+    }
+    
+    /// Leave bodies that hold only comments, such as a `# TODO`, out of the
+    /// empty block and empty function warnings
+    pub fn with_comments(mut self, comments: &[Comment]) -> Self {
+        self.comments = comments.iter().map(|comment| comment.span.clone()).collect();
+        self
+    }
+    
+    /// Check that `name` can be the program's entry point, run by a main that
+    /// `Program::set_entry` synthesizes after checking
+    pub fn with_entry(mut self, name: &str) -> Self {
+        self.entry = Some(name.to_string());
+        self
+    }
+    
+    /// Run the lint rules of these plugins instead of the installed ones
+    pub fn with_plugins(mut self, plugins: &[&'static dyn CompilerPlugin]) -> Self {
+        self.plugins = plugins.to_vec();
+        self
     }
     
     /// Check entire program
@@ -39,11 +120,38 @@ impl TypeChecker {
             let _ = self.register_function(function);
         }
         
+        if let Some(entry) = self.entry.clone() {
+            self.check_entry(program, &entry);
+        }
+        self.effectful_functions.extend(effects::effectful_functions(program, &[]));
+        
         // Second pass: Check function bodies
         for function in &program.functions {
             let _ = self.check_function(function);
         }
         
+        if self.errors.is_empty() {
+            for spot in hot_path::find(program, &self.effectful_functions) {
+                self.warnings.push(CompilerWarning::LoopInvariant {
+                    expression: spot.expression,
+                    reason: spot.reason,
+                    span: (spot.span.start..spot.span.end).into(),
+                });
+            }
+            for plugin in &self.plugins {
+                for lint in plugin.lint(program) {
+                    self.warnings.push(CompilerWarning::Plugin {
+                        plugin: plugin.name().to_string(),
+                        code: lint.code,
+                        message: lint.message,
+                        label: lint.label,
+                        help: lint.help,
+                        span: (lint.span.start..lint.span.end).into(),
+                    });
+                }
+            }
+        }
+        
         if self.errors.is_empty() {
             Ok(())
         } else {
@@ -53,17 +161,9 @@ impl TypeChecker {
     
     /// Register a function in the symbol table
     fn register_function(&mut self, function: &Function) -> Result<(), ()> {
-        let params = function.params.iter()
-            .map(|p| p.typ.clone())
-            .collect();
-        
-        let sig = FunctionSignature {
-            name: function.name.clone(),
-            params,
-            return_type: function.return_type.clone(),
-        };
+        self.check_identifier(&function.name, &function.span);
         
-        if let Err(_) = self.symbol_table.register_function(sig) {
+        if self.symbol_table.register_function(Self::signature(function)).is_err() {
             // Function already defined
             self.errors.push(SemanticError::DuplicateDefinition {
                 name: function.name.clone(),
@@ -76,11 +176,63 @@ impl TypeChecker {
         Ok(())
     }
     
+    /// The entry point is called with no arguments, and like main's, its
+    /// result becomes the exit status
+    fn check_entry(&mut self, program: &Program, name: &str) {
+        let Some(function) = program.functions.iter().find(|f| f.name == name) else {
+            let similar = self.symbol_table.find_similar_functions(name, 3);
+            let suggestion = if similar.is_empty() {
+                format!("The program defines no function named '{}' to use as the entry point.", name)
+            } else {
+                format!("Did you mean: {}?", similar.join(", "))
+            };
+            self.errors.push(SemanticError::UndefinedFunction {
+                name: name.to_string(),
+                span: (0..0).into(),
+                suggestion,
+                context: Some("--entry".to_string()),
+            });
+            return;
+        };
+        
+        let problem = if !function.params.is_empty() {
+            Some(format!("takes {} parameter(s)", function.params.len()))
+        } else {
+            function.return_type.as_ref()
+                .filter(|typ| **typ != Type::Int)
+                .map(|typ| format!("returns {:?}", typ))
+        };
+        if let Some(problem) = problem {
+            self.errors.push(SemanticError::InvalidEntry {
+                name: name.to_string(),
+                problem,
+                span: (function.span.start..function.body.span.start).into(),
+            });
+        }
+    }
+    
+    fn signature(function: &Function) -> FunctionSignature {
+        FunctionSignature {
+            name: function.name.clone(),
+            params: function.params.iter().map(|p| p.typ.clone()).collect(),
+            return_type: function.return_type.clone(),
+            is_pure: function.is_pure,
+            is_inline: function.has_attribute("inline"),
+            deprecated: function.has_attribute("deprecated"),
+            deprecation_note: function.attribute("deprecated").and_then(|attr| attr.argument.clone()),
+        }
+    }
+    
     /// Check a function
     fn check_function(&mut self, function: &Function) -> Result<(), ()> {
         // Set current function context
         self.current_function = Some(function.name.clone());
         self.current_return_type = function.return_type.clone();
+        self.current_function_pure = function.is_pure;
+        self.check_attributes(function);
+        if let Some(return_type) = &function.return_type {
+            self.check_tuple_elements(return_type, &function.span);
+        }
         
         // Enter function scope
         self.symbol_table.enter_scope();
@@ -88,6 +240,9 @@ impl TypeChecker {
         
         // Add parameters to scope
         for param in &function.params {
+            self.check_identifier(&param.name, &param.span);
+            self.check_handle_storage(&param.typ, &param.span);
+            self.check_tuple_elements(&param.typ, &param.span);
             let symbol = Symbol {
                 name: param.name.clone(),
                 symbol_type: SymType::Parameter,
@@ -96,7 +251,7 @@ impl TypeChecker {
                 defined_at: param.span.start,
             };
             
-            if let Err(_) = self.symbol_table.insert(symbol) {
+            if self.symbol_table.insert(symbol).is_err() {
                 self.errors.push(SemanticError::DuplicateDefinition {
                     name: param.name.clone(),
                     span: (param.span.start..param.span.end).into(),
@@ -108,12 +263,35 @@ impl TypeChecker {
         // Check function body
         let _ = self.check_block(&function.body);
         
+        // A function that returns a value already fails with MissingReturn
+        if function.name != "main" && function.return_type.is_none() && self.is_empty(&function.body) {
+            self.warnings.push(CompilerWarning::EmptyFunction {
+                name: function.name.clone(),
+                span: (function.body.span.start..function.body.span.end).into(),
+            });
+        }
+        
+        // main's return value becomes the process exit status
+        if function.name == "main" {
+            if function.visibility == Visibility::Private {
+                self.errors.push(SemanticError::PrivateMain {
+                    span: (function.span.start..function.body.span.start).into(),
+                });
+            }
+            if let Some(return_type) = function.return_type.as_ref().filter(|typ| **typ != Type::Int) {
+                self.errors.push(SemanticError::InvalidMainReturnType {
+                    found: format!("{:?}", return_type),
+                    span: (function.span.start..function.body.span.start).into(),
+                });
+            }
+        }
+        
         // Check if non-void function has return on all paths
-        if function.return_type.is_some() {
-            if !self.block_returns(&function.body) {
+        if let Some(return_type) = &function.return_type {
+            if control_flow::block_completes(&function.body) {
                 self.errors.push(SemanticError::MissingReturn {
                     name: function.name.clone(),
-                    return_type: format!("{:?}", function.return_type.as_ref().unwrap()),
+                    return_type: format!("{:?}", return_type),
                     span: (function.span.start..function.span.end).into(),
                 });
             }
@@ -126,38 +304,54 @@ impl TypeChecker {
         // Clear function context
         self.current_function = None;
         self.current_return_type = None;
+        self.current_function_pure = false;
         
         Ok(())
     }
     
     /// Check a block
     fn check_block(&mut self, block: &Block) -> Result<(), ()> {
-        let mut seen_return = false;
+        let mut unreachable_after: Option<&Statement> = None;
         
-        for (_, statement) in block.statements.iter().enumerate() {
-            if seen_return {
-                // Code after return is unreachable
+        for statement in &block.statements {
+            if let Some(previous) = unreachable_after {
                 self.warnings.push(CompilerWarning::UnreachableCode {
                     span: self.get_statement_span(statement).into(),
-                    reason: "Code after 'send' statement will never execute".to_string(),
+                    reason: Self::unreachable_reason(previous).to_string(),
                 });
             }
             
             let _ = self.check_statement(statement);
             
             if matches!(statement, Statement::Return(_)) {
-                seen_return = true;
                 self.has_return = true;
             }
+            if unreachable_after.is_none() && !control_flow::completes(statement) {
+                unreachable_after = Some(statement);
+            }
         }
         Ok(())
     }
     
+    /// Why nothing after `stmt` can run
+    fn unreachable_reason(stmt: &Statement) -> &'static str {
+        match stmt {
+            Statement::Return(_) => "Code after 'send' statement will never execute",
+            Statement::Break(_) => "Code after 'break' will never execute",
+            Statement::Continue(_) => "Code after 'continue' will never execute",
+            Statement::While(_) | Statement::DoWhile(_) | Statement::For(_) => {
+                "The loop before this never ends: its condition is always true and it has no 'break'"
+            }
+            _ => "Every path through the statement before this sends, breaks or continues",
+        }
+    }
+    
     /// Check a statement
     fn check_statement(&mut self, statement: &Statement) -> Result<(), ()> {
         match statement {
             Statement::Const(const_stmt) => self.check_const_statement(const_stmt),
             Statement::Let(let_stmt) => self.check_let_statement(let_stmt),
+            Statement::LetTuple(let_tuple) => self.check_let_tuple_statement(let_tuple),
             Statement::Display(display_stmt) => self.check_display_statement(display_stmt),
             Statement::If(if_stmt) => self.check_if_statement(if_stmt),
             Statement::While(while_stmt) => self.check_while_statement(while_stmt),
@@ -167,6 +361,9 @@ impl TypeChecker {
             Statement::Expression(expr_stmt) => {
                 if let Expression::Call(call_expr) = &expr_stmt.expression{
                     if let Some(func_sig) = self.symbol_table.lookup_function(&call_expr.function).cloned() {
+                        self.symbol_table.record_function_reference(&call_expr.function, call_expr.span.start);
+                        self.check_call_purity(&func_sig, &call_expr.span);
+                        self.check_call_attributes(&func_sig, &call_expr.span);
                         if call_expr.args.len() != func_sig.params.len(){
                             self.errors.push(SemanticError::ArgumentCountMismatch {
                                 name: call_expr.function.clone(),
@@ -177,6 +374,7 @@ impl TypeChecker {
                             return Err(());
                         }
                         for (arg, expected) in call_expr.args.iter().zip(&func_sig.params) {
+                            self.check_parallel_array_argument(arg, &func_sig, &call_expr.span);
                             if let Ok(arg_type) = self.infer_expression_type(arg) {
                                 if !self.types_compatible(expected, &arg_type) {
                                     self.errors.push(SemanticError::TypeMismatch {
@@ -191,6 +389,26 @@ impl TypeChecker {
                         // Void function calls are OK in statement context
                         return Ok(());
                     }
+                    if call_expr.function == "assert" {
+                        self.check_assert_call(call_expr);
+                        return Ok(());
+                    }
+                    if call_expr.function == "chan_send" {
+                        let _ = self.check_sharing_call(call_expr);
+                        return Ok(());
+                    }
+                    if call_expr.function == "sleep_ms" {
+                        self.check_clock_call(call_expr);
+                        return Ok(());
+                    }
+                    if CANVAS_BUILTINS[1..].contains(&call_expr.function.as_str()) {
+                        self.check_canvas_call(call_expr);
+                        return Ok(());
+                    }
+                    if JSON_BUILTINS.contains(&call_expr.function.as_str()) {
+                        let _ = self.check_json_call(call_expr);
+                        return Ok(());
+                    }
                 }
                 let _ = self.check_expression(&expr_stmt.expression);
                 Ok(())
@@ -205,31 +423,95 @@ impl TypeChecker {
                 Ok(())
             }
             Statement::Break(break_stmt) => {
-                if self.loop_depth == 0 {
-                    self.errors.push(SemanticError::BreakOutsideLoop {
-                        statement: "break".to_string(),
+                if self.parallel_scope.is_some() && self.loop_depth == self.parallel_loop_depth {
+                    self.errors.push(SemanticError::ParallelViolation {
+                        what: "'break'".to_string(),
+                        reason: "The iterations run at the same time on several threads, so there is no later iteration to skip. Use 'continue' to end this one early".to_string(),
                         span: (break_stmt.span.start..break_stmt.span.end).into(),
                     });
                     return Err(());
                 }
+                if self.loop_depth == 0 {
+                    let span = (break_stmt.span.start..break_stmt.span.end).into();
+                    self.errors.push(if self.in_defer {
+                        SemanticError::JumpOutOfDefer { statement: "break".to_string(), span }
+                    } else if self.spawn_scope.is_some() {
+                        SemanticError::SpawnViolation {
+                            what: "'break'".to_string(),
+                            reason: "The spawn block runs on its own thread, so it cannot 'break' a loop of the code that spawned it".to_string(),
+                            span,
+                        }
+                    } else {
+                        SemanticError::BreakOutsideLoop { statement: "break".to_string(), span }
+                    });
+                    return Err(());
+                }
                 Ok(())
             }
 
             Statement::Continue(continue_stmt) => {
                 if self.loop_depth == 0 {
-                    self.errors.push(SemanticError::BreakOutsideLoop {
-                        statement: "continue".to_string(),
-                        span: (continue_stmt.span.start..continue_stmt.span.end).into(),
+                    let span = (continue_stmt.span.start..continue_stmt.span.end).into();
+                    self.errors.push(if self.in_defer {
+                        SemanticError::JumpOutOfDefer { statement: "continue".to_string(), span }
+                    } else if self.spawn_scope.is_some() {
+                        SemanticError::SpawnViolation {
+                            what: "'continue'".to_string(),
+                            reason: "The spawn block runs on its own thread, so it cannot 'continue' a loop of the code that spawned it".to_string(),
+                            span,
+                        }
+                    } else {
+                        SemanticError::BreakOutsideLoop { statement: "continue".to_string(), span }
                     });
                     return Err(());
                 }
                 Ok(())
             }
+
+            Statement::Defer(defer_stmt) => {
+                // Loops outside the defer body can't be targeted from inside it
+                let outer_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+                let outer_in_defer = std::mem::replace(&mut self.in_defer, true);
+                self.symbol_table.enter_scope();
+                self.variable_usage.push(HashMap::new());
+                let _ = self.check_block(&defer_stmt.body);
+                self.check_unused_variables();
+                self.variable_usage.pop();
+                self.symbol_table.exit_scope();
+                self.in_defer = outer_in_defer;
+                self.loop_depth = outer_loop_depth;
+                Ok(())
+            }
+
+            Statement::Spawn(spawn_stmt) => {
+                if self.current_function_pure {
+                    self.push_purity_violation("'spawn' starts a thread".to_string(), &spawn_stmt.span);
+                }
+                // The thread has its own control flow: nothing outside the
+                // body can be jumped to from inside it
+                let outer_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+                let outer_in_defer = std::mem::replace(&mut self.in_defer, false);
+                let outer_parallel = self.parallel_scope.take();
+                self.symbol_table.enter_scope();
+                let outer_spawn = self.spawn_scope.replace(self.symbol_table.current_scope_level());
+                self.variable_usage.push(HashMap::new());
+                let _ = self.check_block(&spawn_stmt.body);
+                self.check_unused_variables();
+                self.variable_usage.pop();
+                self.spawn_scope = outer_spawn;
+                self.parallel_scope = outer_parallel;
+                self.symbol_table.exit_scope();
+                self.in_defer = outer_in_defer;
+                self.loop_depth = outer_loop_depth;
+                Ok(())
+            }
         }
     }
 
     /// Check const statement
     fn check_const_statement(&mut self, stmt: &ConstStmt) -> Result<(), ()> {
+        self.check_identifier(&stmt.name, &stmt.span);
+        
         // Check if const already exists in current scope
         if self.symbol_table.exists_in_current_scope(&stmt.name) {
             let original_span = self.symbol_table.lookup(&stmt.name)
@@ -244,8 +526,21 @@ impl TypeChecker {
             return Err(());
         }
         
+        self.check_tuple_elements(&stmt.typ, &stmt.span);
+        
         // Validate the value expression type
         if let Ok(value_type) = self.infer_expression_type(&stmt.value) {
+            if let (Type::Array(expected_elem, declared), Type::Array(found_elem, found)) = (&stmt.typ, &value_type) {
+                if expected_elem == found_elem && declared != found {
+                    self.errors.push(SemanticError::ArrayLengthMismatch {
+                        name: stmt.name.clone(),
+                        declared: *declared,
+                        found: *found,
+                        span: (stmt.span.start..stmt.span.end).into(),
+                    });
+                    return Err(());
+                }
+            }
             if !self.types_compatible(&stmt.typ, &value_type) {
                 self.errors.push(SemanticError::TypeMismatch {
                     expected: format!("{:?}", stmt.typ),
@@ -265,10 +560,12 @@ impl TypeChecker {
             defined_at: stmt.span.start,
         };
         
-        // Don't track constants as potentially unused
-        // They're meant to be compile-time values
+        // A constant nothing reads is reported like an unused variable
+        if let Some(current_scope) = self.variable_usage.last_mut() {
+            current_scope.insert(stmt.name.clone(), false);
+        }
         
-        if let Err(_) = self.symbol_table.insert(symbol) {
+        if self.symbol_table.insert(symbol).is_err() {
             self.errors.push(SemanticError::DuplicateDefinition {
                 name: stmt.name.clone(),
                 span: (stmt.span.start..stmt.span.end).into(),
@@ -282,6 +579,8 @@ impl TypeChecker {
     
     /// Check let statement
     fn check_let_statement(&mut self, stmt: &LetStmt) -> Result<(), ()> {
+        self.check_identifier(&stmt.name, &stmt.span);
+        
         // Check if variable already exists in current scope
         if self.symbol_table.exists_in_current_scope(&stmt.name) {
             let original_span = self.symbol_table.lookup(&stmt.name)
@@ -296,17 +595,60 @@ impl TypeChecker {
             return Err(());
         }
         
+        // Without an annotation the initializer decides the type
+        let declared_type = match &stmt.typ {
+            Some(typ) => typ.clone(),
+            None => self.infer_let_type(stmt)?,
+        };
+        self.check_handle_storage(&declared_type, &stmt.span);
+        if stmt.typ.is_some() {
+            // An inferred type was already checked where its value was made
+            self.check_tuple_elements(&declared_type, &stmt.span);
+        }
+        if stmt.value.is_none() && declared_type.contains_handle() {
+            // There is nothing to start from, and a missing one would crash the first use
+            let expected = if declared_type.contains(&Type::Channel) {
+                "a channel from channel(capacity)"
+            } else if declared_type.contains(&Type::Atomic) {
+                "an atomic from atomic(value)"
+            } else if declared_type.contains(&Type::Canvas) {
+                "a canvas from canvas(width, height)"
+            } else {
+                "a json value from json_parse(text) or a json literal"
+            };
+            self.errors.push(SemanticError::TypeMismatch {
+                expected: expected.to_string(),
+                found: "no initializer".to_string(),
+                span: (stmt.span.start..stmt.span.end).into(),
+            });
+        }
+        
         // If there's an initializer, check its type
-        if let Some(ref value) = stmt.value {
+        if let (Some(value), Some(declared)) = (&stmt.value, &stmt.typ) {
             if let Ok(value_type) = self.infer_expression_type(value) {
-                // Check type compatibility
-                if !self.types_compatible(&stmt.typ, &value_type) {
-                    self.errors.push(SemanticError::TypeMismatch {
-                        expected: format!("{:?}", stmt.typ),
-                        found: format!("{:?}", value_type),
-                        span: (stmt.span.start..stmt.span.end).into(),
-                    });
-                    return Err(());
+                match (declared, &value_type) {
+                    // Right element type, wrong length: still declare the variable
+                    // so later uses of it don't report as undefined
+                    (Type::Array(expected_elem, declared), Type::Array(found_elem, found))
+                        if expected_elem == found_elem && declared != found =>
+                    {
+                        self.errors.push(SemanticError::ArrayLengthMismatch {
+                            name: stmt.name.clone(),
+                            declared: *declared,
+                            found: *found,
+                            span: (stmt.span.start..stmt.span.end).into(),
+                        });
+                    }
+                    // Check type compatibility
+                    _ if !self.types_compatible(declared, &value_type) => {
+                        self.errors.push(SemanticError::TypeMismatch {
+                            expected: format!("{:?}", declared),
+                            found: format!("{:?}", value_type),
+                            span: (stmt.span.start..stmt.span.end).into(),
+                        });
+                        return Err(());
+                    }
+                    _ => {}
                 }
             }
         }
@@ -315,7 +657,7 @@ impl TypeChecker {
         let symbol = Symbol {
             name: stmt.name.clone(),
             symbol_type: SymType::Variable,
-            data_type: stmt.typ.clone(),
+            data_type: declared_type,
             scope_level: self.symbol_table.current_scope_level(),
             defined_at: stmt.span.start,
         };
@@ -323,7 +665,7 @@ impl TypeChecker {
             current_scope.insert(stmt.name.clone(), false);
         }
         
-        if let Err(_) = self.symbol_table.insert(symbol) {
+        if self.symbol_table.insert(symbol).is_err() {
             self.errors.push(SemanticError::DuplicateDefinition {
                 name: stmt.name.clone(),
                 span: (stmt.span.start..stmt.span.end).into(),
@@ -335,26 +677,184 @@ impl TypeChecker {
         Ok(())
     }
     
+    /// The type of `let name = value;`, from its initializer
+    fn infer_let_type(&mut self, stmt: &LetStmt) -> Result<Type, ()> {
+        let Some(value) = &stmt.value else {
+            // The parser requires an annotation when there is no initializer
+            return Err(());
+        };
+        let reason = match self.infer_expression_type(value)? {
+            Type::Array(_, 0) => "an empty array literal has no element type",
+            typ => return Ok(typ),
+        };
+        self.errors.push(SemanticError::CannotInferType {
+            name: stmt.name.clone(),
+            reason: reason.to_string(),
+            span: (stmt.span.start..stmt.span.end).into(),
+        });
+        Err(())
+    }
+    
+    /// Check tuple destructuring
+    fn check_let_tuple_statement(&mut self, stmt: &LetTupleStmt) -> Result<(), ()> {
+        let value_type = self.infer_expression_type(&stmt.value)?;
+        let element_types = match value_type {
+            Type::Tuple(types) if types.len() == stmt.names.len() => types,
+            other => {
+                self.errors.push(SemanticError::TypeMismatch {
+                    expected: format!("tuple of {} elements", stmt.names.len()),
+                    found: format!("{:?}", other),
+                    span: (stmt.span.start..stmt.span.end).into(),
+                });
+                return Err(());
+            }
+        };
+        
+        for (name, typ) in stmt.names.iter().zip(element_types) {
+            // '_' discards the element
+            if name == "_" {
+                continue;
+            }
+            self.check_identifier(name, &stmt.span);
+            
+            if self.symbol_table.exists_in_current_scope(name) {
+                let original_span = self.symbol_table.lookup(name)
+                    .map(|s| s.defined_at..s.defined_at + name.len())
+                    .unwrap_or(0..1);
+                
+                self.errors.push(SemanticError::DuplicateDefinition {
+                    name: name.clone(),
+                    span: (stmt.span.start..stmt.span.end).into(),
+                    original: original_span.into(),
+                });
+                continue;
+            }
+            
+            let symbol = Symbol {
+                name: name.clone(),
+                symbol_type: SymType::Variable,
+                data_type: typ,
+                scope_level: self.symbol_table.current_scope_level(),
+                defined_at: stmt.span.start,
+            };
+            if let Some(current_scope) = self.variable_usage.last_mut() {
+                current_scope.insert(name.clone(), false);
+            }
+            let _ = self.symbol_table.insert(symbol);
+        }
+        
+        Ok(())
+    }
+    
     /// Check display statement
     fn check_display_statement(&mut self, stmt: &DisplayStmt) -> Result<(), ()> {
+        if self.current_function_pure {
+            self.push_purity_violation(
+                "'display' writes to standard output".to_string(),
+                &stmt.span,
+            );
+        }
         for expr in &stmt.expressions {
-            let _ = self.check_expression(expr);
+            if let Ok(typ @ (Type::Channel | Type::Atomic | Type::Canvas | Type::Json)) = self.infer_expression_type(expr) {
+                self.push_not_printable(typ, expr.span());
+            }
         }
         Ok(())
     }
     
-    /// Check if statement
-    fn check_if_statement(&mut self, stmt: &IfStmt) -> Result<(), ()> {
-        // Check condition is boolean
-        if let Ok(cond_type) = self.infer_expression_type(&stmt.condition) {
-            if cond_type != Type::Bool {
+    /// Check that a `keyword` condition is a bool. Ints get a targeted error
+    /// carrying the comparison C would have made implicitly.
+    fn check_condition(&mut self, condition: &Expression, keyword: &str) {
+        if let Some((effect, span)) = self.side_effect_in(condition) {
+            self.warnings.push(CompilerWarning::SideEffectInCondition {
+                keyword: keyword.to_string(),
+                effect,
+                span: (span.start..span.end).into(),
+            });
+        }
+        
+        let Ok(cond_type) = self.infer_expression_type(condition) else { return };
+        let span = condition.span();
+        match cond_type {
+            Type::Bool => {}
+            Type::Int => {
+                let replacement = match condition {
+                    Expression::Literal(LiteralExpr { value: Literal::Integer(n), .. }) => (*n != 0).to_string(),
+                    _ => Expression::Binary(BinaryExpr {
+                        left: Box::new(condition.clone()),
+                        op: BinaryOp::NotEqual,
+                        right: Box::new(Expression::Literal(LiteralExpr {
+                            value: Literal::Integer(0),
+                            span: Span::new(0, 0),
+                            id: NodeId::default(),
+                        })),
+                        span: Span::new(0, 0),
+                        id: NodeId::default(),
+                        optimization_hint: None,
+                        parenthesized: false,
+                    }).to_string(),
+                };
+                self.errors.push(SemanticError::NonBoolCondition {
+                    keyword: keyword.to_string(),
+                    found: "Int".to_string(),
+                    replacement,
+                    span: (span.start..span.end).into(),
+                });
+            }
+            _ => {
                 self.errors.push(SemanticError::TypeMismatch {
                     expected: "Bool".to_string(),
                     found: format!("{:?}", cond_type),
-                    span: (stmt.span.start..stmt.span.end).into(),
+                    span: (span.start..span.end).into(),
                 });
             }
         }
+    }
+    
+    /// The first assignment or call with side effects in `expr`, described
+    /// for a warning
+    fn side_effect_in(&self, expr: &Expression) -> Option<(String, Span)> {
+        match expr {
+            Expression::Assign(assign) => Some((format!("assigns to '{}'", assign.target), assign.span.clone())),
+            Expression::Call(call) => {
+                if self.effectful_functions.contains(&call.function) {
+                    return Some((format!("calls '{}'", call.function), call.span.clone()));
+                }
+                call.args.iter().find_map(|arg| self.side_effect_in(arg))
+            }
+            Expression::Binary(binary) => self.side_effect_in(&binary.left).or_else(|| self.side_effect_in(&binary.right)),
+            Expression::Unary(unary) => self.side_effect_in(&unary.operand),
+            Expression::Index(index) => self.side_effect_in(&index.array).or_else(|| self.side_effect_in(&index.index)),
+            Expression::Literal(lit) => match &lit.value {
+                Literal::Array(elements) | Literal::Tuple(elements) => {
+                    elements.iter().find_map(|element| self.side_effect_in(element))
+                }
+                Literal::InterpolatedString(parts) => parts.iter().find_map(|part| match part {
+                    StringPart::Expression(expr) => self.side_effect_in(expr),
+                    StringPart::Text(_) => None,
+                }),
+                _ => None,
+            },
+            Expression::Identifier(_) => None,
+        }
+    }
+    
+    /// Check if statement
+    fn check_if_statement(&mut self, stmt: &IfStmt) -> Result<(), ()> {
+        self.check_condition(&stmt.condition, "if");
+        
+        if self.is_empty(&stmt.then_block) {
+            let else_has_code = stmt.else_block.as_ref().is_some_and(|block| !self.is_empty(block));
+            let suggestion = if else_has_code {
+                "Negate the condition and move the 'else' body here"
+            } else {
+                "Remove the 'if'"
+            };
+            self.warn_empty_block("if", suggestion, &stmt.then_block);
+        }
+        if let Some(else_block) = stmt.else_block.as_ref().filter(|block| self.is_empty(block)) {
+            self.warn_empty_block("else", "Remove the empty 'else'", else_block);
+        }
         
         // Check then block
         self.symbol_table.enter_scope();
@@ -379,15 +879,9 @@ impl TypeChecker {
     
     /// Check while statement
     fn check_while_statement(&mut self, stmt: &WhileStmt) -> Result<(), ()> {
-        // Check condition is boolean
-        if let Ok(cond_type) = self.infer_expression_type(&stmt.condition) {
-            if cond_type != Type::Bool {
-                self.errors.push(SemanticError::TypeMismatch {
-                    expected: "Bool".to_string(),
-                    found: format!("{:?}", cond_type),
-                    span: (stmt.span.start..stmt.span.end).into(),
-                });
-            }
+        self.check_condition(&stmt.condition, "while");
+        if self.is_empty(&stmt.body) {
+            self.warn_empty_block("while", "Remove the loop", &stmt.body);
         }
         
         // Check body
@@ -405,6 +899,10 @@ impl TypeChecker {
     
     /// Check do-while statement
     fn check_do_while_statement(&mut self, stmt: &DoWhileStmt) -> Result<(), ()> {
+        if self.is_empty(&stmt.body) {
+            self.warn_empty_block("do-while", "Remove the loop", &stmt.body);
+        }
+        
         // Check body
         self.symbol_table.enter_scope();
         self.variable_usage.push(HashMap::new());
@@ -415,16 +913,7 @@ impl TypeChecker {
         self.variable_usage.pop();
         self.symbol_table.exit_scope();
         
-        // Check condition is boolean
-        if let Ok(cond_type) = self.infer_expression_type(&stmt.condition) {
-            if cond_type != Type::Bool {
-                self.errors.push(SemanticError::TypeMismatch {
-                    expected: "Bool".to_string(),
-                    found: format!("{:?}", cond_type),
-                    span: (stmt.span.start..stmt.span.end).into(),
-                });
-            }
-        }
+        self.check_condition(&stmt.condition, "while");
         
         Ok(())
     }
@@ -441,15 +930,7 @@ impl TypeChecker {
         
         // Check condition
         if let Some(condition) = &stmt.condition {
-            if let Ok(cond_type) = self.infer_expression_type(condition) {
-                if cond_type != Type::Bool {
-                    self.errors.push(SemanticError::TypeMismatch {
-                        expected: "Bool".to_string(),
-                        found: format!("{:?}", cond_type),
-                        span: (stmt.span.start..stmt.span.end).into(),
-                    });
-                }
-            }
+            self.check_condition(condition, "for");
         }
         
         // Check update
@@ -458,8 +939,15 @@ impl TypeChecker {
         }
         
         // Check body
+        if self.is_empty(&stmt.body) {
+            self.warn_empty_block("for", "Remove the loop", &stmt.body);
+        }
         self.loop_depth += 1;
-        let _ = self.check_block(&stmt.body);
+        if stmt.parallel {
+            self.check_parallel_body(stmt);
+        } else {
+            let _ = self.check_block(&stmt.body);
+        }
         self.loop_depth -= 1;
         self.check_unused_variables();
         self.variable_usage.pop();
@@ -467,8 +955,50 @@ impl TypeChecker {
         Ok(())
     }
     
+    /// Whether `block` has neither statements nor comments
+    fn is_empty(&self, block: &Block) -> bool {
+        block.statements.is_empty()
+            && !self.comments.iter().any(|comment| {
+                comment.start >= block.span.start && comment.end <= block.span.end
+            })
+    }
+    
+    fn warn_empty_block(&mut self, construct: &str, removal: &str, block: &Block) {
+        self.warnings.push(CompilerWarning::EmptyBlock {
+            construct: construct.to_string(),
+            suggestion: format!(
+                "{}, or add a '# TODO' comment to the body if it is still to be written",
+                removal
+            ),
+            span: (block.span.start..block.span.end).into(),
+        });
+    }
+    
     /// Check return statement
     fn check_return_statement(&mut self, stmt: &ReturnStmt) -> Result<(), ()> {
+        if self.in_defer {
+            self.errors.push(SemanticError::JumpOutOfDefer {
+                statement: "send".to_string(),
+                span: (stmt.span.start..stmt.span.end).into(),
+            });
+            return Err(());
+        }
+        if self.spawn_scope.is_some() {
+            self.errors.push(SemanticError::SpawnViolation {
+                what: "'send'".to_string(),
+                reason: "The spawn block runs on its own thread, so its function may have finished by the time it could send. Pass results back over a channel".to_string(),
+                span: (stmt.span.start..stmt.span.end).into(),
+            });
+            return Err(());
+        }
+        if self.parallel_scope.is_some() {
+            self.errors.push(SemanticError::ParallelViolation {
+                what: "'send'".to_string(),
+                reason: "The iterations run on several threads, and only the function's own thread can return from it. Collect results in an atomic".to_string(),
+                span: (stmt.span.start..stmt.span.end).into(),
+            });
+            return Err(());
+        }
         let current_return_type = self.current_return_type.clone();
         match (&stmt.value, &current_return_type) {
             (Some(value), Some(expected_type)) => {
@@ -521,7 +1051,21 @@ impl TypeChecker {
             Expression::Identifier(id_expr) => {
                 if let Some(symbol) = self.symbol_table.lookup(&id_expr.name) {
                     let data_type = symbol.data_type.clone(); 
+                    if self.is_captured(&id_expr.name) && matches!(data_type, Type::Array(..)) {
+                        self.errors.push(SemanticError::SpawnViolation {
+                            what: format!("array '{}' shared", id_expr.name),
+                            reason: "Arrays are passed by reference, so the thread and the code that spawned it would use the same elements at once. Copy the values the thread needs into variables".to_string(),
+                            span: (id_expr.span.start..id_expr.span.end).into(),
+                        });
+                    }
+                    if data_type.contains(&Type::Canvas) {
+                        self.check_in_place_sharing(&id_expr.name, &Type::Canvas, &id_expr.span);
+                    }
+                    if data_type.contains(&Type::Json) {
+                        self.check_in_place_sharing(&id_expr.name, &Type::Json, &id_expr.span);
+                    }
                     self.mark_variable_used(&id_expr.name);
+                    self.symbol_table.record_reference(&id_expr.name, id_expr.span.start);
                     Ok(data_type)
                 } else {
                     // Find similar variable names
@@ -565,7 +1109,10 @@ impl TypeChecker {
                 for part in parts {
                     if let StringPart::Expression(expr) = part {
                         // Verify expression has a valid type
-                        let _ = self.infer_expression_type(expr)?;
+                        let typ = self.infer_expression_type(expr)?;
+                        if matches!(typ, Type::Channel | Type::Atomic | Type::Canvas | Type::Json) {
+                            self.push_not_printable(typ, expr.span());
+                        }
                     }
                 }
                 Ok(Type::String)
@@ -590,9 +1137,19 @@ impl TypeChecker {
                         }
                     }
                     
-                    Ok(Type::Array(Box::new(first_type), elements.len()))
+                    let typ = Type::Array(Box::new(first_type), elements.len());
+                    self.check_handle_storage(&typ, span);
+                    Ok(typ)
                 }
             }
+            Literal::Tuple(elements) => {
+                let element_types = elements.iter()
+                    .map(|element| self.infer_expression_type(element))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let typ = Type::Tuple(element_types);
+                self.check_tuple_elements(&typ, span);
+                Ok(typ)
+            }
         }
     }
     
@@ -618,7 +1175,21 @@ impl TypeChecker {
             
             // Comparison operators
             BinaryOp::Equal | BinaryOp::NotEqual => {
-                if self.types_compatible(&left_type, &right_type) {
+                if matches!(left_type, Type::Tuple(_)) {
+                    // Tuples compile to C structs, which have no == operator
+                    self.errors.push(SemanticError::TypeMismatch {
+                        expected: "comparable value (compare tuple elements individually)".to_string(),
+                        found: format!("{:?}", left_type),
+                        span: (binary.span.start..binary.span.end).into(),
+                    });
+                    Err(())
+                } else if self.types_compatible(&left_type, &right_type) {
+                    if left_type == Type::Float {
+                        self.warnings.push(CompilerWarning::FloatEquality {
+                            op: if binary.op == BinaryOp::Equal { "==" } else { "!=" }.to_string(),
+                            span: (binary.span.start..binary.span.end).into(),
+                        });
+                    }
                     Ok(Type::Bool)
                 } else {
                     self.errors.push(SemanticError::TypeMismatch {
@@ -694,6 +1265,10 @@ impl TypeChecker {
     /// Infer type of function call
     fn infer_call_type(&mut self, call: &CallExpr) -> Result<Type, ()> {
         if let Some(func_sig) = self.symbol_table.lookup_function(&call.function).cloned() {
+            self.symbol_table.record_function_reference(&call.function, call.span.start);
+            self.check_call_purity(&func_sig, &call.span);
+            self.check_call_attributes(&func_sig, &call.span);
+            
             // Check argument count
             if call.args.len() != func_sig.params.len() {
                 self.errors.push(SemanticError::ArgumentCountMismatch {
@@ -706,8 +1281,23 @@ impl TypeChecker {
             }
             
             // Check argument types
-            for (_, (arg, expected)) in call.args.iter().zip(&func_sig.params).enumerate() {
+            for (arg, expected) in call.args.iter().zip(&func_sig.params) {
+                self.check_constant_array_argument(arg, &func_sig, &call.span);
+                self.check_parallel_array_argument(arg, &func_sig, &call.span);
                 if let Ok(arg_type) = self.infer_expression_type(arg) {
+                    // The callee bounds-checks against its declared size, so
+                    // a smaller array would let it read past the caller's
+                    if let (Type::Array(expected_elem, expected_size), Type::Array(found_elem, found_size)) = (expected, &arg_type) {
+                        if expected_elem == found_elem && found_size < expected_size {
+                            self.errors.push(SemanticError::ArrayTooSmall {
+                                name: call.function.clone(),
+                                expected: *expected_size,
+                                found: *found_size,
+                                span: (call.span.start..call.span.end).into(),
+                            });
+                            continue;
+                        }
+                    }
                     if !self.types_compatible(expected, &arg_type) {
                         self.errors.push(SemanticError::TypeMismatch {
                             expected: format!("{:?}", expected),
@@ -730,14 +1320,84 @@ impl TypeChecker {
                 });
                 Err(())
             }
-        } else {
-            // Find similar function names
-            let similar = self.symbol_table.find_similar_functions(&call.function, 3);
-            let suggestion = if !similar.is_empty() {
-                format!("Did you mean: {}?", similar.join(", "))
-            } else {
-                format!("Function '{}' is not defined. Check spelling or define the function.", call.function)
-            };
+        } else if call.function == "format" {
+            self.infer_format_type(call)
+        } else if call.function == "approx_equal" {
+            self.infer_approx_equal_type(call)
+        } else if call.function == "to_string" {
+            self.infer_conversion_type(call, Type::Int, Type::String)
+        } else if call.function == "parse_int" {
+            self.infer_conversion_type(call, Type::String, Type::Int)
+        } else if call.function == "assert" {
+            // Gives no value, so it can only be a statement of its own
+            self.errors.push(SemanticError::TypeMismatch {
+                expected: "some return type".to_string(),
+                found: "void".to_string(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            Err(())
+        } else if CHECKED_ARITHMETIC.iter().any(|(name, _)| call.function == *name) {
+            self.infer_checked_arithmetic_type(call)
+        } else if call.function == "chan_send" {
+            // Gives no value, like assert
+            let _ = self.check_sharing_call(call);
+            self.errors.push(SemanticError::TypeMismatch {
+                expected: "some return type".to_string(),
+                found: "void".to_string(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            Err(())
+        } else if CHANNEL_BUILTINS.contains(&call.function.as_str()) || ATOMIC_BUILTINS.contains(&call.function.as_str()) {
+            self.check_sharing_call(call)
+        } else if call.function == "now_ms" {
+            self.check_clock_call(call);
+            Ok(Type::Int)
+        } else if call.function == "sleep_ms" {
+            // Gives no value, like assert
+            self.check_clock_call(call);
+            self.errors.push(SemanticError::TypeMismatch {
+                expected: "some return type".to_string(),
+                found: "void".to_string(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            Err(())
+        } else if call.function == "canvas" {
+            self.check_canvas_call(call);
+            Ok(Type::Canvas)
+        } else if CANVAS_BUILTINS.contains(&call.function.as_str()) {
+            // The others give no value, like assert
+            self.check_canvas_call(call);
+            self.errors.push(SemanticError::TypeMismatch {
+                expected: "some return type".to_string(),
+                found: "void".to_string(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            Err(())
+        } else if JSON_BUILTINS.contains(&call.function.as_str()) {
+            let typ = self.check_json_call(call);
+            if typ.is_none() {
+                // json_set and json_push give no value, like assert
+                self.errors.push(SemanticError::TypeMismatch {
+                    expected: "some return type".to_string(),
+                    found: "void".to_string(),
+                    span: (call.span.start..call.span.end).into(),
+                });
+            }
+            typ.ok_or(())
+        } else if self.private_externals.contains(&call.function) {
+            self.errors.push(SemanticError::PrivateFunction {
+                name: call.function.clone(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            Err(())
+        } else {
+            // Find similar function names
+            let similar = self.symbol_table.find_similar_functions(&call.function, 3);
+            let suggestion = if !similar.is_empty() {
+                format!("Did you mean: {}?", similar.join(", "))
+            } else {
+                format!("Function '{}' is not defined. Check spelling or define the function.", call.function)
+            };
             
             self.errors.push(SemanticError::UndefinedFunction {
                 name: call.function.clone(),
@@ -749,6 +1409,428 @@ impl TypeChecker {
         }
     }
     
+    /// Check the `format(template, args...)` builtin; each `{}` in the template takes one argument
+    fn infer_format_type(&mut self, call: &CallExpr) -> Result<Type, ()> {
+        let placeholders = match call.args.first() {
+            Some(Expression::Literal(LiteralExpr { value: Literal::String(text), .. })) => {
+                text.matches("{}").count()
+            }
+            Some(Expression::Literal(LiteralExpr { value: Literal::InterpolatedString(parts), .. })) => {
+                parts.iter()
+                    .map(|part| match part {
+                        StringPart::Text(text) => text.matches("{}").count(),
+                        StringPart::Expression(_) => 0,
+                    })
+                    .sum()
+            }
+            _ => {
+                self.errors.push(SemanticError::TypeMismatch {
+                    expected: "string literal template".to_string(),
+                    found: call.args.first()
+                        .map(|_| "expression".to_string())
+                        .unwrap_or_else(|| "no arguments".to_string()),
+                    span: (call.span.start..call.span.end).into(),
+                });
+                return Err(());
+            }
+        };
+        
+        if call.args.len() != placeholders + 1 {
+            self.errors.push(SemanticError::ArgumentCountMismatch {
+                name: call.function.clone(),
+                expected: placeholders + 1,
+                found: call.args.len(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            return Err(());
+        }
+        
+        for arg in &call.args {
+            if let Ok(arg_type) = self.infer_expression_type(arg) {
+                if matches!(arg_type, Type::Array(_, _) | Type::Tuple(_) | Type::Channel | Type::Atomic | Type::Canvas | Type::Json) {
+                    self.errors.push(SemanticError::TypeMismatch {
+                        expected: "Int, Float, String or Bool".to_string(),
+                        found: format!("{:?}", arg_type),
+                        span: (call.span.start..call.span.end).into(),
+                    });
+                }
+            }
+        }
+        
+        Ok(Type::String)
+    }
+    
+    /// Type check approx_equal(a, b, eps): three floats, yields bool
+    fn infer_approx_equal_type(&mut self, call: &CallExpr) -> Result<Type, ()> {
+        if call.args.len() != 3 {
+            self.errors.push(SemanticError::ArgumentCountMismatch {
+                name: call.function.clone(),
+                expected: 3,
+                found: call.args.len(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            return Err(());
+        }
+        
+        for arg in &call.args {
+            if let Ok(arg_type) = self.infer_expression_type(arg) {
+                if arg_type != Type::Float {
+                    self.errors.push(SemanticError::TypeMismatch {
+                        expected: format!("{:?}", Type::Float),
+                        found: format!("{:?}", arg_type),
+                        span: (call.span.start..call.span.end).into(),
+                    });
+                }
+            }
+        }
+        
+        Ok(Type::Bool)
+    }
+    
+    /// Type check assert(condition) and assert(condition, message): a bool
+    /// and an optional string, shown when the condition is false
+    fn check_assert_call(&mut self, call: &CallExpr) {
+        if !(1..=2).contains(&call.args.len()) {
+            self.errors.push(SemanticError::ArgumentCountMismatch {
+                name: call.function.clone(),
+                expected: 1,
+                found: call.args.len(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            return;
+        }
+
+        for (arg, expected) in call.args.iter().zip([Type::Bool, Type::String]) {
+            if let Ok(arg_type) = self.infer_expression_type(arg) {
+                if arg_type != expected {
+                    self.errors.push(SemanticError::TypeMismatch {
+                        expected: format!("{:?}", expected),
+                        found: format!("{:?}", arg_type),
+                        span: (call.span.start..call.span.end).into(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Type check add_checked(a, b) and the other checked arithmetic builtins:
+    /// two ints, yields (int, bool)
+    fn infer_checked_arithmetic_type(&mut self, call: &CallExpr) -> Result<Type, ()> {
+        if call.args.len() != 2 {
+            self.errors.push(SemanticError::ArgumentCountMismatch {
+                name: call.function.clone(),
+                expected: 2,
+                found: call.args.len(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            return Err(());
+        }
+
+        for arg in &call.args {
+            if let Ok(arg_type) = self.infer_expression_type(arg) {
+                if arg_type != Type::Int {
+                    self.errors.push(SemanticError::TypeMismatch {
+                        expected: format!("{:?}", Type::Int),
+                        found: format!("{:?}", arg_type),
+                        span: (call.span.start..call.span.end).into(),
+                    });
+                }
+            }
+        }
+
+        Ok(Type::Tuple(vec![Type::Int, Type::Bool]))
+    }
+
+    /// Type check channel(int) -> chan, chan_send(chan, int),
+    /// chan_receive(chan) -> int, atomic(int) -> atomic,
+    /// atomic_add(atomic, int) -> int and atomic_get(atomic) -> int. Other
+    /// threads see what they do, so pure functions can't use them.
+    fn check_sharing_call(&mut self, call: &CallExpr) -> Result<Type, ()> {
+        let (params, result) = match call.function.as_str() {
+            "channel" => (vec![Type::Int], Type::Channel),
+            "chan_send" => (vec![Type::Channel, Type::Int], Type::Int),
+            "chan_receive" => (vec![Type::Channel], Type::Int),
+            "atomic" => (vec![Type::Int], Type::Atomic),
+            "atomic_add" => (vec![Type::Atomic, Type::Int], Type::Int),
+            _ => (vec![Type::Atomic], Type::Int),
+        };
+        if self.current_function_pure {
+            self.push_purity_violation(
+                format!("'{}' shares data with other threads", call.function),
+                &call.span,
+            );
+        }
+        if call.args.len() != params.len() {
+            self.errors.push(SemanticError::ArgumentCountMismatch {
+                name: call.function.clone(),
+                expected: params.len(),
+                found: call.args.len(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            return Err(());
+        }
+
+        for (arg, expected) in call.args.iter().zip(params) {
+            if let Ok(arg_type) = self.infer_expression_type(arg) {
+                if arg_type != expected {
+                    self.errors.push(SemanticError::TypeMismatch {
+                        expected: format!("{:?}", expected),
+                        found: format!("{:?}", arg_type),
+                        span: (call.span.start..call.span.end).into(),
+                    });
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Type check canvas(int, int) -> canvas, canvas_set(canvas, int, int,
+    /// string), canvas_fill(canvas, string) and canvas_render(canvas). A
+    /// canvas is changed in place, so pure functions can't use them.
+    fn check_canvas_call(&mut self, call: &CallExpr) {
+        let params = match call.function.as_str() {
+            "canvas" => vec![Type::Int, Type::Int],
+            "canvas_set" => vec![Type::Canvas, Type::Int, Type::Int, Type::String],
+            "canvas_fill" => vec![Type::Canvas, Type::String],
+            _ => vec![Type::Canvas],
+        };
+        if self.current_function_pure {
+            self.push_purity_violation(format!("'{}' works on a canvas, which changes in place", call.function), &call.span);
+        }
+        if call.args.len() != params.len() {
+            self.errors.push(SemanticError::ArgumentCountMismatch {
+                name: call.function.clone(),
+                expected: params.len(),
+                found: call.args.len(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            return;
+        }
+
+        for (arg, expected) in call.args.iter().zip(params) {
+            if let Ok(arg_type) = self.infer_expression_type(arg) {
+                if arg_type != expected {
+                    self.errors.push(SemanticError::TypeMismatch {
+                        expected: format!("{:?}", expected),
+                        found: format!("{:?}", arg_type),
+                        span: (call.span.start..call.span.end).into(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Type check sleep_ms(int) and now_ms(). What they give depends on
+    /// when they run, so pure functions can't use them.
+    fn check_clock_call(&mut self, call: &CallExpr) {
+        let params = if call.function == "sleep_ms" { vec![Type::Int] } else { vec![] };
+        if self.current_function_pure {
+            self.push_purity_violation(format!("'{}' depends on the clock", call.function), &call.span);
+        }
+        if call.args.len() != params.len() {
+            self.errors.push(SemanticError::ArgumentCountMismatch {
+                name: call.function.clone(),
+                expected: params.len(),
+                found: call.args.len(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            return;
+        }
+
+        for (arg, expected) in call.args.iter().zip(params) {
+            if let Ok(arg_type) = self.infer_expression_type(arg) {
+                if arg_type != expected {
+                    self.errors.push(SemanticError::TypeMismatch {
+                        expected: format!("{:?}", expected),
+                        found: format!("{:?}", arg_type),
+                        span: (call.span.start..call.span.end).into(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Type check the json builtins, giving what the call yields: None for
+    /// json_set and json_push. Values are changed in place, so pure
+    /// functions can't use them.
+    fn check_json_call(&mut self, call: &CallExpr) -> Option<Type> {
+        let params = match call.function.as_str() {
+            "json_parse" => vec![Some(Type::String)],
+            "json_get" | "json_has" => vec![Some(Type::Json), Some(Type::String)],
+            "json_at" => vec![Some(Type::Json), Some(Type::Int)],
+            // None is the value to store, of any type json can hold
+            "json_set" => vec![Some(Type::Json), Some(Type::String), None],
+            "json_push" => vec![Some(Type::Json), None],
+            _ => vec![Some(Type::Json)],
+        };
+        let result = json_builtin_type(&call.function);
+        if self.current_function_pure {
+            self.push_purity_violation(format!("'{}' works on json values, which change in place", call.function), &call.span);
+        }
+        if call.args.len() != params.len() {
+            self.errors.push(SemanticError::ArgumentCountMismatch {
+                name: call.function.clone(),
+                expected: params.len(),
+                found: call.args.len(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            return result;
+        }
+
+        for (arg, expected) in call.args.iter().zip(params) {
+            let Ok(arg_type) = self.infer_expression_type(arg) else {
+                continue;
+            };
+            let expected = match expected {
+                Some(typ) if typ != arg_type => format!("{:?}", typ),
+                None if !matches!(arg_type, Type::Int | Type::Float | Type::Bool | Type::String | Type::Json) => {
+                    "Int, Float, Bool, String or Json".to_string()
+                }
+                _ => continue,
+            };
+            self.errors.push(SemanticError::TypeMismatch {
+                expected,
+                found: format!("{:?}", arg_type),
+                span: (call.span.start..call.span.end).into(),
+            });
+        }
+        result
+    }
+
+    /// Canvases and json values change in place, so one from outside a spawn
+    /// block or parallel for would be changed by several threads at once
+    fn check_in_place_sharing(&mut self, name: &str, typ: &Type, span: &Span) {
+        let (noun, spawn_reason, parallel_reason) = match typ {
+            Type::Canvas => (
+                "canvas",
+                "The thread and the code that spawned it would draw on it at once. Send what to draw through a channel",
+                "The iterations would draw on it at once. Work out what to draw in the loop and draw it after",
+            ),
+            _ => (
+                "json value",
+                "The thread and the code that spawned it would change it at once. Send what the thread works out through a channel",
+                "The iterations would change it at once. Work out the values in the loop and set them after",
+            ),
+        };
+        if self.is_captured(name) {
+            self.errors.push(SemanticError::SpawnViolation {
+                what: format!("{} '{}' shared", noun, name),
+                reason: spawn_reason.to_string(),
+                span: (span.start..span.end).into(),
+            });
+        } else if self.is_shared_by_iterations(name) {
+            self.errors.push(SemanticError::ParallelViolation {
+                what: format!("{} '{}' shared", noun, name),
+                reason: parallel_reason.to_string(),
+                span: (span.start..span.end).into(),
+            });
+        }
+    }
+
+    /// Whether `name`, used inside a spawn block, is a variable of the code
+    /// that spawned it
+    fn is_captured(&self, name: &str) -> bool {
+        match (self.spawn_scope, self.symbol_table.lookup(name)) {
+            (Some(level), Some(symbol)) => symbol.scope_level < level,
+            _ => false,
+        }
+    }
+
+    /// Whether `name`, used inside a parallel for, is a variable all of its
+    /// iterations share: one declared outside the body, or the loop variable
+    fn is_shared_by_iterations(&self, name: &str) -> bool {
+        match (self.parallel_scope, self.symbol_table.lookup(name)) {
+            (Some(level), Some(symbol)) => symbol.scope_level < level,
+            _ => false,
+        }
+    }
+
+    /// Check the body of a parallel for. It gets a scope of its own, so
+    /// that the loop variable is among the variables iterations share.
+    fn check_parallel_body(&mut self, stmt: &ForStmt) {
+        if self.current_function_pure {
+            self.push_purity_violation("'parallel for' starts threads".to_string(), &stmt.span);
+        }
+        self.symbol_table.enter_scope();
+        let outer_scope = self.parallel_scope.replace(self.symbol_table.current_scope_level());
+        let outer_depth = std::mem::replace(&mut self.parallel_loop_depth, self.loop_depth);
+        self.variable_usage.push(HashMap::new());
+        let _ = self.check_block(&stmt.body);
+        self.check_unused_variables();
+        self.variable_usage.pop();
+        self.parallel_loop_depth = outer_depth;
+        self.parallel_scope = outer_scope;
+        self.symbol_table.exit_scope();
+    }
+
+    /// Channels, atomics, canvases and json values only come from their
+    /// builtins, and an array's elements start out as defaults, so they are
+    /// kept in variables, parameters and tuples but not in arrays
+    fn check_handle_storage(&mut self, typ: &Type, span: &Span) {
+        if let Type::Array(element, _) = typ {
+            if element.contains_handle() {
+                self.errors.push(SemanticError::TypeMismatch {
+                    expected: "array elements other than channels, atomics, canvases and json values".to_string(),
+                    found: format!("{:?}", typ),
+                    span: (span.start..span.end).into(),
+                });
+            }
+        }
+    }
+
+    /// Tuples are copied as C structs, which cannot hold an array passed by
+    /// reference, so no tuple may have an array element
+    fn check_tuple_elements(&mut self, typ: &Type, span: &Span) {
+        fn array_in_tuple(typ: &Type) -> bool {
+            match typ {
+                Type::Tuple(elements) => elements.iter()
+                    .any(|element| matches!(element, Type::Array(_, _)) || array_in_tuple(element)),
+                Type::Array(element, _) => array_in_tuple(element),
+                _ => false,
+            }
+        }
+        if array_in_tuple(typ) {
+            self.errors.push(SemanticError::ArrayInTuple {
+                found: typ.to_string(),
+                span: (span.start..span.end).into(),
+            });
+        }
+    }
+
+    fn push_not_printable(&mut self, typ: Type, span: &Span) {
+        self.errors.push(SemanticError::TypeMismatch {
+            expected: "Int, Float, String or Bool".to_string(),
+            found: format!("{:?}", typ),
+            span: (span.start..span.end).into(),
+        });
+    }
+
+    /// Type check to_string(int) and parse_int(string): one argument of type `from`, yields `to`
+    fn infer_conversion_type(&mut self, call: &CallExpr, from: Type, to: Type) -> Result<Type, ()> {
+        if call.args.len() != 1 {
+            self.errors.push(SemanticError::ArgumentCountMismatch {
+                name: call.function.clone(),
+                expected: 1,
+                found: call.args.len(),
+                span: (call.span.start..call.span.end).into(),
+            });
+            return Err(());
+        }
+        
+        if let Ok(arg_type) = self.infer_expression_type(&call.args[0]) {
+            if arg_type != from {
+                self.errors.push(SemanticError::TypeMismatch {
+                    expected: format!("{:?}", from),
+                    found: format!("{:?}", arg_type),
+                    span: (call.span.start..call.span.end).into(),
+                });
+            }
+        }
+        
+        Ok(to)
+    }
+    
     /// Infer type of array indexing
     fn infer_index_type(&mut self, index: &IndexExpr) -> Result<Type, ()> {
         let array_type = self.infer_expression_type(&index.array)?;
@@ -779,8 +1861,50 @@ impl TypeChecker {
     
     /// Infer type of assignment
     fn infer_assign_type(&mut self, assign: &AssignExpr) -> Result<Type, ()> {
-        if assign.target.starts_with("__ARRAY_INDEX__:") {
-        // Just validate the value expression
+        let target = assign.target.strip_prefix("__ARRAY_INDEX__:").unwrap_or(&assign.target);
+        self.symbol_table.record_reference(target, assign.span.start);
+        if self.is_captured(target) {
+            self.errors.push(SemanticError::SpawnViolation {
+                what: format!("assignment to '{}'", target),
+                reason: "The thread works on its own copy of each variable it uses from outside the spawn block, so the assignment would be lost. Send the value back over a channel".to_string(),
+                span: (assign.span.start..assign.span.end).into(),
+            });
+            return Err(());
+        }
+        if self.is_shared_by_iterations(target) {
+            self.errors.push(SemanticError::ParallelViolation {
+                what: format!("assignment to '{}'", target),
+                reason: "The iterations run at the same time on several threads, so they would overwrite each other's values. Declare the variable inside the loop, or add up results with an atomic".to_string(),
+                span: (assign.span.start..assign.span.end).into(),
+            });
+            return Err(());
+        }
+        if let Some(array_name) = assign.target.strip_prefix("__ARRAY_INDEX__:") {
+            let is_constant = self.symbol_table.lookup(array_name)
+                .map(|s| s.symbol_type == SymType::Constant)
+                .unwrap_or(false);
+            if is_constant {
+                self.errors.push(SemanticError::ConstantArrayModified {
+                    name: array_name.to_string(),
+                    reason: "Elements of a const array cannot be assigned. Declare it with 'let' to change it".to_string(),
+                    span: (assign.span.start..assign.span.end).into(),
+                });
+                return Err(());
+            }
+            // Arrays are passed by reference, so writing into a parameter
+            // mutates caller-owned memory
+            if self.current_function_pure {
+                let is_param = self.symbol_table.lookup(array_name)
+                    .map(|s| s.symbol_type == SymType::Parameter)
+                    .unwrap_or(false);
+                if is_param {
+                    self.push_purity_violation(
+                        format!("Assigning into parameter array '{}' modifies the caller's data", array_name),
+                        &assign.span,
+                    );
+                }
+            }
+            // Just validate the value expression
             return self.infer_expression_type(&assign.value);
         }
         
@@ -831,33 +1955,6 @@ impl TypeChecker {
         // TODO: Add implicit conversions if needed (e.g., int to float)
     }
     
-    /// Check if block has return statement on all paths
-    fn block_returns(&self, block: &Block) -> bool {
-        for stmt in &block.statements {
-            if self.statement_returns(stmt) {
-                return true;
-            }
-        }
-        false
-    }
-    
-    /// Check if statement returns
-    fn statement_returns(&self, stmt: &Statement) -> bool {
-        match stmt {
-            Statement::Return(_) => true,
-            Statement::If(if_stmt) => {
-                // Both branches must return
-                if let Some(else_block) = &if_stmt.else_block {
-                    self.block_returns(&if_stmt.then_block) && self.block_returns(else_block)
-                } else {
-                    false
-                }
-            }
-            Statement::Block(block) => self.block_returns(block),
-            _ => false,
-        }
-    }
-    
     /// Get collected errors
     pub fn get_errors(&self) -> &[SemanticError] {
         &self.errors
@@ -873,7 +1970,7 @@ impl TypeChecker {
         }
     }
 
-    /// Check for unused variables at scope exit
+    /// Check for unused variables and constants at scope exit
     fn check_unused_variables(&mut self) {
         let current_usage = self.variable_usage.last();
         
@@ -883,14 +1980,14 @@ impl TypeChecker {
                 .copied()
                 .unwrap_or(true);  // Default to true (no warning) if not found
             
-            if !is_used {
-                if !matches!(symbol.symbol_type, SymType::Parameter) && !name.starts_with("_") {
-                    self.warnings.push(CompilerWarning::UnusedVariable {
-                        name: name.clone(),
-                        span: (symbol.defined_at..symbol.defined_at + name.len()).into(),
-                        defined_at: (symbol.defined_at..symbol.defined_at + name.len()).into(),
-                    });
-                }
+            if is_used || name.starts_with('_') {
+                continue;
+            }
+            let span = (symbol.defined_at..symbol.defined_at + name.len()).into();
+            match symbol.symbol_type {
+                SymType::Parameter => {}
+                SymType::Constant => self.warnings.push(CompilerWarning::UnusedConstant { name, span }),
+                _ => self.warnings.push(CompilerWarning::UnusedVariable { name, span, defined_at: span }),
             }
         }
     }
@@ -899,6 +1996,135 @@ impl TypeChecker {
     pub fn get_warnings(&self) -> &[CompilerWarning] {
         &self.warnings
     }
+    
+    /// The symbols of the checked program, kept after their scopes ended,
+    /// and the references recorded for them
+    pub fn symbol_table(&self) -> &SymbolTable {
+        &self.symbol_table
+    }
+
+    /// Report calls from a pure function to a function that is not pure
+    fn check_call_purity(&mut self, callee: &FunctionSignature, span: &Span) {
+        if self.current_function_pure && !callee.is_pure {
+            self.push_purity_violation(
+                format!("'{}' is not declared 'pure'", callee.name),
+                span,
+            );
+        }
+    }
+
+    /// Arrays are passed by reference, so only a pure function, which cannot
+    /// write through its parameters, may be given a const array
+    fn check_constant_array_argument(&mut self, arg: &Expression, callee: &FunctionSignature, span: &Span) {
+        let Expression::Identifier(id) = arg else { return };
+        let is_constant_array = self.symbol_table.lookup(&id.name)
+            .map(|s| s.symbol_type == SymType::Constant && matches!(s.data_type, Type::Array(..)))
+            .unwrap_or(false);
+        if is_constant_array && !callee.is_pure {
+            self.errors.push(SemanticError::ConstantArrayModified {
+                name: id.name.clone(),
+                reason: format!("'{}' is not declared 'pure' and may write into its array argument. Make it pure, or copy the array into a 'let'", callee.name),
+                span: (span.start..span.end).into(),
+            });
+        }
+    }
+
+    /// In a parallel for, an array from outside the body may only go to a
+    /// pure function: any other could write it while iterations read it
+    fn check_parallel_array_argument(&mut self, arg: &Expression, callee: &FunctionSignature, span: &Span) {
+        let Expression::Identifier(id) = arg else { return };
+        let is_shared_array = self.is_shared_by_iterations(&id.name)
+            && self.symbol_table.lookup(&id.name).is_some_and(|s| matches!(s.data_type, Type::Array(..)));
+        if is_shared_array && !callee.is_pure {
+            self.errors.push(SemanticError::ParallelViolation {
+                what: format!("array '{}' passed to '{}'", id.name, callee.name),
+                reason: format!("'{}' is not declared 'pure' and may write into the array while other iterations read it. Make it pure", callee.name),
+                span: (span.start..span.end).into(),
+            });
+        }
+    }
+
+    /// Validate the attributes written on a function
+    fn check_attributes(&mut self, function: &Function) {
+        let is_scalar = |typ: &Type| matches!(typ, Type::Int | Type::Float | Type::Bool);
+        let unkeyable = function.params.iter().find(|param| !is_scalar(&param.typ));
+        
+        let mut seen = HashSet::new();
+        for attr in &function.attributes {
+            let reason = match attr.name.as_str() {
+                _ if !seen.insert(attr.name.as_str()) => {
+                    Some("it is already applied to this function".to_string())
+                }
+                "inline" | "noopt" | "memo" if attr.argument.is_some() => {
+                    Some(format!("'@{}' takes no argument", attr.name))
+                }
+                "inline" if function.name == "main" => {
+                    Some("'main' is the program entry point and cannot be inlined".to_string())
+                }
+                "memo" if !function.is_pure => {
+                    Some(format!("'{}' is not pure, and a cached result would skip its side effects", function.name))
+                }
+                "memo" if !function.return_type.as_ref().is_some_and(is_scalar) => {
+                    Some("memoized functions must return an int, float or bool".to_string())
+                }
+                "memo" if unkeyable.is_some() => unkeyable.map(|param| format!(
+                    "parameter '{}' is a {}, but results are cached by int, float and bool arguments",
+                    param.name, param.typ
+                )),
+                "memo" if function.has_attribute("inline") => {
+                    Some("'@inline' copies the body into its callers, which would bypass the cache".to_string())
+                }
+                "inline" | "noopt" | "deprecated" | "memo" => None,
+                _ => Some("known attributes are @inline, @noopt, @memo and @deprecated".to_string()),
+            };
+            
+            if let Some(reason) = reason {
+                self.errors.push(SemanticError::InvalidAttribute {
+                    name: attr.name.clone(),
+                    reason,
+                    span: (attr.span.start..attr.span.end).into(),
+                });
+            }
+        }
+    }
+
+    /// Warn about calls to deprecated functions and reject recursive inlining
+    fn check_call_attributes(&mut self, callee: &FunctionSignature, span: &Span) {
+        if callee.deprecated && self.current_function.as_deref() != Some(callee.name.as_str()) {
+            self.warnings.push(CompilerWarning::DeprecatedCall {
+                name: callee.name.clone(),
+                note: callee.deprecation_note.clone(),
+                span: (span.start..span.end).into(),
+            });
+        }
+        
+        if callee.is_inline && self.current_function.as_deref() == Some(callee.name.as_str()) {
+            self.errors.push(SemanticError::InvalidAttribute {
+                name: "inline".to_string(),
+                reason: format!("'{}' calls itself, so it cannot be inlined", callee.name),
+                span: (span.start..span.end).into(),
+            });
+        }
+    }
+
+    /// Names starting with `__` belong to the compiler, e.g. the
+    /// `__ARRAY_INDEX__` marker used for element assignment
+    fn check_identifier(&mut self, name: &str, span: &Span) {
+        if name.starts_with("__") {
+            self.errors.push(SemanticError::ReservedIdentifier {
+                name: name.to_string(),
+                span: (span.start..span.end).into(),
+            });
+        }
+    }
+    
+    fn push_purity_violation(&mut self, reason: String, span: &Span) {
+        self.errors.push(SemanticError::PurityViolation {
+            name: self.current_function.clone().unwrap_or_default(),
+            reason,
+            span: (span.start..span.end).into(),
+        });
+    }
 
     /// Get current context string
     fn get_context(&self) -> Option<String> {
@@ -919,7 +2145,11 @@ impl TypeChecker {
             Statement::Block(s) => s.span.start..s.span.end,
             Statement::Break(s) => s.span.start..s.span.end,
             Statement::Continue(s) => s.span.start..s.span.end,
+            Statement::Defer(s) => s.span.start..s.span.end,
+            Statement::LetTuple(s) => s.span.start..s.span.end,
+            Statement::Spawn(s) => s.span.start..s.span.end,
         }
     }
 
-}
\ No newline at end of file
+}
+
@@ -0,0 +1,154 @@
+// src/usage.rs - Local usage statistics for instructors
+//
+// With `--log-usage <dir>`, every compile appends a line to <dir>/usage.jsonl
+// saying which errors and warnings it reported. The line holds the file's
+// name and the diagnostic codes but no source, paths or hashes, and nothing
+// leaves the machine. `minilang report usage` then adds up one or more such
+// logs, e.g. collected from a classroom, to show which errors students run
+// into most.
+
+use crate::build_log::{BuildRecord, BuildStatus};
+use serde::{Serialize, Deserialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The file in a usage directory that records are appended to
+pub const LOG_FILE: &str = "usage.jsonl";
+
+/// What one compile reported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// Seconds since the Unix epoch when the compile started
+    pub timestamp: u64,
+    pub compiler_version: String,
+    /// The source's file name, without its directory
+    pub file: String,
+    /// `compile`, `run`, `to-c` or `dry-run`
+    pub mode: String,
+    pub status: BuildStatus,
+    /// The code of each error reported, e.g. `minilang::parser::missing_semicolon`
+    pub errors: Vec<String>,
+    /// The code of each warning reported
+    pub warnings: Vec<String>,
+}
+
+impl UsageRecord {
+    /// The parts of a build record that are kept for usage statistics
+    pub fn from_build(record: &BuildRecord) -> Self {
+        let file = Path::new(&record.source)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self {
+            timestamp: record.timestamp,
+            compiler_version: record.compiler_version.clone(),
+            file,
+            mode: record.mode.clone(),
+            status: record.status.clone(),
+            errors: record.error_codes.clone(),
+            warnings: record.warning_codes.clone(),
+        }
+    }
+
+    /// Append this record to the log in `dir`, creating both if needed
+    pub fn append_to_dir(&self, dir: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let line = serde_json::to_string(self)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE))?;
+        // One write per record keeps concurrent appends from interleaving
+        file.write_all(format!("{}\n", line).as_bytes())
+    }
+}
+
+/// Read the records of every log in `paths`. A directory stands for the
+/// usage.jsonl in it.
+pub fn read_usage(paths: &[PathBuf]) -> Result<Vec<UsageRecord>, String> {
+    let mut records = Vec::new();
+    for path in paths {
+        let log = if path.is_dir() { path.join(LOG_FILE) } else { path.clone() };
+        let text = fs::read_to_string(&log).map_err(|e| format!("cannot read {}: {}", log.display(), e))?;
+        for (index, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let record = serde_json::from_str(line)
+                .map_err(|e| format!("{}:{}: not a usage record: {}", log.display(), index + 1, e))?;
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Totals over a set of usage records
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub compiles: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Error codes, most frequent first
+    pub errors: Vec<CodeCount>,
+    /// Warning codes, most frequent first
+    pub warnings: Vec<CodeCount>,
+    /// The stages compiles failed in, most frequent first
+    pub failed_stages: Vec<StageCount>,
+}
+
+/// How often a diagnostic was reported
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CodeCount {
+    pub code: String,
+    /// Every time it was reported
+    pub occurrences: usize,
+    /// How many compiles reported it at least once
+    pub compiles: usize,
+}
+
+/// How many compiles failed in a stage
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StageCount {
+    pub stage: String,
+    pub compiles: usize,
+}
+
+/// Add up `records`
+pub fn summarize(records: &[UsageRecord]) -> UsageReport {
+    let mut failed_stages: BTreeMap<&str, usize> = BTreeMap::new();
+    for record in records {
+        if let BuildStatus::Failed { stage } = &record.status {
+            *failed_stages.entry(stage).or_default() += 1;
+        }
+    }
+    let mut failed_stages: Vec<StageCount> = failed_stages.into_iter()
+        .map(|(stage, compiles)| StageCount { stage: stage.to_string(), compiles })
+        .collect();
+    failed_stages.sort_by_key(|stage| Reverse(stage.compiles));
+
+    UsageReport {
+        compiles: records.len(),
+        succeeded: records.iter().filter(|record| record.status == BuildStatus::Success).count(),
+        failed: records.iter().filter(|record| matches!(record.status, BuildStatus::Failed { .. })).count(),
+        errors: count_codes(records.iter().map(|record| &record.errors)),
+        warnings: count_codes(records.iter().map(|record| &record.warnings)),
+        failed_stages,
+    }
+}
+
+/// Count each code across compiles, most frequent first, ties by code
+fn count_codes<'a>(per_compile: impl Iterator<Item = &'a Vec<String>>) -> Vec<CodeCount> {
+    let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for codes in per_compile {
+        let mut seen = HashSet::new();
+        for code in codes {
+            let (occurrences, compiles) = counts.entry(code).or_default();
+            *occurrences += 1;
+            if seen.insert(code) {
+                *compiles += 1;
+            }
+        }
+    }
+    let mut counts: Vec<CodeCount> = counts.into_iter()
+        .map(|(code, (occurrences, compiles))| CodeCount { code: code.to_string(), occurrences, compiles })
+        .collect();
+    counts.sort_by_key(|count| Reverse(count.occurrences));
+    counts
+}
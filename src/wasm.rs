@@ -31,6 +31,11 @@ pub struct OptimizationStats {
     pub dead_code_removed: usize,
     pub constants_propagated: usize,
     pub strength_reductions: usize,
+    pub comparisons_decided: usize,
+    pub functions_inlined: usize,
+    pub functions_removed: usize,
+    pub functions_specialized: usize,
+    pub allocations_elided: usize,
 }
 
 #[wasm_bindgen]
@@ -138,7 +143,7 @@ fn parser_to_diagnostic(error: &ParserError, source: &str) -> DiagnosticWithSour
             "missing type annotation".to_string(),
             *span,
             "type annotation expected here".to_string(),
-            Some("Variables require type annotations: let name: type = value;".to_string()),
+            Some("A variable declared without a value needs a type annotation: let name: type; (or give it a value to infer the type from: let name = value;)".to_string()),
             "minilang::parser::missing_type",
         ),
         ParserError::UnexpectedEof { expected } => (
@@ -148,6 +153,27 @@ fn parser_to_diagnostic(error: &ParserError, source: &str) -> DiagnosticWithSour
             Some("The program ended unexpectedly. Check for missing closing braces.".to_string()),
             "minilang::parser::unexpected_eof",
         ),
+        ParserError::InvalidCondition { reason, span } => (
+            "invalid compile-time condition".to_string(),
+            *span,
+            "cannot be evaluated at compile time".to_string(),
+            Some(reason.clone()),
+            "minilang::parser::invalid_condition",
+        ),
+        ParserError::FeatureRequiresVersion { feature, required, version, span } => (
+            format!("this feature requires version {}", required),
+            *span,
+            format!("{} (version {} and later)", feature, required),
+            Some(format!("This file is read as MiniLang {}. Start it with `minilang {};` to use {}", version, required, feature)),
+            "minilang::parser::feature_requires_version",
+        ),
+        ParserError::UnsupportedVersion { reason, span } => (
+            "unsupported language version".to_string(),
+            *span,
+            "in this version declaration".to_string(),
+            Some(reason.clone()),
+            "minilang::parser::unsupported_version",
+        ),
     };
 
     DiagnosticWithSource {
@@ -205,6 +231,34 @@ fn semantic_to_diagnostic(error: &SemanticError, source: &str) -> DiagnosticWith
             Some(format!("Function '{}' must return a value of type {} on all code paths", name, return_type)),
             "minilang::semantic::missing_return",
         ),
+        SemanticError::InvalidMainReturnType { found, span } => (
+            format!("'main' cannot return {}", found),
+            *span,
+            "main declared here".to_string(),
+            Some("The value main sends is the program's exit status, so main returns int or nothing: func main() -> int".to_string()),
+            "minilang::semantic::invalid_main_return",
+        ),
+        SemanticError::InvalidEntry { name, problem, span } => (
+            format!("entry point '{}' {}", name, problem),
+            *span,
+            "entry point declared here".to_string(),
+            Some("--entry calls the function with no arguments and exits with its result, so it takes no parameters and returns int or nothing".to_string()),
+            "minilang::semantic::invalid_entry",
+        ),
+        SemanticError::PrivateMain { span } => (
+            "'main' cannot be private".to_string(),
+            *span,
+            "main declared here".to_string(),
+            Some("main is where the program starts, so it has to stay visible to the linker. Remove `private`".to_string()),
+            "minilang::semantic::private_main",
+        ),
+        SemanticError::PrivateFunction { name, span } => (
+            format!("function '{}' is private to another module", name),
+            *span,
+            "private function".to_string(),
+            Some(format!("Only public functions can be called from other modules. Remove `private` from '{}' to share it", name)),
+            "minilang::semantic::private_function",
+        ),
         SemanticError::BreakOutsideLoop { statement, span } => (
             "break/continue outside loop".to_string(),
             *span,
@@ -212,6 +266,97 @@ fn semantic_to_diagnostic(error: &SemanticError, source: &str) -> DiagnosticWith
             Some(format!("'{}' can only be used inside a loop (while, do-while, or for)", statement)),
             "minilang::semantic::break_outside_loop",
         ),
+        SemanticError::JumpOutOfDefer { statement, span } => (
+            format!("'{}' cannot leave a defer block", statement),
+            *span,
+            "jumps out of defer".to_string(),
+            Some("Deferred code runs while the enclosing block is already exiting, so it cannot 'send', 'break' or 'continue' out of it".to_string()),
+            "minilang::semantic::jump_out_of_defer",
+        ),
+        SemanticError::SpawnViolation { what, reason, span } => (
+            format!("{} in a spawn block", what),
+            *span,
+            "not allowed in a spawn block".to_string(),
+            Some(reason.clone()),
+            "minilang::semantic::spawn_violation",
+        ),
+        SemanticError::ParallelViolation { what, reason, span } => (
+            format!("{} in a parallel for", what),
+            *span,
+            "not allowed in a parallel for".to_string(),
+            Some(reason.clone()),
+            "minilang::semantic::parallel_violation",
+        ),
+        SemanticError::PurityViolation { name, reason, span } => (
+            format!("side effect in pure function '{}'", name),
+            *span,
+            "not allowed in a pure function".to_string(),
+            Some(reason.clone()),
+            "minilang::semantic::purity_violation",
+        ),
+        SemanticError::ArrayInTuple { found, span } => (
+            "arrays cannot be tuple elements".to_string(),
+            *span,
+            "tuple with an array element".to_string(),
+            Some(format!("A tuple is copied whole, while an array is passed by reference, so {} cannot be built. Pass the array alongside the tuple instead", found)),
+            "minilang::semantic::array_in_tuple",
+        ),
+        SemanticError::ArrayLengthMismatch { name, declared, found, span } => (
+            format!("array '{}' initializer has {} elements, expected {}", name, found, declared),
+            *span,
+            "initializer length differs from declared size".to_string(),
+            Some(format!("Array '{}' is declared with {} elements. Make the literal match, or write the type as '[]' to take the size from the literal", name, declared)),
+            "minilang::semantic::array_length_mismatch",
+        ),
+        SemanticError::ArrayTooSmall { name, expected, found, span } => (
+            format!("array argument to '{}' has {} elements, expected {}", name, found, expected),
+            *span,
+            "array is too small for this parameter".to_string(),
+            Some(format!("'{}' declares this parameter with {} elements and may read all of them. Pass an array of that size", name, expected)),
+            "minilang::semantic::array_too_small",
+        ),
+        SemanticError::ReservedIdentifier { name, span } => (
+            format!("reserved identifier '{}'", name),
+            *span,
+            "reserved name declared here".to_string(),
+            Some(format!("Names starting with '__' are reserved for the compiler. Rename '{}'", name)),
+            "minilang::semantic::reserved_identifier",
+        ),
+        SemanticError::ConstantOverflow { expression, span } => (
+            format!("constant expression '{}' overflows int", expression),
+            *span,
+            "result does not fit in a 32-bit int".to_string(),
+            Some("With --overflow trap this would abort at runtime. Use smaller values, or --overflow wrap for two's complement wrapping".to_string()),
+            "minilang::semantic::constant_overflow",
+        ),
+        SemanticError::CannotInferType { name, reason, span } => (
+            format!("cannot infer the type of '{}'", name),
+            *span,
+            "type annotation needed".to_string(),
+            Some(format!("{}. Add a type annotation: let {}: type = value;", reason, name)),
+            "minilang::semantic::cannot_infer_type",
+        ),
+        SemanticError::NonBoolCondition { keyword, found, replacement, span } => (
+            format!("{} condition must be a bool, found {}", keyword, found),
+            *span,
+            "not a bool".to_string(),
+            Some(format!("Numbers are not true or false in MiniLang. Write `{}` as the {} condition", replacement, keyword)),
+            "minilang::semantic::non_bool_condition",
+        ),
+        SemanticError::ConstantArrayModified { name, reason, span } => (
+            format!("const array '{}' may be modified", name),
+            *span,
+            "const array used where it could change".to_string(),
+            Some(reason.clone()),
+            "minilang::semantic::constant_array_modified",
+        ),
+        SemanticError::InvalidAttribute { name, reason, span } => (
+            format!("invalid attribute '@{}'", name),
+            *span,
+            "attribute not allowed here".to_string(),
+            Some(reason.clone()),
+            "minilang::semantic::invalid_attribute",
+        ),
     };
 
     DiagnosticWithSource {
@@ -329,7 +474,7 @@ pub fn compile(source: &str, opt_level: u8) -> String {
     let mut optimizer = Optimizer::new(opt_level);
     let opt_stats = optimizer.optimize(&mut program);
 
-    let mut codegen = CodeGenerator::new();
+    let mut codegen = CodeGenerator::new().with_peephole(opt_level >= 1);
     let c_code = match codegen.generate(&program) {
         Ok(code) => code,
         Err(e) => {
@@ -344,6 +489,11 @@ pub fn compile(source: &str, opt_level: u8) -> String {
                     dead_code_removed: opt_stats.dead_code_removed,
                     constants_propagated: opt_stats.constants_propagated,
                     strength_reductions: opt_stats.strength_reductions,
+                    comparisons_decided: opt_stats.comparisons_decided,
+                    functions_inlined: opt_stats.functions_inlined,
+                    functions_removed: opt_stats.functions_removed,
+                    functions_specialized: opt_stats.functions_specialized,
+                    allocations_elided: opt_stats.allocations_elided,
                 },
                 tokens: Some(token_info),
                 ast: Some(ast_display),
@@ -365,6 +515,11 @@ pub fn compile(source: &str, opt_level: u8) -> String {
             dead_code_removed: opt_stats.dead_code_removed,
             constants_propagated: opt_stats.constants_propagated,
             strength_reductions: opt_stats.strength_reductions,
+            comparisons_decided: opt_stats.comparisons_decided,
+            functions_inlined: opt_stats.functions_inlined,
+            functions_removed: opt_stats.functions_removed,
+            functions_specialized: opt_stats.functions_specialized,
+            allocations_elided: opt_stats.allocations_elided,
         },
         tokens: Some(token_info),
         ast: Some(ast_display),
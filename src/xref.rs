@@ -0,0 +1,121 @@
+// src/xref.rs - Cross-reference listing of a program's symbols
+//
+// `minilang xref` lists every function, parameter, variable and constant
+// with where it is defined and every place it is referenced. The type
+// checker resolves each name to the declaration in scope and records the
+// reference in its symbol table, which keeps the symbols of scopes that have
+// ended, so two variables of the same name in different blocks stay apart.
+
+use crate::ast::Program;
+use crate::line_index::{LineCol, LineIndex};
+use crate::symbol_table::{SymbolTable, SymbolType};
+use serde::Serialize;
+
+/// A symbol, where it is defined and where it is used
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct XrefEntry {
+    pub name: String,
+    /// `function`, `parameter`, `variable` or `constant`
+    pub kind: &'static str,
+    /// The type, or the signature of a function, as written in MiniLang
+    #[serde(rename = "type")]
+    pub typ: String,
+    /// The function a parameter or local belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    pub defined: LineCol,
+    /// In source order
+    pub references: Vec<LineCol>,
+}
+
+/// The entries for `program`, checked into `table`, in definition order:
+/// each function followed by its parameters and locals
+pub fn build(program: &Program, table: &SymbolTable, source: &str) -> Vec<XrefEntry> {
+    let lines = LineIndex::new(source);
+    let position = |offset: usize| lines.line_col(source, offset);
+    let symbols = table.all_symbols();
+
+    let mut entries = Vec::new();
+    for function in &program.functions {
+        let params: Vec<String> = function.params.iter()
+            .map(|param| format!("{}: {}", param.name, param.typ))
+            .collect();
+        let returns = function.return_type.as_ref()
+            .map(|typ| format!(" -> {}", typ))
+            .unwrap_or_default();
+        entries.push(XrefEntry {
+            name: function.name.clone(),
+            kind: "function",
+            typ: format!("func({}){}", params.join(", "), returns),
+            function: None,
+            // Attributes may come first, and mention the name in a note
+            defined: position(name_offset(source, name_offset(source, function.span.start, "func"), &function.name)),
+            references: table.function_references(&function.name).into_iter().map(position).collect(),
+        });
+
+        let mut locals: Vec<_> = symbols.iter()
+            .filter(|entry| function.span.start <= entry.symbol.defined_at && entry.symbol.defined_at < function.span.end)
+            .collect();
+        locals.sort_by_key(|entry| entry.symbol.defined_at);
+        for entry in locals {
+            let symbol = entry.symbol;
+            entries.push(XrefEntry {
+                name: symbol.name.clone(),
+                kind: match symbol.symbol_type {
+                    SymbolType::Parameter => "parameter",
+                    SymbolType::Constant => "constant",
+                    SymbolType::Function => "function",
+                    SymbolType::Variable => "variable",
+                },
+                typ: symbol.data_type.to_string(),
+                function: Some(function.name.clone()),
+                defined: position(name_offset(source, symbol.defined_at, &symbol.name)),
+                references: entry.references.iter().copied().map(position).collect(),
+            });
+        }
+    }
+    entries
+}
+
+/// Where `name` is first written as a whole word at or after `from`: symbols
+/// are recorded at the start of their declaration, e.g. at `let`
+fn name_offset(source: &str, from: usize, name: &str) -> usize {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let rest = source.get(from..).unwrap_or_default();
+    rest.match_indices(name)
+        .find(|(at, _)| {
+            let before = rest[..*at].chars().next_back();
+            let after = rest[at + name.len()..].chars().next();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })
+        .map_or(from, |(at, _)| from + at)
+}
+
+/// The listing as aligned columns, one symbol per line
+pub fn render(entries: &[XrefEntry]) -> String {
+    let qualified = |entry: &XrefEntry| match &entry.function {
+        Some(function) => format!("{}::{}", function, entry.name),
+        None => entry.name.clone(),
+    };
+    let at = |position: &LineCol| format!("{}:{}", position.line, position.column);
+    let name_width = entries.iter().map(|entry| qualified(entry).chars().count()).max().unwrap_or(0).max(4);
+    let type_width = entries.iter().map(|entry| entry.typ.chars().count()).max().unwrap_or(0).max(4);
+    let defined_width = entries.iter().map(|entry| at(&entry.defined).len()).max().unwrap_or(0).max(7);
+
+    let mut output = format!(
+        "{:<name_width$}  {:<9}  {:<type_width$}  {:<defined_width$}  REFERENCES\n",
+        "NAME", "KIND", "TYPE", "DEFINED",
+    );
+    for entry in entries {
+        let references = if entry.references.is_empty() {
+            "-".to_string()
+        } else {
+            entry.references.iter().map(at).collect::<Vec<_>>().join(" ")
+        };
+        output.push_str(&format!(
+            "{:<name_width$}  {:<9}  {:<type_width$}  {:<defined_width$}  {}\n",
+            qualified(entry), entry.kind, entry.typ, at(&entry.defined), references,
+        ));
+    }
+    output
+}
@@ -0,0 +1,241 @@
+// tests/backend_tests.rs - Compiling generated C and reporting C compiler failures
+
+use minilang_compiler::backend::{self, GCC_FLAGS};
+use minilang_compiler::{BackendError, CcDiagnostic, CompilerError};
+use miette::Diagnostic;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn write_c(dir: &TempDir, code: &str) -> std::path::PathBuf {
+    let c_file = dir.path().join("test.c");
+    fs::write(&c_file, code).unwrap();
+    c_file
+}
+
+// ==================== SUCCESS ====================
+
+#[test]
+fn test_compile_c_builds_executable() {
+    let dir = TempDir::new().unwrap();
+    let c_file = write_c(&dir, "#include <stdio.h>\nint main(void) { printf(\"hi\\n\"); return 0; }\n");
+    let exe = dir.path().join("test");
+
+    backend::compile_c("gcc", &c_file, &exe, &GCC_FLAGS).unwrap();
+
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hi\n");
+    println!("✓ Valid C compiles to a runnable executable");
+}
+
+#[test]
+fn test_objects_link_into_executable() {
+    let dir = TempDir::new().unwrap();
+    let main_c = dir.path().join("main.c");
+    let util_c = dir.path().join("util.c");
+    fs::write(&main_c, "#include <stdio.h>\nint twice(int n);\nint main(void) { printf(\"%d\\n\", twice(21)); return 0; }\n").unwrap();
+    fs::write(&util_c, "int twice(int n) { return n * 2; }\n").unwrap();
+    let objects = [dir.path().join("main.o"), dir.path().join("util.o")];
+    let exe = dir.path().join("test");
+
+    backend::compile_object("gcc", &main_c, &objects[0], &GCC_FLAGS).unwrap();
+    backend::compile_object("gcc", &util_c, &objects[1], &GCC_FLAGS).unwrap();
+    backend::link("gcc", &objects, &exe, &[]).unwrap();
+
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+    println!("✓ Separately compiled objects link into one executable");
+}
+
+// ==================== FAILURES ====================
+
+#[test]
+fn test_compile_errors_carry_invocation_status_and_stderr() {
+    let dir = TempDir::new().unwrap();
+    let c_file = write_c(&dir, "int main(void) { return missing; }\n");
+    let exe = dir.path().join("test");
+
+    let error = backend::compile_c("gcc", &c_file, &exe, &["-std=c99"]).unwrap_err();
+    let BackendError::CompileFailed { invocation, status, stderr } = &error else {
+        panic!("Expected CompileFailed, got {:?}", error);
+    };
+    assert_eq!(invocation, &vec![
+        "gcc".to_string(),
+        c_file.display().to_string(),
+        "-o".to_string(),
+        exe.display().to_string(),
+        "-std=c99".to_string(),
+    ]);
+    assert_eq!(*status, Some(1));
+    assert!(stderr.contains("missing"), "{}", stderr);
+
+    assert!(error.to_string().starts_with("C compilation failed (exit status 1): gcc "));
+    assert_eq!(error.code().unwrap().to_string(), "minilang::backend::compile_failed");
+    assert!(!exe.exists());
+    println!("✓ Failed C compiles report how gcc was run and what it said");
+}
+
+#[test]
+fn test_link_errors_name_the_objects() {
+    let dir = TempDir::new().unwrap();
+    let c_file = write_c(&dir, "int missing(void);\nint main(void) { return missing(); }\n");
+    let object = dir.path().join("test.o");
+    let exe = dir.path().join("test");
+
+    backend::compile_object("gcc", &c_file, &object, &GCC_FLAGS).unwrap();
+    let error = backend::link("gcc", &[&object], &exe, &[]).unwrap_err();
+    let BackendError::CompileFailed { invocation, stderr, .. } = &error else {
+        panic!("Expected CompileFailed, got {:?}", error);
+    };
+    assert_eq!(invocation, &vec![
+        "gcc".to_string(),
+        object.display().to_string(),
+        "-o".to_string(),
+        exe.display().to_string(),
+    ]);
+    assert!(stderr.contains("missing"), "{}", stderr);
+    println!("✓ Undefined symbols are reported as a failed link");
+}
+
+#[test]
+fn test_missing_compiler_is_a_spawn_error() {
+    let dir = TempDir::new().unwrap();
+    let c_file = write_c(&dir, "int main(void) { return 0; }\n");
+    let exe = dir.path().join("test");
+
+    let error = backend::compile_c("minilang-no-such-cc", &c_file, &exe, &[]).unwrap_err();
+    assert!(matches!(&error, BackendError::Spawn { invocation, .. } if invocation[0] == "minilang-no-such-cc"));
+    assert_eq!(error.code().unwrap().to_string(), "minilang::backend::spawn_failed");
+    println!("✓ A compiler that cannot be started is reported separately");
+}
+
+#[test]
+fn test_backend_errors_are_compiler_errors() {
+    let error = BackendError::CompileFailed {
+        invocation: vec!["gcc".to_string(), "a.c".to_string()],
+        status: None,
+        stderr: "boom".to_string(),
+    };
+    let compiler_error = CompilerError::from(error.clone());
+
+    assert!(matches!(&compiler_error, CompilerError::Backend(inner) if *inner == error));
+    assert_eq!(compiler_error.to_string(), "C compilation failed (killed by a signal): gcc a.c");
+    assert_eq!(compiler_error.help().unwrap().to_string(), "boom");
+    println!("✓ Backend errors convert into CompilerError");
+}
+
+// ==================== SAVED TEMPORARIES ====================
+
+#[test]
+fn test_save_temps_keeps_c_preprocessed_and_command() {
+    let dir = TempDir::new().unwrap();
+    let c_file = write_c(&dir, "#define ANSWER missing\nint main(void) { return ANSWER; }\n");
+    let exe = dir.path().join("test");
+    let error = backend::compile_c("gcc", &c_file, &exe, &GCC_FLAGS).unwrap_err();
+
+    let temps = dir.path().join("test-temps");
+    backend::save_temps(&temps, "gcc", &[&c_file], &GCC_FLAGS, &error).unwrap();
+
+    assert_eq!(fs::read_to_string(temps.join("test.c")).unwrap(), fs::read_to_string(&c_file).unwrap());
+    let preprocessed = fs::read_to_string(temps.join("test.i")).unwrap();
+    assert!(preprocessed.contains("return missing;"), "{}", preprocessed);
+    assert_eq!(
+        fs::read_to_string(temps.join("command.txt")).unwrap(),
+        format!("gcc {} -o {} -std=c99 -Wall -O2 -pthread\n", c_file.display(), exe.display()),
+    );
+    assert!(fs::read_to_string(temps.join("stderr.txt")).unwrap().contains("missing"));
+    println!("✓ --save-temps keeps the C, the preprocessed C, the command and gcc's output");
+}
+
+#[test]
+fn test_shell_command_quotes_when_needed() {
+    let invocation = ["gcc", "my file.c", "-o", "it's", "-O2", ""].map(String::from);
+    assert_eq!(backend::shell_command(&invocation), "gcc 'my file.c' -o 'it'\\''s' -O2 ''");
+    println!("✓ Saved command lines can be pasted into a shell");
+}
+
+// ==================== DRY RUN ====================
+
+#[test]
+fn test_invocations_match_what_runs() {
+    let dir = TempDir::new().unwrap();
+    let c_file = write_c(&dir, "int main(void) { return missing; }\n");
+    let exe = dir.path().join("test");
+    let error = backend::compile_c("gcc", &c_file, &exe, &GCC_FLAGS).unwrap_err();
+
+    let args = backend::compile_c_args(&c_file, &exe, &GCC_FLAGS);
+    assert_eq!(backend::invocation("gcc", &args), error.invocation());
+
+    let object = dir.path().join("test.o");
+    assert_eq!(
+        backend::invocation("gcc", &backend::compile_object_args(&c_file, &object, &[])),
+        ["gcc", "-c", &c_file.display().to_string(), "-o", &object.display().to_string()],
+    );
+    assert_eq!(
+        backend::invocation("gcc", &backend::link_args(&[&object], &exe, &[])),
+        ["gcc", &object.display().to_string(), "-o", &exe.display().to_string()],
+    );
+    println!("✓ Dry runs show the same command lines the backend runs");
+}
+
+#[test]
+fn test_script_builds_executable() {
+    let dir = TempDir::new().unwrap();
+    let c_file = write_c(&dir, "int main(void) { return 3; }\n");
+    let exe = dir.path().join("test");
+    let script = dir.path().join("build.sh");
+    let command = backend::invocation("gcc", &backend::compile_c_args(&c_file, &exe, &GCC_FLAGS));
+    backend::write_script(&script, &[command]).unwrap();
+
+    assert!(fs::read_to_string(&script).unwrap().starts_with("#!/bin/sh\nset -e\ngcc "));
+    assert!(Command::new("sh").arg(&script).status().unwrap().success());
+    assert_eq!(Command::new(&exe).status().unwrap().code(), Some(3));
+    println!("✓ --script writes commands that build the program");
+}
+
+// ==================== ERROR TRANSLATION ====================
+
+const SOURCE: &str = "func main() {\n    let total: int = 1;\n    display total;\n}\n";
+
+#[test]
+fn test_cc_errors_map_to_minilang_lines() {
+    let dir = TempDir::new().unwrap();
+    let c_file = write_c(&dir, "int main(void) {\n#line 2 \"prog.mini\"\n    int ml_total = 1;\n#line 3 \"prog.mini\"\n    return ml_totl;\n}\n");
+    let error = backend::compile_c("gcc", &c_file, &dir.path().join("test"), &GCC_FLAGS).unwrap_err();
+    let BackendError::CompileFailed { stderr, .. } = &error else {
+        panic!("Expected CompileFailed, got {:?}", error);
+    };
+
+    let diagnostics = backend::map_cc_diagnostics(stderr, "prog.mini", SOURCE).expect(stderr);
+    let line_3 = SOURCE.find("display").unwrap();
+    let error = diagnostics.iter()
+        .find(|diagnostic| matches!(diagnostic, CcDiagnostic::Error { .. }))
+        .unwrap();
+    let CcDiagnostic::Error { message, span } = error else { unreachable!() };
+    assert_eq!(span.offset(), line_3);
+    assert_eq!(span.len(), "display total;".len());
+    assert!(message.contains("totl") && !message.contains("ml_"), "{}", message);
+    assert_eq!(error.code().unwrap().to_string(), "minilang::backend::c_error");
+    println!("✓ gcc errors are reported on the MiniLang line that produced them");
+}
+
+#[test]
+fn test_unmapped_cc_errors_fall_back_to_raw_output() {
+    let warning_only = "prog.mini:2:9: warning: unused variable 'ml_total' [-Wunused-variable]\n";
+    assert_eq!(backend::map_cc_diagnostics(warning_only, "prog.mini", SOURCE), None);
+
+    let runtime = "prog.mini:3:5: error: bad\n/tmp/x/prog.c:12:1: error: also bad\n";
+    assert_eq!(backend::map_cc_diagnostics(runtime, "prog.mini", SOURCE), None);
+
+    let linker = "/usr/bin/ld: prog.o: undefined reference to `ml_other'\ncollect2: error: ld returned 1 exit status\n";
+    assert_eq!(backend::map_cc_diagnostics(linker, "prog.mini", SOURCE), None);
+
+    let past_the_end = "prog.mini:40:1: error: bad\n";
+    assert_eq!(backend::map_cc_diagnostics(past_the_end, "prog.mini", SOURCE), None);
+
+    let mapped = "prog.mini: In function 'main':\nprog.mini:2:9: warning: unused variable \u{2018}ml_total\u{2019}\nprog.mini:3:5: fatal error: stop\n";
+    let diagnostics = backend::map_cc_diagnostics(mapped, "prog.mini", SOURCE).unwrap();
+    assert!(matches!(&diagnostics[0], CcDiagnostic::Warning { message, .. } if message == "unused variable \u{2018}total\u{2019}"));
+    assert!(matches!(&diagnostics[1], CcDiagnostic::Error { message, .. } if message == "stop"));
+    println!("✓ Errors outside the MiniLang source leave gcc's output as it is");
+}
@@ -0,0 +1,118 @@
+// tests/banner_tests.rs - Project banners on top of generated C
+
+use minilang_compiler::banner::{Banner, TimestampPolicy};
+use minilang_compiler::cli::{find_manifest, project_banner};
+use minilang_compiler::{CodeGenerator, Lexer, Parser};
+use std::fs;
+use tempfile::TempDir;
+
+const SOURCE: &str = "func main() {\n    display \"archived\";\n}\n";
+
+const MANIFEST: &str = r#"[package]
+name = "lab1"
+version = "0.1.0"
+
+[build]
+opt-level = 1
+
+[banner]
+course = "CS 101 # Compilers"  # a comment
+student-id = "<your id>"
+license = "ends */ early"
+timestamp = "date"
+"#;
+
+fn banner() -> Banner {
+    Banner::from_manifest(MANIFEST).unwrap().unwrap()
+}
+
+// ==================== MANIFESTS ====================
+
+#[test]
+fn test_banner_read_from_manifest() {
+    let banner = banner();
+    assert_eq!(banner.course.as_deref(), Some("CS 101 # Compilers"));
+    assert_eq!(banner.student_id.as_deref(), Some("<your id>"));
+    assert_eq!(banner.license.as_deref(), Some("ends */ early"));
+    assert_eq!(banner.timestamp, TimestampPolicy::Date);
+    assert_eq!(banner.generated_at, None);
+
+    assert_eq!(Banner::from_manifest("[package]\nname = \"lab1\"\n"), Ok(None));
+    assert_eq!(Banner::from_manifest("[banner]\n"), Ok(Some(Banner::default())));
+    println!("✓ [banner] is read from minilang.toml, other tables are ignored");
+}
+
+#[test]
+fn test_bad_banner_tables_rejected() {
+    for (manifest, expected) in [
+        ("[banner]\ncourse = 101\n", "line 2: course must be a string"),
+        ("[banner]\ncolour = \"red\"\n", "line 2: unknown banner key 'colour'"),
+        ("[banner]\ntimestamp = \"always\"\n", "line 2: timestamp must be"),
+        ("[package]\nname\n", "line 2: expected `key = value`"),
+    ] {
+        let error = Banner::from_manifest(manifest).unwrap_err();
+        assert!(error.starts_with(expected), "{}: {}", manifest, error);
+    }
+    println!("✓ Mistakes in [banner] are errors naming their line");
+}
+
+#[test]
+fn test_banner_comment() {
+    let mut banner = banner();
+    let comment = banner.to_c_comment("lab1.mini");
+    assert_eq!(comment, format!(
+        "/*\n * Course: CS 101 # Compilers\n * Student ID: <your id>\n * License: ends * / early\n * Generated by minilang {} from lab1.mini\n */\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+
+    // 2024-02-29 13:05:09 UTC
+    banner.generated_at = Some(1_709_211_909);
+    assert!(banner.to_c_comment("lab1.mini").contains("from lab1.mini on 2024-02-29\n"));
+    banner.timestamp = TimestampPolicy::DateTime;
+    assert!(banner.to_c_comment("lab1.mini").contains("from lab1.mini on 2024-02-29 13:05 UTC\n"));
+    banner.timestamp = TimestampPolicy::None;
+    assert!(banner.to_c_comment("lab1.mini").contains("from lab1.mini\n"));
+    println!("✓ The banner is a C comment with the time its policy asks for");
+}
+
+// ==================== GENERATED CODE ====================
+
+#[test]
+fn test_banner_heads_generated_c() {
+    let tokens = Lexer::new(SOURCE).tokenize().unwrap();
+    let program = Parser::new(tokens, SOURCE.to_string()).parse_program().unwrap();
+    let mut plain_codegen = CodeGenerator::new().with_source("lab1.mini", SOURCE);
+    let plain = plain_codegen.generate(&program).unwrap();
+    let mut codegen = CodeGenerator::new().with_source("lab1.mini", SOURCE).with_banner(banner());
+    let c_code = codegen.generate(&program).unwrap();
+
+    let comment = banner().to_c_comment("lab1.mini");
+    assert_eq!(c_code, format!("{}{}", comment, plain));
+    // Line mappings count the banner's lines
+    let shift = comment.lines().count();
+    let shifted: Vec<_> = plain_codegen.source_map().iter().map(|entry| (entry.c_line + shift, entry.source_line)).collect();
+    let mapped: Vec<_> = codegen.source_map().iter().map(|entry| (entry.c_line, entry.source_line)).collect();
+    assert!(!mapped.is_empty());
+    assert_eq!(mapped, shifted);
+    println!("✓ Generated C starts with the banner");
+}
+
+#[test]
+fn test_project_banner_found_above_source() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("tests")).unwrap();
+    let source = dir.path().join("tests").join("hello.mini");
+    fs::write(&source, SOURCE).unwrap();
+    assert_eq!(project_banner(&source), Ok(None));
+
+    fs::write(dir.path().join("minilang.toml"), MANIFEST).unwrap();
+    assert_eq!(find_manifest(&source), Some(dir.path().join("minilang.toml")));
+    let banner = project_banner(&source).unwrap().unwrap();
+    assert_eq!(banner.course.as_deref(), Some("CS 101 # Compilers"));
+    // timestamp = "date" takes the time from SOURCE_DATE_EPOCH or the clock
+    assert!(banner.generated_at.is_some());
+
+    fs::write(dir.path().join("minilang.toml"), "[banner]\ncourse = 1\n").unwrap();
+    assert!(project_banner(&source).unwrap_err().contains("minilang.toml: line 2"));
+    println!("✓ The nearest minilang.toml decides the banner");
+}
@@ -0,0 +1,297 @@
+// tests/bounds_tests.rs - Bounds checks skipped for provably safe indexes
+
+use minilang_compiler::bounds;
+use minilang_compiler::{CodeGenerator, Lexer, Parser, Program, TypeChecker};
+use pretty_assertions::assert_eq;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn generate(source: &str, always_check: bool) -> String {
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    TypeChecker::new().check_program(&program).unwrap();
+    CodeGenerator::new()
+        .with_always_bounds_check(always_check)
+        .generate(&program)
+        .unwrap()
+}
+
+fn parse(source: &str) -> Program {
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    Parser::new(tokens, source.to_string()).parse_program().unwrap()
+}
+
+fn checks(c_code: &str) -> usize {
+    c_code.matches("CHECK_BOUNDS(").count() - 1  // minus the #define
+}
+
+fn run_c(c_code: &str) -> (String, String) {
+    let temp_dir = TempDir::new().unwrap();
+    let c_file = temp_dir.path().join("test.c");
+    let exe_file = temp_dir.path().join("test");
+    fs::write(&c_file, c_code).unwrap();
+
+    let output = Command::new("gcc")
+        .arg(&c_file)
+        .arg("-o")
+        .arg(&exe_file)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "GCC failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    let output = Command::new(&exe_file).output().unwrap();
+    (String::from_utf8_lossy(&output.stdout).to_string(), String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+// ==================== PROVABLY SAFE ====================
+
+#[test]
+fn test_constant_indexes_in_range_skip_checks() {
+    let source = r#"
+func main() {
+    const LAST: int = 2;
+    let xs: int[3] = [1, 2, 3];
+    display xs[0], xs[LAST], xs[LAST - 1];
+}
+"#;
+    let c_code = generate(source, false);
+    assert_eq!(checks(&c_code), 0, "{}", c_code);
+    assert!(c_code.contains("ml_xs[0]"), "{}", c_code);
+    println!("✓ Constant indexes in range skip bounds checks");
+}
+
+#[test]
+fn test_counting_loops_skip_checks() {
+    let source = r#"
+func main() {
+    let xs: int[10] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+    let total: int = 0;
+    for let i: int = 0; i < 10; i = i + 1 {
+        total = total + xs[i] + xs[9 - i] + xs[i % 3];
+    }
+    for let i: int = 0; i <= 8; i = i + 2 {
+        total = total + xs[i + 1] + xs[i / 2];
+        for let j: int = 0; j < i; j = j + 1 {
+            total = total + xs[j];
+        }
+    }
+    display total;
+}
+"#;
+    let c_code = generate(source, false);
+    assert_eq!(checks(&c_code), 0, "{}", c_code);
+    assert_eq!(run_c(&c_code).0, run_c(&generate(source, true)).0);
+    println!("✓ Counters of loops with constant limits skip bounds checks");
+}
+
+#[test]
+fn test_inferred_declarations_are_tracked() {
+    let source = r#"
+func main() {
+    let xs = [1, 2, 3, 4];
+    let last = 3;
+    let total = xs[last];
+    for let i = 0; i < 4; i = i + 1 {
+        total = total + xs[i];
+    }
+    display total;
+}
+"#;
+    let c_code = generate(source, false);
+    assert_eq!(checks(&c_code), 0, "{}", c_code);
+    assert_eq!(run_c(&c_code).0, "14\n");
+    println!("✓ Declarations without annotations get the same ranges");
+}
+
+#[test]
+fn test_if_conditions_narrow_ranges() {
+    let source = r#"
+func get(xs: int[4], n: int) -> int {
+    if n >= 0 AND n < 4 {
+        send xs[n];
+    }
+    if n < 0 OR n > 3 {
+        send -1;
+    } else {
+        send xs[n];
+    }
+    if NOT (n < 4) {
+        send 0;
+    }
+    send xs[n];
+}
+
+func main() {
+    let xs: int[4] = [10, 20, 30, 40];
+    display get(xs, 2), get(xs, 7), get(xs, -1);
+}
+"#;
+    let c_code = generate(source, false);
+    // Only the last access, guarded on one side only, needs a check
+    assert_eq!(checks(&c_code), 1, "{}", c_code);
+    assert_eq!(run_c(&c_code).0, "30-1-1\n");
+    println!("✓ Branches of an if know what their condition says");
+}
+
+// ==================== STILL CHECKED ====================
+
+#[test]
+fn test_reassigned_variables_are_not_narrowed() {
+    let source = r#"
+func main() {
+    let xs: int[4] = [1, 2, 3, 4];
+    let k: int = 2;
+    if k < 4 {
+        k = k + 5;
+        display xs[k];
+    }
+}
+"#;
+    let c_code = generate(source, false);
+    assert_eq!(checks(&c_code), 1, "{}", c_code);
+    println!("✓ Guards say nothing about variables assigned later");
+}
+
+#[test]
+fn test_unprovable_indexes_keep_checks() {
+    let source = r#"
+func sum(xs: int[4], n: int) -> int {
+    let total: int = 0;
+    for let i: int = 0; i < n; i = i + 1 {
+        total = total + xs[i];
+    }
+    send total + xs[n % 4];
+}
+
+func main() {
+    let xs: int[4] = [1, 2, 3, 4];
+    for let i: int = 0; i <= 4; i = i + 1 {
+        display xs[i % 4];
+        display xs[i - 1];
+    }
+    for let i: int = 0; i < 4; i = i + 1 {
+        i = i + 1;
+        display xs[i];
+    }
+    let k: int = 1;
+    k = 7;
+    display xs[k], xs[4], sum(xs, 4);
+}
+"#;
+    let c_code = generate(source, false);
+    // xs[i % 4] in the first loop of main is the only provable access
+    assert_eq!(checks(&c_code), 6, "{}", c_code);
+    println!("✓ Indexes that might be out of range keep their checks");
+}
+
+#[test]
+fn test_always_bounds_check_keeps_every_check() {
+    let source = r#"
+func main() {
+    let xs: int[3] = [1, 2, 3];
+    for let i: int = 0; i < 3; i = i + 1 {
+        display xs[i];
+    }
+    display xs[0];
+}
+"#;
+    assert_eq!(checks(&generate(source, false)), 0);
+    assert_eq!(checks(&generate(source, true)), 2);
+    println!("✓ --always-bounds-check keeps every check");
+}
+
+#[test]
+fn test_parameter_checks_use_declared_size() {
+    let source = r#"
+func peek(xs: int[3], at: int) -> int {
+    send xs[at];
+}
+
+func main() {
+    let xs: int[3] = [1, 2, 3];
+    display peek(xs, 5);
+}
+"#;
+    let c_code = generate(source, false);
+    assert!(c_code.contains("CHECK_BOUNDS(ml_at, 3)"), "{}", c_code);
+    let (_, stderr) = run_c(&c_code);
+    assert!(stderr.contains("Array index 5 out of bounds (size 3)"), "{}", stderr);
+    println!("✓ Array parameters are checked against their declared size");
+}
+
+#[test]
+fn test_sizes_follow_arrays_through_calls() {
+    let source = r#"
+func last(xs: float[4]) -> float {
+    send xs[3];
+}
+
+func nth(xs: float[4], at: int) -> float {
+    send xs[at] + last(xs);
+}
+
+func main() {
+    let xs: float[4] = [0.5, 1.5, 2.5, 3.5];
+    display nth(xs, 1);
+    display nth(xs, 4);
+}
+"#;
+    let c_code = generate(source, false);
+    assert!(c_code.contains("CHECK_BOUNDS(ml_at, 4)"), "{}", c_code);
+    assert!(!c_code.contains(", 10)"), "{}", c_code);
+    let (stdout, stderr) = run_c(&c_code);
+    assert_eq!(stdout, "5.000000\n");
+    assert!(stderr.contains("Array index 4 out of bounds (size 4)"), "{}", stderr);
+    println!("✓ Callees check against the size carried by the parameter type");
+}
+
+// ==================== DECIDED COMPARISONS ====================
+
+#[test]
+fn test_comparisons_decided_by_ranges() {
+    let source = r#"
+func f(n: int, x: float) {
+    for let i: int = 0; i < 6; i = i + 1 {
+        display i < 10, i >= 0, i == 8, i != -1, i < 3, n < 10;
+    }
+    let m: int = n % 5;
+    display m < 5, m > -5, m == 0, x < 1.0;
+    if n > 100 {
+        display n > 50, n < 100, n == 200;
+    }
+}
+"#;
+    let mut program = parse(source);
+    program.number_nodes();
+    let facts = bounds::analyze(&program);
+
+    let mut decided = Vec::new();
+    program.functions[0].body.statements.iter().for_each(|stmt| collect_decided(stmt, &facts, &mut decided));
+    assert_eq!(decided, vec![
+        "i < 10 = true", "i >= 0 = true", "i == 8 = false", "i != -1 = true",
+        "m < 5 = true", "m > -5 = true",
+        "n > 50 = true", "n < 100 = false",
+    ]);
+    println!("✓ Comparisons are decided from loop counters, remainders and guards");
+}
+
+fn collect_decided(stmt: &minilang_compiler::ast::Statement, facts: &bounds::RangeFacts, out: &mut Vec<String>) {
+    use minilang_compiler::ast::{Expression, Statement};
+    match stmt {
+        Statement::Display(display) => {
+            for expr in &display.expressions {
+                if let Expression::Binary(binary) = expr {
+                    if let Some(result) = facts.comparisons.get(&binary.id) {
+                        out.push(format!("{} = {}", expr, result));
+                    }
+                }
+            }
+        }
+        Statement::For(for_stmt) => for_stmt.body.statements.iter().for_each(|s| collect_decided(s, facts, out)),
+        Statement::If(if_stmt) => if_stmt.then_block.statements.iter().for_each(|s| collect_decided(s, facts, out)),
+        _ => {}
+    }
+}
@@ -0,0 +1,69 @@
+// tests/build_log_tests.rs - Build log records
+
+use minilang_compiler::build_log::{read_log, sha256_hex, BuildRecord, BuildStatus};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[test]
+fn test_sha256_hex() {
+    assert_eq!(
+        sha256_hex(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    println!("✓ Hashes are hex SHA-256");
+}
+
+#[test]
+fn test_records_append_as_json_lines() {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("build.jsonl");
+    let exe = dir.path().join("main");
+    fs::write(&exe, b"binary").unwrap();
+    
+    let mut ok = BuildRecord::new(Path::new("main.mini"), "func main() { }", "compile", 1);
+    ok.record_stage("lex", Duration::from_millis(2));
+    ok.add_artifact("executable", &exe);
+    ok.add_artifact("c", &dir.path().join("missing.c"));
+    ok.append_to(&log).unwrap();
+    
+    let mut failed = BuildRecord::new(Path::new("bad.mini"), "func", "run", 0);
+    failed.fail("parse", 1);
+    failed.append_to(&log).unwrap();
+    
+    let text = fs::read_to_string(&log).unwrap();
+    assert_eq!(text.lines().count(), 2);
+    
+    let records = read_log(&log).unwrap();
+    assert_eq!(records, vec![ok.clone(), failed]);
+    
+    let first = &records[0];
+    assert_eq!(first.status, BuildStatus::Success);
+    assert_eq!(first.source_sha256, sha256_hex(b"func main() { }"));
+    assert_eq!(first.stages[0].stage, "lex");
+    assert_eq!(first.artifacts.len(), 1, "unreadable artifacts are skipped");
+    assert_eq!(first.artifacts[0].sha256, sha256_hex(b"binary"));
+    
+    assert_eq!(records[1].status, BuildStatus::Failed { stage: "parse".to_string() });
+    assert_eq!(records[1].errors, 1);
+    
+    println!("✓ Build records round-trip through the log");
+}
+
+#[test]
+fn test_status_json_shape() {
+    let mut record = BuildRecord::new(Path::new("a.mini"), "", "to-c", 2);
+    assert!(serde_json::to_string(&record).unwrap().contains(r#""status":{"result":"success"}"#));
+    
+    record.fail("type_check", 3);
+    let json = serde_json::to_string(&record).unwrap();
+    assert!(json.contains(r#""status":{"result":"failed","stage":"type_check"}"#));
+    assert!(json.contains(r#""errors":3"#));
+    
+    record.interrupt("cc");
+    let json = serde_json::to_string(&record).unwrap();
+    assert!(json.contains(r#""status":{"result":"interrupted","stage":"cc"}"#));
+    
+    println!("✓ Status serializes with a result tag");
+}
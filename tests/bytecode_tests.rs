@@ -0,0 +1,482 @@
+// tests/bytecode_tests.rs - `compile --target=bytecode` and `minilang vm`
+//
+// The VM has to behave like the native program, so where a C compiler is
+// available the same programs are also compiled to C and their output
+// compared.
+
+use minilang_compiler::bytecode::vm::{self, Clock};
+use minilang_compiler::bytecode::{self, BytecodeCompiler, Instruction, Module};
+use minilang_compiler::optimizer::OverflowMode;
+use minilang_compiler::test_runner::TestRunner;
+use minilang_compiler::test_utils::compile_and_run;
+use minilang_compiler::{BytecodeError, Lexer, Optimizer, Parser, TypeChecker};
+use std::io;
+
+const PROGRAM: &str = r#"func fib(n: int) -> int {
+    if n < 2 { send n; }
+    send fib(n - 1) + fib(n - 2);
+}
+
+func fill(xs: int[4]) {
+    for let i: int = 0; i < 4; i = i + 1 {
+        xs[i] = i * i;
+    }
+}
+
+func split(x: int) -> (int, string) {
+    defer { display "leaving split"; }
+    if x > 2 {
+        send (x, "big");
+    }
+    send (x, "small");
+}
+
+func main() {
+    defer { display "bye"; }
+    let xs: int[4];
+    fill(xs);
+    display xs;
+    let (n, size) = split(3);
+    display n, size;
+    display (1, 2.5, true);
+    display "fib=", fib(15);
+    display format("x={} y={}", 3, 4.5);
+    let i: int = 0;
+    while true {
+        i = i + 1;
+        if i == 3 { continue; }
+        if i > 5 { break; }
+        display i;
+    }
+    do {
+        i = i - 2;
+    } while i > 0;
+    display "i={i}";
+    display parse_int("-42") + 1, " ", to_string(7), " ", approx_equal(0.1 + 0.2, 0.3, 0.0001);
+    let big: int = 2147483647;
+    display big + 1, " ", NOT (i < 0 AND true);
+}
+"#;
+
+const EXPECTED: &str = "[0, 1, 4, 9]
+leaving split
+3big
+(1, 2.500000, true)
+fib=610
+x=3 y=4.500000
+1
+2
+4
+5
+i=0
+-41 7 true
+-2147483648 true
+bye
+";
+
+fn compile(source: &str, level: u8, overflow: OverflowMode) -> Module {
+    let tokens = Lexer::new(source).tokenize().expect("lexing failed");
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().expect("parsing failed");
+    if let Err(errors) = TypeChecker::new().check_program(&program) {
+        panic!("type checking failed: {:?}", errors);
+    }
+    if level > 0 {
+        Optimizer::new(level).with_overflow(overflow).optimize(&mut program);
+    }
+    BytecodeCompiler::new()
+        .with_source("test.mini", source)
+        .with_overflow(overflow)
+        .compile(&program)
+        .expect("bytecode generation failed")
+}
+
+/// What the program printed and how it ended
+fn run(module: &Module) -> (String, Result<i32, vm::Trap>) {
+    let mut out = Vec::new();
+    let result = vm::run(module, &mut out);
+    (String::from_utf8(out).unwrap(), result)
+}
+
+// ==================== VM TESTS ====================
+
+#[test]
+fn test_vm_runs_programs() {
+    for level in [0, 2] {
+        let (stdout, result) = run(&compile(PROGRAM, level, OverflowMode::Wrap));
+        assert_eq!(stdout, EXPECTED, "at -O{}", level);
+        assert_eq!(result, Ok(0));
+    }
+    println!("✓ The VM runs calls, arrays, tuples, loops, defers and builtins");
+}
+
+#[test]
+fn test_vm_matches_native_output() {
+    for level in [0, 2] {
+        let Some(native) = compile_and_run(PROGRAM, level) else { return };
+        let (stdout, result) = run(&compile(PROGRAM, level, OverflowMode::Wrap));
+        assert_eq!(stdout, native.stdout, "at -O{}", level);
+        assert_eq!(result.ok(), native.exit_code);
+    }
+    println!("✓ The VM prints what the native program prints");
+}
+
+#[test]
+fn test_vm_exit_code_is_mains_result() {
+    let source = "func main() -> int {\n    display \"done\";\n    send 3;\n}\n";
+    let (stdout, result) = run(&compile(source, 0, OverflowMode::Wrap));
+    assert_eq!(stdout, "done\n");
+    assert_eq!(result, Ok(3));
+    println!("✓ main's return value is the exit code");
+}
+
+#[test]
+fn test_vm_runtime_errors() {
+    let source = "func main() {\n    let xs: int[3] = [1, 2, 3];\n    let i: int = 1;\n    display xs[i];\n    i = i + 5;\n    display xs[i];\n}\n";
+    let (stdout, result) = run(&compile(source, 0, OverflowMode::Wrap));
+    assert_eq!(stdout, "2\n");
+    let trap = result.unwrap_err();
+    assert_eq!(trap.to_string(), "Runtime Error: Array index 6 out of bounds (size 3)\n  at test.mini:6");
+    assert_eq!(trap.exit_code, 1);
+
+    let source = "func main() {\n    let zero: int = 0;\n    display 7 / zero;\n}\n";
+    let trap = run(&compile(source, 0, OverflowMode::Wrap)).1.unwrap_err();
+    assert_eq!(trap.to_string(), "Runtime Error: arithmetic error (division by zero)\n  at test.mini:3");
+    assert_eq!(trap.exit_code, 136);
+
+    let source = "func main() {\n    display parse_int(\"12x\");\n}\n";
+    let trap = run(&compile(source, 0, OverflowMode::Wrap)).1.unwrap_err();
+    assert_eq!(trap.message, "cannot parse \"12x\" as int");
+
+    let source = "func down(n: int) -> int {\n    send down(n + 1) + 1;\n}\n\nfunc main() {\n    display down(0);\n}\n";
+    let trap = run(&compile(source, 0, OverflowMode::Wrap)).1.unwrap_err();
+    assert_eq!(trap.exit_code, 139);
+    println!("✓ Runtime errors stop the VM like the native program");
+}
+
+#[test]
+fn test_vm_overflow_modes() {
+    let source = "func main() {\n    let big: int = 2147483647;\n    let one: int = 1;\n    display big + one;\n}\n";
+    assert_eq!(run(&compile(source, 0, OverflowMode::Wrap)).0, "-2147483648\n");
+    let trap = run(&compile(source, 0, OverflowMode::Trap)).1.unwrap_err();
+    assert_eq!(trap.message, "integer overflow");
+    assert_eq!(trap.exit_code, 134);
+    println!("✓ --overflow=trap carries over to the VM");
+}
+
+#[test]
+fn test_vm_checked_arithmetic() {
+    let source = "func main() {\n    let big: int = 2147483647;\n    display add_checked(big, 1), \" \", sub_checked(-5, 3), \" \", mul_checked(65536, 65536);\n}\n";
+    for overflow in [OverflowMode::Wrap, OverflowMode::Trap] {
+        let (stdout, result) = run(&compile(source, 0, overflow));
+        assert_eq!(stdout, "(-2147483648, true) (-8, false) (0, true)\n");
+        assert_eq!(result, Ok(0));
+    }
+    println!("✓ Checked arithmetic reports overflow instead of trapping");
+}
+
+#[test]
+fn test_vm_failed_assert() {
+    let source = "func main() {\n    let n: int = 3;\n    assert(n > 1);\n    assert(n > 5, \"n is {n}\");\n    assert(n == 4);\n}\n";
+    let trap = run(&compile(source, 0, OverflowMode::Wrap)).1.unwrap_err();
+    assert_eq!(trap.to_string(), "Runtime Error: assertion failed: n is 3\n  at test.mini:4");
+    
+    let source = source.replace(", \"n is {n}\"", "").replace("n > 5", "n > 1");
+    let trap = run(&compile(&source, 2, OverflowMode::Wrap)).1.unwrap_err();
+    assert_eq!(trap.message, "assertion failed: n == 4");
+    assert_eq!(trap.exit_code, 1);
+    println!("✓ A failed assert stops the VM with its message or condition");
+}
+
+#[test]
+fn test_vm_memo_functions() {
+    let source = "@memo\npure func fib(n: int) -> int {\n    if n < 2 { send n; }\n    send fib(n - 1) + fib(n - 2);\n}\n\nfunc main() {\n    display fib(60 - 15);\n}\n";
+    for level in [0, 2] {
+        let module = compile(source, level, OverflowMode::Wrap);
+        assert!(module.functions.iter().any(|function| function.memo));
+        assert_eq!(Module::decode(&module.encode()), Ok(module.clone()));
+        let (stdout, result) = run(&module);
+        assert_eq!(stdout, "1134903170\n", "at -O{}", level);
+        assert_eq!(result, Ok(0));
+    }
+    println!("✓ The VM caches @memo results");
+}
+
+#[test]
+fn test_vm_canvas() {
+    // A blinker from the Game of Life, one generation on
+    let source = r#"func alive(cells: int[25], x: int, y: int) -> int {
+    if x < 0 OR x > 4 OR y < 0 OR y > 4 { send 0; }
+    send cells[y * 5 + x];
+}
+
+func main() {
+    let cells: int[25];
+    for let x: int = 1; x < 4; x = x + 1 {
+        cells[2 * 5 + x] = 1;
+    }
+    let board: canvas = canvas(5, 5);
+    canvas_fill(board, ".");
+    for let y: int = 0; y < 5; y = y + 1 {
+        for let x: int = 0; x < 5; x = x + 1 {
+            let around: int = 0 - alive(cells, x, y);
+            for let dy: int = -1; dy <= 1; dy = dy + 1 {
+                for let dx: int = -1; dx <= 1; dx = dx + 1 {
+                    around = around + alive(cells, x + dx, y + dy);
+                }
+            }
+            if around == 3 OR (around == 2 AND alive(cells, x, y) == 1) {
+                canvas_set(board, x, y, "█");
+            }
+        }
+    }
+    canvas_render(board);
+}
+"#;
+    for level in [0, 2] {
+        let module = compile(source, level, OverflowMode::Wrap);
+        assert_eq!(Module::decode(&module.encode()).unwrap(), module);
+        let (stdout, result) = run(&module);
+        assert_eq!(stdout, ".....\n..█..\n..█..\n..█..\n.....\n", "at -O{}", level);
+        assert_eq!(result, Ok(0));
+        if let Some(native) = compile_and_run(source, level) {
+            assert_eq!(stdout, native.stdout, "at -O{}", level);
+        }
+    }
+    
+    let source = "func main() {\n    let c: canvas = canvas(3, 2);\n    canvas_set(c, 1, 2, \"x\");\n}\n";
+    let trap = run(&compile(source, 0, OverflowMode::Wrap)).1.unwrap_err();
+    assert_eq!(trap.to_string(), "Runtime Error: cell (1, 2) is outside the 3x2 canvas\n  at test.mini:3");
+    let source = "func main() {\n    let c: canvas = canvas(3, 2);\n    canvas_fill(c, \"ab\");\n}\n";
+    assert_eq!(run(&compile(source, 0, OverflowMode::Wrap)).1.unwrap_err().message, "a canvas cell holds one character, not \"ab\"");
+    let source = "func main() {\n    let c: canvas = canvas(0, 2);\n}\n";
+    assert_eq!(run(&compile(source, 0, OverflowMode::Wrap)).1.unwrap_err().message, "a canvas must be at least 1x1, not 0x2");
+    println!("✓ Canvases draw and render like the native program");
+}
+
+#[test]
+fn test_vm_json() {
+    // Grades from a document, summed up and written back
+    let source = r#"func average(scores: json) -> float {
+    let sum: float = 0.0;
+    let count: float = 0.0;
+    for let i: int = 0; i < json_len(scores); i = i + 1 {
+        sum = sum + json_float(json_at(scores, i));
+        count = count + 1.0;
+    }
+    send sum / count;
+}
+
+func main() {
+    let students: json = json {"list": [{"name": "Ann", "scores": [90, 85.5]}, {"name": "Bo", "scores": [70, 100]}]};
+    students = json_get(students, "list");
+    json_push(json_get(json_at(students, 1), "scores"), json_parse("-1e-1"));
+    json_set(json_at(students, 1), "name", json_string(json_parse("\"Bo \\u00e9\"")));
+    let report: json = json {"class": "CS 101", "results": []};
+    for let i: int = 0; i < json_len(students); i = i + 1 {
+        let student: json = json_at(students, i);
+        let result: json = json {"passed": false};
+        json_set(result, "name", json_string(json_get(student, "name")));
+        json_set(result, "average", average(json_get(student, "scores")));
+        json_set(result, "passed", json_float(json_get(result, "average")) >= 80.0);
+        json_push(json_get(report, "results"), result);
+    }
+    json_set(report, "count", json_len(json_get(report, "results")));
+    display json_stringify(report);
+    display json_type(json_get(report, "count")), json_has(report, "missing");
+}
+"#;
+    for level in [0, 2] {
+        let module = compile(source, level, OverflowMode::Wrap);
+        assert_eq!(Module::decode(&module.encode()).unwrap(), module);
+        let (stdout, result) = run(&module);
+        assert_eq!(
+            stdout,
+            "{\"class\":\"CS 101\",\"results\":[{\"passed\":true,\"name\":\"Ann\",\"average\":87.75},{\"passed\":false,\"name\":\"Bo é\",\"average\":56.63333333333333}],\"count\":2}\nnumberfalse\n",
+            "at -O{}", level,
+        );
+        assert_eq!(result, Ok(0));
+        if let Some(native) = compile_and_run(source, level) {
+            assert_eq!(stdout, native.stdout, "at -O{}", level);
+        }
+    }
+    
+    let source = "func main() {\n    let j: json = json_parse(\"[1, 2,]\");\n}\n";
+    let trap = run(&compile(source, 0, OverflowMode::Wrap)).1.unwrap_err();
+    assert_eq!(trap.to_string(), "Runtime Error: invalid json at byte 6\n  at test.mini:2");
+    let source = "func main() {\n    let j: json = json {\"a\": 2.5};\n    display json_int(json_get(j, \"a\"));\n}\n";
+    assert_eq!(run(&compile(source, 0, OverflowMode::Wrap)).1.unwrap_err().message, "json number 2.5 is not an int");
+    println!("✓ Json values are read, changed and written like the native program");
+}
+
+#[test]
+fn test_vm_watches_variables() {
+    let source = r#"func fill(xs: int[2]) {
+    xs[1] = 7;
+}
+
+func main() {
+    let xs: int[2];
+    let total: int = 1;
+    fill(xs);
+    total = total + xs[1];
+    display total;
+    let label: string = "done";
+}
+"#;
+    let module = compile(source, 0, OverflowMode::Wrap);
+    assert_eq!(module.functions[0].local_names, ["xs"]);
+    
+    let watched = ["total".to_string(), "xs".to_string(), "label".to_string()];
+    let (mut out, mut log) = (Vec::new(), Vec::new());
+    assert_eq!(vm::run_watching(&module, &mut out, &watched, &mut log), Ok(0));
+    assert_eq!(String::from_utf8(out).unwrap(), "8\n");
+    assert_eq!(String::from_utf8(log).unwrap(), "\
+watch: test.mini:6 in main: xs = [0, 0]
+watch: test.mini:7 in main: total = 1
+watch: test.mini:2 in fill: xs[1] = 7 (was 0)
+watch: test.mini:9 in main: total = 8 (was 1)
+watch: test.mini:11 in main: label = \"done\"
+");
+    
+    // Watching nothing, or a name no variable has, changes nothing
+    let (stdout, result) = run(&module);
+    let mut log = Vec::new();
+    assert_eq!(vm::run_watching(&module, &mut Vec::new(), &["missing".to_string()], &mut log), result);
+    assert_eq!(stdout, "8\n");
+    assert!(log.is_empty());
+    println!("✓ --watch-var reports each write with its line and the old value");
+}
+
+#[test]
+fn test_clock() {
+    let source = r#"
+func main() {
+    let start: int = now_ms();
+    sleep_ms(30);
+    sleep_ms(-5);
+    display now_ms() - start;
+}
+"#;
+    let module = compile(source, 2, OverflowMode::Wrap);
+    assert_eq!(Module::decode(&module.encode()).unwrap(), module);
+    let mut out = Vec::new();
+    assert_eq!(vm::run_with_clock(&module, &mut out, Clock::Virtual, &[], &mut io::sink()), Ok(0));
+    assert_eq!(String::from_utf8(out).unwrap(), "30\n");
+    
+    // The real clock waits at least as long
+    let (stdout, result) = run(&module);
+    assert_eq!(result, Ok(0));
+    assert!(stdout.trim().parse::<i32>().unwrap() >= 30, "{}", stdout);
+    println!("✓ The virtual clock moves by exactly what sleep_ms asks for");
+}
+
+#[test]
+fn test_runner_runs_each_test() {
+    let source = r#"func double(x: int) -> int {
+    send x * 2;
+}
+
+test "doubles" {
+    display "running";
+    assert(double(4) == 8);
+}
+
+test "fails" {
+    assert(double(2) == 5, "double(2) is {double(2)}");
+}
+
+test "timed" {
+    sleep_ms(1000);
+    display now_ms();
+}
+
+func main() {
+    display double(1);
+}
+"#;
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    assert_eq!(program.tests.len(), 3);
+    program.include_tests();
+    TypeChecker::new().check_program(&program).unwrap();
+    
+    for level in [0, 2] {
+        let runner = TestRunner::new().with_source("test.mini", source).with_optimization(level);
+        let passed = runner.run(&program, "doubles").unwrap();
+        assert!(passed.passed(), "at -O{}: {:?}", level, passed.failure);
+        assert_eq!(passed.output, "running\n");
+        
+        // On the virtual clock, so tests that time things are repeatable
+        let timed = runner.run(&program, "timed").unwrap();
+        assert_eq!(timed.output, "1000\n");
+        
+        let failed = runner.run(&program, "fails").unwrap();
+        assert_eq!(failed.failure.unwrap().to_string(), "Runtime Error: assertion failed: double(2) is 4\n  at test.mini:11");
+        assert!(runner.run(&program, "missing").is_err());
+    }
+    println!("✓ Each test block runs on its own as the entry point");
+}
+
+#[test]
+fn test_compile_requires_local_functions() {
+    let source = "func main() {\n    display helper(1);\n}\n";
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let error = BytecodeCompiler::new().compile(&program).unwrap_err();
+    assert!(error.contains("'helper' is not defined in this file"), "{}", error);
+    println!("✓ Calls to other modules are reported at compile time");
+}
+
+#[test]
+fn test_compile_rejects_threads() {
+    for source in [
+        "func main() {\n    spawn { display 1; }\n}\n",
+        "func main() {\n    let c: chan = channel(1);\n}\n",
+        "func main() {\n    parallel for i in 0..4 { display i; }\n}\n",
+        "func main() {\n    let a: atomic = atomic(1);\n}\n",
+    ] {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+        let error = BytecodeCompiler::new().compile(&program).unwrap_err();
+        assert!(error.contains("only the C target"), "{}", error);
+    }
+    println!("✓ Spawn blocks, parallel for loops, channels and atomics are reported at compile time");
+}
+
+// ==================== FILE FORMAT TESTS ====================
+
+#[test]
+fn test_module_round_trips() {
+    let module = compile(PROGRAM, 2, OverflowMode::Wrap);
+    let bytes = module.encode();
+    assert!(bytes.starts_with(bytecode::MAGIC));
+    let decoded = Module::decode(&bytes).unwrap();
+    assert_eq!(decoded, module);
+    assert_eq!(decoded.source_name, "test.mini");
+    assert_eq!(run(&decoded).0, EXPECTED);
+
+    let trapping = Module { trap_overflow: true, stamp: Some("minilang-stamp {}".to_string()), ..module };
+    assert_eq!(Module::decode(&trapping.encode()).unwrap(), trapping);
+    println!("✓ Modules survive encoding and decoding");
+}
+
+#[test]
+fn test_decode_rejects_bad_files() {
+    assert!(matches!(Module::decode(b"\x7fELF"), Err(BytecodeError::NotBytecode)));
+
+    let mut bytes = compile(PROGRAM, 0, OverflowMode::Wrap).encode();
+    bytes[4] = 99;
+    assert!(matches!(Module::decode(&bytes), Err(BytecodeError::UnsupportedVersion { found: 99, .. })));
+
+    let bytes = compile(PROGRAM, 0, OverflowMode::Wrap).encode();
+    for cut in [10, bytes.len() / 2, bytes.len() - 1] {
+        assert!(matches!(Module::decode(&bytes[..cut]), Err(BytecodeError::Corrupt { .. })), "cut at {}", cut);
+    }
+
+    // An index the VM would trust is checked up front
+    let mut module = compile("func main() {\n    display 1;\n}\n", 0, OverflowMode::Wrap);
+    module.functions[0].code.insert(0, Instruction::Jump(1000));
+    let error = Module::decode(&module.encode()).unwrap_err();
+    assert!(error.to_string().contains("out of range"), "{}", error);
+    println!("✓ Damaged and foreign files are rejected");
+}
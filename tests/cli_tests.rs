@@ -0,0 +1,663 @@
+// tests/cli_tests.rs - Command line input handling
+
+use minilang_compiler::cli::{expand_inputs, init_project, install_pre_commit_hook, parse_define, pre_commit_hook_script};
+use minilang_compiler::Literal;
+use minilang_compiler::{Lexer, Parser, TypeChecker};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Create a tree of empty files under a temporary directory
+fn make_tree(files: &[&str]) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    for file in files {
+        let path = dir.path().join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "func main() { }").unwrap();
+    }
+    dir
+}
+
+/// Paths relative to the tree root, for readable assertions
+fn relative(dir: &Path, files: Vec<PathBuf>) -> Vec<String> {
+    files
+        .iter()
+        .map(|file| file.strip_prefix(dir).unwrap().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn test_expand_directory_finds_mini_files() {
+    let dir = make_tree(&["a.mini", "notes.txt", "lib/b.mini", "lib/deep/c.mini"]);
+    
+    let files = expand_inputs(&[dir.path().to_path_buf()]);
+    assert_eq!(relative(dir.path(), files), vec!["a.mini", "lib/b.mini", "lib/deep/c.mini"]);
+    
+    println!("✓ Directories expand to their .mini files");
+}
+
+#[test]
+fn test_expand_glob_patterns() {
+    let dir = make_tree(&["a.mini", "b.txt", "src/x.mini", "src/y.mini", "src/nested/z.mini"]);
+    let root = dir.path().to_string_lossy().into_owned();
+    
+    let top = expand_inputs(&[PathBuf::from(format!("{}/*.mini", root))]);
+    assert_eq!(relative(dir.path(), top), vec!["a.mini"]);
+    
+    let recursive = expand_inputs(&[PathBuf::from(format!("{}/src/**/*.mini", root))]);
+    assert_eq!(relative(dir.path(), recursive), vec!["src/nested/z.mini", "src/x.mini", "src/y.mini"]);
+    
+    let single_char = expand_inputs(&[PathBuf::from(format!("{}/src/?.mini", root))]);
+    assert_eq!(relative(dir.path(), single_char), vec!["src/x.mini", "src/y.mini"]);
+    
+    println!("✓ Glob patterns match files under their prefix");
+}
+
+#[test]
+fn test_expand_keeps_plain_paths_and_dedups() {
+    let dir = make_tree(&["a.mini", "b.mini"]);
+    let a = dir.path().join("a.mini");
+    let missing = dir.path().join("missing.mini");
+    
+    let files = expand_inputs(&[a.clone(), dir.path().to_path_buf(), missing.clone()]);
+    assert_eq!(files, vec![a, dir.path().join("b.mini"), missing]);
+    
+    println!("✓ Plain paths are kept in order without duplicates");
+}
+
+// ==================== DEFINE TESTS ====================
+
+#[test]
+fn test_parse_define_values() {
+    assert_eq!(parse_define("DEBUG"), Ok(("DEBUG".to_string(), Literal::Boolean(true))));
+    assert_eq!(parse_define("TRACE=false"), Ok(("TRACE".to_string(), Literal::Boolean(false))));
+    assert_eq!(parse_define("LEVEL=2"), Ok(("LEVEL".to_string(), Literal::Integer(2))));
+    assert_eq!(parse_define("RATIO=0.5"), Ok(("RATIO".to_string(), Literal::Float(0.5))));
+    assert_eq!(parse_define("MODE=fast"), Ok(("MODE".to_string(), Literal::String("fast".to_string()))));
+    assert_eq!(parse_define("MODE=\"a b\""), Ok(("MODE".to_string(), Literal::String("a b".to_string()))));
+}
+
+#[test]
+fn test_parse_define_rejects_bad_names() {
+    assert!(parse_define("=1").is_err());
+    assert!(parse_define("2FAST=1").is_err());
+    assert!(parse_define("MY-FLAG").is_err());
+}
+
+// ==================== PRE-COMMIT HOOK TESTS ====================
+
+#[test]
+fn test_hook_script_checks_staged_files() {
+    let script = pre_commit_hook_script();
+    
+    assert!(script.starts_with("#!/bin/sh\n"));
+    assert!(script.contains("git diff --cached"));
+    assert!(script.contains("\"$MINILANG\" check"));
+    assert!(!script.contains("lint") && !script.contains("fmt"));
+    
+    println!("✓ Hook script checks staged .mini files");
+}
+
+#[test]
+fn test_hook_install_writes_and_reinstalls() {
+    let dir = TempDir::new().unwrap();
+    let hooks = dir.path().join("hooks");
+    
+    let path = install_pre_commit_hook(&hooks, false).unwrap();
+    assert_eq!(path, hooks.join("pre-commit"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), pre_commit_hook_script());
+    
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0o111, "hook should be executable");
+    }
+    
+    // Our own hook can be replaced without --force
+    assert!(install_pre_commit_hook(&hooks, false).is_ok());
+    
+    println!("✓ Hook is installed and can be reinstalled");
+}
+
+#[test]
+fn test_hook_install_keeps_foreign_hook() {
+    let dir = TempDir::new().unwrap();
+    let hook = dir.path().join("pre-commit");
+    fs::write(&hook, "#!/bin/sh\necho custom\n").unwrap();
+    
+    let err = install_pre_commit_hook(dir.path(), false).unwrap_err();
+    assert!(err.contains("--force"));
+    assert_eq!(fs::read_to_string(&hook).unwrap(), "#!/bin/sh\necho custom\n");
+    
+    install_pre_commit_hook(dir.path(), true).unwrap();
+    assert_eq!(fs::read_to_string(&hook).unwrap(), pre_commit_hook_script());
+    
+    println!("✓ Existing hooks are only replaced with --force");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hook_checks_staged_content() {
+    use std::process::Command;
+    
+    let dir = TempDir::new().unwrap();
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(dir.path()).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    };
+    git(&["init", "--quiet"]);
+    let hook = install_pre_commit_hook(&dir.path().join(".git").join("hooks"), false).unwrap();
+    let run_hook = || {
+        Command::new(&hook)
+            .current_dir(dir.path())
+            .env("MINILANG", env!("CARGO_BIN_EXE_minilang"))
+            .output()
+            .unwrap()
+    };
+    let good = "func main() {\n    display 1;\n}\n";
+    let bad = "func main() {\n    let x: int = \"one\";\n}\n";
+    let file = dir.path().join("main.mini");
+    
+    // A broken file fixed only in the working tree is still broken in the commit
+    fs::write(&file, bad).unwrap();
+    git(&["add", "main.mini"]);
+    fs::write(&file, good).unwrap();
+    let output = run_hook();
+    assert!(!output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("main.mini"));
+    
+    // And a good staged file passes whatever the working tree holds
+    git(&["add", "main.mini"]);
+    fs::write(&file, bad).unwrap();
+    let output = run_hook();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    
+    println!("✓ Hook checks what is staged, not the working tree");
+}
+
+// ==================== PROJECT INIT TESTS ====================
+
+#[test]
+fn test_init_creates_project() {
+    let dir = TempDir::new().unwrap();
+    let project = dir.path().join("my_game");
+    
+    let files = init_project(&project).unwrap();
+    assert_eq!(relative(&project, files), vec![
+        "minilang.toml",
+        "main.mini",
+        "tests/hello.mini",
+        "tests/hello.expected",
+        ".gitignore",
+    ]);
+    
+    let manifest = fs::read_to_string(project.join("minilang.toml")).unwrap();
+    assert!(manifest.contains("name = \"my_game\""));
+    assert!(manifest.contains("entry = \"main.mini\""));
+    assert!(fs::read_to_string(project.join(".gitignore")).unwrap().contains("*.c"));
+    
+    println!("✓ init scaffolds a project");
+}
+
+#[test]
+fn test_init_sources_compile() {
+    let dir = TempDir::new().unwrap();
+    let project = dir.path().join("demo");
+    init_project(&project).unwrap();
+    
+    for file in ["main.mini", "tests/hello.mini"] {
+        let source = fs::read_to_string(project.join(file)).unwrap();
+        let tokens = Lexer::new(&source).tokenize().unwrap();
+        let program = Parser::new(tokens, source.clone()).parse_program().unwrap();
+        assert!(TypeChecker::new().check_program(&program).is_ok(), "{} should type check", file);
+    }
+    
+    println!("✓ Scaffolded programs type check");
+}
+
+#[test]
+fn test_init_rejects_existing_and_invalid() {
+    let dir = TempDir::new().unwrap();
+    let project = dir.path().join("taken");
+    fs::create_dir(&project).unwrap();
+    fs::write(project.join("notes.txt"), "keep me").unwrap();
+    
+    assert!(init_project(&project).unwrap_err().contains("not empty"));
+    assert_eq!(fs::read_to_string(project.join("notes.txt")).unwrap(), "keep me");
+    
+    assert!(init_project(&dir.path().join("has space")).is_err());
+    
+    // An existing empty directory is fine
+    let empty = dir.path().join("empty");
+    fs::create_dir(&empty).unwrap();
+    assert!(init_project(&empty).is_ok());
+    
+    println!("✓ init refuses to overwrite or use bad names");
+}
+
+#[test]
+fn test_quiet_flag_is_global() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "check", "src", "lib", "--quiet"]).unwrap();
+    assert!(cli.quiet);
+    assert!(matches!(cli.command, Commands::Check { ref files } if files.len() == 2));
+    
+    let cli = Cli::try_parse_from(["minilang", "-q", "check", "main.mini"]).unwrap();
+    assert!(cli.quiet);
+    assert!(!Cli::try_parse_from(["minilang", "check", "main.mini"]).unwrap().quiet);
+    
+    println!("✓ --quiet is accepted anywhere on the command line");
+}
+
+#[test]
+fn test_out_dir_flag() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::Cli;
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "main.mini", "--out-dir", "build", "--keep-c"]).unwrap();
+    assert_eq!(cli.out_dir, Some(PathBuf::from("build")));
+    assert!(cli.keep_c);
+    
+    let cli = Cli::try_parse_from(["minilang", "--out-dir=build", "clean"]).unwrap();
+    assert_eq!(cli.out_dir, Some(PathBuf::from("build")));
+    assert_eq!(Cli::try_parse_from(["minilang", "run", "main.mini"]).unwrap().out_dir, None);
+    
+    println!("✓ --out-dir is a global option");
+}
+
+#[test]
+fn test_always_bounds_check_flag() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::Cli;
+    
+    assert!(Cli::try_parse_from(["minilang", "run", "main.mini", "--always-bounds-check"]).unwrap().always_bounds_check);
+    assert!(!Cli::try_parse_from(["minilang", "run", "main.mini"]).unwrap().always_bounds_check);
+    
+    println!("✓ --always-bounds-check is a global option");
+}
+
+#[test]
+fn test_fast_math_flag() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::Cli;
+    
+    assert!(Cli::try_parse_from(["minilang", "compile", "main.mini", "--fast-math", "-O2"]).unwrap().fast_math);
+    assert!(!Cli::try_parse_from(["minilang", "compile", "main.mini"]).unwrap().fast_math);
+    
+    println!("✓ --fast-math is off unless asked for");
+}
+
+#[test]
+fn test_overflow_flag() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::Cli;
+    use minilang_compiler::OverflowMode;
+    
+    assert_eq!(Cli::try_parse_from(["minilang", "run", "main.mini"]).unwrap().overflow, OverflowMode::Wrap);
+    assert_eq!(Cli::try_parse_from(["minilang", "run", "main.mini", "--overflow", "trap"]).unwrap().overflow, OverflowMode::Trap);
+    assert!(Cli::try_parse_from(["minilang", "run", "main.mini", "--overflow", "saturate"]).is_err());
+    assert_eq!(OverflowMode::Wrap.cc_flag(), "-fwrapv");
+    assert_eq!(OverflowMode::Trap.cc_flag(), "-ftrapv");
+    
+    println!("✓ --overflow picks wrap or trap semantics");
+}
+
+#[test]
+fn test_compile_accepts_modules_and_emit() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands, Emit};
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "main.mini", "util.mini", "--emit", "obj"]).unwrap();
+    assert!(matches!(cli.command, Commands::Compile { ref files, emit: Some(Emit::Obj), .. } if files.len() == 2));
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "main.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Compile { emit: None, to_c: false, .. }));
+    
+    assert!(Cli::try_parse_from(["minilang", "compile", "main.mini", "--emit", "c", "--to-c"]).is_err());
+    assert!(Cli::try_parse_from(["minilang", "compile", "main.mini", "--emit", "asm"]).is_err());
+    assert!(Cli::try_parse_from(["minilang", "compile"]).is_err());
+    
+    println!("✓ compile takes several modules and an --emit kind");
+}
+
+#[test]
+fn test_dry_run_flags() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "main.mini", "--dry-run", "--script", "build.sh"]).unwrap();
+    let Commands::Compile { dry_run, script, .. } = cli.command else {
+        panic!("Expected compile");
+    };
+    assert!(dry_run);
+    assert_eq!(script.as_deref(), Some(std::path::Path::new("build.sh")));
+    
+    assert!(Cli::try_parse_from(["minilang", "compile", "main.mini", "--script", "build.sh"]).is_err());
+    assert!(Cli::try_parse_from(["minilang", "compile", "main.mini", "--dry-run", "--to-c"]).is_err());
+    
+    println!("✓ --dry-run and --script parse, and --script needs --dry-run");
+}
+
+#[test]
+fn test_show_c_mapping_flag() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "main.mini", "--show-c-mapping", "--to-c"]).unwrap();
+    assert!(matches!(cli.command, Commands::Compile { show_c_mapping: true, to_c: true, .. }));
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "main.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Compile { show_c_mapping: false, .. }));
+    
+    println!("✓ compile --show-c-mapping parses");
+}
+
+#[test]
+fn test_xref_command() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "xref", "main.mini", "--json"]).unwrap();
+    let Commands::Xref { file, json } = cli.command else {
+        panic!("Expected xref");
+    };
+    assert_eq!(file, std::path::PathBuf::from("main.mini"));
+    assert!(json);
+    assert!(Cli::try_parse_from(["minilang", "xref"]).is_err());
+    
+    println!("✓ xref takes a file and --json");
+}
+
+#[test]
+fn test_reduce_command() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "reduce", "crash.mini", "--check", "./crashes.sh {}", "-o", "small.mini"]).unwrap();
+    assert_eq!(cli.output.as_deref(), Some("small.mini"));
+    let Commands::Reduce { file, check, timeout } = cli.command else {
+        panic!("Expected reduce");
+    };
+    assert_eq!(file, std::path::PathBuf::from("crash.mini"));
+    assert_eq!(check, "./crashes.sh {}");
+    assert_eq!(timeout, 10);
+    // The check has to be given
+    assert!(Cli::try_parse_from(["minilang", "reduce", "crash.mini"]).is_err());
+    
+    println!("✓ reduce takes a file, --check, --timeout and -o");
+}
+
+#[test]
+fn test_bytecode_target_and_vm_commands() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands, Target};
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "--target", "bytecode", "hw.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Compile { target: Target::Bytecode, .. }));
+    let cli = Cli::try_parse_from(["minilang", "compile", "hw.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Compile { target: Target::Native, .. }));
+    // Bytecode does not go through C
+    assert!(Cli::try_parse_from(["minilang", "compile", "--target=bytecode", "--to-c", "hw.mini"]).is_err());
+    assert!(Cli::try_parse_from(["minilang", "compile", "--target=bytecode", "--emit", "obj", "hw.mini"]).is_err());
+    
+    let cli = Cli::try_parse_from(["minilang", "vm", "hw.mbc"]).unwrap();
+    let Commands::Vm { file, watch, virtual_clock } = cli.command else {
+        panic!("Expected vm");
+    };
+    assert_eq!(file, std::path::PathBuf::from("hw.mbc"));
+    assert!(watch.is_empty());
+    assert!(!virtual_clock);
+    let cli = Cli::try_parse_from(["minilang", "vm", "hw.mbc", "--watch-var", "i", "--watch-var", "total"]).unwrap();
+    let Commands::Vm { watch, .. } = cli.command else {
+        panic!("Expected vm");
+    };
+    assert_eq!(watch, ["i", "total"]);
+    let cli = Cli::try_parse_from(["minilang", "vm", "--virtual-clock", "hw.mbc"]).unwrap();
+    assert!(matches!(cli.command, Commands::Vm { virtual_clock: true, .. }));
+    
+    println!("✓ compile --target bytecode and vm parse");
+}
+
+#[test]
+fn test_test_command() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "test", "a.mini", "b.mini", "--filter", "parse"]).unwrap();
+    let Commands::Test { files, filter } = cli.command else {
+        panic!("Expected test");
+    };
+    assert_eq!(files, vec![PathBuf::from("a.mini"), PathBuf::from("b.mini")]);
+    assert_eq!(filter.as_deref(), Some("parse"));
+    assert!(Cli::try_parse_from(["minilang", "test"]).is_err());
+    
+    println!("✓ test takes files and a --filter");
+}
+
+#[test]
+fn test_runtime_and_profile_options() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::Cli;
+    use minilang_compiler::runtime::RuntimeMode;
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "hw.mini"]).unwrap();
+    assert_eq!(cli.runtime, RuntimeMode::Arena);
+    assert!(!cli.profile);
+    assert_eq!(cli.codegen_flags(), vec!["--overflow=wrap"]);
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "hw.mini", "--runtime", "rc", "--profile"]).unwrap();
+    assert_eq!(cli.runtime, RuntimeMode::Rc);
+    assert!(cli.profile);
+    assert_eq!(cli.codegen_flags(), vec!["--overflow=wrap", "--runtime=rc", "--profile"]);
+    
+    let cli = Cli::try_parse_from(["minilang", "--runtime=none", "run", "hw.mini"]).unwrap();
+    assert_eq!(cli.runtime, RuntimeMode::None);
+    assert!(Cli::try_parse_from(["minilang", "compile", "hw.mini", "--runtime", "gc"]).is_err());
+    
+    println!("✓ --runtime and --profile parse and reach the build stamp");
+}
+
+#[test]
+fn test_repro_save_command() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands, ReproAction};
+    use minilang_compiler::repro::Failure;
+    
+    let cli = Cli::try_parse_from(["minilang", "repro", "save", "bug.mini", "--failure", "wrong-output"]).unwrap();
+    let Commands::Repro { action: ReproAction::Save { file, failure, corpus, name } } = cli.command else {
+        panic!("Expected repro save");
+    };
+    assert_eq!(file, std::path::PathBuf::from("bug.mini"));
+    assert_eq!(failure, Failure::WrongOutput);
+    assert_eq!(corpus, std::path::PathBuf::from("tests/repro"));
+    assert_eq!(name, None);
+    
+    let cli = Cli::try_parse_from([
+        "minilang", "repro", "save", "bug.mini", "--failure", "ice", "--corpus", "cases", "--name", "crash",
+    ]).unwrap();
+    let Commands::Repro { action: ReproAction::Save { corpus, name, .. } } = cli.command else {
+        panic!("Expected repro save");
+    };
+    assert_eq!(corpus, std::path::PathBuf::from("cases"));
+    assert_eq!(name.as_deref(), Some("crash"));
+    // The failure has to be named
+    assert!(Cli::try_parse_from(["minilang", "repro", "save", "bug.mini"]).is_err());
+    
+    println!("✓ repro save takes a file, --failure, --corpus and --name");
+}
+
+#[test]
+fn test_inspect_command() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "inspect", "main", "--source", "main.mini", "--json"]).unwrap();
+    let Commands::Inspect { file, source, json } = cli.command else {
+        panic!("Expected inspect");
+    };
+    assert_eq!(file, std::path::PathBuf::from("main"));
+    assert_eq!(source, Some(std::path::PathBuf::from("main.mini")));
+    assert!(json);
+    
+    println!("✓ inspect takes an artifact, an optional --source and --json");
+}
+
+#[test]
+fn test_codegen_flags_for_stamps() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::Cli;
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "main.mini"]).unwrap();
+    assert_eq!(cli.codegen_flags(), ["--overflow=wrap"]);
+    
+    let cli = Cli::try_parse_from([
+        "minilang", "compile", "main.mini", "-O", "2", "--overflow", "trap", "--fast-math",
+        "--always-bounds-check", "--entry", "exercise", "-D", "DEBUG", "-D", "LEVEL=3",
+    ]).unwrap();
+    assert_eq!(cli.codegen_flags(), [
+        "--overflow=trap", "--fast-math", "--always-bounds-check", "--entry=exercise", "-DDEBUG=true", "-DLEVEL=3",
+    ]);
+    
+    println!("✓ Stamps list the options that change the generated code");
+}
+
+#[test]
+fn test_save_temps_flag() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::Cli;
+    
+    assert!(Cli::try_parse_from(["minilang", "compile", "main.mini", "--save-temps"]).unwrap().save_temps);
+    assert!(Cli::try_parse_from(["minilang", "--save-temps", "run", "main.mini"]).unwrap().save_temps);
+    assert!(!Cli::try_parse_from(["minilang", "compile", "main.mini"]).unwrap().save_temps);
+    
+    println!("✓ --save-temps is a global flag, off by default");
+}
+
+#[test]
+fn test_run_forwards_arguments_and_stdin() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "run", "main.mini", "--stdin", "input.txt", "--", "one", "--two", "-O2"]).unwrap();
+    let Commands::Run { file, stdin, program_args } = cli.command else {
+        panic!("Expected run");
+    };
+    assert_eq!(file, PathBuf::from("main.mini"));
+    assert_eq!(stdin, Some(PathBuf::from("input.txt")));
+    assert_eq!(program_args, ["one", "--two", "-O2"]);
+    // Flags after -- belong to the program, not to minilang
+    assert_eq!(cli.optimization, 1);
+    
+    let cli = Cli::try_parse_from(["minilang", "run", "main.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Run { stdin: None, ref program_args, .. } if program_args.is_empty()));
+    
+    println!("✓ run takes program arguments after -- and a --stdin file");
+}
+
+#[test]
+fn test_analyze_only_function() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "analyze", "main.mini", "--only-function", "parse", "--only-function", "eval"]).unwrap();
+    assert!(matches!(cli.command, Commands::Analyze { ref only_functions, .. } if only_functions == &["parse", "eval"]));
+    
+    let cli = Cli::try_parse_from(["minilang", "analyze", "main.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Analyze { ref only_functions, .. } if only_functions.is_empty()));
+    
+    println!("✓ analyze can be narrowed to named functions");
+}
+
+#[test]
+fn test_analyze_project_options() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "analyze", "src/", "lib/*.mini", "--html", "--worst", "3"]).unwrap();
+    assert!(matches!(cli.command, Commands::Analyze { ref files, html: true, worst: 3, .. } if files.len() == 2));
+    
+    let cli = Cli::try_parse_from(["minilang", "analyze", "src/"]).unwrap();
+    assert!(matches!(cli.command, Commands::Analyze { html: false, json: false, worst: 10, .. }));
+    
+    assert!(Cli::try_parse_from(["minilang", "analyze"]).is_err());
+    assert!(Cli::try_parse_from(["minilang", "analyze", "src/", "--json", "--html"]).is_err());
+    
+    println!("✓ analyze takes several inputs and report options");
+}
+
+#[test]
+fn test_ast_options() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "ast", "main.mini", "--depth", "3", "--function", "a", "--function", "b", "--spans"]).unwrap();
+    assert!(matches!(cli.command, Commands::Ast { depth: Some(3), ref functions, spans: true, .. } if functions == &["a", "b"]));
+    
+    let cli = Cli::try_parse_from(["minilang", "ast", "main.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Ast { depth: None, ref functions, spans: false, .. } if functions.is_empty()));
+    
+    println!("✓ ast takes depth, function and span options");
+}
+
+#[test]
+fn test_entry_flag() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::Cli;
+    
+    let cli = Cli::try_parse_from(["minilang", "run", "main.mini", "--entry", "exercise"]).unwrap();
+    assert_eq!(cli.entry.as_deref(), Some("exercise"));
+    
+    let cli = Cli::try_parse_from(["minilang", "compile", "main.mini"]).unwrap();
+    assert_eq!(cli.entry, None);
+    
+    println!("✓ --entry names the function to start at");
+}
+
+#[test]
+fn test_tokens_json_flag() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "tokens", "main.mini", "--json"]).unwrap();
+    assert!(matches!(cli.command, Commands::Tokens { json: true, .. }));
+    
+    let cli = Cli::try_parse_from(["minilang", "tokens", "main.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Tokens { json: false, .. }));
+    
+    println!("✓ tokens can print JSON");
+}
+
+#[test]
+fn test_analyze_cyclomatic_mode() {
+    use clap::Parser as _;
+    use minilang_compiler::analyzer::CyclomaticMode;
+    use minilang_compiler::cli::{Cli, Commands};
+    
+    let cli = Cli::try_parse_from(["minilang", "analyze", "main.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Analyze { cyclomatic: CyclomaticMode::Strict, .. }));
+    
+    let cli = Cli::try_parse_from(["minilang", "analyze", "main.mini", "--cyclomatic", "classic"]).unwrap();
+    assert!(matches!(cli.command, Commands::Analyze { cyclomatic: CyclomaticMode::Classic, .. }));
+    
+    assert!(Cli::try_parse_from(["minilang", "analyze", "main.mini", "--cyclomatic", "loose"]).is_err());
+    
+    println!("✓ analyze chooses how AND/OR count toward cyclomatic complexity");
+}
+
+#[test]
+fn test_refactor_early_return_command() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::{Cli, Commands, RefactorAction};
+    
+    let cli = Cli::try_parse_from(["minilang", "refactor", "early-return", "main.mini"]).unwrap();
+    assert!(matches!(cli.command, Commands::Refactor { action: RefactorAction::EarlyReturn { write: false, .. } }));
+    
+    let cli = Cli::try_parse_from(["minilang", "refactor", "early-return", "main.mini", "--write"]).unwrap();
+    assert!(matches!(cli.command, Commands::Refactor { action: RefactorAction::EarlyReturn { write: true, .. } }));
+    
+    assert!(Cli::try_parse_from(["minilang", "refactor", "main.mini"]).is_err());
+    println!("✓ refactor early-return previews by default and writes with --write");
+}
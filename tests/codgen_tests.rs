@@ -1,6 +1,6 @@
 // tests/codegen_tests.rs - Code generation testing
 
-use minilang_compiler::{Lexer, Parser, TypeChecker, CodeGenerator};
+use minilang_compiler::{Lexer, Parser, TypeChecker, CodeGenerator, Optimizer};
 use std::fs;
 use std::process::Command;
 use tempfile::TempDir;
@@ -30,7 +30,10 @@ fn compile_to_c(source: &str) -> Result<String, Box<dyn std::error::Error>> {
 
 fn compile_and_run(source: &str) -> Result<String, Box<dyn std::error::Error>> {
     let c_code = compile_to_c(source)?;
-    
+    run_c(c_code)
+}
+
+fn run_c(c_code: String) -> Result<String, Box<dyn std::error::Error>> {
     // Create temp directory
     let temp_dir = TempDir::new()?;
     let c_file = temp_dir.path().join("test.c");
@@ -84,10 +87,10 @@ func main() {
 "#;
     
     let c_code = compile_to_c(source).unwrap();
-    assert!(c_code.contains("int x = 42"));
-    assert!(c_code.contains("double y = 3.14"));
-    assert!(c_code.contains("const char* s = \"test\""));
-    assert!(c_code.contains("bool b = true"));
+    assert!(c_code.contains("int ml_x = 42"));
+    assert!(c_code.contains("double ml_y = 3.14"));
+    assert!(c_code.contains("const char* ml_s = \"test\""));
+    assert!(c_code.contains("bool ml_b = true"));
 }
 
 #[test]
@@ -104,7 +107,7 @@ func main() {
 "#;
     
     let c_code = compile_to_c(source).unwrap();
-    assert!(c_code.contains("if ((x > 5))"));
+    assert!(c_code.contains("if ((ml_x > 5))"));
     assert!(c_code.contains("else"));
 }
 
@@ -128,7 +131,7 @@ func main() {
     
     let c_code = compile_to_c(source).unwrap();
     assert!(c_code.contains("while (true)"));
-    assert!(c_code.contains("for (int i = 0"));
+    assert!(c_code.contains("for (int ml_i = 0"));
     assert!(c_code.contains("do {"));
     assert!(c_code.contains("break;"));
     assert!(c_code.contains("continue;"));
@@ -146,8 +149,8 @@ func main() {
 "#;
     
     let c_code = compile_to_c(source).unwrap();
-    assert!(c_code.contains("const double PI = 3.14159"));
-    assert!(c_code.contains("const int MAX = 100"));
+    assert!(c_code.contains("const double ml_PI = 3.14159"));
+    assert!(c_code.contains("const int ml_MAX = 100"));
     println!("✓ Const declarations generate valid C");
 }
 
@@ -182,12 +185,46 @@ func main() {
     println!("✓ Multiple consts compute correctly");
 }
 
+#[test]
+fn test_const_array_output() {
+    let source = r#"
+pure func sum(values: int[3]) -> int {
+    let total: int = 0;
+    for let i: int = 0; i < 3; i = i + 1 {
+        total = total + values[i];
+    }
+    send total;
+}
+
+func main() {
+    const TABLE: int[3] = [10, 20, 30];
+    let i: int = 1;
+    displayln TABLE[2], " ", TABLE[i], " ", sum(TABLE);
+}
+"#;
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("int ml_TABLE[3] = {"), "{}", c_code);
+    assert_eq!(run_c(c_code).unwrap(), "30 20 60\n");
+    
+    // Folded lookups must leave valid C behind
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    Optimizer::new(2).optimize(&mut program);
+    let c_code = CodeGenerator::new().generate(&program).unwrap();
+    assert!(!c_code.contains("{10, 20, 30}["), "{}", c_code);
+    assert_eq!(run_c(c_code).unwrap(), "30 20 60\n");
+    
+    println!("✓ Const arrays are declared as C arrays and fold cleanly");
+}
+
 #[test]
 fn test_array_bounds_with_actual_size() {
     let source = r#"
 func main() {
     let arr: int[5] = [1, 2, 3, 4, 5];
-    let val: int = arr[4];
+    let i: int = 0;
+    i = 4;
+    let val: int = arr[i];
     display val;
 }
 "#;
@@ -195,7 +232,7 @@ func main() {
     let c_code = compile_to_c(source).unwrap();
     assert!(c_code.contains("CHECK_BOUNDS"));
     // Should contain the actual size 5, not hardcoded 10
-    assert!(c_code.contains("CHECK_BOUNDS(4, 5)") || c_code.contains("_minilang_check_bounds(4, 5"));
+    assert!(c_code.contains("CHECK_BOUNDS(ml_i, 5)") || c_code.contains("_minilang_check_bounds(ml_i, 5"));
     println!("✓ Array bounds use actual size");
 }
 
@@ -241,6 +278,94 @@ func main() {
     println!("✓ Break and continue generate valid C");
 }
 
+#[test]
+fn test_bare_send_in_main_returns_zero() {
+    let source = r#"
+func main() {
+    let ready: bool = false;
+    if NOT ready {
+        send;
+    }
+    display "ready";
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(!c_code.contains("return;"), "{}", c_code);
+    assert_eq!(run_c(c_code).unwrap(), "");
+    println!("✓ A bare send in main exits with status 0");
+}
+
+#[test]
+fn test_main_return_value_is_exit_status() {
+    let source = "func check(n: int) -> int {\n    send n % 2;\n}\n\nfunc main() -> int {\n    defer {\n        display \"cleanup\";\n    }\n    let failures = check(3) + check(5);\n    if failures > 0 {\n        send 40 + failures;\n    }\n    send 0;\n}\n";
+    let (_, code) = run_mapped(source);
+    assert_eq!(code, Some(42));
+    
+    let (_, code) = run_mapped("func main() -> int {\n    send 0;\n}\n");
+    assert_eq!(code, Some(0));
+    println!("✓ The int main sends is the exit status");
+}
+
+#[test]
+fn test_entry_replaces_main() {
+    let source = r#"
+func exercise() -> int {
+    displayln "exercise";
+    send 7;
+}
+
+func greet() {
+    displayln "hello";
+}
+
+func main() {
+    displayln "main";
+}
+"#;
+    for (entry, expected) in [("exercise", "exercise\n"), ("greet", "hello\n")] {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+        TypeChecker::new().with_entry(entry).check_program(&program).unwrap();
+        assert!(program.set_entry(entry));
+        
+        let main = program.functions.iter().find(|f| f.name == "main").unwrap();
+        assert_eq!(main.return_type.is_some(), entry == "exercise");
+        assert_eq!(program.functions.iter().filter(|f| f.name == "main").count(), 1);
+        
+        let c_code = CodeGenerator::new().generate(&program).unwrap();
+        assert_eq!(run_c(c_code).unwrap(), expected);
+    }
+    println!("✓ --entry runs the chosen function instead of main");
+}
+
+#[test]
+fn test_inferred_types_output() {
+    let source = r#"
+func half(x: float) -> float {
+    send x / 2.0;
+}
+
+func main() {
+    let count = 3;
+    let ratio = half(3.0);
+    let name = "n{count}";
+    let flags = [true, false];
+    let pair = (count, ratio > 1.0);
+    let total = 0;
+    for let i = 0; i < count; i = i + 1 {
+        total = total + i;
+    }
+    displayln count, " ", ratio, " ", name, " ", flags, " ", pair, " ", total;
+}
+"#;
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("double ml_ratio"), "{}", c_code);
+    assert!(c_code.contains("bool ml_flags[2]"), "{}", c_code);
+    assert_eq!(run_c(c_code).unwrap(), "3 1.500000 n3 [true, false] (3, true) 3\n");
+    println!("✓ Inferred declarations get the C type of their value");
+}
+
 // ==================== OUTPUT CORRECTNESS TESTS ====================
 
 #[test]
@@ -278,9 +403,9 @@ func main() {
     
     let output = compile_and_run(source).unwrap();
     let lines: Vec<&str> = output.lines().collect();
-    assert_eq!(lines[0], "0");  // false
-    assert_eq!(lines[1], "1");  // true
-    assert_eq!(lines[2], "0");  // false
+    assert_eq!(lines[0], "false");
+    assert_eq!(lines[1], "true");
+    assert_eq!(lines[2], "false");
 }
 
 #[test]
@@ -368,10 +493,149 @@ func main() {
 "#;
     
     let c_code = compile_to_c(source).unwrap();
-    // Check that multiple printf calls are generated
-    let printf_count = c_code.matches("printf").count();
-    assert!(printf_count >= 3); // "Hello, ", name, "!", and newline
-    println!("✓ String interpolation generates multiple printfs");
+    assert!(c_code.contains("printf(\"Hello, %s!\\n\", ml_name);"), "{}", c_code);
+    println!("✓ String interpolation generates a single printf");
+}
+
+#[test]
+fn test_display_parts_share_one_printf() {
+    let source = r#"
+func main() {
+    let x: int = 7;
+    let ok: bool = true;
+    display "x = ", x, " (100%) ", ok, "!";
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("printf(\"x = %d (100%%) %s!\\n\", ml_x, ml_ok ? \"true\" : \"false\");"), "{}", c_code);
+    assert_eq!(compile_and_run(source).unwrap(), "x = 7 (100%) true!\n");
+}
+
+#[test]
+fn test_display_keeps_order_around_calls() {
+    let source = r#"
+func noisy() -> int {
+    display_raw "[noisy]";
+    send 1;
+}
+
+func main() {
+    display_raw "a", noisy(), "b", noisy(), "c";
+}
+"#;
+    
+    assert_eq!(compile_and_run(source).unwrap(), "a[noisy]1b[noisy]1c");
+}
+
+#[test]
+fn test_display_line_endings_output() {
+    let source = r#"
+func main() {
+    display_raw "a", 1;
+    display_raw " b";
+    display "";
+    displayln "c";
+    display (2, true);
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("printf(\"a%d\", 1);"), "{}", c_code);
+    assert!(c_code.contains("printf(\"\\n\");"), "{}", c_code);
+    assert_eq!(compile_and_run(source).unwrap(), "a1 b\nc\n(2, true)\n");
+}
+
+#[test]
+fn test_display_call_results_of_every_type() {
+    let source = r#"
+func count() -> int { send 42; }
+func half(x: float) -> float { send x / 2.0; }
+func greet(name: string) -> string { send "hi {name}"; }
+func ready() -> bool { send true; }
+
+func main() {
+    display count();
+    display half(3.0);
+    display greet("bob");
+    display ready();
+    display half(1.0) * 4.0, " ", -half(5.0);
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output, "42\n1.500000\nhi bob\ntrue\n2.000000 -2.500000\n");
+    println!("✓ Call results display with their return type");
+}
+
+#[test]
+fn test_display_array_elements_of_every_type() {
+    let source = r#"
+func show(prices: float[2], names: string[2]) {
+    display prices[1], " ", names[0];
+}
+
+func main() {
+    let counts: int[2] = [4, 5];
+    let prices: float[2] = [1.25, 2.5];
+    let names: string[2] = ["ann", "bo"];
+    let flags: bool[2] = [true, false];
+    display counts[1], " ", prices[0], " ", names[1], " ", flags[1];
+    display prices[0] + 1.0, " ", NOT flags[0], " ", -prices[1];
+    show(prices, names);
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output, "5 1.250000 bo false\n2.250000 false -2.500000\n2.500000 ann\n");
+    println!("✓ Array elements display with their element type");
+}
+
+#[test]
+fn test_display_whole_arrays() {
+    let source = r#"
+func show(xs: int[3]) {
+    display "xs = {xs}";
+}
+
+func main() {
+    let xs: int[3] = [1, 2, 3];
+    let prices: float[2] = [0.5, 1.5];
+    let names: string[2] = ["ann", "bo"];
+    let flags: bool[2] = [true, false];
+    let none: int[0] = [];
+    display xs;
+    display prices, " ", names, " ", flags;
+    display [7, 8], none;
+    show(xs);
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(!c_code.contains("%p"), "{}", c_code);
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output, "[1, 2, 3]\n[0.500000, 1.500000] [ann, bo] [true, false]\n[7, 8][]\nxs = [1, 2, 3]\n");
+    println!("✓ Whole arrays display element by element");
+}
+
+#[test]
+fn test_interpolated_calls_and_elements_of_every_type() {
+    let source = r#"
+func half(x: float) -> float { send x / 2.0; }
+func greet() -> string { send "hey"; }
+
+func main() {
+    let prices: float[2] = [1.25, 2.5];
+    let names: string[2] = ["ann", "bo"];
+    let flags: bool[2] = [true, false];
+    display "{half(5.0)} {greet()} {prices[1]} {names[0]} {flags[0]}";
+    display format("{} {} {}", prices[0], names[1], flags[1]);
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output, "2.500000 hey 2.500000 ann true\n1.250000 bo false\n");
+    println!("✓ Interpolated calls and elements display with their type");
 }
 
 #[test]
@@ -408,7 +672,7 @@ func main() {
 
 #[test]
 fn test_generated_c_compiles() {
-    let test_programs = vec![
+    let test_programs = [
         // Simple program
         r#"func main() { display "test"; }"#,
         
@@ -429,4 +693,894 @@ fn test_generated_c_compiles() {
         let result = compile_and_run(source);
         assert!(result.is_ok(), "Test program {} failed to compile: {:?}", i, result);
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_default_initializers_are_c99() {
+    let source = r#"
+func main() {
+    let nums: int[3];
+    let flags: bool[2];
+    let names: string[2];
+    let pair: (int, float);
+    let total: float;
+    let done: bool;
+    let label: string;
+    let tagged: (int, string);
+    display nums[1];
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("int ml_nums[3] = {0};"));
+    assert!(c_code.contains("bool ml_flags[2] = {false};"));
+    assert!(c_code.contains("const char* ml_names[2] = {\"\", \"\"};"));
+    assert!(c_code.contains("_minilang_tuple_int_float ml_pair = {0};"));
+    assert!(c_code.contains("double ml_total = 0.0;"));
+    assert!(c_code.contains("bool ml_done = false;"));
+    assert!(c_code.contains("const char* ml_label = \"\";"));
+    assert!(c_code.contains("_minilang_tuple_int_string ml_tagged = {0, \"\"};"));
+    
+    // Empty braces are a GNU extension; strict C99 must accept the output
+    let temp_dir = TempDir::new().unwrap();
+    let c_file = temp_dir.path().join("test.c");
+    fs::write(&c_file, c_code).unwrap();
+    let output = Command::new("gcc")
+        .arg(&c_file)
+        .args(["-std=c99", "-pedantic-errors", "-fsyntax-only"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_uninitialized_strings_are_empty() {
+    use minilang_compiler::runtime::RuntimeMode;
+    use minilang_compiler::test_utils::compile_and_run_with_runtime;
+    
+    let source = r#"
+func main() {
+    let s: string;
+    let names: string[3];
+    let (n, t) = (1, s);
+    display "[", s, "]", names, n, t, "|";
+    display parse_int(names[2]);
+}
+"#;
+    // As in the VM: empty strings, never NULL
+    for runtime in [RuntimeMode::Arena, RuntimeMode::Rc] {
+        let Some(output) = compile_and_run_with_runtime(source, 0, runtime, false) else { return };
+        assert_eq!(output.stdout, "[][, , ]1|\n", "--runtime={}", runtime.name());
+        assert_eq!(output.exit_code, Some(1), "{}", output.stderr);
+        assert!(output.stderr.contains("cannot parse \"\" as int"), "{}", output.stderr);
+    }
+}
+
+#[test]
+fn test_user_names_cannot_clash_with_c() {
+    let source = r#"
+func printf(exit: int) -> int {
+    send exit * 2;
+}
+
+func CHECK_BOUNDS(strlen: int, malloc: int) -> int {
+    send strlen + malloc;
+}
+
+func _minilang_format(x: int) -> int {
+    send x + 1;
+}
+
+func main() {
+    let puts: int[3] = [1, 2, 3];
+    let signal: string = "ok";
+    let _ok: int = puts[1];
+    display printf(CHECK_BOUNDS(_ok, puts[2])), " ", _minilang_format(1), " ", signal;
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("int ml_printf(int ml_exit)"), "{}", c_code);
+    assert_eq!(compile_and_run(source).unwrap(), "10 2 ok\n");
+    println!("✓ User symbols are mangled away from libc and the runtime");
+}
+
+// ==================== DEFER TESTS ====================
+
+#[test]
+fn test_defer_runs_at_block_exit_in_reverse_order() {
+    let source = r#"
+func main() {
+    display_raw "a";
+    defer { display_raw "d"; }
+    defer { display_raw "c"; }
+    display_raw "b";
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "abcd");
+}
+
+#[test]
+fn test_defer_runs_before_return_with_value_evaluated_first() {
+    let source = r#"
+func bump(n: int) -> int {
+    let x: int = n;
+    defer { x = x + 100; display_raw "cleanup "; }
+    send x;
+}
+
+func main() {
+    display bump(5);
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "cleanup 5");
+}
+
+#[test]
+fn test_defer_in_loop_body_runs_on_break_and_continue() {
+    let source = r#"
+func main() {
+    for let i: int = 0; i < 5; i = i + 1 {
+        defer { display_raw ";"; }
+        if i == 1 {
+            continue;
+        }
+        if i == 3 {
+            break;
+        }
+        display_raw i;
+    }
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "0;;2;;");
+}
+
+#[test]
+fn test_defer_sees_the_variables_of_its_declaration_site() {
+    use minilang_compiler::runtime::RuntimeMode;
+    use minilang_compiler::test_utils::compile_and_run_with_runtime;
+    
+    // The inner lets shadow the names the deferred body uses before it runs
+    let source = r#"
+func main() -> int {
+    let x: int = 1;
+    let s: string = "outer";
+    defer { display x, " ", s; }
+    if true {
+        let x: string = "inner";
+        let s: int = 99;
+        display x, " ", s;
+        send 0;
+    }
+    send 1;
+}
+"#;
+    
+    for runtime in [RuntimeMode::Arena, RuntimeMode::Rc] {
+        let Some(output) = compile_and_run_with_runtime(source, 0, runtime, false) else { return };
+        assert_eq!(output.stdout, "inner 99\n1 outer\n", "--runtime={}", runtime.name());
+    }
+}
+
+#[test]
+fn test_defer_emits_nothing_at_declaration_site() {
+    let source = r#"
+func main() {
+    defer { display "done"; }
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert_eq!(c_code.matches("done").count(), 1);
+}
+
+// ==================== TUPLE TESTS ====================
+
+#[test]
+fn test_tuple_return_destructuring_output() {
+    let source = r#"
+func divmod(a: int, b: int) -> (int, int) {
+    send (a / b, a % b);
+}
+
+func main() {
+    let (q, r) = divmod(17, 5);
+    display q, ":", r;
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "3:2");
+}
+
+#[test]
+fn test_tuple_array_element_stops_before_codegen() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = temp_dir.path().join("pack.mini");
+    fs::write(&file, "func pack(a: int[3]) -> (int[3], int) {\n    send (a, 4);\n}\n\nfunc main() {\n    let a: int[3] = [1, 2, 3];\n    let (b, n) = pack(a);\n    display b[0], n;\n}\n").unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_minilang"))
+        .arg("run")
+        .arg(&file)
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("arrays cannot be tuple elements"), "{}", stderr);
+    assert!(!temp_dir.path().join("pack.c").exists());
+}
+
+#[test]
+fn test_tuple_display_output() {
+    let source = r#"
+func label(n: int) -> (int, bool, string) {
+    send (n, n > 0, "ok");
+}
+
+func main() {
+    display label(4);
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "(4, true, ok)");
+}
+
+#[test]
+fn test_tuple_struct_declared_once() {
+    let source = r#"
+func a() -> (int, int) { send (1, 2); }
+func b() -> (int, int) { send (3, 4); }
+
+func main() {
+    let (x, y) = a();
+    display x + y;
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert_eq!(c_code.matches("} _minilang_tuple_int_int;").count(), 1);
+}
+
+// ==================== FORMAT TESTS ====================
+
+#[test]
+fn test_format_builtin_output() {
+    let source = r#"
+func main() {
+    let total: int = 3;
+    let s: string = format("{} + {} = {}", 1, 2, total);
+    display s;
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "1 + 2 = 3");
+}
+
+#[test]
+fn test_format_bool_and_percent_output() {
+    let source = r#"
+func main() {
+    let ok: bool = 2 > 1;
+    display format("{}: 100%", ok);
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "true: 100%");
+}
+
+#[test]
+fn test_interpolated_string_as_value() {
+    let source = r#"
+func main() {
+    let name: string = "world";
+    let greeting: string = "hello {name}";
+    display greeting;
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("_minilang_format(\"hello %s\", ml_name)"));
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "hello world");
+}
+
+// ==================== ARRAY INITIALIZATION TESTS ====================
+
+#[test]
+fn test_inferred_array_size_output() {
+    let source = r#"
+func main() {
+    let arr: int[] = [4, 5, 6];
+    display arr[2];
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("int ml_arr[3]"));
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "6");
+}
+
+// ==================== STRING ASSIGNMENT TESTS ====================
+
+#[test]
+fn test_string_reassignment_output() {
+    let source = r#"
+func main() {
+    let s: string = "start";
+    let alias: string = s;
+    for let i: int = 0; i < 3; i = i + 1 {
+        s = "step {i}";
+    }
+    display alias, " -> ", s;
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "start -> step 2");
+}
+
+#[test]
+fn test_built_strings_do_not_leak() {
+    let source = r#"
+func main() {
+    let s: string = "";
+    for let i: int = 0; i < 5; i = i + 1 {
+        s = format("{}{}", s, i);
+    }
+    display s;
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    let temp_dir = TempDir::new().unwrap();
+    let c_file = temp_dir.path().join("test.c");
+    let exe_file = temp_dir.path().join("test");
+    fs::write(&c_file, c_code).unwrap();
+    
+    let compiled = Command::new("gcc")
+        .arg(&c_file)
+        .arg("-o")
+        .arg(&exe_file)
+        .args(["-std=c99", "-fsanitize=address"])
+        .output()
+        .unwrap();
+    if !compiled.status.success() {
+        // Toolchain without AddressSanitizer; nothing to check
+        return;
+    }
+    
+    let output = Command::new(&exe_file)
+        .env("ASAN_OPTIONS", "detect_leaks=1")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "01234");
+}
+
+// ==================== FLOAT COMPARISON TESTS ====================
+
+#[test]
+fn test_approx_equal_output() {
+    let source = r#"
+func main() {
+    let sum: float = 0.1 + 0.2;
+    display approx_equal(sum, 0.3, 0.000001), " ", sum == 0.3;
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "true false");
+}
+
+#[test]
+fn test_float_literals_round_trip() {
+    let source = r#"
+func main() {
+    let sum: float = 0.30000000000000004;
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("double ml_sum = 0.30000000000000004;"));
+}
+
+// ==================== CONVERSION TESTS ====================
+
+#[test]
+fn test_int_string_conversions_output() {
+    let source = r#"
+func main() {
+    let n: int = parse_int("-42");
+    display to_string(n * 2), " ", parse_int("+7") + 1, " ", parse_int("2147483647"), " ", parse_int("-2147483648");
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "-84 8 2147483647 -2147483648");
+}
+
+#[test]
+fn test_parse_int_rejects_non_ints() {
+    for text in ["", "-", "12a", " 12", "1.5", "2147483648", "-2147483649"] {
+        let source = format!("func main() {{\n    let text: string = \"{}\";\n    display parse_int(text);\n}}\n", text);
+        let (stderr, code) = run_mapped(&source);
+        
+        assert_eq!(code, Some(1), "{:?} should not parse", text);
+        assert!(stderr.contains(&format!("cannot parse \"{}\" as int", text)), "{}", stderr);
+        assert!(stderr.contains("at prog.mini:3"), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_checked_arithmetic_output() {
+    let source = r#"
+func main() {
+    let big: int = 2147483647;
+    let (sum, overflowed) = add_checked(big, 1);
+    display sum, " ", overflowed;
+    display sub_checked(-5, 3), " ", mul_checked(65536, 65536);
+}
+"#;
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "-2147483648 true\n(-8, false) (0, true)");
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("__builtin_add_overflow(a, b, &result._0)"));
+    assert!(!c_code.contains("_minilang_div_checked"));
+}
+
+#[test]
+fn test_memo_function_output() {
+    let source = r#"
+@memo
+pure func fib(n: int) -> int {
+    if n < 2 { send n; }
+    send fib(n - 1) + fib(n - 2);
+}
+
+func main() {
+    display fib(30);
+}
+"#;
+    
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("static int _minilang_memo_ml_fib(int ml_n) {"));
+    assert!(c_code.contains("static __thread struct { bool used; int _0; int result; } cache[_MINILANG_MEMO_SLOTS];"));
+    assert!(c_code.contains("int result = _minilang_memo_ml_fib(ml_n);"));
+    // The body's recursive calls go through the cache
+    assert!(c_code.contains("return (ml_fib((ml_n - 1)) + ml_fib((ml_n - 2)));"));
+    assert!(!compile_to_c("func main() { }").unwrap().contains("_minilang_memo_mix"));
+    
+    let output = compile_and_run(source).unwrap();
+    assert_eq!(output.trim(), "832040");
+}
+
+// ==================== INTEGER DIVISION TESTS ====================
+
+#[test]
+fn test_negative_division_truncates_when_optimized() {
+    let source = r#"
+func show(x: int) {
+    display_raw x / 2, " ", x % 2, " ", x / 4, " ", x % 16, "|";
+}
+
+func main() {
+    show(-7);
+    show(7);
+    show(-16);
+    show(-17);
+}
+"#;
+    let expected = "-3 -1 -1 -7|3 1 1 7|-8 0 -4 0|-8 -1 -4 -1|";
+    
+    assert_eq!(compile_and_run(source).unwrap().trim(), expected);
+    
+    // Strength reduction turns these into shift/mask hints; results must not change
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens, source.to_string());
+    let mut program = parser.parse_program().unwrap();
+    let stats = Optimizer::new(2).optimize(&mut program);
+    assert!(stats.strength_reductions >= 4);
+    
+    let c_code = CodeGenerator::new().generate(&program).unwrap();
+    assert_eq!(run_c(c_code).unwrap().trim(), expected);
+}
+
+#[test]
+fn test_float_identities_keep_negative_zero() {
+    let source = r#"
+func show(z: float) {
+    display_raw z + 0.0, " ", z * 1.0, " ", z - 0.0, "|";
+}
+
+func main() {
+    show(-0.0);
+}
+"#;
+    let ieee = "0.000000 -0.000000 -0.000000|";
+    assert_eq!(compile_and_run(source).unwrap().trim(), ieee);
+    
+    let optimized = |fast_math: bool| {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+        let stats = Optimizer::new(1).with_fast_math(fast_math).optimize(&mut program);
+        let c_code = CodeGenerator::new().generate(&program).unwrap();
+        (stats.strength_reductions, run_c(c_code).unwrap())
+    };
+    
+    // By default only the exact identities are applied
+    let (reductions, output) = optimized(false);
+    assert_eq!(reductions, 2);
+    assert_eq!(output.trim(), ieee);
+    
+    // Fast math also drops `+ 0.0`, which is wrong for -0.0
+    let (reductions, output) = optimized(true);
+    assert_eq!(reductions, 3);
+    assert_eq!(output.trim(), "-0.000000 -0.000000 -0.000000|");
+    
+    println!("✓ Float identities respect -0.0 unless fast math is on");
+}
+
+#[test]
+fn test_wrapped_constants_stay_ints() {
+    let source = r#"
+func main() {
+    display 2147483647 + 1;
+}
+"#;
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    Optimizer::new(1).optimize(&mut program);
+    let c_code = CodeGenerator::new().generate(&program).unwrap();
+    
+    // -2147483648 in C is the negation of a long
+    assert!(c_code.contains("(-2147483647 - 1)"), "{}", c_code);
+    assert_eq!(run_c(c_code).unwrap().trim(), "-2147483648");
+    
+    println!("✓ Folded i32::MIN is written as an int expression");
+}
+
+#[test]
+fn test_negative_divisor_strength_reduction_output() {
+    let source = r#"
+func show(x: int) {
+    display_raw x / -4, " ", x % -16, "|";
+}
+
+func main() {
+    show(-17);
+    show(17);
+}
+"#;
+    let expected = "4 -1|-4 1|";
+    
+    assert_eq!(compile_and_run(source).unwrap().trim(), expected);
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens, source.to_string());
+    let mut program = parser.parse_program().unwrap();
+    let stats = Optimizer::new(1).optimize(&mut program);
+    assert_eq!(stats.strength_reductions, 2);
+    
+    let c_code = CodeGenerator::new().generate(&program).unwrap();
+    assert_eq!(run_c(c_code).unwrap().trim(), expected);
+}
+
+// ==================== SOURCE MAP TESTS ====================
+
+/// Compile with source mapping and run, returning (stderr, exit code)
+fn run_mapped(source: &str) -> (String, Option<i32>) {
+    run_mapped_with(source, true)
+}
+
+fn run_mapped_with(source: &str, line_tracking: bool) -> (String, Option<i32>) {
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let c_code = CodeGenerator::new()
+        .with_source("prog.mini", source)
+        .with_line_tracking(line_tracking)
+        .generate(&program)
+        .unwrap();
+    
+    let temp_dir = TempDir::new().unwrap();
+    let c_file = temp_dir.path().join("test.c");
+    let exe_file = temp_dir.path().join("test");
+    fs::write(&c_file, c_code).unwrap();
+    
+    let output = Command::new("gcc")
+        .arg(&c_file)
+        .arg("-o")
+        .arg(&exe_file)
+        .arg("-std=c99")
+        .arg("-O2")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "GCC failed: {}", String::from_utf8_lossy(&output.stderr));
+    
+    let output = Command::new(&exe_file).output().unwrap();
+    (String::from_utf8_lossy(&output.stderr).to_string(), output.status.code())
+}
+
+#[test]
+fn test_source_map_lines() {
+    let source = "func main() {\n    let x: int = 1;\n\n    display x;\n}\n";
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    
+    let mut codegen = CodeGenerator::new().with_source("prog.mini", source);
+    let c_code = codegen.generate(&program).unwrap();
+    assert!(c_code.contains("#line 2 \"prog.mini\""));
+    assert!(c_code.contains("#line 4 \"prog.mini\""));
+    
+    // Each entry points at the C line generated for that MiniLang line
+    let c_lines: Vec<&str> = c_code.lines().collect();
+    let entries = codegen.source_map();
+    assert_eq!(entries.iter().map(|e| e.source_line).collect::<Vec<_>>(), vec![1, 2, 4]);
+    assert!(c_lines[entries[1].c_line - 1].contains("int ml_x = 1;"));
+    assert!(c_lines[entries[2].c_line - 1].contains("printf"));
+    
+    // Without a source the output is unchanged
+    let plain = CodeGenerator::new().generate(&program).unwrap();
+    assert!(!plain.contains("#line"));
+    assert!(!plain.contains("_minilang_line"));
+}
+
+#[test]
+fn test_bounds_error_reports_minilang_line() {
+    let source = "func main() {\n    let arr: int[3] = [1, 2, 3];\n    let i: int = 3;\n    display arr[i];\n}\n";
+    let (stderr, code) = run_mapped(source);
+    
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("out of bounds"), "{}", stderr);
+    assert!(stderr.contains("at prog.mini:4"), "{}", stderr);
+}
+
+#[test]
+fn test_division_trap_reports_minilang_line() {
+    let source = "func divide(a: int, b: int) -> int {\n    send a / b;\n}\n\nfunc main() {\n    let zero: int = 0;\n    display divide(10, zero);\n}\n";
+    let (stderr, code) = run_mapped(source);
+    
+    assert!(code.unwrap_or(0) > 128, "should exit through the trap handler");
+    assert!(stderr.contains("division by zero"), "{}", stderr);
+    assert!(stderr.contains("at prog.mini:2"), "{}", stderr);
+}
+
+#[test]
+fn test_untracked_trap_names_only_the_file() {
+    let source = "func divide(a: int, b: int) -> int {\n    send a / b;\n}\n\nfunc main() {\n    let zero: int = 0;\n    display divide(10, zero);\n}\n";
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let c_code = CodeGenerator::new()
+        .with_source("prog.mini", source)
+        .with_line_tracking(false)
+        .generate(&program)
+        .unwrap();
+    // Optimized builds keep the #line directives but store nothing per statement
+    assert!(c_code.contains("#line 2 \"prog.mini\""));
+    assert!(!c_code.contains("_minilang_line"));
+    
+    let (stderr, code) = run_mapped_with(source, false);
+    assert!(code.unwrap_or(0) > 128, "should exit through the trap handler");
+    assert!(stderr.contains("division by zero"), "{}", stderr);
+    assert!(stderr.contains("in prog.mini (build with -O0 to see the line)"), "{}", stderr);
+}
+
+#[test]
+fn test_c_mapping_pairs_statements_with_c() {
+    use minilang_compiler::c_mapping::{map_statements, render};
+    
+    let source = "func main() {\n    let total: int = 0;\n    if total == 0 {\n        display \"a string long enough to be cut short in the MiniLang column\";\n    }\n}\n";
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let mut codegen = CodeGenerator::new().with_source("prog.mini", source);
+    let c_code = codegen.generate(&program).unwrap();
+    
+    let statements = map_statements(&c_code, codegen.source_map());
+    assert_eq!(statements.iter().map(|s| s.source_line).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    let c_lines: Vec<&str> = c_code.lines().collect();
+    for statement in &statements {
+        for (line, text) in &statement.c_lines {
+            assert_eq!(c_lines[line - 1].trim_end(), text);
+            assert!(!text.contains("_minilang_line") && !text.contains("#line"), "{}", text);
+            assert_ne!(text.trim(), "}");
+        }
+    }
+    assert!(statements[1].c_lines[0].1.contains("int ml_total = 0;"));
+    assert!(statements[2].c_lines[0].1.contains("if ("));
+    assert!(statements[3].c_lines[0].1.contains("printf"));
+    
+    let rendered = render(source, &statements);
+    let row = rendered.lines().find(|line| line.contains("printf")).unwrap();
+    assert!(row.contains("display \"a string"), "{}", row);
+    let left = &row[..row.find("printf").unwrap()];
+    assert!(!left.contains("MiniLang column"), "long lines are cut short: {}", row);
+    assert!(rendered.lines().any(|line| line.contains("let total: int = 0;") && line.contains("int ml_total = 0;")));
+    println!("✓ Each statement is shown next to the C generated for it");
+}
+
+// ==================== GENERATOR REUSE TESTS ====================
+
+fn parse_checked(source: &str) -> minilang_compiler::Program {
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    TypeChecker::new().check_program(&program).unwrap();
+    program
+}
+
+#[test]
+fn test_one_generator_many_programs() {
+    let mut sources: Vec<&str> = minilang_compiler::examples::EXAMPLES.iter()
+        .map(|example| example.source)
+        .collect();
+    sources.extend([
+        r#"
+func split(n: int) -> (int, bool) {
+    send (n / 2, n % 2 == 0);
+}
+
+func main() {
+    let pair: (int, bool) = split(7);
+    display pair;
+}
+"#,
+        r#"
+func main() {
+    let pair: (int, bool) = (1, true);
+    let xs: float[2] = [0.5, 1.5];
+    defer { display "done"; }
+    display pair, " ", xs;
+}
+"#,
+    ]);
+    
+    let mut shared = CodeGenerator::new();
+    for source in &sources {
+        let program = parse_checked(source);
+        let reused = shared.generate(&program).unwrap();
+        let fresh = CodeGenerator::new().generate(&program).unwrap();
+        assert_eq!(reused, fresh);
+        assert!(run_c(reused).is_ok());
+    }
+    println!("✓ A reused generator gives the same C as a fresh one");
+}
+
+#[test]
+fn test_generate_twice_is_stable() {
+    let source = r#"
+func main() {
+    let xs: int[3] = [1, 2, 3];
+    for let i: int = 0; i < 3; i = i + 1 {
+        display xs[i];
+    }
+}
+"#;
+    let program = parse_checked(source);
+    let mut codegen = CodeGenerator::new()
+        .with_source("prog.mini", source)
+        .with_peephole(true);
+    let first = codegen.generate(&program).unwrap();
+    let first_map = codegen.source_map().to_vec();
+    let first_elided = codegen.bounds_checks_elided();
+    
+    let second = codegen.generate(&program).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(first_map, codegen.source_map());
+    assert_eq!(first_elided, codegen.bounds_checks_elided());
+    assert!(second.contains("#line"), "builder options survive a second generate");
+    println!("✓ Generating the same program twice gives identical output");
+}
+
+// ==================== EVALUATION ORDER TESTS ====================
+
+#[test]
+fn test_operands_evaluate_left_to_right() {
+    let source = r#"
+func trace(label: string, value: int) -> int {
+    displayln label;
+    send value;
+}
+
+func pair(a: int, b: int) -> int {
+    send a * 10 + b;
+}
+
+func fill(values: int[2], v: int) -> int {
+    values[0] = v;
+    send v;
+}
+
+func main() {
+    let values: int[2] = [1, 2];
+    displayln trace("a", 1) + trace("b", 2);
+    displayln pair(trace("c", 3), trace("d", 4));
+    displayln values[0] + fill(values, 7);
+    displayln format("{}{}", trace("e", 5), trace("f", 6));
+    let both = (trace("g", 1), trace("h", 2));
+    let listed: int[2] = [trace("i", 1), trace("j", 2)];
+    values[trace("k", 0)] = trace("l", 9);
+    displayln values[0], listed[1], both;
+}
+"#;
+    let c_code = compile_to_c(source).unwrap();
+    assert!(c_code.contains("_minilang_arg_"), "{}", c_code);
+    assert_eq!(
+        run_c(c_code).unwrap(),
+        "a\nb\n3\nc\nd\n34\n8\ne\nf\n56\ng\nh\ni\nj\nk\nl\n92(1, 2)\n"
+    );
+    println!("✓ Arguments, operands and initializers run left to right");
+}
+
+#[test]
+fn test_no_temporaries_without_side_effects() {
+    // fib never displays or writes an array, so C may evaluate in any order
+    let source = r#"
+func fib(n: int) -> int {
+    if n < 2 {
+        send n;
+    }
+    send fib(n - 1) + fib(n - 2);
+}
+
+pure func twice(x: int) -> int {
+    send x * 2;
+}
+
+func main() {
+    let xs: int[2] = [3, 4];
+    display fib(10) + twice(xs[0]) + xs[1];
+}
+"#;
+    let c_code = compile_to_c(source).unwrap();
+    assert!(!c_code.contains("_minilang_arg_"), "{}", c_code);
+    assert_eq!(run_c(c_code).unwrap(), "65\n");
+    println!("✓ Operands without side effects are emitted as written");
+}
+
+#[test]
+fn test_effects_through_calls() {
+    let program = parse_checked(r#"
+func show(x: int) -> int {
+    display x;
+    send x;
+}
+
+func indirect(x: int) -> int {
+    send show(x) + 1;
+}
+
+func write(values: int[2]) -> int {
+    values[1] = 0;
+    send 0;
+}
+
+func local() -> int {
+    let own: int[2] = [1, 2];
+    own[0] = 5;
+    send own[0];
+}
+
+func main() {
+    display indirect(1), local();
+}
+"#);
+    let effectful = minilang_compiler::effects::effectful_functions(&program, &[]);
+    assert!(effectful.contains("show"));
+    assert!(effectful.contains("indirect"));
+    assert!(effectful.contains("write"));
+    assert!(!effectful.contains("local"), "writing its own array is not visible to callers");
+    println!("✓ Side effects are found directly and through calls");
+}
@@ -0,0 +1,167 @@
+// tests/daemon_tests.rs - The daemon's cache of checked files and its socket
+
+use minilang_compiler::daemon::{self, Cache, CheckOptions, CompileOptions, Request, Response};
+use minilang_compiler::style::Style;
+use minilang_compiler::{Defines, Version};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+const GOOD: &str = "func main() {\n    display 1 + 2;\n}\n";
+const BAD: &str = "func main() {\n    let x: int = \"three\";\n}\n";
+
+fn options(edition: Version) -> CheckOptions {
+    CheckOptions::new(&Defines::new(), edition, Style { color: false, unicode: true })
+}
+
+fn status(cache: &Cache) -> (usize, u64, u64) {
+    match cache.respond(&Request::Status) {
+        Response::Status { files, hits, misses } => (files, hits, misses),
+        other => panic!("expected a status, got {:?}", other),
+    }
+}
+
+// ==================== CACHE TESTS ====================
+
+#[test]
+fn test_cache_reuses_unchanged_files() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("main.mini");
+    fs::write(&file, GOOD).unwrap();
+    let cache = Cache::default();
+
+    let first = cache.load(&file, "main.mini", &options(Version::LATEST));
+    assert_eq!(first.outcome.status, "ok");
+    assert!(first.program.is_some());
+    cache.load(&file, "main.mini", &options(Version::LATEST));
+    assert_eq!(status(&cache), (1, 1, 1));
+
+    // Another edition, then another source, each need a fresh check
+    cache.load(&file, "main.mini", &options(Version::V1_0));
+    fs::write(&file, BAD).unwrap();
+    let changed = cache.load(&file, "main.mini", &options(Version::V1_0));
+    assert_eq!(status(&cache), (1, 1, 3));
+    assert_eq!(changed.outcome.status, "type error");
+    assert_eq!(changed.outcome.errors, 1);
+    assert!(changed.outcome.diagnostics.contains("main.mini:2:5"), "{}", changed.outcome.diagnostics);
+    assert!(changed.program.is_none());
+
+    fs::remove_file(&file).unwrap();
+    let gone = cache.load(&file, "main.mini", &options(Version::V1_0));
+    assert_eq!(gone.outcome.status, "unreadable");
+    assert_eq!(status(&cache).0, 0);
+
+    println!("✓ The cache answers from memory until the file or the options change");
+}
+
+#[test]
+fn test_cache_outcome_matches_a_plain_check() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("bad.mini");
+    fs::write(&file, BAD).unwrap();
+
+    let plain = daemon::check_file(&file, "bad.mini", &options(Version::LATEST));
+    let cached = Cache::default().load(&file, "bad.mini", &options(Version::LATEST)).outcome.clone();
+    assert_eq!(plain, cached);
+
+    println!("✓ A cached check reports what checking the file directly does");
+}
+
+#[test]
+fn test_compile_from_cached_program() {
+    let dir = TempDir::new().unwrap();
+    let good = dir.path().join("good.mini");
+    let bad = dir.path().join("bad.mini");
+    fs::write(&good, GOOD).unwrap();
+    fs::write(&bad, BAD).unwrap();
+    let cache = Cache::default();
+    let compile = CompileOptions { optimization: 1, ..CompileOptions::default() };
+
+    cache.load(&good, "good.mini", &options(Version::LATEST));
+    let (outcome, c_code) = cache.compile(&good, "good.mini", &options(Version::LATEST), &compile);
+    assert_eq!(outcome.status, "ok");
+    let c_code = c_code.expect("a passing file compiles");
+    assert!(c_code.contains("int main("), "{}", c_code);
+    assert_eq!(status(&cache), (1, 1, 1));
+
+    let (outcome, c_code) = cache.compile(&bad, "bad.mini", &options(Version::LATEST), &compile);
+    assert_eq!(outcome.status, "type error");
+    assert!(c_code.is_none());
+
+    println!("✓ Compile requests generate C from the cached typed program");
+}
+
+#[test]
+fn test_requests_are_one_json_line() {
+    let request = Request::Check {
+        file: Path::new("/work/a.mini").to_path_buf(),
+        name: "a.mini".to_string(),
+        options: options(Version::V1_2),
+    };
+    let line = serde_json::to_string(&request).unwrap();
+    assert!(line.starts_with(r#"{"request":"check","#), "{}", line);
+    assert!(!line.contains('\n'));
+    assert_eq!(serde_json::from_str::<Request>(&line).unwrap(), request);
+    assert_eq!(serde_json::to_string(&Request::Stop).unwrap(), r#"{"request":"stop"}"#);
+
+    println!("✓ Requests travel as one JSON object per line");
+}
+
+// ==================== SOCKET TESTS ====================
+
+#[cfg(unix)]
+#[test]
+fn test_check_uses_running_daemon() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.mini"), GOOD).unwrap();
+    fs::write(dir.path().join("b.mini"), BAD).unwrap();
+    let socket = dir.path().join("d.sock");
+    let minilang = env!("CARGO_BIN_EXE_minilang");
+
+    let mut server = Command::new(minilang)
+        .args(["daemon", "--socket"])
+        .arg(&socket)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut ready = String::new();
+    BufReader::new(server.stdout.take().unwrap()).read_line(&mut ready).unwrap();
+    assert!(ready.starts_with("Daemon listening on"), "{}", ready);
+
+    let check = || {
+        Command::new(minilang)
+            .args(["check", "a.mini", "b.mini"])
+            .current_dir(dir.path())
+            .env(daemon::SOCKET_ENV, &socket)
+            .output()
+            .unwrap()
+    };
+    let daemon_status = || {
+        let output = Command::new(minilang).args(["daemon", "--status", "--socket"]).arg(&socket).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let first = check();
+    assert_eq!(first.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&first.stderr).contains("type mismatch"));
+    assert!(daemon_status().contains("Checked afresh: 2"));
+
+    let again = check();
+    assert_eq!(again.status.code(), Some(1));
+    assert_eq!(again.stderr, first.stderr);
+    assert!(daemon_status().contains("Answered from memory: 2"));
+
+    let stopped = Command::new(minilang).args(["daemon", "--stop", "--socket"]).arg(&socket).output().unwrap();
+    assert!(stopped.status.success());
+    assert!(server.wait().unwrap().success());
+    assert!(!socket.exists());
+
+    // With the daemon gone, check works on its own
+    assert_eq!(check().stderr, first.stderr);
+
+    println!("✓ check hands its files to a running daemon and falls back without one");
+}
@@ -0,0 +1,153 @@
+// tests/escape_tests.rs - Strings that never leave their function go on the stack
+
+use minilang_compiler::runtime::RuntimeMode;
+use minilang_compiler::test_utils::{compile_and_run, compile_and_run_with_runtime};
+use minilang_compiler::{CodeGenerator, Lexer, Optimizer, Parser, Pass, Program, TypeChecker};
+
+fn parse(source: &str) -> Program {
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    TypeChecker::new().check_program(&program).unwrap();
+    program
+}
+
+/// The C for `source` with only the stack strings pass, and how many
+/// strings it moved
+fn generate(source: &str, runtime: RuntimeMode) -> (String, usize) {
+    let mut program = parse(source);
+    let stats = Optimizer::with_passes(&[Pass::StackStrings]).optimize(&mut program);
+    let c_code = CodeGenerator::new().with_runtime(runtime).generate(&program).unwrap();
+    (c_code, stats.allocations_elided)
+}
+
+/// The count on a line of the profile report, like `allocations:  12 (96 bytes)`
+fn profile_count(stderr: &str, label: &str) -> usize {
+    let line = stderr.lines()
+        .find(|line| line.starts_with(label))
+        .unwrap_or_else(|| panic!("no '{}' line in:\n{}", label, stderr));
+    line[label.len()..].split_whitespace().next().unwrap().parse().unwrap()
+}
+
+// ==================== ELIDED TESTS ====================
+
+#[test]
+fn test_local_strings_go_on_the_stack() {
+    let source = r#"
+func main() {
+    let n: int = 7;
+    let s: string = "n={n}";
+    const t: string = format("{}+{}", n, 1);
+    display s, " ", t;
+    display to_string(n);
+    display parse_int(to_string(n)) + 1;
+}
+"#;
+    let (c_code, elided) = generate(source, RuntimeMode::Arena);
+    assert_eq!(elided, 4);
+    assert!(c_code.contains("const char* ml_s = _minilang_format_into(&_minilang_buf_0, \"n=%d\", ml_n);"), "{}", c_code);
+    assert!(c_code.contains("_minilang_format_into(&_minilang_buf_1, \"%d+%d\", ml_n, 1)"), "{}", c_code);
+    assert!(c_code.contains("_minilang_format_into(&_minilang_buf_2, \"%d\", ml_n)"), "{}", c_code);
+    assert!(!c_code.contains("_minilang_format(\"n=") && !c_code.contains("_minilang_int_to_string(ml_n)"), "{}", c_code);
+    println!("✓ Strings that are only printed or read go on the stack");
+}
+
+#[test]
+fn test_display_of_interpolation_is_not_counted() {
+    // Printed part by part already, so there is nothing to move
+    let source = "func main() {\n    let n: int = 1;\n    display \"n={n}\";\n}\n";
+    let (c_code, elided) = generate(source, RuntimeMode::Arena);
+    assert_eq!(elided, 0);
+    assert!(!c_code.contains("_minilang_stack_string"));
+    println!("✓ Displayed interpolations are left alone");
+}
+
+#[test]
+fn test_format_template_stays_a_literal() {
+    // Only the result can move; the template has to reach the code generator as written
+    let source = "func main() {\n    let i: int = 3;\n    let s: string = format(\"v{i}\");\n    display s;\n}\n";
+    let (c_code, elided) = generate(source, RuntimeMode::Arena);
+    assert_eq!(elided, 1, "{}", c_code);
+    if let Some(output) = compile_and_run(source, 2) {
+        assert_eq!(output.exit_code, Some(0), "{}", output.stderr);
+        assert_eq!(output.stdout, "v3\n");
+    }
+    println!("✓ An interpolated format() template is not moved to the stack");
+}
+
+// ==================== ESCAPING TESTS ====================
+
+#[test]
+fn test_escaping_strings_stay_on_the_heap() {
+    let source = r#"
+func keep(s: string) -> string {
+    send s;
+}
+
+func returned(n: int) -> string {
+    let s: string = "r{n}";
+    send s;
+}
+
+func main() {
+    let n: int = 2;
+    let names: string[2];
+    names[0] = to_string(n);
+    let x: string = format("x{}", n);
+    names[1] = x;
+    display keep("k{n}"), returned(n), names;
+    let y: string = "y{n}";
+    y = "{y}!";
+    display y;
+    let (a, b) = (n, to_string(n));
+    display a, b;
+}
+"#;
+    let (c_code, elided) = generate(source, RuntimeMode::Arena);
+    // Every allocation here outlives the statement that made it
+    assert_eq!(elided, 0, "{}", c_code);
+    assert!(!c_code.contains("_minilang_format_into"));
+    println!("✓ Returned, passed, stored and reassigned strings stay on the heap");
+}
+
+#[test]
+fn test_noopt_functions_are_skipped() {
+    let source = "@noopt\nfunc main() {\n    let n: int = 1;\n    let s: string = \"n={n}\";\n    display s;\n}\n";
+    let (_, elided) = generate(source, RuntimeMode::Arena);
+    assert_eq!(elided, 0);
+    println!("✓ @noopt functions keep their heap strings");
+}
+
+// ==================== RUNTIME TESTS ====================
+
+const LOOP: &str = r#"
+func main() {
+    let total: int = 0;
+    for let i: int = 0; i < 500; i = i + 1 {
+        let s: string = "value {i}";
+        total = total + parse_int(to_string(i));
+        if i % 250 == 0 {
+            display s;
+        }
+    }
+    display total;
+    let long: int = 1234567890;
+    let big: string = "{long}{long}{long}{long}{long}{long}{long}{long}";
+    display big;
+}
+"#;
+
+#[test]
+fn test_stack_strings_cut_heap_allocations() {
+    for runtime in [RuntimeMode::Arena, RuntimeMode::Rc, RuntimeMode::None] {
+        let Some(o0) = compile_and_run_with_runtime(LOOP, 0, runtime, true) else { return };
+        let o2 = compile_and_run_with_runtime(LOOP, 2, runtime, true).unwrap();
+        assert_eq!(o0.exit_code, Some(0), "{}", o0.stderr);
+        assert_eq!(o2.stdout, o0.stdout, "--runtime={}", runtime.name());
+        assert!(o0.stdout.ends_with("124750\n12345678901234567890123456789012345678901234567890123456789012345678901234567890\n"), "{}", o0.stdout);
+
+        assert_eq!(profile_count(&o0.stderr, "allocations:"), 1001, "{}", o0.stderr);
+        // Only the string too long for its buffer reaches the heap
+        assert_eq!(profile_count(&o2.stderr, "allocations:"), 1, "{}", o2.stderr);
+    }
+    println!("✓ -O2 keeps loop strings off the heap and prints the same");
+}
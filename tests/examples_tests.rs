@@ -0,0 +1,79 @@
+// tests/examples_tests.rs - The embedded examples double as end-to-end smoke tests
+
+use minilang_compiler::examples::{find_example, EXAMPLES};
+use minilang_compiler::{CodeGenerator, Lexer, Optimizer, Parser, TypeChecker};
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Run an example through the same pipeline as `minilang run`
+fn compile_example(source: &str) -> String {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("example should tokenize");
+    let mut parser = Parser::new(tokens, source.to_string());
+    let mut program = parser.parse_program().expect("example should parse");
+    
+    let mut type_checker = TypeChecker::new();
+    type_checker.check_program(&program).expect("example should type check");
+    
+    Optimizer::new(1).optimize(&mut program);
+    
+    let mut codegen = CodeGenerator::new();
+    codegen.generate(&program).expect("example should generate C")
+}
+
+fn run_c(c_code: &str) -> String {
+    let temp_dir = TempDir::new().unwrap();
+    let c_file = temp_dir.path().join("example.c");
+    let exe_file = temp_dir.path().join("example");
+    fs::write(&c_file, c_code).unwrap();
+    
+    let output = Command::new("gcc")
+        .arg(&c_file)
+        .arg("-o")
+        .arg(&exe_file)
+        .arg("-std=c99")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "GCC failed: {}", String::from_utf8_lossy(&output.stderr));
+    
+    let output = Command::new(&exe_file).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn test_examples_have_unique_names() {
+    for (i, example) in EXAMPLES.iter().enumerate() {
+        assert!(!example.source.is_empty());
+        assert!(EXAMPLES[i + 1..].iter().all(|other| other.name != example.name));
+    }
+    
+    assert_eq!(find_example("factorial").unwrap().title, "Factorial");
+    assert_eq!(find_example("factorial.mini").unwrap().name, "factorial");
+    assert!(find_example("missing").is_none());
+    
+    println!("✓ Examples are registered under unique names");
+}
+
+#[test]
+fn test_every_example_runs() {
+    let expected: &[(&str, &[&str])] = &[
+        ("hello", &["Hello, MiniLang!", "Welcome to the compiler playground!"]),
+        ("fibonacci", &["0", "13", "55"]),
+        ("factorial", &["5! = ", "120"]),
+        ("bubble_sort", &["Before: ", "After : "]),
+        ("prime_numbers", &["2 3 5 7", "97", "Total: ", "25", " primes"]),
+    ];
+    assert_eq!(expected.len(), EXAMPLES.len(), "every example needs expected output");
+    
+    for (name, fragments) in expected {
+        let example = find_example(name).unwrap();
+        let output = run_c(&compile_example(example.source));
+        for fragment in *fragments {
+            assert!(output.contains(fragment), "{}: missing {:?} in output:\n{}", name, fragment, output);
+        }
+    }
+    
+    println!("✓ All {} examples compile and run", EXAMPLES.len());
+}
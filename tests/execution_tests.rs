@@ -0,0 +1,378 @@
+// tests/execution_tests.rs - Compile programs to executables and check what they do
+//
+// Every program is built at each optimization level, so an optimization that
+// changes behavior fails here. Without a working C compiler (see
+// `test_utils::c_compiler`) the tests pass without running anything.
+
+use minilang_compiler::test_utils::{compile_and_run, RunOutput};
+
+/// Build and run `source` at `-O0` to `-O2`; `None` when there is no C compiler
+fn run_at_all_levels(source: &str) -> Option<Vec<RunOutput>> {
+    (0..=2).map(|level| compile_and_run(source, level)).collect()
+}
+
+/// Check that `source` prints `stdout` and exits with `exit_code` at every level
+fn assert_runs(source: &str, stdout: &str, exit_code: i32) {
+    let Some(runs) = run_at_all_levels(source) else { return };
+    for (level, run) in runs.iter().enumerate() {
+        assert_eq!(run.stdout, stdout, "-O{}: {}", level, run.stderr);
+        assert_eq!(run.exit_code, Some(exit_code), "-O{}: {}", level, run.stderr);
+    }
+}
+
+// ==================== OUTPUT TESTS ====================
+
+#[test]
+fn test_display_output() {
+    assert_runs(r#"
+func main() {
+    let count: int = 3;
+    let ratio: float = 1.5;
+    let name: string = "item {count}";
+    display count, ratio, name, count > 2;
+    displayln count, " and ", name;
+    display_raw "no newline";
+    display "";
+}
+"#, "31.500000item 3true\n3 and item 3\nno newline\n", 0);
+    println!("✓ display, displayln and display_raw print as documented");
+}
+
+#[test]
+fn test_arithmetic_matches_c() {
+    // Negative division truncates toward zero, also after strength reduction
+    assert_runs(r#"
+func main() {
+    let x: int = -7;
+    displayln x / 4, " ", x % 4, " ", x * 8, " ", 2147483647 + 1;
+}
+"#, "-1 -3 -56 -2147483648\n", 0);
+    println!("✓ Int arithmetic truncates and wraps like C at every level");
+}
+
+// ==================== CONTROL FLOW TESTS ====================
+
+#[test]
+fn test_loops_and_recursion() {
+    assert_runs(r#"
+func fib(n: int) -> int {
+    if n < 2 {
+        send n;
+    }
+    send fib(n - 1) + fib(n - 2);
+}
+
+func main() {
+    let total: int = 0;
+    for let i: int = 0; i < 10; i = i + 1 {
+        if i % 2 == 0 {
+            continue;
+        }
+        if i > 7 {
+            break;
+        }
+        total = total + i;
+    }
+    displayln total, " ", fib(15);
+}
+"#, "16 610\n", 0);
+    println!("✓ Loops, break, continue and recursion run correctly");
+}
+
+#[test]
+fn test_defer_runs_on_return() {
+    assert_runs(r#"
+func main() -> int {
+    defer {
+        display "cleanup";
+    }
+    display "work";
+    send 3;
+}
+"#, "work\ncleanup\n", 3);
+    println!("✓ Deferred blocks run before main returns its exit code");
+}
+
+#[test]
+fn test_left_to_right_evaluation() {
+    assert_runs(r#"
+func trace(label: string, value: int) -> int {
+    display_raw label;
+    send value;
+}
+
+func main() {
+    let sum: int = trace("a", 1) + trace("b", 2) * trace("c", 3);
+    displayln " ", sum, " ", max(trace("d", 4), trace("e", 5));
+}
+
+func max(a: int, b: int) -> int {
+    if a > b {
+        send a;
+    }
+    send b;
+}
+"#, "abc 7 de5\n", 0);
+    println!("✓ Operands and arguments run left to right at every level");
+}
+
+#[test]
+fn test_loops_that_return() {
+    assert_runs(r#"
+func first(x: int) -> int {
+    do {
+        send x;
+    } while x > 0;
+}
+
+func grow(x: int) -> int {
+    while true {
+        if x > 10 {
+            send x;
+        }
+        x = x * 2;
+    }
+}
+
+func main() {
+    do {
+        display "once";
+    } while false;
+    displayln first(-4), " ", grow(3);
+}
+"#, "once\n-4 12\n", 0);
+    println!("✓ Functions that return from inside loops run correctly");
+}
+
+// ==================== ARRAY TESTS ====================
+
+#[test]
+fn test_arrays() {
+    assert_runs(r#"
+func sum(values: int[4]) -> int {
+    let total: int = 0;
+    for let i: int = 0; i < 4; i = i + 1 {
+        total = total + values[i];
+    }
+    send total;
+}
+
+func main() {
+    let values: int[4] = [1, 2, 3, 4];
+    values[2] = 10;
+    displayln sum(values), " ", values;
+}
+"#, "17 [1, 2, 10, 4]\n", 0);
+    println!("✓ Arrays are read, written and passed correctly");
+}
+
+#[test]
+fn test_out_of_bounds_index_exits() {
+    let source = r#"
+func get(values: int[3], i: int) -> int {
+    send values[i];
+}
+
+func main() {
+    let values: int[3] = [1, 2, 3];
+    display get(values, 1);
+    display get(values, 5);
+}
+"#;
+    let Some(runs) = run_at_all_levels(source) else { return };
+    for (level, run) in runs.iter().enumerate() {
+        assert_eq!(run.stdout, "2\n", "-O{}", level);
+        assert_eq!(run.exit_code, Some(1), "-O{}", level);
+        assert!(run.stderr.contains("Array index 5 out of bounds (size 3)"), "-O{}: {}", level, run.stderr);
+    }
+    println!("✓ Out-of-bounds indexes stop the program with an error");
+}
+
+#[test]
+fn test_memo_functions() {
+    // Far too slow without the cache
+    let source = r#"
+@memo
+pure func fib(n: int) -> int {
+    if n < 2 { send n; }
+    send fib(n - 1) + fib(n - 2);
+}
+
+@memo
+pure func paths(rows: int, cols: int) -> int {
+    if rows == 0 OR cols == 0 { send 1; }
+    send paths(rows - 1, cols) + paths(rows, cols - 1);
+}
+
+@memo
+pure func scale(x: float, twice: bool) -> float {
+    if twice { send x * 2.0; }
+    send x;
+}
+
+func main() {
+    display fib(45);
+    display paths(16, 16);
+    display scale(1.5, true), " ", scale(1.5, false);
+}
+"#;
+    let Some(runs) = run_at_all_levels(source) else { return };
+    for (level, run) in runs.iter().enumerate() {
+        assert_eq!(run.stdout, "1134903170\n601080390\n3.000000 1.500000\n", "-O{}: {}", level, run.stderr);
+    }
+    println!("✓ @memo functions compute each result once");
+}
+
+#[test]
+fn test_failed_assert_exits() {
+    let source = r#"
+func main() {
+    let n: int = 3;
+    assert(n * 2 == 6);
+    display "checked";
+    assert(n * 2 == 7);
+    display "unreachable";
+}
+"#;
+    let Some(runs) = run_at_all_levels(source) else { return };
+    for (level, run) in runs.iter().enumerate() {
+        assert_eq!(run.stdout, "checked\n", "-O{}", level);
+        assert_eq!(run.exit_code, Some(1), "-O{}", level);
+        // The condition as written, even after the optimizer has folded it
+        assert!(run.stderr.contains("assertion failed: n * 2 == 7"), "-O{}: {}", level, run.stderr);
+    }
+    println!("✓ A failed assert stops the program with its condition");
+}
+
+#[test]
+fn test_spawn_and_channels() {
+    let source = r#"
+func produce(out: chan, from: int, count: int) {
+    for let i: int = 0; i < count; i = i + 1 {
+        chan_send(out, from + i);
+    }
+}
+
+func main() {
+    let numbers: chan = channel(2);
+    let squares: chan = channel(2);
+    {
+        spawn { produce(numbers, 1, 50); }
+        spawn {
+            for let i: int = 0; i < 50; i = i + 1 {
+                let n: int = chan_receive(numbers);
+                chan_send(squares, n * n);
+            }
+        }
+        let total: int = 0;
+        for let i: int = 0; i < 50; i = i + 1 {
+            total = total + chan_receive(squares);
+        }
+        display total;
+    }
+    display "joined";
+}
+"#;
+    assert_runs(source, "42925\njoined\n", 0);
+    println!("✓ Spawned threads pass values through channels in order");
+}
+
+#[test]
+fn test_parallel_for_with_atomics() {
+    let source = r#"
+func main() {
+    let weights: int[4] = [1, 2, 3, 4];
+    let total: atomic = atomic(0);
+    let evens: atomic = atomic(0);
+    parallel for i in 0..1000 {
+        let value: int = i * weights[i % 4];
+        atomic_add(total, value);
+        if i % 2 == 0 {
+            atomic_add(evens, 1);
+        }
+    }
+    parallel for i in 5..5 {
+        display "never";
+    }
+    display atomic_get(total);
+    display atomic_get(evens);
+}
+"#;
+    assert_runs(source, "1250000\n500\n", 0);
+    println!("✓ Parallel for iterations all run and add up through atomics");
+}
+
+#[test]
+fn test_sleep_and_now() {
+    let source = r#"
+func main() {
+    let start: int = now_ms();
+    for let frame: int = 0; frame < 3; frame = frame + 1 {
+        display_raw "frame {frame} ";
+        sleep_ms(10);
+    }
+    sleep_ms(-1);
+    let elapsed: int = now_ms() - start;
+    display "";
+    display elapsed >= 30 AND elapsed < 10000;
+}
+"#;
+    assert_runs(source, "frame 0 frame 1 frame 2 \ntrue\n", 0);
+    println!("✓ sleep_ms waits and now_ms counts the milliseconds");
+}
+
+#[test]
+fn test_canvas_runtime_errors() {
+    for (call, message) in [
+        ("canvas_set(c, 3, 0, \"x\")", "cell (3, 0) is outside the 3x2 canvas"),
+        ("canvas_set(c, 0, 0, \"\")", "a canvas cell holds one character, not \"\""),
+        ("canvas_fill(c, \"éé\")", "a canvas cell holds one character, not \"éé\""),
+    ] {
+        let source = format!("func main() {{\n    let c: canvas = canvas(3, 2);\n    canvas_fill(c, \"é\");\n    {};\n    canvas_render(c);\n}}\n", call);
+        let Some(runs) = run_at_all_levels(&source) else { return };
+        for (level, run) in runs.iter().enumerate() {
+            assert_eq!(run.exit_code, Some(1), "-O{}", level);
+            assert!(run.stderr.contains(message), "-O{}: {}", level, run.stderr);
+            assert!(run.stderr.contains(":4"), "-O{}: {}", level, run.stderr);
+            assert_eq!(run.stdout, "");
+        }
+    }
+    println!("✓ Drawing outside a canvas or more than one character stops the program");
+}
+
+#[test]
+fn test_json_runtime_errors() {
+    for (call, message) in [
+        ("json_parse(\"[1 2]\")", "invalid json at byte 3"),
+        ("json_get(j, \"b\")", "json object has no key \"b\""),
+        ("json_at(json_get(j, \"a\"), 3)", "json index 3 out of bounds (size 2)"),
+        ("json_get(json_get(j, \"a\"), \"x\")", "expected a json object, found array"),
+        ("json_at(json_get(j, \"a\"), 0)", "expected a json array or object, found number"),
+    ] {
+        let source = format!("func main() {{\n    let j: json = json {{\"a\": [1, 2]}};\n    let k: json = {};\n    display json_len(k);\n}}\n", call);
+        let Some(runs) = run_at_all_levels(&source) else { return };
+        for (level, run) in runs.iter().enumerate() {
+            assert_eq!(run.exit_code, Some(1), "-O{}", level);
+            assert!(run.stderr.contains(message), "-O{}: {}", level, run.stderr);
+            assert_eq!(run.stdout, "");
+        }
+    }
+    println!("✓ Bad json text, missing keys and the wrong kind of value stop the program");
+}
+
+#[test]
+fn test_channel_capacity_must_be_positive() {
+    let source = r#"
+func main() {
+    let size: int = 0;
+    let c: chan = channel(size);
+    display "unreachable";
+}
+"#;
+    let Some(runs) = run_at_all_levels(source) else { return };
+    for (level, run) in runs.iter().enumerate() {
+        assert_eq!(run.exit_code, Some(1), "-O{}", level);
+        assert!(run.stderr.contains("channel capacity must be at least 1"), "-O{}: {}", level, run.stderr);
+    }
+    println!("✓ channel(0) stops the program");
+}
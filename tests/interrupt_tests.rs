@@ -0,0 +1,34 @@
+// tests/interrupt_tests.rs - Ctrl-C handling for the programs `run` starts
+//
+// Everything is in one test: the handler and the interrupted flag belong to
+// the whole test process.
+#![cfg(unix)]
+
+use minilang_compiler::interrupt;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_interrupt_kills_running_child() {
+    interrupt::install();
+    interrupt::install();
+
+    let mut child = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+    let status = interrupt::wait(&mut child).unwrap().expect("not interrupted yet");
+    assert_eq!(status.code(), Some(3));
+    assert!(!interrupt::interrupted());
+
+    let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+    let started = Instant::now();
+    let signalled = Command::new("kill")
+        .args(["-INT", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(signalled.success());
+
+    assert_eq!(interrupt::wait(&mut child).unwrap(), None);
+    assert!(interrupt::interrupted());
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(child.try_wait().unwrap().is_some(), "child should have been reaped");
+    println!("✓ Ctrl-C stops the child instead of the compiler");
+}
@@ -28,18 +28,10 @@ fn assert_lexer_success(source: &str) {
 // ==================== UNEXPECTED CHARACTER ERRORS ====================
 
 #[test]
-fn test_error_at_symbol() {
-    let source = "let x = 42 @ 13;";
-    
-    assert_lexer_error(source, |err| {
-        match err {
-            LexerError::UnexpectedChar { char, .. } => {
-                assert_eq!(char, '@');
-                println!("✓ Correctly detected @ as invalid character");
-            }
-            _ => panic!("Expected UnexpectedChar error, got: {:?}", err),
-        }
-    });
+fn test_at_symbol_is_valid() {
+    // '@' introduces function attributes
+    assert_lexer_success("@inline func main() {}");
+    println!("✓ @ accepted as attribute marker");
 }
 
 #[test]
@@ -255,12 +247,12 @@ fn test_error_unterminated_string_with_content() {
 
 #[test]
 fn test_error_at_start_of_file() {
-    let source = "@func main() {}";
+    let source = "$func main() {}";
     
     assert_lexer_error(source, |err| {
         match err {
             LexerError::UnexpectedChar { char, span } => {
-                assert_eq!(char, '@');
+                assert_eq!(char, '$');
                 assert_eq!(span.offset(), 0, "Error should be at position 0");
                 println!("✓ Correctly detected error at start of file");
             }
@@ -271,12 +263,12 @@ fn test_error_at_start_of_file() {
 
 #[test]
 fn test_error_at_end_of_file() {
-    let source = "let x = 42;@";
+    let source = "let x = 42;$";
     
     assert_lexer_error(source, |err| {
         match err {
             LexerError::UnexpectedChar { char, .. } => {
-                assert_eq!(char, '@');
+                assert_eq!(char, '$');
                 println!("✓ Correctly detected error at end of file");
             }
             _ => panic!("Expected UnexpectedChar error"),
@@ -286,13 +278,13 @@ fn test_error_at_end_of_file() {
 
 #[test]
 fn test_error_with_leading_whitespace() {
-    let source = "let x =     @;";
+    let source = "let x =     $;";
     
     assert_lexer_error(source, |err| {
         match err {
             LexerError::UnexpectedChar { char, .. } => {
-                assert_eq!(char, '@');
-                println!("✓ Correctly detected @ after whitespace");
+                assert_eq!(char, '$');
+                println!("✓ Correctly detected $ after whitespace");
             }
             _ => panic!("Expected UnexpectedChar error"),
         }
@@ -301,13 +293,13 @@ fn test_error_with_leading_whitespace() {
 
 #[test]
 fn test_error_in_middle_of_line() {
-    let source = "let x = 10 + @ - 5;";
+    let source = "let x = 10 + $ - 5;";
     
     assert_lexer_error(source, |err| {
         match err {
             LexerError::UnexpectedChar { char, .. } => {
-                assert_eq!(char, '@');
-                println!("✓ Correctly detected @ in middle of expression");
+                assert_eq!(char, '$');
+                println!("✓ Correctly detected $ in middle of expression");
             }
             _ => panic!("Expected UnexpectedChar error"),
         }
@@ -318,11 +310,11 @@ fn test_error_in_middle_of_line() {
 
 #[test]
 fn test_error_priority_invalid_char_before_unterminated_string() {
-    let source = r#"let x = 42 @ "unterminated"#;
+    let source = r#"let x = 42 $ "unterminated"#;
     let mut lexer = Lexer::new(source);
     let result = lexer.tokenize();
     
-    // Should detect AN error (either @ or unterminated string)
+    // Should detect AN error (either $ or unterminated string)
     assert!(result.is_err(), "Should detect an error");
     println!("✓ Error detected in source with multiple issues");
 }
@@ -343,12 +335,12 @@ fn test_error_priority_unterminated_string_before_invalid_char() {
 
 #[test]
 fn test_multiple_errors_stops_at_first() {
-    let source = "let x = @ $ % & *;";
+    let source = "let x = $ ~ % & *;";
     
     assert_lexer_error(source, |err| {
         match err {
             LexerError::UnexpectedChar { char, .. } => {
-                assert_eq!(char, '@');
+                assert_eq!(char, '$');
                 println!("✓ Correctly stops at first error");
             }
             _ => panic!("Expected UnexpectedChar error"),
@@ -511,7 +503,7 @@ fn test_error_across_multiple_lines() {
     let source = r#"
 func main() {
     let x = 42;
-    let y = @;
+    let y = $;
     let z = 13;
 }
 "#;
@@ -519,7 +511,7 @@ func main() {
     assert_lexer_error(source, |err| {
         match err {
             LexerError::UnexpectedChar { char, .. } => {
-                assert_eq!(char, '@');
+                assert_eq!(char, '$');
                 println!("✓ Error detected across multiple lines");
             }
             _ => panic!("Expected UnexpectedChar error"),
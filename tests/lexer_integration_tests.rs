@@ -111,7 +111,7 @@ fn test_array_declaration() {
 fn test_all_data_types() {
     let source = r#"
 let i: int = 42;
-let f: float = 3.14;
+let f: float = 2.75;
 let s: string = "hello";
 let b: bool = true;
 "#;
@@ -126,7 +126,7 @@ let b: bool = true;
     
     // Verify all literal types
     assert!(tokens.contains(&Token::Integer(42)));
-    assert!(tokens.contains(&Token::Float(3.14)));
+    assert!(tokens.contains(&Token::Float(2.75)));
     assert!(tokens.contains(&Token::String("hello".to_string())));
     assert!(tokens.contains(&Token::True));
     
@@ -233,11 +233,11 @@ fn test_whitespace_handling() {
 
 #[test]
 fn test_negative_numbers() {
-    let source = "let x = -42; let y = -3.14;";
+    let source = "let x = -42; let y = -2.75;";
     let tokens = tokenize(source);
     
     assert!(tokens.contains(&Token::Integer(-42)));
-    assert!(tokens.contains(&Token::Float(-3.14)));
+    assert!(tokens.contains(&Token::Float(-2.75)));
     
     println!("✓ Negative numbers tokenize correctly");
 }
@@ -12,7 +12,7 @@ fn test_integers() {
 
 #[test]
 fn test_floats() {
-    assert_eq!(tokenize("3.14").unwrap(), vec![Token::Float(3.14)]);
+    assert_eq!(tokenize("2.75").unwrap(), vec![Token::Float(2.75)]);
     assert_eq!(tokenize("-2.5").unwrap(), vec![Token::Float(-2.5)]);
 }
 
@@ -31,7 +31,7 @@ fn test_strings() {
 
 #[test]
 fn test_keywords() {
-    let tokens = tokenize("let func if else while for const break continue").unwrap();
+    let tokens = tokenize("let func if else while for const break continue defer").unwrap();
     assert_eq!(tokens, vec![
         Token::Let,
         Token::Func,
@@ -42,6 +42,7 @@ fn test_keywords() {
         Token::Const,
         Token::Break,
         Token::Continue,
+        Token::Defer,
     ]);
 }
 
@@ -67,6 +68,31 @@ fn test_identifiers() {
     ]);
 }
 
+#[test]
+fn test_keywords_are_whole_words() {
+    // Keywords are matched by the lexer's state machine along with
+    // identifiers, so a keyword only wins when it is the whole word
+    let keywords = [
+        "let", "const", "func", "display", "displayln", "display_raw", "send", "if", "else",
+        "while", "do", "for", "true", "false", "AND", "OR", "NOT", "break", "continue", "defer",
+        "int", "float", "string", "bool",
+    ];
+    for keyword in keywords {
+        let tokens = tokenize(keyword).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert!(!matches!(tokens[0], Token::Identifier(_)), "{} lexed as {:?}", keyword, tokens[0]);
+        
+        for word in [format!("{}x", keyword), format!("_{}", keyword), format!("{}_1", keyword)] {
+            assert_eq!(tokenize(&word).unwrap(), vec![Token::Identifier(word.clone())]);
+        }
+    }
+    // Keywords are case-sensitive
+    assert_eq!(tokenize("and Let").unwrap(), vec![
+        Token::Identifier("and".to_string()),
+        Token::Identifier("Let".to_string()),
+    ]);
+}
+
 #[test]
 fn test_operators() {
     let tokens = tokenize("+ - * / % =").unwrap();
@@ -178,15 +204,26 @@ fn test_function_declaration() {
     ]);
 }
 
+#[test]
+fn test_display_keywords() {
+    let tokens = tokenize("display displayln display_raw display_rows").unwrap();
+    assert_eq!(tokens, vec![
+        Token::Display,
+        Token::DisplayLn,
+        Token::DisplayRaw,
+        Token::Identifier("display_rows".to_string()),
+    ]);
+}
+
 #[test]
 fn test_invalid_character() {
     use minilang_compiler::test_utils::expect_error;
     use minilang_compiler::LexerError;
     
-    let err = expect_error("42 @ 13");
+    let err = expect_error("42 $ 13");
     match err {
         LexerError::UnexpectedChar { char, .. } => {
-            assert_eq!(char, '@');
+            assert_eq!(char, '$');
         }
         _ => panic!("Wrong error type"),
     }
@@ -212,4 +249,62 @@ fn test_unterminated_string() {
         }
         _ => panic!("Wrong error type"),
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_comments_collected_as_trivia() {
+    use minilang_compiler::{Comment, Lexer};
+    
+    let source = "let s = \"#1\"; # note\n## block\n ## let y = 2;\n##";
+    let comments = Lexer::new(source).comments();
+    let texts: Vec<&str> = comments.iter().map(|c| &source[c.span.clone()]).collect();
+    assert_eq!(texts, vec!["# note", "## block\n ##", "##"]);
+    assert_eq!(comments[0], Comment { span: 14..20, block: false });
+    assert!(comments[1].block);
+    // An unclosed block comment runs to the end of the source
+    assert!(comments[2].block);
+    
+    println!("✓ Comments are collected outside the token stream");
+}
+
+#[test]
+fn test_spans_index_original_source_after_block_comment() {
+    use minilang_compiler::Lexer;
+    
+    let source = "## é ##\nlet x";
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let lexemes: Vec<&str> = tokens.iter().map(|t| &source[t.span.clone()]).collect();
+    assert_eq!(lexemes, vec!["let", "x"]);
+    
+    println!("✓ Token spans stay exact after block comments");
+}
+
+#[test]
+fn test_token_kind_and_category() {
+    assert_eq!(Token::Identifier("x".to_string()).kind(), "Identifier");
+    assert_eq!(Token::LeftParen.kind(), "LeftParen");
+    assert_eq!(Token::While.category(), "keyword");
+    assert_eq!(Token::TypeInt.category(), "type");
+    assert_eq!(Token::Integer(1).category(), "literal");
+    assert_eq!(Token::True.category(), "literal");
+    assert_eq!(Token::And.category(), "operator");
+    assert_eq!(Token::Semicolon.category(), "punctuation");
+    
+    println!("✓ Tokens report their kind and category");
+}
+
+#[test]
+fn test_line_index_line_col() {
+    use minilang_compiler::{LineCol, LineIndex};
+    
+    let source = "let a\n  é b\n";
+    let lines = LineIndex::new(source);
+    assert_eq!(lines.line_col(source, 0), LineCol { line: 1, column: 1 });
+    assert_eq!(lines.line_col(source, 5), LineCol { line: 1, column: 6 });
+    assert_eq!(lines.line_col(source, 6), LineCol { line: 2, column: 1 });
+    // Columns count characters, so `b` follows the two-byte `é` at column 5
+    assert_eq!(lines.line_col(source, source.find('b').unwrap()), LineCol { line: 2, column: 5 });
+    assert_eq!(lines.line_of(source.len()), 3);
+    
+    println!("✓ LineIndex maps byte offsets to lines and columns");
+}
@@ -0,0 +1,212 @@
+// tests/module_tests.rs - Compiling modules separately and linking them
+
+use minilang_compiler::backend::{self, GCC_FLAGS};
+use minilang_compiler::{CodeGenerator, Function, Lexer, Parser, Program, SemanticError, TypeChecker};
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn parse(source: &str) -> Program {
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    Parser::new(tokens, source.to_string()).parse_program().unwrap()
+}
+
+/// Every function defined by the modules other than `index`
+fn externals(programs: &[Program], index: usize) -> Vec<Function> {
+    programs.iter()
+        .enumerate()
+        .filter(|(other, _)| *other != index)
+        .flat_map(|(_, program)| program.functions.iter().cloned())
+        .collect()
+}
+
+fn check(programs: &[Program], index: usize) -> Result<(), Vec<SemanticError>> {
+    TypeChecker::new()
+        .with_externals(&externals(programs, index))
+        .check_program(&programs[index])
+}
+
+/// Compile each module to an object file, link them and run the result
+fn link_and_run(sources: &[&str]) -> String {
+    let programs: Vec<Program> = sources.iter().map(|source| parse(source)).collect();
+    let dir = TempDir::new().unwrap();
+    let mut objects = Vec::new();
+
+    for (index, program) in programs.iter().enumerate() {
+        check(&programs, index).unwrap();
+        let c_code = CodeGenerator::new()
+            .with_externals(&externals(&programs, index))
+            .generate(program)
+            .unwrap();
+
+        let c_file = dir.path().join(format!("module{}.c", index));
+        let object = c_file.with_extension("o");
+        fs::write(&c_file, &c_code).unwrap();
+        backend::compile_object("gcc", &c_file, &object, &GCC_FLAGS).unwrap();
+        objects.push(object);
+    }
+
+    let exe = dir.path().join("program");
+    backend::link("gcc", &objects, &exe, &[]).unwrap();
+    let output = Command::new(&exe).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+const MAIN: &str = r#"
+func main() {
+    let area: int = square(7);
+    displayln "area: ", area;
+    greet("modules");
+}
+"#;
+
+const UTIL: &str = r#"
+func square(n: int) -> int {
+    send n * n;
+}
+
+@inline
+func greet(name: string) {
+    displayln "hello ", name;
+}
+"#;
+
+// ==================== TYPE CHECKING ====================
+
+#[test]
+fn test_modules_call_each_others_functions() {
+    let programs = [parse(MAIN), parse(UTIL)];
+
+    assert!(TypeChecker::new().check_program(&programs[0]).is_err());
+    assert!(check(&programs, 0).is_ok());
+    assert!(check(&programs, 1).is_ok());
+    println!("✓ Functions from other modules are visible to the type checker");
+}
+
+#[test]
+fn test_external_signatures_are_checked() {
+    let programs = [parse("func main() {\n    let s: string = square(2);\n}\n"), parse(UTIL)];
+
+    let errors = check(&programs, 0).unwrap_err();
+    assert!(matches!(errors[0], SemanticError::TypeMismatch { .. }), "{:?}", errors);
+    println!("✓ Calls into other modules are type checked against their signatures");
+}
+
+#[test]
+fn test_function_defined_in_two_modules() {
+    let programs = [parse(UTIL), parse("func square(n: int) -> int {\n    send n;\n}\n")];
+
+    let errors = check(&programs, 1).unwrap_err();
+    assert!(matches!(&errors[0], SemanticError::DuplicateDefinition { name, .. } if name == "square"));
+    println!("✓ A function may only be defined by one module");
+}
+
+// ==================== CODE GENERATION ====================
+
+#[test]
+fn test_module_prototypes_and_linkage() {
+    let programs = [parse(MAIN), parse(UTIL)];
+
+    let main_c = CodeGenerator::new().with_externals(&externals(&programs, 0)).generate(&programs[0]).unwrap();
+    assert!(main_c.contains("int ml_square(int ml_n);"));
+    assert!(main_c.contains("void ml_greet(const char* ml_name);"));
+    assert!(!main_c.contains("ml_square(int ml_n) {"));
+
+    // @inline normally makes the function static, which would hide it from main
+    let util_c = CodeGenerator::new().with_externals(&externals(&programs, 1)).generate(&programs[1]).unwrap();
+    assert!(!util_c.contains("static inline void ml_greet"));
+    assert!(CodeGenerator::new().generate(&programs[1]).unwrap().contains("static inline void ml_greet"));
+    println!("✓ Modules declare each other's functions and keep them linkable");
+}
+
+#[test]
+fn test_modules_link_and_run() {
+    assert_eq!(link_and_run(&[MAIN, UTIL]), "area: 49\nhello modules\n");
+    println!("✓ Separately compiled modules link into a working program");
+}
+
+#[test]
+fn test_runtime_helpers_do_not_clash() {
+    // Both modules build strings, so both carry the runtime
+    let main = r#"
+func main() {
+    let label: string = "n={double(4)}";
+    displayln label, " ", describe(5);
+}
+
+func double(n: int) -> int {
+    send n * 2;
+}
+"#;
+    let util = r#"
+func describe(n: int) -> string {
+    send "value {double(n)}";
+}
+"#;
+    assert_eq!(link_and_run(&[main, util]), "n=8 value 10\n");
+    println!("✓ Each module's runtime support stays private to it");
+}
+
+// ==================== VISIBILITY ====================
+
+const HELPERS: &str = r#"
+public func area(w: int, h: int) -> int {
+    send scale(w) * h;
+}
+
+private pure func scale(n: int) -> int {
+    send n * 2;
+}
+"#;
+
+#[test]
+fn test_visibility_qualifiers_parse() {
+    use minilang_compiler::ast::Visibility;
+
+    let program = parse(HELPERS);
+    assert_eq!(program.functions[0].visibility, Visibility::Public);
+    assert_eq!(program.functions[1].visibility, Visibility::Private);
+    assert!(program.functions[1].is_pure);
+
+    // Still contextual: the words remain usable as names
+    let program = parse("func private(public: int) -> int {\n    let pure: int = public;\n    send pure;\n}\n");
+    assert_eq!(program.functions[0].name, "private");
+    assert_eq!(program.functions[0].visibility, Visibility::Public);
+    println!("✓ public/private qualify functions without becoming keywords");
+}
+
+#[test]
+fn test_private_function_hidden_from_other_modules() {
+    let programs = [parse("func main() {\n    displayln area(2, 3), scale(1);\n}\n"), parse(HELPERS)];
+
+    let errors = check(&programs, 0).unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(matches!(&errors[0], SemanticError::PrivateFunction { name, .. } if name == "scale"));
+    assert!(check(&programs, 1).is_ok());
+    println!("✓ Other modules cannot call private functions");
+}
+
+#[test]
+fn test_private_main_rejected() {
+    let errors = TypeChecker::new().check_program(&parse("private func main() {\n}\n")).unwrap_err();
+    assert!(matches!(errors[0], SemanticError::PrivateMain { .. }), "{:?}", errors);
+    println!("✓ main cannot be private");
+}
+
+#[test]
+fn test_private_functions_are_static() {
+    let main = "func main() {\n    displayln area(2, 3), \" \", scale(5);\n}\n\nprivate func scale(n: int) -> int {\n    send n;\n}\n";
+    let programs = [parse(main), parse(HELPERS)];
+
+    // Each module keeps its own private scale, so neither sees the other's
+    assert!(check(&programs, 0).is_ok());
+    let main_c = CodeGenerator::new().with_externals(&externals(&programs, 0)).generate(&programs[0]).unwrap();
+    assert!(main_c.contains("int ml_area(int ml_w, int ml_h);"));
+    assert_eq!(main_c.matches("static int ml_scale(int ml_n)").count(), 2);
+    let helpers_c = CodeGenerator::new().with_externals(&externals(&programs, 1)).generate(&programs[1]).unwrap();
+    assert!(helpers_c.contains("static int ml_scale(int ml_n) {"));
+
+    assert_eq!(link_and_run(&[main, HELPERS]), "12 5\n");
+    println!("✓ Private functions get static linkage and may share names across modules");
+}
@@ -1,6 +1,7 @@
 // tests/optimizer_edge_cases.rs - Edge cases and corner scenarios for optimizer
 
-use minilang_compiler::{Lexer, Parser, Program, Optimizer, OptimizationStats};
+use minilang_compiler::{Lexer, Parser, Program, Optimizer, OptimizationStats, OverflowMode, SemanticError};
+use minilang_compiler::ast::{Expression, Literal, Statement};
 use pretty_assertions::assert_eq;
 
 fn optimize(source: &str, level: u8) -> (Program, OptimizationStats) {
@@ -15,6 +16,31 @@ fn optimize(source: &str, level: u8) -> (Program, OptimizationStats) {
     (program, stats)
 }
 
+/// Optimize with an overflow mode, returning the program and any errors
+fn optimize_with_overflow(source: &str, level: u8, overflow: OverflowMode) -> (Program, Vec<SemanticError>) {
+    let tokens = Lexer::new(source).tokenize().expect("Lexer failed");
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().expect("Parser failed");
+    let mut optimizer = Optimizer::new(level).with_overflow(overflow);
+    optimizer.optimize(&mut program);
+    (program, optimizer.errors().to_vec())
+}
+
+/// The values of the `let` statements in main, as literals where folded
+fn let_values(program: &Program) -> Vec<Option<Literal>> {
+    program.functions.iter()
+        .find(|f| f.name == "main")
+        .unwrap()
+        .body.statements.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Let(let_stmt) => Some(match let_stmt.value.as_ref().unwrap() {
+                Expression::Literal(lit) => Some(lit.value.clone()),
+                _ => None,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
 // ==================== EDGE CASES FOR CONSTANT FOLDING ====================
 
 #[test]
@@ -157,6 +183,86 @@ func main() {
     println!("✓ Function calls not folded");
 }
 
+// ==================== OVERFLOW MODES ====================
+
+#[test]
+fn test_wrap_mode_folds_with_wrapping() {
+    let source = r#"
+func main() {
+    let a: int = 2147483647 + 1;
+    let b: int = -2147483647 - 2;
+    let c: int = 65536 * 65536;
+    let d: int = -(-2147483647 - 1);
+    let e: int = (-2147483647 - 1) / -1;
+    let f: int = (-2147483647 - 1) % -1;
+}
+"#;
+    
+    let (program, errors) = optimize_with_overflow(source, 1, OverflowMode::Wrap);
+    assert!(errors.is_empty());
+    assert_eq!(let_values(&program), vec![
+        Some(Literal::Integer(i32::MIN)),
+        Some(Literal::Integer(i32::MAX)),
+        Some(Literal::Integer(0)),
+        Some(Literal::Integer(i32::MIN)),
+        // x / -1 is strength-reduced to -x, which wraps
+        Some(Literal::Integer(i32::MIN)),
+        // i32::MIN % -1 traps in C even with -fwrapv, so it stays for runtime
+        None,
+    ]);
+    
+    println!("✓ Wrap mode folds overflowing constants with wrapping arithmetic");
+}
+
+#[test]
+fn test_trap_mode_rejects_overflowing_constants() {
+    let source = r#"
+func main() {
+    let a: int = 2147483647 + 1;
+    let b: int = 1000 * 1000;
+    let c: int = -(-2147483647 - 1);
+    let d: int = (-2147483647 - 1) / -1;
+}
+"#;
+    
+    let (program, errors) = optimize_with_overflow(source, 1, OverflowMode::Trap);
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec![
+        "constant expression '2147483647 + 1' overflows int",
+        "constant expression '-(-2147483648)' overflows int",
+        // d's division was strength-reduced to a negation first
+        "constant expression '-(-2147483648)' overflows int",
+    ]);
+    // Expressions that fit are still folded
+    assert_eq!(let_values(&program)[1], Some(Literal::Integer(1000000)));
+    
+    println!("✓ Trap mode reports overflowing constant expressions");
+}
+
+#[test]
+fn test_trap_mode_reports_propagated_overflow_once() {
+    let source = r#"
+func bump(n: int) -> int {
+    let big: int = 2147483000;
+    send big + n;
+}
+
+func main() {
+    display bump(1000), bump(1000);
+}
+"#;
+    
+    // Specialization and propagation expose big + 1000, in the original and
+    // its clone and on every fixpoint round, but it is one mistake
+    let (_, errors) = optimize_with_overflow(source, 2, OverflowMode::Trap);
+    assert_eq!(errors.len(), 1);
+    
+    let (_, errors) = optimize_with_overflow(source, 1, OverflowMode::Trap);
+    assert!(errors.is_empty());
+    
+    println!("✓ Each overflowing expression is reported once");
+}
+
 // ==================== EDGE CASES FOR DEAD CODE ====================
 
 #[test]
@@ -484,15 +590,15 @@ fn test_strength_reduce_float_not_optimized() {
     let source = r#"
 func main() {
     let x: float = 5.5;
-    let y: float = x * 1.0;
+    let y: float = x + 0.0;
 }
 "#;
     
     let (_, stats) = optimize(source, 1);
-    // Strength reduction currently only works with integers
+    // x + 0.0 is not x when x is -0.0, so it needs --fast-math
     assert_eq!(stats.strength_reductions, 0);
     
-    println!("✓ Strength reduction skips float operations");
+    println!("✓ Strength reduction skips inexact float operations");
 }
 
 #[test]
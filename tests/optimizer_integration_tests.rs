@@ -1,6 +1,6 @@
 // tests/optimizer_integration_tests.rs - Real MiniLang program optimization tests
 
-use minilang_compiler::{Lexer, Parser, Program, Optimizer, OptimizationStats};
+use minilang_compiler::{Lexer, Parser, Program, Optimizer, OptimizationStats, Statement, Expression, Literal};
 
 fn optimize_program(source: &str, level: u8) -> (Program, OptimizationStats) {
     let mut lexer = Lexer::new(source);
@@ -511,3 +511,276 @@ func main() {
     println!("   Strength reductions: {}", stats.strength_reductions);
     println!("   Dead code removed: {}", stats.dead_code_removed);
 }
+
+// ==================== DEAD FUNCTION TESTS ====================
+
+#[test]
+fn test_unreachable_functions_removed_at_o2() {
+    let source = r#"
+func used(n: int) -> int {
+    send helper(n) + 1;
+}
+
+func helper(n: int) -> int {
+    send n * 3;
+}
+
+func unused() -> int {
+    send orphan_callee();
+}
+
+func orphan_callee() -> int {
+    send 7;
+}
+
+func countdown(n: int) {
+    if n > 0 {
+        countdown(n - 1);
+    }
+}
+
+func main() {
+    let n: int = 3;
+    countdown(n);
+    display "{used(n)}";
+}
+"#;
+
+    let (program, stats) = optimize_program(source, 2);
+    let mut names: Vec<_> = program.functions.iter().map(|f| f.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["countdown", "helper", "main", "used"]);
+    assert_eq!(stats.functions_removed, 2);
+    println!("✓ Functions unreachable from main removed");
+}
+
+#[test]
+fn test_dead_functions_kept_below_o2_or_without_main() {
+    let with_main = "func unused() { } func main() { }";
+    let (program, stats) = optimize_program(with_main, 1);
+    assert_eq!(program.functions.len(), 2);
+    assert_eq!(stats.functions_removed, 0);
+
+    let library = "func a() { } func b() { }";
+    let (program, _) = optimize_program(library, 2);
+    assert_eq!(program.functions.len(), 2);
+    println!("✓ Dead function elimination limited to -O2 programs with main");
+}
+
+// ==================== SPECIALIZATION TESTS ====================
+
+#[test]
+fn test_constant_argument_specialization() {
+    let source = r#"
+func scale(x: int, factor: int) -> int {
+    send x * factor;
+}
+
+func main() {
+    let a: int = 5;
+    display scale(a, 8), scale(a + 1, 8);
+}
+"#;
+
+    let (program, stats) = optimize_program(source, 2);
+    assert_eq!(stats.functions_specialized, 1);
+    assert!(program.functions.iter().all(|f| f.name != "scale"), "original should be unreachable");
+
+    let clone = program.functions.iter().find(|f| f.name.starts_with("scale__")).unwrap();
+    assert_eq!(clone.params.len(), 1);
+    assert_eq!(clone.params[0].name, "x");
+    println!("✓ Constant parameter specialized into a clone");
+}
+
+#[test]
+fn test_varying_arguments_not_specialized() {
+    let source = r#"
+func scale(x: int, factor: int) -> int {
+    send x * factor;
+}
+
+func power(base: int, exp: int) -> int {
+    if exp == 0 {
+        send 1;
+    }
+    send base * power(base, exp - 1);
+}
+
+func main() {
+    display scale(1, 2), scale(3, 4), power(2, 10);
+}
+"#;
+
+    let (program, stats) = optimize_program(source, 2);
+    assert_eq!(stats.functions_specialized, 0);
+    assert!(program.functions.iter().any(|f| f.name == "scale"));
+
+    let (_, stats) = optimize_program(source.replace("scale(3, 4)", "scale(3, 2)").as_str(), 1);
+    assert_eq!(stats.functions_specialized, 0, "specialization is -O2 only");
+    println!("✓ Varying arguments and -O1 leave functions alone");
+}
+
+// ==================== RANGE FOLDING TESTS ====================
+
+#[test]
+fn test_range_decided_branches_removed_at_o2() {
+    let source = r#"
+func main() {
+    for let i: int = 0; i < 5; i = i + 1 {
+        if i < 10 {
+            display i;
+        } else {
+            display "never";
+        }
+        if i % 3 > 2 {
+            display "also never";
+        }
+    }
+}
+"#;
+    
+    let (program, stats) = optimize_program(source, 2);
+    assert_eq!(stats.comparisons_decided, 2);
+    assert!(stats.dead_code_removed >= 2);
+    let Statement::For(for_stmt) = &program.functions[0].body.statements[0] else {
+        panic!("Expected for loop");
+    };
+    let text = format!("{:?}", for_stmt.body);
+    assert!(!text.contains("never"), "{}", text);
+    
+    // Range folding is an -O2 pass
+    let (_, stats) = optimize_program(source, 1);
+    assert_eq!(stats.comparisons_decided, 0);
+    
+    println!("✓ Comparisons decided by ranges let DCE drop branches");
+}
+
+#[test]
+fn test_range_folding_leaves_open_comparisons() {
+    let source = r#"
+func check(n: int, x: float) -> bool {
+    send n < 10 OR x < 1.0 OR next() > 0;
+}
+
+func next() -> int {
+    send 1;
+}
+
+func main() {
+    display check(3, 0.5);
+}
+"#;
+    
+    let (_, stats) = optimize_program(source, 2);
+    assert_eq!(stats.comparisons_decided, 0);
+    
+    println!("✓ Unknown ints, floats and calls are not decided");
+}
+
+// ==================== CONST ARRAY TESTS ====================
+
+#[test]
+fn test_const_array_lookups_folded() {
+    let source = r#"
+func main() {
+    const TABLE: int[4] = [1, 2, 3, 4];
+    let i: int = 3;
+    display TABLE[2] * 10, TABLE[i], TABLE[7];
+}
+"#;
+    
+    let (program, stats) = optimize_program(source, 2);
+    assert!(stats.constants_propagated >= 3);
+    let Statement::Display(display) = &program.functions[0].body.statements[2] else {
+        panic!("Expected display");
+    };
+    // TABLE[2] * 10 folds all the way, and `i` is known to be 3
+    assert!(matches!(&display.expressions[0], Expression::Literal(lit) if lit.value == Literal::Integer(30)), "{:?}", display.expressions[0]);
+    assert!(matches!(&display.expressions[1], Expression::Literal(lit) if lit.value == Literal::Integer(4)), "{:?}", display.expressions[1]);
+    // Out of range: left for the bounds check
+    assert!(matches!(&display.expressions[2], Expression::Index(_)));
+    
+    // Constant propagation is an -O2 pass
+    let (program, _) = optimize_program(source, 1);
+    let Statement::Display(display) = &program.functions[0].body.statements[2] else {
+        panic!("Expected display");
+    };
+    assert!(matches!(&display.expressions[1], Expression::Index(_)));
+    println!("✓ Constant-index lookups into const arrays fold to the element");
+}
+
+// ==================== ATTRIBUTE TESTS ====================
+
+#[test]
+fn test_inline_attribute_substitutes_body() {
+    let source = r#"
+@inline
+func area(w: int, h: int) -> int {
+    send w * h;
+}
+
+func main() {
+    let side: int = 4;
+    let a: int = area(side, 3);
+    display a;
+}
+"#;
+
+    let (program, stats) = optimize_program(source, 1);
+    assert_eq!(stats.functions_inlined, 1);
+    let main = program.functions.iter().find(|f| f.name == "main").unwrap();
+    assert!(matches!(
+        &main.body.statements[1],
+        Statement::Let(let_stmt) if matches!(let_stmt.value, Some(Expression::Binary(_)))
+    ));
+    println!("✓ @inline call replaced by its body");
+}
+
+#[test]
+fn test_inline_skips_side_effecting_arguments() {
+    let source = r#"
+@inline
+func twice(x: int) -> int {
+    send x + x;
+}
+
+func next() -> int {
+    send 1;
+}
+
+func main() {
+    display twice(next());
+}
+"#;
+
+    let (_, stats) = optimize_program(source, 2);
+    assert_eq!(stats.functions_inlined, 0);
+    println!("✓ Call with side-effecting argument left alone");
+}
+
+#[test]
+fn test_noopt_attribute_skips_passes() {
+    let source = r#"
+@noopt
+func keep() -> int {
+    send 2 + 3;
+}
+
+func fold() -> int {
+    send 2 + 3;
+}
+
+func main() {
+    display keep(), fold();
+}
+"#;
+
+    let (program, stats) = optimize_program(source, 2);
+    assert_eq!(stats.constants_folded, 1);
+    let keep = program.functions.iter().find(|f| f.name == "keep").unwrap();
+    assert!(matches!(
+        &keep.body.statements[0],
+        Statement::Return(ret) if matches!(ret.value, Some(Expression::Binary(_)))
+    ));
+    println!("✓ @noopt function left unoptimized");
+}
@@ -1,6 +1,6 @@
 // tests/optimizer_test.rs - Basic optimizer functionality tests
 
-use minilang_compiler::{Lexer, OptimizationStats, Optimizer, Parser, Program, UnaryOp};
+use minilang_compiler::{Lexer, OptimizationHint, OptimizationStats, Optimizer, Parser, Pass, Program, UnaryOp};
 use minilang_compiler::ast::{Statement, Expression, Literal};
 use pretty_assertions::assert_eq;
 
@@ -17,6 +17,14 @@ fn optimize(source: &str, level: u8) -> (Program, OptimizationStats) {
     (program, stats)
 }
 
+/// Helper to parse and run a chosen list of passes
+fn optimize_with(source: &str, passes: &[Pass]) -> (Program, OptimizationStats) {
+    let tokens = Lexer::new(source).tokenize().expect("Lexer failed");
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().expect("Parser failed");
+    let stats = Optimizer::with_passes(passes).optimize(&mut program);
+    (program, stats)
+}
+
 /// Helper to get first statement from main function
 fn get_first_statement(program: &Program) -> &Statement {
     &program.functions[0].body.statements[0]
@@ -172,6 +180,35 @@ func main() {
     println!("✓ Float operations folded");
 }
 
+#[test]
+fn test_float_folding_is_exact() {
+    let source = r#"
+func main() {
+    let sum: float = 0.1 + 0.2;
+    let same: bool = 0.5 == 0.5;
+}
+"#;
+    
+    let (program, stats) = optimize(source, 1);
+    // Float comparisons stay for runtime
+    assert_eq!(stats.constants_folded, 1);
+    
+    match get_first_statement(&program) {
+        Statement::Let(let_stmt) => match &let_stmt.value {
+            Some(Expression::Literal(lit)) => assert_eq!(lit.value, Literal::Float(0.1 + 0.2)),
+            _ => panic!("Expected folded literal"),
+        },
+        _ => panic!("Expected let statement"),
+    }
+    
+    // Overflow to infinity has no C literal, so it is not folded
+    let source = format!("func main() {{ let huge: float = 1{}.0 * 10.0; }}", "0".repeat(308));
+    let (_, stats) = optimize(&source, 1);
+    assert_eq!(stats.constants_folded, 0);
+    
+    println!("✓ Float folding keeps runtime semantics");
+}
+
 #[test]
 fn test_fold_boolean_and() {
     let source = r#"
@@ -609,6 +646,71 @@ func main() {
     println!("✓ Code after continue eliminated");
 }
 
+#[test]
+fn test_eliminate_code_after_endless_loop() {
+    let source = r#"
+func main() {
+    let i: int = 0;
+    while true {
+        i = i + 1;
+        if i > 3 {
+            send;
+        }
+    }
+    display "after loop - dead";
+}
+"#;
+    
+    let (program, stats) = optimize(source, 1);
+    assert!(stats.dead_code_removed > 0);
+    assert_eq!(program.functions[0].body.statements.len(), 2);
+    
+    println!("✓ Code after a loop with no way out but send eliminated");
+}
+
+#[test]
+fn test_loop_with_break_keeps_following_code() {
+    let source = r#"
+func main() {
+    for ;; {
+        while true {
+            break;
+        }
+        break;
+    }
+    display "after loop";
+}
+"#;
+    
+    let (program, _) = optimize(source, 1);
+    assert_eq!(program.functions[0].body.statements.len(), 2);
+    
+    println!("✓ Code after a loop that breaks is kept");
+}
+
+#[test]
+fn test_do_while_false_runs_once() {
+    let source = r#"
+func main() {
+    do {
+        display "runs once";
+    } while false;
+    for let i: int = 0; false; i = i + 1 {
+        display "never runs";
+    }
+    display "after loops";
+}
+"#;
+    
+    let (program, stats) = optimize(source, 1);
+    assert_eq!(stats.dead_code_removed, 1);
+    let statements = &program.functions[0].body.statements;
+    assert_eq!(statements.len(), 2);
+    assert!(matches!(statements[0], Statement::DoWhile(_)));
+    
+    println!("✓ A do-while body runs once even when its condition is false");
+}
+
 // ==================== STRENGTH REDUCTION TESTS ====================
 
 #[test]
@@ -869,6 +971,77 @@ func main() {
     println!("✓ Strength reduction: x % 16 marked for bitwise AND optimization");
 }
 
+#[test]
+fn test_strength_reduce_negative_power_of_two_divisor() {
+    let source = r#"
+func f(x: int) {
+    let q: int = x / -4;
+    let r: int = x % -16;
+}
+"#;
+    
+    let (program, stats) = optimize(source, 1);
+    assert_eq!(stats.strength_reductions, 2);
+    
+    // x / -4 -> -(x / 4) with the shift hint on the inner division
+    match get_first_statement(&program) {
+        Statement::Let(let_stmt) => match let_stmt.value.as_ref().unwrap() {
+            Expression::Unary(unary) => {
+                assert_eq!(unary.op, UnaryOp::Negate);
+                match &*unary.operand {
+                    Expression::Binary(binary) => {
+                        assert_eq!(binary.optimization_hint, Some(OptimizationHint::ShiftRight(2)));
+                        assert!(matches!(&*binary.right, Expression::Literal(lit) if lit.value == Literal::Integer(4)));
+                    }
+                    _ => panic!("Expected division inside negation"),
+                }
+            }
+            _ => panic!("Expected negated division"),
+        },
+        _ => panic!("Expected let statement"),
+    }
+    
+    // x % -16 has the same result as x % 16
+    match &program.functions[0].body.statements[1] {
+        Statement::Let(let_stmt) => match let_stmt.value.as_ref().unwrap() {
+            Expression::Binary(binary) => {
+                assert_eq!(binary.optimization_hint, Some(OptimizationHint::BitwiseAnd(15)));
+            }
+            _ => panic!("Expected binary expression"),
+        },
+        _ => panic!("Expected let statement"),
+    }
+    
+    println!("✓ Strength reduction: negative power-of-two divisors");
+}
+
+#[test]
+fn test_strength_reduce_skips_min_int_divisor() {
+    let source = r#"
+func f(x: int) {
+    let q: int = x / -3;
+    let r: int = x % 12;
+}
+"#;
+    
+    let (_, stats) = optimize(source, 1);
+    assert_eq!(stats.strength_reductions, 0);
+    
+    // -2147483648 has no positive counterpart in i32, so it is never rewritten
+    let mut program = optimize(source, 0).0;
+    if let Statement::Let(let_stmt) = &mut program.functions[0].body.statements[0] {
+        if let Some(Expression::Binary(binary)) = &mut let_stmt.value {
+            if let Expression::Literal(lit) = &mut *binary.right {
+                lit.value = Literal::Integer(i32::MIN);
+            }
+        }
+    }
+    let stats = Optimizer::new(1).optimize(&mut program);
+    assert_eq!(stats.strength_reductions, 0);
+    
+    println!("✓ Strength reduction: non-power-of-two and i32::MIN divisors left alone");
+}
+
 #[test]
 fn test_strength_reduce_multiple_in_expression() {
     let source = r#"
@@ -942,3 +1115,292 @@ func main() {
     
     println!("✓ Strength reduction combined with constant folding");
 }
+
+#[test]
+fn test_strength_reduce_exact_float_identities() {
+    let source = r#"
+func f(x: float) {
+    let a: float = x * 1.0;
+    let b: float = 1.0 * x;
+    let c: float = x / 1.0;
+    let d: float = x - 0.0;
+    let e: float = x + -0.0;
+    let g: float = x * -1.0;
+    let h: float = x / -1.0;
+}
+"#;
+    
+    let (program, stats) = optimize(source, 1);
+    assert_eq!(stats.strength_reductions, 7);
+    
+    for (i, statement) in program.functions[0].body.statements.iter().enumerate() {
+        let Statement::Let(let_stmt) = statement else { panic!("Expected let statement") };
+        match (i, let_stmt.value.as_ref().unwrap()) {
+            (0..=4, Expression::Identifier(id)) => assert_eq!(id.name, "x"),
+            (5 | 6, Expression::Unary(unary)) => assert_eq!(unary.op, UnaryOp::Negate),
+            (_, other) => panic!("Statement {} not reduced: {:?}", i, other),
+        }
+    }
+    
+    println!("✓ Strength reduction: exact float identities reduced");
+}
+
+#[test]
+fn test_float_identities_need_fast_math() {
+    let source = r#"
+func f(x: float) {
+    let a: float = x + 0.0;
+    let b: float = 0.0 + x;
+    let c: float = x - -0.0;
+    let d: float = x * 0.0;
+    let e: float = 0.0 - x;
+    let g: float = 1.0 / x;
+}
+"#;
+    
+    // -0.0 + 0.0 is 0.0 and NaN * 0.0 is NaN, so none of these are exact
+    let (_, stats) = optimize(source, 1);
+    assert_eq!(stats.strength_reductions, 0);
+    
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let stats = Optimizer::new(1).with_fast_math(true).optimize(&mut program);
+    assert_eq!(stats.strength_reductions, 4);
+    match &program.functions[0].body.statements[3] {
+        Statement::Let(let_stmt) => match let_stmt.value.as_ref().unwrap() {
+            Expression::Literal(lit) => assert_eq!(lit.value, Literal::Float(0.0)),
+            other => panic!("Expected 0.0, got {:?}", other),
+        },
+        _ => panic!("Expected let statement"),
+    }
+    
+    println!("✓ Strength reduction: inexact float identities only with fast math");
+}
+
+#[test]
+fn test_fast_math_keeps_side_effects() {
+    let source = r#"
+func next() -> float {
+    display "called";
+    send 2.0;
+}
+
+func main() {
+    let x: float = next() * 0.0;
+}
+"#;
+    
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let stats = Optimizer::new(1).with_fast_math(true).optimize(&mut program);
+    assert_eq!(stats.strength_reductions, 0);
+    
+    println!("✓ Fast math never drops a call");
+}
+
+// ==================== PASS SELECTION TESTS ====================
+
+#[test]
+fn test_single_pass_only_touches_its_own_stats() {
+    let source = r#"
+func main() {
+    let x: int = 2 + 3;
+    let y: int = x * 8;
+    if false {
+        display x;
+    }
+}
+"#;
+
+    let (program, stats) = optimize_with(source, &[Pass::ConstFold]);
+    assert_eq!(stats.for_pass(Pass::ConstFold), 1);
+    assert_eq!(stats.for_pass(Pass::StrengthReduce), 0);
+    assert_eq!(stats.for_pass(Pass::DCE), 0);
+    assert_eq!(program.functions[0].body.statements.len(), 3);
+
+    let (program, stats) = optimize_with(source, &[Pass::DCE]);
+    assert_eq!(stats.for_pass(Pass::DCE), 1);
+    assert_eq!(stats.for_pass(Pass::ConstFold), 0);
+    assert_eq!(program.functions[0].body.statements.len(), 2);
+
+    let (_, stats) = optimize_with(source, &[Pass::StrengthReduce]);
+    assert_eq!(stats.for_pass(Pass::StrengthReduce), 1);
+    assert_eq!(stats.for_pass(Pass::ConstFold), 0);
+
+    println!("✓ Each pass runs on its own and counts in its own stat");
+}
+
+#[test]
+fn test_pass_order_is_respected() {
+    let source = r#"
+func main() {
+    let x: int = 4;
+    let y: int = x + 1;
+}
+"#;
+
+    // In a single run, folding before propagation has nothing to fold yet
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let stats = Optimizer::with_passes(&[Pass::ConstFold, Pass::ConstProp])
+        .with_max_iterations(1)
+        .optimize(&mut program);
+    assert_eq!(stats.constants_propagated, 1);
+    assert_eq!(stats.constants_folded, 0);
+
+    let (program, stats) = optimize_with(source, &[Pass::ConstProp, Pass::ConstFold]);
+    assert_eq!(stats.constants_propagated, 1);
+    assert_eq!(stats.constants_folded, 1);
+    match &program.functions[0].body.statements[1] {
+        Statement::Let(let_stmt) => match let_stmt.value.as_ref().unwrap() {
+            Expression::Literal(lit) => assert_eq!(lit.value, Literal::Integer(5)),
+            _ => panic!("Expected folded literal"),
+        },
+        _ => panic!("Expected let statement"),
+    }
+
+    println!("✓ Passes run in the order given");
+}
+
+#[test]
+fn test_levels_match_their_pass_lists() {
+    let source = r#"
+func square(n: int) -> int {
+    send n * n;
+}
+
+func unused() -> int {
+    send 1;
+}
+
+func main() {
+    let x: int = 3;
+    let y: int = square(x + 0) * 4;
+    display y;
+}
+"#;
+
+    assert!(Pass::for_level(0).is_empty());
+    for level in 0..=2 {
+        let (by_level, level_stats) = optimize(source, level);
+        let (by_passes, pass_stats) = optimize_with(source, &Pass::for_level(level));
+        assert_eq!(by_passes, by_level, "level {}", level);
+        assert_eq!(format!("{:?}", pass_stats), format!("{:?}", level_stats), "level {}", level);
+    }
+
+    println!("✓ Optimization levels are shorthand for pass lists");
+}
+
+#[test]
+fn test_selected_passes_skip_noopt_functions() {
+    let source = r#"
+@noopt
+func keep() -> int {
+    send 2 * 8;
+}
+
+func main() {
+    display keep();
+}
+"#;
+
+    let (_, stats) = optimize_with(source, &[Pass::ConstFold, Pass::StrengthReduce]);
+    assert_eq!(stats.constants_folded, 0);
+    assert_eq!(stats.strength_reductions, 0);
+
+    println!("✓ Explicit passes still respect @noopt");
+}
+
+// ==================== FIXPOINT TESTS ====================
+
+#[test]
+fn test_constants_exposed_by_dead_code_are_propagated() {
+    let source = r#"
+func main() {
+    let x: int = 5;
+    if false {
+        x = 6;
+    }
+    display x * 2;
+}
+"#;
+
+    // The dead assignment keeps x from being a constant until DCE removes it
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut once = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let stats = Optimizer::new(2).with_max_iterations(1).optimize(&mut once);
+    assert_eq!(stats.constants_propagated, 0);
+
+    let (program, stats) = optimize(source, 2);
+    assert!(stats.constants_propagated > 0);
+    assert!(stats.iterations > 1);
+    match program.functions[0].body.statements.last().unwrap() {
+        Statement::Display(display) => match &display.expressions[0] {
+            Expression::Literal(lit) => assert_eq!(lit.value, Literal::Integer(10)),
+            other => panic!("Expected folded literal, got {:?}", other),
+        },
+        _ => panic!("Expected display statement"),
+    }
+
+    println!("✓ Passes repeat until constants exposed by DCE are used");
+}
+
+#[test]
+fn test_second_optimize_is_a_no_op() {
+    let source = r#"
+func scale(n: int, k: int) -> int {
+    send n * k;
+}
+
+func main() {
+    let x: int = 3;
+    let y: int = x / 8 + x % 4;
+    if 1 < 2 {
+        display scale(y, 2) * 16;
+    }
+}
+"#;
+
+    for level in 1..=2 {
+        let (mut program, first) = optimize(source, level);
+        assert!(first.strength_reductions > 0);
+        let optimized = program.clone();
+
+        let second = Optimizer::new(level).optimize(&mut program);
+        assert_eq!(program, optimized, "level {}", level);
+        assert_eq!(second.iterations, 1);
+        assert_eq!(
+            format!("{:?}", second),
+            format!("{:?}", OptimizationStats { iterations: 1, ..Default::default() }),
+            "level {}", level
+        );
+    }
+
+    println!("✓ Optimizing an optimized program changes nothing");
+}
+
+#[test]
+fn test_iteration_cap() {
+    let source = r#"
+@inline
+func down(n: int) -> int {
+    send down(n - 1);
+}
+
+func main() {
+    display down(5);
+}
+"#;
+
+    // Each round inlines one more level of the recursion, so only the cap stops it
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let stats = Optimizer::with_passes(&[Pass::Inline]).with_max_iterations(3).optimize(&mut program);
+    assert_eq!(stats.iterations, 3);
+    assert!(stats.functions_inlined >= 3);
+
+    let (_, stats) = optimize_with("func main() { display 1; }", &[Pass::ConstFold]);
+    assert_eq!(stats.iterations, 1);
+
+    println!("✓ Iteration cap bounds passes that never settle");
+}
@@ -44,7 +44,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Deeply nested expressions parsed");
 }
 
@@ -64,7 +64,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Deeply nested blocks parsed");
 }
 
@@ -77,7 +77,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Array access parsed");
 }
 
@@ -107,7 +107,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Expression in array index parsed");
 }
 
@@ -134,7 +134,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Mixed types in display parsed");
 }
 
@@ -167,7 +167,7 @@ func classify(x: int) -> string {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Multiple return paths parsed");
 }
 
@@ -181,7 +181,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ For loop without init parsed");
 }
 
@@ -197,7 +197,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ For loop without condition parsed");
 }
 
@@ -212,7 +212,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ For loop without update parsed");
 }
 
@@ -230,7 +230,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Infinite loops parsed");
 }
 
@@ -244,7 +244,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Chained comparisons parsed");
 }
 
@@ -259,7 +259,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Negation and NOT parsed");
 }
 
@@ -284,7 +284,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Large array declaration parsed");
 }
 
@@ -296,7 +296,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ String with escape sequences parsed");
 }
 
@@ -309,7 +309,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Parenthesized expressions parsed");
 }
 
@@ -333,7 +333,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Function call with many arguments parsed");
 }
 
@@ -384,7 +384,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Assignment with complex expression parsed");
 }
 
@@ -415,7 +415,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Deeply nested if statements parsed");
 }
 
@@ -437,7 +437,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Long else-if chain parsed");
 }
 
@@ -455,7 +455,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Triply nested loops parsed");
 }
 
@@ -467,7 +467,7 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ All precedence levels in one expression parsed");
 }
 
@@ -499,6 +499,61 @@ func main() {
 }
 "#;
     let ast = parse(source).unwrap();
-    assert!(ast.functions.len() > 0);
+    assert!(!ast.functions.is_empty());
     println!("✓ Const with expression parsed");
-}
\ No newline at end of file
+}
+// ==================== TRUNCATED INPUT TESTS ====================
+
+#[test]
+fn test_every_token_prefix_parses_or_errors() {
+    let source = include_str!("../examples/hello.mini");
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    
+    // Cutting the program off at any token must give an error, never a panic
+    for len in 0..tokens.len() {
+        let result = std::panic::catch_unwind(|| {
+            Parser::new(tokens[..len].to_vec(), source.to_string()).parse_program().is_ok()
+        });
+        assert!(result.is_ok(), "parser panicked on the first {} tokens", len);
+    }
+    
+    println!("✓ Truncated programs never panic the parser");
+}
+
+#[test]
+fn test_eof_reported_as_eof() {
+    use minilang_compiler::ParserError;
+    
+    let source = "func";
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let err = Parser::new(tokens, source.to_string()).parse_program().unwrap_err();
+    assert!(matches!(err, ParserError::UnexpectedEof { .. }), "got {:?}", err);
+    
+    println!("✓ Running out of tokens is an end-of-input error");
+}
+
+#[test]
+fn test_cursor_lookahead_and_marks() {
+    use minilang_compiler::parser::Cursor;
+    use minilang_compiler::Token;
+    
+    let tokens = Lexer::new("let x = 1;").tokenize().unwrap();
+    let mut cursor = Cursor::new(tokens);
+    assert_eq!(cursor.peek_n(1).map(|t| &t.token), Some(&Token::Identifier("x".to_string())));
+    assert!(cursor.previous().is_none());
+    
+    cursor.advance();
+    let mark = cursor.mark();
+    cursor.advance();
+    cursor.advance();
+    assert_eq!(cursor.previous().map(|t| &t.token), Some(&Token::Assign));
+    cursor.reset(mark);
+    assert_eq!(cursor.peek().map(|t| &t.token), Some(&Token::Identifier("x".to_string())));
+    
+    while cursor.advance().is_some() {}
+    assert!(cursor.is_at_end());
+    assert!(cursor.advance().is_none());
+    assert_eq!(cursor.previous().map(|t| &t.token), Some(&Token::Semicolon));
+    
+    println!("✓ Cursor peeks ahead and rewinds to marks");
+}
@@ -1,6 +1,6 @@
 // tests/parser_error_tests.rs - Parser error detection tests
 
-use minilang_compiler::{Lexer, Parser, ParserError};
+use minilang_compiler::{Lexer, Parser, ParserError, Version};
 use pretty_assertions::assert_eq;
 
 /// Helper to test parser errors
@@ -71,11 +71,12 @@ func () {
 fn test_error_missing_type_annotation() {
     let source = r#"
 func main() {
-    let x = 42;  # Missing : type
+    let x;  # No type and no value to infer it from
 }
 "#;
     
-    let _error = parse_expect_error(source);
+    let error = parse_expect_error(source);
+    assert!(matches!(error, ParserError::MissingType { .. }), "{:?}", error);
     println!("✓ Missing type annotation error detected");
 }
 
@@ -201,7 +202,7 @@ func main() {
 fn test_error_missing_array_size() {
     let source = r#"
 func main() {
-    let arr: int[] = [1, 2, 3];  # Missing array size
+    let arr: int[];  # Missing array size and no literal to infer it from
 }
 "#;
     
@@ -280,4 +281,102 @@ fn test_error_empty_program() {
     assert_eq!(result.functions.len(), 0);
     
     println!("✓ Empty program handled correctly");
-}
\ No newline at end of file
+}
+
+// ==================== ERROR LOCATION TESTS ====================
+
+#[test]
+fn test_interpolation_errors_point_into_the_string() {
+    let source = "func main() {\n    let a: int = 1;\n    display \"sum {a + * 2}\";\n}\n";
+    let error = parse_expect_error(source);
+    let ParserError::InvalidExpression { span } = &error else {
+        panic!("Expected InvalidExpression, got {:?}", error);
+    };
+    assert_eq!(&source[span.offset()..span.offset() + span.len()], "*");
+    
+    // Running out of tokens inside {...} points at the last one
+    let source = "func main() {\n    let a: int = 1;\n    display \"sum {(a + 1}\";\n}\n";
+    let error = parse_expect_error(source);
+    let ParserError::UnexpectedToken { span, .. } = &error else {
+        panic!("Expected UnexpectedToken, got {:?}", error);
+    };
+    assert_eq!(&source[span.offset()..span.offset() + span.len()], "1");
+    
+    println!("✓ Errors inside interpolations point at the string, not the start of the file");
+}
+
+// ==================== LANGUAGE VERSION TESTS ====================
+
+/// Parse `source` as if `--edition` was `edition`
+fn parse_edition(source: &str, edition: Version) -> Result<(), ParserError> {
+    let tokens = Lexer::new(source).tokenize().expect("Lexer should not fail");
+    Parser::new(tokens, source.to_string()).with_edition(edition).parse_program().map(|_| ())
+}
+
+#[test]
+fn test_version_header_gates_newer_syntax() {
+    let newer = [
+        ("pure func f() {}\nfunc main() {}", "pure functions", "1.1"),
+        ("func main() {\n    defer {\n        display 1;\n    }\n}", "defer blocks", "1.1"),
+        ("func main() {\n    let t: (int, int) = (1, 2);\n}", "tuples", "1.1"),
+        ("func main() {\n    let (a, b) = f();\n}", "tuples", "1.1"),
+        ("func main() {\n    display \"{(1, 2)}\";\n}", "tuples", "1.1"),
+        ("@inline\nfunc f() {}\nfunc main() {}", "function attributes", "1.2"),
+        ("private func f() {}\nfunc main() {}", "public and private functions", "1.2"),
+        ("func main() {\n    @if DEBUG {\n        display 1;\n    }\n}", "@if blocks", "1.2"),
+        ("func main() {\n    let x = 1;\n}", "let without a type", "1.2"),
+        ("test \"one\" {\n    assert(true);\n}\nfunc main() {}", "test blocks", "1.3"),
+        ("func main() {\n    spawn {\n        display 1;\n    }\n}", "spawn blocks and channels", "1.3"),
+        ("func f(c: chan) {}\nfunc main() {}", "spawn blocks and channels", "1.3"),
+        ("func main() {\n    parallel for i in 0..4 {}\n}", "parallel for loops and atomics", "1.3"),
+        ("func f(a: atomic) {}\nfunc main() {}", "parallel for loops and atomics", "1.3"),
+        ("func f(c: canvas) {}\nfunc main() {}", "canvases", "1.3"),
+        ("func f(j: json) {}\nfunc main() {}", "json values", "1.3"),
+        ("func main() {\n    let n: int = json_len(json {});\n}", "json values", "1.3"),
+    ];
+    for (body, expected_feature, expected_version) in newer {
+        let error = parse_expect_error(&format!("minilang 1.0;\n{}", body));
+        let ParserError::FeatureRequiresVersion { feature, required, version, .. } = &error else {
+            panic!("Expected FeatureRequiresVersion for {:?}, got {:?}", body, error);
+        };
+        assert_eq!((feature.as_str(), required.as_str(), version.as_str()), (expected_feature, expected_version, "1.0"));
+        
+        // Fine in the version that added it, and without a header
+        let header = format!("minilang {};\n{}", expected_version, body);
+        assert!(parse_edition(&header, Version::V1_0).is_ok(), "{}", header);
+        assert!(parse_edition(body, Version::LATEST).is_ok(), "{}", body);
+    }
+    
+    println!("✓ Syntax newer than the declared version is rejected with the version it needs");
+}
+
+#[test]
+fn test_edition_applies_without_header() {
+    let source = "func main() {\n    let x = 1;\n}";
+    assert!(matches!(parse_edition(source, Version::V1_1), Err(ParserError::FeatureRequiresVersion { .. })));
+    
+    // A header wins over --edition, in either direction
+    assert!(parse_edition(&format!("minilang 1.2;\n{}", source), Version::V1_0).is_ok());
+    let older = format!("minilang 1.1;\n{}", source);
+    assert!(parse_edition(&older, Version::LATEST).is_err());
+    
+    println!("✓ --edition sets the version of files without a header");
+}
+
+#[test]
+fn test_unsupported_version_header() {
+    for header in ["minilang 9.0;", "minilang 1;", "minilang 1.10;"] {
+        let source = format!("{}\nfunc main() {{}}", header);
+        let error = parse_expect_error(&source);
+        let ParserError::UnsupportedVersion { span, .. } = &error else {
+            panic!("Expected UnsupportedVersion for {:?}, got {:?}", header, error);
+        };
+        assert_eq!(&source[span.offset()..span.offset() + span.len()], &header[9..header.len() - 1]);
+    }
+    
+    assert_eq!("1.1".parse::<Version>(), Ok(Version::V1_1));
+    assert!("1.".parse::<Version>().unwrap_err().contains("MAJOR.MINOR"));
+    assert!("2.0".parse::<Version>().unwrap_err().contains("1.0, 1.1, 1.2 or 1.3"));
+    
+    println!("✓ Unknown or malformed versions are reported at the header");
+}
@@ -338,4 +338,73 @@ func main() {
     assert_eq!(ast.functions[1].name, "main");
     
     println!("✓ Program with function-local constants parsed");
-}
\ No newline at end of file
+}
+// ==================== AST PRETTY PRINTER TESTS ====================
+
+const PRETTY_SOURCE: &str = r#"func double(n: int) -> int {
+    send n * 2;
+}
+
+func main() {
+    if double(2) > 3 {
+        display "big";
+    }
+}
+"#;
+
+#[test]
+fn test_pretty_renders_nested_nodes() {
+    use minilang_compiler::ast::pretty::{render, PrettyOptions};
+    
+    let tree = render(&parse_program(PRETTY_SOURCE), &PrettyOptions::default());
+    let expected = "\
+├── Function: double
+│   ├── Parameters: 1
+│   │   └── n : Int
+│   ├── Return Type: Int
+│   └── Body: 1 statement(s)
+│       └── send
+│           └── Multiply
+│               ├── identifier: n
+│               └── 2
+└── Function: main
+    ├── Parameters: none
+    └── Body: 1 statement(s)
+        └── if
+            ├── condition:
+            │   └── Greater
+            │       ├── call: double(1 args)
+            │       │   └── 2
+            │       └── 3
+            └── then: 1 statement(s)
+                └── display (1 expression(s))
+                    └── \"big\"
+";
+    assert_eq!(tree, expected);
+    
+    println!("✓ AST pretty printer renders the whole tree");
+}
+
+#[test]
+fn test_pretty_depth_function_and_spans() {
+    use minilang_compiler::ast::pretty::{render, PrettyOptions};
+    
+    let program = parse_program(PRETTY_SOURCE);
+    let options = PrettyOptions {
+        max_depth: Some(2),
+        functions: vec!["main".to_string()],
+        spans: true,
+    };
+    let tree = render(&program, &options);
+    let main = &program.functions[1];
+    let body = &main.body.span;
+    assert_eq!(tree, format!(
+        "└── Function: main [{}..{}]\n    ├── Parameters: none\n    └── Body: 1 statement(s) [{}..{}] …\n",
+        main.span.start, main.span.end, body.start, body.end,
+    ));
+    
+    let options = PrettyOptions { functions: vec!["missing".to_string()], ..Default::default() };
+    assert_eq!(render(&program, &options), "(empty program)\n");
+    
+    println!("✓ AST pretty printer limits depth, filters functions and shows spans");
+}
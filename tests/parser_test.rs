@@ -1,7 +1,7 @@
 // tests/parser_test.rs - Basic parser functionality tests
 
 use minilang_compiler::{Lexer, Parser, Program, Statement, Expression};
-use minilang_compiler::ast::Literal; 
+use minilang_compiler::ast::{Literal, ReturnStmt, Type};
 use pretty_assertions::assert_eq;
 
 /// Helper to parse source code
@@ -95,6 +95,76 @@ func third() { }
     println!("✓ Multiple functions parsed");
 }
 
+#[test]
+fn test_parse_pure_function() {
+    let source = r#"
+pure func square(x: int) -> int { send x * x; }
+func main() { }
+"#;
+
+    let ast = parse(source).unwrap();
+    assert_eq!(ast.functions.len(), 2);
+    assert!(ast.functions[0].is_pure);
+    assert_eq!(ast.functions[0].name, "square");
+    assert!(!ast.functions[1].is_pure);
+
+    println!("✓ Pure function qualifier parsed");
+}
+
+#[test]
+fn test_parse_function_attributes() {
+    let source = r#"
+@inline
+@deprecated("use cube")
+pure func square(x: int) -> int { send x * x; }
+func main() { }
+"#;
+
+    let ast = parse(source).unwrap();
+    let square = &ast.functions[0];
+    assert!(square.is_pure);
+    assert_eq!(square.attributes.len(), 2);
+    assert!(square.has_attribute("inline"));
+    assert_eq!(square.attribute("inline").unwrap().argument, None);
+    assert_eq!(square.attribute("deprecated").unwrap().argument.as_deref(), Some("use cube"));
+    assert_eq!(square.span.start, source.find("@inline").unwrap());
+    assert!(ast.functions[1].attributes.is_empty());
+
+    println!("✓ Function attributes parsed");
+}
+
+#[test]
+fn test_parse_attribute_requires_string_argument() {
+    let source = "@deprecated(42) func old() { }";
+    assert!(parse(source).is_err());
+    println!("✓ Non-string attribute argument rejected");
+}
+
+#[test]
+fn test_parse_test_blocks() {
+    let source = r#"
+func test(x: int) -> int { send x; }
+test "adds" {
+    let test: int = test(1);
+    assert(test == 1);
+}
+func main() { }
+"#;
+
+    let mut ast = parse(source).unwrap();
+    assert_eq!(ast.functions.len(), 2);
+    assert_eq!(ast.tests.len(), 1);
+    assert_eq!(ast.tests[0].name, "adds");
+    assert_eq!(ast.tests[0].body.statements.len(), 2);
+    assert_eq!(ast.tests[0].span.start, source.find("test \"adds\"").unwrap());
+
+    ast.include_tests();
+    assert!(ast.tests.is_empty());
+    assert!(ast.functions.iter().any(|f| f.name == "test \"adds\""));
+
+    println!("✓ Test blocks parsed alongside a function named test");
+}
+
 // ==================== VARIABLE DECLARATION TESTS ====================
 
 #[test]
@@ -202,6 +272,81 @@ func main() {
     println!("✓ Array declarations parsed");
 }
 
+#[test]
+fn test_parse_inferred_array_size() {
+    let source = r#"
+func main() {
+    let arr: float[] = [1.5, 2.5];
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    match &ast.functions[0].body.statements[0] {
+        Statement::Let(let_stmt) => {
+            assert_eq!(let_stmt.typ, Some(Type::Array(Box::new(Type::Float), 2)));
+        }
+        _ => panic!("Expected let statement"),
+    }
+    
+    // Without a literal there is nothing to infer the size from
+    assert!(parse("func main() { let arr: int[]; }").is_err());
+    assert!(parse("func f(a: int[]) { }").is_err());
+    
+    println!("✓ Inferred array size parsed");
+}
+
+#[test]
+fn test_parse_let_without_annotation() {
+    let source = r#"
+func main() {
+    let count = 10;
+    for let i = 0; i < count; i = i + 1 { }
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    let Statement::Let(let_stmt) = &ast.functions[0].body.statements[0] else {
+        panic!("Expected let statement");
+    };
+    assert_eq!(let_stmt.typ, None);
+    assert!(let_stmt.value.is_some());
+    
+    let Statement::For(for_stmt) = &ast.functions[0].body.statements[1] else {
+        panic!("Expected for statement");
+    };
+    assert!(matches!(for_stmt.init.as_deref(), Some(Statement::Let(init)) if init.typ.is_none()));
+    
+    // Parameters still need their types
+    assert!(parse("func f(a) { }").is_err());
+    
+    println!("✓ Let without a type annotation parsed");
+}
+
+#[test]
+fn test_parse_const_array() {
+    let source = r#"
+func main() {
+    const TABLE: int[] = [1, 2, 3, 4];
+    const NAMES: string[2] = ["a", "b"];
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    let types: Vec<Type> = ast.functions[0].body.statements.iter()
+        .map(|statement| match statement {
+            Statement::Const(const_stmt) => const_stmt.typ.clone(),
+            _ => panic!("Expected const statement"),
+        })
+        .collect();
+    assert_eq!(types, vec![
+        Type::Array(Box::new(Type::Int), 4),
+        Type::Array(Box::new(Type::String), 2),
+    ]);
+    assert!(parse("func main() { const N: int[] = 3; }").is_err());
+    
+    println!("✓ Const arrays parsed");
+}
+
 
 
 // ==================== EXPRESSION TESTS ====================
@@ -278,6 +423,149 @@ func main() {
     println!("✓ Operator precedence parsed correctly");
 }
 
+#[test]
+fn test_precedence_table_drives_parsing() {
+    use minilang_compiler::BinaryOp;
+    
+    let operators = [
+        BinaryOp::Or, BinaryOp::And,
+        BinaryOp::Equal, BinaryOp::NotEqual,
+        BinaryOp::Less, BinaryOp::Greater, BinaryOp::LessEqual, BinaryOp::GreaterEqual,
+        BinaryOp::Add, BinaryOp::Subtract,
+        BinaryOp::Multiply, BinaryOp::Divide, BinaryOp::Modulo,
+    ];
+    
+    // Every pair: the tighter operator groups first, ties group to the left
+    for first in &operators {
+        for second in &operators {
+            let source = format!(
+                "func main() {{ let v: int = a {} b {} c; }}",
+                first.symbol(), second.symbol()
+            );
+            let ast = parse(&source).unwrap();
+            let Statement::Let(let_stmt) = &ast.functions[0].body.statements[0] else {
+                panic!("Expected let statement");
+            };
+            let Some(Expression::Binary(root)) = &let_stmt.value else {
+                panic!("Expected binary expression for {}", source);
+            };
+            
+            if second.precedence() > first.precedence() {
+                assert_eq!(&root.op, first, "{}", source);
+                assert!(matches!(&*root.right, Expression::Binary(inner) if &inner.op == second), "{}", source);
+            } else {
+                assert_eq!(&root.op, second, "{}", source);
+                assert!(matches!(&*root.left, Expression::Binary(inner) if &inner.op == first), "{}", source);
+            }
+        }
+    }
+    
+    println!("✓ Parser follows the precedence table for all operator pairs");
+}
+
+#[test]
+fn test_unary_expression_spans() {
+    let source = "func main() { let v: int = -x * 2 + NOT y; }";
+    
+    let ast = parse(source).unwrap();
+    let Statement::Let(let_stmt) = &ast.functions[0].body.statements[0] else {
+        panic!("Expected let statement");
+    };
+    let Some(Expression::Binary(add)) = &let_stmt.value else {
+        panic!("Expected binary expression");
+    };
+    assert_eq!(&source[add.span.start..add.span.end], "-x * 2 + NOT y");
+    match &*add.right {
+        Expression::Unary(not) => assert_eq!(&source[not.span.start..not.span.end], "NOT y"),
+        _ => panic!("Expected unary NOT"),
+    }
+    
+    println!("✓ Unary expressions carry their source span");
+}
+
+#[test]
+fn test_parse_records_parentheses() {
+    let source = "func main() { let x: int = (a + b) * c; }";
+    
+    let ast = parse(source).unwrap();
+    match &ast.functions[0].body.statements[0] {
+        Statement::Let(let_stmt) => match let_stmt.value.as_ref().unwrap() {
+            Expression::Binary(mul) => {
+                assert!(!mul.parenthesized);
+                // The span covers the user's text, parens included
+                assert_eq!(&source[mul.span.start..mul.span.end], "(a + b) * c");
+                match &*mul.left {
+                    Expression::Binary(add) => {
+                        assert!(add.parenthesized);
+                        assert_eq!(&source[add.span.start..add.span.end], "(a + b)");
+                    }
+                    _ => panic!("Expected grouped addition"),
+                }
+            }
+            _ => panic!("Expected binary expression"),
+        },
+        _ => panic!("Expected let statement"),
+    }
+    
+    println!("✓ Parenthesized grouping recorded");
+}
+
+#[test]
+fn test_expression_display_keeps_grouping() {
+    let source = r#"
+func main() {
+    let a: int = (x + y) * z - (w);
+    let b: bool = NOT (p AND q) OR (r);
+    let c: int = a - (b - c);
+    let d: int = ((x * y)) + 1;
+    arr[i + 1] = f(a, "say \"hi\"", [1, 2]);
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    let printed: Vec<String> = ast.functions[0].body.statements.iter()
+        .map(|stmt| match stmt {
+            Statement::Let(let_stmt) => let_stmt.value.as_ref().unwrap().to_string(),
+            Statement::Expression(expr_stmt) => expr_stmt.expression.to_string(),
+            _ => panic!("Unexpected statement"),
+        })
+        .collect();
+    
+    assert_eq!(printed, vec![
+        "(x + y) * z - w",
+        "NOT (p AND q) OR r",
+        "a - (b - c)",
+        "(x * y) + 1",
+        r#"arr[i + 1] = f(a, "say \"hi\"", [1, 2])"#,
+    ]);
+    
+    println!("✓ Expressions print with the user's grouping");
+}
+
+#[test]
+fn test_expression_display_adds_required_parens() {
+    // Trees built by the optimizer carry no parens flag; precedence still has to hold
+    let sum = parse("func main() { let s: int = a + b; }").unwrap();
+    let Statement::Let(let_stmt) = &sum.functions[0].body.statements[0] else {
+        panic!("Expected let statement");
+    };
+    let mut sum = let_stmt.value.clone().unwrap();
+    if let Expression::Binary(binary) = &mut sum {
+        binary.parenthesized = false;
+    }
+    
+    let negated = Expression::Unary(minilang_compiler::ast::UnaryExpr {
+        op: minilang_compiler::UnaryOp::Negate,
+        operand: Box::new(sum),
+        span: Default::default(),
+        id: Default::default(),
+        parenthesized: false,
+    });
+    assert_eq!(negated.to_string(), "-(a + b)");
+    
+    println!("✓ Required parens added when printing rewritten trees");
+}
+
 // ==================== CONTROL FLOW TESTS ====================
 
 #[test]
@@ -454,6 +742,28 @@ func main() {
     println!("✓ Display statements parsed");
 }
 
+#[test]
+fn test_parse_display_line_endings() {
+    let source = r#"
+func main() {
+    display "a";
+    displayln "b";
+    display_raw "c", 1;
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    let newlines: Vec<bool> = ast.functions[0].body.statements.iter()
+        .map(|stmt| match stmt {
+            Statement::Display(display) => display.newline,
+            _ => panic!("Expected display"),
+        })
+        .collect();
+    assert_eq!(newlines, vec![true, true, false]);
+    
+    println!("✓ Display line endings parsed");
+}
+
 #[test]
 fn test_parse_return_statement() {
     let source = r#"
@@ -523,6 +833,228 @@ func main() {
     println!("✓ Continue statement parsed");
 }
 
+#[test]
+fn test_parse_defer_statement() {
+    let source = r#"
+func main() {
+    defer {
+        display "bye";
+    }
+    display "hi";
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    match &ast.functions[0].body.statements[0] {
+        Statement::Defer(defer_stmt) => {
+            assert_eq!(defer_stmt.body.statements.len(), 1);
+        }
+        _ => panic!("Expected defer statement"),
+    }
+    
+    println!("✓ Defer statement parsed");
+}
+
+#[test]
+fn test_parse_spawn_statement() {
+    let source = r#"
+func main() {
+    let results: chan = channel(4);
+    spawn {
+        chan_send(results, 1);
+    }
+    let spawn: int = 2;
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    let statements = &ast.functions[0].body.statements;
+    match &statements[0] {
+        Statement::Let(let_stmt) => assert_eq!(let_stmt.typ, Some(Type::Channel)),
+        _ => panic!("Expected let statement"),
+    }
+    match &statements[1] {
+        Statement::Spawn(spawn_stmt) => assert_eq!(spawn_stmt.body.statements.len(), 1),
+        _ => panic!("Expected spawn statement"),
+    }
+    // `spawn` is only a keyword in front of a block
+    assert!(matches!(&statements[2], Statement::Let(_)));
+    
+    println!("✓ Spawn statement parsed");
+}
+
+#[test]
+fn test_parse_parallel_for_statement() {
+    let source = r#"
+func main() {
+    parallel for i in 0..n + 1 {
+        display i;
+    }
+    let parallel: int = 2;
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    let statements = &ast.functions[0].body.statements;
+    let Statement::For(for_stmt) = &statements[0] else { panic!("Expected for statement") };
+    assert!(for_stmt.parallel);
+    match for_stmt.init.as_deref() {
+        Some(Statement::Let(let_stmt)) => {
+            assert_eq!(let_stmt.name, "i");
+            assert_eq!(let_stmt.typ, Some(Type::Int));
+        }
+        _ => panic!("Expected the loop variable"),
+    }
+    assert!(matches!(&for_stmt.condition, Some(Expression::Binary(_))));
+    assert!(for_stmt.update.is_some());
+    assert_eq!(for_stmt.body.statements.len(), 1);
+    // `parallel` is only a keyword in front of `for`
+    assert!(matches!(&statements[1], Statement::Let(_)));
+    
+    println!("✓ Parallel for statement parsed");
+}
+
+#[test]
+fn test_parse_json_literal() {
+    let source = r#"
+func main() {
+    let j: json = json {"name": "{x}", "list": [1, -2.5, true, null], "empty": {}};
+    if json {
+        display 1;
+    }
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    let statements = &ast.functions[0].body.statements;
+    let Statement::Let(let_stmt) = &statements[0] else { panic!("Expected let statement") };
+    assert_eq!(let_stmt.typ, Some(Type::Json));
+    // Written out as the JSON text json_parse reads, braces in strings and all
+    let Some(Expression::Call(call)) = &let_stmt.value else { panic!("Expected a call") };
+    assert_eq!(call.function, "json_parse");
+    let Expression::Literal(text) = &call.args[0] else { panic!("Expected the JSON text") };
+    assert_eq!(text.value, Literal::String(r#"{"name":"{x}","list":[1,-2.5,true,null],"empty":{}}"#.to_string()));
+    // A block that doesn't start like an object is still a condition's
+    let Statement::If(if_stmt) = &statements[1] else { panic!("Expected if statement") };
+    assert!(matches!(&if_stmt.condition, Expression::Identifier(id) if id.name == "json"));
+    
+    println!("✓ Json literal parsed");
+}
+
+#[test]
+fn test_parse_tuple_return_and_destructuring() {
+    let source = r#"
+func divmod(a: int, b: int) -> (int, int) {
+    send (a / b, a % b);
+}
+
+func main() {
+    let (q, _) = divmod(7, 2);
+    let grouped: int = (1 + 2) * 3;
+}
+"#;
+    
+    let ast = parse(source).unwrap();
+    assert_eq!(ast.functions[0].return_type, Some(Type::Tuple(vec![Type::Int, Type::Int])));
+    match &ast.functions[0].body.statements[0] {
+        Statement::Return(ReturnStmt { value: Some(Expression::Literal(lit)), .. }) => {
+            assert!(matches!(&lit.value, Literal::Tuple(elements) if elements.len() == 2));
+        }
+        _ => panic!("Expected send of a tuple literal"),
+    }
+    match &ast.functions[1].body.statements[0] {
+        Statement::LetTuple(let_tuple) => {
+            assert_eq!(let_tuple.names, vec!["q".to_string(), "_".to_string()]);
+        }
+        _ => panic!("Expected tuple destructuring"),
+    }
+    // Parentheses without a comma are still just grouping
+    assert!(matches!(&ast.functions[1].body.statements[1], Statement::Let(_)));
+    
+    println!("✓ Tuple types, literals and destructuring parsed");
+}
+
+// ==================== CONDITIONAL COMPILATION TESTS ====================
+
+fn parse_with_defines(source: &str, defines: &[(&str, Literal)]) -> Result<Program, Box<dyn std::error::Error>> {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize()?;
+    let defines = defines.iter().map(|(name, value)| (name.to_string(), value.clone())).collect();
+    let mut parser = Parser::new(tokens, source.to_string()).with_defines(defines);
+    Ok(parser.parse_program()?)
+}
+
+/// Strings displayed by the block an `@if` resolved to
+fn displayed_in_conditional(program: &Program) -> Vec<String> {
+    let Statement::Block(block) = &program.functions[0].body.statements[0] else {
+        panic!("expected @if to resolve to a block");
+    };
+    block.statements.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Display(display) => match &display.expressions[0] {
+                Expression::Literal(lit) => match &lit.value {
+                    Literal::String(text) => Some(text.clone()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_conditional_selects_branch_from_defines() {
+    let source = r#"
+func main() {
+    @if DEBUG {
+        display "debug";
+    } else @if LEVEL >= 2 AND MODE == "fast" {
+        display "fast";
+    } else {
+        display "release";
+    }
+}
+"#;
+
+    let debug = parse_with_defines(source, &[("DEBUG", Literal::Boolean(true))]).unwrap();
+    assert_eq!(displayed_in_conditional(&debug), vec!["debug"]);
+
+    let fast = parse_with_defines(source, &[
+        ("LEVEL", Literal::Integer(3)),
+        ("MODE", Literal::String("fast".to_string())),
+    ]).unwrap();
+    assert_eq!(displayed_in_conditional(&fast), vec!["fast"]);
+
+    let release = parse_with_defines(source, &[("LEVEL", Literal::Integer(1))]).unwrap();
+    assert_eq!(displayed_in_conditional(&release), vec!["release"]);
+
+    println!("✓ @if branches resolved from defines");
+}
+
+#[test]
+fn test_conditional_without_else_leaves_empty_block() {
+    let source = r#"
+func main() {
+    @if NOT DEBUG { display "quiet"; }
+}
+"#;
+
+    let program = parse_with_defines(source, &[("DEBUG", Literal::Integer(1))]).unwrap();
+    assert!(displayed_in_conditional(&program).is_empty());
+    println!("✓ Disabled @if removes its body");
+}
+
+#[test]
+fn test_conditional_rejects_runtime_expressions() {
+    for condition in ["check()", "LEVEL + 1", "MODE < 2"] {
+        let source = format!("func main() {{ @if {} {{ }} }}", condition);
+        let result = parse_with_defines(&source, &[("MODE", Literal::String("fast".to_string()))]);
+        assert!(result.is_err(), "'{}' should not be a valid condition", condition);
+    }
+    println!("✓ Non-constant @if conditions rejected");
+}
+
 // ==================== COMPLEX TESTS ====================
 
 #[test]
@@ -542,7 +1074,7 @@ func main() {
 "#;
     
     let ast = parse(source).unwrap();
-    assert!(ast.functions[0].body.statements.len() > 0);
+    assert!(!ast.functions[0].body.statements.is_empty());
     
     println!("✓ Nested blocks parsed");
 }
@@ -699,3 +1231,246 @@ func main() {
     assert!(result.is_err());
     println!("✓ Empty interpolation correctly rejected");
 }
+
+#[test]
+fn test_parse_format_placeholders() {
+    let source = r#"
+func main() {
+    let s: string = format("{} of {total}", 1);
+}
+"#;
+    
+    let program = parse(source).unwrap();
+    let func = &program.functions[0];
+    match &func.body.statements[0] {
+        Statement::Let(let_stmt) => match let_stmt.value.as_ref().unwrap() {
+            Expression::Call(call) => {
+                assert_eq!(call.function, "format");
+                assert_eq!(call.args.len(), 2);
+                assert!(matches!(
+                    &call.args[0],
+                    Expression::Literal(lit) if matches!(lit.value, Literal::InterpolatedString(_))
+                ));
+            }
+            _ => panic!("Expected format call"),
+        },
+        _ => panic!("Expected let statement"),
+    }
+    println!("✓ format keeps {{}} placeholders in its template");
+}
+
+#[test]
+fn test_parse_format_unclosed_brace_fails() {
+    for template in ["x{", "x{total", "{} and {"] {
+        let source = format!("func main() {{\n    display format(\"{}\", 5);\n}}\n", template);
+        let error = parse(&source).unwrap_err();
+        assert_eq!(error.to_string(), "invalid expression", "{}", template);
+    }
+    println!("✓ An unclosed {{ in a format template is rejected, not a placeholder");
+}
+
+// ==================== NODE ID AND SPAN TESTS ====================
+
+/// Collect the id and span of every node in a program
+fn collect_nodes(program: &mut Program) -> Vec<(u32, std::ops::Range<usize>)> {
+    let mut nodes = Vec::new();
+    program.visit_nodes_mut(&mut |id, span| nodes.push((id.0, span.start..span.end)));
+    nodes
+}
+
+const NODE_SOURCE: &str = r#"
+func add(a: int, b: int) -> int {
+    send a + b;
+}
+
+func main() {
+    const LIMIT: int = 3;
+    let arr: int[3] = [1, 2, 3];
+    let (q, r) = (7 / 2, 7 % 2);
+    let i: int;
+    for i = 0; i < LIMIT; i = i + 1 {
+        if arr[i] > 1 {
+            display "big {arr[i] * 2} at { i }";
+        } else {
+            continue;
+        }
+    }
+    while NOT false {
+        break;
+    }
+    display add(q, -r);
+}
+"#;
+
+#[test]
+fn test_node_ids_are_unique() {
+    let mut program = parse(NODE_SOURCE).unwrap();
+    let nodes = collect_nodes(&mut program);
+    
+    let mut ids: Vec<u32> = nodes.iter().map(|(id, _)| *id).collect();
+    assert!(ids.iter().all(|&id| id != 0), "every node should be numbered");
+    let count = ids.len();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), count, "node ids should be unique");
+    
+    println!("✓ {} nodes numbered uniquely", count);
+}
+
+#[test]
+fn test_node_spans_cover_source() {
+    let mut program = parse(NODE_SOURCE).unwrap();
+    for (id, span) in collect_nodes(&mut program) {
+        assert!(span.start < span.end, "node {} has empty span {:?}", id, span);
+        assert!(span.end <= NODE_SOURCE.len(), "node {} span {:?} is outside the source", id, span);
+    }
+    println!("✓ Every node has a real span");
+}
+
+#[test]
+fn test_for_init_and_update_spans() {
+    let program = parse(NODE_SOURCE).unwrap();
+    let for_stmt = program.functions[1].body.statements.iter()
+        .find_map(|stmt| match stmt {
+            Statement::For(for_stmt) => Some(for_stmt),
+            _ => None,
+        })
+        .unwrap();
+    
+    let init = match for_stmt.init.as_deref() {
+        Some(Statement::Expression(expr_stmt)) => expr_stmt,
+        _ => panic!("Expected expression init"),
+    };
+    assert_eq!(&NODE_SOURCE[init.span.start..init.span.end], "i = 0;");
+    match &init.expression {
+        Expression::Assign(assign) => {
+            assert_eq!(&NODE_SOURCE[assign.span.start..assign.span.end], "i = 0");
+        }
+        _ => panic!("Expected assignment init"),
+    }
+    
+    match &for_stmt.update {
+        Some(Expression::Assign(assign)) => {
+            assert_eq!(&NODE_SOURCE[assign.span.start..assign.span.end], "i = i + 1");
+        }
+        _ => panic!("Expected assignment update"),
+    }
+    println!("✓ for init and update point at their source");
+}
+
+#[test]
+fn test_interpolation_spans_point_into_source() {
+    let source = r#"func main() { let i: int = 1; display "at { i } and {i * 2}"; }"#;
+    let program = parse(source).unwrap();
+    
+    let parts = match &program.functions[0].body.statements[1] {
+        Statement::Display(display) => match &display.expressions[0] {
+            Expression::Literal(lit) => match &lit.value {
+                Literal::InterpolatedString(parts) => parts.clone(),
+                _ => panic!("Expected interpolated string"),
+            },
+            _ => panic!("Expected literal"),
+        },
+        _ => panic!("Expected display"),
+    };
+    
+    let spans: Vec<&str> = parts.iter()
+        .filter_map(|part| match part {
+            minilang_compiler::StringPart::Expression(expr) => Some(expr),
+            _ => None,
+        })
+        .map(|expr| match expr {
+            Expression::Identifier(id) => &source[id.span.start..id.span.end],
+            Expression::Binary(bin) => &source[bin.span.start..bin.span.end],
+            _ => panic!("Unexpected interpolation expression"),
+        })
+        .collect();
+    assert_eq!(spans, vec!["i", "i * 2"]);
+    println!("✓ Interpolated expressions carry source spans");
+}
+
+#[test]
+fn test_assignment_spans_stop_before_semicolon() {
+    let source = "func main() {\n    let arr: int[2] = [1, 2];\n    let x: int = 0;\n    arr[0] = arr[1] + 1;\n    x = 5;\n}\n";
+    let program = parse(source).unwrap();
+    let text = |span: &minilang_compiler::ast::Span| &source[span.start..span.end];
+    
+    let Statement::Expression(stmt) = &program.functions[0].body.statements[2] else {
+        panic!("Expected array assignment");
+    };
+    assert_eq!(text(&stmt.span), "arr[0] = arr[1] + 1;");
+    let Expression::Assign(assign) = &stmt.expression else { panic!("Expected assignment") };
+    assert_eq!(text(&assign.span), "arr[0] = arr[1] + 1");
+    let Expression::Binary(pair) = assign.value.as_ref() else { panic!("Expected index/value pair") };
+    assert_eq!(text(&pair.span), "arr[0] = arr[1] + 1");
+    let Expression::Index(index) = pair.left.as_ref() else { panic!("Expected index target") };
+    assert_eq!(text(&index.span), "arr[0]");
+    
+    let Statement::Expression(stmt) = &program.functions[0].body.statements[3] else {
+        panic!("Expected assignment");
+    };
+    let Expression::Assign(assign) = &stmt.expression else { panic!("Expected assignment") };
+    assert_eq!(text(&assign.span), "x = 5");
+    println!("✓ Assignment expressions end at their value, like for-loop updates");
+}
+
+#[test]
+fn test_no_invalid_spans_in_any_construct() {
+    let source = r#"
+@inline
+func sq(n: int) -> int {
+    send n * n;
+}
+
+@deprecated("use sq")
+func pair() -> (int, bool) {
+    send (1, true);
+}
+
+func main() {
+    let (a, _) = pair();
+    let arr: int[3] = [1, 2, 3];
+    defer {
+        displayln "bye {a}";
+    }
+    do {
+        arr[0] = sq(arr[1]);
+        break;
+    } while false;
+    for let j: int = -1; NOT (j > 2); j = j + 1 {
+        display -j, format("{}", j);
+    }
+    {
+        display approx_equal(1.0, 2.0, 0.1);
+    }
+}
+"#;
+    let program = parse(source).unwrap();
+    assert_eq!(program.invalid_spans(source.len()), vec![]);
+    
+    let mut empty = program.clone();
+    empty.functions[0].span = Default::default();
+    assert_eq!(empty.invalid_spans(source.len()).len(), 1);
+    println!("✓ Every construct gets a non-empty span inside the source");
+}
+
+#[test]
+fn test_node_ids_unique_after_optimization() {
+    let source = r#"
+func main() {
+    let x: int = 12;
+    let y: int = x / -4;
+    display y, x % 8, 2 + 3;
+}
+"#;
+    let mut program = parse(source).unwrap();
+    minilang_compiler::Optimizer::new(2).optimize(&mut program);
+    
+    let mut ids: Vec<u32> = collect_nodes(&mut program).into_iter().map(|(id, _)| id).collect();
+    assert!(ids.iter().all(|&id| id != 0));
+    let count = ids.len();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), count);
+    println!("✓ Optimized program keeps unique node ids");
+}
@@ -0,0 +1,110 @@
+// tests/peephole_tests.rs - Clean-up pass over generated C
+
+use minilang_compiler::examples::EXAMPLES;
+use minilang_compiler::peephole;
+use minilang_compiler::{CodeGenerator, Lexer, Optimizer, Parser, TypeChecker};
+use pretty_assertions::assert_eq;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn generate(source: &str, peephole: bool) -> String {
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    TypeChecker::new().check_program(&program).unwrap();
+    Optimizer::new(1).optimize(&mut program);
+    CodeGenerator::new().with_peephole(peephole).generate(&program).unwrap()
+}
+
+fn run_c(c_code: &str) -> String {
+    let temp_dir = TempDir::new().unwrap();
+    let c_file = temp_dir.path().join("test.c");
+    let exe_file = temp_dir.path().join("test");
+    fs::write(&c_file, c_code).unwrap();
+
+    let output = Command::new("gcc")
+        .arg(&c_file)
+        .arg("-o")
+        .arg(&exe_file)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "GCC failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    let output = Command::new(&exe_file).output().unwrap();
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+// ==================== RULE TESTS ====================
+
+#[test]
+fn test_redundant_parens_removed() {
+    let code = "    if ((x > 5)) {\n    int y = (a + b);\n    return ((a * b) + c);\n    f((a - 1), (b));\n";
+    let (cleaned, stats) = peephole::optimize(code);
+    assert_eq!(cleaned, "    if (x > 5) {\n    int y = a + b;\n    return (a * b) + c;\n    f(a - 1, b);\n");
+    assert_eq!(stats.parens_removed, 5);
+    println!("✓ Redundant parentheses removed");
+}
+
+#[test]
+fn test_needed_parens_kept() {
+    let code = concat!(
+        "    int y = (a + b) * c;\n",
+        "    x = (c ? a : b) + 1;\n",
+        "    f((a, b));\n",
+        "    int z = (int)(w);\n",
+        "    printf(\"(%d)\", y);\n",
+        "#define SQUARE(x) ((x) * (x))\n",
+        "__attribute__((constructor)) static void start(void) {\n",
+    );
+    let (cleaned, stats) = peephole::optimize(code);
+    assert_eq!(cleaned, code);
+    assert_eq!(stats.parens_removed, 0);
+    println!("✓ Meaningful parentheses kept");
+}
+
+#[test]
+fn test_trailing_and_duplicate_returns_removed() {
+    let code = "void f(void) {\n    g();\n    return;\n}\nint h(void) {\n    return 1;\n    return 0;\n}\nvoid k(void) {\n    if (x) {\n        return;\n    }\n}\n";
+    let (cleaned, stats) = peephole::optimize(code);
+    assert_eq!(cleaned, "void f(void) {\n    g();\n}\nint h(void) {\n    return 1;\n}\nvoid k(void) {\n    if (x) {\n        return;\n    }\n}\n");
+    assert_eq!(stats.returns_removed, 2);
+    println!("✓ Redundant returns removed");
+}
+
+#[test]
+fn test_adjacent_printfs_merged() {
+    let code = concat!(
+        "    printf(\"%s\", \"Total: \");\n",
+        "    printf(\"%d\", count);\n",
+        "    printf(\"%s\", \" at 100%\");\n",
+        "    printf(\"%s\", done ? \"true\" : \"false\");\n",
+    );
+    let (cleaned, stats) = peephole::optimize(code);
+    assert_eq!(cleaned, "    printf(\"Total: %d at 100%%%s\", count, done ? \"true\" : \"false\");\n");
+    assert_eq!(stats.printfs_merged, 3);
+    println!("✓ Adjacent printf calls merged");
+}
+
+#[test]
+fn test_printfs_with_calls_not_merged() {
+    let code = "    printf(\"%d\", next());\n    printf(\"%d\", total);\n    printf(\"%d\", i = 2);\n";
+    let (cleaned, stats) = peephole::optimize(code);
+    assert_eq!(cleaned, code);
+    assert_eq!(stats.printfs_merged, 0);
+    println!("✓ printf calls with side effects kept apart");
+}
+
+// ==================== END-TO-END TESTS ====================
+
+#[test]
+fn test_peephole_preserves_example_output() {
+    for example in EXAMPLES {
+        let plain = generate(example.source, false);
+        let tidy = generate(example.source, true);
+        assert!(tidy.len() < plain.len(), "{}: expected shorter C", example.name);
+        assert_eq!(run_c(&tidy), run_c(&plain), "{}: output changed", example.name);
+    }
+    println!("✓ Peephole output behaves like the original for every example");
+}
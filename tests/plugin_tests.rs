@@ -0,0 +1,150 @@
+// tests/plugin_tests.rs - Compiler plugins: their passes, lints and metrics
+
+use minilang_compiler::analyzer::{analyze_program_with_plugins, CyclomaticMode};
+use minilang_compiler::ast::{Expression, Literal, LiteralExpr, Span};
+use minilang_compiler::optimizer::for_each_expression_in_block;
+use minilang_compiler::plugin::{self, ApiVersion, CompilerPlugin, Lint, Metric, API_VERSION};
+use minilang_compiler::{Function, Lexer, Optimizer, Parser, Program, TypeChecker};
+
+/// A course's plugin: turns `7` into `8`, flags functions named `helper`
+/// and counts each function's parameters
+struct Course;
+
+static COURSE: Course = Course;
+
+impl CompilerPlugin for Course {
+    fn name(&self) -> &'static str {
+        "course"
+    }
+
+    fn api_version(&self) -> ApiVersion {
+        ApiVersion::new(1, 0)
+    }
+
+    fn optimize(&self, program: &mut Program) -> usize {
+        let mut changes = 0;
+        for function in program.functions.iter_mut().filter(|f| !f.has_attribute("noopt")) {
+            for_each_expression_in_block(&mut function.body, &mut |expr| {
+                if let Expression::Literal(LiteralExpr { value: value @ Literal::Integer(7), .. }) = expr {
+                    *value = Literal::Integer(8);
+                    changes += 1;
+                }
+            });
+        }
+        changes
+    }
+
+    fn lint(&self, program: &Program) -> Vec<Lint> {
+        program.functions.iter()
+            .filter(|function| function.name == "helper")
+            .map(|function| Lint {
+                code: "vague_name".to_string(),
+                message: format!("'{}' does not say what it does", function.name),
+                label: "here".to_string(),
+                help: None,
+                span: Span { start: function.span.start, end: function.span.start + 4 },
+            })
+            .collect()
+    }
+
+    fn metrics(&self, function: &Function) -> Vec<Metric> {
+        vec![Metric { name: "params".to_string(), value: function.params.len() as f64 }]
+    }
+}
+
+fn parse(source: &str) -> Program {
+    let tokens = Lexer::new(source).tokenize().expect("Lexer failed");
+    Parser::new(tokens, source.to_string()).parse_program().expect("Parser failed")
+}
+
+const SOURCE: &str = "func helper(a: int, b: int) -> int {\n    send a + b + 7;\n}\n\n@noopt\nfunc kept() -> int {\n    send 7;\n}\n\nfunc main() {\n    display helper(1, 2) + kept();\n}\n";
+
+// ==================== VERSION TESTS ====================
+
+#[test]
+fn test_api_versions_follow_semver() {
+    assert!(ApiVersion::new(1, 0).works_with(ApiVersion::new(1, 0)));
+    assert!(ApiVersion::new(1, 0).works_with(ApiVersion::new(1, 3)));
+    assert!(!ApiVersion::new(1, 4).works_with(ApiVersion::new(1, 3)));
+    assert!(!ApiVersion::new(2, 0).works_with(ApiVersion::new(1, 3)));
+    assert!(!ApiVersion::new(0, 9).works_with(ApiVersion::new(1, 0)));
+    assert_eq!(API_VERSION.to_string(), "1.0");
+    assert!(plugin::rejected().is_empty());
+
+    println!("✓ Plugins load when their API major matches and their minor is not newer");
+}
+
+// ==================== HOOK TESTS ====================
+
+#[test]
+fn test_plugin_pass_runs_with_the_optimizer() {
+    let mut program = parse(SOURCE);
+    let stats = Optimizer::new(1).with_plugins(&[&COURSE]).optimize(&mut program);
+    assert_eq!(stats.plugin_changes, 1);
+    let text = format!("{:?}", program.functions[0].body);
+    assert!(text.contains("Integer(8)") && !text.contains("Integer(7)"), "{}", text);
+    // The plugin leaves @noopt functions alone
+    assert!(format!("{:?}", program.functions[1].body).contains("Integer(7)"));
+
+    // Plugin passes run even without built-in ones
+    let mut program = parse(SOURCE);
+    let stats = Optimizer::with_passes(&[]).with_plugins(&[&COURSE]).optimize(&mut program);
+    assert_eq!(stats.plugin_changes, 1);
+
+    println!("✓ Plugin passes run after the built-in ones until nothing changes");
+}
+
+#[test]
+fn test_plugin_lints_become_warnings() {
+    let program = parse(SOURCE);
+    let mut checker = TypeChecker::new().with_plugins(&[&COURSE]);
+    checker.check_program(&program).expect("type checks");
+    let warnings = checker.get_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code(), "course::vague_name");
+
+    let rendered = format!("{:?}", warnings[0].report(SOURCE, "course.mini"));
+    assert!(rendered.contains("'helper' does not say what it does"), "{}", rendered);
+    assert!(rendered.contains("course.mini:1:1"), "{}", rendered);
+
+    // Lints only run on programs that type check
+    let broken = parse("func helper() {\n    let x: int = \"no\";\n}\n\nfunc main() {\n    helper();\n}\n");
+    let mut checker = TypeChecker::new().with_plugins(&[&COURSE]);
+    assert!(checker.check_program(&broken).is_err());
+    assert!(!checker.get_warnings().iter().any(|warning| warning.code() == "course::vague_name"));
+
+    println!("✓ Plugin lint rules report warnings with the plugin's code");
+}
+
+#[test]
+fn test_plugin_metrics_in_analysis() {
+    let program = parse(SOURCE);
+    let report = analyze_program_with_plugins(&program, SOURCE, CyclomaticMode::default(), &[&COURSE]);
+    assert_eq!(report.functions[0].plugin_metrics.get("course.params"), Some(&2.0));
+    assert_eq!(report.functions[2].plugin_metrics.get("course.params"), Some(&0.0));
+    assert!(serde_json::to_string(&report).unwrap().contains("\"course.params\":2.0"));
+
+    let plain = analyze_program_with_plugins(&program, SOURCE, CyclomaticMode::default(), &[]);
+    assert!(!serde_json::to_string(&plain).unwrap().contains("plugin_metrics"));
+
+    println!("✓ Plugin metrics appear next to the analyzer's own");
+}
+
+#[cfg(feature = "plugin-style-guide")]
+#[test]
+fn test_style_guide_plugin() {
+    let source = "func addTwo(firstValue: int) -> int {\n    send firstValue + 42;\n}\n\nfunc main() {\n    display addTwo(3);\n}\n";
+    let program = parse(source);
+    assert!(plugin::installed().iter().any(|plugin| plugin.name() == "style_guide"));
+
+    let mut checker = TypeChecker::new();
+    checker.check_program(&program).expect("type checks");
+    let codes: Vec<String> = checker.get_warnings().iter().map(|warning| warning.code()).collect();
+    assert_eq!(codes, vec!["style_guide::snake_case", "style_guide::snake_case"]);
+
+    let report = minilang_compiler::analyze_program(&program, source);
+    assert_eq!(report.functions[0].plugin_metrics.get("style_guide.magic_numbers"), Some(&1.0));
+    assert_eq!(report.functions[1].plugin_metrics.get("style_guide.magic_numbers"), Some(&1.0));
+
+    println!("✓ The style guide plugin flags camelCase names and counts magic numbers");
+}
@@ -0,0 +1,105 @@
+// tests/reduce_tests.rs - Shrinking programs for `minilang reduce`
+
+use minilang_compiler::reduce::{self, Check};
+use std::path::Path;
+use std::time::Duration;
+
+const PROGRAM: &str = r#"func twice(x: int) -> int {
+    send x * 2;
+}
+
+func unused(y: int) -> int {
+    let z: int = y + 1;
+    send z;
+}
+
+func main() {
+    let total: int = 0;
+    for let i: int = 0; i < 3; i = i + 1 {
+        if i > 1 {
+            display i; total = total + twice(i);
+        } else {
+            display 0;
+        }
+    }
+    display total;
+}
+"#;
+
+// ==================== DDMIN TESTS ====================
+
+#[test]
+fn test_ddmin_finds_the_needed_items() {
+    let mut tests = 0;
+    let kept = reduce::ddmin((0..16).collect(), &mut |items: &[i32]| {
+        tests += 1;
+        items.contains(&3) && items.contains(&11)
+    });
+    assert_eq!(kept, vec![3, 11]);
+    assert!(tests < 40, "{} tests", tests);
+
+    assert_eq!(reduce::ddmin(vec![1, 2, 3], &mut |_: &[i32]| true), Vec::<i32>::new());
+    assert_eq!(reduce::ddmin(vec![5], &mut |items: &[i32]| !items.is_empty()), vec![5]);
+    println!("✓ ddmin keeps only the items the test needs");
+}
+
+// ==================== REDUCE TESTS ====================
+
+#[test]
+fn test_reduce_removes_functions_and_statements() {
+    let reduction = reduce::reduce(PROGRAM, |candidate| candidate.contains("twice(i)"));
+    assert_eq!(reduction.source, r#"
+
+func main() {
+    for let i: int = 0; i < 3; i = i + 1 {
+        if i > 1 {
+             total = total + twice(i);
+        } else {
+        }
+    }
+}
+"#);
+    assert!(reduction.tests > 0);
+    println!("✓ reduce drops every function and statement the failure does not need");
+}
+
+#[test]
+fn test_reduce_only_checks_programs_that_parse() {
+    let mut checked = Vec::new();
+    let reduction = reduce::reduce(PROGRAM, |candidate| {
+        checked.push(candidate.to_string());
+        candidate.contains("send x * 2;")
+    });
+    assert!(checked.iter().all(|candidate| reduce::parses(candidate)));
+    assert!(reduction.source.trim().starts_with("func twice(x: int) -> int {\n    send x * 2;\n}"));
+    assert!(!reduction.source.contains("main"));
+    assert!(!reduce::parses("func main( {"));
+    println!("✓ reduce hands the check only candidates that parse");
+}
+
+// ==================== CHECK TESTS ====================
+
+#[test]
+fn test_check_command_line() {
+    let path = Path::new("/tmp/my bug.mini");
+    let check = Check { command: "minilang -O2 run {} | grep -q 42".to_string(), timeout: Duration::from_secs(1) };
+    assert_eq!(check.command_line(path), "minilang -O2 run '/tmp/my bug.mini' | grep -q 42");
+    let check = Check { command: "./still-crashes.sh".to_string(), timeout: Duration::from_secs(1) };
+    assert_eq!(check.command_line(path), "./still-crashes.sh '/tmp/my bug.mini'");
+    println!("✓ The candidate's path replaces {{}} or goes at the end");
+}
+
+#[test]
+fn test_check_runs_on_the_candidate() {
+    let check = Check { command: "grep -q twice".to_string(), timeout: Duration::from_secs(10) };
+    assert!(check.passes(PROGRAM, "bug.mini").unwrap());
+    assert!(!check.passes("func main() { }", "bug.mini").unwrap());
+
+    let check = Check { command: "test \"$(basename {})\" = bug.mini".to_string(), timeout: Duration::from_secs(10) };
+    assert!(check.passes(PROGRAM, "bug.mini").unwrap());
+
+    // A check that hangs counts as the failure not showing
+    let check = Check { command: "sleep 5 && test -f {}".to_string(), timeout: Duration::from_millis(100) };
+    assert!(!check.passes(PROGRAM, "bug.mini").unwrap());
+    println!("✓ Checks see the candidate under the original file name and time out");
+}
@@ -0,0 +1,175 @@
+// tests/refactor_tests.rs - Source-to-source refactorings
+
+use minilang_compiler::refactor::{self, Refactoring};
+use minilang_compiler::Defines;
+
+fn early_return(source: &str) -> Refactoring {
+    refactor::early_return(source, &Defines::new())
+}
+
+// ==================== EARLY RETURN TESTS ====================
+
+#[test]
+fn test_nested_guards_become_early_returns() {
+    let source = r#"func process(n: int) {
+    # only positive numbers
+    if n > 0 {
+        let doubled: int = n * 2;
+        if doubled < 100 {
+            displayln "small ", doubled;
+            # done
+        }
+    }
+}
+"#;
+    let expected = r#"func process(n: int) {
+    # only positive numbers
+    if NOT (n > 0) {
+        send;
+    }
+    let doubled: int = n * 2;
+    if NOT (doubled < 100) {
+        send;
+    }
+    displayln "small ", doubled;
+    # done
+}
+"#;
+    
+    let refactoring = early_return(source);
+    assert_eq!(refactoring.source, expected);
+    assert_eq!(refactoring.functions, vec!["process"]);
+    
+    // Already flat: nothing left to do
+    assert_eq!(early_return(expected).functions, Vec::<String>::new());
+    println!("✓ Nested guards are flattened into early returns");
+}
+
+#[test]
+fn test_returning_branch_becomes_the_guard() {
+    let source = r#"func describe(n: int) -> string {
+    if NOT ready(n) {
+        let label: string = "value {n}";
+        send label;
+    } else {
+        send "not ready";
+    }
+}
+
+func sign(n: int) -> int {
+    if n < 0 {
+        send -1;
+    } else {
+        display "checking";
+        send 1;
+    }
+}
+
+func ready(n: int) -> bool {
+    send n > 0;
+}
+"#;
+    let expected = r#"func describe(n: int) -> string {
+    if ready(n) {
+        send "not ready";
+    }
+    let label: string = "value {n}";
+    send label;
+}
+
+func sign(n: int) -> int {
+    if n < 0 {
+        send -1;
+    }
+    display "checking";
+    send 1;
+}
+
+func ready(n: int) -> bool {
+    send n > 0;
+}
+"#;
+    
+    let refactoring = early_return(source);
+    assert_eq!(refactoring.source, expected);
+    assert_eq!(refactoring.functions, vec!["describe", "sign"]);
+    println!("✓ The branch that returns is kept as the guard");
+}
+
+#[test]
+fn test_one_line_blocks() {
+    let source = "func main() {\n    let on: bool = true;\n    if on { display \"on\"; }\n}\n";
+    let expected = "func main() {\n    let on: bool = true;\n    if NOT on {\n        send;\n    }\n    display \"on\";\n}\n";
+    
+    assert_eq!(early_return(source).source, expected);
+    println!("✓ Blocks written on one line are unwrapped too");
+}
+
+#[test]
+fn test_non_guards_left_alone() {
+    let sources = [
+        // Code after the if still runs when the condition is false
+        "func main() {\n    let n: int = 1;\n    if n > 0 {\n        display n;\n    }\n    display \"end\";\n}\n",
+        // Neither branch returns
+        "func main() {\n    let n: int = 1;\n    if n > 0 {\n        display n;\n    } else {\n        display 0;\n    }\n}\n",
+        // else if chains
+        "func f(n: int) -> int {\n    if n > 0 {\n        send 1;\n    } else if n < 0 {\n        send -1;\n    } else {\n        send 0;\n    }\n}\n",
+        // A function with a result has no bare `send`
+        "func f(n: int) -> int {\n    let r: int = 0;\n    if n > 0 {\n        r = n;\n    }\n    send r;\n}\n",
+    ];
+    
+    for source in sources {
+        let refactoring = early_return(source);
+        assert_eq!(refactoring.source, source);
+        assert!(refactoring.functions.is_empty());
+    }
+    println!("✓ Ifs that do not guard the rest of the function are not touched");
+}
+
+#[test]
+fn test_rewrites_that_would_not_compile_are_skipped() {
+    // Moving `x` out of the block would clash with the outer `x`
+    let source = r#"func main() {
+    let x: int = 1;
+    if x > 0 {
+        let x: int = 2;
+        display x;
+    }
+}
+"#;
+    
+    let refactoring = early_return(source);
+    assert_eq!(refactoring.source, source);
+    assert!(refactoring.functions.is_empty());
+    println!("✓ Rewrites are kept only when the result still compiles");
+}
+
+// ==================== DIFF TESTS ====================
+
+#[test]
+fn test_unified_diff() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+    let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nl\nm\n";
+    let expected = "\
+--- a/x.mini
++++ b/x.mini
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -8,5 +8,5 @@
+ h
+ i
+ j
+-k
+ l
++m
+";
+    
+    assert_eq!(refactor::unified_diff(old, new, "x.mini"), expected);
+    assert_eq!(refactor::unified_diff(old, old, "x.mini"), "");
+    println!("✓ Changes are shown as a unified diff with three lines of context");
+}
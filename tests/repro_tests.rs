@@ -0,0 +1,125 @@
+// tests/repro_tests.rs - The bug corpus and the tools that build it
+//
+// Every program in tests/repro was saved by `minilang repro save` while it
+// showed a compiler bug. Once the bug is fixed it must stay fixed, so the
+// corpus test fails for any case that still shows its failure.
+
+use minilang_compiler::repro::{self, Failure};
+use minilang_compiler::test_utils::c_compiler;
+use std::path::Path;
+use tempfile::TempDir;
+
+const WORKING: &str = r#"func square(x: int) -> int {
+    send x * x;
+}
+
+func main() {
+    let total: int = 0;
+    for let i: int = 0; i < 4; i = i + 1 {
+        total = total + square(i);
+    }
+    display total;
+}
+"#;
+
+// ==================== CORPUS TESTS ====================
+
+#[test]
+fn test_corpus_stays_fixed() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join(repro::DEFAULT_CORPUS);
+    let cases = repro::read_corpus(&corpus).unwrap();
+
+    for case in &cases {
+        let cc = match (case.failure, c_compiler()) {
+            (Failure::Ice, _) => "",
+            (_, Some(cc)) => cc,
+            (_, None) => {
+                eprintln!("skipped {}: no working C compiler found (set CC to choose one)", case.path.display());
+                continue;
+            }
+        };
+        assert!(
+            !repro::reproduces(case.failure, &case.source, cc),
+            "{} still shows the '{}' failure",
+            case.path.display(),
+            case.failure.name(),
+        );
+    }
+    println!("✓ None of the {} corpus cases shows its bug", cases.len());
+}
+
+#[test]
+fn test_corpus_files_round_trip() {
+    let dir = TempDir::new().unwrap();
+    let corpus = dir.path().join("repro");
+    assert!(repro::read_corpus(&corpus).unwrap().is_empty());
+
+    let path = repro::save(&corpus, "square", Failure::WrongOutput, WORKING).unwrap();
+    assert_eq!(path, corpus.join("square.mini"));
+    assert!(std::fs::read_to_string(&path).unwrap().starts_with("# repro: wrong-output\nfunc square"));
+    // A case is never replaced
+    assert!(repro::save(&corpus, "square", Failure::Ice, WORKING).is_err());
+
+    let cases = repro::read_corpus(&corpus).unwrap();
+    assert_eq!(cases.len(), 1);
+    assert_eq!(cases[0].failure, Failure::WrongOutput);
+    assert_eq!(cases[0].source, repro::case_file(Failure::WrongOutput, WORKING));
+
+    std::fs::write(corpus.join("stray.mini"), WORKING).unwrap();
+    let error = repro::read_corpus(&corpus).unwrap_err();
+    assert!(error.to_string().contains("stray.mini"), "{}", error);
+    println!("✓ Corpus cases keep their failure in a header comment");
+}
+
+// ==================== FAILURE TESTS ====================
+
+#[test]
+fn test_working_programs_show_no_failure() {
+    assert!(!repro::reproduces(Failure::Ice, WORKING, "cc"));
+    // A program the front end rejects is not a compiler bug
+    assert!(!repro::reproduces(Failure::Ice, "func main() { let x: int = \"no\"; }", "cc"));
+    assert!(!repro::reproduces(Failure::Ice, "func main( {", "cc"));
+
+    let Some(cc) = c_compiler() else {
+        eprintln!("skipped: no working C compiler found (set CC to choose one)");
+        return;
+    };
+    assert!(!repro::reproduces(Failure::Divergence, WORKING, cc));
+    assert!(!repro::reproduces(Failure::WrongOutput, WORKING, cc));
+    assert!(!repro::reproduces(Failure::WrongOutput, "func main( {", cc));
+    println!("✓ Working programs show none of the failures");
+}
+
+#[test]
+fn test_failure_names() {
+    for failure in [Failure::Ice, Failure::WrongOutput, Failure::Divergence] {
+        assert_eq!(Failure::from_name(failure.name()), Some(failure));
+    }
+    assert_eq!(Failure::from_name("crash"), None);
+    println!("✓ Failures are named the same on the command line and in the corpus");
+}
+
+// ==================== SHRINKING TESTS ====================
+
+#[test]
+fn test_minimize_keeps_only_needed_lines() {
+    let needed = |source: &str| source.contains("total = total + square(i);") && source.contains("display total;");
+    let reduction = repro::minimize(WORKING, needed);
+    assert_eq!(reduction.source, "        total = total + square(i);\n    display total;\n");
+    assert!(reduction.tests > 0);
+    println!("✓ Shrinking drops every line the failure does not need");
+}
+
+#[test]
+fn test_minimize_unwraps_blocks() {
+    // Stands in for a failure that needs a program that still parses
+    let parses_with_call = |source: &str| {
+        let parses = minilang_compiler::Lexer::new(source).tokenize().ok()
+            .and_then(|tokens| minilang_compiler::Parser::new(tokens, source.to_string()).parse_program().ok())
+            .is_some();
+        parses && source.contains("square(i)")
+    };
+    let reduction = repro::minimize(WORKING, parses_with_call);
+    assert_eq!(reduction.source, "func main() {\n        total = total + square(i);\n}\n");
+    println!("✓ Shrinking removes a block's braces together");
+}
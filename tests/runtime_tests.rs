@@ -0,0 +1,210 @@
+// tests/runtime_tests.rs - `--runtime=rc|arena|none` and `--profile`
+//
+// Every mode has to print the same thing; they differ only in when strings
+// are freed, which `--profile` makes visible.
+
+use minilang_compiler::runtime::RuntimeMode;
+use minilang_compiler::test_utils::compile_and_run_with_runtime;
+use minilang_compiler::{CodeGenerator, Lexer, Parser, Program, TypeChecker};
+
+const PROGRAM: &str = r#"func label(n: int) -> string {
+    let s: string = "item {n}";
+    defer { display "done {n}"; }
+    if n > 1000 {
+        send "big";
+    }
+    send s;
+}
+
+func shout(s: string) -> string {
+    s = "{s}!";
+    send s;
+}
+
+func pair(n: int) -> (int, string) {
+    send (n, to_string(n));
+}
+
+func main() {
+    let total: string = "";
+    let names: string[2];
+    for let i: int = 0; i < 1200; i = i + 1 {
+        let l: string = label(i);
+        if i % 400 == 0 {
+            display l;
+            total = "{total}{i},";
+        }
+    }
+    names[0] = label(2000);
+    names[1] = format("n={}", 5);
+    display names;
+    let k: int = 0;
+    for let s: string = "a"; k < 3; s = "{s}a" {
+        display s;
+        k = k + 1;
+    }
+    let (n, t) = pair(42);
+    display n, " ", t;
+    display total, " ", shout(shout(format("x={}", 3)));
+    while parse_int(to_string(k)) != 6 {
+        k = k + 1;
+    }
+    display k;
+}
+"#;
+
+const MODES: [RuntimeMode; 3] = [RuntimeMode::Arena, RuntimeMode::Rc, RuntimeMode::None];
+
+fn generate(source: &str, mut codegen: CodeGenerator) -> String {
+    let tokens = Lexer::new(source).tokenize().expect("lexing failed");
+    let program: Program = Parser::new(tokens, source.to_string()).parse_program().expect("parsing failed");
+    if let Err(errors) = TypeChecker::new().check_program(&program) {
+        panic!("type checking failed: {:?}", errors);
+    }
+    codegen.generate(&program).expect("code generation failed")
+}
+
+/// The count on a line of the profile report, like `freed:  12 (96 bytes)`
+fn profile_count(stderr: &str, label: &str) -> usize {
+    let line = stderr.lines()
+        .find(|line| line.starts_with(label))
+        .unwrap_or_else(|| panic!("no '{}' line in:\n{}", label, stderr));
+    line[label.len()..].split_whitespace().next().unwrap().parse().unwrap()
+}
+
+// ==================== OUTPUT TESTS ====================
+
+#[test]
+fn test_every_runtime_prints_the_same() {
+    for level in [0, 2] {
+        let Some(arena) = compile_and_run_with_runtime(PROGRAM, level, RuntimeMode::Arena, false) else { return };
+        assert_eq!(arena.exit_code, Some(0), "{}", arena.stderr);
+        assert!(arena.stdout.contains("item 0\n"), "{}", arena.stdout);
+        assert!(arena.stdout.contains("[big, n=5]\n"), "{}", arena.stdout);
+        assert!(arena.stdout.contains("a\naa\naaa\n42 42\n0,400,800, x=3!!\n6\n"), "{}", arena.stdout);
+        for runtime in [RuntimeMode::Rc, RuntimeMode::None] {
+            let run = compile_and_run_with_runtime(PROGRAM, level, runtime, false).unwrap();
+            assert_eq!(run, arena, "--runtime={} at -O{}", runtime.name(), level);
+        }
+    }
+    println!("✓ rc, arena and none print the same output");
+}
+
+#[test]
+fn test_arena_is_the_default() {
+    let c_code = generate(PROGRAM, CodeGenerator::new());
+    assert_eq!(c_code, generate(PROGRAM, CodeGenerator::new().with_runtime(RuntimeMode::Arena)));
+    assert!(c_code.contains("_minilang_arena"));
+    assert!(!c_code.contains("_minilang_retain"));
+    assert!(!c_code.contains("_minilang_stats"));
+    println!("✓ Without --runtime, strings go in the arena");
+}
+
+// ==================== PROFILE TESTS ====================
+
+#[test]
+fn test_rc_frees_strings_while_running() {
+    for level in [0, 2] {
+        let Some(rc) = compile_and_run_with_runtime(PROGRAM, level, RuntimeMode::Rc, true) else { return };
+        let arena = compile_and_run_with_runtime(PROGRAM, level, RuntimeMode::Arena, true).unwrap();
+        assert!(rc.stderr.contains("--- runtime profile (--runtime=rc) ---"), "{}", rc.stderr);
+        assert!(arena.stderr.contains("--- runtime profile (--runtime=arena) ---"), "{}", arena.stderr);
+
+        let allocated = profile_count(&rc.stderr, "allocations:");
+        assert_eq!(allocated, profile_count(&arena.stderr, "allocations:"));
+        // Each label() below 1000 returns a new string; at -O2 its `s` is on the stack
+        assert!(allocated > 1000, "{}", rc.stderr);
+
+        // The loop's strings are freed as it goes, except those in `names`
+        assert!(profile_count(&rc.stderr, "freed:") > 1000, "{}", rc.stderr);
+        assert!(profile_count(&rc.stderr, "peak live:") < 10, "{}", rc.stderr);
+        assert_eq!(profile_count(&arena.stderr, "freed:"), 0);
+        assert_eq!(profile_count(&arena.stderr, "peak live:"), allocated);
+    }
+    println!("✓ --runtime=rc frees strings no variable holds");
+}
+
+#[test]
+fn test_profile_report_follows_output() {
+    let source = "func main() {\n    let v: int = 1;\n    let s: string = \"x={v}\";\n    display s;\n    display to_string(2);\n}\n";
+    for runtime in MODES {
+        let Some(run) = compile_and_run_with_runtime(source, 0, runtime, true) else { return };
+        assert_eq!(run.stdout, "x=1\n2\n");
+        assert_eq!(profile_count(&run.stderr, "allocations:"), 2, "{}", run.stderr);
+        let run = compile_and_run_with_runtime(source, 0, runtime, false).unwrap();
+        assert_eq!(run.stderr, "");
+    }
+    println!("✓ --profile reports allocations on stderr at exit");
+}
+
+// ==================== CODEGEN TESTS ====================
+
+#[test]
+fn test_rc_counts_string_variables() {
+    let c_code = generate(PROGRAM, CodeGenerator::new().with_runtime(RuntimeMode::Rc));
+    // Literals are static objects that reference counting leaves alone
+    assert!(c_code.contains("_minilang_str_0 = {{_MINILANG_STATIC_REFS, 0, 0, 0}, \"big\"};"), "{}", c_code);
+    assert!(c_code.contains("const char* ml_s = _minilang_retain(_minilang_format(\"item %d\", ml_n));"), "{}", c_code);
+    assert!(c_code.contains("_minilang_assign(&ml_s, _minilang_format(\"%s!\", ml_s))"), "{}", c_code);
+    // Aggregates keep their strings until exit
+    assert!(c_code.contains("ml_names[0] = _minilang_pin(ml_label(2000))"), "{}", c_code);
+    assert!(c_code.contains("_minilang_pin(_minilang_int_to_string(ml_n))"), "{}", c_code);
+    assert!(c_code.contains("_minilang_drain(_minilang_mark);"));
+    assert!(!c_code.contains("_minilang_arena"));
+    println!("✓ --runtime=rc retains, releases and pins strings");
+}
+
+#[test]
+fn test_none_never_frees() {
+    let c_code = generate(PROGRAM, CodeGenerator::new().with_runtime(RuntimeMode::None));
+    assert!(c_code.contains("static char* _minilang_string_alloc(size_t size)"));
+    assert!(!c_code.contains("free("));
+    println!("✓ --runtime=none leaves strings allocated");
+}
+
+// ==================== THREAD TESTS ====================
+
+const THREADED: &str = r#"func main() {
+    let done: chan = channel(4);
+    let n: int = 0;
+    while n < 4 {
+        spawn {
+            let sum: int = 0;
+            for let i: int = 0; i < 200; i = i + 1 {
+                let s: string = "{i}";
+                sum = sum + parse_int(s);
+            }
+            chan_send(done, sum);
+        }
+        n = n + 1;
+    }
+    let total: int = 0;
+    for let i: int = 0; i < 4; i = i + 1 {
+        total = total + chan_receive(done);
+    }
+    display total;
+}
+"#;
+
+#[test]
+fn test_threads_allocate_under_a_lock() {
+    for runtime in [RuntimeMode::Arena, RuntimeMode::None] {
+        let Some(run) = compile_and_run_with_runtime(THREADED, 0, runtime, true) else { return };
+        assert_eq!(run.stdout, "79600\n", "{}", run.stderr);
+        // Every thread's strings are counted
+        assert_eq!(profile_count(&run.stderr, "allocations:"), 800, "{}", run.stderr);
+    }
+    let c_code = generate(THREADED, CodeGenerator::new());
+    assert!(c_code.contains("pthread_mutex_lock(&_minilang_heap_lock);"));
+    assert!(!generate(PROGRAM, CodeGenerator::new()).contains("pthread"));
+    println!("✓ Spawned threads share the arena and the profile counters safely");
+}
+
+#[test]
+fn test_rc_rejects_threads() {
+    let tokens = Lexer::new(THREADED).tokenize().unwrap();
+    let program = Parser::new(tokens, THREADED.to_string()).parse_program().unwrap();
+    let error = CodeGenerator::new().with_runtime(RuntimeMode::Rc).generate(&program).unwrap_err();
+    assert!(error.contains("--runtime=arena or --runtime=none"), "{}", error);
+    println!("✓ --runtime=rc refuses programs with spawn blocks");
+}
@@ -324,4 +324,287 @@ func main() {
     let errors = expect_semantic_error(source);
     assert!(errors.iter().any(|e| matches!(e, SemanticError::UndefinedVariable { .. })));
     println!("✓ Const use before declaration detected");
-}
\ No newline at end of file
+}
+// ==================== DEFER TESTS ====================
+
+#[test]
+fn test_defer_block_valid() {
+    let source = r#"
+func main() {
+    let count: int = 0;
+    defer {
+        count = count + 1;
+        display count;
+    }
+    for let i: int = 0; i < 3; i = i + 1 {
+        defer { display i; }
+    }
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Defer blocks pass");
+}
+
+#[test]
+fn test_send_inside_defer_error() {
+    let source = r#"
+func get() -> int {
+    defer { send 1; }
+    send 0;
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::JumpOutOfDefer { .. })));
+    println!("✓ Send inside defer detected");
+}
+
+#[test]
+fn test_break_out_of_defer_error() {
+    let source = r#"
+func main() {
+    while true {
+        defer { break; }
+    }
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::JumpOutOfDefer { .. })));
+    println!("✓ Break out of defer detected");
+}
+
+#[test]
+fn test_loop_inside_defer_allows_break() {
+    let source = r#"
+func main() {
+    defer {
+        while true {
+            break;
+        }
+    }
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Break within a loop inside defer passes");
+}
+
+// ==================== SPAWN TESTS ====================
+
+#[test]
+fn test_spawn_block_valid() {
+    let source = r#"
+func main() {
+    let results: chan = channel(2);
+    let base: int = 10;
+    let name: string = "worker";
+    spawn {
+        let total: int = base;
+        for let i: int = 0; i < 3; i = i + 1 {
+            total = total + i;
+        }
+        display name;
+        chan_send(results, total);
+    }
+    display chan_receive(results);
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Spawn blocks reading copies of outer values pass");
+}
+
+#[test]
+fn test_spawn_block_violations() {
+    for (source, what) in [
+        ("func main() {\n    let n: int = 0;\n    spawn { n = 1; }\n}", "assignment to 'n'"),
+        ("func main() {\n    let xs: int[2] = [1, 2];\n    spawn { display xs[0]; }\n}", "array 'xs' shared"),
+        ("func get() -> int {\n    spawn { send 1; }\n    send 0;\n}", "'send'"),
+        ("func main() {\n    while true {\n        spawn { break; }\n    }\n}", "'break'"),
+    ] {
+        let errors = expect_semantic_error(source);
+        assert!(
+            errors.iter().any(|e| matches!(e, SemanticError::SpawnViolation { what: found, .. } if found == what)),
+            "expected {} for {}: {:?}", what, source, errors
+        );
+    }
+    println!("✓ Spawn blocks cannot change or share the spawning code's state");
+}
+
+#[test]
+fn test_channel_misuse() {
+    for source in [
+        "func main() {\n    let c: chan;\n}",
+        "func main() {\n    let c: chan = channel(1);\n    display c;\n}",
+        "func main() {\n    let c: chan = channel(1);\n    chan_send(c, 1.5);\n}",
+        "func main() {\n    let cs: chan[2];\n}",
+        "func main() {\n    let c: chan = channel(1);\n    let n: int = chan_send(c, 1);\n}",
+    ] {
+        assert!(analyze(source).is_err(), "{}", source);
+    }
+    
+    let source = "pure func f(c: chan) -> int {\n    send chan_receive(c);\n}\npure func g() {\n    spawn { }\n}\nfunc main() { }";
+    let errors = expect_semantic_error(source);
+    assert_eq!(errors.iter().filter(|e| matches!(e, SemanticError::PurityViolation { .. })).count(), 2, "{:?}", errors);
+    println!("✓ Channels are checked and kept out of pure functions");
+}
+
+// ==================== PARALLEL FOR TESTS ====================
+
+#[test]
+fn test_parallel_for_valid() {
+    let source = r#"
+func square(x: int) -> int {
+    send x * x;
+}
+
+func main() {
+    let xs: int[4] = [1, 2, 3, 4];
+    let total: atomic = atomic(0);
+    parallel for i in 0..4 {
+        let value: int = square(xs[i]);
+        for let j: int = 0; j < 2; j = j + 1 {
+            value = value + 1;
+        }
+        atomic_add(total, value);
+    }
+    display atomic_get(total);
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Parallel for bodies writing their own locals and atomics pass");
+}
+
+#[test]
+fn test_parallel_for_violations() {
+    for (source, what) in [
+        ("func main() {\n    let n: int = 0;\n    parallel for i in 0..4 { n = n + i; }\n}", "assignment to 'n'"),
+        ("func main() {\n    parallel for i in 0..4 { i = 0; }\n}", "assignment to 'i'"),
+        ("func main() {\n    let xs: int[4] = [1, 2, 3, 4];\n    parallel for i in 0..4 { xs[i] = 0; }\n}", "assignment to 'xs'"),
+        ("func fill(xs: int[4]) {}\nfunc main() {\n    let xs: int[4] = [1, 2, 3, 4];\n    parallel for i in 0..4 { fill(xs); }\n}", "array 'xs' passed to 'fill'"),
+        ("func get() -> int {\n    parallel for i in 0..4 { send i; }\n    send 0;\n}", "'send'"),
+        ("func main() {\n    parallel for i in 0..4 { break; }\n}", "'break'"),
+    ] {
+        let errors = expect_semantic_error(source);
+        assert!(
+            errors.iter().any(|e| matches!(e, SemanticError::ParallelViolation { what: found, .. } if found == what)),
+            "expected {} for {}: {:?}", what, source, errors
+        );
+    }
+    // A loop of the body's own may still break
+    let source = "func main() {\n    parallel for i in 0..4 {\n        while true { break; }\n    }\n}";
+    assert!(analyze(source).is_ok());
+    println!("✓ Parallel for bodies cannot write state shared between iterations");
+}
+
+// ==================== CANVAS TESTS ====================
+
+#[test]
+fn test_canvas_valid() {
+    let source = r#"
+func border(c: canvas, width: int, height: int) {
+    for let x: int = 0; x < width; x = x + 1 {
+        canvas_set(c, x, 0, "-");
+        canvas_set(c, x, height - 1, "-");
+    }
+}
+
+func main() {
+    let board: canvas = canvas(8, 4);
+    let pair: (canvas, int) = (board, 8);
+    canvas_fill(board, ".");
+    border(board, 8, 4);
+    let (shown, width) = pair;
+    canvas_render(shown);
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Canvases are made, passed around, drawn on and rendered");
+}
+
+#[test]
+fn test_canvas_misuse() {
+    for source in [
+        "func main() {\n    let c: canvas;\n}",
+        "func main() {\n    let cs: canvas[2];\n}",
+        "func main() {\n    let c: canvas = canvas(2, 2);\n    display c;\n}",
+        "func main() {\n    let c: canvas = canvas(2, 2);\n    canvas_set(c, 0, 0, 1);\n}",
+        "func main() {\n    let c: canvas = canvas(2, 2);\n    let n: int = canvas_render(c);\n}",
+        "func main() {\n    let c: canvas = canvas(2);\n}",
+        "pure func blank() -> canvas {\n    send canvas(2, 2);\n}\nfunc main() {}",
+    ] {
+        expect_semantic_error(source);
+    }
+    
+    let errors = expect_semantic_error("func main() {\n    let c: canvas = canvas(2, 2);\n    spawn { canvas_render(c); }\n}");
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::SpawnViolation { what, .. } if what == "canvas 'c' shared")), "{:?}", errors);
+    let errors = expect_semantic_error("func main() {\n    let c: canvas = canvas(2, 2);\n    parallel for i in 0..2 { canvas_set(c, i, 0, \"x\"); }\n}");
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::ParallelViolation { what, .. } if what == "canvas 'c' shared")), "{:?}", errors);
+    println!("✓ Canvases only come from canvas(), are not displayed and are not shared between threads");
+}
+
+// ==================== JSON TESTS ====================
+
+#[test]
+fn test_json_valid() {
+    let source = r#"
+func total(scores: json) -> float {
+    let sum: float = 0.0;
+    for let i: int = 0; i < json_len(scores); i = i + 1 {
+        sum = sum + json_float(json_at(scores, i));
+    }
+    send sum;
+}
+
+func main() {
+    let data: json = json {"name": "Ann", "scores": [90, 85.5]};
+    json_push(json_get(data, "scores"), 70);
+    json_set(data, "total", total(json_get(data, "scores")));
+    json_set(data, "passed", true);
+    json_set(data, "copy", json_parse("[1, 2]"));
+    if json_has(data, "name") AND json_type(data) == "object" {
+        display json_string(json_get(data, "name"));
+    }
+    display json_stringify(data);
+    display json_int(json_at(json_get(data, "copy"), 0)) + 1;
+    display json_bool(json_get(data, "passed"));
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Json values are parsed, read, changed and written out");
+}
+
+#[test]
+fn test_json_misuse() {
+    for source in [
+        "func main() {\n    let j: json;\n}",
+        "func main() {\n    let js: json[2];\n}",
+        "func main() {\n    let j: json = json {};\n    display j;\n}",
+        "func main() {\n    let j: json = json {};\n    display \"{j}\";\n}",
+        "func main() {\n    let j: json = json_parse(1);\n}",
+        "func main() {\n    let j: json = json {};\n    let n: int = json_get(j, 0);\n}",
+        "func main() {\n    let j: json = json {};\n    json_set(j, \"xs\", [1, 2]);\n}",
+        "func main() {\n    let j: json = json {};\n    let n: int = json_push(j, 1);\n}",
+        "func main() {\n    let j: json = json {};\n    json_push(j);\n}",
+        "pure func parse(text: string) -> json {\n    send json_parse(text);\n}\nfunc main() {}",
+    ] {
+        expect_semantic_error(source);
+    }
+    
+    let errors = expect_semantic_error("func main() {\n    let j: json = json {};\n    spawn { json_set(j, \"a\", 1); }\n}");
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::SpawnViolation { what, .. } if what == "json value 'j' shared")), "{:?}", errors);
+    let errors = expect_semantic_error("func main() {\n    let j: json = json_parse(\"[]\");\n    parallel for i in 0..2 { json_push(j, i); }\n}");
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::ParallelViolation { what, .. } if what == "json value 'j' shared")), "{:?}", errors);
+    println!("✓ Json values are checked, not displayed and not shared between threads");
+}
+
+#[test]
+fn test_atomic_misuse() {
+    for source in [
+        "func main() {\n    let a: atomic = atomic(0);\n    display a;\n}",
+        "func main() {\n    let a: atomic;\n}",
+        "func main() {\n    let a: atomic = atomic(0);\n    atomic_add(a, 1.5);\n}",
+        "func main() {\n    let n: int = atomic_get(3);\n}",
+        "@pure\nfunc f() -> int {\n    parallel for i in 0..2 {}\n    send 0;\n}",
+    ] {
+        expect_semantic_error(source);
+    }
+    println!("✓ Atomics are only made, added to and read through their builtins");
+}
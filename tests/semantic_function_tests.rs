@@ -1,6 +1,7 @@
 // tests/semantic_function_tests.rs - Function-related semantic tests
 
 use minilang_compiler::{Lexer, Parser, TypeChecker, SemanticError};
+use minilang_compiler::errors::CompilerWarning;
 
 fn analyze(source: &str) -> Result<(), Vec<SemanticError>> {
     let mut lexer = Lexer::new(source);
@@ -206,6 +207,125 @@ func getValue(x: int) -> int {
     println!("✓ Valid conditional returns pass");
 }
 
+#[test]
+fn test_loop_return_paths() {
+    // A do-while body always runs, and a loop that never ends needs no send after it
+    let source = r#"
+func first(x: int) -> int {
+    do {
+        send x;
+    } while x > 0;
+}
+
+func forever(x: int) -> int {
+    while true {
+        if x > 10 {
+            send x;
+        }
+        x = x + 1;
+    }
+}
+
+func counter() -> int {
+    for let i: int = 0; ; i = i + 1 {
+        if i == 5 {
+            send i;
+        }
+    }
+}
+"#;
+    assert!(analyze(source).is_ok(), "{:?}", analyze(source));
+    
+    // A while body may not run, and a break leaves the loop
+    for body in ["while x > 0 {\n        send x;\n    }", "while true {\n        break;\n    }"] {
+        let source = format!("func value(x: int) -> int {{\n    {}\n}}\n", body);
+        let errors = expect_semantic_error(&source);
+        assert!(errors.iter().any(|e| matches!(e, SemanticError::MissingReturn { .. })), "{}", source);
+    }
+    println!("✓ Loops count as returning exactly when control can't leave them");
+}
+
+#[test]
+fn test_unreachable_after_jumps_and_endless_loops() {
+    let source = r#"
+func main() {
+    for let i: int = 0; i < 3; i = i + 1 {
+        continue;
+        display "skipped";
+    }
+    while true {
+        display "forever";
+    }
+    display "never";
+}
+"#;
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let mut type_checker = TypeChecker::new();
+    assert!(type_checker.check_program(&program).is_ok());
+    let reasons: Vec<&str> = type_checker.get_warnings().iter()
+        .filter_map(|w| match w {
+            CompilerWarning::UnreachableCode { reason, .. } => Some(reason.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(reasons.len(), 2, "{:?}", reasons);
+    assert!(reasons[0].contains("'continue'"));
+    assert!(reasons[1].contains("never ends"));
+    println!("✓ Code after continue or an endless loop is reported unreachable");
+}
+
+#[test]
+fn test_main_may_return_int() {
+    let source = r#"
+func main() -> int {
+    display "done";
+    send 3;
+}
+"#;
+    assert!(analyze(source).is_ok());
+    
+    let source = r#"
+func main() -> string {
+    send "done";
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(matches!(&errors[0], SemanticError::InvalidMainReturnType { found, .. } if found == "String"), "{:?}", errors);
+    println!("✓ main returns int or nothing");
+}
+
+#[test]
+fn test_entry_must_take_no_arguments() {
+    let source = r#"
+func grade(score: int) -> int {
+    send score;
+}
+
+func label() -> string {
+    send "done";
+}
+
+func run() {
+    display "ok";
+}
+"#;
+    let check_entry = |entry: &str| {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+        TypeChecker::new().with_entry(entry).check_program(&program)
+    };
+    
+    assert!(check_entry("run").is_ok());
+    let errors = check_entry("grade").unwrap_err();
+    assert!(matches!(&errors[0], SemanticError::InvalidEntry { name, problem, .. } if name == "grade" && problem == "takes 1 parameter(s)"), "{:?}", errors);
+    let errors = check_entry("label").unwrap_err();
+    assert!(matches!(&errors[0], SemanticError::InvalidEntry { problem, .. } if problem == "returns String"), "{:?}", errors);
+    let errors = check_entry("rn").unwrap_err();
+    assert!(matches!(&errors[0], SemanticError::UndefinedFunction { name, suggestion, .. } if name == "rn" && suggestion.contains("run")), "{:?}", errors);
+    println!("✓ The entry point takes no arguments and returns int or nothing");
+}
+
 // ==================== RECURSION TESTS ====================
 
 #[test]
@@ -307,6 +427,27 @@ func main() {
     println!("✓ Array parameter size mismatch detected");
 }
 
+#[test]
+fn test_array_parameter_too_small() {
+    let source = r#"
+func process(arr: int[5]) -> int {
+    send arr[4];
+}
+
+func main() {
+    let nums: int[3] = [1, 2, 3];
+    display process(nums);  # Callee may read past the end
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(
+        e,
+        SemanticError::ArrayTooSmall { name, expected: 5, found: 3, .. } if name == "process"
+    )));
+    assert!(!errors.iter().any(|e| matches!(e, SemanticError::TypeMismatch { .. })));
+    println!("✓ Passing a smaller array than the parameter declares is rejected");
+}
+
 
 #[test]
 fn test_const_in_function() {
@@ -341,4 +482,370 @@ func main() {
 "#;
     assert!(analyze(source).is_ok());
     println!("✓ Const as function argument works");
-}
\ No newline at end of file
+}
+// ==================== PURITY TESTS ====================
+
+#[test]
+fn test_pure_function_valid() {
+    let source = r#"
+pure func square(x: int) -> int {
+    let result: int = x * x;
+    send result;
+}
+
+pure func sum_of_squares(a: int, b: int) -> int {
+    send square(a) + square(b);
+}
+
+func main() {
+    display sum_of_squares(3, 4);
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Pure functions calling pure functions pass");
+}
+
+#[test]
+fn test_pure_function_display_rejected() {
+    let source = r#"
+pure func noisy(x: int) -> int {
+    display x;
+    send x;
+}
+
+func main() {
+    display noisy(1);
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::PurityViolation { .. })));
+    println!("✓ Display inside pure function detected");
+}
+
+#[test]
+fn test_pure_function_impure_call_rejected() {
+    let source = r#"
+func helper(x: int) -> int {
+    send x + 1;
+}
+
+pure func wrapper(x: int) -> int {
+    send helper(x);
+}
+
+func main() {
+    display wrapper(1);
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::PurityViolation { .. })));
+    println!("✓ Impure call inside pure function detected");
+}
+
+#[test]
+fn test_pure_function_parameter_array_write_rejected() {
+    let source = r#"
+pure func clear(arr: int[3]) -> int {
+    arr[0] = 0;
+    send 0;
+}
+
+func main() {
+    let data: int[3] = [1, 2, 3];
+    display clear(data);
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::PurityViolation { .. })));
+    println!("✓ Write through parameter array inside pure function detected");
+}
+
+#[test]
+fn test_pure_function_local_mutation_allowed() {
+    let source = r#"
+pure func sum_to(n: int) -> int {
+    let total: int = 0;
+    let buffer: int[2] = [0, 0];
+    for let i: int = 0; i < n; i = i + 1 {
+        total = total + i;
+    }
+    buffer[0] = total;
+    send buffer[0];
+}
+
+func main() {
+    display sum_to(5);
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Local mutation inside pure function allowed");
+}
+
+// ==================== ATTRIBUTE TESTS ====================
+
+#[test]
+fn test_known_attributes_valid() {
+    let source = r#"
+@inline
+func twice(x: int) -> int {
+    send x * 2;
+}
+
+@noopt
+@deprecated("use twice")
+func double(x: int) -> int {
+    send x + x;
+}
+
+func main() {
+    display twice(1), double(2);
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Known attributes accepted");
+}
+
+#[test]
+fn test_unknown_attribute_rejected() {
+    let source = r#"
+@fast
+func f() { }
+
+func main() { f(); }
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::InvalidAttribute { name, .. } if name == "fast")));
+    println!("✓ Unknown attribute detected");
+}
+
+#[test]
+fn test_invalid_attribute_uses_rejected() {
+    for source in [
+        "@inline(\"always\") func f() { } func main() { f(); }",
+        "@noopt @noopt func f() { } func main() { f(); }",
+        "@inline func main() { }",
+        "@inline func f(n: int) -> int { send f(n - 1); } func main() { display f(1); }",
+    ] {
+        let errors = expect_semantic_error(source);
+        assert!(
+            errors.iter().any(|e| matches!(e, SemanticError::InvalidAttribute { .. })),
+            "expected an attribute error for: {}", source
+        );
+    }
+    println!("✓ Misused attributes detected");
+}
+
+#[test]
+fn test_memo_attribute_checked() {
+    let source = r#"
+@memo
+pure func paths(rows: int, cols: int, wrap: bool) -> int {
+    if rows == 0 OR cols == 0 { send 1; }
+    send paths(rows - 1, cols, wrap) + paths(rows, cols - 1, wrap);
+}
+
+func main() {
+    display paths(3, 3, false);
+}
+"#;
+    assert!(analyze(source).is_ok());
+    
+    for (source, reason) in [
+        ("@memo func f(n: int) -> int { send n; } func main() { display f(1); }", "is not pure"),
+        ("@memo pure func f(n: int) { } func main() { f(1); }", "must return an int, float or bool"),
+        ("@memo pure func f(s: string) -> int { send 1; } func main() { display f(\"a\"); }", "parameter 's' is a string"),
+        ("@memo @inline pure func f(n: int) -> int { send n; } func main() { display f(1); }", "'@inline' copies the body"),
+        ("@memo(\"big\") pure func f(n: int) -> int { send n; } func main() { display f(1); }", "takes no argument"),
+    ] {
+        let errors = expect_semantic_error(source);
+        assert!(
+            errors.iter().any(|e| matches!(e, SemanticError::InvalidAttribute { reason: found, .. } if found.contains(reason))),
+            "expected '{}' for {}: {:?}", reason, source, errors
+        );
+    }
+    println!("✓ @memo needs a pure function of ints, floats and bools with such a result");
+}
+
+#[test]
+fn test_deprecated_call_warns() {
+    let source = r#"
+@deprecated("use new_area")
+func old_area(w: int, h: int) -> int {
+    send w * h;
+}
+
+func main() {
+    let a: int = old_area(2, 3);
+    old_area(1, 1);
+    display a;
+}
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexer should succeed");
+    let mut parser = Parser::new(tokens, source.to_string());
+    let program = parser.parse_program().expect("Parser should succeed");
+
+    let mut type_checker = TypeChecker::new();
+    assert!(type_checker.check_program(&program).is_ok());
+    let notes: Vec<_> = type_checker.get_warnings().iter()
+        .filter_map(|w| match w {
+            CompilerWarning::DeprecatedCall { name, note, .. } if name == "old_area" => Some(note.clone()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(notes, vec![Some("use new_area".to_string()); 2]);
+    println!("✓ Calls to deprecated function warned about");
+}
+
+// ==================== TUPLE TESTS ====================
+
+#[test]
+fn test_tuple_return_and_destructuring_valid() {
+    let source = r#"
+func min_max(a: int, b: int) -> (int, int) {
+    if a < b {
+        send (a, b);
+    }
+    send (b, a);
+}
+
+func main() {
+    let (lo, hi) = min_max(9, 4);
+    let pair: (int, int) = min_max(lo, hi);
+    let (_, top) = pair;
+    display top;
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ Tuple return and destructuring pass");
+}
+
+#[test]
+fn test_tuple_return_type_mismatch() {
+    let source = r#"
+func pair() -> (int, bool) {
+    send (1, 2);
+}
+
+func main() {
+    let (a, b) = pair();
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::TypeMismatch { .. })));
+    println!("✓ Tuple return type mismatch detected");
+}
+
+#[test]
+fn test_destructuring_arity_mismatch() {
+    let source = r#"
+func pair() -> (int, int) {
+    send (1, 2);
+}
+
+func main() {
+    let (a, b, c) = pair();
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::TypeMismatch { .. })));
+    println!("✓ Destructuring arity mismatch detected");
+}
+
+#[test]
+fn test_destructured_names_are_typed() {
+    let source = r#"
+func pair() -> (int, string) {
+    send (1, "one");
+}
+
+func main() {
+    let (n, s) = pair();
+    let bad: int = s;
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::TypeMismatch { .. })));
+    println!("✓ Destructured names carry element types");
+}
+
+#[test]
+fn test_tuple_equality_rejected() {
+    let source = r#"
+func main() {
+    let a: (int, int) = (1, 2);
+    let b: (int, int) = (1, 2);
+    let same: bool = a == b;
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::TypeMismatch { .. })));
+    println!("✓ Tuple equality rejected");
+}
+
+#[test]
+fn test_tuple_array_elements_rejected() {
+    let source = r#"
+func pack(a: int[3]) -> (int[3], int) {
+    send (a, 4);
+}
+
+func main() {
+    let a: int[3] = [1, 2, 3];
+    let t = (a, 4);
+    let u: (int, (bool, int[2]));
+    let (p, q) = pack(a);
+}
+"#;
+    let errors = expect_semantic_error(source);
+    let found: Vec<String> = errors.iter()
+        .filter_map(|e| match e {
+            SemanticError::ArrayInTuple { found, .. } => Some(found.clone()),
+            _ => None,
+        })
+        .collect();
+    // The return type, both literals and the annotation
+    assert_eq!(found.len(), 4, "{:?}", errors);
+    assert!(found.contains(&"(int, (bool, int[2]))".to_string()), "{:?}", found);
+    println!("✓ Tuples with array elements rejected");
+}
+
+// ==================== FORMAT TESTS ====================
+
+#[test]
+fn test_format_returns_string() {
+    let source = r#"
+func main() {
+    let n: int = 7;
+    let s: string = format("n = {}, half = {}", n, 3.5);
+    display s;
+}
+"#;
+    assert!(analyze(source).is_ok());
+    println!("✓ format returns a string");
+}
+
+#[test]
+fn test_format_argument_count_mismatch() {
+    let source = r#"
+func main() {
+    let s: string = format("{} and {}", 1);
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::ArgumentCountMismatch { .. })));
+    println!("✓ format placeholder count checked");
+}
+
+#[test]
+fn test_format_requires_literal_template() {
+    let source = r#"
+func main() {
+    let template: string = "value";
+    let s: string = format(template, 1);
+}
+"#;
+    let errors = expect_semantic_error(source);
+    assert!(errors.iter().any(|e| matches!(e, SemanticError::TypeMismatch { .. })));
+    println!("✓ format template must be a literal");
+}
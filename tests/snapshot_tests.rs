@@ -0,0 +1,142 @@
+// tests/snapshot_tests.rs - Generated C snapshots at each optimization level
+//
+// Each program below is compiled the way `minilang compile` does it and the
+// full C is compared with tests/snapshots/. After an intended codegen change,
+// review and accept the new output with `cargo insta review`, or rerun with
+// INSTA_UPDATE=always.
+
+use minilang_compiler::{Lexer, Parser, TypeChecker, CodeGenerator, Optimizer};
+
+/// The levels `-O` accepts
+const LEVELS: [u8; 3] = [0, 1, 2];
+
+fn generate_c(name: &str, source: &str, level: u8) -> String {
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let mut program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+
+    if let Err(errors) = TypeChecker::new().check_program(&program) {
+        panic!("{} does not type check: {:?}", name, errors);
+    }
+
+    if level > 0 {
+        let mut optimizer = Optimizer::new(level);
+        optimizer.optimize(&mut program);
+        assert!(optimizer.errors().is_empty(), "{}: {:?}", name, optimizer.errors());
+    }
+
+    // No stamp: it holds the compiler version, which would churn every snapshot
+    CodeGenerator::new()
+        .with_source(&format!("{}.mini", name), source)
+        .with_peephole(level >= 1)
+        .with_line_tracking(level == 0)
+        .generate(&program)
+        .unwrap()
+}
+
+fn assert_snapshots(name: &str, source: &str) {
+    for level in LEVELS {
+        insta::assert_snapshot!(format!("{}_O{}", name, level), generate_c(name, source, level));
+    }
+}
+
+// ==================== DISPLAY FORMAT TESTS ====================
+
+#[test]
+fn test_display_formats() {
+    assert_snapshots("display_formats", r#"
+func half(x: float) -> float {
+    send x / 2.0;
+}
+
+func main() {
+    let count: int = 3;
+    let ratio: float = half(3.0);
+    let name: string = "n{count}";
+    let ready: bool = ratio > 1.0;
+    let flags = [true, false];
+    let pair = (count, ready);
+    display count, ratio, name, ready;
+    displayln count, " ", ratio, " ", flags, " ", pair;
+    display_raw "no newline";
+    display "";
+}
+"#);
+    println!("✓ Display format strings are stable");
+}
+
+// ==================== BOUNDS CHECK TESTS ====================
+
+#[test]
+fn test_bounds_checks() {
+    assert_snapshots("bounds_checks", r#"
+func pick(values: int[4], i: int) -> int {
+    send values[i];
+}
+
+func main() {
+    let values: int[4] = [1, 2, 3, 4];
+    let total: int = 0;
+    for let i: int = 0; i < 4; i = i + 1 {
+        total = total + values[i];
+    }
+    values[2] = total;
+    display total, values[3], pick(values, 1);
+}
+"#);
+    println!("✓ Bounds checks are kept or dropped consistently");
+}
+
+// ==================== OPTIMIZATION HINT TESTS ====================
+
+#[test]
+fn test_optimization_hints() {
+    assert_snapshots("optimization_hints", r#"
+func scale(x: int) -> int {
+    send x * 8 + x / 4 + x % 16;
+}
+
+func main() {
+    let width: int = 2 * 3;
+    let area: int = width * width;
+    if area > 100 {
+        display "big";
+    } else {
+        display scale(area);
+    }
+}
+"#);
+    println!("✓ Strength reduction hints and folding are stable");
+}
+
+// ==================== CONTROL FLOW TESTS ====================
+
+#[test]
+fn test_control_flow() {
+    assert_snapshots("control_flow", r#"
+func fib(n: int) -> int {
+    if n < 2 {
+        send n;
+    }
+    send fib(n - 1) + fib(n - 2);
+}
+
+func main() -> int {
+    defer {
+        display "done";
+    }
+    let i: int = 0;
+    do {
+        i = i + 1;
+        if i == 2 {
+            continue;
+        }
+        if i > 4 {
+            break;
+        }
+        display fib(i);
+    } while i < 10;
+    send 0;
+}
+"#);
+    println!("✓ Loops, defer and recursion are stable");
+}
@@ -0,0 +1,79 @@
+// tests/stamp_tests.rs - Build stamps in generated C and executables
+
+use minilang_compiler::backend::{self, GCC_FLAGS};
+use minilang_compiler::build_log::sha256_hex;
+use minilang_compiler::stamp::{Stamp, MARKER};
+use minilang_compiler::{CodeGenerator, Lexer, Parser};
+use std::fs;
+use tempfile::TempDir;
+
+const SOURCE: &str = "func main() {\n    displayln \"stamped\";\n}\n";
+
+fn stamp() -> Stamp {
+    Stamp::new(SOURCE, 2, vec!["--overflow=trap".to_string(), "-DNAME=a \"quoted\" (value)".to_string()])
+}
+
+fn generate(peephole: bool) -> String {
+    let tokens = Lexer::new(SOURCE).tokenize().unwrap();
+    let program = Parser::new(tokens, SOURCE.to_string()).parse_program().unwrap();
+    CodeGenerator::new()
+        .with_source("main.mini", SOURCE)
+        .with_peephole(peephole)
+        .with_stamp(stamp())
+        .generate(&program)
+        .unwrap()
+}
+
+// ==================== STAMPS ====================
+
+#[test]
+fn test_stamp_records_version_options_and_hash() {
+    let stamp = stamp();
+    assert_eq!(stamp.compiler_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(stamp.optimization, 2);
+    assert_eq!(stamp.source_sha256, sha256_hex(SOURCE.as_bytes()));
+    assert!(stamp.matches_source(SOURCE));
+    assert!(!stamp.matches_source("func main() { }"));
+
+    let line = stamp.to_line();
+    assert!(line.starts_with(MARKER));
+    assert!(!line.contains('\n'));
+    println!("✓ Stamps record the compiler version, options and source hash");
+}
+
+#[test]
+fn test_find_all_stamps() {
+    let first = stamp();
+    let second = Stamp::new("func helper() { }", 0, vec![]);
+    let bytes = format!("\0junk{}\0more{}\nminilang-stamp not json\0", first.to_line(), second.to_line());
+
+    assert_eq!(Stamp::find_all(bytes.as_bytes()), vec![first, second]);
+    assert!(Stamp::find_all(b"no stamp here").is_empty());
+    println!("✓ Every stamp is found, and text that only looks like one is skipped");
+}
+
+// ==================== GENERATED CODE ====================
+
+#[test]
+fn test_stamp_heads_generated_c() {
+    for peephole in [false, true] {
+        let c_code = generate(peephole);
+        assert_eq!(c_code.lines().next(), Some(format!("// {}", stamp().to_line()).as_str()));
+        assert_eq!(Stamp::find_all(c_code.as_bytes()), vec![stamp()], "the escaped copy is skipped");
+    }
+    println!("✓ Generated C starts with its stamp");
+}
+
+#[test]
+fn test_stamp_survives_into_executable() {
+    let dir = TempDir::new().unwrap();
+    let c_file = dir.path().join("main.c");
+    let exe = dir.path().join("main");
+    fs::write(&c_file, generate(true)).unwrap();
+    backend::compile_c("gcc", &c_file, &exe, &GCC_FLAGS).unwrap();
+
+    assert_eq!(Stamp::find_all(&fs::read(&exe).unwrap()), vec![stamp()]);
+    let output = std::process::Command::new(&exe).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "stamped\n");
+    println!("✓ The stamp is kept in the optimized executable");
+}
@@ -0,0 +1,53 @@
+// tests/style_tests.rs - Color and glyph policy
+
+use minilang_compiler::style::{ascii_fallback, color_enabled, locale_supports_unicode, ColorChoice};
+
+#[test]
+fn test_color_choice_resolution() {
+    // Explicit choices ignore the environment
+    assert!(color_enabled(ColorChoice::Always, true, false));
+    assert!(!color_enabled(ColorChoice::Never, false, true));
+    
+    // Auto needs a terminal and no NO_COLOR
+    assert!(color_enabled(ColorChoice::Auto, false, true));
+    assert!(!color_enabled(ColorChoice::Auto, true, true));
+    assert!(!color_enabled(ColorChoice::Auto, false, false));
+    
+    println!("✓ --color and NO_COLOR resolve as documented");
+}
+
+#[test]
+fn test_unicode_locale_detection() {
+    assert!(locale_supports_unicode(None));
+    assert!(locale_supports_unicode(Some("en_US.UTF-8")));
+    assert!(locale_supports_unicode(Some("C.utf8")));
+    assert!(!locale_supports_unicode(Some("C")));
+    assert!(!locale_supports_unicode(Some("POSIX")));
+    
+    println!("✓ Unicode support follows the locale");
+}
+
+#[test]
+fn test_ascii_fallbacks() {
+    for glyph in ["✅", "❌", "⚠️", "•", "═", "─", "▪", "📊", "📋", "🔥", "🌳", "🟢", "🟡", "🟠", "🔴", "💀"] {
+        assert!(ascii_fallback(glyph).is_ascii(), "{} needs an ASCII fallback", glyph);
+    }
+    assert_eq!(ascii_fallback("✅"), "[ok]");
+    assert_eq!(ascii_fallback("❌"), "[x]");
+    assert_eq!(ascii_fallback("->"), "->");
+    
+    println!("✓ Every glyph has an ASCII fallback");
+}
+
+#[test]
+fn test_color_flag_parsing() {
+    use clap::Parser as _;
+    use minilang_compiler::cli::Cli;
+    
+    assert_eq!(Cli::try_parse_from(["minilang", "check", "a.mini"]).unwrap().color, ColorChoice::Auto);
+    assert_eq!(Cli::try_parse_from(["minilang", "check", "a.mini", "--color=never"]).unwrap().color, ColorChoice::Never);
+    assert_eq!(Cli::try_parse_from(["minilang", "--color", "always", "check", "a.mini"]).unwrap().color, ColorChoice::Always);
+    assert!(Cli::try_parse_from(["minilang", "check", "a.mini", "--color=rainbow"]).is_err());
+    
+    println!("✓ --color accepts auto, always and never");
+}
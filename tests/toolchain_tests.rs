@@ -0,0 +1,106 @@
+// tests/toolchain_tests.rs - Version reporting and self-update, without the network
+
+use minilang_compiler::toolchain::{self, download_binary, is_newer, parse_release, replace_executable};
+use std::fs;
+use tempfile::TempDir;
+
+/// A trimmed reply from GitHub's releases API
+const RELEASE_JSON: &str = r#"{
+    "tag_name": "v0.2.0",
+    "name": "MiniLang 0.2.0",
+    "assets": [
+        {"name": "minilang-x86_64-unknown-linux-gnu", "browser_download_url": "https://example.com/linux", "size": 10},
+        {"name": "minilang-x86_64-unknown-linux-gnu.sha256", "browser_download_url": "https://example.com/linux.sha256"},
+        {"name": "minilang-x86_64-pc-windows-msvc.exe", "browser_download_url": "https://example.com/windows"}
+    ]
+}"#;
+
+// ==================== RELEASE TESTS ====================
+
+#[test]
+fn test_parse_release() {
+    let release = parse_release(RELEASE_JSON).unwrap();
+    assert_eq!(release.tag, "v0.2.0");
+    assert_eq!(release.version(), "0.2.0");
+
+    let linux = release.binary_for("x86_64-unknown-linux-gnu").unwrap();
+    assert_eq!(linux.url, "https://example.com/linux");
+    assert_eq!(release.checksum_for(linux).unwrap().name, "minilang-x86_64-unknown-linux-gnu.sha256");
+
+    let windows = release.binary_for("x86_64-pc-windows-msvc").unwrap();
+    assert_eq!(windows.url, "https://example.com/windows");
+    assert!(release.checksum_for(windows).is_none());
+    assert!(release.binary_for("aarch64-apple-darwin").is_none());
+
+    assert!(parse_release(r#"{"message": "Not Found"}"#).unwrap_err().contains("GitHub"));
+    println!("✓ Releases are read from GitHub's JSON and matched to targets");
+}
+
+#[test]
+fn test_unverifiable_download_is_refused() {
+    let release = parse_release(RELEASE_JSON).unwrap();
+    let windows = release.binary_for("x86_64-pc-windows-msvc").unwrap();
+    // Refused before anything is downloaded
+    let error = download_binary(&release, windows, true).unwrap_err();
+    assert!(error.contains("no minilang-x86_64-pc-windows-msvc.exe.sha256"), "{}", error);
+    assert!(error.contains("--no-verify"), "{}", error);
+    println!("✓ A build without a published checksum needs --no-verify");
+}
+
+#[test]
+fn test_is_newer() {
+    assert!(is_newer("0.2.0", "0.1.0"));
+    assert!(is_newer("v0.10.0", "0.9.1"));
+    assert!(is_newer("1.0.0", "0.99.99"));
+    assert!(is_newer("0.1.1", "0.1"));
+    assert!(!is_newer("0.1.0", "0.1.0"));
+    assert!(!is_newer("0.1.0", "0.1"));
+    assert!(!is_newer("v0.1.0", "0.2.0"));
+    println!("✓ Versions compare numerically part by part");
+}
+
+// ==================== VERSION TESTS ====================
+
+#[test]
+fn test_build_info() {
+    assert_eq!(toolchain::VERSION, env!("CARGO_PKG_VERSION"));
+    assert!(!toolchain::GIT_HASH.is_empty());
+    assert!(toolchain::TARGET.contains('-'), "{}", toolchain::TARGET);
+    assert_eq!(toolchain::repository(), "heetabhanushali/minilang");
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_minilang"))
+        .args(["self", "version"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains(&format!("minilang {}", toolchain::VERSION)), "{}", stdout);
+    assert!(stdout.contains(toolchain::GIT_HASH), "{}", stdout);
+    assert!(stdout.contains(toolchain::TARGET), "{}", stdout);
+    println!("✓ self version shows the version, commit and target");
+}
+
+// ==================== UPDATE TESTS ====================
+
+#[test]
+fn test_replace_executable() {
+    let dir = TempDir::new().unwrap();
+    let exe = dir.path().join("minilang");
+    fs::write(&exe, b"old").unwrap();
+
+    // Something that does not run is refused, and the old file stays
+    assert!(replace_executable(&exe, b"not a program").unwrap_err().contains("does not run"));
+    assert_eq!(fs::read(&exe).unwrap(), b"old");
+
+    let compiler = fs::read(env!("CARGO_BIN_EXE_minilang")).unwrap();
+    replace_executable(&exe, &compiler).unwrap();
+    assert_eq!(fs::read(&exe).unwrap(), compiler);
+
+    // No staged copies are left behind
+    let left: Vec<_> = fs::read_dir(dir.path()).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with(".minilang-update"))
+        .collect();
+    assert!(left.is_empty(), "{:?}", left);
+    println!("✓ The executable is only replaced by a build that runs");
+}
@@ -0,0 +1,124 @@
+// tests/usage_tests.rs - Local usage logs and the report built from them
+
+use minilang_compiler::build_log::{BuildRecord, BuildStatus};
+use minilang_compiler::errors::CompilerWarning;
+use minilang_compiler::usage::{read_usage, summarize, UsageRecord, LOG_FILE};
+use minilang_compiler::{Lexer, Parser, TypeChecker};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// A usage record for a compile of `file` that reported `errors` and `warnings`
+fn record(file: &str, stage: Option<&str>, errors: &[&str], warnings: &[&str]) -> UsageRecord {
+    let mut build = BuildRecord::new(Path::new(file), "", "compile", 1);
+    if let Some(stage) = stage {
+        build.fail(stage, errors.len());
+    }
+    build.error_codes = errors.iter().map(|code| code.to_string()).collect();
+    build.warning_codes = warnings.iter().map(|code| code.to_string()).collect();
+    UsageRecord::from_build(&build)
+}
+
+// ==================== RECORD TESTS ====================
+
+#[test]
+fn test_records_keep_codes_but_no_source() {
+    let source = "func main() {\n    let secret: int = \"answer\";\n}\n";
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let errors = TypeChecker::new().check_program(&program).unwrap_err();
+
+    let mut build = BuildRecord::new(Path::new("/home/student/course/week1.mini"), source, "run", 0);
+    build.add_error_codes(&errors);
+    build.fail("type_check", errors.len());
+    let usage = UsageRecord::from_build(&build);
+
+    assert_eq!(usage.file, "week1.mini");
+    assert_eq!(usage.mode, "run");
+    assert_eq!(usage.errors, vec!["minilang::semantic::type_mismatch"]);
+    let json = serde_json::to_string(&usage).unwrap();
+    for private in ["secret", "answer", "/home/student", &build.source_sha256] {
+        assert!(!json.contains(private), "{} leaked into {}", private, json);
+    }
+
+    let warning = CompilerWarning::EmptyFunction { name: "f".to_string(), span: (0..1).into() };
+    assert_eq!(warning.code(), "minilang::warning::empty_function");
+
+    println!("✓ Usage records hold diagnostic codes and the file name, not the source");
+}
+
+#[test]
+fn test_read_usage_from_directories_and_files() {
+    let dir = TempDir::new().unwrap();
+    let alice = dir.path().join("alice");
+    let bob = dir.path().join("bob");
+
+    record("a.mini", None, &[], &[]).append_to_dir(&alice).unwrap();
+    record("a.mini", Some("parse"), &["minilang::parser::missing_semicolon"], &[]).append_to_dir(&alice).unwrap();
+    record("b.mini", None, &[], &["minilang::warning::unused_variable"]).append_to_dir(&bob).unwrap();
+
+    let records = read_usage(&[alice.clone(), bob.join(LOG_FILE)]).unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[1].status, BuildStatus::Failed { stage: "parse".to_string() });
+    assert_eq!(records[2].file, "b.mini");
+
+    let missing: PathBuf = dir.path().join("carol");
+    assert!(read_usage(&[missing]).unwrap_err().contains("carol"));
+
+    println!("✓ Usage logs are read from directories and files");
+}
+
+// ==================== REPORT TESTS ====================
+
+#[test]
+fn test_summarize_counts_codes() {
+    let records = vec![
+        record("a.mini", Some("type_check"), &["minilang::semantic::type_mismatch", "minilang::semantic::type_mismatch"], &[]),
+        record("a.mini", Some("type_check"), &["minilang::semantic::type_mismatch", "minilang::semantic::undefined_variable"], &[]),
+        record("b.mini", Some("parse"), &["minilang::parser::unexpected_token"], &[]),
+        record("b.mini", None, &[], &["minilang::warning::unused_variable"]),
+    ];
+    let report = summarize(&records);
+
+    assert_eq!((report.compiles, report.succeeded, report.failed), (4, 1, 3));
+    let errors: Vec<(&str, usize, usize)> = report.errors.iter()
+        .map(|count| (count.code.as_str(), count.occurrences, count.compiles))
+        .collect();
+    assert_eq!(errors, vec![
+        ("minilang::semantic::type_mismatch", 3, 2),
+        ("minilang::parser::unexpected_token", 1, 1),
+        ("minilang::semantic::undefined_variable", 1, 1),
+    ]);
+    assert_eq!(report.warnings[0].code, "minilang::warning::unused_variable");
+    let stages: Vec<(&str, usize)> = report.failed_stages.iter()
+        .map(|stage| (stage.stage.as_str(), stage.compiles))
+        .collect();
+    assert_eq!(stages, vec![("type_check", 2), ("parse", 1)]);
+
+    println!("✓ The report counts each code and the compiles it appeared in");
+}
+
+#[test]
+fn test_compile_logs_usage() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("broken.mini");
+    std::fs::write(&source, "func main() {\n    display missing;\n}\n").unwrap();
+    let logs = dir.path().join("usage");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_minilang"))
+        .arg("compile")
+        .arg(&source)
+        .arg("--log-usage")
+        .arg(&logs)
+        .output()
+        .unwrap()
+        .status;
+    assert!(!status.success());
+
+    let records = read_usage(&[logs]).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].file, "broken.mini");
+    assert_eq!(records[0].errors, vec!["minilang::semantic::undefined_variable"]);
+
+    println!("✓ compile --log-usage appends a record per compile");
+}
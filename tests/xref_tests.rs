@@ -0,0 +1,109 @@
+// tests/xref_tests.rs - Symbol cross-references from the type checker
+
+use minilang_compiler::xref::{self, XrefEntry};
+use minilang_compiler::{Lexer, Parser, SymbolType, TypeChecker};
+
+fn cross_reference(source: &str) -> Vec<XrefEntry> {
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens, source.to_string()).parse_program().unwrap();
+    let mut type_checker = TypeChecker::new();
+    type_checker.check_program(&program).unwrap();
+    xref::build(&program, type_checker.symbol_table(), source)
+}
+
+/// Positions as `line:column`
+fn positions(entry: &XrefEntry) -> (String, Vec<String>) {
+    let at = |p: &minilang_compiler::LineCol| format!("{}:{}", p.line, p.column);
+    (at(&entry.defined), entry.references.iter().map(at).collect())
+}
+
+const PROGRAM: &str = r#"@deprecated("add is old")
+func add(a: int, b: int) -> int {
+    send a + b;
+}
+
+func main() {
+    let total: int = 0;
+    for let i: int = 0; i < 3; i = i + 1 {
+        let step: int = add(i, 1);
+        total = total + step;
+    }
+    if total > 2 {
+        let step: int = 5;
+        display "{total} {step}";
+    }
+    let xs: int[3] = [1, 2, 3];
+    xs[0] = total;
+    display xs[0];
+}
+"#;
+
+// ==================== SYMBOL TABLE TESTS ====================
+
+#[test]
+fn test_symbols_outlive_their_scope() {
+    let tokens = Lexer::new(PROGRAM).tokenize().unwrap();
+    let program = Parser::new(tokens, PROGRAM.to_string()).parse_program().unwrap();
+    let mut type_checker = TypeChecker::new();
+    type_checker.check_program(&program).unwrap();
+    let table = type_checker.symbol_table();
+
+    let names: Vec<&str> = table.all_symbols().iter().map(|entry| entry.symbol.name.as_str()).collect();
+    assert_eq!(names, vec!["a", "b", "total", "i", "step", "step", "xs"]);
+    let steps: Vec<_> = table.all_symbols().into_iter().filter(|entry| entry.symbol.name == "step").collect();
+    assert_eq!(steps[0].references.len(), 1);
+    assert_eq!(steps[1].references.len(), 1);
+    assert_ne!(steps[0].references, steps[1].references);
+    assert_eq!(table.all_symbols()[0].symbol.symbol_type, SymbolType::Parameter);
+    assert_eq!(table.function_references("add").len(), 1);
+    assert!(table.function_references("main").is_empty());
+    println!("✓ The symbol table keeps exited scopes and their references");
+}
+
+// ==================== XREF TESTS ====================
+
+#[test]
+fn test_xref_definitions_and_references() {
+    let entries = cross_reference(PROGRAM);
+    let listed: Vec<(&str, Option<&str>, &str)> = entries.iter()
+        .map(|entry| (entry.name.as_str(), entry.function.as_deref(), entry.kind))
+        .collect();
+    assert_eq!(listed, vec![
+        ("add", None, "function"),
+        ("a", Some("add"), "parameter"),
+        ("b", Some("add"), "parameter"),
+        ("main", None, "function"),
+        ("total", Some("main"), "variable"),
+        ("i", Some("main"), "variable"),
+        ("step", Some("main"), "variable"),
+        ("step", Some("main"), "variable"),
+        ("xs", Some("main"), "variable"),
+    ]);
+
+    // The function is found after its attribute, which mentions its name
+    assert_eq!(entries[0].typ, "func(a: int, b: int) -> int");
+    assert_eq!(positions(&entries[0]), ("2:6".to_string(), vec!["9:25".to_string()]));
+    // Reads, assignments and interpolated strings all count
+    assert_eq!(positions(&entries[4]).1, vec!["10:9", "10:17", "12:8", "14:19", "17:13"]);
+    // Each 'step' gets the references of its own block
+    assert_eq!(positions(&entries[6]), ("9:13".to_string(), vec!["10:25".to_string()]));
+    assert_eq!(positions(&entries[7]), ("13:13".to_string(), vec!["14:27".to_string()]));
+    assert_eq!(entries[8].typ, "int[3]");
+    assert_eq!(positions(&entries[8]).1, vec!["17:5", "18:13"]);
+    println!("✓ xref lists each symbol's definition and references");
+}
+
+#[test]
+fn test_xref_render() {
+    let rendered = xref::render(&cross_reference(PROGRAM));
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert!(lines[0].starts_with("NAME"), "{}", lines[0]);
+    assert!(lines.iter().any(|line| line.starts_with("main::xs") && line.ends_with("17:5 18:13")));
+    assert!(lines.iter().any(|line| line.starts_with("main ") && line.ends_with(" -")));
+
+    let json = serde_json::to_value(cross_reference(PROGRAM)).unwrap();
+    assert_eq!(json[1]["function"], "add");
+    assert_eq!(json[1]["type"], "int");
+    assert!(json[0].get("function").is_none());
+    println!("✓ xref renders as columns and JSON");
+}
@@ -78,8 +78,8 @@ function initializeEditor() {
         monaco.languages.setMonarchTokensProvider('minilang', {
             keywords: [
                 'func', 'let', 'const', 'if', 'else', 'while', 'do', 'for',
-                'send', 'display', 'int', 'float', 'string', 'bool',
-                'true', 'false', 'AND', 'OR', 'NOT', 'break', 'continue'
+                'send', 'display', 'displayln', 'display_raw', 'int', 'float', 'string', 'bool',
+                'true', 'false', 'AND', 'OR', 'NOT', 'break', 'continue', 'defer'
             ],
             
             operators: [
@@ -94,7 +94,7 @@ function initializeEditor() {
                     [/##/, 'comment', '@comment_multiline'],
                     
                     // Keywords
-                    [/\b(func|let|const|if|else|while|do|for|send|display|break|continue)\b/, 'keyword'],
+                    [/\b(func|let|const|if|else|while|do|for|send|display|break|continue|defer)\b/, 'keyword'],
                     
                     // Types
                     [/\b(int|float|string|bool)\b/, 'type'],
@@ -768,6 +768,8 @@ function parseErrorDetails(errorMessage) {
         'While': 'while',    
         'For': 'for',       
         'Display': 'display', 
+        'DisplayLn': 'displayln',
+        'DisplayRaw': 'display_raw',
         'Send': 'send', 
     };
     
@@ -1154,6 +1156,10 @@ function displayAnalysisResult(result) {
                     <span class="value">${totals.total_loc}</span>
                     <span class="label">LOC</span>
                 </div>
+                <div class="analyze-banner-stat">
+                    <span class="value">${totals.comment_density.toFixed(0)}%</span>
+                    <span class="label">Comments</span>
+                </div>
                 <div class="analyze-banner-stat">
                     <span class="value">${totals.avg_cyclomatic.toFixed(1)}</span>
                     <span class="label">Avg Cyclo</span>
@@ -1162,6 +1168,14 @@ function displayAnalysisResult(result) {
                     <span class="value">${totals.avg_cognitive.toFixed(1)}</span>
                     <span class="label">Avg Cog</span>
                 </div>
+                <div class="analyze-banner-stat">
+                    <span class="value">${formatDuration(totals.estimated_time_seconds)}</span>
+                    <span class="label">Est. Time</span>
+                </div>
+                <div class="analyze-banner-stat">
+                    <span class="value">${totals.estimated_bugs.toFixed(2)}</span>
+                    <span class="label">Est. Bugs</span>
+                </div>
             </div>
         </div>
 
@@ -1223,10 +1237,26 @@ function displayAnalysisResult(result) {
                         <span class="analyze-detail-value">${func.fan_out}</span>
                         <span class="analyze-detail-label">Fan-out</span>
                     </div>
+                    <div class="analyze-detail">
+                        <span class="analyze-detail-value">${func.comment_lines} (${func.comment_density.toFixed(0)}%)</span>
+                        <span class="analyze-detail-label">Comment Lines</span>
+                    </div>
+                    <div class="analyze-detail">
+                        <span class="analyze-detail-value">${func.blank_lines}</span>
+                        <span class="analyze-detail-label">Blank Lines</span>
+                    </div>
                     <div class="analyze-detail">
                         <span class="analyze-detail-value">${func.halstead.volume.toFixed(0)}</span>
                         <span class="analyze-detail-label">Volume</span>
                     </div>
+                    <div class="analyze-detail">
+                        <span class="analyze-detail-value">${formatDuration(func.halstead.time_seconds)}</span>
+                        <span class="analyze-detail-label">Est. Time</span>
+                    </div>
+                    <div class="analyze-detail">
+                        <span class="analyze-detail-value">${func.halstead.delivered_bugs.toFixed(3)}</span>
+                        <span class="analyze-detail-label">Est. Bugs</span>
+                    </div>
                 </div>
                 
                 ${warnings.length > 0 ? `
@@ -1246,6 +1276,13 @@ function displayAnalysisResult(result) {
     display.innerHTML = html;
 }
 
+// Halstead time estimate in the largest unit that keeps it readable
+function formatDuration(seconds) {
+    if (seconds < 60) return `${seconds.toFixed(0)}s`;
+    if (seconds < 3600) return `${(seconds / 60).toFixed(1)} min`;
+    return `${(seconds / 3600).toFixed(1)} h`;
+}
+
 function getRatingLabel(rating) {
     const labels = {
         'A': 'Excellent',